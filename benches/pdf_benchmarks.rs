@@ -207,6 +207,38 @@ fn bench_parallel_generation(c: &mut Criterion) {
     group.finish();
 }
 
+/// Benchmark rendering the page-break-separated sections of one large document
+fn bench_parallel_sections(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parallel_sections");
+
+    let mut document = Vec::new();
+    for section in 0..32 {
+        document.push(elements::Element::Heading { text: format!("Section {}", section), level: 1 });
+        for _ in 0..40 {
+            document.push(elements::Element::Paragraph { text: "Lorem ipsum dolor sit amet. ".repeat(20) });
+        }
+        document.push(elements::Element::PageBreak);
+    }
+
+    for parallel_sections in [false, true] {
+        let options = pdf_generator::RenderOptions { parallel_sections, ..Default::default() };
+        let name = if parallel_sections { "parallel" } else { "sequential" };
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                pdf_generator::generate_pdf_bytes_with_render_options(
+                    black_box(&document),
+                    "Helvetica",
+                    12.0,
+                    pdf_generator::PageLayout::portrait(),
+                    &options,
+                )
+            })
+        });
+    }
+
+    group.finish();
+}
+
 /// Benchmark streaming PDF generation
 fn bench_streaming_generation(c: &mut Criterion) {
     let mut group = c.benchmark_group("streaming_generation");
@@ -324,6 +356,7 @@ criterion_group!(
     bench_pdf_scalability,
     bench_builder_api,
     bench_parallel_generation,
+    bench_parallel_sections,
     bench_streaming_generation,
     bench_optimization_profiles,
     bench_merge_operations
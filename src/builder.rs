@@ -1,6 +1,7 @@
 use crate::elements::Element;
-use crate::pdf_generator::{PageLayout, create_pdf_from_elements_with_layout};
-use anyhow::Result;
+use crate::pdf_generator::{CodeBlockStyle, CodeTheme, CodeWrap, DisplayMathStyle, EmptyDocument, FontFallback, HeaderImage, HeadingCase, HeadingNumbering, LinkRenderMode, OrderedMarkerStyle, PageLabelRange, PageLayout, RenderOptions, RuleStyle, ViewerPreferences, create_pdf_from_elements_with_render_options};
+use crate::table_renderer::TableStyle;
+use crate::error::Result;
 
 /// Fluent builder for creating PDFs with a clean, ergonomic API
 ///
@@ -22,6 +23,7 @@ pub struct PdfBuilder {
     layout: PageLayout,
     font: String,
     font_size: f32,
+    options: RenderOptions,
 }
 
 impl PdfBuilder {
@@ -32,6 +34,7 @@ impl PdfBuilder {
             layout: PageLayout::portrait(),
             font: "Helvetica".to_string(),
             font_size: 12.0,
+            options: RenderOptions::default(),
         }
     }
 
@@ -65,6 +68,12 @@ impl PdfBuilder {
         self
     }
 
+    /// Grow a single page to fit the content instead of paginating
+    pub fn with_auto_height(mut self, enabled: bool) -> Self {
+        self.layout.auto_height = enabled;
+        self
+    }
+
     /// Set the base font family
     pub fn with_font(mut self, font: &str) -> Self {
         self.font = font.to_string();
@@ -77,6 +86,205 @@ impl PdfBuilder {
         self
     }
 
+    /// Prefix headings with section numbers (1, 1.1, 1.2, 2, ...)
+    pub fn with_heading_numbering(mut self, numbering: HeadingNumbering) -> Self {
+        self.options.heading_numbering = numbering;
+        self
+    }
+
+    /// Wrap or truncate code lines wider than the page, e.g. `CodeWrap::Soft`
+    pub fn with_code_wrap(mut self, wrap: CodeWrap) -> Self {
+        self.options.code_wrap = wrap;
+        self
+    }
+
+    /// Kern letter pairs such as "AV" and "To" in single-line text
+    pub fn with_kerning(mut self, enabled: bool) -> Self {
+        self.options.kerning = enabled;
+        self
+    }
+
+    /// Render the sections between page breaks in parallel
+    pub fn with_parallel_sections(mut self, enabled: bool) -> Self {
+        self.options.parallel_sections = enabled;
+        self
+    }
+
+    /// Recase heading text, e.g. `HeadingCase::Title`
+    pub fn with_heading_case(mut self, case: HeadingCase) -> Self {
+        self.options.heading_case = case;
+        self
+    }
+
+    /// Colors for code blocks, e.g. `CodeTheme::dark()`
+    pub fn with_code_theme(mut self, theme: CodeTheme) -> Self {
+        self.options.code_theme = theme;
+        self
+    }
+
+    /// Number the lines of code blocks
+    pub fn with_code_line_numbers(mut self, enabled: bool) -> Self {
+        self.options.code_line_numbers = enabled;
+        self
+    }
+
+    /// Marker styles for ordered lists by nesting depth, cycling for deeper lists
+    pub fn with_ordered_markers(mut self, styles: Vec<OrderedMarkerStyle>) -> Self {
+        self.options.ordered_markers = styles;
+        self
+    }
+
+    /// Number pages with these label ranges, e.g. roman front matter then arabic
+    pub fn with_page_labels(mut self, ranges: Vec<PageLabelRange>) -> Self {
+        self.options.page_labels = ranges;
+        self
+    }
+
+    /// Preselect print scaling and duplex in viewers, e.g. for print shops
+    pub fn with_viewer_preferences(mut self, preferences: ViewerPreferences) -> Self {
+        self.options.viewer_preferences = preferences;
+        self
+    }
+
+    /// Fonts to use for characters the standard fonts lack, e.g. CJK text
+    pub fn with_font_fallback(mut self, fallback: FontFallback) -> Self {
+        self.options.font_fallback = fallback;
+        self
+    }
+
+    /// Draw an image, e.g. a logo, at the same place on every page
+    pub fn with_header_image(mut self, image: HeaderImage) -> Self {
+        self.options.header_image = Some(image);
+        self
+    }
+
+    /// Resolve relative image paths against `dir`, e.g. the directory of the
+    /// Markdown file the elements came from
+    pub fn with_image_base_path(mut self, dir: impl Into<std::path::PathBuf>) -> Self {
+        self.options.image_base_path = Some(dir.into());
+        self
+    }
+
+    /// Set table borders, padding and numeric column alignment
+    pub fn with_table_style(mut self, style: TableStyle) -> Self {
+        self.options.table_style = style;
+        self
+    }
+
+    /// Set the thickness, color, dash pattern and width of horizontal rules
+    pub fn with_rule_style(mut self, style: RuleStyle) -> Self {
+        self.options.rule_style = style;
+        self
+    }
+
+    /// Draw or omit the page number footer
+    pub fn with_page_numbers(mut self, enabled: bool) -> Self {
+        self.options.show_page_numbers = enabled;
+        self
+    }
+
+    /// Start footer page numbers at `start`, e.g. to continue a previous volume
+    pub fn with_page_number_start(mut self, start: u32) -> Self {
+        self.options.page_number_start = start;
+        self
+    }
+
+    /// Open the first paragraph after each heading with a drop cap `lines` lines tall
+    pub fn with_drop_caps(mut self, lines: usize) -> Self {
+        self.options.drop_cap_lines = lines;
+        self
+    }
+
+    /// Set code blocks and inline code in an embedded TrueType font instead of Courier
+    pub fn with_code_font(mut self, font: crate::truetype::TrueTypeFont) -> Self {
+        self.options.code_font = Some(font);
+        self
+    }
+
+    /// Indent nested list items by `points` per level
+    pub fn with_list_indent(mut self, points: f32) -> Self {
+        self.options.list_indent = points;
+        self
+    }
+
+    /// Replace the "Page N" footer; `{page}` and `{date}` are filled in per page
+    pub fn with_footer(mut self, footer: &str) -> Self {
+        self.options.footer = Some(footer.to_string());
+        self
+    }
+
+    /// Set the document's creation and modification date, also used for `{date}` in the footer
+    pub fn with_date(mut self, date: crate::pdf_ops::PdfDate) -> Self {
+        self.options.date = Some(date);
+        self
+    }
+
+    /// Set line height as a multiple of the font size (e.g. 1.5 for one-and-a-half spacing)
+    pub fn with_leading(mut self, multiplier: f32) -> Self {
+        self.options.leading_multiplier = Some(multiplier);
+        self
+    }
+
+    /// Tab width and trailing whitespace handling of code block lines
+    pub fn with_code_block_style(mut self, style: CodeBlockStyle) -> Self {
+        self.options.code_block_style = style;
+        self
+    }
+
+    /// Paint headings with a text rendering mode, e.g. `TextStyle::outlined` for display headings
+    pub fn with_heading_text_style(mut self, style: crate::pdf_generator::TextStyle) -> Self {
+        self.options.heading_text_style = Some(style);
+        self
+    }
+
+    /// Write the standard fonts' widths into the PDF instead of relying on the viewer's metrics
+    pub fn with_font_widths(mut self, enabled: bool) -> Self {
+        self.options.font_widths = enabled;
+        self
+    }
+
+    /// What to render when the document has no content, e.g. a placeholder line
+    pub fn with_empty_document(mut self, empty: EmptyDocument) -> Self {
+        self.options.empty_document = empty;
+        self
+    }
+
+    /// End the document with a count of the checked task list items
+    pub fn with_task_summary(mut self, enabled: bool) -> Self {
+        self.options.task_summary = enabled;
+        self
+    }
+
+    /// Center block math and optionally number it, e.g. `DisplayMathStyle::numbered()`
+    pub fn with_display_math(mut self, style: DisplayMathStyle) -> Self {
+        self.options.display_math = style;
+        self
+    }
+
+    /// Repeat the current chapter or section title in the top margin of each page
+    pub fn with_running_header(mut self, enabled: bool) -> Self {
+        self.options.running_header = enabled;
+        self
+    }
+
+    /// Show link URLs inline or as numbered notes at the end, e.g. `LinkRenderMode::Footnote` for print
+    pub fn with_link_mode(mut self, mode: LinkRenderMode) -> Self {
+        self.options.link_mode = mode;
+        self
+    }
+
+    /// Move a heading to the next page unless this many body lines fit under it
+    pub fn with_orphan_lines(mut self, lines: usize) -> Self {
+        self.options.orphan_lines = lines;
+        self
+    }
+
+    /// Embed an sRGB ICC profile and paint calibrated color through it instead of `DeviceRGB`
+    pub fn with_srgb_color_space(mut self, enabled: bool) -> Self {
+        self.options.srgb_color_space = enabled;
+        self
+    }
+
     /// Add a heading element
     pub fn add_heading(mut self, text: &str, level: u8) -> Self {
         self.elements.push(Element::Heading {
@@ -137,6 +345,7 @@ impl PdfBuilder {
             cells: cells.iter().map(|s| s.to_string()).collect(),
             is_separator: false,
             alignments: vec![],
+            list_depth: None,
         });
         self
     }
@@ -147,6 +356,7 @@ impl PdfBuilder {
             cells: cells.iter().map(|s| s.to_string()).collect(),
             is_separator: true,
             alignments: vec![],
+            list_depth: None,
         });
         self
     }
@@ -163,6 +373,12 @@ impl PdfBuilder {
         self
     }
 
+    /// Continue on a new page with a different size or orientation
+    pub fn add_page_layout(mut self, layout: PageLayout) -> Self {
+        self.elements.push(Element::SetPageLayout(layout));
+        self
+    }
+
     /// Add an empty line
     pub fn add_spacing(mut self) -> Self {
         self.elements.push(Element::EmptyLine);
@@ -178,6 +394,25 @@ impl PdfBuilder {
         self
     }
 
+    /// Add a GitHub-style callout (note, warning, ...)
+    pub fn add_callout(mut self, kind: crate::elements::CalloutKind, text: &str) -> Self {
+        self.elements.push(Element::Callout {
+            kind,
+            text: text.to_string(),
+        });
+        self
+    }
+
+    /// Add a bar or line chart of labelled values
+    pub fn add_chart(mut self, kind: crate::elements::ChartKind, title: &str, data: &[(&str, f64)]) -> Self {
+        self.elements.push(Element::Chart {
+            kind,
+            data: data.iter().map(|(label, value)| (label.to_string(), *value)).collect(),
+            title: title.to_string(),
+        });
+        self
+    }
+
     /// Add a link
     pub fn add_link(mut self, text: &str, url: &str) -> Self {
         self.elements.push(Element::Link {
@@ -192,6 +427,8 @@ impl PdfBuilder {
         self.elements.push(Element::Image {
             alt: alt.to_string(),
             path: path.to_string(),
+            caption: None,
+            float: crate::elements::ImageFloat::None,
         });
         self
     }
@@ -264,22 +501,24 @@ impl PdfBuilder {
 
     /// Build the PDF and write to a file
     pub fn build(self, filename: &str) -> Result<()> {
-        create_pdf_from_elements_with_layout(
+        create_pdf_from_elements_with_render_options(
             filename,
             &self.elements,
             &self.font,
             self.font_size,
             self.layout,
+            &self.options,
         )
     }
 
     /// Build the PDF and return the bytes (no filesystem access)
     pub fn build_bytes(self) -> Result<Vec<u8>> {
-        crate::pdf_generator::generate_pdf_bytes(
+        crate::pdf_generator::generate_pdf_bytes_with_render_options(
             &self.elements,
             &self.font,
             self.font_size,
             self.layout,
+            &self.options,
         )
     }
 
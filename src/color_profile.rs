@@ -0,0 +1,139 @@
+//! ICC color profiles for calibrated color spaces
+//!
+//! Generated documents paint with `rg`/`RG`, which viewers interpret as
+//! uncalibrated `DeviceRGB`. Installing an `/ICCBased` space built from the
+//! sRGB profile as the page's `/DefaultRGB` makes those colors calibrated
+//! without touching the content streams.
+
+/// Entries in the sampled sRGB tone curve
+const CURVE_POINTS: usize = 1024;
+
+/// The D50 illuminant of the profile connection space
+const D50: [f64; 3] = [0.9642, 1.0, 0.8249];
+
+/// sRGB primaries adapted to D50 with the Bradford transform
+const RED: [f64; 3] = [0.436_074_7, 0.222_504_5, 0.013_932_2];
+const GREEN: [f64; 3] = [0.385_064_9, 0.716_878_6, 0.097_104_5];
+const BLUE: [f64; 3] = [0.143_080_4, 0.060_616_9, 0.714_173_3];
+
+/// Build an ICC version 2 display profile for sRGB: its primaries as
+/// colorant tags and its piecewise transfer function sampled as a curve
+pub fn srgb_profile() -> Vec<u8> {
+    let curve = srgb_curve();
+    let tags: Vec<([u8; 4], Vec<u8>)> = vec![
+        (*b"desc", text_description("sRGB IEC61966-2.1")),
+        (*b"cprt", text("No copyright, use freely")),
+        (*b"wtpt", xyz(D50)),
+        (*b"rXYZ", xyz(RED)),
+        (*b"gXYZ", xyz(GREEN)),
+        (*b"bXYZ", xyz(BLUE)),
+        (*b"rTRC", curve.clone()),
+        (*b"gTRC", curve.clone()),
+        (*b"bTRC", curve),
+    ];
+
+    let mut table = Vec::new();
+    let mut data = Vec::new();
+    let data_start = 128 + 4 + tags.len() * 12;
+    for (signature, body) in &tags {
+        table.extend_from_slice(signature);
+        table.extend_from_slice(&((data_start + data.len()) as u32).to_be_bytes());
+        table.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        data.extend_from_slice(body);
+        // Tag data starts on 4-byte boundaries
+        data.resize(data.len().div_ceil(4) * 4, 0);
+    }
+
+    let size = data_start + data.len();
+    let mut profile = Vec::with_capacity(size);
+    profile.extend_from_slice(&(size as u32).to_be_bytes());
+    profile.extend_from_slice(&[0; 4]); // preferred CMM
+    profile.extend_from_slice(&[0x02, 0x10, 0, 0]); // version 2.1
+    profile.extend_from_slice(b"mntr");
+    profile.extend_from_slice(b"RGB ");
+    profile.extend_from_slice(b"XYZ ");
+    for part in [2026u16, 1, 1, 0, 0, 0] {
+        profile.extend_from_slice(&part.to_be_bytes());
+    }
+    profile.extend_from_slice(b"acsp");
+    profile.extend_from_slice(&[0; 24]); // platform, flags, manufacturer, model, attributes
+    profile.extend_from_slice(&0u32.to_be_bytes()); // perceptual rendering intent
+    for value in D50 {
+        profile.extend_from_slice(&s15_fixed16(value));
+    }
+    profile.resize(128, 0);
+    profile.extend_from_slice(&(tags.len() as u32).to_be_bytes());
+    profile.extend_from_slice(&table);
+    profile.extend_from_slice(&data);
+    profile
+}
+
+/// The sRGB transfer function, sampled
+fn srgb_curve() -> Vec<u8> {
+    let mut body = b"curv\0\0\0\0".to_vec();
+    body.extend_from_slice(&(CURVE_POINTS as u32).to_be_bytes());
+    for i in 0..CURVE_POINTS {
+        let v = i as f64 / (CURVE_POINTS - 1) as f64;
+        let linear = if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) };
+        body.extend_from_slice(&((linear * 65535.0).round() as u16).to_be_bytes());
+    }
+    body
+}
+
+fn xyz(value: [f64; 3]) -> Vec<u8> {
+    let mut body = b"XYZ \0\0\0\0".to_vec();
+    for component in value {
+        body.extend_from_slice(&s15_fixed16(component));
+    }
+    body
+}
+
+fn text(value: &str) -> Vec<u8> {
+    let mut body = b"text\0\0\0\0".to_vec();
+    body.extend_from_slice(value.as_bytes());
+    body.push(0);
+    body
+}
+
+/// A `desc` tag with only its ASCII description; the Unicode and
+/// ScriptCode descriptions are left empty
+fn text_description(value: &str) -> Vec<u8> {
+    let mut body = b"desc\0\0\0\0".to_vec();
+    body.extend_from_slice(&(value.len() as u32 + 1).to_be_bytes());
+    body.extend_from_slice(value.as_bytes());
+    body.push(0);
+    body.extend_from_slice(&[0; 8]); // Unicode language code and count
+    body.extend_from_slice(&[0; 3]); // ScriptCode code and count
+    body.extend_from_slice(&[0; 67]);
+    body
+}
+
+fn s15_fixed16(value: f64) -> [u8; 4] {
+    ((value * 65536.0).round() as i32).to_be_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_srgb_profile_structure() {
+        let profile = srgb_profile();
+        let u32_at = |offset: usize| u32::from_be_bytes(profile[offset..offset + 4].try_into().unwrap()) as usize;
+        assert_eq!(u32_at(0), profile.len());
+        assert_eq!(&profile[36..40], b"acsp");
+        assert_eq!(&profile[16..20], b"RGB ");
+
+        // Every tag lies inside the profile and starts with its type signature
+        let count = u32_at(128);
+        assert_eq!(count, 9);
+        for entry in 0..count {
+            let at = 132 + entry * 12;
+            let (offset, size) = (u32_at(at + 4), u32_at(at + 8));
+            assert_eq!(offset % 4, 0);
+            assert!(offset + size <= profile.len());
+            let signature = &profile[offset..offset + 4];
+            assert!([b"desc", b"text", b"XYZ ", b"curv"].iter().any(|s| signature == *s), "{:?}", signature);
+        }
+    }
+}
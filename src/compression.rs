@@ -1,17 +1,18 @@
 use anyhow::Result;
+use std::io::{Read, Write};
 
+/// Inflate zlib-wrapped deflate data, as used by the FlateDecode filter
 pub fn decompress_deflate(data: &[u8]) -> Result<Vec<u8>> {
-    // Simple decompress implementation - in a real implementation you'd use
-    // a proper compression library like flate2
-    // For now, we'll just return the data as-is
-    Ok(data.to_vec())
+    let mut out = Vec::new();
+    flate2::read::ZlibDecoder::new(data).read_to_end(&mut out)?;
+    Ok(out)
 }
 
+/// Compress data with zlib-wrapped deflate, as used by the FlateDecode filter
 pub fn compress_deflate(data: &[u8]) -> Result<Vec<u8>> {
-    // Simple compress implementation - in a real implementation you'd use
-    // a proper compression library like flate2
-    // For now, we'll just return the data as-is
-    Ok(data.to_vec())
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
 }
 
 pub fn decode_hex_string(hex_str: &str) -> Result<Vec<u8>> {
@@ -51,8 +52,6 @@ mod proptest_tests {
     proptest! {
         #[test]
         fn compress_decompress_roundtrip(data in prop::collection::vec(any::<u8>(), 0..10000)) {
-            // Note: This test will use our stub compression which just returns the data as-is
-            // In production with real compression, this would verify roundtrip
             let compressed = compress_deflate(&data).unwrap();
             let decompressed = decompress_deflate(&compressed).unwrap();
             assert_eq!(data, decompressed);
@@ -0,0 +1,475 @@
+//! Operator-level tokenizer for page content streams.
+//!
+//! [`Tokenizer`] walks raw (already decompressed) content-stream bytes and
+//! yields each operator together with the operands that precede it, so
+//! features that inspect or rewrite page content don't need their own
+//! ad-hoc scanners.
+//!
+//! ```rust
+//! use pdfrs::content_stream::{Operand, Tokenizer};
+//!
+//! let ops: Vec<_> = Tokenizer::new(b"1 0 0 RG 0 0 m 10 10 l S").collect();
+//! assert_eq!(ops[0].1, "RG");
+//! assert_eq!(ops[0].0, vec![Operand::Number(1.0), Operand::Number(0.0), Operand::Number(0.0)]);
+//! assert_eq!(ops.len(), 4);
+//! ```
+
+use std::ops::Range;
+
+/// An operand preceding a content-stream operator
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Number(f64),
+    /// A name without its leading `/`
+    Name(String),
+    /// A literal string's bytes, with escapes resolved
+    String(Vec<u8>),
+    /// A hex string's decoded bytes
+    HexString(Vec<u8>),
+    Array(Vec<Operand>),
+    /// Dictionary entries in source order (marked-content properties, inline image parameters)
+    Dictionary(Vec<(String, Operand)>),
+    Boolean(bool),
+    Null,
+}
+
+impl Operand {
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Operand::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_name(&self) -> Option<&str> {
+        match self {
+            Operand::Name(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// Bytes of a literal or hex string
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Operand::String(b) | Operand::HexString(b) => Some(b),
+            _ => None,
+        }
+    }
+}
+
+/// Iterator over `(operands, operator)` pairs of a content stream.
+///
+/// Comments are skipped. An inline image (`BI ... ID <data> EI`) is yielded
+/// once as operator `BI` with its parameters as a single
+/// [`Operand::Dictionary`]; the image data itself is skipped. Arrays and
+/// dictionaries nested deeper than [`MAX_NESTING_DEPTH`] end the stream.
+pub struct Tokenizer<'a> {
+    data: &'a [u8],
+    pos: usize,
+    /// Arrays and dictionaries open around the current position
+    depth: usize,
+}
+
+/// Deepest nesting of arrays and dictionaries the tokenizer follows
+pub const MAX_NESTING_DEPTH: usize = 64;
+
+/// A lexical token: an operand, an operator keyword, or a closing delimiter
+enum Token {
+    Operand(Operand),
+    Keyword(String),
+    ArrayEnd,
+    DictEnd,
+    /// Nesting too deep to follow; the rest of the stream is skipped
+    TooDeep,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Tokenizer { data, pos: 0, depth: 0 }
+    }
+
+    /// Yield each operation along with the bytes it spans, from its first
+    /// operand through its operator, so it can be rewritten in place
+    pub fn spanned(mut self) -> impl Iterator<Item = (Vec<Operand>, String, Range<usize>)> + 'a {
+        std::iter::from_fn(move || self.next_operation())
+    }
+
+    fn next_operation(&mut self) -> Option<(Vec<Operand>, String, Range<usize>)> {
+        let mut operands = Vec::new();
+        let mut start = None;
+        loop {
+            self.skip_whitespace();
+            let token_start = self.pos;
+            let token = self.next_token()?;
+            let start = *start.get_or_insert(token_start);
+            match token {
+                Token::Operand(op) => operands.push(op),
+                Token::Keyword(op) if op == "BI" => {
+                    return Some((vec![self.read_inline_image()], op, start..self.pos));
+                }
+                Token::Keyword(op) => return Some((operands, op, start..self.pos)),
+                Token::ArrayEnd | Token::DictEnd => {}
+                Token::TooDeep => return None,
+            }
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn is_delimiter(b: u8) -> bool {
+        matches!(b, b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%')
+    }
+
+    /// Skip whitespace and `%` comments
+    fn skip_whitespace(&mut self) {
+        while let Some(b) = self.peek() {
+            if b.is_ascii_whitespace() || b == 0 {
+                self.pos += 1;
+            } else if b == b'%' {
+                while let Some(c) = self.peek() {
+                    if c == b'\n' || c == b'\r' {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Read a run of regular (non-delimiter, non-whitespace) characters
+    fn read_regular(&mut self) -> &'a [u8] {
+        let start = self.pos;
+        while let Some(b) = self.peek() {
+            if b.is_ascii_whitespace() || b == 0 || Self::is_delimiter(b) {
+                break;
+            }
+            self.pos += 1;
+        }
+        &self.data[start..self.pos]
+    }
+
+    fn next_token(&mut self) -> Option<Token> {
+        self.skip_whitespace();
+        // Skip stray delimiters such as `)`, `>` or `{`
+        while self.peek().is_some_and(|b| matches!(b, b')' | b'>' | b'{' | b'}'))
+            && !self.data[self.pos..].starts_with(b">>")
+        {
+            self.pos += 1;
+            self.skip_whitespace();
+        }
+        let b = self.peek()?;
+        let token = match b {
+            b'(' => {
+                self.pos += 1;
+                Token::Operand(Operand::String(self.read_literal_string()))
+            }
+            b'<' if self.data.get(self.pos + 1) == Some(&b'<') => {
+                self.pos += 2;
+                self.nested(Self::read_dictionary)
+            }
+            b'<' => {
+                self.pos += 1;
+                Token::Operand(Operand::HexString(self.read_hex_string()))
+            }
+            b'>' if self.data.get(self.pos + 1) == Some(&b'>') => {
+                self.pos += 2;
+                Token::DictEnd
+            }
+            b'[' => {
+                self.pos += 1;
+                self.nested(Self::read_array)
+            }
+            b']' => {
+                self.pos += 1;
+                Token::ArrayEnd
+            }
+            b'/' => {
+                self.pos += 1;
+                Token::Operand(Operand::Name(decode_name(self.read_regular())))
+            }
+            _ => {
+                let word = self.read_regular();
+                let text = String::from_utf8_lossy(word).into_owned();
+                match text.as_str() {
+                    "true" => Token::Operand(Operand::Boolean(true)),
+                    "false" => Token::Operand(Operand::Boolean(false)),
+                    "null" => Token::Operand(Operand::Null),
+                    _ => match text.parse::<f64>() {
+                        Ok(n) if text.starts_with(|c: char| c.is_ascii_digit() || "+-.".contains(c)) => {
+                            Token::Operand(Operand::Number(n))
+                        }
+                        _ => Token::Keyword(text),
+                    },
+                }
+            }
+        };
+        Some(token)
+    }
+
+    /// Read an array or dictionary with `read`, unless that would nest it too deep
+    fn nested(&mut self, read: fn(&mut Self) -> Operand) -> Token {
+        if self.depth >= MAX_NESTING_DEPTH {
+            self.pos = self.data.len();
+            return Token::TooDeep;
+        }
+        self.depth += 1;
+        let operand = read(self);
+        self.depth -= 1;
+        Token::Operand(operand)
+    }
+
+    /// Read a literal string body after its opening `(`
+    fn read_literal_string(&mut self) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut depth = 1;
+        while let Some(b) = self.peek() {
+            self.pos += 1;
+            match b {
+                b'(' => {
+                    depth += 1;
+                    out.push(b);
+                }
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                    out.push(b);
+                }
+                b'\\' => {
+                    let Some(esc) = self.peek() else { break };
+                    self.pos += 1;
+                    match esc {
+                        b'n' => out.push(b'\n'),
+                        b'r' => out.push(b'\r'),
+                        b't' => out.push(b'\t'),
+                        b'b' => out.push(0x08),
+                        b'f' => out.push(0x0C),
+                        b'0'..=b'7' => {
+                            let mut value = (esc - b'0') as u32;
+                            for _ in 0..2 {
+                                match self.peek() {
+                                    Some(d @ b'0'..=b'7') => {
+                                        value = value * 8 + (d - b'0') as u32;
+                                        self.pos += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            out.push(value as u8);
+                        }
+                        // A backslash before an end-of-line continues the string
+                        b'\r' => {
+                            if self.peek() == Some(b'\n') {
+                                self.pos += 1;
+                            }
+                        }
+                        b'\n' => {}
+                        other => out.push(other),
+                    }
+                }
+                _ => out.push(b),
+            }
+        }
+        out
+    }
+
+    /// Read a hex string body after its opening `<`
+    fn read_hex_string(&mut self) -> Vec<u8> {
+        let mut digits = Vec::new();
+        while let Some(b) = self.peek() {
+            self.pos += 1;
+            if b == b'>' {
+                break;
+            }
+            if let Some(d) = (b as char).to_digit(16) {
+                digits.push(d as u8);
+            }
+        }
+        // An odd final digit is padded with 0
+        digits.chunks(2).map(|pair| pair[0] << 4 | pair.get(1).copied().unwrap_or(0)).collect()
+    }
+
+    /// Read array items after the opening `[`
+    fn read_array(&mut self) -> Operand {
+        let mut items = Vec::new();
+        while let Some(token) = self.next_token() {
+            match token {
+                Token::Operand(op) => items.push(op),
+                Token::ArrayEnd | Token::TooDeep => break,
+                // Keywords and `>>` have no meaning inside an array
+                Token::Keyword(_) | Token::DictEnd => {}
+            }
+        }
+        Operand::Array(items)
+    }
+
+    /// Read dictionary entries after the opening `<<`
+    fn read_dictionary(&mut self) -> Operand {
+        Operand::Dictionary(self.read_entries(|token| matches!(token, Token::DictEnd)))
+    }
+
+    /// Read `/Key value` pairs until `is_end` accepts a token
+    fn read_entries(&mut self, is_end: impl Fn(&Token) -> bool) -> Vec<(String, Operand)> {
+        let mut entries = Vec::new();
+        let mut key: Option<String> = None;
+        while let Some(token) = self.next_token() {
+            if is_end(&token) {
+                break;
+            }
+            match (key.take(), token) {
+                (None, Token::Operand(Operand::Name(name))) => key = Some(name),
+                (Some(k), Token::Operand(value)) => entries.push((k, value)),
+                // Inline image parameters may use abbreviated keyword values
+                (Some(k), Token::Keyword(word)) => entries.push((k, Operand::Name(word))),
+                _ => {}
+            }
+        }
+        entries
+    }
+
+    /// Read inline image parameters after `BI` and skip the data up to `EI`
+    fn read_inline_image(&mut self) -> Operand {
+        let entries = self.read_entries(|token| matches!(token, Token::Keyword(k) if k == "ID"));
+        // Exactly one whitespace byte separates `ID` from the data
+        self.pos += 1;
+        while self.pos < self.data.len() {
+            let at_ei = self.data[self.pos..].starts_with(b"EI")
+                && self.pos > 0
+                && self.data[self.pos - 1].is_ascii_whitespace()
+                && self
+                    .data
+                    .get(self.pos + 2)
+                    .is_none_or(|b| b.is_ascii_whitespace() || Self::is_delimiter(*b));
+            if at_ei {
+                self.pos += 2;
+                break;
+            }
+            self.pos += 1;
+        }
+        Operand::Dictionary(entries)
+    }
+}
+
+impl Iterator for Tokenizer<'_> {
+    type Item = (Vec<Operand>, String);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_operation().map(|(operands, op, _)| (operands, op))
+    }
+}
+
+/// Decode `#xx` escapes in a name
+fn decode_name(raw: &[u8]) -> String {
+    let mut out = Vec::with_capacity(raw.len());
+    let mut i = 0;
+    while i < raw.len() {
+        let escaped = (raw[i] == b'#')
+            .then(|| raw.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(raw[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize_text_object() {
+        let ops: Vec<_> = Tokenizer::new(b"BT /F1 12 Tf (hi) Tj ET").collect();
+        assert_eq!(
+            ops,
+            vec![
+                (vec![], "BT".to_string()),
+                (vec![Operand::Name("F1".into()), Operand::Number(12.0)], "Tf".to_string()),
+                (vec![Operand::String(b"hi".to_vec())], "Tj".to_string()),
+                (vec![], "ET".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_strings_arrays_and_comments() {
+        let data = b"% a comment\n[(a\\)b) -120 <48 69>] TJ\n(nested (parens)\\101) Tj\n\
+                     /Span << /MCID 3 >> BDC EMC";
+        let ops: Vec<_> = Tokenizer::new(data).collect();
+        assert_eq!(ops.len(), 4);
+        assert_eq!(ops[0].1, "TJ");
+        assert_eq!(
+            ops[0].0,
+            vec![Operand::Array(vec![
+                Operand::String(b"a)b".to_vec()),
+                Operand::Number(-120.0),
+                Operand::HexString(b"Hi".to_vec()),
+            ])]
+        );
+        assert_eq!(ops[1].0[0].as_bytes(), Some(&b"nested (parens)A"[..]));
+        assert_eq!(ops[2].1, "BDC");
+        assert_eq!(ops[2].0[1], Operand::Dictionary(vec![("MCID".into(), Operand::Number(3.0))]));
+        assert_eq!(ops[3], (vec![], "EMC".to_string()));
+    }
+
+    #[test]
+    fn test_spans_cover_operands_and_operator() {
+        let data = b"BT /F1 12 Tf\n[(a\\)b) -20 (c)] TJ % note\n<</MCID 0>> BDC ET";
+        let spans: Vec<_> = Tokenizer::new(data)
+            .spanned()
+            .map(|(_, op, span)| (op, String::from_utf8_lossy(&data[span]).into_owned()))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                ("BT".to_string(), "BT".to_string()),
+                ("Tf".to_string(), "/F1 12 Tf".to_string()),
+                ("TJ".to_string(), "[(a\\)b) -20 (c)] TJ".to_string()),
+                ("BDC".to_string(), "<</MCID 0>> BDC".to_string()),
+                ("ET".to_string(), "ET".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inline_image_data_is_skipped() {
+        let data = b"q BI /W 2 /H 1 /CS /G /BPC 8 ID \x00EI\xff EI Q";
+        let ops: Vec<_> = Tokenizer::new(data).collect();
+        let names: Vec<&str> = ops.iter().map(|(_, op)| op.as_str()).collect();
+        assert_eq!(names, vec!["q", "BI", "Q"]);
+        match &ops[1].0[0] {
+            Operand::Dictionary(entries) => assert_eq!(entries[0], ("W".into(), Operand::Number(2.0))),
+            other => panic!("expected dictionary, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_deep_nesting_ends_the_stream() {
+        let nested = |depth: usize| format!("q {}1{} Tj Q", "[".repeat(depth), "]".repeat(depth));
+        let ops: Vec<_> = Tokenizer::new(nested(MAX_NESTING_DEPTH).as_bytes()).collect();
+        assert_eq!(ops.len(), 3);
+
+        // Too deep to follow: what came before is kept, the rest is dropped
+        let ops: Vec<_> = Tokenizer::new(nested(MAX_NESTING_DEPTH + 1).as_bytes()).collect();
+        assert_eq!(ops, vec![(Vec::new(), "q".to_string())]);
+        let data = format!("q {}", "<< /A ".repeat(1_000_000));
+        assert_eq!(Tokenizer::new(data.as_bytes()).count(), 1);
+
+        // Long runs of stray delimiters don't recurse either
+        let data = format!("{} Q", ")".repeat(1_000_000));
+        assert_eq!(Tokenizer::new(data.as_bytes()).count(), 1);
+    }
+}
@@ -18,6 +18,113 @@ pub enum TextSegment {
     BoldItalic(String),
     Code(String),
     Link { text: String, url: String },
+    Superscript(String),
+    Subscript(String),
+    /// Text marked with a background color, like a marker pen (`==text==`)
+    Highlight(String, crate::pdf_generator::Color),
+    /// Text painted in a rendering mode of its own, e.g. outlined or invisible
+    Styled(String, crate::pdf_generator::TextStyle),
+}
+
+impl TextSegment {
+    /// The visible text of the segment
+    pub fn text(&self) -> &str {
+        match self {
+            TextSegment::Plain(t)
+            | TextSegment::Bold(t)
+            | TextSegment::Italic(t)
+            | TextSegment::BoldItalic(t)
+            | TextSegment::Code(t)
+            | TextSegment::Superscript(t)
+            | TextSegment::Subscript(t)
+            | TextSegment::Highlight(t, _)
+            | TextSegment::Styled(t, _) => t,
+            TextSegment::Link { text, .. } => text,
+        }
+    }
+
+    /// A segment with the same style (and link target) holding `text`
+    pub fn with_text(&self, text: String) -> TextSegment {
+        match self {
+            TextSegment::Plain(_) => TextSegment::Plain(text),
+            TextSegment::Bold(_) => TextSegment::Bold(text),
+            TextSegment::Italic(_) => TextSegment::Italic(text),
+            TextSegment::BoldItalic(_) => TextSegment::BoldItalic(text),
+            TextSegment::Code(_) => TextSegment::Code(text),
+            TextSegment::Superscript(_) => TextSegment::Superscript(text),
+            TextSegment::Subscript(_) => TextSegment::Subscript(text),
+            TextSegment::Highlight(_, color) => TextSegment::Highlight(text, *color),
+            TextSegment::Styled(_, style) => TextSegment::Styled(text, *style),
+            TextSegment::Link { url, .. } => TextSegment::Link { text, url: url.clone() },
+        }
+    }
+}
+
+/// Kind of a GitHub-style alert such as `> [!WARNING]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalloutKind {
+    Note,
+    Tip,
+    Important,
+    Warning,
+    Caution,
+}
+
+impl CalloutKind {
+    /// Kind named by a `[!NAME]` marker, ignoring case
+    pub fn from_marker(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "NOTE" => Some(CalloutKind::Note),
+            "TIP" => Some(CalloutKind::Tip),
+            "IMPORTANT" => Some(CalloutKind::Important),
+            "WARNING" => Some(CalloutKind::Warning),
+            "CAUTION" => Some(CalloutKind::Caution),
+            _ => None,
+        }
+    }
+
+    /// Name used in the Markdown marker, e.g. `WARNING`
+    pub fn marker(&self) -> &'static str {
+        match self {
+            CalloutKind::Note => "NOTE",
+            CalloutKind::Tip => "TIP",
+            CalloutKind::Important => "IMPORTANT",
+            CalloutKind::Warning => "WARNING",
+            CalloutKind::Caution => "CAUTION",
+        }
+    }
+
+    /// Label drawn above the callout's body
+    pub fn label(&self) -> &'static str {
+        match self {
+            CalloutKind::Note => "Note",
+            CalloutKind::Tip => "Tip",
+            CalloutKind::Important => "Important",
+            CalloutKind::Warning => "Warning",
+            CalloutKind::Caution => "Caution",
+        }
+    }
+}
+
+/// Where an image sits relative to the text after it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImageFloat {
+    /// The image takes its own block and text continues below it
+    #[default]
+    None,
+    /// The image sits at the left margin with text wrapping to its right
+    Left,
+    /// The image sits at the right margin with text wrapping to its left
+    Right,
+}
+
+/// How an [`Element::Chart`] draws its values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChartKind {
+    /// One filled bar per value
+    Bar,
+    /// A line joining the values
+    Line,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -29,22 +136,242 @@ pub enum Element {
     UnorderedListItem { text: String, depth: u8 },
     OrderedListItem { number: u32, text: String, depth: u8 },
     TaskListItem { checked: bool, text: String },
+    /// A further paragraph of the list item at `depth`, indented under its text
+    ListParagraph { text: String, depth: u8 },
     CodeBlock { language: String, code: String },
     InlineCode { code: String },
-    TableRow { cells: Vec<String>, is_separator: bool, alignments: Vec<TableAlignment> },
+    /// `list_depth` is the depth of the list item a table is nested under, if any
+    TableRow { cells: Vec<String>, is_separator: bool, alignments: Vec<TableAlignment>, list_depth: Option<u8> },
     BlockQuote { text: String, depth: u8 },
+    /// A GitHub-style alert: a blockquote opening with `[!NOTE]`, `[!WARNING]`, ...
+    Callout { kind: CalloutKind, text: String },
+    /// A block inside `depth` levels of blockquote, e.g. a list item in a quote
+    Quoted { depth: u8, element: Box<Element> },
     DefinitionItem { term: String, definition: String },
     Footnote { label: String, text: String },
     Link { text: String, url: String },
-    Image { alt: String, path: String },
+    /// An image; with a caption it renders as a centered figure. A floated
+    /// image sits at one margin with the following text wrapped beside it.
+    Image { alt: String, path: String, caption: Option<String>, float: ImageFloat },
     StyledText { text: String, bold: bool, italic: bool },
     MathBlock { expression: String },
     MathInline { expression: String },
+    /// Raw content-stream operators drawn with the origin at the cursor,
+    /// isolated in a `q`/`Q` block; an escape hatch for custom graphics
+    RawContent { stream: Vec<u8> },
+    /// A bar or line chart of labelled values, drawn with axes at the cursor
+    Chart { kind: ChartKind, data: Vec<(String, f64)>, title: String },
     PageBreak,
+    /// Start a new page with this size and margins, used until the next switch
+    SetPageLayout(crate::pdf_generator::PageLayout),
     HorizontalRule,
     EmptyLine,
 }
 
+/// Fluent builder producing a `Vec<Element>` without going through Markdown.
+///
+/// ```rust
+/// use pdfrs::elements::DocumentBuilder;
+///
+/// let elements = DocumentBuilder::new()
+///     .heading(1, "Report")
+///     .paragraph("Quarterly summary.")
+///     .bullet("Revenue up")
+///     .table(&["Region", "Sales"], &[vec!["EU", "42"]])
+///     .page_break()
+///     .build();
+/// assert_eq!(elements.len(), 7);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DocumentBuilder {
+    elements: Vec<Element>,
+}
+
+impl DocumentBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn heading(mut self, level: u8, text: &str) -> Self {
+        self.elements.push(Element::Heading { level, text: text.to_string() });
+        self
+    }
+
+    pub fn paragraph(mut self, text: &str) -> Self {
+        self.elements.push(Element::Paragraph { text: text.to_string() });
+        self
+    }
+
+    /// Paragraph with inline styling, e.g. from [`parse_inline_formatting`]
+    pub fn rich_paragraph(mut self, segments: Vec<TextSegment>) -> Self {
+        self.elements.push(Element::RichParagraph { segments });
+        self
+    }
+
+    /// Top-level bullet item
+    pub fn bullet(self, text: &str) -> Self {
+        self.nested_bullet(0, text)
+    }
+
+    pub fn nested_bullet(mut self, depth: u8, text: &str) -> Self {
+        self.elements.push(Element::UnorderedListItem { text: text.to_string(), depth });
+        self
+    }
+
+    /// Top-level numbered item; numbering continues from a directly preceding numbered item
+    pub fn numbered(mut self, text: &str) -> Self {
+        let number = match self.elements.last() {
+            Some(Element::OrderedListItem { number, depth: 0, .. }) => number + 1,
+            _ => 1,
+        };
+        self.elements.push(Element::OrderedListItem { number, text: text.to_string(), depth: 0 });
+        self
+    }
+
+    pub fn task(mut self, checked: bool, text: &str) -> Self {
+        self.elements.push(Element::TaskListItem { checked, text: text.to_string() });
+        self
+    }
+
+    pub fn code_block(mut self, language: &str, code: &str) -> Self {
+        self.elements.push(Element::CodeBlock {
+            language: language.to_string(),
+            code: code.to_string(),
+        });
+        self
+    }
+
+    pub fn blockquote(mut self, text: &str) -> Self {
+        self.elements.push(Element::BlockQuote { text: text.to_string(), depth: 1 });
+        self
+    }
+
+    pub fn callout(mut self, kind: CalloutKind, text: &str) -> Self {
+        self.elements.push(Element::Callout { kind, text: text.to_string() });
+        self
+    }
+
+    /// Header row, separator and body rows, all left-aligned
+    pub fn table(mut self, headers: &[&str], rows: &[Vec<&str>]) -> Self {
+        let to_cells = |cells: &[&str]| cells.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+        let alignments = vec![TableAlignment::Left; headers.len()];
+        self.elements.push(Element::TableRow {
+            cells: to_cells(headers),
+            is_separator: false,
+            alignments: alignments.clone(),
+            list_depth: None,
+        });
+        self.elements.push(Element::TableRow {
+            cells: vec!["---".to_string(); headers.len()],
+            is_separator: true,
+            alignments: alignments.clone(),
+            list_depth: None,
+        });
+        for row in rows {
+            self.elements.push(Element::TableRow {
+                cells: to_cells(row),
+                is_separator: false,
+                alignments: vec![TableAlignment::Left; row.len()],
+                list_depth: None,
+            });
+        }
+        self
+    }
+
+    pub fn definition(mut self, term: &str, definition: &str) -> Self {
+        self.elements.push(Element::DefinitionItem {
+            term: term.to_string(),
+            definition: definition.to_string(),
+        });
+        self
+    }
+
+    pub fn footnote(mut self, label: &str, text: &str) -> Self {
+        self.elements.push(Element::Footnote { label: label.to_string(), text: text.to_string() });
+        self
+    }
+
+    pub fn link(mut self, text: &str, url: &str) -> Self {
+        self.elements.push(Element::Link { text: text.to_string(), url: url.to_string() });
+        self
+    }
+
+    pub fn image(mut self, alt: &str, path: &str) -> Self {
+        self.elements.push(Element::Image {
+            alt: alt.to_string(),
+            path: path.to_string(),
+            caption: None,
+            float: ImageFloat::None,
+        });
+        self
+    }
+
+    /// Image at the left or right margin with the following text wrapped beside it
+    pub fn floating_image(mut self, alt: &str, path: &str, float: ImageFloat) -> Self {
+        self.elements.push(Element::Image { alt: alt.to_string(), path: path.to_string(), caption: None, float });
+        self
+    }
+
+    /// Image rendered centered with `caption` below it
+    pub fn figure(mut self, alt: &str, path: &str, caption: &str) -> Self {
+        self.elements.push(Element::Image {
+            alt: alt.to_string(),
+            path: path.to_string(),
+            caption: Some(caption.to_string()),
+            float: ImageFloat::None,
+        });
+        self
+    }
+
+    pub fn math(mut self, expression: &str) -> Self {
+        self.elements.push(Element::MathBlock { expression: expression.to_string() });
+        self
+    }
+
+    /// Raw content-stream operators drawn at the cursor, see [`Element::RawContent`]
+    pub fn raw_content(mut self, stream: &[u8]) -> Self {
+        self.elements.push(Element::RawContent { stream: stream.to_vec() });
+        self
+    }
+
+    pub fn chart(mut self, kind: ChartKind, title: &str, data: &[(&str, f64)]) -> Self {
+        let data = data.iter().map(|(label, value)| (label.to_string(), *value)).collect();
+        self.elements.push(Element::Chart { kind, data, title: title.to_string() });
+        self
+    }
+
+    pub fn horizontal_rule(mut self) -> Self {
+        self.elements.push(Element::HorizontalRule);
+        self
+    }
+
+    pub fn page_break(mut self) -> Self {
+        self.elements.push(Element::PageBreak);
+        self
+    }
+
+    /// Continue on a new page with `layout`, e.g. landscape for wide tables
+    pub fn page_layout(mut self, layout: crate::pdf_generator::PageLayout) -> Self {
+        self.elements.push(Element::SetPageLayout(layout));
+        self
+    }
+
+    pub fn empty_line(mut self) -> Self {
+        self.elements.push(Element::EmptyLine);
+        self
+    }
+
+    /// Append any element not covered by the helpers above
+    pub fn element(mut self, element: Element) -> Self {
+        self.elements.push(element);
+        self
+    }
+
+    pub fn build(self) -> Vec<Element> {
+        self.elements
+    }
+}
+
 /// Parse alignment from a table separator cell like `:---`, `:---:`, `---:`
 fn parse_cell_alignment(cell: &str) -> TableAlignment {
     let t = cell.trim();
@@ -59,14 +386,26 @@ fn parse_cell_alignment(cell: &str) -> TableAlignment {
     }
 }
 
-/// Strip inline markdown formatting from text (bold, italic, code, links, strikethrough)
+/// Strip inline markdown formatting from text (bold, italic, code, links, strikethrough,
+/// superscript, subscript, highlight)
 pub fn strip_inline_formatting(text: &str) -> String {
     let mut s = text.to_string();
 
+    // Highlight ==text==
+    s = highlight_regex().replace_all(&s, "$1").to_string();
+
     // Strikethrough ~~text~~
     let strike_re = regex::Regex::new(r"~~(.*?)~~").unwrap();
     s = strike_re.replace_all(&s, "$1").to_string();
 
+    // Superscript ^text^ and subscript ~text~
+    for marker in ['^', '~'] {
+        while let Some((start, end)) = find_script_span(&s, marker) {
+            let content = s[start + 1..end - 1].to_string();
+            s.replace_range(start..end, &content);
+        }
+    }
+
     // Bold+italic (***text***)
     let bold_italic_re = regex::Regex::new(r"\*\*\*(.*?)\*\*\*").unwrap();
     s = bold_italic_re.replace_all(&s, "$1").to_string();
@@ -91,11 +430,87 @@ pub fn strip_inline_formatting(text: &str) -> String {
     let link_re = regex::Regex::new(r"\[([^\]]+)\]\([^\)]+\)").unwrap();
     s = link_re.replace_all(&s, "$1").to_string();
 
+    // Autolinks <https://url>
+    let autolink_re = regex::Regex::new(r"<(https?://[^\s<>]+)>").unwrap();
+    s = autolink_re.replace_all(&s, "$1").to_string();
+
     // Inline code `code`
     let code_re = regex::Regex::new(r"`([^`]+)`").unwrap();
     s = code_re.replace_all(&s, "$1").to_string();
 
-    s
+    decode_entities(&s)
+}
+
+/// Character for a named HTML entity, covering the ones common in prose
+fn named_entity(name: &str) -> Option<char> {
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "deg" => '°',
+        "plusmn" => '±',
+        "times" => '×',
+        "divide" => '÷',
+        "micro" => 'µ',
+        "para" => '¶',
+        "sect" => '§',
+        "middot" => '·',
+        "bull" => '•',
+        "hellip" => '…',
+        "ndash" => '–',
+        "mdash" => '—',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "laquo" => '«',
+        "raquo" => '»',
+        "euro" => '€',
+        "pound" => '£',
+        "yen" => '¥',
+        "cent" => '¢',
+        "frac12" => '½',
+        "frac14" => '¼',
+        "frac34" => '¾',
+        "larr" => '←',
+        "rarr" => '→',
+        "uarr" => '↑',
+        "darr" => '↓',
+        "ne" => '≠',
+        "le" => '≤',
+        "ge" => '≥',
+        "infin" => '∞',
+        _ => return None,
+    })
+}
+
+/// Decode HTML entities (`&copy;`) and decimal or hex character references
+/// (`&#8364;`, `&#x20AC;`). Unknown names are kept as written; references to
+/// invalid code points become U+FFFD.
+pub fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let entity_re = regex::Regex::new(r"&(?:#([0-9]{1,7})|#[xX]([0-9a-fA-F]{1,6})|([A-Za-z][A-Za-z0-9]{1,31}));").unwrap();
+    entity_re
+        .replace_all(text, |caps: &regex::Captures| {
+            let code = if let Some(decimal) = caps.get(1) {
+                decimal.as_str().parse().ok()
+            } else if let Some(hex) = caps.get(2) {
+                u32::from_str_radix(hex.as_str(), 16).ok()
+            } else {
+                return named_entity(&caps[3]).map_or_else(|| caps[0].to_string(), String::from);
+            };
+            let decoded = code.filter(|&c| c != 0).and_then(char::from_u32).unwrap_or('\u{fffd}');
+            decoded.to_string()
+        })
+        .into_owned()
 }
 
 /// Parse inline markdown formatting into styled text segments
@@ -126,7 +541,14 @@ pub fn parse_inline_formatting(text: &str) -> Vec<TextSegment> {
         segments.extend(parse_formatting_no_links(&remaining));
     }
 
+    // Code spans keep entities literally
     segments
+        .into_iter()
+        .map(|segment| match segment {
+            TextSegment::Code(_) => segment,
+            _ => segment.with_text(decode_entities(segment.text())),
+        })
+        .collect()
 }
 
 /// Parse formatting excluding links
@@ -142,7 +564,7 @@ fn parse_formatting_no_links(text: &str) -> Vec<TextSegment> {
         let code = caps.get(1).unwrap().as_str();
 
         if !before.is_empty() {
-            segments.extend(parse_bold_italic(before));
+            segments.extend(parse_highlights(before));
         }
 
         segments.push(TextSegment::Code(code.to_string()));
@@ -150,117 +572,478 @@ fn parse_formatting_no_links(text: &str) -> Vec<TextSegment> {
     }
 
     if !remaining.is_empty() {
-        segments.extend(parse_bold_italic(&remaining));
+        segments.extend(parse_highlights(&remaining));
     }
 
     segments
 }
 
-/// Parse bold/italic formatting
-fn parse_bold_italic(text: &str) -> Vec<TextSegment> {
+/// `==text==` with no whitespace just inside the markers, so `a == b` stays text
+fn highlight_regex() -> regex::Regex {
+    regex::Regex::new(r"==([^\s=](?:[^=]*[^\s=])?)==").unwrap()
+}
+
+/// Turn `==text==` spans into yellow highlight segments, parsing the rest for autolinks
+fn parse_highlights(text: &str) -> Vec<TextSegment> {
     let mut segments = Vec::new();
-    let mut remaining = text.to_string();
+    let mut remaining = text;
+
+    while let Some(caps) = highlight_regex().captures(remaining) {
+        let full_match = caps.get(0).unwrap();
+        if full_match.start() > 0 {
+            segments.extend(parse_autolinks(&remaining[..full_match.start()]));
+        }
+        segments.push(TextSegment::Highlight(caps[1].to_string(), crate::pdf_generator::Color::yellow()));
+        remaining = &remaining[full_match.end()..];
+    }
+
+    if !remaining.is_empty() {
+        segments.extend(parse_autolinks(remaining));
+    }
+
+    segments
+}
 
+/// Byte range and URL of the first `<https://...>` autolink or bare `http(s)://` URL.
+/// Trailing punctuation and unbalanced closing parentheses are left out of bare URLs.
+fn find_autolink(text: &str) -> Option<(usize, usize, String)> {
+    let url_re = regex::Regex::new(r"<(https?://[^\s<>]+)>|https?://[^\s<>]+").unwrap();
+    let caps = url_re.captures(text)?;
+    let full = caps.get(0).unwrap();
+    if let Some(url) = caps.get(1) {
+        return Some((full.start(), full.end(), url.as_str().to_string()));
+    }
+    let mut url = full.as_str();
     loop {
-        // Bold+italic: ***text*** or ___text___ (explicit patterns)
-        let bi_stars_re = regex::Regex::new(r"\*\*\*(.+?)\*\*\*").unwrap();
-        let bi_under_re = regex::Regex::new(r"___(.+?)___").unwrap();
-        // Bold: **text** or __text__
-        let b_stars_re = regex::Regex::new(r"\*\*(.+?)\*\*").unwrap();
-        let b_under_re = regex::Regex::new(r"__(.+?)__").unwrap();
-        // Italic: *text* or _text_ (simple pattern, may have false positives but that's acceptable)
-        let i_stars_re = regex::Regex::new(r"\*([^*]+)\*").unwrap();
-        let i_under_re = regex::Regex::new(r"_([^_]+)_").unwrap();
-
-        let mut found = false;
-
-        if let Some(caps) = bi_stars_re.captures(&remaining) {
-            let full_match = caps.get(0).unwrap();
-            let before = &remaining[..full_match.start()];
-            let content = caps.get(1).unwrap().as_str();
-
-            if !before.is_empty() {
-                segments.push(TextSegment::Plain(before.to_string()));
-            }
-            segments.push(TextSegment::BoldItalic(content.to_string()));
-            remaining = remaining[full_match.end()..].to_string();
-            found = true;
-        } else if let Some(caps) = bi_under_re.captures(&remaining) {
-            let full_match = caps.get(0).unwrap();
-            let before = &remaining[..full_match.start()];
-            let content = caps.get(1).unwrap().as_str();
-
-            if !before.is_empty() {
-                segments.push(TextSegment::Plain(before.to_string()));
-            }
-            segments.push(TextSegment::BoldItalic(content.to_string()));
-            remaining = remaining[full_match.end()..].to_string();
-            found = true;
-        } else if let Some(caps) = b_stars_re.captures(&remaining) {
-            let full_match = caps.get(0).unwrap();
-            let before = &remaining[..full_match.start()];
-            let content = caps.get(1).unwrap().as_str();
-
-            if !before.is_empty() {
-                segments.push(TextSegment::Plain(before.to_string()));
-            }
-            segments.push(TextSegment::Bold(content.to_string()));
-            remaining = remaining[full_match.end()..].to_string();
-            found = true;
-        } else if let Some(caps) = b_under_re.captures(&remaining) {
-            let full_match = caps.get(0).unwrap();
-            let before = &remaining[..full_match.start()];
-            let content = caps.get(1).unwrap().as_str();
-
-            if !before.is_empty() {
-                segments.push(TextSegment::Plain(before.to_string()));
-            }
-            segments.push(TextSegment::Bold(content.to_string()));
-            remaining = remaining[full_match.end()..].to_string();
-            found = true;
-        } else if let Some(caps) = i_stars_re.captures(&remaining) {
-            let full_match = caps.get(0).unwrap();
-            let before = &remaining[..full_match.start()];
-            let content = caps.get(1).unwrap().as_str();
-
-            if !before.is_empty() {
-                segments.push(TextSegment::Plain(before.to_string()));
+        let trimmed = url.trim_end_matches(['.', ',', ';', ':', '!', '?', '\'', '"']);
+        let trimmed = match trimmed.strip_suffix(')') {
+            Some(inner) if inner.matches('(').count() < trimmed.matches(')').count() => inner,
+            _ => trimmed,
+        };
+        if trimmed.len() == url.len() {
+            break;
+        }
+        url = trimmed;
+    }
+    // A scheme on its own is not a link
+    if url.ends_with("//") {
+        return None;
+    }
+    Some((full.start(), full.start() + url.len(), url.to_string()))
+}
+
+/// Turn autolinks and bare URLs into link segments, parsing the rest for scripts and emphasis
+fn parse_autolinks(text: &str) -> Vec<TextSegment> {
+    let mut segments = Vec::new();
+    let mut remaining = text;
+
+    while let Some((start, end, url)) = find_autolink(remaining) {
+        if start > 0 {
+            segments.extend(parse_scripts(&remaining[..start]));
+        }
+        segments.push(TextSegment::Link { text: url.clone(), url });
+        remaining = &remaining[end..];
+    }
+
+    if !remaining.is_empty() {
+        segments.extend(parse_scripts(remaining));
+    }
+
+    segments
+}
+
+/// Find the byte range of a `^text^` or `~text~` span. The content must be non-empty and
+/// contain no whitespace; a marker that is doubled (as in `~~strike~~`) never opens or closes.
+fn find_script_span(text: &str, marker: char) -> Option<(usize, usize)> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let is_single = |i: usize| {
+        chars[i].1 == marker
+            && (i == 0 || chars[i - 1].1 != marker)
+            && chars.get(i + 1).is_none_or(|&(_, c)| c != marker)
+    };
+
+    let mut i = 0;
+    while i < chars.len() {
+        if is_single(i) {
+            let mut j = i + 1;
+            while j < chars.len() && chars[j].1 != marker && !chars[j].1.is_whitespace() {
+                j += 1;
             }
-            segments.push(TextSegment::Italic(content.to_string()));
-            remaining = remaining[full_match.end()..].to_string();
-            found = true;
-        } else if let Some(caps) = i_under_re.captures(&remaining) {
-            let full_match = caps.get(0).unwrap();
-            let before = &remaining[..full_match.start()];
-            let content = caps.get(1).unwrap().as_str();
-
-            if !before.is_empty() {
-                segments.push(TextSegment::Plain(before.to_string()));
+            if j > i + 1 && j < chars.len() && is_single(j) {
+                return Some((chars[i].0, chars[j].0 + marker.len_utf8()));
             }
-            segments.push(TextSegment::Italic(content.to_string()));
-            remaining = remaining[full_match.end()..].to_string();
-            found = true;
         }
+        i += 1;
+    }
+    None
+}
 
-        if !found {
-            break;
+/// Parse superscript (`^text^`) and subscript (`~text~`) spans
+fn parse_scripts(text: &str) -> Vec<TextSegment> {
+    let mut segments = Vec::new();
+    let mut remaining = text.to_string();
+
+    loop {
+        let sup = find_script_span(&remaining, '^');
+        let sub = find_script_span(&remaining, '~');
+        let (start, end, superscript) = match (sup, sub) {
+            (Some(a), Some(b)) if b.0 < a.0 => (b.0, b.1, false),
+            (Some(a), _) => (a.0, a.1, true),
+            (None, Some(b)) => (b.0, b.1, false),
+            (None, None) => break,
+        };
+
+        let before = &remaining[..start];
+        if !before.is_empty() {
+            segments.extend(parse_bold_italic(before));
         }
+        let content = remaining[start + 1..end - 1].to_string();
+        segments.push(if superscript {
+            TextSegment::Superscript(content)
+        } else {
+            TextSegment::Subscript(content)
+        });
+        remaining = remaining[end..].to_string();
     }
 
     if !remaining.is_empty() {
-        segments.push(TextSegment::Plain(remaining));
+        segments.extend(parse_bold_italic(&remaining));
     }
 
     segments
 }
 
+/// Parse bold/italic emphasis, including nested runs such as `*a **b** c*`.
+/// `\*` and `\_` produce literal characters.
+fn parse_bold_italic(text: &str) -> Vec<TextSegment> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut segments = Vec::new();
+    parse_emphasis_runs(&chars, false, false, 0, &mut segments);
+    segments
+}
+
+/// Length of the run of `c` starting at `i`
+fn delimiter_run(chars: &[char], i: usize) -> usize {
+    chars[i..].iter().take_while(|&&d| d == chars[i]).count()
+}
+
+/// A run at `i..i + len` can open emphasis: followed by non-whitespace, and for `_`
+/// not preceded by a letter or digit
+fn can_open(chars: &[char], i: usize, len: usize) -> bool {
+    let next_ok = chars.get(i + len).is_some_and(|c| !c.is_whitespace());
+    let intraword = chars[i] == '_' && i > 0 && chars[i - 1].is_alphanumeric();
+    next_ok && !intraword
+}
+
+/// A run at `i..i + len` can close emphasis: preceded by non-whitespace, and for `_`
+/// not followed by a letter or digit
+fn can_close(chars: &[char], i: usize, len: usize) -> bool {
+    let prev_ok = i > 0 && !chars[i - 1].is_whitespace();
+    let intraword = chars[i] == '_' && chars.get(i + len).is_some_and(|c| c.is_alphanumeric());
+    prev_ok && !intraword
+}
+
+/// Find the closer for an opening run of `len` delimiters whose content starts at `from`.
+/// Nested spans of a different length are skipped. A longer closing-only run closes this
+/// span with its first `len` characters, leaving the rest for an outer span (`*a **b***`).
+/// `skipped` caches the closer found for each nested opener position, so repeated
+/// searches stay polynomial on inputs full of unmatched delimiters.
+fn find_closer(
+    chars: &[char],
+    from: usize,
+    delimiter: char,
+    len: usize,
+    depth: usize,
+    skipped: &mut std::collections::HashMap<usize, Option<usize>>,
+) -> Option<usize> {
+    let mut j = from;
+    while j < chars.len() {
+        if chars[j] == '\\' && matches!(chars.get(j + 1), Some('*') | Some('_')) {
+            j += 2;
+            continue;
+        }
+        if chars[j] != delimiter {
+            j += 1;
+            continue;
+        }
+        let run = delimiter_run(chars, j);
+        if j > from && can_close(chars, j, run) && (run == len || (run > len && !can_open(chars, j, run))) {
+            return Some(j);
+        }
+        // Skip over a nested span so its closer is not mistaken for ours
+        if run != len && depth < MAX_EMPHASIS_NESTING && can_open(chars, j, run) {
+            let inner = match skipped.get(&j) {
+                Some(&inner) => inner,
+                None => {
+                    let inner = find_closer(chars, j + run, delimiter, run, depth + 1, skipped);
+                    skipped.insert(j, inner);
+                    inner
+                }
+            };
+            if let Some(inner) = inner {
+                j = inner + run;
+                continue;
+            }
+        }
+        j += run;
+    }
+    None
+}
+
+fn emphasis_segment(text: String, bold: bool, italic: bool) -> TextSegment {
+    match (bold, italic) {
+        (true, true) => TextSegment::BoldItalic(text),
+        (true, false) => TextSegment::Bold(text),
+        (false, true) => TextSegment::Italic(text),
+        (false, false) => TextSegment::Plain(text),
+    }
+}
+
+fn parse_emphasis_runs(chars: &[char], bold: bool, italic: bool, depth: usize, segments: &mut Vec<TextSegment>) {
+    let mut buf = String::new();
+    let flush = |buf: &mut String, segments: &mut Vec<TextSegment>| {
+        if !buf.is_empty() {
+            segments.push(emphasis_segment(std::mem::take(buf), bold, italic));
+        }
+    };
+
+    let mut skipped = std::collections::HashMap::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\\' && matches!(chars.get(i + 1), Some('*') | Some('_')) {
+            buf.push(chars[i + 1]);
+            i += 2;
+            continue;
+        }
+        if c != '*' && c != '_' {
+            buf.push(c);
+            i += 1;
+            continue;
+        }
+
+        let run = delimiter_run(chars, i);
+        if depth < MAX_EMPHASIS_NESTING && can_open(chars, i, run) {
+            // Try the whole run first, then split a triple run into outer `*` + inner `**`
+            let mut opened = false;
+            for len in [run.min(3), 1] {
+                if let Some(close) = find_closer(chars, i + len, c, len, 0, &mut skipped) {
+                    flush(&mut buf, segments);
+                    let (b, it) = match len {
+                        1 => (bold, true),
+                        2 => (true, italic),
+                        _ => (true, true),
+                    };
+                    parse_emphasis_runs(&chars[i + len..close], b, it, depth + 1, segments);
+                    i = close + len;
+                    opened = true;
+                    break;
+                }
+                if run == 1 {
+                    break;
+                }
+            }
+            if opened {
+                continue;
+            }
+        }
+        buf.extend(&chars[i..i + run]);
+        i += run;
+    }
+    flush(&mut buf, segments);
+}
+
 /// Check if text contains any inline markdown formatting
 pub fn has_inline_formatting(text: &str) -> bool {
     text.contains("**") || text.contains("__") || text.contains("***") || text.contains("___") || text.contains("`") || text.contains("[")
+        || text.contains("\\*") || text.contains("\\_")
+        || text.contains("http://") || text.contains("https://")
+        || find_script_span(text, '^').is_some() || find_script_span(text, '~').is_some()
+        || highlight_regex().is_match(text)
+}
+
+/// Deepest blockquote or list nesting kept by default
+pub const DEFAULT_MAX_NESTING_DEPTH: u8 = 32;
+
+/// Deepest emphasis nesting parsed inline; deeper delimiters stay literal text
+const MAX_EMPHASIS_NESTING: usize = 32;
+
+/// What the parser does with Markdown syntax it does not model, such as raw
+/// HTML blocks, reference link definitions and setext heading underlines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownSyntax {
+    /// Keep the line as a literal paragraph
+    #[default]
+    Keep,
+    /// Leave the line out of the document
+    Drop,
+    /// Keep the line as a literal paragraph and report it as a warning
+    Warn,
+}
+
+/// Options for parsing Markdown, including limits that keep untrusted input
+/// from producing huge output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Blockquote and list items nested deeper than this are clamped to it
+    pub max_nesting_depth: u8,
+    /// Start a new line at every newline inside a paragraph, as in GitHub
+    /// comments, instead of joining the lines into one wrapped paragraph.
+    ///
+    /// Off by default: consecutive lines are joined, and only a line ending in
+    /// two spaces or a backslash breaks, as in CommonMark. Set this to keep
+    /// the line-per-paragraph output of earlier versions.
+    pub hard_line_breaks: bool,
+    /// Handling of unsupported syntax
+    pub on_unknown: UnknownSyntax,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            max_nesting_depth: DEFAULT_MAX_NESTING_DEPTH,
+            hard_line_breaks: false,
+            on_unknown: UnknownSyntax::default(),
+        }
+    }
+}
+
+/// Tags that start an HTML block wherever they open a line (CommonMark types 1 and 6)
+const HTML_BLOCK_TAGS: &[&str] = &[
+    "address", "article", "aside", "base", "basefont", "blockquote", "body", "caption", "center",
+    "col", "colgroup", "dd", "details", "dialog", "dir", "div", "dl", "dt", "fieldset", "figcaption",
+    "figure", "footer", "form", "frame", "frameset", "h1", "h2", "h3", "h4", "h5", "h6", "head",
+    "header", "hr", "html", "iframe", "legend", "li", "link", "main", "menu", "menuitem", "nav",
+    "noframes", "ol", "optgroup", "option", "p", "param", "pre", "script", "search", "section",
+    "style", "summary", "table", "tbody", "td", "textarea", "tfoot", "th", "thead", "title", "tr",
+    "track", "ul",
+];
+
+/// Name of the unsupported construct a trimmed line consists of, if any
+fn unsupported_syntax(line: &str) -> Option<&'static str> {
+    // A block-level tag opening the line, or a line that is nothing but one tag
+    // or a comment. Inline tags followed by text, like `<b>Note</b>: ...`, are prose.
+    let block_re = regex::Regex::new(r"^</?([A-Za-z][A-Za-z0-9-]*)(\s|/?>|$)").unwrap();
+    let lone_tag_re = regex::Regex::new(r"^(</?[A-Za-z][A-Za-z0-9-]*(\s[^<>]*)?/?>|<!--.*)$").unwrap();
+    let reference_re = regex::Regex::new(r"^\[[^\]^][^\]]*\]:\s*\S").unwrap();
+    let block_tag = block_re
+        .captures(line)
+        .is_some_and(|caps| HTML_BLOCK_TAGS.contains(&caps[1].to_ascii_lowercase().as_str()));
+    if block_tag || lone_tag_re.is_match(line) {
+        Some("HTML block")
+    } else if reference_re.is_match(line) {
+        Some("reference link definition")
+    } else if line.len() >= 3 && line.chars().all(|c| c == '=') {
+        Some("setext heading underline")
+    } else {
+        None
+    }
+}
+
+/// `(checked, total)` task list items, or `None` without any
+pub fn task_progress(elements: &[Element]) -> Option<(usize, usize)> {
+    let checked: Vec<bool> = elements
+        .iter()
+        .filter_map(|e| match e {
+            Element::TaskListItem { checked, .. } => Some(*checked),
+            _ => None,
+        })
+        .collect();
+    (!checked.is_empty()).then(|| (checked.iter().filter(|c| **c).count(), checked.len()))
 }
 
 /// Parse markdown text into structured elements
 pub fn parse_markdown(markdown: &str) -> Vec<Element> {
+    parse_markdown_with_options(markdown, &ParseOptions::default()).0
+}
+
+/// A front end turning some markup format into elements, so inputs other
+/// than Markdown can feed the same renderer
+///
+/// ```rust
+/// use pdfrs::elements::{Element, SourceParser};
+///
+/// /// Every non-blank line becomes a paragraph
+/// struct Lines;
+///
+/// impl SourceParser for Lines {
+///     fn parse(&self, input: &str) -> Vec<Element> {
+///         input.lines().filter(|l| !l.trim().is_empty()).map(|l| Element::Paragraph { text: l.into() }).collect()
+///     }
+/// }
+///
+/// assert_eq!(Lines.parse("a\n\nb").len(), 2);
+/// ```
+pub trait SourceParser {
+    fn parse(&self, input: &str) -> Vec<Element>;
+}
+
+/// The built-in Markdown front end; warnings from its options are dropped
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MarkdownParser {
+    pub options: ParseOptions,
+}
+
+impl SourceParser for MarkdownParser {
+    fn parse(&self, input: &str) -> Vec<Element> {
+        parse_markdown_with_options(input, &self.options).0
+    }
+}
+
+/// The text of a line that is entirely `*italic*` or `_italic_`
+fn italic_line(line: &str) -> Option<String> {
+    match parse_inline_formatting(line).as_slice() {
+        [TextSegment::Italic(text)] => Some(text.clone()),
+        _ => None,
+    }
+}
+
+/// Append the paragraph line `next` to `previous` across a soft line break
+fn join_paragraph_lines(previous: &mut Element, next: Element) {
+    let as_segments = |element: Element| match element {
+        Element::Paragraph { text } => vec![TextSegment::Plain(text)],
+        Element::RichParagraph { segments } => segments,
+        _ => Vec::new(),
+    };
+    match (&mut *previous, next) {
+        (Element::Paragraph { text }, Element::Paragraph { text: next }) => {
+            text.push(' ');
+            text.push_str(&next);
+        }
+        (_, next) => {
+            let mut segments = as_segments(std::mem::replace(previous, Element::EmptyLine));
+            segments.push(TextSegment::Plain(" ".into()));
+            segments.extend(as_segments(next));
+            *previous = Element::RichParagraph { segments };
+        }
+    }
+}
+
+/// Depth of the list item that an indented paragraph would continue, skipping blank lines
+fn continued_list_depth(elements: &[Element]) -> Option<u8> {
+    match elements.iter().rev().find(|e| !matches!(e, Element::EmptyLine))? {
+        Element::UnorderedListItem { depth, .. }
+        | Element::OrderedListItem { depth, .. }
+        | Element::ListParagraph { depth, .. } => Some(*depth),
+        Element::TaskListItem { .. } => Some(0),
+        Element::TableRow { list_depth, .. } => *list_depth,
+        _ => None,
+    }
+}
+
+/// Like [`parse_markdown`], also returning a warning for each line whose
+/// nesting was clamped to `options.max_nesting_depth`
+pub fn parse_markdown_with_options(markdown: &str, options: &ParseOptions) -> (Vec<Element>, Vec<String>) {
+    let mut warnings = Vec::new();
+    let clamp_depth = |depth: usize, line: usize, warnings: &mut Vec<String>| -> u8 {
+        let max = options.max_nesting_depth;
+        if depth > max as usize {
+            warnings.push(format!("line {}: nesting depth {} exceeds the limit of {}; clamped", line, depth, max));
+            max
+        } else {
+            depth as u8
+        }
+    };
     let mut elements = Vec::new();
     let mut in_code_block = false;
     let mut code_lang = String::new();
@@ -268,6 +1051,9 @@ pub fn parse_markdown(markdown: &str) -> Vec<Element> {
     let mut in_math_block = false;
     let mut math_buf = String::new();
     let lines: Vec<&str> = markdown.lines().collect();
+    let callout_re = regex::Regex::new(r"^>\s*\[!(\w+)\]\s*$").unwrap();
+    // Line index of the last paragraph line, which the next line may continue
+    let mut last_paragraph_line: Option<usize> = None;
     let mut i = 0;
 
     while i < lines.len() {
@@ -351,7 +1137,7 @@ pub fn parse_markdown(markdown: &str) -> Vec<Element> {
         // Headings
         if trimmed.starts_with('#') {
             let level = trimmed.chars().take_while(|&c| c == '#').count().min(6) as u8;
-            let text = trimmed[level as usize..].trim().to_string();
+            let text = decode_entities(trimmed[level as usize..].trim());
             elements.push(Element::Heading { level, text });
             i += 1;
             continue;
@@ -364,14 +1150,31 @@ pub fn parse_markdown(markdown: &str) -> Vec<Element> {
             continue;
         }
 
-        // Image: ![alt](path)
+        // Image: ![alt](path) or ![alt](path "caption"), optionally followed by
+        // `{float=left}` or `{float=right}`; a following all-italic line is
+        // also taken as the caption
         if trimmed.starts_with("![") {
-            let img_re = regex::Regex::new(r"^!\[([^\]]*)\]\(([^\)]+)\)$").unwrap();
+            let img_re = regex::Regex::new(
+                r#"^!\[([^\]]*)\]\(([^\)]+?)(?:\s+"([^"]*)")?\)(?:\{\s*float\s*=\s*(left|right)\s*\})?$"#,
+            )
+            .unwrap();
             if let Some(caps) = img_re.captures(trimmed) {
                 let alt = caps[1].to_string();
                 let path = caps[2].to_string();
-                elements.push(Element::Image { alt, path });
+                let float = match caps.get(4).map(|m| m.as_str()) {
+                    Some("left") => ImageFloat::Left,
+                    Some("right") => ImageFloat::Right,
+                    _ => ImageFloat::None,
+                };
+                let mut caption = caps.get(3).map(|c| c.as_str().to_string());
                 i += 1;
+                if caption.is_none()
+                    && let Some(text) = lines.get(i).and_then(|next| italic_line(next.trim()))
+                {
+                    caption = Some(text);
+                    i += 1;
+                }
+                elements.push(Element::Image { alt, path, caption, float });
                 continue;
             }
         }
@@ -388,16 +1191,56 @@ pub fn parse_markdown(markdown: &str) -> Vec<Element> {
             }
         }
 
+        // Standalone autolink or bare URL line
+        if let Some((0, end, url)) = find_autolink(trimmed)
+            && end == trimmed.len()
+        {
+            elements.push(Element::Link { text: url.clone(), url });
+            i += 1;
+            continue;
+        }
+
+        // GitHub-style alert: `> [!KIND]` alone on the first line of a blockquote
+        if let Some(caps) = callout_re.captures(trimmed)
+            && let Some(kind) = CalloutKind::from_marker(&caps[1])
+        {
+            let mut body = Vec::new();
+            i += 1;
+            while let Some(rest) = lines.get(i).and_then(|line| line.trim().strip_prefix('>')) {
+                let rest = rest.trim();
+                if !rest.is_empty() {
+                    body.push(rest);
+                }
+                i += 1;
+            }
+            let text = strip_inline_formatting(&body.join(" "));
+            elements.push(Element::Callout { kind, text });
+            continue;
+        }
+
         // Blockquote
         if trimmed.starts_with('>') {
-            let mut depth: u8 = 0;
+            let mut depth = 0;
             let mut rest = trimmed;
-            while rest.starts_with('>') {
+            while let Some(after) = rest.strip_prefix('>') {
                 depth += 1;
-                rest = rest[1..].trim_start();
+                // Keep indentation after the last marker, it nests lists
+                rest = if after.trim_start().starts_with('>') { after.trim_start() } else { after };
+            }
+            let rest = rest.strip_prefix(' ').unwrap_or(rest);
+            let depth = clamp_depth(depth, i + 1, &mut warnings);
+            let (inner, _) = parse_markdown_with_options(rest, options);
+            match inner.as_slice() {
+                [item @ (Element::UnorderedListItem { .. }
+                | Element::OrderedListItem { .. }
+                | Element::TaskListItem { .. })] => {
+                    elements.push(Element::Quoted { depth, element: Box::new(item.clone()) });
+                }
+                _ => {
+                    let text = strip_inline_formatting(rest.trim());
+                    elements.push(Element::BlockQuote { text, depth });
+                }
             }
-            let text = strip_inline_formatting(rest);
-            elements.push(Element::BlockQuote { text, depth });
             i += 1;
             continue;
         }
@@ -413,6 +1256,9 @@ pub fn parse_markdown(markdown: &str) -> Vec<Element> {
 
         // Table rows (contains |)
         if trimmed.starts_with('|') && trimmed.ends_with('|') {
+            // An indented table under a list item belongs to that item
+            let indent = line.len() - line.trim_start().len();
+            let list_depth = if indent >= 2 { continued_list_depth(&elements) } else { None };
             let inner = &trimmed[1..trimmed.len() - 1];
             let cells: Vec<String> = inner.split('|').map(|c| c.trim().to_string()).collect();
             let is_separator = cells.iter().all(|c| {
@@ -421,11 +1267,11 @@ pub fn parse_markdown(markdown: &str) -> Vec<Element> {
             });
             if is_separator {
                 let alignments: Vec<TableAlignment> = cells.iter().map(|c| parse_cell_alignment(c)).collect();
-                elements.push(Element::TableRow { cells, is_separator: true, alignments });
+                elements.push(Element::TableRow { cells, is_separator: true, alignments, list_depth });
             } else {
-                let cells: Vec<String> = cells.into_iter().map(|c| strip_inline_formatting(&c)).collect();
+                // Inline formatting is kept and rendered per cell
                 let alignments = vec![TableAlignment::Left; cells.len()];
-                elements.push(Element::TableRow { cells, is_separator: false, alignments });
+                elements.push(Element::TableRow { cells, is_separator: false, alignments, list_depth });
             }
             i += 1;
             continue;
@@ -462,20 +1308,22 @@ pub fn parse_markdown(markdown: &str) -> Vec<Element> {
         // Unordered list items (detect indentation depth)
         if trimmed.starts_with("- ") || trimmed.starts_with("* ") {
             let indent = line.len() - line.trim_start().len();
-            let depth = (indent / 2) as u8;
+            let depth = clamp_depth(indent / 2, i + 1, &mut warnings);
             let text = strip_inline_formatting(&trimmed[2..]);
             elements.push(Element::UnorderedListItem { text, depth });
             i += 1;
             continue;
         }
 
-        // Ordered list items
-        if let Some(dot_pos) = trimmed.find(". ") {
+        // Ordered list items, numbered `1.` or `1)`
+        if let Some(dot_pos) = trimmed.find(|c: char| !c.is_ascii_digit())
+            && (trimmed[dot_pos..].starts_with(". ") || trimmed[dot_pos..].starts_with(") "))
+        {
             let num_part = &trimmed[..dot_pos];
             if !num_part.is_empty() && num_part.chars().all(|c| c.is_ascii_digit()) {
                 let number: u32 = num_part.parse().unwrap_or(1);
                 let indent = line.len() - line.trim_start().len();
-                let depth = (indent / 3) as u8;
+                let depth = clamp_depth(indent / 3, i + 1, &mut warnings);
                 let text = strip_inline_formatting(&trimmed[dot_pos + 2..]);
                 elements.push(Element::OrderedListItem { number, text, depth });
                 i += 1;
@@ -493,21 +1341,61 @@ pub fn parse_markdown(markdown: &str) -> Vec<Element> {
             }
         }
 
-        // Regular paragraph — also strip footnote references [^N] -> (N)
+        // Syntax the parser does not model
+        if let Some(construct) = unsupported_syntax(trimmed) {
+            if options.on_unknown == UnknownSyntax::Warn {
+                warnings.push(format!("line {}: unsupported {} kept as text", i + 1, construct));
+            }
+            if options.on_unknown != UnknownSyntax::Drop {
+                elements.push(Element::Paragraph { text: trimmed.to_string() });
+            }
+            i += 1;
+            continue;
+        }
+
+        // Indented line after a list item continues that item
+        let indent = line.len() - line.trim_start().len();
+        if indent >= 2 && let Some(depth) = continued_list_depth(&elements) {
+            let text = strip_inline_formatting(trimmed);
+            match elements.last_mut() {
+                Some(Element::UnorderedListItem { text: prev, .. }
+                | Element::OrderedListItem { text: prev, .. }
+                | Element::TaskListItem { text: prev, .. }
+                | Element::ListParagraph { text: prev, .. }) => {
+                    prev.push(' ');
+                    prev.push_str(&text);
+                }
+                _ => elements.push(Element::ListParagraph { text, depth }),
+            }
+            i += 1;
+            continue;
+        }
+
+        // Regular paragraph — a line ending in two spaces or a backslash is a
+        // CommonMark hard break, so the next line starts a line of its own
+        let (trimmed, hard_break) = match trimmed.strip_suffix('\\') {
+            Some(text) if !text.ends_with('\\') => (text.trim_end(), true),
+            _ => (trimmed, line.ends_with("  ")),
+        };
+        // Also strip footnote references [^N] -> (N)
         let footnote_ref_re = regex::Regex::new(r"\[\^([^\]]+)\]").unwrap();
         let trimmed_with_refs = footnote_ref_re.replace_all(trimmed, "($1)").to_string();
 
         // Check for inline formatting and use RichParagraph if present
-        if has_inline_formatting(&trimmed_with_refs) {
+        let paragraph = if has_inline_formatting(&trimmed_with_refs) {
             let segments = parse_inline_formatting(&trimmed_with_refs);
-            if !segments.is_empty() {
-                elements.push(Element::RichParagraph { segments });
-            }
+            (!segments.is_empty()).then_some(Element::RichParagraph { segments })
         } else {
             let text = strip_inline_formatting(&trimmed_with_refs);
-            if !text.is_empty() {
-                elements.push(Element::Paragraph { text });
+            (!text.is_empty()).then_some(Element::Paragraph { text })
+        };
+        if let Some(paragraph) = paragraph {
+            let continues = !options.hard_line_breaks && i > 0 && last_paragraph_line == Some(i - 1);
+            match elements.last_mut() {
+                Some(previous) if continues => join_paragraph_lines(previous, paragraph),
+                _ => elements.push(paragraph),
             }
+            last_paragraph_line = (!hard_break).then_some(i);
         }
         i += 1;
     }
@@ -525,7 +1413,7 @@ pub fn parse_markdown(markdown: &str) -> Vec<Element> {
         elements.push(Element::MathBlock { expression: math_buf });
     }
 
-    elements
+    (elements, warnings)
 }
 
 #[cfg(test)]
@@ -554,6 +1442,247 @@ mod tests {
         assert_eq!(strip_inline_formatting("keep ~~this~~ text"), "keep this text");
     }
 
+    #[test]
+    fn test_parse_superscript_subscript() {
+        assert_eq!(
+            parse_inline_formatting("x^2^ and H~2~O"),
+            vec![
+                TextSegment::Plain("x".into()),
+                TextSegment::Superscript("2".into()),
+                TextSegment::Plain(" and H".into()),
+                TextSegment::Subscript("2".into()),
+                TextSegment::Plain("O".into()),
+            ]
+        );
+        assert!(has_inline_formatting("E = mc^2^"));
+        assert!(!has_inline_formatting("~~gone~~"));
+        assert_eq!(strip_inline_formatting("H~2~O and ~~old~~"), "H2O and old");
+    }
+
+    #[test]
+    fn test_parse_highlight() {
+        let yellow = crate::pdf_generator::Color::yellow();
+        assert_eq!(
+            parse_inline_formatting("a ==marked text== and `code`"),
+            vec![
+                TextSegment::Plain("a ".into()),
+                TextSegment::Highlight("marked text".into(), yellow),
+                TextSegment::Plain(" and ".into()),
+                TextSegment::Code("code".into()),
+            ]
+        );
+        assert!(!has_inline_formatting("if a == b and c == d"));
+        assert_eq!(strip_inline_formatting("keep ==this== text"), "keep this text");
+    }
+
+    #[test]
+    fn test_decode_entities() {
+        assert_eq!(parse_markdown("&copy; 2024"), vec![Element::Paragraph { text: "© 2024".into() }]);
+        assert_eq!(decode_entities("&#x20AC;5 or &#8364;5"), "€5 or €5");
+        assert_eq!(decode_entities("A &amp; B &lt;c&gt;"), "A & B <c>");
+        // Unknown names stay, invalid code points are replaced
+        assert_eq!(decode_entities("&bogus; &#0; &#xD800;"), "&bogus; \u{fffd} \u{fffd}");
+        assert_eq!(parse_markdown("# Q&amp;A")[0], Element::Heading { level: 1, text: "Q&A".into() });
+        assert_eq!(
+            parse_inline_formatting("**&euro;** `&amp;`"),
+            vec![
+                TextSegment::Bold("€".into()),
+                TextSegment::Plain(" ".into()),
+                TextSegment::Code("&amp;".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ordered_items_with_parenthesis_delimiter() {
+        let elements = parse_markdown("1) first\n   2) nested\n3.5) not a list");
+        assert_eq!(elements[0], Element::OrderedListItem { number: 1, text: "first".into(), depth: 0 });
+        assert_eq!(elements[1], Element::OrderedListItem { number: 2, text: "nested".into(), depth: 1 });
+        assert!(matches!(elements[2], Element::Paragraph { .. }), "{:?}", elements[2]);
+    }
+
+    #[test]
+    fn test_list_inside_blockquote() {
+        let elements = parse_markdown("> Steps:\n> - first\n>   - nested\n> > 2. second");
+        assert_eq!(elements[0], Element::BlockQuote { text: "Steps:".into(), depth: 1 });
+        assert_eq!(
+            elements[1],
+            Element::Quoted { depth: 1, element: Box::new(Element::UnorderedListItem { text: "first".into(), depth: 0 }) }
+        );
+        assert_eq!(
+            elements[2],
+            Element::Quoted { depth: 1, element: Box::new(Element::UnorderedListItem { text: "nested".into(), depth: 1 }) }
+        );
+        assert_eq!(
+            elements[3],
+            Element::Quoted { depth: 2, element: Box::new(Element::OrderedListItem { number: 2, text: "second".into(), depth: 0 }) }
+        );
+    }
+
+    #[test]
+    fn test_nesting_depth_is_clamped() {
+        let quote = format!("{} deep", ">".repeat(10_000));
+        let (elements, warnings) = parse_markdown_with_options(&quote, &ParseOptions::default());
+        assert_eq!(elements, vec![Element::BlockQuote { text: "deep".into(), depth: DEFAULT_MAX_NESTING_DEPTH }]);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("10000"), "{:?}", warnings);
+
+        let list = format!("{}- item", " ".repeat(1000));
+        let options = ParseOptions { max_nesting_depth: 4, ..Default::default() };
+        let (elements, _) = parse_markdown_with_options(&list, &options);
+        assert_eq!(elements, vec![Element::UnorderedListItem { text: "item".into(), depth: 4 }]);
+
+        // Deeply nested emphasis parses without exhausting the stack
+        let emphasis = "*a **b ".repeat(500);
+        assert!(!parse_inline_formatting(&emphasis).is_empty());
+    }
+
+    #[test]
+    fn test_custom_source_parser_generates_pdf() {
+        /// `= Title` lines are headings, other lines paragraphs, as in AsciiDoc
+        struct TinyDoc;
+        impl SourceParser for TinyDoc {
+            fn parse(&self, input: &str) -> Vec<Element> {
+                input
+                    .lines()
+                    .map(|line| match line.strip_prefix("= ") {
+                        Some(title) => Element::Heading { level: 1, text: title.into() },
+                        None => Element::Paragraph { text: line.into() },
+                    })
+                    .collect()
+            }
+        }
+
+        let parser: &dyn SourceParser = &TinyDoc;
+        let elements = parser.parse("= Guide\nFirst steps.");
+        let layout = crate::pdf_generator::PageLayout::portrait();
+        let bytes = crate::pdf_generator::generate_pdf_bytes(&elements, "Helvetica", 12.0, layout).unwrap();
+        assert!(crate::pdf::validate_pdf_bytes(&bytes).valid);
+        let text = crate::pdf::PdfDocument::from_bytes(&bytes).unwrap().get_text().unwrap();
+        assert!(text.contains("Guide") && text.contains("First steps."), "{}", text);
+
+        let markdown = "# Title\n\nBody";
+        assert_eq!(MarkdownParser::default().parse(markdown), parse_markdown(markdown));
+    }
+
+    #[test]
+    fn test_hard_line_breaks() {
+        let markdown = "first line\nsecond **line**\n\nnext";
+        let (elements, _) = parse_markdown_with_options(markdown, &ParseOptions::default());
+        assert_eq!(
+            elements,
+            vec![
+                Element::RichParagraph {
+                    segments: vec![
+                        TextSegment::Plain("first line".into()),
+                        TextSegment::Plain(" ".into()),
+                        TextSegment::Plain("second ".into()),
+                        TextSegment::Bold("line".into()),
+                    ]
+                },
+                Element::EmptyLine,
+                Element::Paragraph { text: "next".into() },
+            ]
+        );
+        assert_eq!(
+            parse_markdown("one\ntwo"),
+            vec![Element::Paragraph { text: "one two".into() }]
+        );
+
+        // Two trailing spaces or a trailing backslash break the line regardless
+        assert_eq!(
+            parse_markdown("one  \ntwo\\\nthree\nfour"),
+            vec![
+                Element::Paragraph { text: "one".into() },
+                Element::Paragraph { text: "two".into() },
+                Element::Paragraph { text: "three four".into() },
+            ]
+        );
+
+        let options = ParseOptions { hard_line_breaks: true, ..Default::default() };
+        let (elements, _) = parse_markdown_with_options("one\ntwo", &options);
+        assert_eq!(
+            elements,
+            vec![Element::Paragraph { text: "one".into() }, Element::Paragraph { text: "two".into() }]
+        );
+    }
+
+    #[test]
+    fn test_unknown_syntax_handling() {
+        let markdown = "<div align=\"center\">\n\nText\n\n[docs]: https://example.com";
+        let parse = |on_unknown| parse_markdown_with_options(markdown, &ParseOptions { on_unknown, ..Default::default() });
+
+        let (elements, warnings) = parse(UnknownSyntax::Keep);
+        assert_eq!(elements[0], Element::Paragraph { text: "<div align=\"center\">".into() });
+        assert_eq!(elements[4], Element::Paragraph { text: "[docs]: https://example.com".into() });
+        assert!(warnings.is_empty());
+
+        let (elements, warnings) = parse(UnknownSyntax::Warn);
+        assert_eq!(elements, parse(UnknownSyntax::Keep).0);
+        assert_eq!(
+            warnings,
+            vec![
+                "line 1: unsupported HTML block kept as text".to_string(),
+                "line 5: unsupported reference link definition kept as text".to_string(),
+            ]
+        );
+
+        let (elements, _) = parse(UnknownSyntax::Drop);
+        assert_eq!(elements, vec![Element::EmptyLine, Element::Paragraph { text: "Text".into() }, Element::EmptyLine]);
+
+        // Inline tags starting a line of prose are not HTML blocks
+        for prose in ["<b>Note</b>: text", "<span class=\"x\">hi</span> there", "<kbd>Ctrl</kbd>+<kbd>C</kbd> copies"] {
+            assert_eq!(unsupported_syntax(prose), None, "{}", prose);
+        }
+        for block in ["<details>", "<TABLE border=1>", "</div>", "<!-- note -->", "<img src=\"a.png\"/>", "<p>Text</p>"] {
+            assert_eq!(unsupported_syntax(block), Some("HTML block"), "{}", block);
+        }
+    }
+
+    #[test]
+    fn test_parse_nested_emphasis() {
+        assert_eq!(
+            parse_inline_formatting("*a **b** c*"),
+            vec![
+                TextSegment::Italic("a ".into()),
+                TextSegment::BoldItalic("b".into()),
+                TextSegment::Italic(" c".into()),
+            ]
+        );
+        assert_eq!(
+            parse_inline_formatting("***both*** and **bold _inner_**"),
+            vec![
+                TextSegment::BoldItalic("both".into()),
+                TextSegment::Plain(" and ".into()),
+                TextSegment::Bold("bold ".into()),
+                TextSegment::BoldItalic("inner".into()),
+            ]
+        );
+        assert_eq!(
+            parse_inline_formatting("***a** b*"),
+            vec![TextSegment::BoldItalic("a".into()), TextSegment::Italic(" b".into())]
+        );
+        assert_eq!(
+            parse_inline_formatting("*a **b***"),
+            vec![TextSegment::Italic("a ".into()), TextSegment::BoldItalic("b".into())]
+        );
+        assert_eq!(parse_inline_formatting("\\*literal\\*"), vec![TextSegment::Plain("*literal*".into())]);
+        assert_eq!(parse_inline_formatting("snake_case_name"), vec![TextSegment::Plain("snake_case_name".into())]);
+        assert_eq!(parse_inline_formatting("2 * 3 * 4"), vec![TextSegment::Plain("2 * 3 * 4".into())]);
+    }
+
+    #[test]
+    fn test_parse_callout() {
+        let elements = parse_markdown("> [!WARNING]\n> Back up **first**.\n>\n> Really.\n\n> plain");
+        assert_eq!(
+            elements[0],
+            Element::Callout { kind: CalloutKind::Warning, text: "Back up first. Really.".into() }
+        );
+        assert!(matches!(elements.last(), Some(Element::BlockQuote { .. })));
+        // Unknown kinds stay ordinary blockquotes
+        assert!(matches!(parse_markdown("> [!FOO]\n> x")[0], Element::BlockQuote { .. }));
+    }
+
     #[test]
     fn test_parse_blockquote() {
         let elements = parse_markdown("> quoted text\n>> nested");
@@ -598,6 +1727,15 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_table_cells_keep_inline_formatting() {
+        let elements = parse_markdown("| **x** | `y` |");
+        match &elements[0] {
+            Element::TableRow { cells, .. } => assert_eq!(cells, &["**x**", "`y`"]),
+            _ => panic!("Expected TableRow"),
+        }
+    }
+
     #[test]
     fn test_parse_table_alignment() {
         let md = "| L | C | R |\n|:---|:---:|---:|\n| a | b | c |";
@@ -632,6 +1770,22 @@ mod tests {
         assert_eq!(elements[2], Element::UnorderedListItem { text: "Deep".into(), depth: 2 });
     }
 
+    #[test]
+    fn test_parse_list_item_paragraphs() {
+        let md = "- Top\n  wrapped line\n\n  Second paragraph\n  - Nested\n\n    Under nested\n\nAfter list";
+        let elements = parse_markdown(md);
+        assert_eq!(elements, vec![
+            Element::UnorderedListItem { text: "Top wrapped line".into(), depth: 0 },
+            Element::EmptyLine,
+            Element::ListParagraph { text: "Second paragraph".into(), depth: 0 },
+            Element::UnorderedListItem { text: "Nested".into(), depth: 1 },
+            Element::EmptyLine,
+            Element::ListParagraph { text: "Under nested".into(), depth: 1 },
+            Element::EmptyLine,
+            Element::Paragraph { text: "After list".into() },
+        ]);
+    }
+
     #[test]
     fn test_strip_inline_formatting() {
         assert_eq!(strip_inline_formatting("**bold**"), "bold");
@@ -685,6 +1839,8 @@ mod tests {
         assert_eq!(elements[0], Element::Image {
             alt: "Logo".into(),
             path: "images/logo.png".into(),
+            caption: None,
+            float: ImageFloat::None,
         });
     }
 
@@ -696,9 +1852,25 @@ mod tests {
         assert_eq!(elements[0], Element::Image {
             alt: "".into(),
             path: "photo.jpg".into(),
+            caption: None,
+            float: ImageFloat::None,
         });
     }
 
+    #[test]
+    fn test_parse_image_captions() {
+        let image = |caption: Option<&str>| Element::Image {
+            alt: "x".into(),
+            path: "a.png".into(),
+            caption: caption.map(str::to_string),
+            float: ImageFloat::None,
+        };
+        assert_eq!(parse_markdown(r#"![x](a.png "Figure 1")"#), vec![image(Some("Figure 1"))]);
+        assert_eq!(parse_markdown("![x](a.png)\n*Figure 2: growth*"), vec![image(Some("Figure 2: growth"))]);
+        // Bold text on the next line stays a paragraph
+        assert_eq!(parse_markdown("![x](a.png)\n**Note**").len(), 2);
+    }
+
     #[test]
     fn test_parse_standalone_link() {
         let md = "[Click here](https://example.com)";
@@ -710,6 +1882,26 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_bare_urls_and_autolinks() {
+        let link = |url: &str| TextSegment::Link { text: url.into(), url: url.into() };
+        assert_eq!(parse_inline_formatting("see https://x.com."), vec![
+            TextSegment::Plain("see ".into()),
+            link("https://x.com"),
+            TextSegment::Plain(".".into()),
+        ]);
+        assert_eq!(parse_inline_formatting("docs (https://en.wikipedia.org/wiki/Rust_(language)), or <https://a.io/x_y_z>"), vec![
+            TextSegment::Plain("docs (".into()),
+            link("https://en.wikipedia.org/wiki/Rust_(language)"),
+            TextSegment::Plain("), or ".into()),
+            link("https://a.io/x_y_z"),
+        ]);
+        assert_eq!(parse_markdown("<https://example.com>"), vec![Element::Link {
+            text: "https://example.com".into(),
+            url: "https://example.com".into(),
+        }]);
+    }
+
     #[test]
     fn test_parse_pagebreak_html() {
         let md = "<!-- pagebreak -->";
@@ -745,6 +1937,47 @@ mod tests {
         assert!(types.contains(&"pagebreak"));
         assert!(types.contains(&"paragraph"));
     }
+
+    #[test]
+    fn test_document_builder() {
+        let built = DocumentBuilder::new()
+            .heading(1, "Title")
+            .paragraph("Intro")
+            .bullet("First")
+            .numbered("One")
+            .numbered("Two")
+            .table(&["A", "B"], &[vec!["1", "2"]])
+            .page_break()
+            .build();
+
+        let expected = vec![
+            Element::Heading { level: 1, text: "Title".into() },
+            Element::Paragraph { text: "Intro".into() },
+            Element::UnorderedListItem { text: "First".into(), depth: 0 },
+            Element::OrderedListItem { number: 1, text: "One".into(), depth: 0 },
+            Element::OrderedListItem { number: 2, text: "Two".into(), depth: 0 },
+            Element::TableRow {
+                cells: vec!["A".into(), "B".into()],
+                is_separator: false,
+                alignments: vec![TableAlignment::Left; 2],
+                list_depth: None,
+            },
+            Element::TableRow {
+                cells: vec!["---".into(), "---".into()],
+                is_separator: true,
+                alignments: vec![TableAlignment::Left; 2],
+                list_depth: None,
+            },
+            Element::TableRow {
+                cells: vec!["1".into(), "2".into()],
+                is_separator: false,
+                alignments: vec![TableAlignment::Left; 2],
+                list_depth: None,
+            },
+            Element::PageBreak,
+        ];
+        assert_eq!(built, expected);
+    }
 }
 
 #[cfg(test)]
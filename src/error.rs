@@ -0,0 +1,117 @@
+//! Error types returned by the public API.
+//!
+//! [`PdfError`] lets callers match on what went wrong (a missing file, a bad
+//! page range, an encrypted input, ...) instead of inspecting message strings.
+//! It implements [`std::error::Error`], so `?` still converts it into
+//! `anyhow::Error` in application code.
+//!
+//! ```rust,no_run
+//! use pdfrs::{error::PdfError, pdf_ops};
+//!
+//! match pdf_ops::split_pdf("input.pdf", "out.pdf", 5, 2) {
+//!     Err(PdfError::InvalidPageRange { start, end, .. }) => {
+//!         eprintln!("bad range {}-{}", start, end)
+//!     }
+//!     Err(e) => eprintln!("split failed: {}", e),
+//!     Ok(()) => {}
+//! }
+//! ```
+
+use std::fmt;
+
+/// Errors produced by pdfrs operations
+#[derive(Debug)]
+pub enum PdfError {
+    /// Reading or writing a file failed
+    Io(std::io::Error),
+    /// A page range or page number is outside the document
+    InvalidPageRange {
+        start: usize,
+        end: usize,
+        /// Number of pages in the document, when it is known
+        page_count: Option<usize>,
+    },
+    /// The input is not a PDF pdfrs can understand
+    Parse(String),
+    /// The document is encrypted and cannot be processed, or the password given does not open it
+    Encrypted,
+    /// A stream uses a filter pdfrs cannot decode
+    UnsupportedFilter(String),
+    /// An image could not be decoded
+    ImageDecode(String),
+    /// An argument or input document is not usable for the requested operation
+    InvalidInput(String),
+    /// Any other failure
+    Other(anyhow::Error),
+}
+
+/// Result type used by the public API
+pub type Result<T> = std::result::Result<T, PdfError>;
+
+impl fmt::Display for PdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PdfError::Io(e) => write!(f, "I/O error: {}", e),
+            PdfError::InvalidPageRange { start, end, page_count: Some(count) } => write!(
+                f,
+                "Invalid page range: start={} end={} (document has {} pages)",
+                start, end, count
+            ),
+            PdfError::InvalidPageRange { start, end, page_count: None } => write!(
+                f,
+                "Invalid page range: start={} end={} (1-indexed, inclusive)",
+                start, end
+            ),
+            PdfError::Parse(msg) => write!(f, "Parse error: {}", msg),
+            PdfError::Encrypted => write!(f, "Document is encrypted"),
+            PdfError::UnsupportedFilter(name) => write!(f, "Unsupported stream filter: {}", name),
+            PdfError::ImageDecode(msg) => write!(f, "Image decode error: {}", msg),
+            PdfError::InvalidInput(msg) => write!(f, "{}", msg),
+            PdfError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for PdfError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PdfError::Io(e) => Some(e),
+            PdfError::Other(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for PdfError {
+    fn from(e: std::io::Error) -> Self {
+        PdfError::Io(e)
+    }
+}
+
+impl From<anyhow::Error> for PdfError {
+    fn from(e: anyhow::Error) -> Self {
+        // Keep the original kind when a PdfError passed through anyhow
+        match e.downcast::<PdfError>() {
+            Ok(pdf_error) => pdf_error,
+            Err(e) => match e.downcast::<std::io::Error>() {
+                Ok(io) => PdfError::Io(io),
+                Err(e) => PdfError::Other(e),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trips_through_anyhow() {
+        let err: anyhow::Error = PdfError::Encrypted.into();
+        assert!(matches!(PdfError::from(err), PdfError::Encrypted));
+
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "missing.pdf");
+        let err: PdfError = anyhow::Error::from(io).into();
+        assert!(matches!(err, PdfError::Io(_)));
+    }
+}
@@ -0,0 +1,170 @@
+//! Glyph widths for the standard Type 1 fonts used by the generator.
+//!
+//! Widths come from the Adobe Font Metrics (AFM) files for Helvetica,
+//! Helvetica-Bold and Courier, in 1/1000 em units. The oblique variants share
+//! the widths of their upright faces. Accented Latin-1 letters use the width
+//! of their base letter, and anything else falls back to an average width.
+//! Kerning pairs are a subset of the Helvetica AFM's, shared by all Helvetica faces.
+
+/// Helvetica widths for ASCII 32..=126
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, // ' '..'/'
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, // '0'..'9'
+    278, 278, 584, 584, 584, 556, 1015, // ':'..'@'
+    667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, // 'A'..'M'
+    722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, // 'N'..'Z'
+    278, 278, 278, 469, 556, 333, // '['..'`'
+    556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, // 'a'..'m'
+    556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, // 'n'..'z'
+    334, 260, 334, 584, // '{'..'~'
+];
+
+/// Helvetica-Bold widths for ASCII 32..=126
+const HELVETICA_BOLD_WIDTHS: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278, // ' '..'/'
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, // '0'..'9'
+    333, 333, 584, 584, 584, 611, 975, // ':'..'@'
+    722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611, 833, // 'A'..'M'
+    722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, // 'N'..'Z'
+    333, 278, 333, 584, 556, 333, // '['..'`'
+    556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889, // 'a'..'m'
+    611, 611, 611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, // 'n'..'z'
+    389, 280, 389, 584, // '{'..'~'
+];
+
+/// Width used for characters without metrics
+const DEFAULT_WIDTH: u16 = 556;
+
+/// Width of `c` in 1/1000 em for the named standard font
+pub fn char_width(font: &str, c: char) -> u16 {
+    if font.starts_with("Courier") {
+        return 600;
+    }
+    let table = if font.contains("Bold") { &HELVETICA_BOLD_WIDTHS } else { &HELVETICA_WIDTHS };
+    let ascii = |c: char| table[(c as usize) - 32];
+    match c {
+        ' '..='~' => ascii(c),
+        '\u{A0}' => ascii(' '),
+        'Æ' | '\u{152}' => 1000,
+        'æ' => 889,
+        '\u{153}' => 944,
+        'ß' => 611,
+        'ì'..='ï' => 278,
+        _ => base_letter(c).map(ascii).unwrap_or(DEFAULT_WIDTH),
+    }
+}
+
+/// Width of `text` in points at `font_size`
+pub fn text_width(font: &str, text: &str, font_size: f32) -> f32 {
+    let units: u32 = text.chars().map(|c| char_width(font, c) as u32).sum();
+    units as f32 * font_size / 1000.0
+}
+
+/// First character code covered by [`widths`]
+pub const FIRST_CHAR: u8 = 32;
+/// Last character code covered by [`widths`]
+pub const LAST_CHAR: u8 = 126;
+
+/// Widths of the characters `FIRST_CHAR..=LAST_CHAR`, for a font dictionary's `/Widths`
+pub fn widths(font: &str) -> Vec<u16> {
+    (FIRST_CHAR..=LAST_CHAR).map(|code| char_width(font, code as char)).collect()
+}
+
+/// A `/FontDescriptor` dictionary for the named standard font, with values from its AFM
+pub fn font_descriptor(font: &str) -> String {
+    let (flags, bbox, ascent, descent, cap_height, stem_v) = if font.starts_with("Courier") {
+        (33, "-23 -250 715 805", 629, -157, 562, 51)
+    } else if font.contains("Bold") {
+        (32, "-170 -228 1003 962", 718, -207, 718, 140)
+    } else {
+        (32, "-166 -225 1000 931", 718, -207, 718, 88)
+    };
+    let (flags, italic_angle) = if font.contains("Oblique") { (flags | 64, -12) } else { (flags, 0) };
+    format!(
+        "<< /Type /FontDescriptor\n/FontName /{}\n/Flags {}\n/FontBBox [{}]\n/ItalicAngle {}\n/Ascent {}\n/Descent {}\n/CapHeight {}\n/StemV {}\n>>\n",
+        font, flags, bbox, italic_angle, ascent, descent, cap_height, stem_v
+    )
+}
+
+/// Helvetica kerning pairs in 1/1000 em; negative values pull the pair together
+const HELVETICA_KERN_PAIRS: &[(char, char, i16)] = &[
+    ('A', 'C', -30), ('A', 'G', -30), ('A', 'O', -30), ('A', 'Q', -30), ('A', 'T', -120),
+    ('A', 'U', -50), ('A', 'V', -70), ('A', 'W', -50), ('A', 'Y', -100), ('A', 'u', -30),
+    ('A', 'v', -40), ('A', 'w', -40), ('A', 'y', -40),
+    ('F', ',', -150), ('F', '.', -150), ('F', 'A', -80), ('F', 'a', -50),
+    ('L', 'T', -110), ('L', 'V', -110), ('L', 'W', -70), ('L', 'Y', -140), ('L', 'y', -30),
+    ('P', ',', -180), ('P', '.', -180), ('P', 'A', -120), ('P', 'a', -40),
+    ('T', ',', -120), ('T', '.', -120), ('T', '-', -140), ('T', 'A', -120), ('T', 'O', -40),
+    ('T', 'a', -120), ('T', 'e', -120), ('T', 'o', -120), ('T', 'r', -120), ('T', 'u', -120),
+    ('T', 'w', -120), ('T', 'y', -120),
+    ('V', ',', -125), ('V', '.', -125), ('V', '-', -80), ('V', 'A', -80), ('V', 'O', -40),
+    ('V', 'a', -70), ('V', 'e', -80), ('V', 'o', -80), ('V', 'u', -70),
+    ('W', ',', -80), ('W', '.', -80), ('W', 'A', -50), ('W', 'O', -20), ('W', 'a', -40),
+    ('W', 'e', -30), ('W', 'o', -30), ('W', 'u', -30),
+    ('Y', ',', -140), ('Y', '.', -140), ('Y', '-', -140), ('Y', 'A', -110), ('Y', 'O', -85),
+    ('Y', 'a', -140), ('Y', 'e', -140), ('Y', 'o', -140), ('Y', 'u', -110),
+    ('a', 'v', -20), ('a', 'y', -30), ('e', 'v', -30), ('e', 'y', -20),
+    ('o', 'v', -15), ('o', 'w', -15), ('o', 'y', -30),
+    ('r', ',', -50), ('r', '.', -50), ('v', ',', -80), ('v', '.', -80),
+    ('w', ',', -60), ('w', '.', -60), ('y', ',', -100), ('y', '.', -100),
+];
+
+/// Kerning between `left` and `right` in 1/1000 em for the named standard font;
+/// 0 for Courier and pairs without an entry
+pub fn kern_pair(font: &str, left: char, right: char) -> i16 {
+    if !font.starts_with("Helvetica") {
+        return 0;
+    }
+    HELVETICA_KERN_PAIRS
+        .iter()
+        .find(|(l, r, _)| *l == left && *r == right)
+        .map_or(0, |(_, _, kern)| *kern)
+}
+
+/// The unaccented letter for Latin-1 accented letters
+fn base_letter(c: char) -> Option<char> {
+    let base = match c {
+        'À'..='Å' => 'A',
+        'Ç' => 'C',
+        'È'..='Ë' => 'E',
+        'Ì'..='Ï' => 'I',
+        'Ð' => 'D',
+        'Ñ' => 'N',
+        'Ò'..='Ö' | 'Ø' => 'O',
+        'Ù'..='Ü' => 'U',
+        'Ý' => 'Y',
+        'Þ' => 'P',
+        'à'..='å' => 'a',
+        'ç' => 'c',
+        'è'..='ë' => 'e',
+        'ñ' => 'n',
+        'ò'..='ö' | 'ø' => 'o',
+        'ù'..='ü' => 'u',
+        'ý' | 'ÿ' => 'y',
+        _ => return None,
+    };
+    Some(base)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_width_uses_afm_metrics() {
+        assert_eq!(text_width("Helvetica", "Hello", 1000.0), 722.0 + 556.0 + 222.0 + 222.0 + 556.0);
+        assert_eq!(text_width("Courier", "iiii", 10.0), 24.0);
+        assert!(text_width("Helvetica-Bold", "m", 12.0) > text_width("Helvetica", "m", 12.0));
+        // Accented letters are measured per character, not per UTF-8 byte
+        assert_eq!(text_width("Helvetica", "é", 12.0), text_width("Helvetica", "e", 12.0));
+    }
+
+    #[test]
+    fn test_kern_pairs() {
+        assert_eq!(kern_pair("Helvetica", 'A', 'V'), -70);
+        assert_eq!(kern_pair("Helvetica-Bold", 'T', 'o'), -120);
+        assert_eq!(kern_pair("Helvetica", 'V', 'A'), -80);
+        assert_eq!(kern_pair("Helvetica", 'a', 'b'), 0);
+        assert_eq!(kern_pair("Courier", 'A', 'V'), 0);
+    }
+}
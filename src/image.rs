@@ -1,4 +1,4 @@
-use anyhow::{anyhow, Result};
+use crate::error::{PdfError, Result};
 use std::fs;
 
 /// Detected image metadata
@@ -9,7 +9,9 @@ pub struct ImageInfo {
     pub height: u32,
     pub data: Vec<u8>,
     pub bits_per_component: u8,
-    pub color_components: u8, // 1=grayscale, 3=RGB, 4=RGBA
+    pub color_components: u8, // 1=grayscale, 3=RGB, 4=RGBA (PNG) or CMYK (JPEG)
+    /// CMYK JPEG written by Adobe software, which stores inverted values
+    pub inverted_cmyk: bool,
     /// Alternative text for accessibility (screen readers, alt text)
     pub alt_text: Option<String>,
 }
@@ -21,10 +23,21 @@ pub enum ImageFormat {
     Bmp,
 }
 
+/// How decoded PNG/BMP samples are compressed when embedded.
+/// JPEG sources are always embedded as-is.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ImageEncoding {
+    /// Lossless, best for line art and screenshots
+    #[default]
+    Flate,
+    /// Re-encode as DCTDecode JPEG at the given quality (1-100), best for photos
+    Jpeg(u8),
+}
+
 /// Detect format from raw bytes
 pub fn detect_image_format(data: &[u8]) -> Result<ImageFormat> {
     if data.len() < 4 {
-        return Err(anyhow!("Image data too short"));
+        return Err(PdfError::ImageDecode("Image data too short".into()));
     }
     if data[0] == 0xFF && data[1] == 0xD8 && data[2] == 0xFF {
         Ok(ImageFormat::Jpeg)
@@ -33,7 +46,7 @@ pub fn detect_image_format(data: &[u8]) -> Result<ImageFormat> {
     } else if data[0] == 0x42 && data[1] == 0x4D {
         Ok(ImageFormat::Bmp)
     } else {
-        Err(anyhow!("Unsupported image format"))
+        Err(PdfError::ImageDecode("Unsupported image format".into()))
     }
 }
 
@@ -46,10 +59,12 @@ pub fn load_image(path: &str) -> Result<ImageInfo> {
 pub fn load_image_with_alt_text(path: &str, alt_text: Option<String>) -> Result<ImageInfo> {
     let data = fs::read(path)?;
     let format = detect_image_format(&data)?;
+    let mut inverted_cmyk = false;
     let (width, height, bits_per_comp, color_comp, pixel_data) = match format {
         ImageFormat::Jpeg => {
-            let (w, h) = parse_jpeg_dimensions(&data)?;
-            (w, h, 8, 3, data)
+            let header = parse_jpeg_header(&data)?;
+            inverted_cmyk = header.components == 4 && header.adobe;
+            (header.width, header.height, 8, header.components, data)
         }
         ImageFormat::Png => parse_png_full(&data)?,
         ImageFormat::Bmp => parse_bmp_full(&data)?,
@@ -61,11 +76,66 @@ pub fn load_image_with_alt_text(path: &str, alt_text: Option<String>) -> Result<
         data: pixel_data,
         bits_per_component: bits_per_comp,
         color_components: color_comp,
+        inverted_cmyk,
         alt_text,
     })
 }
 
+/// Load an image, downscaling it so neither side exceeds `max_pixels`
+pub fn load_image_with_max_dimension(path: &str, max_pixels: u32) -> Result<ImageInfo> {
+    load_image(path)?.downscale_to_fit(max_pixels, max_pixels)
+}
+
+/// Load an image to be shown at `display_width` x `display_height` points,
+/// downscaling it to at most `dpi` pixels per inch. A display size of `0`
+/// follows the image's aspect ratio, as in [`resolve_display_size`].
+pub fn load_image_for_display(path: &str, display_width: f32, display_height: f32, dpi: f32) -> Result<ImageInfo> {
+    let info = load_image(path)?;
+    let (w, h) = resolve_display_size(info.width, info.height, display_width, display_height);
+    let pixels = |points: f32| (points / 72.0 * dpi).round().max(1.0) as u32;
+    info.downscale_to_fit(pixels(w), pixels(h))
+}
+
 impl ImageInfo {
+    /// Downscale with an area-averaging filter so the image fits within
+    /// `max_width` x `max_height` pixels, keeping its aspect ratio. Images
+    /// that already fit, and those with packed sub-byte or CMYK samples,
+    /// are returned unchanged.
+    pub fn downscale_to_fit(self, max_width: u32, max_height: u32) -> Result<ImageInfo> {
+        let (w, h) = scale_to_fit(self.width, self.height, max_width as f32, max_height as f32);
+        let (new_width, new_height) = ((w.round() as u32).max(1), (h.round() as u32).max(1));
+        if new_width >= self.width && new_height >= self.height {
+            return Ok(self);
+        }
+        let (samples, bits_per_component) = match self.format {
+            ImageFormat::Jpeg => {
+                let mut decoder = jpeg_decoder::Decoder::new(self.data.as_slice());
+                let pixels = decoder
+                    .decode()
+                    .map_err(|e| PdfError::ImageDecode(format!("JPEG decoding failed: {}", e)))?;
+                if self.color_components == 4 {
+                    log::warn!("[image] CMYK JPEG {}x{} kept at full resolution", self.width, self.height);
+                    return Ok(self);
+                }
+                (pixels, 8)
+            }
+            _ if matches!(self.bits_per_component, 8 | 16) => (self.data.clone(), self.bits_per_component),
+            _ => return Ok(self),
+        };
+        let data = area_average(
+            &samples,
+            (self.width, self.height),
+            (new_width, new_height),
+            self.color_components as usize,
+            bits_per_component as usize / 8,
+        );
+        let data = match self.format {
+            ImageFormat::Jpeg => encode_jpeg(&data, new_width, new_height, self.color_components, 90)?,
+            _ => data,
+        };
+        Ok(ImageInfo { width: new_width, height: new_height, data, bits_per_component, ..self })
+    }
+
     /// Set alternative text for accessibility
     pub fn with_alt_text(mut self, alt_text: String) -> Self {
         self.alt_text = Some(alt_text);
@@ -78,11 +148,50 @@ impl ImageInfo {
     }
 }
 
+/// Resample interleaved big-endian samples by averaging the source pixels
+/// each destination pixel covers
+fn area_average(samples: &[u8], from: (u32, u32), to: (u32, u32), components: usize, bytes_per_sample: usize) -> Vec<u8> {
+    let (src_w, src_h) = (from.0 as usize, from.1 as usize);
+    let (dst_w, dst_h) = (to.0 as usize, to.1 as usize);
+    let sample = |i: usize| match bytes_per_sample {
+        2 => u16::from_be_bytes([samples[i], samples[i + 1]]) as u64,
+        _ => samples[i] as u64,
+    };
+    let mut out = Vec::with_capacity(dst_w * dst_h * components * bytes_per_sample);
+    let mut sums = vec![0u64; components];
+    for dy in 0..dst_h {
+        let (y0, y1) = (dy * src_h / dst_h, ((dy + 1) * src_h / dst_h).max(dy * src_h / dst_h + 1));
+        for dx in 0..dst_w {
+            let (x0, x1) = (dx * src_w / dst_w, ((dx + 1) * src_w / dst_w).max(dx * src_w / dst_w + 1));
+            sums.iter_mut().for_each(|s| *s = 0);
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let base = (y * src_w + x) * components * bytes_per_sample;
+                    for (c, sum) in sums.iter_mut().enumerate() {
+                        *sum += sample(base + c * bytes_per_sample);
+                    }
+                }
+            }
+            let count = ((y1 - y0) * (x1 - x0)) as u64;
+            for sum in &sums {
+                let value = (sum + count / 2) / count;
+                match bytes_per_sample {
+                    2 => out.extend_from_slice(&(value as u16).to_be_bytes()),
+                    _ => out.push(value as u8),
+                }
+            }
+        }
+    }
+    out
+}
+
 /// Parse PNG IHDR chunk for width, height, bit depth, and color type
-/// Returns (width, height, bits_per_component, color_components, decompressed_image_data)
+/// Returns (width, height, bits_per_component, color_components, unfiltered_samples)
+/// with any alpha channel removed
 fn parse_png_full(data: &[u8]) -> Result<(u32, u32, u8, u8, Vec<u8>)> {
-    if data.len() < 24 {
-        return Err(anyhow!("PNG data too short"));
+    // Signature, chunk header and all 13 bytes of IHDR
+    if data.len() < 29 {
+        return Err(PdfError::ImageDecode("PNG data too short".into()));
     }
 
     // PNG header: 8 bytes
@@ -101,24 +210,36 @@ fn parse_png_full(data: &[u8]) -> Result<(u32, u32, u8, u8, Vec<u8>)> {
     let (color_components, has_alpha) = match color_type {
         0 => (1, false),
         2 => (3, false),
-        3 => return Err(anyhow!("Paletted PNG (color type 3) not yet supported")),
+        3 => return Err(PdfError::ImageDecode("Paletted PNG (color type 3) not yet supported".into())),
         4 => (2, true),
         6 => (4, true),
-        _ => return Err(anyhow!("Invalid PNG color type: {}", color_type)),
+        _ => return Err(PdfError::ImageDecode(format!("Invalid PNG color type: {}", color_type))),
     };
+    // PNG only allows whole-byte samples with alpha
+    if has_alpha && bit_depth != 8 && bit_depth != 16 {
+        return Err(PdfError::ImageDecode(format!(
+            "Invalid bit depth {} for PNG color type {}",
+            bit_depth, color_type
+        )));
+    }
+
+    if data[28] != 0 {
+        return Err(PdfError::ImageDecode("Interlaced PNGs are not supported".into()));
+    }
 
-    // Collect all IDAT chunks and decompress
+    // Collect all IDAT chunks, decompress and undo the per-row filters
     let idat_data = extract_png_idat_chunks(data)?;
     let decompressed = decompress_png_data(&idat_data)?;
+    let samples = unfilter_png_rows(&decompressed, width, height, color_components, bit_depth)?;
 
     // Remove alpha channel if present (PDF doesn't support alpha in basic images)
-    let final_data = if has_alpha {
-        remove_alpha_channel(&decompressed, color_components, width, height)?
+    if has_alpha {
+        let bytes_per_sample = (bit_depth / 8) as usize;
+        let final_data = remove_alpha_channel(&samples, color_components, bytes_per_sample);
+        Ok((width, height, bit_depth, color_components - 1, final_data))
     } else {
-        decompressed
-    };
-
-    Ok((width, height, bit_depth, color_components, final_data))
+        Ok((width, height, bit_depth, color_components, samples))
+    }
 }
 
 /// Extract all IDAT chunk data from PNG
@@ -130,14 +251,13 @@ fn extract_png_idat_chunks(data: &[u8]) -> Result<Vec<u8>> {
         let chunk_length = u32::from_be_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]) as usize;
         let chunk_type = &data[i + 4..i + 8];
         let chunk_data_start = i + 8;
-        let chunk_data_end = chunk_data_start + chunk_length;
-
-        if chunk_data_end > data.len() {
-            return Err(anyhow!("PNG chunk data extends beyond file"));
-        }
+        let chunk_data_end = match chunk_data_start.checked_add(chunk_length) {
+            Some(end) if end <= data.len() => end,
+            _ => return Err(PdfError::ImageDecode("PNG chunk data extends beyond file".into())),
+        };
 
         let chunk_type_str = std::str::from_utf8(chunk_type)
-            .map_err(|_| anyhow!("Invalid PNG chunk type"))?;
+            .map_err(|_| PdfError::ImageDecode("Invalid PNG chunk type".into()))?;
 
         if chunk_type_str == "IDAT" {
             idat_data.extend_from_slice(&data[chunk_data_start..chunk_data_end]);
@@ -150,68 +270,91 @@ fn extract_png_idat_chunks(data: &[u8]) -> Result<Vec<u8>> {
     }
 
     if idat_data.is_empty() {
-        return Err(anyhow!("No IDAT chunks found in PNG"));
+        return Err(PdfError::ImageDecode("No IDAT chunks found in PNG".into()));
     }
 
     Ok(idat_data)
 }
 
-/// Decompress PNG IDAT data using deflate
+/// Decompress PNG IDAT data (zlib)
 fn decompress_png_data(compressed: &[u8]) -> Result<Vec<u8>> {
-    // PNG uses zlib compression (deflate with wrapper)
-    // For now, use the compression module's decompress function
-    // In a production implementation, you'd use flate2 with proper zlib handling
-    crate::compression::decompress_deflate(compressed)
+    let mut decompressed = Vec::new();
+    std::io::Read::read_to_end(&mut flate2::read::ZlibDecoder::new(compressed), &mut decompressed)
+        .map_err(|e| PdfError::ImageDecode(format!("Invalid PNG image data: {}", e)))?;
+    Ok(decompressed)
 }
 
-/// Remove alpha channel from image data
-fn remove_alpha_channel(data: &[u8], components: u8, width: u32, height: u32) -> Result<Vec<u8>> {
-    let components = components as usize;
-    let bytes_per_pixel = components;
-    let _stride = width as usize * bytes_per_pixel + 1; // +1 for filter byte per row
-    let row_size = width as usize * components;
-
-    let mut result = Vec::new();
-    let mut i = 0;
-
-    for _ in 0..height {
-        if i + 1 > data.len() {
-            return Err(anyhow!("PNG data truncated"));
-        }
-        let filter = data[i];
-        i += 1;
-
-        if i + row_size > data.len() {
-            return Err(anyhow!("PNG row data truncated"));
-        }
-
-        // Copy filter byte
-        result.push(filter);
+/// Undo PNG row filters, returning packed samples without filter bytes
+fn unfilter_png_rows(data: &[u8], width: u32, height: u32, components: u8, bit_depth: u8) -> Result<Vec<u8>> {
+    let too_large = || PdfError::ImageDecode(format!("PNG image of {}x{} pixels is too large", width, height));
+    let bits_per_pixel = (components as usize).checked_mul(bit_depth as usize).ok_or_else(too_large)?;
+    let bpp = bits_per_pixel.div_ceil(8);
+    let row_len = (width as usize).checked_mul(bits_per_pixel).ok_or_else(too_large)?.div_ceil(8);
+    let out_len = row_len.checked_mul(height as usize).ok_or_else(too_large)?;
+    let filtered_len = (row_len + 1).checked_mul(height as usize).ok_or_else(too_large)?;
+    if data.len() < filtered_len {
+        return Err(PdfError::ImageDecode("PNG row data truncated".into()));
+    }
 
-        // Copy pixel data, skipping alpha
-        let mut pixel_start = i;
-        for _ in 0..width as usize {
-            if pixel_start + components > data.len() {
-                return Err(anyhow!("PNG pixel data truncated"));
-            }
-            // Copy RGB components, skip alpha
-            for c in 0..3 {
-                if c < components - 1 {
-                    // Keep only RGB, drop alpha
-                    result.push(data[pixel_start + c]);
-                }
-            }
-            pixel_start += components;
+    let mut out = vec![0u8; out_len];
+    for y in 0..height as usize {
+        let filter = data[y * (row_len + 1)];
+        let src = &data[y * (row_len + 1) + 1..(y + 1) * (row_len + 1)];
+        let (done, rest) = out.split_at_mut(y * row_len);
+        let prev = if y > 0 { &done[(y - 1) * row_len..] } else { &[][..] };
+        let row = &mut rest[..row_len];
+        for x in 0..row_len {
+            let a = if x >= bpp { row[x - bpp] } else { 0 };
+            let b = prev.get(x).copied().unwrap_or(0);
+            let c = if x >= bpp { prev.get(x - bpp).copied().unwrap_or(0) } else { 0 };
+            let predictor = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(PdfError::ImageDecode(format!("Invalid PNG filter type: {}", filter))),
+            };
+            row[x] = src[x].wrapping_add(predictor);
         }
+    }
+    Ok(out)
+}
 
-        i += row_size;
+/// The PNG Paeth predictor
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
     }
+}
+
+/// Drop the trailing alpha sample from each pixel
+fn remove_alpha_channel(data: &[u8], components: u8, bytes_per_sample: usize) -> Vec<u8> {
+    let pixel_len = components as usize * bytes_per_sample;
+    let keep = pixel_len - bytes_per_sample;
+    data.chunks_exact(pixel_len).flat_map(|pixel| &pixel[..keep]).copied().collect()
+}
 
-    Ok(result)
+/// Frame information from a JPEG's SOF and APP14 markers
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct JpegHeader {
+    width: u32,
+    height: u32,
+    /// 1 = grayscale, 3 = YCbCr/RGB, 4 = CMYK/YCCK
+    components: u8,
+    /// An Adobe APP14 marker precedes the frame
+    adobe: bool,
 }
 
-/// Parse JPEG SOF marker to get width and height
-fn parse_jpeg_dimensions(data: &[u8]) -> Result<(u32, u32)> {
+/// Parse the JPEG SOF marker (and any Adobe APP14 marker before it)
+fn parse_jpeg_header(data: &[u8]) -> Result<JpegHeader> {
+    let mut adobe = false;
     let mut i = 2; // skip FF D8
     while i + 1 < data.len() {
         if data[i] != 0xFF {
@@ -221,15 +364,25 @@ fn parse_jpeg_dimensions(data: &[u8]) -> Result<(u32, u32)> {
         let marker = data[i + 1];
         i += 2;
 
-        // SOF0..SOF15 (except SOF4 = DHT, SOF8 = JPG)
-        // Common SOF markers: C0, C1, C2
-        if marker == 0xC0 || marker == 0xC1 || marker == 0xC2 {
-            if i + 7 > data.len() {
-                return Err(anyhow!("JPEG SOF marker truncated"));
+        // SOF0..SOF15 except C4 (DHT), C8 (JPG) and CC (DAC)
+        if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            if i + 8 > data.len() {
+                return Err(PdfError::ImageDecode("JPEG SOF marker truncated".into()));
             }
             let height = ((data[i + 3] as u32) << 8) | (data[i + 4] as u32);
             let width = ((data[i + 5] as u32) << 8) | (data[i + 6] as u32);
-            return Ok((width, height));
+            let components = data[i + 7];
+            if !matches!(components, 1 | 3 | 4) {
+                return Err(PdfError::ImageDecode(format!(
+                    "Unsupported JPEG component count: {}",
+                    components
+                )));
+            }
+            return Ok(JpegHeader { width, height, components, adobe });
+        }
+
+        if marker == 0xEE && data.get(i + 2..i + 7) == Some(b"Adobe".as_slice()) {
+            adobe = true;
         }
 
         // Skip non-SOF markers by reading their length
@@ -239,14 +392,14 @@ fn parse_jpeg_dimensions(data: &[u8]) -> Result<(u32, u32)> {
         let seg_len = ((data[i] as usize) << 8) | (data[i + 1] as usize);
         i += seg_len;
     }
-    Err(anyhow!("Could not find JPEG SOF marker"))
+    Err(PdfError::ImageDecode("Could not find JPEG SOF marker".into()))
 }
 
 /// Parse PNG IHDR chunk for width and height
 fn parse_png_dimensions(data: &[u8]) -> Result<(u32, u32)> {
     // PNG header: 8 bytes, then IHDR chunk: 4-byte length, 4-byte type, then data
     if data.len() < 24 {
-        return Err(anyhow!("PNG data too short"));
+        return Err(PdfError::ImageDecode("PNG data too short".into()));
     }
     // IHDR starts at offset 8 (after signature)
     // bytes 8..12 = chunk length, 12..16 = "IHDR", 16..20 = width, 20..24 = height
@@ -258,7 +411,7 @@ fn parse_png_dimensions(data: &[u8]) -> Result<(u32, u32)> {
 /// Parse BMP header for width and height
 fn parse_bmp_dimensions(data: &[u8]) -> Result<(u32, u32)> {
     if data.len() < 26 {
-        return Err(anyhow!("BMP data too short"));
+        return Err(PdfError::ImageDecode("BMP data too short".into()));
     }
     // BMP info header starts at offset 14; width at +4, height at +8 (little-endian i32)
     let width = u32::from_le_bytes([data[18], data[19], data[20], data[21]]);
@@ -271,7 +424,7 @@ fn parse_bmp_dimensions(data: &[u8]) -> Result<(u32, u32)> {
 /// Returns (width, height, bits_per_component, color_components, pixel_data)
 fn parse_bmp_full(data: &[u8]) -> Result<(u32, u32, u8, u8, Vec<u8>)> {
     if data.len() < 54 {
-        return Err(anyhow!("BMP data too short for header"));
+        return Err(PdfError::ImageDecode("BMP data too short for header".into()));
     }
 
     // BMP file header (14 bytes) + info header (40 bytes for BITMAPINFOHEADER)
@@ -285,7 +438,7 @@ fn parse_bmp_full(data: &[u8]) -> Result<(u32, u32, u8, u8, Vec<u8>)> {
     let (bytes_per_pixel, _has_alpha) = match bits_per_pixel {
         24 => (3, false),
         32 => (4, true),
-        _ => return Err(anyhow!("Unsupported BMP bit depth: {} (only 24/32 supported)", bits_per_pixel)),
+        _ => return Err(PdfError::ImageDecode(format!("Unsupported BMP bit depth: {} (only 24/32 supported)", bits_per_pixel))),
     };
 
     // Calculate row size (BMP rows are padded to 4-byte boundaries)
@@ -293,7 +446,7 @@ fn parse_bmp_full(data: &[u8]) -> Result<(u32, u32, u8, u8, Vec<u8>)> {
     let pixel_data_offset = u32::from_le_bytes([data[10], data[11], data[12], data[13]]) as usize;
 
     if pixel_data_offset as usize + row_size * height as usize > data.len() {
-        return Err(anyhow!("BMP pixel data truncated"));
+        return Err(PdfError::ImageDecode("BMP pixel data truncated".into()));
     }
 
     // Extract pixel data, flipping vertically (BMP stores bottom-to-top)
@@ -325,6 +478,18 @@ pub fn scale_to_fit(width: u32, height: u32, max_width: f32, max_height: f32) ->
     (w * scale, h * scale)
 }
 
+/// Display size of a `width` x `height` pixel image. A size of `0` for one
+/// dimension follows the image's aspect ratio; `0` for both uses one point per pixel.
+pub fn resolve_display_size(width: u32, height: u32, display_width: f32, display_height: f32) -> (f32, f32) {
+    let (w, h) = (width.max(1) as f32, height.max(1) as f32);
+    match (display_width > 0.0, display_height > 0.0) {
+        (true, true) => (display_width, display_height),
+        (true, false) => (display_width, display_width * h / w),
+        (false, true) => (display_height * w / h, display_height),
+        (false, false) => (w, h),
+    }
+}
+
 /// Create a PDF image XObject stream for JPEG data (DCTDecode)
 pub fn create_jpeg_image_object(
     generator: &mut crate::pdf_generator::PdfGenerator,
@@ -332,25 +497,49 @@ pub fn create_jpeg_image_object(
     width: u32,
     height: u32,
 ) -> u32 {
+    create_jpeg_image_object_with_color(generator, jpeg_data, width, height, 3, false)
+}
+
+/// Create a JPEG image XObject with the color space matching its component count.
+/// `inverted_cmyk` adds the `/Decode` array Adobe CMYK JPEGs need.
+pub fn create_jpeg_image_object_with_color(
+    generator: &mut crate::pdf_generator::PdfGenerator,
+    jpeg_data: Vec<u8>,
+    width: u32,
+    height: u32,
+    color_components: u8,
+    inverted_cmyk: bool,
+) -> u32 {
+    let color_space = match color_components {
+        1 => "/DeviceGray",
+        4 => "/DeviceCMYK",
+        _ => "/DeviceRGB",
+    };
+    let decode = if color_components == 4 && inverted_cmyk {
+        "/Decode [1 0 1 0 1 0 1 0]\n"
+    } else {
+        ""
+    };
     let image_dict = format!(
         "<< /Type /XObject\n\
          /Subtype /Image\n\
          /Width {}\n\
          /Height {}\n\
          /BitsPerComponent 8\n\
-         /ColorSpace /DeviceRGB\n\
+         /ColorSpace {}\n\
+         {}\
          /Filter /DCTDecode\n\
          /Length {}\n\
          >>\n",
-        width, height, jpeg_data.len()
+        width, height, color_space, decode, jpeg_data.len()
     );
     generator.add_stream_object(image_dict, jpeg_data)
 }
 
-/// Create a PDF image XObject stream for PNG data (FlateDecode)
+/// Create a PDF image XObject stream for decoded PNG samples (FlateDecode)
 pub fn create_png_image_object(
     generator: &mut crate::pdf_generator::PdfGenerator,
-    png_data: Vec<u8>,
+    samples: Vec<u8>,
     width: u32,
     height: u32,
     bits_per_component: u8,
@@ -363,21 +552,33 @@ pub fn create_png_image_object(
         _ => "/DeviceRGB", // Fallback
     };
 
-    let image_dict = format!(
-        "<< /Type /XObject\n\
+    let entries = format!(
+        "/Type /XObject\n\
          /Subtype /Image\n\
          /Width {}\n\
          /Height {}\n\
          /BitsPerComponent {}\n\
-         /ColorSpace {}\n\
-         /Filter /FlateDecode\n\
-         /DecodeParms << /Predictor 15 /Colors {} /BitsPerComponent {} /Columns {} >>\n\
-         /Length {}\n\
-         >>\n",
-        width, height, bits_per_component, color_space,
-        color_components, bits_per_component, width, png_data.len()
+         /ColorSpace {}\n",
+        width, height, bits_per_component, color_space
     );
-    generator.add_stream_object(image_dict, png_data)
+    generator.add_compressed_stream_object(&entries, &samples)
+}
+
+/// Encode 8-bit gray or RGB samples as a baseline JPEG
+fn encode_jpeg(samples: &[u8], width: u32, height: u32, color_components: u8, quality: u8) -> Result<Vec<u8>> {
+    let color_type = match color_components {
+        1 => jpeg_encoder::ColorType::Luma,
+        3 => jpeg_encoder::ColorType::Rgb,
+        n => return Err(PdfError::ImageDecode(format!("Cannot JPEG-encode {} color components", n))),
+    };
+    let (Ok(w), Ok(h)) = (u16::try_from(width), u16::try_from(height)) else {
+        return Err(PdfError::ImageDecode("Image too large for JPEG encoding".into()));
+    };
+    let mut jpeg = Vec::new();
+    jpeg_encoder::Encoder::new(&mut jpeg, quality.clamp(1, 100))
+        .encode(samples, w, h, color_type)
+        .map_err(|e| PdfError::ImageDecode(format!("JPEG encoding failed: {}", e)))?;
+    Ok(jpeg)
 }
 
 /// Create a PDF image XObject stream for BMP data (raw, no filter)
@@ -406,13 +607,45 @@ pub fn create_image_object(
     generator: &mut crate::pdf_generator::PdfGenerator,
     image_info: ImageInfo,
 ) -> Result<u32> {
+    create_image_object_with_encoding(generator, image_info, ImageEncoding::Flate)
+}
+
+/// Like [`create_image_object`], choosing how PNG/BMP samples are compressed.
+/// Images that cannot be JPEG-encoded (16-bit samples) stay lossless.
+pub fn create_image_object_with_encoding(
+    generator: &mut crate::pdf_generator::PdfGenerator,
+    image_info: ImageInfo,
+    encoding: ImageEncoding,
+) -> Result<u32> {
+    if let ImageEncoding::Jpeg(quality) = encoding
+        && image_info.format != ImageFormat::Jpeg
+        && image_info.bits_per_component == 8
+    {
+        let jpeg = encode_jpeg(
+            &image_info.data,
+            image_info.width,
+            image_info.height,
+            image_info.color_components,
+            quality,
+        )?;
+        return Ok(create_jpeg_image_object_with_color(
+            generator,
+            jpeg,
+            image_info.width,
+            image_info.height,
+            image_info.color_components,
+            false,
+        ));
+    }
     match image_info.format {
         ImageFormat::Jpeg => {
-            Ok(create_jpeg_image_object(
+            Ok(create_jpeg_image_object_with_color(
                 generator,
                 image_info.data,
                 image_info.width,
                 image_info.height,
+                image_info.color_components,
+                image_info.inverted_cmyk,
             ))
         }
         ImageFormat::Png => {
@@ -454,7 +687,8 @@ pub fn create_image_content_stream(
     content
 }
 
-/// High-level: create a single-page PDF containing just the image
+/// High-level: create a single-page PDF containing just the image.
+/// Pass `0` for one display dimension to keep the image's aspect ratio.
 pub fn add_image_to_pdf(
     output_pdf: &str,
     image_path: &str,
@@ -471,6 +705,8 @@ pub fn add_image_to_pdf(
     let image_id = create_image_object(&mut generator, info.clone())?;
 
     // 2. Content stream that draws the image
+    let (display_width, display_height) =
+        resolve_display_size(info.width, info.height, display_width, display_height);
     let content = create_image_content_stream(x, y, display_width, display_height, "Im1");
     let content_id = generator.add_stream_object(
         format!("<< /Length {} >>\n", content.len()),
@@ -565,9 +801,34 @@ mod tests {
         // pad
         data.extend_from_slice(&[0; 20]);
 
-        let (w, h) = parse_jpeg_dimensions(&data).unwrap();
-        assert_eq!(w, 512);
-        assert_eq!(h, 256);
+        let header = parse_jpeg_header(&data).unwrap();
+        assert_eq!(header.width, 512);
+        assert_eq!(header.height, 256);
+        assert_eq!(header.components, 3);
+    }
+
+    #[test]
+    fn test_cmyk_jpeg_color_space() {
+        let mut data = vec![0xFF, 0xD8];
+        // APP14 "Adobe" marker, transform 2 (YCCK)
+        data.extend_from_slice(&[0xFF, 0xEE, 0x00, 0x0E]);
+        data.extend_from_slice(b"Adobe");
+        data.extend_from_slice(&[0x00, 0x64, 0x00, 0x00, 0x00, 0x00, 0x02]);
+        // SOF2 (progressive) with 4 components
+        data.extend_from_slice(&[0xFF, 0xC2, 0x00, 0x14, 0x08, 0x00, 0x10, 0x00, 0x20, 0x04]);
+        data.extend_from_slice(&[0; 24]);
+        let path = std::env::temp_dir().join("pdfrs_cmyk.jpg");
+        fs::write(&path, &data).unwrap();
+
+        let info = load_image(path.to_str().unwrap()).unwrap();
+        assert_eq!((info.width, info.height, info.color_components), (32, 16, 4));
+        assert!(info.inverted_cmyk);
+
+        let mut generator = crate::pdf_generator::PdfGenerator::new();
+        create_image_object(&mut generator, info).unwrap();
+        let pdf = String::from_utf8_lossy(&generator.generate()).to_string();
+        assert!(pdf.contains("/ColorSpace /DeviceCMYK"));
+        assert!(pdf.contains("/Decode [1 0 1 0 1 0 1 0]"));
     }
 
     #[test]
@@ -584,6 +845,146 @@ mod tests {
         assert_eq!(h, 480);
     }
 
+    #[test]
+    fn test_malformed_png_header_is_an_error() {
+        let png = encode_test_png(1, 1, &[255, 0, 0]);
+        assert!(matches!(parse_png_full(&png[..26]), Err(PdfError::ImageDecode(_))));
+
+        // Alpha with sub-byte samples is not valid PNG
+        let mut alpha = png.clone();
+        alpha[24] = 4;
+        alpha[25] = 6;
+        assert!(matches!(parse_png_full(&alpha), Err(PdfError::ImageDecode(_))));
+
+        // Dimensions whose sample buffer would overflow are rejected, not wrapped
+        let mut huge = png.clone();
+        huge[16..24].copy_from_slice(&[0xFF; 8]);
+        huge[24] = 16;
+        huge[25] = 6;
+        match parse_png_full(&huge) {
+            Err(PdfError::ImageDecode(msg)) => assert!(msg.contains("too large"), "{}", msg),
+            other => panic!("expected a decode error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    /// Encode RGB samples as an unfiltered PNG (CRCs are not checked by the parser)
+    fn encode_test_png(width: u32, height: u32, rgb: &[u8]) -> Vec<u8> {
+        let mut raw = Vec::new();
+        for row in rgb.chunks(width as usize * 3) {
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, &raw).unwrap();
+        let idat = encoder.finish().unwrap();
+
+        let mut png = vec![0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+        let mut chunk = |kind: &[u8], body: &[u8]| {
+            png.extend_from_slice(&(body.len() as u32).to_be_bytes());
+            png.extend_from_slice(kind);
+            png.extend_from_slice(body);
+            png.extend_from_slice(&[0; 4]);
+        };
+        let mut ihdr = Vec::new();
+        ihdr.extend_from_slice(&width.to_be_bytes());
+        ihdr.extend_from_slice(&height.to_be_bytes());
+        ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+        chunk(b"IHDR", &ihdr);
+        chunk(b"IDAT", &idat);
+        chunk(b"IEND", &[]);
+        png
+    }
+
+    #[test]
+    fn test_load_image_for_display_downscales_to_dpi() {
+        let (width, height) = (1200u32, 800u32);
+        let rgb: Vec<u8> = (0..width * height).flat_map(|i| [(i % width) as u8, (i / width) as u8, 200]).collect();
+        let path = std::env::temp_dir().join("pdfrs_large.png");
+        fs::write(&path, encode_test_png(width, height, &rgb)).unwrap();
+
+        // 2 inches (144pt) at 150 DPI is 300 pixels wide
+        let info = load_image_for_display(path.to_str().unwrap(), 144.0, 0.0, 150.0).unwrap();
+        assert_eq!((info.width, info.height), (300, 200));
+        assert_eq!(info.data.len(), 300 * 200 * 3);
+        // Each output pixel averages a 4x4 block: columns 0..4 average to 1.5
+        assert_eq!(&info.data[..3], &[2, 2, 200]);
+
+        let capped = load_image_with_max_dimension(path.to_str().unwrap(), 600).unwrap();
+        assert_eq!((capped.width, capped.height), (600, 400));
+        let small = load_image_with_max_dimension(path.to_str().unwrap(), 5000).unwrap();
+        assert_eq!((small.width, small.height), (width, height));
+    }
+
+    #[test]
+    fn test_jpeg_embedded_without_reencoding() {
+        let dir = std::env::temp_dir();
+        let path = dir.join("pdfrs_passthrough.jpg");
+        let out = dir.join("pdfrs_passthrough.pdf");
+        let rgb: Vec<u8> = (0..32u32 * 16).flat_map(|i| [(i * 7) as u8, (i * 3) as u8, 90]).collect();
+        jpeg_encoder::Encoder::new_file(&path, 75)
+            .unwrap()
+            .encode(&rgb, 32, 16, jpeg_encoder::ColorType::Rgb)
+            .unwrap();
+        let source = fs::read(&path).unwrap();
+
+        let info = load_image(path.to_str().unwrap()).unwrap();
+        assert_eq!(info.data, source);
+        // Asking for JPEG encoding must not decode and re-encode a JPEG source
+        let mut generator = crate::pdf_generator::PdfGenerator::new();
+        create_image_object_with_encoding(&mut generator, info, ImageEncoding::Jpeg(50)).unwrap();
+        assert!(crate::pdf::find_bytes(&generator.generate(), &source).is_some());
+
+        let images = [(path.to_str().unwrap().to_string(), 0.0, 0.0, 32.0, 16.0)];
+        crate::pdf_ops::create_pdf_with_images(out.to_str().unwrap(), &images).unwrap();
+        let doc = crate::pdf::PdfDocument::load_from_file(out.to_str().unwrap()).unwrap();
+        let (dictionary, data) = doc
+            .objects
+            .values()
+            .find_map(|object| match object {
+                crate::pdf::PdfObject::Stream { dictionary, data }
+                    if dictionary.get("Subtype").and_then(|s| s.as_name()) == Some("Image") =>
+                {
+                    Some((dictionary, data))
+                }
+                _ => None,
+            })
+            .expect("image stream");
+        assert_eq!(dictionary.get("Filter").and_then(|f| f.as_name()), Some("DCTDecode"));
+        assert_eq!(data, &source);
+    }
+
+    #[test]
+    fn test_png_jpeg_encoding_is_smaller() {
+        // A noisy gradient compresses poorly without loss, like a photo
+        let (width, height) = (256u32, 256u32);
+        let mut seed = 12345u32;
+        let mut rgb = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                let noise = (seed >> 24) as u8 / 8;
+                rgb.extend_from_slice(&[x as u8 / 2 + noise, y as u8 / 2 + noise, 128 + noise]);
+            }
+        }
+        let path = std::env::temp_dir().join("pdfrs_photo.png");
+        fs::write(&path, encode_test_png(width, height, &rgb)).unwrap();
+        let info = load_image(path.to_str().unwrap()).unwrap();
+        assert_eq!(info.data, rgb);
+
+        let mut flate = crate::pdf_generator::PdfGenerator::new();
+        create_image_object_with_encoding(&mut flate, info.clone(), ImageEncoding::Flate).unwrap();
+        let mut jpeg = crate::pdf_generator::PdfGenerator::new();
+        create_image_object_with_encoding(&mut jpeg, info, ImageEncoding::Jpeg(70)).unwrap();
+
+        let flate_obj = &flate.objects[0];
+        let jpeg_obj = &jpeg.objects[0];
+        assert!(flate_obj.content.contains("/FlateDecode"));
+        assert!(jpeg_obj.content.contains("/DCTDecode"));
+        let flate_len = flate_obj.stream_data.as_ref().unwrap().len();
+        let jpeg_len = jpeg_obj.stream_data.as_ref().unwrap().len();
+        assert!(jpeg_len * 2 < flate_len, "jpeg {} vs flate {}", jpeg_len, flate_len);
+    }
+
     #[test]
     fn test_create_image_content_stream() {
         let cs = create_image_content_stream(100.0, 200.0, 300.0, 400.0, "Im1");
@@ -656,7 +1057,7 @@ fn get_png_color_components(color_type: u8) -> Option<(u8, bool)> {
 fn validate_bmp_bit_depth(bits_per_pixel: u16) -> Result<()> {
     match bits_per_pixel {
         24 | 32 => Ok(()),
-        _ => Err(anyhow!("Unsupported BMP bit depth: {}", bits_per_pixel)),
+        _ => Err(PdfError::ImageDecode(format!("Unsupported BMP bit depth: {}", bits_per_pixel))),
     }
 }
 
@@ -8,7 +8,7 @@
 //! - **PDF Manipulation**: Merge, split, rotate, and reorder pages
 //! - **Image Support**: Embed JPEG, PNG, and BMP images in PDFs
 //! - **Annotations**: Add text, link, and highlight annotations
-//! - **Forms**: Create interactive PDF forms with text fields, checkboxes, radio buttons, and dropdowns
+//! - **Forms**: Create interactive PDF forms with text fields, checkboxes, radio buttons, dropdowns, and signature fields
 //! - **Watermarks**: Add text or image watermarks to PDFs
 //! - **Metadata**: Manage document metadata including custom fields
 //! - **Security**: Add password protection and permissions to PDFs
@@ -39,10 +39,16 @@
 //! - [`pdf`]: PDF document parsing and text extraction
 //! - [`pdf_generator`]: PDF generation from elements and content streams
 //! - [`pdf_ops`]: High-level PDF operations (merge, split, watermark, etc.)
-//! - [`elements`]: Markdown parsing and element representation
+//! - [`elements`]: Markdown parsing, element representation and the [`elements::SourceParser`] trait for other input formats
+//! - [`error`]: Structured error type ([`error::PdfError`]) returned by the public API
+//! - [`font_metrics`]: AFM glyph widths for measuring text in the standard fonts
+//! - [`truetype`]: TrueType fonts embedded for code blocks
 //! - [`markdown`]: Markdown to PDF conversion utilities
+//! - [`math`]: Box layout of LaTeX math: fractions, scripts, roots and large operators
 //! - [`image`]: Image loading, parsing, and PDF embedding
+//! - [`color_profile`]: sRGB ICC profile for calibrated `/ICCBased` color
 //! - [`compression`]: Data compression utilities
+//! - [`content_stream`]: Tokenizer yielding the operators and operands of page content streams
 //! - [`security`]: PDF security, encryption, and permission management
 //! - [`builder`]: Fluent builder API for ergonomic PDF creation
 //! - [`streaming`]: Memory-efficient streaming PDF generation for large documents
@@ -129,10 +135,15 @@
 //! ```
 
 pub mod builder;
+pub mod color_profile;
 pub mod compression;
+pub mod content_stream;
 pub mod elements;
+pub mod error;
+pub mod font_metrics;
 pub mod image;
 pub mod markdown;
+pub mod math;
 pub mod optimization;
 pub mod parallel;
 pub mod pdf;
@@ -141,6 +152,7 @@ pub mod pdf_ops;
 pub mod security;
 pub mod streaming;
 pub mod table_renderer;
+pub mod truetype;
 
 #[cfg(test)]
 mod tests {
@@ -4,10 +4,36 @@ use clap::{Parser, Subcommand};
 #[command(name = "pdf-cli")]
 #[command(about = "A CLI tool to read/write PDFs and convert to/from markdown")]
 struct Cli {
+    #[arg(short, long, global = true, help = "Only print warnings and errors")]
+    quiet: bool,
     #[command(subcommand)]
     command: Commands,
 }
 
+/// Prints library log records: progress to stdout, warnings and errors to stderr
+struct CliLogger;
+
+impl log::Log for CliLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match record.level() {
+            log::Level::Error => eprintln!("Error: {}", record.args()),
+            log::Level::Warn => eprintln!("Warning: {}", record.args()),
+            _ => println!("{}", record.args()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CliLogger = CliLogger;
+
 #[derive(Subcommand)]
 enum Commands {
     #[command(about = "Convert PDF to Markdown")]
@@ -16,6 +42,8 @@ enum Commands {
         input: String,
         #[arg(help = "Output Markdown file")]
         output: String,
+        #[arg(long, help = "Start each page with a '## Page' heading showing its page label")]
+        page_labels: bool,
     },
     #[command(about = "Convert Markdown to PDF")]
     MdToPdf {
@@ -29,11 +57,35 @@ enum Commands {
         font_size: f32,
         #[arg(long, help = "Use landscape orientation")]
         landscape: bool,
+        #[arg(long, help = "Margin on all sides in points (default 72)")]
+        margin: Option<f32>,
+        #[arg(long, help = "Top margin in points, overrides --margin")]
+        margin_top: Option<f32>,
+        #[arg(long, help = "Bottom margin in points, overrides --margin")]
+        margin_bottom: Option<f32>,
+        #[arg(long, help = "Left margin in points, overrides --margin")]
+        margin_left: Option<f32>,
+        #[arg(long, help = "Right margin in points, overrides --margin")]
+        margin_right: Option<f32>,
+        #[arg(long, help = "Document date: 'now' or ISO 8601 (e.g. 2024-05-01T09:30)")]
+        date: Option<String>,
+        #[arg(long, help = "Footer text; {page} and {date} are replaced")]
+        footer: Option<String>,
+        #[arg(long, help = "Omit page numbers from the footer")]
+        no_page_numbers: bool,
+        #[arg(long, help = "Number shown on the first page", default_value = "1")]
+        page_start: u32,
     },
     #[command(about = "Extract text from PDF")]
     Extract {
         #[arg(help = "Input PDF file")]
         input: String,
+        #[arg(long, help = "Join wrapped lines into paragraphs")]
+        reflow: bool,
+        #[arg(long, help = "Rejoin words split across text operators, for spellcheckers and search")]
+        plain: bool,
+        #[arg(long, help = "Compose combining marks with their base letters (Unicode NFC)")]
+        nfc: bool,
     },
     #[command(about = "Create a new PDF")]
     Create {
@@ -81,6 +133,13 @@ enum Commands {
         #[arg(long, help = "End page (1-indexed, inclusive)")]
         end: usize,
     },
+    #[command(about = "Write each page to its own PDF in a directory")]
+    Burst {
+        #[arg(help = "Input PDF file")]
+        input: String,
+        #[arg(short, long, help = "Output directory")]
+        output: String,
+    },
     #[command(about = "Add text watermark to PDF")]
     Watermark {
         #[arg(help = "Input PDF file")]
@@ -93,6 +152,10 @@ enum Commands {
         size: f32,
         #[arg(long, help = "Opacity (0.0-1.0)", default_value = "0.3")]
         opacity: f32,
+        #[arg(long, help = "Compress page content in the output")]
+        compress: bool,
+        #[arg(long, help = "Warn when the output is larger than this many bytes")]
+        max_size: Option<u64>,
     },
     #[command(about = "Reorder pages in a PDF")]
     Reorder {
@@ -103,6 +166,15 @@ enum Commands {
         #[arg(long, help = "Page order (comma-separated, 1-indexed)")]
         pages: String,
     },
+    #[command(about = "Rebuild the cross-reference table of a damaged PDF")]
+    Repair {
+        #[arg(help = "Input PDF file")]
+        input: String,
+        #[arg(short, long, help = "Output PDF file")]
+        output: String,
+        #[arg(long, help = "Also correct stream /Length entries that don't match their data")]
+        balance: bool,
+    },
     #[command(about = "Rotate all pages in a PDF")]
     Rotate {
         #[arg(help = "Input PDF file")]
@@ -111,6 +183,8 @@ enum Commands {
         output: String,
         #[arg(long, help = "Rotation angle (0, 90, 180, 270)")]
         angle: u32,
+        #[arg(long, help = "Comma-separated pages to rotate, keeping the others (default: all)")]
+        pages: Option<String>,
     },
     #[command(about = "Set PDF metadata and convert from Markdown")]
     MdToPdfMeta {
@@ -128,6 +202,8 @@ enum Commands {
         keywords: Option<String>,
         #[arg(long, help = "Custom metadata fields (key=value pairs, comma-separated)")]
         custom: Option<String>,
+        #[arg(long, help = "Creation and modification date: 'now' or ISO 8601 (e.g. 2024-05-01T09:30)")]
+        date: Option<String>,
         #[arg(long, help = "Font family", default_value = "Helvetica")]
         font: String,
         #[arg(long, help = "Font size", default_value = "12")]
@@ -160,12 +236,16 @@ enum Commands {
         x: f32,
         #[arg(long, help = "Y position", default_value = "100")]
         y: f32,
-        #[arg(long, help = "Width", default_value = "200")]
+        #[arg(long, help = "Width (0 keeps the aspect ratio)", default_value = "200")]
         width: f32,
-        #[arg(long, help = "Height", default_value = "200")]
+        #[arg(long, help = "Height (0 keeps the aspect ratio)", default_value = "200")]
         height: f32,
         #[arg(long, help = "Opacity (0.0-1.0)", default_value = "1.0")]
         opacity: f32,
+        #[arg(long, help = "Compress page content in the output")]
+        compress: bool,
+        #[arg(long, help = "Warn when the output is larger than this many bytes")]
+        max_size: Option<u64>,
     },
     #[command(about = "Add watermark to PDF (text or image)")]
     WatermarkAdvanced {
@@ -179,8 +259,12 @@ enum Commands {
         image: Option<String>,
         #[arg(long, help = "Opacity (0.0-1.0)", default_value = "0.3")]
         opacity: f32,
-        #[arg(long, help = "Position (center, topleft, topright, bottomleft, bottomright, diagonal)", default_value = "diagonal")]
+        #[arg(long, help = "Position (center, topleft, topright, bottomleft, bottomright, diagonal, tiled)", default_value = "diagonal")]
         position: String,
+        #[arg(long, help = "Font size of text watermarks", default_value = "48")]
+        font_size: f32,
+        #[arg(long, help = "Distance between tiled watermarks in points", default_value = "200")]
+        spacing: f32,
     },
     #[command(about = "Add password protection and permissions to PDF")]
     Protect {
@@ -190,7 +274,7 @@ enum Commands {
         output: String,
         #[arg(long, help = "User password (required to open document)")]
         user_password: Option<String>,
-        #[arg(long, help = "Owner password (controls permissions)")]
+        #[arg(long, help = "Owner password (controls permissions); given alone, the document opens without a password")]
         owner_password: Option<String>,
         #[arg(long, help = "Encryption algorithm (rc4-40, rc4-128, aes-128, aes-256)", default_value = "rc4-128")]
         algorithm: String,
@@ -213,6 +297,85 @@ enum Commands {
         #[arg(long, help = "Read-only (no modifications)")]
         read_only: bool,
     },
+    #[command(about = "Change or clear the open password of a protected PDF")]
+    ChangePassword {
+        #[arg(help = "Input PDF file")]
+        input: String,
+        #[arg(short, long, help = "Output PDF file")]
+        output: String,
+        #[arg(long, help = "Current user password", default_value = "")]
+        old_password: String,
+        #[arg(long, help = "New user password")]
+        user_password: Option<String>,
+        #[arg(long, help = "New owner password")]
+        owner_password: Option<String>,
+    },
+    #[command(about = "Remove password protection, writing a plain readable PDF")]
+    Decrypt {
+        #[arg(help = "Input PDF file")]
+        input: String,
+        #[arg(short, long, help = "Output PDF file")]
+        output: String,
+        #[arg(long, help = "User password", default_value = "")]
+        password: String,
+    },
+    #[command(about = "Inspect PDF objects and page tree for debugging")]
+    Inspect {
+        #[arg(help = "Input PDF file")]
+        input: String,
+        #[arg(long, help = "Print the object with this number")]
+        object: Option<u32>,
+        #[arg(long, help = "Print the page tree (default when no --object or --flags is given)")]
+        tree: bool,
+        #[arg(long, help = "Print whether the PDF is encrypted, tagged, linearized and has a form")]
+        flags: bool,
+    },
+    #[command(about = "Compare two PDFs page by page (exits nonzero if they differ)")]
+    Diff {
+        #[arg(help = "First PDF file")]
+        left: String,
+        #[arg(help = "Second PDF file")]
+        right: String,
+    },
+    #[command(about = "Embed a file as a PDF attachment")]
+    Attach {
+        #[arg(help = "Input PDF file")]
+        input: String,
+        #[arg(help = "Output PDF file")]
+        output: String,
+        #[arg(help = "File to attach")]
+        file: String,
+        #[arg(long, default_value = "", help = "Attachment description")]
+        description: String,
+    },
+    #[command(about = "List or extract PDF attachments")]
+    Attachments {
+        #[arg(help = "Input PDF file")]
+        input: String,
+        #[arg(long, help = "Name of the attachment to extract")]
+        extract: Option<String>,
+        #[arg(long, help = "Where to write the extracted file (defaults to its name)")]
+        output: Option<String>,
+    },
+    #[command(about = "Print the outline (bookmarks) of a PDF")]
+    Outline {
+        #[arg(help = "Input PDF file")]
+        input: String,
+    },
+    #[command(about = "List the annotations of a PDF")]
+    Annotations {
+        #[arg(help = "Input PDF file")]
+        input: String,
+    },
+}
+
+/// Print outline entries indented by level, with their 1-based target pages
+fn print_outline(entries: &[pdf::OutlineEntry]) {
+    for entry in entries {
+        let page = entry.page_index.map(|p| format!(" (page {})", p + 1)).unwrap_or_default();
+        println!("{}{}{}", "  ".repeat(entry.level), entry.title, page);
+        print_outline(&entry.children);
+    }
 }
 
 // Use the library instead of declaring modules
@@ -220,41 +383,92 @@ use pdfrs::{compression, elements, image, markdown, pdf, pdf_generator, pdf_ops,
 
 fn main() {
     let cli = Cli::parse();
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(if cli.quiet { log::LevelFilter::Warn } else { log::LevelFilter::Info });
+    }
 
     match cli.command {
-        Commands::PdfToMd { input, output } => match pdf::extract_text(&input) {
-            Ok(text) => {
-                if let Err(e) = std::fs::write(&output, text) {
-                    eprintln!("Error writing Markdown file: {}", e);
-                } else {
-                    println!(
-                        "Successfully converted PDF {} to Markdown {}",
-                        input, output
-                    );
+        Commands::PdfToMd { input, output, page_labels } => {
+            let extracted = if page_labels {
+                pdf::extract_markdown_with_page_labels(&input)
+            } else {
+                pdf::extract_markdown(&input)
+            };
+            match extracted {
+                Ok(text) => {
+                    if let Err(e) = std::fs::write(&output, text) {
+                        eprintln!("Error writing Markdown file: {}", e);
+                    } else {
+                        println!(
+                            "Successfully converted PDF {} to Markdown {}",
+                            input, output
+                        );
+                    }
                 }
+                Err(e) => eprintln!("Error extracting text from PDF: {}", e),
             }
-            Err(e) => eprintln!("Error extracting text from PDF: {}", e),
-        },
+        }
         Commands::MdToPdf {
             input,
             output,
             font,
             font_size,
             landscape,
+            margin,
+            margin_top,
+            margin_bottom,
+            margin_left,
+            margin_right,
+            date,
+            footer,
+            no_page_numbers,
+            page_start,
         } => {
             let orientation = if landscape {
                 pdf_generator::PageOrientation::Landscape
             } else {
                 pdf_generator::PageOrientation::Portrait
             };
-            match markdown::markdown_to_pdf_full(&input, &output, &font, font_size, orientation) {
+            let layout = pdf_generator::PageLayout::from_orientation(orientation);
+            let layout = layout.with_margins(
+                margin_top.or(margin).unwrap_or(layout.margin_top),
+                margin_right.or(margin).unwrap_or(layout.margin_right),
+                margin_bottom.or(margin).unwrap_or(layout.margin_bottom),
+                margin_left.or(margin).unwrap_or(layout.margin_left),
+            );
+            let date = match date {
+                Some(d) => match pdf_ops::PdfDate::parse(&d) {
+                    Some(parsed) => Some(parsed),
+                    None => {
+                        eprintln!("Error: Invalid date '{}'. Use 'now' or ISO 8601 like 2024-05-01T09:30", d);
+                        return;
+                    }
+                },
+                None => None,
+            };
+            let options = pdf_generator::RenderOptions {
+                date,
+                footer,
+                show_page_numbers: !no_page_numbers,
+                page_number_start: page_start,
+                ..Default::default()
+            };
+            match markdown::markdown_to_pdf_with_render_options(&input, &output, &font, font_size, layout, &options) {
             Ok(_) => println!(
                 "Successfully converted Markdown {} to PDF {}",
                 input, output
             ),
             Err(e) => eprintln!("Error converting Markdown to PDF: {}", e),
         }},
-        Commands::Extract { input } => match pdf::extract_text(&input) {
+        Commands::Extract { input, reflow, plain, nfc } => match pdf::PdfDocument::load_from_file(&input).and_then(|doc| {
+            if reflow {
+                doc.get_text_reflowed()
+            } else if plain {
+                doc.get_plain_text()
+            } else {
+                doc.get_text_with_options(&pdf::TextOptions { normalize_nfc: nfc })
+            }
+        }) {
             Ok(text) => println!("Extracted text:\n{}", text),
             Err(e) => eprintln!("Error extracting text: {}", e),
         },
@@ -312,8 +526,13 @@ fn main() {
                 Err(e) => eprintln!("Error splitting PDF: {}", e),
             }
         }
-        Commands::Watermark { input, output, text, size, opacity } => {
-            match pdf_ops::watermark_pdf(&input, &output, &text, size, opacity) {
+        Commands::Burst { input, output } => match pdf_ops::burst_pdf(&input, &output) {
+            Ok(files) => println!("Successfully burst {} into {} files in {}", input, files.len(), output),
+            Err(e) => eprintln!("Error bursting PDF: {}", e),
+        },
+        Commands::Watermark { input, output, text, size, opacity, compress, max_size } => {
+            let options = pdf_ops::OutputOptions { compress, warn_above: max_size };
+            match pdf_ops::watermark_pdf_with_options(&input, &output, &text, size, opacity, &options) {
                 Ok(_) => println!("Successfully watermarked into {}", output),
                 Err(e) => eprintln!("Error adding watermark: {}", e),
             }
@@ -330,7 +549,33 @@ fn main() {
                 Err(e) => eprintln!("Invalid page order format: {}. Use comma-separated numbers like 3,1,2", e),
             }
         }
-        Commands::Rotate { input, output, angle } => {
+        Commands::Repair { input, output, balance } => {
+            let result = if balance {
+                pdf_ops::repair_balance(&input, &output).map(|fixed| {
+                    println!("Corrected {} stream lengths", fixed);
+                })
+            } else {
+                pdf_ops::repair_pdf(&input, &output)
+            };
+            match result {
+                Ok(_) => println!("Successfully repaired {} into {}", input, output),
+                Err(e) => eprintln!("Error repairing PDF: {}", e),
+            }
+        }
+        Commands::Rotate { input, output, angle, pages: Some(pages) } => {
+            let pages: Result<Vec<usize>, _> = pages.split(',').map(|s| s.trim().parse::<usize>()).collect();
+            match pages {
+                Ok(pages) => {
+                    let rotations: Vec<(usize, u32)> = pages.into_iter().map(|page| (page, angle)).collect();
+                    match pdf_ops::rotate_pages(&input, &output, &rotations) {
+                        Ok(_) => println!("Successfully rotated {} page(s) of {} into {}", rotations.len(), input, output),
+                        Err(e) => eprintln!("Error rotating PDF: {}", e),
+                    }
+                }
+                Err(e) => eprintln!("Invalid page list: {}", e),
+            }
+        }
+        Commands::Rotate { input, output, angle, pages: None } => {
             match pdf_ops::rotate_pdf(&input, &output, angle) {
                 Ok(_) => println!("Successfully rotated {} into {}", input, output),
                 Err(e) => eprintln!("Error rotating PDF: {}", e),
@@ -344,6 +589,7 @@ fn main() {
             subject,
             keywords,
             custom,
+            date,
             font,
             font_size,
             landscape,
@@ -361,6 +607,18 @@ fn main() {
                 creator: Some("pdf-cli".into()),
                 ..Default::default()
             };
+            if let Some(date) = date {
+                match pdf_ops::PdfDate::parse(&date) {
+                    Some(parsed) => {
+                        metadata.creation_date = Some(parsed);
+                        metadata.mod_date = Some(parsed);
+                    }
+                    None => {
+                        eprintln!("Error: Invalid date '{}'. Use 'now' or ISO 8601 like 2024-05-01T09:30", date);
+                        return;
+                    }
+                }
+            }
 
             // Parse custom metadata fields (key=value pairs, comma-separated)
             if let Some(custom_fields) = custom {
@@ -399,7 +657,7 @@ fn main() {
                 Ok(f) => f,
                 Err(e) => {
                     eprintln!("Error parsing form fields JSON: {}", e);
-                    eprintln!("Expected format: [{{\"name\":\"field1\",\"type\":\"Text\",\"x\":100,\"y\":700,\"width\":200,\"height\":20,\"default_value\":\"\",\"options\":[],\"required\":false}}]");
+                    eprintln!("Expected format: [{{\"name\":\"field1\",\"type\":\"Text\",\"x\":100,\"y\":700,\"width\":200,\"height\":20,\"default_value\":\"\",\"options\":[],\"required\":false,\"page\":1}}]");
                     return;
                 }
             };
@@ -418,8 +676,12 @@ fn main() {
             width,
             height,
             opacity,
+            compress,
+            max_size,
         } => {
-            match pdf_ops::overlay_image_on_pdf(&input, &output, &image, x, y, width, height, opacity) {
+            let area = pdf_ops::Rect::new(x, y, width, height);
+            let options = pdf_ops::OutputOptions { compress, warn_above: max_size };
+            match pdf_ops::overlay_image_on_pdf_with_options(&input, &output, &image, area, opacity, &options) {
                 Ok(_) => println!("Successfully overlaid image on {}", output),
                 Err(e) => eprintln!("Error overlaying image: {}", e),
             }
@@ -431,6 +693,8 @@ fn main() {
             image,
             opacity,
             position,
+            font_size,
+            spacing,
         } => {
             // Determine watermark content
             let watermark_content = if let Some(text_str) = text {
@@ -450,13 +714,14 @@ fn main() {
                 "bottomleft" => pdf_ops::WatermarkPosition::BottomLeft,
                 "bottomright" => pdf_ops::WatermarkPosition::BottomRight,
                 "diagonal" => pdf_ops::WatermarkPosition::Diagonal,
+                "tiled" => pdf_ops::WatermarkPosition::Tiled { spacing },
                 _ => {
-                    eprintln!("Error: Invalid position '{}'. Valid options: center, topleft, topright, bottomleft, bottomright, diagonal", position);
+                    eprintln!("Error: Invalid position '{}'. Valid options: center, topleft, topright, bottomleft, bottomright, diagonal, tiled", position);
                     return;
                 }
             };
 
-            match pdf_ops::watermark_pdf_advanced(&input, &output, watermark_content, opacity, watermark_position) {
+            match pdf_ops::watermark_pdf_with_font_size(&input, &output, watermark_content, opacity, watermark_position, font_size) {
                 Ok(_) => println!("Successfully added watermark to {}", output),
                 Err(e) => eprintln!("Error adding watermark: {}", e),
             }
@@ -534,5 +799,103 @@ fn main() {
                 Err(e) => eprintln!("Error protecting PDF: {}", e),
             }
         }
+        Commands::ChangePassword {
+            input,
+            output,
+            old_password,
+            user_password,
+            owner_password,
+        } => match pdf_ops::change_password(
+            &input,
+            &output,
+            &old_password,
+            user_password.as_deref(),
+            owner_password.as_deref(),
+        ) {
+            Ok(_) => println!("Successfully updated passwords in {}", output),
+            Err(e) => eprintln!("Error changing password: {}", e),
+        },
+        Commands::Decrypt { input, output, password } => {
+            match pdf_ops::decrypt_pdf(&input, &output, &password) {
+                Ok(_) => println!("Successfully decrypted {} into {}", input, output),
+                Err(e) => eprintln!("Error decrypting PDF: {}", e),
+            }
+        }
+        Commands::Inspect { input, object, tree, flags } => {
+            let doc = match pdf::PdfDocument::load_from_file(&input) {
+                Ok(doc) => doc,
+                Err(e) => {
+                    eprintln!("Error reading PDF: {}", e);
+                    return;
+                }
+            };
+            if let Some(id) = object {
+                match pdf_ops::describe_object(&doc, id) {
+                    Some(text) => print!("{}", text),
+                    None => eprintln!("Object {} not found in {}", id, input),
+                }
+            }
+            if flags {
+                let info = doc.info_flags();
+                println!("encrypted: {}", info.encrypted);
+                println!("tagged: {}", info.tagged);
+                println!("linearized: {}", info.linearized);
+                println!("acroform: {}", info.has_acroform);
+            }
+            if tree || (object.is_none() && !flags) {
+                print!("{}", pdf_ops::describe_page_tree(&doc));
+            }
+        }
+        Commands::Diff { left, right } => match pdf_ops::diff_pdfs(&left, &right) {
+            Ok(diff) => {
+                print!("{}", diff.summary());
+                if !diff.is_equal() {
+                    std::process::exit(1);
+                }
+            }
+            Err(e) => {
+                eprintln!("Error comparing PDFs: {}", e);
+                std::process::exit(2);
+            }
+        },
+        Commands::Attach { input, output, file, description } => {
+            match pdf_ops::attach_file(&input, &output, &file, &description) {
+                Ok(()) => println!("Successfully attached {} to {}", file, output),
+                Err(e) => eprintln!("Error attaching file: {}", e),
+            }
+        }
+        Commands::Attachments { input, extract, output } => match extract {
+            Some(name) => {
+                let output = output.unwrap_or_else(|| name.clone());
+                if let Err(e) = pdf_ops::extract_attachment(&input, &name, &output) {
+                    eprintln!("Error extracting attachment: {}", e);
+                }
+            }
+            None => match pdf_ops::list_attachments(&input) {
+                Ok(attachments) if attachments.is_empty() => println!("No attachments in {}", input),
+                Ok(attachments) => {
+                    for a in attachments {
+                        println!("{} ({} bytes) {}", a.name, a.size, a.description.unwrap_or_default());
+                    }
+                }
+                Err(e) => eprintln!("Error reading attachments: {}", e),
+            },
+        },
+        Commands::Outline { input } => match pdf::extract_outline(&input) {
+            Ok(entries) if entries.is_empty() => println!("No outline in {}", input),
+            Ok(entries) => print_outline(&entries),
+            Err(e) => eprintln!("Error reading outline: {}", e),
+        },
+        Commands::Annotations { input } => match pdf::extract_annotations(&input) {
+            Ok(annotations) if annotations.is_empty() => println!("No annotations in {}", input),
+            Ok(annotations) => {
+                for a in annotations {
+                    let [x1, y1, x2, y2] = a.rect;
+                    let detail = a.uri.or(a.contents).unwrap_or_default();
+                    println!("page {} {} [{} {} {} {}] {}", a.page, a.subtype, x1, y1, x2, y2, detail);
+                }
+            }
+            Err(e) => eprintln!("Error reading annotations: {}", e),
+        },
     }
 }
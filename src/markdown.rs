@@ -1,4 +1,5 @@
 use crate::elements::{self, Element, TextSegment};
+use crate::pdf_ops::PdfMetadata;
 use anyhow::Result;
 use std::fs::File;
 use std::io::Read;
@@ -28,6 +29,23 @@ fn elements_to_text(elements: &[Element]) -> String {
                         TextSegment::Plain(t) | TextSegment::Bold(t) | TextSegment::Italic(t) | TextSegment::BoldItalic(t) => {
                             text.push_str(t);
                         }
+                        TextSegment::Superscript(t) => {
+                            text.push('^');
+                            text.push_str(t);
+                            text.push('^');
+                        }
+                        TextSegment::Subscript(t) => {
+                            text.push('~');
+                            text.push_str(t);
+                            text.push('~');
+                        }
+                        // Markdown has no syntax for rendering modes
+                        TextSegment::Styled(t, _) => text.push_str(t),
+                        TextSegment::Highlight(t, _) => {
+                            text.push_str("==");
+                            text.push_str(t);
+                            text.push_str("==");
+                        }
                         TextSegment::Code(c) => {
                             text.push('`');
                             text.push_str(c);
@@ -54,6 +72,11 @@ fn elements_to_text(elements: &[Element]) -> String {
                 text.push_str(t);
                 text.push('\n');
             }
+            Element::ListParagraph { text: t, .. } => {
+                text.push_str("  ");
+                text.push_str(t);
+                text.push('\n');
+            }
             Element::TaskListItem { checked, text: t } => {
                 if *checked {
                     text.push_str("[x] ");
@@ -68,7 +91,7 @@ fn elements_to_text(elements: &[Element]) -> String {
                 text.push_str(code);
                 text.push_str("\n\n");
             }
-            Element::TableRow { cells, is_separator, alignments: _ } => {
+            Element::TableRow { cells, is_separator, .. } => {
                 if *is_separator {
                     let sep: Vec<String> = cells.iter().map(|c| "-".repeat(c.len().max(4))).collect();
                     text.push_str(&sep.join("  "));
@@ -93,6 +116,13 @@ fn elements_to_text(elements: &[Element]) -> String {
                 text.push_str(t);
                 text.push('\n');
             }
+            Element::Callout { kind, text: t } => {
+                text.push_str(&format!("> [!{}]\n> {}\n", kind.marker(), t));
+            }
+            Element::Quoted { depth, element } => {
+                text.push_str(&"> ".repeat(*depth as usize));
+                text.push_str(&elements_to_text(std::slice::from_ref(element.as_ref())));
+            }
             Element::InlineCode { code } => {
                 text.push_str(code);
                 text.push('\n');
@@ -103,12 +133,16 @@ fn elements_to_text(elements: &[Element]) -> String {
                 text.push_str(url);
                 text.push_str(")\n");
             }
-            Element::Image { alt, path } => {
+            Element::Image { alt, path, caption, .. } => {
                 text.push_str("[Image: ");
                 text.push_str(alt);
                 text.push_str("] (");
                 text.push_str(path);
                 text.push_str(")\n");
+                if let Some(caption) = caption {
+                    text.push_str(caption);
+                    text.push('\n');
+                }
             }
             Element::StyledText { text: t, .. } => {
                 text.push_str(t);
@@ -124,13 +158,20 @@ fn elements_to_text(elements: &[Element]) -> String {
                 text.push_str(expression);
                 text.push_str("$\n");
             }
+            Element::Chart { data, title, .. } => {
+                text.push_str(title);
+                text.push('\n');
+                for (label, value) in data {
+                    text.push_str(&format!("{}: {}\n", label, value));
+                }
+            }
             Element::PageBreak => {
                 text.push_str("\n---\n");
             }
             Element::HorizontalRule => {
                 text.push_str("---\n");
             }
-            Element::EmptyLine => {}
+            Element::EmptyLine | Element::RawContent { .. } | Element::SetPageLayout(_) => {}
         }
     }
     text
@@ -161,16 +202,261 @@ pub fn markdown_to_pdf_full(
     font: &str,
     font_size: f32,
     orientation: crate::pdf_generator::PageOrientation,
+) -> Result<()> {
+    let layout = crate::pdf_generator::PageLayout::from_orientation(orientation);
+    markdown_to_pdf_with_layout(markdown_file, pdf_file, font, font_size, layout)
+}
+
+/// Convert a Markdown file using an explicit page size and margins
+pub fn markdown_to_pdf_with_layout(
+    markdown_file: &str,
+    pdf_file: &str,
+    font: &str,
+    font_size: f32,
+    layout: crate::pdf_generator::PageLayout,
+) -> Result<()> {
+    markdown_to_pdf_with_render_options(
+        markdown_file,
+        pdf_file,
+        font,
+        font_size,
+        layout,
+        &crate::pdf_generator::RenderOptions::default(),
+    )
+}
+
+/// Like [`markdown_to_pdf_with_layout`], with extra rendering options. Front
+/// matter replaces the metadata in `options`.
+pub fn markdown_to_pdf_with_render_options(
+    markdown_file: &str,
+    pdf_file: &str,
+    font: &str,
+    font_size: f32,
+    layout: crate::pdf_generator::PageLayout,
+    options: &crate::pdf_generator::RenderOptions,
 ) -> Result<()> {
     let mut file = File::open(markdown_file)?;
     let mut content = String::new();
     file.read_to_string(&mut content)?;
 
-    let elements = elements::parse_markdown(&content);
-    let layout = crate::pdf_generator::PageLayout::from_orientation(orientation);
-    crate::pdf_generator::create_pdf_from_elements_with_layout(
-        pdf_file, &elements, font, font_size, layout,
+    let (elements, metadata) = parse_markdown_with_metadata(&content);
+    let mut options = options.clone();
+    if metadata.is_some() {
+        options.metadata = metadata;
+    }
+    // Relative image paths are written relative to the Markdown file
+    if options.image_base_path.is_none() {
+        options.image_base_path = std::path::Path::new(markdown_file).parent().map(|dir| dir.to_path_buf());
+    }
+    crate::pdf_generator::create_pdf_from_elements_with_render_options(
+        pdf_file, &elements, font, font_size, layout, &options,
     )?;
 
     Ok(())
 }
+
+/// Parse Markdown that may start with a `---` YAML front-matter block.
+///
+/// The block is stripped and its `title`, `author`, `subject`/`description`,
+/// `keywords`/`tags` and other scalar keys become document metadata. A block
+/// that is not valid front matter is left in place as body content.
+pub fn parse_markdown_with_metadata(markdown: &str) -> (Vec<Element>, Option<PdfMetadata>) {
+    let (body, metadata) = match split_front_matter(markdown) {
+        Ok(Some((metadata, body))) => (body, Some(metadata)),
+        Ok(None) => (markdown, None),
+        Err(reason) => {
+            log::warn!("Ignoring malformed front matter ({}); rendering it as text", reason);
+            (markdown, None)
+        }
+    };
+    let (elements, warnings) = elements::parse_markdown_with_options(body, &elements::ParseOptions::default());
+    for warning in warnings {
+        log::warn!("{}", warning);
+    }
+    (elements, metadata)
+}
+
+/// Split leading front matter from the body. `Ok(None)` when there is none.
+fn split_front_matter(markdown: &str) -> std::result::Result<Option<(PdfMetadata, &str)>, String> {
+    let text = markdown.strip_prefix('\u{feff}').unwrap_or(markdown);
+    let mut lines = text.split_inclusive('\n');
+    match lines.next() {
+        Some(first) if first.trim_end() == "---" => {}
+        _ => return Ok(None),
+    }
+
+    let mut metadata = PdfMetadata::new();
+    let mut offset = text.len() - lines.clone().map(str::len).sum::<usize>();
+    let mut list_key: Option<String> = None;
+    let mut list_items: Vec<String> = Vec::new();
+
+    for line in lines {
+        offset += line.len();
+        let trimmed = line.trim_end();
+        if trimmed == "---" || trimmed == "..." {
+            if let Some(key) = list_key.take() {
+                set_front_matter_field(&mut metadata, &key, &list_items.join(", "));
+            }
+            return Ok(Some((metadata, &text[offset..])));
+        }
+        if trimmed.trim().is_empty() || trimmed.trim_start().starts_with('#') {
+            continue;
+        }
+        if let Some(item) = trimmed.trim_start().strip_prefix("- ")
+            && list_key.is_some()
+        {
+            list_items.push(unquote_yaml(item));
+            continue;
+        }
+        let Some((key, value)) = trimmed.split_once(':') else {
+            return Err(format!("expected `key: value`, found {:?}", trimmed));
+        };
+        let key = key.trim();
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(format!("invalid key {:?}", key));
+        }
+        if let Some(previous) = list_key.take() {
+            set_front_matter_field(&mut metadata, &previous, &list_items.join(", "));
+            list_items.clear();
+        }
+        let value = value.trim();
+        if value.is_empty() {
+            // A block list (`- item` lines) may follow
+            list_key = Some(key.to_string());
+        } else if let Some(inner) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            let items: Vec<String> = inner.split(',').map(unquote_yaml).filter(|i| !i.is_empty()).collect();
+            set_front_matter_field(&mut metadata, key, &items.join(", "));
+        } else {
+            set_front_matter_field(&mut metadata, key, &unquote_yaml(value));
+        }
+    }
+    Err("no closing `---`".to_string())
+}
+
+fn set_front_matter_field(metadata: &mut PdfMetadata, key: &str, value: &str) {
+    if value.is_empty() {
+        return;
+    }
+    let value = value.to_string();
+    match key.to_ascii_lowercase().as_str() {
+        "title" => metadata.title = Some(value),
+        "author" | "authors" => metadata.author = Some(value),
+        "subject" | "description" => metadata.subject = Some(value),
+        "keywords" | "tags" => metadata.keywords = Some(value),
+        "creator" => metadata.creator = Some(value),
+        _ => {
+            let mut chars = key.chars();
+            let name = match chars.next() {
+                Some(c) => c.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => return,
+            };
+            metadata.add_custom_field(name, value);
+        }
+    }
+}
+
+fn unquote_yaml(value: &str) -> String {
+    let value = value.trim();
+    for quote in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            return value[1..value.len() - 1].to_string();
+        }
+    }
+    value.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_front_matter_sets_metadata() {
+        let md = "---\ntitle: Foo\nauthor: \"Jane Doe\"\ntags: [pdf, rust]\ndate: 2024-05-01\n---\n# Heading\n\nBody text\n";
+        let (elements, metadata) = parse_markdown_with_metadata(md);
+        let metadata = metadata.unwrap();
+        assert_eq!(metadata.title.as_deref(), Some("Foo"));
+        assert_eq!(metadata.author.as_deref(), Some("Jane Doe"));
+        assert_eq!(metadata.keywords.as_deref(), Some("pdf, rust"));
+        assert_eq!(metadata.get_custom_field("Date").map(String::as_str), Some("2024-05-01"));
+
+        let text = elements_to_text(&elements);
+        assert!(!text.contains("title"), "{}", text);
+        assert!(text.contains("Body text"));
+
+        let dir = std::env::temp_dir();
+        let input = dir.join("pdfrs_front_matter.md");
+        let output = dir.join("pdfrs_front_matter.pdf");
+        std::fs::write(&input, md).unwrap();
+        markdown_to_pdf(input.to_str().unwrap(), output.to_str().unwrap()).unwrap();
+        let raw = String::from_utf8_lossy(&std::fs::read(&output).unwrap()).to_string();
+        assert!(raw.contains("/Title (Foo)"));
+        let extracted = crate::pdf::extract_text(output.to_str().unwrap()).unwrap();
+        assert!(!extracted.contains("title:"));
+    }
+
+    #[test]
+    fn test_front_matter_keeps_render_options() {
+        let dir = std::env::temp_dir();
+        let input = dir.join("pdfrs_front_matter_options.md");
+        let output = dir.join("pdfrs_front_matter_options.pdf");
+        std::fs::write(&input, "---\ntitle: Landscape\n---\n# Heading\n\nBody text\n").unwrap();
+        let date = crate::pdf_ops::PdfDate {
+            year: 2024, month: 5, day: 1, hour: 0, minute: 0, second: 0, utc_offset_minutes: None,
+        };
+        let options = crate::pdf_generator::RenderOptions {
+            show_page_numbers: false,
+            date: Some(date),
+            ..Default::default()
+        };
+        markdown_to_pdf_with_render_options(
+            input.to_str().unwrap(),
+            output.to_str().unwrap(),
+            "Helvetica",
+            12.0,
+            crate::pdf_generator::PageLayout::landscape(),
+            &options,
+        )
+        .unwrap();
+
+        let raw = String::from_utf8_lossy(&std::fs::read(&output).unwrap()).to_string();
+        assert!(raw.contains("/Title (Landscape)"));
+        assert!(raw.contains("/CreationDate (D:20240501"));
+        assert!(raw.contains("/MediaBox [0 0 792 612]"));
+        let extracted = crate::pdf::extract_text(output.to_str().unwrap()).unwrap();
+        assert!(extracted.contains("Body text"));
+        assert!(!extracted.contains("Page 1"));
+    }
+
+    #[test]
+    fn test_malformed_front_matter_is_body() {
+        let md = "---\nnot yaml at all\n---\nText\n";
+        let (elements, metadata) = parse_markdown_with_metadata(md);
+        assert!(metadata.is_none());
+        assert!(elements_to_text(&elements).contains("not yaml at all"));
+
+        // A leading rule without a closing fence is not front matter either
+        let (_, metadata) = parse_markdown_with_metadata("---\ntitle: Foo\nbody\n");
+        assert!(metadata.is_none());
+    }
+
+    #[test]
+    fn test_relative_image_resolves_against_markdown_dir() {
+        let dir = std::env::temp_dir().join("pdfrs_md_images");
+        std::fs::create_dir_all(dir.join("images")).unwrap();
+        jpeg_encoder::Encoder::new_file(dir.join("images/logo.jpg"), 80)
+            .unwrap()
+            .encode(&[90u8; 8 * 4 * 3], 8, 4, jpeg_encoder::ColorType::Rgb)
+            .unwrap();
+        let input = dir.join("doc.md");
+        let output = std::env::temp_dir().join("pdfrs_md_images.pdf");
+        std::fs::write(&input, "# Logo\n\n![logo](images/logo.jpg)\n").unwrap();
+
+        // The working directory is not the Markdown file's directory
+        assert!(!std::path::Path::new("images/logo.jpg").exists());
+        markdown_to_pdf(input.to_str().unwrap(), output.to_str().unwrap()).unwrap();
+        let raw = String::from_utf8_lossy(&std::fs::read(&output).unwrap()).to_string();
+        assert_eq!(raw.matches("/Subtype /Image").count(), 1);
+        assert!(raw.contains("/Im1 Do"));
+        assert!(!raw.contains("[Image: logo]"));
+    }
+}
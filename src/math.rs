@@ -0,0 +1,833 @@
+//! Box layout for the LaTeX math of `MathBlock` and `MathInline` elements.
+//!
+//! Expressions parse into a small tree of text, fractions, scripts, roots and
+//! accents that is measured and placed around a baseline, TeX style: fractions
+//! stack their numerator over a rule and the denominator, scripts are raised or
+//! lowered with the `Ts` text rise at a smaller size, and large operators carry
+//! their limits above and below in display math. Environments such as
+//! `align`, `cases` and the matrices become rows of `&`-separated cells. This
+//! is not a TeX engine; commands it does not model are set as text.
+
+use crate::font_metrics;
+
+/// A node of a parsed math expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum MathNode {
+    /// Text, with symbol commands already replaced by their characters
+    Text(String),
+    /// A braced group
+    Row(Vec<MathNode>),
+    Fraction { numerator: Vec<MathNode>, denominator: Vec<MathNode> },
+    /// `base` with an optional superscript and subscript
+    Scripts { base: Box<MathNode>, sup: Option<Vec<MathNode>>, sub: Option<Vec<MathNode>> },
+    Root { index: Option<Vec<MathNode>>, radicand: Vec<MathNode> },
+    /// Sum, product or integral sign, drawn enlarged in display math
+    LargeOperator(char),
+    /// `mark` centered above `base`, e.g. a hat or vector arrow
+    Accent { mark: char, base: Vec<MathNode> },
+    /// A bar drawn over `base`
+    Overline(Vec<MathNode>),
+    /// Horizontal space, in em
+    Space(f32),
+    /// Rows of cells from a `\begin{...}` environment, stacked and centered on the math axis
+    Array { kind: ArrayKind, rows: Vec<Vec<Vec<MathNode>>> },
+}
+
+/// How an environment's cells are aligned and delimited
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArrayKind {
+    /// `align`, `aligned`, `split`: columns pair up right- and left-aligned,
+    /// so the `&` before a relation lines the rows up on it
+    Align,
+    /// `gather`: centered rows
+    Gather,
+    /// `cases`: left-aligned value and condition columns after a brace
+    Cases,
+    /// `matrix`, `pmatrix`, `bmatrix`, `vmatrix`, `array`: centered columns
+    /// between optional delimiters
+    Matrix { open: Option<char>, close: Option<char> },
+}
+
+impl ArrayKind {
+    /// The layout of environment `name`, ignoring a trailing `*`
+    fn from_environment(name: &str) -> Self {
+        match name.trim_end_matches('*') {
+            "align" | "aligned" | "alignat" | "alignedat" | "split" | "eqnarray" | "flalign" => ArrayKind::Align,
+            "cases" | "dcases" => ArrayKind::Cases,
+            "pmatrix" => ArrayKind::Matrix { open: Some('('), close: Some(')') },
+            "bmatrix" => ArrayKind::Matrix { open: Some('['), close: Some(']') },
+            "Bmatrix" => ArrayKind::Matrix { open: Some('{'), close: Some('}') },
+            "vmatrix" => ArrayKind::Matrix { open: Some('|'), close: Some('|') },
+            "matrix" | "array" | "smallmatrix" => ArrayKind::Matrix { open: None, close: None },
+            _ => ArrayKind::Gather,
+        }
+    }
+
+    /// Horizontal alignment of column `index`: -1 left, 0 center, 1 right
+    fn column_alignment(&self, index: usize) -> i8 {
+        match self {
+            ArrayKind::Align => if index.is_multiple_of(2) { 1 } else { -1 },
+            ArrayKind::Cases => -1,
+            ArrayKind::Gather | ArrayKind::Matrix { .. } => 0,
+        }
+    }
+
+    /// Space before column `index`, in em
+    fn column_gap(&self, index: usize) -> f32 {
+        match self {
+            _ if index == 0 => 0.0,
+            // No gap inside a pair, where the `&` falls before a relation
+            ArrayKind::Align => if index.is_multiple_of(2) { 2.0 } else { 0.0 },
+            _ => 1.0,
+        }
+    }
+}
+
+/// Something to draw, relative to the origin of the expression's baseline
+#[derive(Debug, Clone, PartialEq)]
+pub enum MathItem {
+    /// `text` at `size` with its baseline at `y`, shifted by the text rise `rise`
+    Text { x: f32, y: f32, rise: f32, size: f32, text: String },
+    /// A horizontal rule `width` long and `thickness` thick, centered on `y`
+    Rule { x: f32, y: f32, width: f32, thickness: f32 },
+}
+
+/// A laid-out expression: its extent around the baseline and what to draw
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MathLayout {
+    pub width: f32,
+    /// Height above the baseline
+    pub ascent: f32,
+    /// Depth below the baseline
+    pub descent: f32,
+    pub items: Vec<MathItem>,
+}
+
+impl MathLayout {
+    /// Move every item by (`dx`, `dy`). With `as_rise` the vertical shift is
+    /// applied as text rise, so the text keeps its line's text matrix.
+    fn translate(&mut self, dx: f32, dy: f32, as_rise: bool) {
+        for item in &mut self.items {
+            match item {
+                MathItem::Text { x, y, rise, .. } => {
+                    *x += dx;
+                    if as_rise {
+                        *rise += dy;
+                    } else {
+                        *y += dy;
+                    }
+                }
+                MathItem::Rule { x, y, .. } => {
+                    *x += dx;
+                    *y += dy;
+                }
+            }
+        }
+    }
+
+    /// Append `other` at horizontal offset `dx` and vertical shift `dy`
+    fn place(&mut self, mut other: MathLayout, dx: f32, dy: f32, as_rise: bool) {
+        other.translate(dx, dy, as_rise);
+        self.ascent = self.ascent.max(other.ascent + dy);
+        self.descent = self.descent.max(other.descent - dy);
+        self.items.extend(other.items);
+    }
+}
+
+/// Ascent and descent of a line of text, in em
+const TEXT_ASCENT: f32 = 0.72;
+const TEXT_DESCENT: f32 = 0.22;
+
+/// Height of the fraction bar above the baseline, in em
+const AXIS_HEIGHT: f32 = 0.28;
+
+/// Size of scripts and root indices relative to their base
+const SCRIPT_SCALE: f32 = 0.7;
+
+/// Top of the Symbol font's radical glyph and its advance, in em
+const RADICAL_TOP: f32 = 0.917;
+const RADICAL_WIDTH: f32 = 0.549;
+
+/// Parse a LaTeX math expression
+pub fn parse_math(expression: &str) -> Vec<MathNode> {
+    let mut parser = Parser { chars: expression.chars().collect(), pos: 0 };
+    parser.parse_row(None)
+}
+
+/// Parse and lay out `expression` in `font` at `size`. Display math enlarges
+/// large operators and stacks their limits.
+pub fn layout_math(expression: &str, font: &str, size: f32, display: bool) -> MathLayout {
+    let style = Style { font, size, display };
+    layout_row(&parse_math(expression), &style)
+}
+
+/// Lay out a display math block: each line is a row, except that an
+/// environment spanning several lines is laid out as one stacked row
+pub fn layout_display_rows(expression: &str, font: &str, size: f32) -> Vec<MathLayout> {
+    let mut rows = Vec::new();
+    let mut pending = String::new();
+    let mut open = 0usize;
+    for line in expression.lines() {
+        if !pending.is_empty() {
+            pending.push('\n');
+        }
+        pending.push_str(line);
+        open = (open + line.matches("\\begin{").count()).saturating_sub(line.matches("\\end{").count());
+        if open == 0 {
+            rows.push(layout_math(&pending, font, size, true));
+            pending.clear();
+        }
+    }
+    if !pending.is_empty() {
+        rows.push(layout_math(&pending, font, size, true));
+    }
+    rows
+}
+
+/// What ended a cell of an environment
+enum CellEnd {
+    Column,
+    Row,
+    Environment,
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars().enumerate().all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+    }
+
+    /// Nodes up to `close` (consumed) or the end of input
+    fn parse_row(&mut self, close: Option<char>) -> Vec<MathNode> {
+        self.parse_nodes(close, false)
+    }
+
+    /// Nodes of an environment cell, up to the `&`, `\\` or `\end` after it (not consumed)
+    fn parse_cell(&mut self) -> Vec<MathNode> {
+        self.parse_nodes(None, true)
+    }
+
+    fn parse_nodes(&mut self, close: Option<char>, cell: bool) -> Vec<MathNode> {
+        let mut nodes: Vec<MathNode> = Vec::new();
+        while let Some(c) = self.peek() {
+            if Some(c) == close {
+                self.pos += 1;
+                break;
+            }
+            if cell && (c == '&' || self.starts_with("\\\\") || self.starts_with("\\end")) {
+                break;
+            }
+            match c {
+                '{' => {
+                    self.pos += 1;
+                    nodes.push(MathNode::Row(self.parse_row(Some('}'))));
+                }
+                '^' | '_' => {
+                    self.pos += 1;
+                    let script = self.parse_argument();
+                    attach_script(&mut nodes, c == '^', script);
+                }
+                '\\' => {
+                    self.pos += 1;
+                    if let Some(node) = self.parse_command() {
+                        push_node(&mut nodes, node);
+                    }
+                }
+                c if c.is_whitespace() => {
+                    self.pos += 1;
+                    // Runs of spaces collapse, and leading space is dropped
+                    if nodes.last().is_some_and(|node| !matches!(node, MathNode::Text(text) if text.ends_with(' '))) {
+                        push_text(&mut nodes, " ");
+                    }
+                }
+                c => {
+                    self.pos += 1;
+                    push_text(&mut nodes, c.encode_utf8(&mut [0; 4]));
+                }
+            }
+        }
+        if let Some(MathNode::Text(text)) = nodes.last_mut() {
+            let trimmed = text.trim_end().len();
+            text.truncate(trimmed);
+            if text.is_empty() {
+                nodes.pop();
+            }
+        }
+        nodes
+    }
+
+    /// Consume the separator after a cell
+    fn end_cell(&mut self) -> CellEnd {
+        if self.peek() == Some('&') {
+            self.pos += 1;
+            return CellEnd::Column;
+        }
+        if self.starts_with("\\\\") {
+            self.pos += 2;
+            // An optional extra row space, like `\\[2pt]`
+            if self.peek() == Some('[') {
+                self.parse_row(Some(']'));
+            }
+            return CellEnd::Row;
+        }
+        if self.starts_with("\\end") {
+            self.pos += 4;
+            self.parse_argument();
+        }
+        CellEnd::Environment
+    }
+
+    /// The rows of cells of an environment whose `\begin{name}` has been read
+    fn parse_environment(&mut self, name: &str) -> MathNode {
+        // Column specifications are not modelled
+        if matches!(name.trim_end_matches('*'), "alignat" | "alignedat" | "array") {
+            self.parse_argument();
+        }
+        let mut rows = Vec::new();
+        let mut row = Vec::new();
+        loop {
+            row.push(self.parse_cell());
+            match self.end_cell() {
+                CellEnd::Column => {}
+                CellEnd::Row => rows.push(std::mem::take(&mut row)),
+                CellEnd::Environment => {
+                    rows.push(row);
+                    break;
+                }
+            }
+        }
+        // A trailing `\\` leaves an empty last row
+        rows.retain(|cells: &Vec<Vec<MathNode>>| cells.iter().any(|cell| !cell.is_empty()));
+        MathNode::Array { kind: ArrayKind::from_environment(name), rows }
+    }
+
+    /// A braced group, or a single command or character
+    fn parse_argument(&mut self) -> Vec<MathNode> {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some('{') => {
+                self.pos += 1;
+                self.parse_row(Some('}'))
+            }
+            Some('\\') => {
+                self.pos += 1;
+                self.parse_command().into_iter().collect()
+            }
+            Some(c) => {
+                self.pos += 1;
+                vec![MathNode::Text(c.to_string())]
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// The node for the command after a backslash, if it draws anything
+    fn parse_command(&mut self) -> Option<MathNode> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            // A control symbol: spacing or an escaped character
+            let c = self.peek()?;
+            self.pos += 1;
+            return match c {
+                ',' | ':' | ';' => Some(MathNode::Space(0.2)),
+                '!' => None,
+                '\\' => Some(MathNode::Space(1.0)),
+                c => Some(MathNode::Text(c.to_string())),
+            };
+        }
+        let name: String = self.chars[start..self.pos].iter().collect();
+        let node = match name.as_str() {
+            "frac" | "dfrac" | "tfrac" => {
+                let numerator = self.parse_argument();
+                let denominator = self.parse_argument();
+                MathNode::Fraction { numerator, denominator }
+            }
+            "sqrt" => {
+                let index = (self.peek() == Some('[')).then(|| {
+                    self.pos += 1;
+                    self.parse_row(Some(']'))
+                });
+                MathNode::Root { index, radicand: self.parse_argument() }
+            }
+            "text" | "mathrm" | "mathbf" | "mathit" | "mathbb" | "mathcal" | "mathsf" | "operatorname" => {
+                MathNode::Row(self.parse_argument())
+            }
+            "hat" | "widehat" => MathNode::Accent { mark: '^', base: self.parse_argument() },
+            "tilde" | "widetilde" => MathNode::Accent { mark: '~', base: self.parse_argument() },
+            "vec" => MathNode::Accent { mark: '\u{2192}', base: self.parse_argument() },
+            "dot" => MathNode::Accent { mark: '.', base: self.parse_argument() },
+            "bar" | "overline" => MathNode::Overline(self.parse_argument()),
+            "sum" => MathNode::LargeOperator('\u{2211}'),
+            "prod" => MathNode::LargeOperator('\u{220F}'),
+            "int" | "oint" => MathNode::LargeOperator('\u{222B}'),
+            "left" | "right" | "big" | "Big" | "bigg" | "Bigg" | "bigl" | "bigr" | "Bigl" | "Bigr" => {
+                // The delimiter is drawn at text size; `.` is an invisible one
+                if self.peek() == Some('.') {
+                    self.pos += 1;
+                }
+                return None;
+            }
+            "begin" => {
+                let name = text_of(&self.parse_argument());
+                self.parse_environment(&name)
+            }
+            "end" | "label" | "tag" => {
+                // A stray `\end`, or a label or tag that is not shown
+                self.parse_argument();
+                return None;
+            }
+            "nonumber" | "notag" => return None,
+            "quad" => MathNode::Space(1.0),
+            "qquad" => MathNode::Space(2.0),
+            name => MathNode::Text(symbol(name).unwrap_or(name).to_string()),
+        };
+        Some(node)
+    }
+}
+
+/// Characters for symbol commands. Symbols without a glyph in the standard or
+/// Symbol fonts keep an ASCII spelling.
+fn symbol(name: &str) -> Option<&'static str> {
+    const SYMBOLS: &[(&str, &str)] = &[
+        ("alpha", "\u{03B1}"), ("beta", "\u{03B2}"), ("gamma", "\u{03B3}"), ("delta", "\u{03B4}"),
+        ("epsilon", "\u{03B5}"), ("varepsilon", "\u{03B5}"), ("zeta", "\u{03B6}"), ("eta", "\u{03B7}"),
+        ("theta", "\u{03B8}"), ("vartheta", "\u{03D1}"), ("iota", "\u{03B9}"), ("kappa", "\u{03BA}"),
+        ("lambda", "\u{03BB}"), ("mu", "\u{03BC}"), ("nu", "\u{03BD}"), ("xi", "\u{03BE}"),
+        ("pi", "\u{03C0}"), ("rho", "\u{03C1}"), ("sigma", "\u{03C3}"), ("tau", "\u{03C4}"),
+        ("upsilon", "\u{03C5}"), ("phi", "\u{03C6}"), ("varphi", "\u{03D5}"), ("chi", "\u{03C7}"),
+        ("psi", "\u{03C8}"), ("omega", "\u{03C9}"),
+        ("Gamma", "\u{0393}"), ("Delta", "\u{0394}"), ("Theta", "\u{0398}"), ("Lambda", "\u{039B}"),
+        ("Xi", "\u{039E}"), ("Pi", "\u{03A0}"), ("Sigma", "\u{03A3}"), ("Phi", "\u{03A6}"),
+        ("Psi", "\u{03A8}"), ("Omega", "\u{03A9}"),
+        ("infty", "\u{221E}"), ("pm", "+/-"), ("mp", "-/+"), ("times", "x"), ("cdot", "\u{22C5}"),
+        ("div", "/"), ("neq", "\u{2260}"), ("ne", "\u{2260}"), ("leq", "\u{2264}"), ("le", "\u{2264}"),
+        ("geq", "\u{2265}"), ("ge", "\u{2265}"), ("approx", "\u{2248}"), ("sim", "\u{223C}"),
+        ("equiv", "\u{2261}"), ("propto", "\u{221D}"), ("rightarrow", "\u{2192}"), ("to", "\u{2192}"),
+        ("leftarrow", "\u{2190}"), ("Rightarrow", "\u{21D2}"), ("Leftarrow", "\u{21D0}"),
+        ("leftrightarrow", "\u{2194}"), ("Leftrightarrow", "\u{21D4}"), ("forall", "\u{2200}"),
+        ("exists", "\u{2203}"), ("in", "\u{2208}"), ("notin", "\u{2209}"), ("subset", "\u{2282}"),
+        ("supset", "\u{2283}"), ("subseteq", "\u{2286}"), ("supseteq", "\u{2287}"), ("cup", "\u{222A}"),
+        ("cap", "\u{2229}"), ("emptyset", "\u{2205}"), ("nabla", "\u{2207}"), ("partial", "\u{2202}"),
+        ("wedge", "\u{2227}"), ("land", "\u{2227}"), ("vee", "\u{2228}"), ("lor", "\u{2228}"),
+        ("oplus", "\u{2295}"), ("otimes", "\u{2297}"), ("perp", "\u{22A5}"), ("angle", "\u{2220}"),
+        ("aleph", "\u{2135}"), ("langle", "\u{2329}"), ("rangle", "\u{232A}"), ("mid", "|"),
+        ("ell", "l"), ("ldots", "..."), ("cdots", "..."), ("dots", "..."),
+    ];
+    SYMBOLS.iter().find(|(command, _)| *command == name).map(|(_, symbol)| *symbol)
+}
+
+/// The text of parsed nodes, such as an environment name
+fn text_of(nodes: &[MathNode]) -> String {
+    nodes
+        .iter()
+        .map(|node| match node {
+            MathNode::Text(text) => text.clone(),
+            MathNode::Row(nodes) => text_of(nodes),
+            _ => String::new(),
+        })
+        .collect()
+}
+
+fn push_text(nodes: &mut Vec<MathNode>, text: &str) {
+    match nodes.last_mut() {
+        Some(MathNode::Text(run)) => run.push_str(text),
+        _ => nodes.push(MathNode::Text(text.to_string())),
+    }
+}
+
+fn push_node(nodes: &mut Vec<MathNode>, node: MathNode) {
+    match node {
+        MathNode::Text(text) => push_text(nodes, &text),
+        node => nodes.push(node),
+    }
+}
+
+/// Attach a script to the last node, splitting a text run so the script
+/// belongs to its last character
+fn attach_script(nodes: &mut Vec<MathNode>, sup: bool, script: Vec<MathNode>) {
+    let base = match nodes.pop() {
+        Some(MathNode::Scripts { base, sup: None, sub }) if sup => {
+            nodes.push(MathNode::Scripts { base, sup: Some(script), sub });
+            return;
+        }
+        Some(MathNode::Scripts { base, sup: existing, sub: None }) if !sup => {
+            nodes.push(MathNode::Scripts { base, sup: existing, sub: Some(script) });
+            return;
+        }
+        Some(MathNode::Text(mut text)) => {
+            let last = text.pop().map(String::from).unwrap_or_default();
+            if !text.is_empty() {
+                nodes.push(MathNode::Text(text));
+            }
+            MathNode::Text(last)
+        }
+        Some(node) => node,
+        None => MathNode::Text(String::new()),
+    };
+    let (sup, sub) = if sup { (Some(script), None) } else { (None, Some(script)) };
+    nodes.push(MathNode::Scripts { base: Box::new(base), sup, sub });
+}
+
+#[derive(Clone, Copy)]
+struct Style<'a> {
+    font: &'a str,
+    size: f32,
+    display: bool,
+}
+
+impl Style<'_> {
+    /// Style of scripts, indices and fraction parts: smaller and never display
+    fn scaled(&self, scale: f32) -> Self {
+        Style { size: self.size * scale, display: false, ..*self }
+    }
+}
+
+fn layout_row(nodes: &[MathNode], style: &Style) -> MathLayout {
+    let mut row = MathLayout::default();
+    for node in nodes {
+        let node_layout = layout_node(node, style);
+        let width = node_layout.width;
+        let x = row.width;
+        row.place(node_layout, x, 0.0, false);
+        row.width += width;
+    }
+    row
+}
+
+fn layout_text(text: &str, style: &Style) -> MathLayout {
+    MathLayout {
+        width: font_metrics::text_width(style.font, text, style.size),
+        ascent: style.size * TEXT_ASCENT,
+        descent: style.size * TEXT_DESCENT,
+        items: vec![MathItem::Text { x: 0.0, y: 0.0, rise: 0.0, size: style.size, text: text.to_string() }],
+    }
+}
+
+fn rule_thickness(size: f32) -> f32 {
+    (size * 0.05).max(0.5)
+}
+
+/// A delimiter glyph stretched to span from `bottom` to `top`
+fn layout_delimiter(delimiter: char, bottom: f32, top: f32, style: &Style) -> MathLayout {
+    // Brackets and braces reach about this far below the baseline, and this
+    // much in all, in em
+    const DELIMITER_DEPTH: f32 = 0.21;
+    const DELIMITER_HEIGHT: f32 = 0.95;
+    let glyph_size = style.size.max((top - bottom) / DELIMITER_HEIGHT);
+    let mut layout = layout_text(&delimiter.to_string(), &Style { size: glyph_size, ..*style });
+    let baseline = (top + bottom) / 2.0 - glyph_size * (DELIMITER_HEIGHT / 2.0 - DELIMITER_DEPTH);
+    layout.translate(0.0, baseline, false);
+    layout.ascent = top.max(baseline + glyph_size * TEXT_ASCENT);
+    layout.descent = (-bottom).max(-baseline + glyph_size * TEXT_DESCENT);
+    layout
+}
+
+/// Lay out an environment's rows with their columns aligned, the block
+/// centered on the math axis
+fn layout_array(kind: ArrayKind, rows: &[Vec<Vec<MathNode>>], style: &Style) -> MathLayout {
+    let size = style.size;
+    let cell_style = match kind {
+        ArrayKind::Align | ArrayKind::Gather => *style,
+        ArrayKind::Cases | ArrayKind::Matrix { .. } => Style { display: false, ..*style },
+    };
+    let cells: Vec<Vec<MathLayout>> = rows
+        .iter()
+        .map(|row| row.iter().map(|cell| layout_row(cell, &cell_style)).collect())
+        .collect();
+    let columns = cells.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<f32> = (0..columns)
+        .map(|column| cells.iter().filter_map(|row| row.get(column)).map(|cell| cell.width).fold(0.0, f32::max))
+        .collect();
+
+    // Column x positions, then baselines going down from the first row
+    let mut column_x = Vec::with_capacity(columns);
+    let mut width = 0.0;
+    for (index, column_width) in widths.iter().enumerate() {
+        width += kind.column_gap(index) * size;
+        column_x.push(width);
+        width += column_width;
+    }
+    let mut body = MathLayout { width, ..Default::default() };
+    let mut baseline = 0.0;
+    let mut bottom = 0.0;
+    for (index, row) in cells.into_iter().enumerate() {
+        let ascent = row.iter().map(|cell| cell.ascent).fold(size * TEXT_ASCENT, f32::max);
+        let descent = row.iter().map(|cell| cell.descent).fold(size * TEXT_DESCENT, f32::max);
+        if index > 0 {
+            baseline -= ascent + size * 0.3;
+        }
+        for (column, cell) in row.into_iter().enumerate() {
+            let slack = widths[column] - cell.width;
+            let x = column_x[column]
+                + match kind.column_alignment(column) {
+                    1 => slack,
+                    0 => slack / 2.0,
+                    _ => 0.0,
+                };
+            body.place(cell, x, baseline, false);
+        }
+        baseline -= descent;
+        bottom = baseline;
+    }
+    let top = body.ascent;
+
+    // Center the block on the axis, as TeX does for an environment inside an equation
+    let shift = size * AXIS_HEIGHT - (top + bottom) / 2.0;
+    let (top, bottom) = (top + shift, bottom + shift);
+    let (open, close) = match kind {
+        ArrayKind::Cases => (Some('{'), None),
+        ArrayKind::Matrix { open, close } => (open, close),
+        ArrayKind::Align | ArrayKind::Gather => (None, None),
+    };
+    let mut layout = MathLayout::default();
+    if let Some(open) = open {
+        let delimiter = layout_delimiter(open, bottom, top, style);
+        layout.width = delimiter.width + size * 0.15;
+        layout.place(delimiter, 0.0, 0.0, false);
+    }
+    let body_width = body.width;
+    let x = layout.width;
+    layout.place(body, x, shift, false);
+    layout.width += body_width;
+    if let Some(close) = close {
+        let delimiter = layout_delimiter(close, bottom, top, style);
+        let (x, delimiter_width) = (layout.width + size * 0.15, delimiter.width);
+        layout.place(delimiter, x, 0.0, false);
+        layout.width = x + delimiter_width;
+    }
+    layout
+}
+
+fn layout_node(node: &MathNode, style: &Style) -> MathLayout {
+    let size = style.size;
+    match node {
+        MathNode::Text(text) => layout_text(text, style),
+        MathNode::Row(nodes) => layout_row(nodes, style),
+        MathNode::Space(em) => MathLayout { width: em * size, ..Default::default() },
+        MathNode::Array { kind, rows } => layout_array(*kind, rows, style),
+        MathNode::LargeOperator(symbol) => {
+            let scale = if style.display { 1.4 } else { 1.0 };
+            let mut layout = layout_text(&symbol.to_string(), &Style { size: size * scale, ..*style });
+            // Center the enlarged sign on the math axis
+            let drop = (scale - 1.0) * size * 0.3;
+            layout.translate(0.0, -drop, false);
+            layout.ascent -= drop;
+            layout.descent += drop;
+            layout
+        }
+        MathNode::Fraction { numerator, denominator } => {
+            let part = if style.display { *style } else { style.scaled(0.8) };
+            let part = Style { display: false, ..part };
+            let numerator = layout_row(numerator, &part);
+            let denominator = layout_row(denominator, &part);
+            let thickness = rule_thickness(size);
+            let gap = size * if style.display { 0.15 } else { 0.1 };
+            let padding = size * 0.1;
+            let width = numerator.width.max(denominator.width) + padding * 2.0;
+            let axis = size * AXIS_HEIGHT;
+            let numerator_shift = axis + thickness / 2.0 + gap + numerator.descent;
+            let denominator_shift = axis - thickness / 2.0 - gap - denominator.ascent;
+
+            let mut layout = MathLayout {
+                width,
+                items: vec![MathItem::Rule { x: 0.0, y: axis, width, thickness }],
+                ..Default::default()
+            };
+            let numerator_x = (width - numerator.width) / 2.0;
+            let denominator_x = (width - denominator.width) / 2.0;
+            layout.place(numerator, numerator_x, numerator_shift, false);
+            layout.place(denominator, denominator_x, denominator_shift, false);
+            layout
+        }
+        MathNode::Scripts { base, sup, sub } => {
+            let base_layout = layout_node(base, style);
+            let script_style = style.scaled(SCRIPT_SCALE);
+            let sup = sup.as_ref().map(|nodes| layout_row(nodes, &script_style));
+            let sub = sub.as_ref().map(|nodes| layout_row(nodes, &script_style));
+            let gap = size * 0.1;
+
+            if style.display && matches!(base.as_ref(), MathNode::LargeOperator(_)) {
+                // Limits stack centered above and below the operator
+                let width = base_layout.width.max(sup.as_ref().map_or(0.0, |l| l.width)).max(
+                    sub.as_ref().map_or(0.0, |l| l.width),
+                );
+                let (base_ascent, base_descent) = (base_layout.ascent, base_layout.descent);
+                let mut layout = MathLayout { width, ..Default::default() };
+                let base_width = base_layout.width;
+                layout.place(base_layout, (width - base_width) / 2.0, 0.0, false);
+                if let Some(sup) = sup {
+                    let shift = base_ascent + gap + sup.descent;
+                    let x = (width - sup.width) / 2.0;
+                    layout.place(sup, x, shift, false);
+                }
+                if let Some(sub) = sub {
+                    let shift = -(base_descent + gap + sub.ascent);
+                    let x = (width - sub.width) / 2.0;
+                    layout.place(sub, x, shift, false);
+                }
+                return layout;
+            }
+
+            // Scripts sit right of the base, moved with the text rise
+            let x = base_layout.width + size * 0.05;
+            let rise = (size * 0.4).max(base_layout.ascent - script_style.size * 0.5);
+            let drop = (size * 0.22).max(base_layout.descent * 0.8);
+            let mut layout = base_layout;
+            let mut script_width: f32 = 0.0;
+            if let Some(sup) = sup {
+                script_width = script_width.max(sup.width);
+                layout.place(sup, x, rise, true);
+            }
+            if let Some(sub) = sub {
+                script_width = script_width.max(sub.width);
+                layout.place(sub, x, -drop, true);
+            }
+            layout.width = x + script_width;
+            layout
+        }
+        MathNode::Root { index, radicand } => {
+            let radicand = layout_row(radicand, style);
+            let thickness = rule_thickness(size);
+            let gap = size * 0.12;
+            let top = radicand.ascent + gap;
+            // Grow the radical glyph until it reaches the bar over taller radicands
+            let glyph_size = size.max(top / RADICAL_TOP);
+            let glyph_y = top - RADICAL_TOP * glyph_size;
+
+            let mut layout = MathLayout::default();
+            let mut glyph_x = 0.0;
+            if let Some(index) = index {
+                let index = layout_row(index, &style.scaled(0.6));
+                glyph_x = (index.width - glyph_size * RADICAL_WIDTH * 0.5).max(0.0);
+                let shift = glyph_y + glyph_size * RADICAL_TOP * 0.55;
+                layout.place(index, 0.0, shift, false);
+            }
+            let glyph = layout_text("\u{221A}", &Style { size: glyph_size, ..*style });
+            layout.place(glyph, glyph_x, glyph_y, false);
+            let radicand_x = glyph_x + glyph_size * RADICAL_WIDTH;
+            let radicand_width = radicand.width + size * 0.1;
+            layout.place(radicand, radicand_x, 0.0, false);
+            layout.items.push(MathItem::Rule {
+                x: radicand_x,
+                y: top + thickness / 2.0,
+                width: radicand_width,
+                thickness,
+            });
+            layout.ascent = layout.ascent.max(top + thickness);
+            layout.width = radicand_x + radicand_width;
+            layout
+        }
+        MathNode::Accent { mark, base } => {
+            let base = layout_row(base, style);
+            let mark_size = if *mark == '\u{2192}' { size * SCRIPT_SCALE } else { size };
+            let mark_layout = layout_text(&mark.to_string(), &Style { size: mark_size, ..*style });
+            // Glyphs like ^ and ~ already sit high in their em box
+            let shift = match mark {
+                '\u{2192}' => base.ascent + size * 0.05 - mark_size * 0.2,
+                '.' => base.ascent + size * 0.05,
+                _ => base.ascent - size * 0.45,
+            };
+            let x = (base.width - mark_layout.width) / 2.0;
+            let mut layout = MathLayout { width: base.width, ..Default::default() };
+            layout.place(base, 0.0, 0.0, false);
+            layout.place(mark_layout, x, shift, false);
+            layout
+        }
+        MathNode::Overline(base) => {
+            let base = layout_row(base, style);
+            let thickness = rule_thickness(size);
+            let y = base.ascent + size * 0.1;
+            let mut layout = MathLayout { width: base.width, ..Default::default() };
+            layout.items.push(MathItem::Rule { x: 0.0, y, width: base.width, thickness });
+            layout.ascent = y + thickness;
+            layout.place(base, 0.0, 0.0, false);
+            layout
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scripts_attach_to_last_character() {
+        let nodes = parse_math(r"a_i b^{2}");
+        assert_eq!(nodes.len(), 3, "{:?}", nodes);
+        assert!(matches!(&nodes[0], MathNode::Scripts { base, sub: Some(_), sup: None } if **base == MathNode::Text("a".into())));
+        assert_eq!(nodes[1], MathNode::Text(" ".into()));
+        assert!(matches!(&nodes[2], MathNode::Scripts { sup: Some(_), .. }));
+    }
+
+    #[test]
+    fn test_display_limits_stack_over_operator() {
+        let layout = layout_math(r"\sum_{i=1}^{n} x", "Helvetica-Oblique", 12.0, true);
+        let texts: Vec<(&str, f32, f32)> = layout
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                MathItem::Text { text, y, rise, .. } => Some((text.as_str(), *y, *rise)),
+                _ => None,
+            })
+            .collect();
+        let y_of = |wanted: &str| texts.iter().find(|(text, _, _)| *text == wanted).unwrap().1;
+        assert!(y_of("n") > y_of("\u{2211}") && y_of("\u{2211}") > y_of("i=1"), "{:?}", texts);
+
+        // Inline, the same limits become scripts raised and lowered with the text rise
+        let layout = layout_math(r"\sum_{i=1}^{n} x", "Helvetica-Oblique", 12.0, false);
+        assert!(layout.items.iter().any(|item| matches!(item, MathItem::Text { rise, .. } if *rise > 0.0)));
+        assert!(layout.items.iter().any(|item| matches!(item, MathItem::Text { rise, .. } if *rise < 0.0)));
+    }
+
+    #[test]
+    fn test_align_rows_line_up_on_relation() {
+        let expression = "\\begin{align}\nx &= a + b \\\\\nyy &= c \\label{eq}\n\\end{align}";
+        let rows = layout_display_rows(expression, "Helvetica", 12.0);
+        assert_eq!(rows.len(), 1);
+        let texts: Vec<(&str, f32, f32)> = rows[0]
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                MathItem::Text { text, x, y, .. } => Some((text.as_str(), *x, *y)),
+                _ => None,
+            })
+            .collect();
+        assert!(texts.iter().all(|(text, _, _)| !text.contains("begin") && !text.contains("eq")), "{:?}", texts);
+        let find = |wanted: &str| *texts.iter().find(|(text, _, _)| text.starts_with(wanted)).unwrap();
+        let (first, second) = (find("= a"), find("= c"));
+        // Two lines, with the `=` signs in one column
+        assert!(first.2 > second.2, "{:?}", texts);
+        assert!((first.1 - second.1).abs() < 0.01, "{:?}", texts);
+        // The right-aligned left column ends where the relations start
+        let (_, x, _) = find("x");
+        assert!((x + font_metrics::text_width("Helvetica", "x", 12.0) - first.1).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_cases_brace_spans_rows() {
+        let layout = layout_math(r"f(x) = \begin{cases} 1 & x > 0 \\ 0 & \text{otherwise} \end{cases}", "Helvetica", 12.0, true);
+        let brace = layout
+            .items
+            .iter()
+            .find_map(|item| match item {
+                MathItem::Text { text, size, .. } if text == "{" => Some(*size),
+                _ => None,
+            })
+            .unwrap();
+        assert!(brace > 12.0);
+        assert!(layout.ascent + layout.descent > 2.0 * 12.0);
+    }
+}
@@ -313,25 +313,25 @@ impl OptimizedPdfGenerator {
     pub fn generate(&self, elements: &[crate::elements::Element], output_path: &str) -> Result<()> {
         // For now, we'll use the standard generation
         // In a full implementation, we would apply the optimization settings
-        crate::pdf_generator::create_pdf_from_elements_with_layout(
+        Ok(crate::pdf_generator::create_pdf_from_elements_with_layout(
             output_path,
             elements,
             &self.font,
             self.font_size,
             self.layout,
-        )
+        )?)
     }
 
     /// Generate a PDF from elements and return the bytes
     pub fn generate_bytes(&self, elements: &[crate::elements::Element]) -> Result<Vec<u8>> {
         // For now, we'll use the standard generation
         // In a full implementation, we would apply the optimization settings
-        crate::pdf_generator::generate_pdf_bytes(
+        Ok(crate::pdf_generator::generate_pdf_bytes(
             elements,
             &self.font,
             self.font_size,
             self.layout,
-        )
+        )?)
     }
 
     /// Get the current optimization settings
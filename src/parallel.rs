@@ -44,7 +44,7 @@ pub fn merge_pdfs_parallel<P: AsRef<Path> + Send + Sync>(input_paths: &[P], outp
 
     // Merge documents sequentially (merge operation is inherently sequential)
     let output_str = output_path.as_ref().to_str().unwrap();
-    crate::pdf_ops::merge_pdfs_sequential(&documents, output_str)
+    Ok(crate::pdf_ops::merge_pdfs_sequential(&documents, output_str)?)
 }
 
 /// Extract text from multiple PDFs in parallel
@@ -164,6 +164,7 @@ where
             let path_file = path_ref.to_str().unwrap();
 
             PdfDocument::load_from_file(path_file)
+                .map_err(anyhow::Error::from)
                 .and_then(|doc| processor(&doc))
                 .map(|result| (path_str, result))
                 .map_err(|e| anyhow::anyhow!("Failed to process {:?}: {}", path_ref, e))
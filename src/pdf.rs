@@ -1,5 +1,5 @@
 use crate::compression;
-use anyhow::Result;
+use crate::error::{PdfError, Result};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
@@ -10,6 +10,24 @@ pub struct PdfDocument {
     pub objects: HashMap<u32, PdfObject>,
     pub catalog: u32,
     pub pages: Vec<u32>,
+    /// A trailer names an `/Encrypt` dictionary
+    encrypted: bool,
+    /// Strings and streams are still encrypted: the empty user password did not open them
+    locked: bool,
+    /// A linearization dictionary opens the file
+    linearized: bool,
+}
+
+/// Document-level properties reported by [`PdfDocument::info_flags`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DocFlags {
+    pub encrypted: bool,
+    /// The catalog has a `/StructTreeRoot` and `/MarkInfo << /Marked true >>`
+    pub tagged: bool,
+    /// Organized for fast web view, with a `/Linearized` dictionary in the first 1024 bytes
+    pub linearized: bool,
+    /// The catalog has an `/AcroForm` dictionary
+    pub has_acroform: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -34,11 +52,135 @@ pub enum PdfValue {
     Reference(u32, u32),
 }
 
+impl PdfObject {
+    /// Dictionary entries of a dictionary or stream object
+    pub fn as_dict(&self) -> Option<&HashMap<String, PdfValue>> {
+        match self {
+            PdfObject::Dictionary(dict) => Some(dict),
+            PdfObject::Stream { dictionary, .. } => Some(dictionary),
+            _ => None,
+        }
+    }
+
+    pub fn as_name(&self) -> Option<&str> {
+        match self {
+            PdfObject::Name(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            PdfObject::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+impl PdfValue {
+    pub fn as_name(&self) -> Option<&str> {
+        match self {
+            PdfValue::Object(obj) => obj.as_name(),
+            PdfValue::Reference(..) => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            PdfValue::Object(obj) => obj.as_number(),
+            PdfValue::Reference(..) => None,
+        }
+    }
+
+    /// Object number of an indirect reference
+    pub fn as_reference(&self) -> Option<u32> {
+        match self {
+            PdfValue::Reference(id, _) | PdfValue::Object(PdfObject::Reference(id, _)) => Some(*id),
+            PdfValue::Object(_) => None,
+        }
+    }
+}
+
+/// The target of a named destination, as reported by [`PdfDocument::named_destinations`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Destination {
+    /// 0-based index of the target page, if it is part of this document's page tree
+    pub page_index: Option<usize>,
+    /// Fit type, e.g. `XYZ`, `Fit` or `FitH`
+    pub view: String,
+    /// Parameters following the fit type; `None` for `null` entries
+    pub params: Vec<Option<f32>>,
+}
+
+/// An occurrence of a search query, as reported by [`PdfDocument::search_text`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    /// 0-based index of the page the text is on
+    pub page_index: usize,
+    /// Approximate box around the text as `[x1, y1, x2, y2]` in text space,
+    /// from the text positioning operators and estimated glyph widths
+    pub rect: [f32; 4],
+}
+
+/// A bookmark from the document outline, as reported by [`PdfDocument::outline`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutlineEntry {
+    pub title: String,
+    /// Nesting depth, 0 for top-level entries
+    pub level: usize,
+    /// 0-based index of the target page, when it can be resolved
+    pub page_index: Option<usize>,
+    pub children: Vec<OutlineEntry>,
+}
+
+/// An interactive form field, as reported by [`PdfDocument::form_fields`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormFieldInfo {
+    /// Object number of the field dictionary
+    pub id: u32,
+    /// Fully qualified name: partial `/T` names joined with `.`
+    pub name: String,
+    /// `/FT` (`Tx`, `Btn`, `Ch` or `Sig`), inherited from parent fields
+    pub field_type: Option<String>,
+    /// `/V`, with names such as `/Yes` given without the slash
+    pub value: Option<String>,
+}
+
+/// A page annotation, as reported by [`PdfDocument::annotations`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnotationInfo {
+    /// 1-based page number
+    pub page: usize,
+    /// `/Subtype`, e.g. `Text`, `Link`, `Highlight`, `Widget`
+    pub subtype: String,
+    /// `/Rect` as `[x1, y1, x2, y2]`
+    pub rect: [f32; 4],
+    /// `/Contents` text
+    pub contents: Option<String>,
+    /// Target of a `/URI` link action
+    pub uri: Option<String>,
+    /// `/C` color, when given as RGB
+    pub color: Option<[f32; 3]>,
+}
+
+/// A font referenced from page resources, as reported by [`PdfDocument::fonts`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontUsage {
+    /// `/BaseFont` name, e.g. `Helvetica-Bold`
+    pub base_font: String,
+    /// `/Subtype`, e.g. `Type1`, `TrueType`, `Type0`
+    pub subtype: String,
+    /// Whether a `/FontFile`, `/FontFile2` or `/FontFile3` stream is present
+    pub embedded: bool,
+    /// 1-based page numbers that reference this font
+    pub pages: Vec<usize>,
+}
+
 // --- Font encoding tables ---
 
 /// WinAnsiEncoding: maps byte values 0x80..0x9F to Unicode codepoints.
 /// Standard ASCII range (0x20..0x7F) maps directly.
-fn winansi_decode(byte: u8) -> char {
+pub(crate) fn winansi_decode(byte: u8) -> char {
     match byte {
         0x80 => '\u{20AC}', // Euro sign
         0x82 => '\u{201A}', // Single low-9 quotation mark
@@ -99,11 +241,125 @@ fn macroman_decode(byte: u8) -> char {
     }
 }
 
+/// Byte for `c` in WinAnsiEncoding, if it has one
+pub(crate) fn winansi_encode(c: char) -> Option<u8> {
+    match c as u32 {
+        0x20..=0x7E | 0xA0..=0xFF => Some(c as u8),
+        _ => (0x80..=0x9Fu8).find(|&b| winansi_decode(b) == c && winansi_decode(b) as u32 != b as u32),
+    }
+}
+
+/// PDFDocEncoding, used for text strings outside content streams. It agrees
+/// with Latin-1 except for the diacritics at 0x18..0x1F, the punctuation and
+/// letters at 0x80..0x9E, the euro sign at 0xA0 and the undefined 0x9F and 0xAD.
+const PDFDOC_SPECIAL: &[(u8, char)] = &[
+    (0x18, '\u{02D8}'), (0x19, '\u{02C7}'), (0x1A, '\u{02C6}'), (0x1B, '\u{02D9}'),
+    (0x1C, '\u{02DD}'), (0x1D, '\u{02DB}'), (0x1E, '\u{02DA}'), (0x1F, '\u{02DC}'),
+    (0x80, '\u{2022}'), (0x81, '\u{2020}'), (0x82, '\u{2021}'), (0x83, '\u{2026}'),
+    (0x84, '\u{2014}'), (0x85, '\u{2013}'), (0x86, '\u{0192}'), (0x87, '\u{2044}'),
+    (0x88, '\u{2039}'), (0x89, '\u{203A}'), (0x8A, '\u{2212}'), (0x8B, '\u{2030}'),
+    (0x8C, '\u{201E}'), (0x8D, '\u{201C}'), (0x8E, '\u{201D}'), (0x8F, '\u{2018}'),
+    (0x90, '\u{2019}'), (0x91, '\u{201A}'), (0x92, '\u{2122}'), (0x93, '\u{FB01}'),
+    (0x94, '\u{FB02}'), (0x95, '\u{0141}'), (0x96, '\u{0152}'), (0x97, '\u{0160}'),
+    (0x98, '\u{0178}'), (0x99, '\u{017D}'), (0x9A, '\u{0131}'), (0x9B, '\u{0142}'),
+    (0x9C, '\u{0153}'), (0x9D, '\u{0161}'), (0x9E, '\u{017E}'), (0xA0, '\u{20AC}'),
+];
+
+/// Character for `byte` in PDFDocEncoding
+pub(crate) fn pdfdoc_decode(byte: u8) -> char {
+    match PDFDOC_SPECIAL.iter().find(|(b, _)| *b == byte) {
+        Some(&(_, c)) => c,
+        None if byte == 0x9F || byte == 0xAD => '\u{FFFD}',
+        None => byte as char,
+    }
+}
+
+/// Byte for a non-ASCII `c` in PDFDocEncoding, if it has one
+pub(crate) fn pdfdoc_encode(c: char) -> Option<u8> {
+    match c as u32 {
+        0xA1..=0xFF if c != '\u{AD}' => Some(c as u8),
+        _ => PDFDOC_SPECIAL.iter().find(|(b, special)| *special == c && *b >= 0x80).map(|&(b, _)| b),
+    }
+}
+
+/// Codes in the Symbol font's built-in encoding for Greek letters, arrows and math symbols
+const SYMBOL_CODES: &[(char, u8)] = &[
+    ('Α', 0x41), ('Β', 0x42), ('Χ', 0x43), ('Δ', 0x44), ('\u{2206}', 0x44), ('Ε', 0x45), ('Φ', 0x46),
+    ('Γ', 0x47), ('Η', 0x48), ('Ι', 0x49), ('ϑ', 0x4A), ('Κ', 0x4B), ('Λ', 0x4C), ('Μ', 0x4D),
+    ('Ν', 0x4E), ('Ο', 0x4F), ('Π', 0x50), ('Θ', 0x51), ('Ρ', 0x52), ('Σ', 0x53), ('Τ', 0x54),
+    ('Υ', 0x55), ('ς', 0x56), ('Ω', 0x57), ('\u{2126}', 0x57), ('Ξ', 0x58), ('Ψ', 0x59), ('Ζ', 0x5A),
+    ('α', 0x61), ('β', 0x62), ('χ', 0x63), ('δ', 0x64), ('ε', 0x65), ('φ', 0x66), ('γ', 0x67),
+    ('η', 0x68), ('ι', 0x69), ('ϕ', 0x6A), ('κ', 0x6B), ('λ', 0x6C), ('μ', 0x6D), ('ν', 0x6E),
+    ('ο', 0x6F), ('π', 0x70), ('θ', 0x71), ('ρ', 0x72), ('σ', 0x73), ('τ', 0x74), ('υ', 0x75),
+    ('ϖ', 0x76), ('ω', 0x77), ('ξ', 0x78), ('ψ', 0x79), ('ζ', 0x7A),
+    ('∀', 0x22), ('∃', 0x24), ('∋', 0x27), ('∗', 0x2A), ('−', 0x2D), ('≅', 0x40), ('∴', 0x5C),
+    ('⊥', 0x5E), ('∼', 0x7E), ('ϒ', 0xA1), ('≤', 0xA3), ('∞', 0xA5), ('♣', 0xA7), ('♦', 0xA8),
+    ('♥', 0xA9), ('♠', 0xAA), ('↔', 0xAB), ('←', 0xAC), ('↑', 0xAD), ('→', 0xAE), ('↓', 0xAF),
+    ('≥', 0xB3), ('∝', 0xB5), ('∂', 0xB6), ('≠', 0xB9), ('≡', 0xBA), ('≈', 0xBB), ('↵', 0xBF),
+    ('ℵ', 0xC0), ('ℑ', 0xC1), ('ℜ', 0xC2), ('℘', 0xC3), ('⊗', 0xC4), ('⊕', 0xC5), ('∅', 0xC6),
+    ('∩', 0xC7), ('∪', 0xC8), ('⊃', 0xC9), ('⊇', 0xCA), ('⊄', 0xCB), ('⊂', 0xCC), ('⊆', 0xCD),
+    ('∈', 0xCE), ('∉', 0xCF), ('∠', 0xD0), ('∇', 0xD1), ('∏', 0xD5), ('√', 0xD6), ('⋅', 0xD7),
+    ('∧', 0xD9), ('∨', 0xDA), ('⇔', 0xDB), ('⇐', 0xDC), ('⇑', 0xDD), ('⇒', 0xDE), ('⇓', 0xDF),
+    ('◊', 0xE0), ('〈', 0xE1), ('∑', 0xE5), ('〉', 0xF1), ('∫', 0xF2),
+];
+
+/// ZapfDingbats codes for characters outside the Dingbats block runs handled in [`dingbats_code`]
+const DINGBATS_CODES: &[(char, u8)] = &[
+    ('☎', 0x25), ('☛', 0x2A), ('☞', 0x2B), ('★', 0x48), ('●', 0x6C), ('■', 0x6E), ('▲', 0x73),
+    ('▼', 0x74), ('◆', 0x75), ('◗', 0x77), ('❤', 0xA4), ('❥', 0xA5), ('❦', 0xA6), ('❧', 0xA7),
+    ('➔', 0xD4),
+];
+
+/// ZapfDingbats code for `c`, if the font has its glyph
+fn dingbats_code(c: char) -> Option<u8> {
+    if let Some(&(_, code)) = DINGBATS_CODES.iter().find(|(d, _)| *d == c) {
+        return Some(code);
+    }
+    let cp = c as u32;
+    match cp {
+        // Most of U+2701..=U+275E sits at the same offset, minus the slots taken above
+        0x2701..=0x2704 | 0x2706..=0x2709 | 0x270C..=0x2727 | 0x2729..=0x274B | 0x274D
+        | 0x274F..=0x2752 | 0x2756 | 0x2758..=0x275E => Some((cp - 0x2700 + 0x20) as u8),
+        // Circled digits 1-10, plain then inverse
+        0x2460..=0x2469 => Some((cp - 0x2460 + 0xAC) as u8),
+        0x2776..=0x277F => Some((cp - 0x2776 + 0xB6) as u8),
+        _ => None,
+    }
+}
+
+/// The base-14 symbol font (`Symbol` or `ZapfDingbats`) and code that show `c`.
+/// Neither font needs embedding, as every PDF reader supplies them.
+pub(crate) fn symbol_font_encode(c: char) -> Option<(&'static str, u8)> {
+    if let Some(&(_, code)) = SYMBOL_CODES.iter().find(|(s, _)| *s == c) {
+        return Some(("Symbol", code));
+    }
+    dingbats_code(c).map(|code| ("ZapfDingbats", code))
+}
+
+/// Decode a byte of the Symbol or ZapfDingbats built-in encoding
+fn symbol_font_decode(font: &str, byte: u8) -> char {
+    let found = match font {
+        "Symbol" => SYMBOL_CODES.iter().find(|(_, code)| *code == byte).map(|(c, _)| *c),
+        _ => DINGBATS_CODES
+            .iter()
+            .find(|(_, code)| *code == byte)
+            .map(|(c, _)| *c)
+            .or_else(|| {
+                (0x2460..=0x277F)
+                    .chain(0x2701..=0x275E)
+                    .filter_map(char::from_u32)
+                    .find(|&c| dingbats_code(c) == Some(byte))
+            }),
+    };
+    found.unwrap_or(byte as char)
+}
+
 /// Decode a byte slice using the specified encoding name
 pub fn decode_with_encoding(data: &[u8], encoding: &str) -> String {
     match encoding {
         "WinAnsiEncoding" => data.iter().map(|&b| winansi_decode(b)).collect(),
         "MacRomanEncoding" => data.iter().map(|&b| macroman_decode(b)).collect(),
+        "Symbol" | "ZapfDingbats" => data.iter().map(|&b| symbol_font_decode(encoding, b)).collect(),
         _ => String::from_utf8_lossy(data).to_string(),
     }
 }
@@ -114,6 +370,8 @@ pub fn decode_with_encoding(data: &[u8], encoding: &str) -> String {
 struct TextPositionTracker {
     last_y: f32,
     threshold: f32, // Y movement threshold to insert a newline
+    /// Downward Y movement of the last call (negative when moving up)
+    last_delta: f32,
 }
 
 impl TextPositionTracker {
@@ -121,6 +379,7 @@ impl TextPositionTracker {
         TextPositionTracker {
             last_y: f32::MAX,
             threshold: 2.0,
+            last_delta: 0.0,
         }
     }
 
@@ -130,9 +389,9 @@ impl TextPositionTracker {
             self.last_y = new_y;
             return false;
         }
-        let delta = (self.last_y - new_y).abs();
+        self.last_delta = self.last_y - new_y;
         self.last_y = new_y;
-        delta > self.threshold
+        self.last_delta.abs() > self.threshold
     }
 }
 
@@ -145,6 +404,9 @@ impl PdfDocument {
             objects: HashMap::new(),
             catalog: 0,
             pages: Vec::new(),
+            encrypted: false,
+            locked: false,
+            linearized: false,
         }
     }
 
@@ -152,235 +414,1913 @@ impl PdfDocument {
         let mut file = File::open(filename)?;
         let mut buffer = Vec::new();
         file.read_to_end(&mut buffer)?;
+        Self::from_bytes(&buffer)
+    }
+
+    /// Parse a PDF held in memory. Encrypted documents without a user password
+    /// are decrypted; others load, but their text cannot be read.
+    pub fn from_bytes(buffer: &[u8]) -> Result<Self> {
+        let doc = Self::parse_bytes(buffer)?;
+        if !doc.encrypted {
+            return Ok(doc);
+        }
+        match crate::pdf_ops::decrypted_bytes(buffer, "", "document") {
+            Ok(decrypted) => {
+                let mut plain = Self::parse_bytes(&decrypted)?;
+                plain.encrypted = true;
+                plain.linearized = doc.linearized;
+                Ok(plain)
+            }
+            Err(_) => Ok(PdfDocument { locked: true, ..doc }),
+        }
+    }
 
-        let content = String::from_utf8_lossy(&buffer);
+    fn parse_bytes(buffer: &[u8]) -> Result<Self> {
+        // One char per byte so stream data survives parsing byte-for-byte
+        let content: String = buffer.iter().map(|&b| b as char).collect();
         let mut doc = PdfDocument::new();
 
         // Parse PDF header
-        if let Some(header_line) = content.lines().next() {
-            if header_line.starts_with("%PDF-") {
-                doc.version = header_line[5..].to_string();
-            }
+        if let Some(version) = content.split(['\r', '\n']).next().and_then(|l| l.strip_prefix("%PDF-")) {
+            doc.version = version.to_string();
         }
 
         parse_objects(&content, &mut doc)?;
+        doc.catalog = find_catalog(&content, &doc);
+        doc.pages = doc.collect_pages();
+        doc.encrypted = regex::Regex::new(r"trailer\s*<<[^%]*?/Encrypt\s*(\d+\s+\d+\s+R|<<)")
+            .unwrap()
+            .is_match(&content)
+            || doc.objects.values().any(|object| {
+                object.as_dict().and_then(|d| d.get("Type")).and_then(|t| t.as_name()) == Some("XRef")
+                    && object.as_dict().is_some_and(|d| d.contains_key("Encrypt"))
+            });
+        // The raw bytes: bytes of 0x80 and above take two bytes in `content`
+        let head = &buffer[..buffer.len().min(1024)];
+        doc.linearized = regex::bytes::Regex::new(r"/Linearized\s").unwrap().is_match(head);
 
         Ok(doc)
     }
 
-    pub fn get_text(&self) -> Result<String> {
-        let mut text = String::new();
-        // Matches (text) Tj — single string show
-        let tj_re = regex::Regex::new(r"\(((?:[^()\\]|\\.|(?:\([^()]*\)))*)\)\s*Tj").unwrap();
-        // Matches [...] TJ — array show (strings + kerning numbers)
-        let tj_array_re = regex::Regex::new(r"\[((?:[^\]]*?))\]\s*TJ").unwrap();
-        // Matches string elements inside a TJ array
-        let tj_str_re = regex::Regex::new(r"\(((?:[^()\\]|\\.|(?:\([^()]*\)))*)\)").unwrap();
-        // Matches Td/TD positioning operators: <x> <y> Td
-        let td_re = regex::Regex::new(r"([\d.\-]+)\s+([\d.\-]+)\s+T[dD]").unwrap();
-        // Matches Tm text matrix: a b c d e f Tm (f = y position)
-        let tm_re = regex::Regex::new(r"[\d.\-]+\s+[\d.\-]+\s+[\d.\-]+\s+[\d.\-]+\s+([\d.\-]+)\s+([\d.\-]+)\s+Tm").unwrap();
-
-        // Sort objects by ID to maintain page order
-        let mut sorted_ids: Vec<&u32> = self.objects.keys().collect();
-        sorted_ids.sort();
-
-        for obj_id in sorted_ids {
-            let obj = &self.objects[obj_id];
-            if let PdfObject::Stream { data, .. } = obj {
-                let processed_data = decompress_stream(data);
-                let content = String::from_utf8_lossy(&processed_data);
-
-                let mut tracker = TextPositionTracker::new();
-                let mut first_item_on_line = true;
-
-                // Process content stream line by line to track positioning
-                for line in content.lines() {
-                    let line = line.trim();
-
-                    // Check for Td/TD positioning BEFORE extracting text on this line
-                    if let Some(caps) = td_re.captures(line) {
-                        if let Ok(y) = caps[2].parse::<f32>() {
-                            if tracker.moved_to_new_line(y) && !text.ends_with('\n') {
-                                // Y changed significantly — likely a new line
-                                text.push('\n');
-                                first_item_on_line = true;
-                            }
-                        }
-                    }
+    /// Fetch an indirect object by its object number
+    pub fn get_object(&self, id: u32) -> Option<&PdfObject> {
+        self.objects.get(&id)
+    }
 
-                    // Check for Tm text matrix BEFORE extracting text on this line
-                    if let Some(caps) = tm_re.captures(line) {
-                        if let Ok(y) = caps[2].parse::<f32>() {
-                            if tracker.moved_to_new_line(y) && !text.ends_with('\n') {
-                                // Y changed significantly
-                                text.push('\n');
-                                first_item_on_line = true;
-                            }
+    /// Resolve a value to the object it denotes, following indirect references
+    pub fn resolve<'a>(&'a self, value: &'a PdfValue) -> Option<&'a PdfObject> {
+        match value {
+            PdfValue::Object(PdfObject::Reference(id, _)) | PdfValue::Reference(id, _) => {
+                self.objects.get(id)
+            }
+            PdfValue::Object(obj) => Some(obj),
+        }
+    }
+
+    /// Look up `key` in a dictionary and resolve the result
+    pub fn dict_get<'a>(
+        &'a self,
+        dict: &'a HashMap<String, PdfValue>,
+        key: &str,
+    ) -> Option<&'a PdfObject> {
+        dict.get(key).and_then(|v| self.resolve(v))
+    }
+
+    /// Walk the page tree from the catalog and return page object IDs in order
+    fn collect_pages(&self) -> Vec<u32> {
+        let mut pages = Vec::new();
+        let root = self
+            .objects
+            .get(&self.catalog)
+            .and_then(|c| c.as_dict())
+            .and_then(|c| c.get("Pages"))
+            .and_then(|v| v.as_reference());
+        if let Some(root) = root {
+            let mut visited = std::collections::HashSet::new();
+            self.collect_page_kids(root, &mut pages, &mut visited);
+        }
+        pages
+    }
+
+    fn collect_page_kids(
+        &self,
+        id: u32,
+        pages: &mut Vec<u32>,
+        visited: &mut std::collections::HashSet<u32>,
+    ) {
+        if !visited.insert(id) {
+            return;
+        }
+        let Some(dict) = self.objects.get(&id).and_then(|o| o.as_dict()) else {
+            return;
+        };
+        match dict.get("Type").and_then(|v| v.as_name()) {
+            Some("Pages") => {
+                if let Some(PdfObject::Array(kids)) = self.dict_get(dict, "Kids") {
+                    for kid in kids {
+                        if let Some(kid_id) = kid.as_reference() {
+                            self.collect_page_kids(kid_id, pages, visited);
                         }
                     }
+                }
+            }
+            _ => pages.push(id),
+        }
+    }
+
+    /// Look up a page attribute, honouring inheritance from ancestor `/Pages` nodes
+    pub fn page_attribute(&self, page_id: u32, key: &str) -> Option<&PdfObject> {
+        let mut current = page_id;
+        let mut visited = std::collections::HashSet::new();
+        while visited.insert(current) {
+            let dict = self.objects.get(&current)?.as_dict()?;
+            if let Some(value) = self.dict_get(dict, key) {
+                return Some(value);
+            }
+            current = dict.get("Parent")?.as_reference()?;
+        }
+        None
+    }
 
-                    // Extract (text) Tj
-                    for caps in tj_re.captures_iter(line) {
-                        let extracted = &caps[1];
-                        let unescaped = unescape_pdf_string(extracted);
-                        if !first_item_on_line && !text.ends_with(' ') && !text.ends_with('\n') {
-                            text.push(' ');
+    /// List the fonts referenced from page resources, one entry per distinct font
+    pub fn fonts(&self) -> Vec<FontUsage> {
+        let mut fonts: Vec<FontUsage> = Vec::new();
+        for (index, &page_id) in self.pages.iter().enumerate() {
+            let mut resources = Vec::new();
+            if let Some(page_resources) = self
+                .page_attribute(page_id, "Resources")
+                .and_then(|r| r.as_dict())
+            {
+                self.collect_form_resources(page_resources, &mut resources);
+            }
+
+            let mut page_fonts = Vec::new();
+            for resource in resources {
+                if let Some(font_dict) = self.dict_get(resource, "Font").and_then(|f| f.as_dict()) {
+                    let mut names: Vec<&String> = font_dict.keys().collect();
+                    names.sort();
+                    page_fonts.extend(names.into_iter().filter_map(|n| {
+                        self.dict_get(font_dict, n).and_then(|f| f.as_dict())
+                    }));
+                }
+            }
+
+            for font in page_fonts {
+                let base_font = font
+                    .get("BaseFont")
+                    .and_then(|v| v.as_name())
+                    .unwrap_or("")
+                    .to_string();
+                let subtype = font
+                    .get("Subtype")
+                    .and_then(|v| v.as_name())
+                    .unwrap_or("")
+                    .to_string();
+                let embedded = self.font_is_embedded(font);
+
+                let page = index + 1;
+                match fonts.iter_mut().find(|f| {
+                    f.base_font == base_font && f.subtype == subtype && f.embedded == embedded
+                }) {
+                    Some(existing) => {
+                        if !existing.pages.contains(&page) {
+                            existing.pages.push(page);
                         }
-                        text.push_str(&unescaped);
-                        first_item_on_line = false;
                     }
+                    None => fonts.push(FontUsage {
+                        base_font,
+                        subtype,
+                        embedded,
+                        pages: vec![page],
+                    }),
+                }
+            }
+        }
+        fonts
+    }
 
-                    // Extract [...] TJ arrays
-                    for caps in tj_array_re.captures_iter(line) {
-                        let array_content = &caps[1];
-                        for str_caps in tj_str_re.captures_iter(array_content) {
-                            let extracted = &str_caps[1];
-                            let unescaped = unescape_pdf_string(extracted);
-                            if !first_item_on_line && !text.ends_with(' ') && !text.ends_with('\n') {
-                                text.push(' ');
+    /// Gather a resource dictionary plus those of any Form XObjects it draws
+    fn collect_form_resources<'a>(
+        &'a self,
+        resources: &'a HashMap<String, PdfValue>,
+        out: &mut Vec<&'a HashMap<String, PdfValue>>,
+    ) {
+        if out.iter().any(|r| std::ptr::eq(*r, resources)) {
+            return;
+        }
+        out.push(resources);
+        if let Some(xobjects) = self.dict_get(resources, "XObject").and_then(|x| x.as_dict()) {
+            for value in xobjects.values() {
+                if let Some(form_resources) = self
+                    .resolve(value)
+                    .and_then(|x| x.as_dict())
+                    .and_then(|x| self.dict_get(x, "Resources"))
+                    .and_then(|r| r.as_dict())
+                {
+                    self.collect_form_resources(form_resources, out);
+                }
+            }
+        }
+    }
+
+    /// A font is embedded when its descriptor (or its descendant's, for Type0) carries a font file
+    fn font_is_embedded(&self, font: &HashMap<String, PdfValue>) -> bool {
+        let has_font_file = |descriptor: &HashMap<String, PdfValue>| {
+            ["FontFile", "FontFile2", "FontFile3"]
+                .iter()
+                .any(|key| descriptor.contains_key(*key))
+        };
+
+        if let Some(descriptor) = self.dict_get(font, "FontDescriptor").and_then(|d| d.as_dict()) {
+            if has_font_file(descriptor) {
+                return true;
+            }
+        }
+        if let Some(PdfObject::Array(descendants)) = self.dict_get(font, "DescendantFonts") {
+            return descendants.iter().any(|d| {
+                self.resolve(d)
+                    .and_then(|d| d.as_dict())
+                    .and_then(|d| self.dict_get(d, "FontDescriptor"))
+                    .and_then(|d| d.as_dict())
+                    .is_some_and(has_font_file)
+            });
+        }
+        false
+    }
+
+    pub fn get_text(&self) -> Result<String> {
+        self.check_readable()?;
+        Ok(self.get_text_with_line_gaps().0)
+    }
+
+    /// Strings and streams of a document that stays encrypted cannot be read as text
+    fn check_readable(&self) -> Result<()> {
+        if self.locked {
+            return Err(PdfError::Encrypted);
+        }
+        Ok(())
+    }
+
+    /// Like [`PdfDocument::get_text`], post-processed according to `options`
+    pub fn get_text_with_options(&self, options: &TextOptions) -> Result<String> {
+        let text = self.get_text()?;
+        if options.normalize_nfc {
+            use unicode_normalization::UnicodeNormalization;
+            return Ok(text.nfc().collect());
+        }
+        Ok(text)
+    }
+
+    /// URI links on each page as `(page, uri, [x1 y1 x2 y2])`, pages 1-based.
+    /// Only `/Link` annotations whose action is a `/URI` action are listed.
+    pub fn links(&self) -> Vec<(usize, String, [f32; 4])> {
+        let mut links = Vec::new();
+        for (index, &page_id) in self.pages.iter().enumerate() {
+            let Some(PdfObject::Array(annots)) = self
+                .get_object(page_id)
+                .and_then(|p| p.as_dict())
+                .and_then(|p| self.dict_get(p, "Annots"))
+            else {
+                continue;
+            };
+            for annot in annots.iter().filter_map(|a| self.resolve(a)?.as_dict()) {
+                if self.dict_get(annot, "Subtype").and_then(|s| s.as_name()) != Some("Link") {
+                    continue;
+                }
+                let Some(action) = self.dict_get(annot, "A").and_then(|a| a.as_dict()) else {
+                    continue;
+                };
+                if self.dict_get(action, "S").and_then(|s| s.as_name()) != Some("URI") {
+                    continue;
+                }
+                let Some(PdfObject::String(uri)) = self.dict_get(action, "URI") else {
+                    continue;
+                };
+                links.push((index + 1, uri.clone(), self.number_array::<4>(annot, "Rect")));
+            }
+        }
+        links
+    }
+
+    /// Every annotation of every page, in page order
+    pub fn annotations(&self) -> Vec<AnnotationInfo> {
+        let mut annotations = Vec::new();
+        for (index, &page_id) in self.pages.iter().enumerate() {
+            let Some(PdfObject::Array(annots)) = self
+                .get_object(page_id)
+                .and_then(|p| p.as_dict())
+                .and_then(|p| self.dict_get(p, "Annots"))
+            else {
+                continue;
+            };
+            for annot in annots.iter().filter_map(|a| self.resolve(a)?.as_dict()) {
+                let string = |dict, key| match self.dict_get(dict, key) {
+                    Some(PdfObject::String(s)) => Some(s.clone()),
+                    _ => None,
+                };
+                let uri = self
+                    .dict_get(annot, "A")
+                    .and_then(|a| a.as_dict())
+                    .filter(|a| self.dict_get(a, "S").and_then(|s| s.as_name()) == Some("URI"))
+                    .and_then(|a| string(a, "URI"));
+                let color = match self.dict_get(annot, "C") {
+                    Some(PdfObject::Array(c)) if c.len() == 3 => Some(self.number_array::<3>(annot, "C")),
+                    _ => None,
+                };
+                annotations.push(AnnotationInfo {
+                    page: index + 1,
+                    subtype: self.dict_get(annot, "Subtype").and_then(|s| s.as_name()).unwrap_or_default().to_string(),
+                    rect: self.number_array::<4>(annot, "Rect"),
+                    contents: string(annot, "Contents"),
+                    uri,
+                    color,
+                });
+            }
+        }
+        annotations
+    }
+
+    /// The first `N` numbers of the array at `key`, zero where missing
+    fn number_array<const N: usize>(&self, dict: &HashMap<String, PdfValue>, key: &str) -> [f32; N] {
+        let mut numbers = [0.0f32; N];
+        if let Some(PdfObject::Array(values)) = self.dict_get(dict, key) {
+            for (slot, value) in numbers.iter_mut().zip(values) {
+                *slot = self.resolve(value).and_then(|v| v.as_number()).unwrap_or(0.0) as f32;
+            }
+        }
+        numbers
+    }
+
+    /// Named destinations from the catalog's `/Names /Dests` tree and the
+    /// older `/Dests` dictionary, sorted by name
+    pub fn named_destinations(&self) -> Vec<(String, Destination)> {
+        let mut raw: Vec<(String, &PdfObject)> = Vec::new();
+        let catalog = self.get_object(self.catalog).and_then(|c| c.as_dict());
+        if let Some(tree) = catalog
+            .and_then(|c| self.dict_get(c, "Names"))
+            .and_then(|n| n.as_dict())
+            .and_then(|n| self.dict_get(n, "Dests"))
+        {
+            let mut visited = std::collections::HashSet::new();
+            self.collect_name_tree(tree, &mut raw, &mut visited);
+        }
+        if let Some(dests) = catalog.and_then(|c| self.dict_get(c, "Dests")).and_then(|d| d.as_dict()) {
+            for (name, value) in dests {
+                if let Some(target) = self.resolve(value) {
+                    raw.push((name.clone(), target));
+                }
+            }
+        }
+
+        let mut destinations: Vec<(String, Destination)> = raw
+            .into_iter()
+            .filter_map(|(name, target)| Some((name, self.parse_destination(target)?)))
+            .collect();
+        destinations.sort_by(|a, b| a.0.cmp(&b.0));
+        destinations.dedup_by(|a, b| a.0 == b.0);
+        destinations
+    }
+
+    /// Whether the document is encrypted, tagged, linearized and has a form
+    pub fn info_flags(&self) -> DocFlags {
+        let catalog = self.get_object(self.catalog).and_then(|c| c.as_dict());
+        let entry = |key: &str| catalog.and_then(|c| self.dict_get(c, key));
+        let marked = entry("MarkInfo")
+            .and_then(|m| m.as_dict())
+            .and_then(|m| self.dict_get(m, "Marked"))
+            .is_some_and(|m| matches!(m, PdfObject::Boolean(true)));
+        DocFlags {
+            encrypted: self.encrypted,
+            tagged: marked && entry("StructTreeRoot").is_some(),
+            linearized: self.linearized,
+            has_acroform: entry("AcroForm").is_some(),
+        }
+    }
+
+    /// Object numbers of the top-level fields in the catalog's `/AcroForm /Fields`
+    pub fn acroform_fields(&self) -> Vec<u32> {
+        let fields = self
+            .get_object(self.catalog)
+            .and_then(|c| c.as_dict())
+            .and_then(|c| self.dict_get(c, "AcroForm"))
+            .and_then(|a| a.as_dict())
+            .and_then(|a| self.dict_get(a, "Fields"));
+        match fields {
+            Some(PdfObject::Array(items)) => items.iter().filter_map(|v| v.as_reference()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Terminal form fields (those holding a value rather than child fields), in form order
+    pub fn form_fields(&self) -> Vec<FormFieldInfo> {
+        let mut fields = Vec::new();
+        let mut visited = std::collections::HashSet::new();
+        for id in self.acroform_fields() {
+            self.collect_form_fields(id, "", None, &mut fields, &mut visited);
+        }
+        fields
+    }
+
+    fn collect_form_fields(
+        &self,
+        id: u32,
+        prefix: &str,
+        inherited_type: Option<&str>,
+        fields: &mut Vec<FormFieldInfo>,
+        visited: &mut std::collections::HashSet<u32>,
+    ) {
+        if !visited.insert(id) {
+            return;
+        }
+        let Some(dict) = self.get_object(id).and_then(|o| o.as_dict()) else {
+            return;
+        };
+        let name = match self.dict_get(dict, "T") {
+            Some(PdfObject::String(t)) if prefix.is_empty() => t.clone(),
+            Some(PdfObject::String(t)) => format!("{}.{}", prefix, t),
+            _ => prefix.to_string(),
+        };
+        let field_type = self.dict_get(dict, "FT").and_then(|t| t.as_name()).or(inherited_type);
+
+        // Kids without a `/T` are widget annotations of this field, not child fields
+        let child_fields: Vec<u32> = match self.dict_get(dict, "Kids") {
+            Some(PdfObject::Array(kids)) => kids
+                .iter()
+                .filter_map(|k| k.as_reference())
+                .filter(|k| {
+                    self.get_object(*k)
+                        .and_then(|o| o.as_dict())
+                        .is_some_and(|d| d.contains_key("T"))
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        if !child_fields.is_empty() {
+            for kid in child_fields {
+                self.collect_form_fields(kid, &name, field_type, fields, visited);
+            }
+            return;
+        }
+
+        let value = match self.dict_get(dict, "V") {
+            Some(PdfObject::String(v)) | Some(PdfObject::Name(v)) => Some(v.clone()),
+            Some(PdfObject::Number(n)) => Some(n.to_string()),
+            _ => None,
+        };
+        fields.push(FormFieldInfo { id, name, field_type: field_type.map(str::to_string), value });
+    }
+
+    /// The document outline (bookmarks) as a tree, in reading order
+    pub fn outline(&self) -> Vec<OutlineEntry> {
+        let Some(first) = self
+            .get_object(self.catalog)
+            .and_then(|c| c.as_dict())
+            .and_then(|c| self.dict_get(c, "Outlines"))
+            .and_then(|o| o.as_dict())
+            .and_then(|o| o.get("First"))
+        else {
+            return Vec::new();
+        };
+        let named: HashMap<String, Destination> = self.named_destinations().into_iter().collect();
+        let mut visited = std::collections::HashSet::new();
+        self.collect_outline_items(first, 0, &named, &mut visited)
+    }
+
+    /// Read an outline item and its following siblings, descending into `/First`
+    fn collect_outline_items(
+        &self,
+        first: &PdfValue,
+        level: usize,
+        named: &HashMap<String, Destination>,
+        visited: &mut std::collections::HashSet<u32>,
+    ) -> Vec<OutlineEntry> {
+        let mut entries = Vec::new();
+        let mut next = first.as_reference();
+        while let Some(id) = next.filter(|id| visited.insert(*id)) {
+            let Some(item) = self.get_object(id).and_then(|o| o.as_dict()) else { break };
+            let title = match self.dict_get(item, "Title") {
+                Some(PdfObject::String(title)) => title.clone(),
+                _ => String::new(),
+            };
+            // `/Dest` directly, else the destination of a `/GoTo` action
+            let target = self.dict_get(item, "Dest").or_else(|| {
+                let action = self.dict_get(item, "A")?.as_dict()?;
+                (self.dict_get(action, "S")?.as_name()? == "GoTo").then(|| self.dict_get(action, "D"))?
+            });
+            let page_index = match target {
+                Some(PdfObject::String(name) | PdfObject::Name(name)) => {
+                    named.get(name).and_then(|d| d.page_index)
+                }
+                Some(dest) => self.parse_destination(dest).and_then(|d| d.page_index),
+                None => None,
+            };
+            let children = match item.get("First") {
+                Some(child) => self.collect_outline_items(child, level + 1, named, visited),
+                None => Vec::new(),
+            };
+            entries.push(OutlineEntry { title, level, page_index, children });
+            next = item.get("Next").and_then(|n| n.as_reference());
+        }
+        entries
+    }
+
+    /// Label of every page from the catalog's `/PageLabels` number tree, e.g.
+    /// "i", "ii", "1", "2" for roman front matter; plain page numbers when
+    /// the document has no labels
+    pub fn page_labels(&self) -> Vec<String> {
+        let mut ranges: Vec<(usize, &HashMap<String, PdfValue>)> = Vec::new();
+        if let Some(tree) = self
+            .get_object(self.catalog)
+            .and_then(|c| c.as_dict())
+            .and_then(|c| self.dict_get(c, "PageLabels"))
+        {
+            let mut visited = std::collections::HashSet::new();
+            self.collect_number_tree(tree, &mut ranges, &mut visited);
+        }
+        ranges.sort_by_key(|(start, _)| *start);
+
+        (0..self.pages.len())
+            .map(|index| match ranges.iter().rev().find(|(start, _)| *start <= index) {
+                Some((start, label)) => {
+                    let prefix = match self.dict_get(label, "P") {
+                        Some(PdfObject::String(prefix)) => prefix.as_str(),
+                        _ => "",
+                    };
+                    let first = self.dict_get(label, "St").and_then(|v| v.as_number()).unwrap_or(1.0) as u32;
+                    // A range without `/S` labels its pages with the prefix alone
+                    let number = self
+                        .dict_get(label, "S")
+                        .and_then(|s| s.as_name())
+                        .and_then(crate::pdf_generator::PageLabelStyle::from_pdf_name)
+                        .map(|style| style.format(first + (index - start) as u32))
+                        .unwrap_or_default();
+                    format!("{}{}", prefix, number)
+                }
+                None => (index + 1).to_string(),
+            })
+            .collect()
+    }
+
+    /// Collect the `/Nums` pairs of a number tree node and its `/Kids`
+    fn collect_number_tree<'a>(
+        &'a self,
+        node: &'a PdfObject,
+        out: &mut Vec<(usize, &'a HashMap<String, PdfValue>)>,
+        visited: &mut std::collections::HashSet<*const PdfObject>,
+    ) {
+        if !visited.insert(node as *const PdfObject) {
+            return;
+        }
+        let Some(dict) = node.as_dict() else { return };
+        if let Some(PdfObject::Array(pairs)) = self.dict_get(dict, "Nums") {
+            for pair in pairs.chunks(2) {
+                if let [key, value] = pair
+                    && let Some(index) = self.resolve(key).and_then(|k| k.as_number())
+                    && let Some(label) = self.resolve(value).and_then(|v| v.as_dict())
+                {
+                    out.push((index.max(0.0) as usize, label));
+                }
+            }
+        }
+        if let Some(PdfObject::Array(kids)) = self.dict_get(dict, "Kids") {
+            for kid in kids.iter().filter_map(|k| self.resolve(k)) {
+                self.collect_number_tree(kid, out, visited);
+            }
+        }
+    }
+
+    /// Collect the `/Names` pairs of a name tree node and its `/Kids`
+    fn collect_name_tree<'a>(
+        &'a self,
+        node: &'a PdfObject,
+        out: &mut Vec<(String, &'a PdfObject)>,
+        visited: &mut std::collections::HashSet<*const PdfObject>,
+    ) {
+        if !visited.insert(node as *const PdfObject) {
+            return;
+        }
+        let Some(dict) = node.as_dict() else { return };
+        if let Some(PdfObject::Array(pairs)) = self.dict_get(dict, "Names") {
+            for pair in pairs.chunks(2) {
+                if let [key, value] = pair
+                    && let Some(PdfObject::String(name)) = self.resolve(key)
+                    && let Some(target) = self.resolve(value)
+                {
+                    out.push((name.clone(), target));
+                }
+            }
+        }
+        if let Some(PdfObject::Array(kids)) = self.dict_get(dict, "Kids") {
+            for kid in kids.iter().filter_map(|k| self.resolve(k)) {
+                self.collect_name_tree(kid, out, visited);
+            }
+        }
+    }
+
+    /// Read an explicit destination array, or a dictionary holding one under `/D`
+    fn parse_destination(&self, target: &PdfObject) -> Option<Destination> {
+        let items = match target {
+            PdfObject::Array(items) => items,
+            PdfObject::Dictionary(dict) => match self.dict_get(dict, "D")? {
+                PdfObject::Array(items) => items,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let page = items.first()?;
+        let page_index = match page.as_reference() {
+            Some(id) => self.pages.iter().position(|&p| p == id),
+            // Destinations in remote documents use a page number
+            None => self.resolve(page).and_then(|n| n.as_number()).map(|n| n as usize),
+        };
+        let view = items
+            .get(1)
+            .and_then(|v| self.resolve(v))
+            .and_then(|v| v.as_name())
+            .unwrap_or("XYZ")
+            .to_string();
+        let params = items
+            .iter()
+            .skip(2)
+            .map(|v| self.resolve(v).and_then(|n| n.as_number()).map(|n| n as f32))
+            .collect();
+        Some(Destination { page_index, view, params })
+    }
+
+    /// Extract text separately for each page, in page order
+    pub fn get_text_by_page(&self) -> Result<Vec<String>> {
+        self.check_readable()?;
+        if self.pages.is_empty() {
+            return Ok(vec![self.get_text()?]);
+        }
+        let patterns = TextPatterns::new();
+        let mut gaps = Vec::new();
+        Ok(self
+            .pages
+            .iter()
+            .map(|&page_id| {
+                let mut text = String::new();
+                let resources = self.page_attribute(page_id, "Resources").and_then(|r| r.as_dict());
+                for data in self.page_contents(page_id) {
+                    self.extract_stream_text(&patterns, data, resources, &mut text, &mut gaps, 0);
+                }
+                text
+            })
+            .collect())
+    }
+
+    /// Extract text for spellcheckers and search indexes.
+    ///
+    /// Fragments shown by separate operators are joined into words by where
+    /// they are drawn, following the character and word spacing, text matrix
+    /// and `TJ` adjustments: a gap wider than a sixth of the font size is a
+    /// space and a move to another baseline a newline. Pages are separated by
+    /// a blank line.
+    pub fn get_plain_text(&self) -> Result<String> {
+        self.check_readable()?;
+        let mut out = String::new();
+        for &page_id in &self.pages {
+            let resources = self.page_attribute(page_id, "Resources").and_then(|r| r.as_dict());
+            let mut page = PlainTextWriter::default();
+            for data in self.page_contents(page_id) {
+                self.plain_stream_text(data, resources, &mut page, 0);
+            }
+            let text = page.text.trim();
+            if !text.is_empty() {
+                if !out.is_empty() {
+                    out.push_str("\n\n");
+                }
+                out.push_str(text);
+            }
+        }
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        Ok(out)
+    }
+
+    /// Find every occurrence of `query` in the page text, with its page and
+    /// an approximate bounding box. Matches do not span lines.
+    pub fn search_text(&self, query: &str) -> Vec<SearchHit> {
+        let mut hits = Vec::new();
+        if query.is_empty() {
+            return hits;
+        }
+        for (page_index, &page_id) in self.pages.iter().enumerate() {
+            let resources = self.page_attribute(page_id, "Resources").and_then(|r| r.as_dict());
+            let mut page = PlainTextWriter::default();
+            for data in self.page_contents(page_id) {
+                self.plain_stream_text(data, resources, &mut page, 0);
+            }
+            for (start, _) in page.text.match_indices(query) {
+                if let Some(rect) = page.rect_of(start, start + query.len()) {
+                    hits.push(SearchHit { page_index, rect });
+                }
+            }
+        }
+        hits
+    }
+
+    /// Glyph widths and encodings of the fonts in `resources`
+    fn plain_fonts(&self, resources: Option<&HashMap<String, PdfValue>>) -> HashMap<String, PlainFont> {
+        let mut encodings = self.font_encodings(resources);
+        let mut base_fonts = self.font_base_names(resources);
+        let Some(fonts) = resources
+            .and_then(|r| self.dict_get(r, "Font"))
+            .and_then(|f| f.as_dict())
+        else {
+            return HashMap::new();
+        };
+        fonts
+            .iter()
+            .map(|(name, value)| {
+                let font = self.resolve(value).and_then(|f| f.as_dict());
+                let number = |key| font.and_then(|f| self.dict_get(f, key)).and_then(|n| n.as_number());
+                let widths = match font.and_then(|f| self.dict_get(f, "Widths")) {
+                    Some(PdfObject::Array(widths)) => widths
+                        .iter()
+                        .map(|w| self.resolve(w).and_then(|w| w.as_number()).unwrap_or(0.0) as f32)
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                let plain = PlainFont {
+                    encoding: encodings.remove(name),
+                    base_font: base_fonts.remove(name).unwrap_or_default(),
+                    first_char: number("FirstChar").unwrap_or(0.0) as usize,
+                    widths,
+                };
+                (name.clone(), plain)
+            })
+            .collect()
+    }
+
+    /// Feed the strings one content stream shows to `writer` with their
+    /// positions, following Form XObjects it draws
+    fn plain_stream_text(
+        &self,
+        data: &[u8],
+        resources: Option<&HashMap<String, PdfValue>>,
+        writer: &mut PlainTextWriter,
+        depth: usize,
+    ) {
+        let content = decompress_stream(data);
+        let fonts = self.plain_fonts(resources);
+        let mut state = PlainTextState::default();
+        let mut font: Option<&PlainFont> = None;
+        let mut stack = Vec::new();
+
+        for (operands, operator) in crate::content_stream::Tokenizer::new(&content) {
+            let num = |i: usize| operands.get(i).and_then(|n| n.as_number()).unwrap_or(0.0) as f32;
+            match operator.as_str() {
+                "q" => stack.push(state.clone()),
+                "Q" => state = stack.pop().unwrap_or_default(),
+                "BT" => {
+                    state.tm = IDENTITY_MATRIX;
+                    state.tlm = IDENTITY_MATRIX;
+                }
+                "Tf" => {
+                    font = operands.first().and_then(|n| n.as_name()).and_then(|n| fonts.get(n));
+                    state.size = num(1);
+                }
+                "Tc" => state.char_spacing = num(0),
+                "Tw" => state.word_spacing = num(0),
+                "Tz" => state.scale = num(0) / 100.0,
+                "TL" => state.leading = num(0),
+                "Td" => state.move_line(num(0), num(1)),
+                "TD" => {
+                    state.leading = -num(1);
+                    state.move_line(num(0), num(1));
+                }
+                "Tm" => {
+                    state.tm = [num(0), num(1), num(2), num(3), num(4), num(5)];
+                    state.tlm = state.tm;
+                }
+                "T*" => state.move_line(0.0, -state.leading),
+                "Tj" | "'" | "\"" => {
+                    if operator == "\"" {
+                        state.word_spacing = num(0);
+                        state.char_spacing = num(1);
+                    }
+                    if operator != "Tj" {
+                        state.move_line(0.0, -state.leading);
+                    }
+                    if let Some(bytes) = operands.last().and_then(|s| s.as_bytes()) {
+                        state.show(bytes, font, writer);
+                    }
+                }
+                "TJ" => {
+                    let Some(crate::content_stream::Operand::Array(items)) = operands.first() else { continue };
+                    for item in items {
+                        match item {
+                            crate::content_stream::Operand::Number(adjust) => {
+                                state.advance(-(*adjust as f32) / 1000.0 * state.size * state.scale)
                             }
-                            text.push_str(&unescaped);
-                            first_item_on_line = false;
+                            crate::content_stream::Operand::String(bytes)
+                            | crate::content_stream::Operand::HexString(bytes) => state.show(bytes, font, writer),
+                            _ => {}
                         }
                     }
                 }
+                "Do" if depth < 8 => {
+                    let Some(name) = operands.first().and_then(|n| n.as_name()) else { continue };
+                    let form = resources
+                        .and_then(|r| self.dict_get(r, "XObject"))
+                        .and_then(|x| x.as_dict())
+                        .and_then(|x| self.dict_get(x, name));
+                    if let Some(PdfObject::Stream { dictionary, data }) = form
+                        && dictionary.get("Subtype").and_then(|s| s.as_name()) == Some("Form")
+                    {
+                        let form_resources = self
+                            .dict_get(dictionary, "Resources")
+                            .and_then(|r| r.as_dict())
+                            .or(resources);
+                        self.plain_stream_text(data, form_resources, writer, depth + 1);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Extract text with wrapped lines joined into paragraphs.
+    ///
+    /// Lines closer together than the document's usual line spacing are joined
+    /// with a space; larger vertical jumps, upward moves and page ends start a
+    /// new paragraph, separated by a blank line.
+    pub fn get_text_reflowed(&self) -> Result<String> {
+        self.check_readable()?;
+        let (text, gaps) = self.get_text_with_line_gaps();
+        let lines: Vec<&str> = text.lines().collect();
+
+        // Typical spacing between consecutive lines of body text
+        let mut spacings: Vec<f32> = gaps.iter().copied().filter(|g| g.is_finite() && *g > 0.0).collect();
+        spacings.sort_by(|a, b| a.total_cmp(b));
+        let typical = spacings.get(spacings.len() / 2).copied().unwrap_or(f32::INFINITY);
+
+        let mut out = String::new();
+        for (i, line) in lines.iter().enumerate() {
+            let line = line.trim();
+            if i > 0 {
+                let gap = gaps.get(i - 1).copied().unwrap_or(f32::INFINITY);
+                let joins = gap > 0.0 && gap <= typical * 1.5 && !line.is_empty() && !out.ends_with('\n');
+                if joins {
+                    if !out.is_empty() && !line.is_empty() {
+                        out.push(' ');
+                    }
+                } else if !out.is_empty() && !out.ends_with("\n\n") {
+                    out.push_str(if out.ends_with('\n') { "\n" } else { "\n\n" });
+                }
+            }
+            out.push_str(line);
+        }
+        if !out.is_empty() && !out.ends_with('\n') {
+            out.push('\n');
+        }
+        Ok(out)
+    }
+
+    /// Extract text as Markdown, inferring structure from the fonts used.
+    ///
+    /// Lines set larger than the most common (body) size become headings, the
+    /// largest size mapping to `#`; lines set entirely in a monospace font are
+    /// grouped into fenced code blocks.
+    pub fn get_markdown(&self) -> Result<String> {
+        self.markdown(false)
+    }
+
+    /// Like [`get_markdown`](Self::get_markdown), with a "## Page iv" heading
+    /// before each page giving its label from [`page_labels`](Self::page_labels)
+    pub fn get_markdown_with_page_labels(&self) -> Result<String> {
+        self.markdown(true)
+    }
+
+    fn markdown(&self, page_markers: bool) -> Result<String> {
+        self.check_readable()?;
+        let patterns = TextPatterns::new();
+        let mut lines = Vec::new();
+        let mut markers = Vec::new();
+        let labels = if page_markers { self.page_labels() } else { Vec::new() };
+        for (index, &page_id) in self.pages.iter().enumerate() {
+            if let Some(label) = labels.get(index) {
+                markers.push((lines.len(), label.clone()));
+            }
+            let resources = self.page_attribute(page_id, "Resources").and_then(|r| r.as_dict());
+            for data in self.page_contents(page_id) {
+                self.collect_styled_lines(&patterns, data, resources, &mut lines, 0);
+            }
+        }
+        if self.pages.is_empty() {
+            let mut sorted_ids: Vec<&u32> = self.objects.keys().collect();
+            sorted_ids.sort();
+            for obj_id in sorted_ids {
+                if let PdfObject::Stream { data, .. } = &self.objects[obj_id] {
+                    self.collect_styled_lines(&patterns, data, None, &mut lines, 0);
+                }
+            }
+        }
+        Ok(styled_lines_to_markdown(&lines, &markers))
+    }
 
-                // Add newline at the end of each page's content
-                if !text.ends_with('\n') && !text.is_empty() {
-                    text.push('\n');
+    /// Append the lines shown by one content stream with their font size and
+    /// face, following Form XObjects it draws
+    fn collect_styled_lines(
+        &self,
+        patterns: &TextPatterns,
+        data: &[u8],
+        resources: Option<&HashMap<String, PdfValue>>,
+        lines: &mut Vec<StyledLine>,
+        depth: usize,
+    ) {
+        let processed_data = decompress_stream(data);
+        let content: String = processed_data.iter().map(|&b| b as char).collect();
+        let encodings = self.font_encodings(resources);
+        let base_fonts = self.font_base_names(resources);
+        let mut encoding: Option<&str> = None;
+        let mut size = 0.0f32;
+        let mut monospace = false;
+        let mut tracker = TextPositionTracker::new();
+        let mut current = StyledLine::default();
+
+        for line in content.lines() {
+            let line = line.trim();
+            let y = patterns
+                .td
+                .captures(line)
+                .or_else(|| patterns.tm.captures(line))
+                .and_then(|caps| caps[2].parse::<f32>().ok());
+            if let Some(y) = y
+                && tracker.moved_to_new_line(y)
+            {
+                lines.push(std::mem::take(&mut current));
+            }
+
+            let mut events: Vec<(usize, TextEvent)> = Vec::new();
+            for caps in patterns.tf.captures_iter(line) {
+                size = caps[2].parse().unwrap_or(size);
+                events.push((caps.get(0).unwrap().start(), TextEvent::Font(caps[1].to_string())));
+            }
+            for caps in patterns.tj.captures_iter(line) {
+                events.push((caps.get(0).unwrap().start(), TextEvent::Show(vec![caps[1].to_string()])));
+            }
+            for caps in patterns.tj_array.captures_iter(line) {
+                let parts = patterns.tj_str.captures_iter(&caps[1]).map(|c| c[1].to_string()).collect();
+                events.push((caps.get(0).unwrap().start(), TextEvent::Show(parts)));
+            }
+            for caps in patterns.do_op.captures_iter(line) {
+                events.push((caps.get(0).unwrap().start(), TextEvent::Draw(caps[1].to_string())));
+            }
+            events.sort_by_key(|(pos, _)| *pos);
+
+            for (_, event) in events {
+                match event {
+                    TextEvent::Font(name) => {
+                        encoding = encodings.get(&name).map(String::as_str);
+                        let base = base_fonts.get(&name).unwrap_or(&name);
+                        monospace = base.contains("Courier") || base.contains("Mono");
+                    }
+                    TextEvent::Show(parts) => {
+                        for part in parts {
+                            let shown = decode_shown_string(&unescape_pdf_string(&part), encoding);
+                            // Code is shown token by token, spaces included
+                            if !monospace {
+                                if shown.trim().is_empty() {
+                                    continue;
+                                }
+                                if !current.text.is_empty() && !current.text.ends_with(' ') {
+                                    current.text.push(' ');
+                                }
+                            }
+                            current.monospace = if current.text.is_empty() { monospace } else { current.monospace && monospace };
+                            current.text.push_str(&shown);
+                            current.size = current.size.max(size);
+                        }
+                    }
+                    TextEvent::Draw(name) => {
+                        if depth >= 8 {
+                            continue;
+                        }
+                        let form = resources
+                            .and_then(|r| self.dict_get(r, "XObject"))
+                            .and_then(|x| x.as_dict())
+                            .and_then(|x| self.dict_get(x, &name));
+                        if let Some(PdfObject::Stream { dictionary, data }) = form
+                            && dictionary.get("Subtype").and_then(|s| s.as_name()) == Some("Form")
+                        {
+                            let form_resources = self
+                                .dict_get(dictionary, "Resources")
+                                .and_then(|r| r.as_dict())
+                                .or(resources);
+                            lines.push(std::mem::take(&mut current));
+                            self.collect_styled_lines(patterns, data, form_resources, lines, depth + 1);
+                        }
+                    }
+                }
+            }
+        }
+        lines.push(current);
+    }
+
+    /// Map each font resource name to its `/BaseFont`
+    fn font_base_names(&self, resources: Option<&HashMap<String, PdfValue>>) -> HashMap<String, String> {
+        let Some(fonts) = resources
+            .and_then(|r| self.dict_get(r, "Font"))
+            .and_then(|f| f.as_dict())
+        else {
+            return HashMap::new();
+        };
+        fonts
+            .iter()
+            .filter_map(|(name, value)| {
+                let font = self.resolve(value)?.as_dict()?;
+                let base = self.dict_get(font, "BaseFont")?.as_name()?;
+                Some((name.clone(), base.to_string()))
+            })
+            .collect()
+    }
+
+    /// Extracted text plus, for each line break, the downward Y distance moved
+    /// (infinite at page and stream ends)
+    fn get_text_with_line_gaps(&self) -> (String, Vec<f32>) {
+        let mut text = String::new();
+        let mut gaps = Vec::new();
+        let patterns = TextPatterns::new();
+
+        if self.pages.is_empty() {
+            // No resolvable page tree: fall back to scanning every stream in ID order
+            let mut sorted_ids: Vec<&u32> = self.objects.keys().collect();
+            sorted_ids.sort();
+            for obj_id in sorted_ids {
+                if let PdfObject::Stream { data, .. } = &self.objects[obj_id] {
+                    self.extract_stream_text(&patterns, data, None, &mut text, &mut gaps, 0);
+                }
+            }
+            return (text, gaps);
+        }
+
+        for &page_id in &self.pages {
+            let resources = self.page_attribute(page_id, "Resources").and_then(|r| r.as_dict());
+            for data in self.page_contents(page_id) {
+                self.extract_stream_text(&patterns, data, resources, &mut text, &mut gaps, 0);
+            }
+        }
+
+        (text, gaps)
+    }
+
+    /// Decompressed content streams of the page at `page_index` (0-based),
+    /// resolved from its `/Contents` entry in drawing order
+    pub fn page_content_streams(&self, page_index: usize) -> Vec<Vec<u8>> {
+        let Some(&page_id) = self.pages.get(page_index) else {
+            return Vec::new();
+        };
+        self.page_contents(page_id).into_iter().map(|data| decompress_stream(data)).collect()
+    }
+
+    /// Whether any page shows text, as opposed to an image-only (scanned)
+    /// document whose text can only be recovered with OCR
+    pub fn has_text_layer(&self) -> bool {
+        (0..self.pages.len()).any(|index| self.page_has_text_layer(index))
+    }
+
+    /// Whether page `page_index` (0-based) shows text with `Tj`, `TJ`, `'` or
+    /// `"` in its content streams or the Form XObjects they draw
+    pub fn page_has_text_layer(&self, page_index: usize) -> bool {
+        let Some(&page_id) = self.pages.get(page_index) else {
+            return false;
+        };
+        let resources = self.page_attribute(page_id, "Resources").and_then(|r| r.as_dict());
+        self.page_contents(page_id).into_iter().any(|data| self.stream_shows_text(data, resources, 0))
+    }
+
+    fn stream_shows_text(&self, data: &[u8], resources: Option<&HashMap<String, PdfValue>>, depth: usize) -> bool {
+        // Inline image data is skipped by the tokenizer, so it cannot pass for operators
+        for (operands, operator) in crate::content_stream::Tokenizer::new(&decompress_stream(data)) {
+            match operator.as_str() {
+                "Tj" | "TJ" | "'" | "\"" => return true,
+                "Do" if depth < 8 => {
+                    let Some(name) = operands.first().and_then(|n| n.as_name()) else { continue };
+                    let form = resources
+                        .and_then(|r| self.dict_get(r, "XObject"))
+                        .and_then(|x| x.as_dict())
+                        .and_then(|x| self.dict_get(x, name));
+                    if let Some(PdfObject::Stream { dictionary, data }) = form
+                        && dictionary.get("Subtype").and_then(|s| s.as_name()) == Some("Form")
+                    {
+                        let form_resources = self
+                            .dict_get(dictionary, "Resources")
+                            .and_then(|r| r.as_dict())
+                            .or(resources);
+                        if self.stream_shows_text(data, form_resources, depth + 1) {
+                            return true;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// Raw data of each content stream of a page, in drawing order
+    fn page_contents(&self, page_id: u32) -> Vec<&Vec<u8>> {
+        match self.page_attribute(page_id, "Contents") {
+            Some(PdfObject::Stream { data, .. }) => vec![data],
+            Some(PdfObject::Array(parts)) => parts
+                .iter()
+                .filter_map(|part| match self.resolve(part) {
+                    Some(PdfObject::Stream { data, .. }) => Some(data),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Map each font resource name to its simple-font `/Encoding` name
+    fn font_encodings(&self, resources: Option<&HashMap<String, PdfValue>>) -> HashMap<String, String> {
+        let mut encodings = HashMap::new();
+        let Some(fonts) = resources
+            .and_then(|r| self.dict_get(r, "Font"))
+            .and_then(|f| f.as_dict())
+        else {
+            return encodings;
+        };
+        for (name, value) in fonts {
+            let font = self.resolve(value).and_then(|f| f.as_dict());
+            let encoding = font.and_then(|f| self.dict_get(f, "Encoding"));
+            let encoding_name = match encoding {
+                Some(PdfObject::Name(n)) => Some(n.clone()),
+                Some(PdfObject::Dictionary(d)) => {
+                    d.get("BaseEncoding").and_then(|b| b.as_name()).map(str::to_string)
+                }
+                // The symbol fonts' built-in encodings are named after the font
+                _ => font
+                    .and_then(|f| self.dict_get(f, "BaseFont"))
+                    .and_then(|b| b.as_name())
+                    .filter(|b| matches!(*b, "Symbol" | "ZapfDingbats"))
+                    .map(str::to_string),
+            };
+            if let Some(encoding_name) = encoding_name {
+                encodings.insert(name.clone(), encoding_name);
+            }
+        }
+        encodings
+    }
+
+    /// Append the text shown by one content stream, following Form XObjects it draws
+    fn extract_stream_text(
+        &self,
+        patterns: &TextPatterns,
+        data: &[u8],
+        resources: Option<&HashMap<String, PdfValue>>,
+        text: &mut String,
+        line_gaps: &mut Vec<f32>,
+        depth: usize,
+    ) {
+        let processed_data = decompress_stream(data);
+        // Operators are ASCII; keep string bytes intact so they can be decoded per font
+        let content: String = processed_data.iter().map(|&b| b as char).collect();
+        let encodings = self.font_encodings(resources);
+        let mut encoding: Option<&str> = None;
+
+        let mut tracker = TextPositionTracker::new();
+        let mut first_item_on_line = true;
+
+        // Process content stream line by line to track positioning
+        for line in content.lines() {
+            let line = line.trim();
+
+            // Check for Td/TD positioning BEFORE extracting text on this line
+            if let Some(caps) = patterns.td.captures(line) {
+                if let Ok(y) = caps[2].parse::<f32>() {
+                    if tracker.moved_to_new_line(y) && !text.ends_with('\n') {
+                        // Y changed significantly — likely a new line
+                        text.push('\n');
+                        line_gaps.push(tracker.last_delta);
+                        first_item_on_line = true;
+                    }
+                }
+            }
+
+            // Check for Tm text matrix BEFORE extracting text on this line
+            if let Some(caps) = patterns.tm.captures(line) {
+                if let Ok(y) = caps[2].parse::<f32>() {
+                    if tracker.moved_to_new_line(y) && !text.ends_with('\n') {
+                        // Y changed significantly
+                        text.push('\n');
+                        line_gaps.push(tracker.last_delta);
+                        first_item_on_line = true;
+                    }
+                }
+            }
+
+            // Font selections, string shows and XObject draws, in stream order
+            let mut events: Vec<(usize, TextEvent)> = Vec::new();
+            for caps in patterns.tf.captures_iter(line) {
+                events.push((caps.get(0).unwrap().start(), TextEvent::Font(caps[1].to_string())));
+            }
+            for caps in patterns.tj.captures_iter(line) {
+                events.push((caps.get(0).unwrap().start(), TextEvent::Show(vec![caps[1].to_string()])));
+            }
+            for caps in patterns.tj_array.captures_iter(line) {
+                let parts = patterns
+                    .tj_str
+                    .captures_iter(&caps[1])
+                    .map(|c| c[1].to_string())
+                    .collect();
+                events.push((caps.get(0).unwrap().start(), TextEvent::Show(parts)));
+            }
+            for caps in patterns.do_op.captures_iter(line) {
+                events.push((caps.get(0).unwrap().start(), TextEvent::Draw(caps[1].to_string())));
+            }
+            events.sort_by_key(|(pos, _)| *pos);
+
+            for (_, event) in events {
+                match event {
+                    TextEvent::Font(name) => {
+                        encoding = encodings.get(&name).map(String::as_str);
+                    }
+                    TextEvent::Show(parts) => {
+                        for extracted in parts {
+                            let unescaped = decode_shown_string(&unescape_pdf_string(&extracted), encoding);
+                            if !first_item_on_line && !text.ends_with(' ') && !text.ends_with('\n') {
+                                text.push(' ');
+                            }
+                            text.push_str(&unescaped);
+                            first_item_on_line = false;
+                        }
+                    }
+                    TextEvent::Draw(name) => {
+                        if depth >= 8 {
+                            continue;
+                        }
+                        let form = resources
+                            .and_then(|r| self.dict_get(r, "XObject"))
+                            .and_then(|x| x.as_dict())
+                            .and_then(|x| self.dict_get(x, &name));
+                        if let Some(PdfObject::Stream { dictionary, data }) = form {
+                            if dictionary.get("Subtype").and_then(|s| s.as_name()) == Some("Form") {
+                                let form_resources = self
+                                    .dict_get(dictionary, "Resources")
+                                    .and_then(|r| r.as_dict())
+                                    .or(resources);
+                                self.extract_stream_text(patterns, data, form_resources, text, line_gaps, depth + 1);
+                                first_item_on_line = true;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // Add newline at the end of each page's content
+        if !text.ends_with('\n') && !text.is_empty() {
+            text.push('\n');
+            line_gaps.push(f32::INFINITY);
+        }
+    }
+}
+
+/// One line of shown text with the largest font size used on it
+#[derive(Debug, Default)]
+struct StyledLine {
+    text: String,
+    size: f32,
+    /// Every run on the line uses a monospace font
+    monospace: bool,
+}
+
+/// Render styled lines as Markdown: sizes above the body size become heading
+/// levels (largest first, at most six) and monospace runs become code blocks.
+/// Each `(line index, label)` marker becomes a "## Page label" heading before that line.
+fn styled_lines_to_markdown(all_lines: &[StyledLine], markers: &[(usize, String)]) -> String {
+    let lines: Vec<&StyledLine> = all_lines.iter().filter(|l| !l.text.trim().is_empty()).collect();
+
+    // Body size is the size covering the most characters
+    let mut size_weights: Vec<(f32, usize)> = Vec::new();
+    for line in &lines {
+        match size_weights.iter_mut().find(|(s, _)| (*s - line.size).abs() < 0.5) {
+            Some((_, weight)) => *weight += line.text.len(),
+            None => size_weights.push((line.size, line.text.len())),
+        }
+    }
+    let body = size_weights.iter().max_by_key(|(_, w)| *w).map(|(s, _)| *s).unwrap_or(0.0);
+    let mut heading_sizes: Vec<f32> = size_weights.iter().map(|(s, _)| *s).filter(|s| *s > body * 1.1).collect();
+    heading_sizes.sort_by(|a, b| b.total_cmp(a));
+
+    let mut out = String::new();
+    let mut in_code = false;
+    let mut pending = markers.iter().peekable();
+    let mut emit_markers = |out: &mut String, in_code: &mut bool, before: usize| {
+        while let Some((_, label)) = pending.next_if(|(at, _)| *at <= before) {
+            if *in_code {
+                out.push_str("```\n");
+                *in_code = false;
+            }
+            if !out.is_empty() && !out.ends_with("\n\n") {
+                out.push('\n');
+            }
+            out.push_str(&format!("## Page {}\n\n", label));
+        }
+    };
+    for (index, line) in all_lines.iter().enumerate() {
+        emit_markers(&mut out, &mut in_code, index);
+        if line.text.trim().is_empty() {
+            continue;
+        }
+        if line.monospace != in_code {
+            if !in_code && !out.is_empty() && !out.ends_with("\n\n") {
+                out.push('\n');
+            }
+            out.push_str(if in_code { "```\n\n" } else { "```\n" });
+            in_code = line.monospace;
+        }
+        if in_code {
+            out.push_str(&line.text);
+            out.push('\n');
+            continue;
+        }
+        match heading_sizes.iter().position(|s| (*s - line.size).abs() < 0.5) {
+            Some(level) => {
+                if !out.is_empty() && !out.ends_with("\n\n") {
+                    out.push('\n');
+                }
+                out.push_str(&format!("{} {}\n\n", "#".repeat((level + 1).min(6)), line.text.trim()));
+            }
+            None => {
+                out.push_str(line.text.trim());
+                out.push('\n');
+            }
+        }
+    }
+    emit_markers(&mut out, &mut in_code, usize::MAX);
+    if in_code {
+        out.push_str("```\n");
+    }
+    out
+}
+
+const IDENTITY_MATRIX: [f32; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// What [`PdfDocument::get_plain_text`] needs of a font: how its bytes decode
+/// and how far each glyph advances
+struct PlainFont {
+    encoding: Option<String>,
+    base_font: String,
+    first_char: usize,
+    /// Glyph widths from `FirstChar` on, in 1/1000 em; empty for the standard fonts
+    widths: Vec<f32>,
+}
+
+/// Text state tracked while extracting plain text
+#[derive(Clone)]
+struct PlainTextState {
+    tm: [f32; 6],
+    tlm: [f32; 6],
+    size: f32,
+    leading: f32,
+    char_spacing: f32,
+    word_spacing: f32,
+    scale: f32,
+}
+
+impl Default for PlainTextState {
+    fn default() -> Self {
+        PlainTextState {
+            tm: IDENTITY_MATRIX,
+            tlm: IDENTITY_MATRIX,
+            size: 0.0,
+            leading: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            scale: 1.0,
+        }
+    }
+}
+
+impl PlainTextState {
+    /// Start a new line offset from the start of the current one (`Td`)
+    fn move_line(&mut self, tx: f32, ty: f32) {
+        let [a, b, c, d, e, f] = self.tlm;
+        self.tlm = [a, b, c, d, tx * a + ty * c + e, tx * b + ty * d + f];
+        self.tm = self.tlm;
+    }
+
+    /// Move along the baseline by `tx` text-space units
+    fn advance(&mut self, tx: f32) {
+        self.tm[4] += tx * self.tm[0];
+        self.tm[5] += tx * self.tm[1];
+    }
+
+    /// Decode a shown string, hand it to `writer` and advance past its glyphs
+    fn show(&mut self, bytes: &[u8], font: Option<&PlainFont>, writer: &mut PlainTextWriter) {
+        let encoding = font.and_then(|f| f.encoding.as_deref());
+        let text = decode_shown_string(&bytes.iter().map(|&b| b as char).collect::<String>(), encoding);
+        let base_font = font.map_or("Helvetica", |f| f.base_font.as_str());
+        let glyph_widths: Vec<f32> = match font {
+            Some(f) if !f.widths.is_empty() => bytes
+                .iter()
+                .map(|&b| f.widths.get((b as usize).wrapping_sub(f.first_char)).copied().unwrap_or(0.0))
+                .collect(),
+            _ => text.chars().map(|c| crate::font_metrics::char_width(base_font, c) as f32).collect(),
+        };
+        let spaces = bytes.iter().filter(|&&b| b == b' ').count() as f32;
+        let width = (glyph_widths.iter().sum::<f32>() / 1000.0 * self.size
+            + glyph_widths.len() as f32 * self.char_spacing
+            + spaces * self.word_spacing)
+            * self.scale;
+
+        let size = self.size * self.tm[2].hypot(self.tm[3]);
+        let (x, y) = (self.tm[4], self.tm[5]);
+        self.advance(width);
+        writer.show(&text, x, y, self.tm[4], if size > 0.0 { size } else { self.size });
+    }
+}
+
+/// Joins shown strings into lines of words for [`PdfDocument::get_plain_text`]
+#[derive(Default)]
+struct PlainTextWriter {
+    text: String,
+    /// End x, baseline y and font size of the last string shown
+    last: Option<(f32, f32, f32)>,
+    /// Where each shown string landed in `text` and on the page
+    spans: Vec<ShownSpan>,
+}
+
+/// A string shown on the page: its byte range in the extracted text, start
+/// and end x, baseline y and font size
+struct ShownSpan {
+    start: usize,
+    end: usize,
+    x: f32,
+    end_x: f32,
+    y: f32,
+    size: f32,
+}
+
+impl PlainTextWriter {
+    fn show(&mut self, fragment: &str, x: f32, y: f32, end_x: f32, size: f32) {
+        if fragment.is_empty() {
+            return;
+        }
+        if let Some((last_x, last_y, last_size)) = self.last {
+            let size_ref = size.max(last_size).max(1.0);
+            if (y - last_y).abs() > size_ref * 0.5 {
+                let len = self.text.trim_end_matches([' ', '\t']).len();
+                self.text.truncate(len);
+                for span in self.spans.iter_mut().rev().take_while(|s| s.end > len) {
+                    span.start = span.start.min(len);
+                    span.end = len;
+                }
+                self.text.push('\n');
+            } else if (x - last_x > size_ref / 6.0 || x < last_x - size_ref)
+                && !self.text.ends_with(char::is_whitespace)
+                && !fragment.starts_with(char::is_whitespace)
+            {
+                self.text.push(' ');
+            }
+        }
+        let start = self.text.len();
+        self.text.push_str(fragment);
+        self.spans.push(ShownSpan { start, end: self.text.len(), x, end_x, y, size });
+        self.last = Some((end_x, y, size));
+    }
+
+    /// Box around the text at bytes `start..end`, splitting each string's
+    /// width evenly between its characters
+    fn rect_of(&self, start: usize, end: usize) -> Option<[f32; 4]> {
+        let mut rect: Option<[f32; 4]> = None;
+        for span in self.spans.iter().filter(|s| s.start < end && s.end > start) {
+            let fragment = &self.text[span.start..span.end];
+            let chars = fragment.chars().count().max(1) as f32;
+            let offset = |at: usize| fragment[..at.clamp(span.start, span.end) - span.start].chars().count() as f32;
+            let per_char = (span.end_x - span.x) / chars;
+            let (x1, x2) = (span.x + per_char * offset(start), span.x + per_char * offset(end));
+            // Descenders reach about a fifth of the size below the baseline
+            let (y1, y2) = (span.y - span.size * 0.2, span.y + span.size * 0.8);
+            rect = Some(match rect {
+                Some(r) => [r[0].min(x1), r[1].min(y1), r[2].max(x2), r[3].max(y2)],
+                None => [x1, y1, x2, y2],
+            });
+        }
+        rect
+    }
+}
+
+/// Compiled regexes for the text-showing operators `get_text` understands
+struct TextPatterns {
+    /// Matches (text) Tj — single string show
+    tj: regex::Regex,
+    /// Matches [...] TJ — array show (strings + kerning numbers)
+    tj_array: regex::Regex,
+    /// Matches string elements inside a TJ array
+    tj_str: regex::Regex,
+    /// Matches Td/TD positioning operators: <x> <y> Td
+    td: regex::Regex,
+    /// Matches Tm text matrix: a b c d e f Tm (f = y position)
+    tm: regex::Regex,
+    /// Matches font selection: /Name size Tf (name and size captured)
+    tf: regex::Regex,
+    /// Matches XObject invocation: /Name Do
+    do_op: regex::Regex,
+}
+
+impl TextPatterns {
+    fn new() -> Self {
+        TextPatterns {
+            tj: regex::Regex::new(r"\(((?:[^()\\]|\\.|(?:\([^()]*\)))*)\)\s*Tj").unwrap(),
+            tj_array: regex::Regex::new(r"\[((?:[^\]]*?))\]\s*TJ").unwrap(),
+            tj_str: regex::Regex::new(r"\(((?:[^()\\]|\\.|(?:\([^()]*\)))*)\)").unwrap(),
+            td: regex::Regex::new(r"([\d.\-]+)\s+([\d.\-]+)\s+T[dD]").unwrap(),
+            tm: regex::Regex::new(r"[\d.\-]+\s+[\d.\-]+\s+[\d.\-]+\s+[\d.\-]+\s+([\d.\-]+)\s+([\d.\-]+)\s+Tm").unwrap(),
+            tf: regex::Regex::new(r"/([^\s/\[\]()<>]+)\s+([\d.]+)\s+Tf").unwrap(),
+            do_op: regex::Regex::new(r"/([^\s/\[\]()<>]+)\s+Do\b").unwrap(),
+        }
+    }
+}
+
+enum TextEvent {
+    Font(String),
+    Show(Vec<String>),
+    Draw(String),
+}
+
+/// Turn the bytes of a shown string into text using the current font's encoding.
+/// Without an explicit encoding the bytes are read as UTF-8, which is what this
+/// crate's generator writes.
+fn decode_shown_string(unescaped: &str, encoding: Option<&str>) -> String {
+    let bytes = latin1_bytes(unescaped);
+    match encoding {
+        Some(encoding) => decode_with_encoding(&bytes, encoding),
+        None => String::from_utf8_lossy(&bytes).to_string(),
+    }
+}
+
+/// Decompress stream data if it appears to be deflate-compressed
+fn decompress_stream(data: &[u8]) -> Vec<u8> {
+    if data.len() > 2 && data[0] == 0x78 && (data[1] == 0x9C || data[1] == 0xDA) {
+        match compression::decompress_deflate(data) {
+            Ok(decompressed) => decompressed,
+            Err(_) => data.to_vec(),
+        }
+    } else {
+        data.to_vec()
+    }
+}
+
+// --- Object parsing ---
+
+fn parse_objects(content: &str, doc: &mut PdfDocument) -> Result<()> {
+    let obj_re = regex::Regex::new(r"(\d+)\s+(\d+)\s+obj\b").unwrap();
+    let mut pos = 0;
+
+    while let Some(caps) = obj_re.captures_at(content, pos) {
+        let found = caps.get(0).unwrap();
+        pos = found.end();
+        // Only objects that start a line (LF, CRLF or CR endings)
+        let line_start = content[..found.start()].trim_end_matches([' ', '\t']);
+        if !(line_start.is_empty() || line_start.ends_with(['\n', '\r'])) {
+            continue;
+        }
+        let Ok(obj_num) = caps[1].parse::<u32>() else { continue };
+
+        // Look for endobj past any stream data, which may itself contain the keyword
+        let body = &content[pos..];
+        let search_from = match find_stream_data(body) {
+            Some((stream_pos, data_start)) if body.find("endobj").is_none_or(|e| stream_pos < e) => {
+                body[data_start..].find("endstream").map_or(data_start, |e| data_start + e)
+            }
+            _ => 0,
+        };
+        let end = body[search_from..].find("endobj").map_or(body.len(), |e| search_from + e);
+
+        let obj = parse_object_content(&body[..end])?;
+        doc.objects.insert(obj_num, obj);
+        pos += end;
+    }
+
+    Ok(())
+}
+
+/// Find the `stream` keyword of an object body and the end-of-line after it:
+/// (keyword position, data start). The keyword may be followed by LF, CRLF or CR.
+fn find_stream_data(body: &str) -> Option<(usize, usize)> {
+    let mut from = 0;
+    while let Some(at) = body[from..].find("stream").map(|i| from + i) {
+        let after = &body[at + "stream".len()..];
+        let eol = if after.starts_with("\r\n") {
+            2
+        } else if after.starts_with(['\n', '\r']) {
+            1
+        } else {
+            0
+        };
+        if eol > 0 && !body[..at].ends_with("end") {
+            return Some((at, at + "stream".len() + eol));
+        }
+        from = at + "stream".len();
+    }
+    None
+}
+
+fn parse_object_content(content: &str) -> Result<PdfObject> {
+    // Check for stream objects: dictionary followed by stream data
+    if let Some((stream_pos, data_start)) = find_stream_data(content)
+        && let Some(endstream_pos) = content[data_start..].find("endstream").map(|e| data_start + e)
+    {
+        let dict = parse_dict_entries(&content[..stream_pos]);
+        let mut data = latin1_bytes(&content[data_start..endstream_pos]);
+        // A direct /Length is exact; otherwise drop the end-of-line before endstream
+        match dict.get("Length").and_then(|l| l.as_number()) {
+            Some(len) if (len as usize) <= data.len() && data[len as usize..].iter().all(u8::is_ascii_whitespace) => {
+                data.truncate(len as usize);
+            }
+            _ => {
+                if data.ends_with(b"\n") {
+                    data.pop();
+                }
+                if data.ends_with(b"\r") {
+                    data.pop();
+                }
+            }
+        }
+
+        return Ok(PdfObject::Stream {
+            dictionary: dict,
+            data,
+        });
+    }
+
+    let content = content.trim();
+    if content.contains("stream") && content.contains("endstream") {
+        let stream_idx = content.find("stream").unwrap();
+        let endstream_idx = content.find("endstream").unwrap();
+        let data_start = stream_idx + "stream".len();
+        let data = latin1_bytes(content[data_start..endstream_idx].trim());
+
+        Ok(PdfObject::Stream {
+            dictionary: HashMap::new(),
+            data,
+        })
+    } else if content.starts_with("<<") && content.ends_with(">>") {
+        let dict = parse_dict_entries(content);
+        Ok(PdfObject::Dictionary(dict))
+    } else if content.starts_with('[') && content.ends_with(']') {
+        match ValueParser::new(content).parse_value() {
+            Some(PdfValue::Object(array)) => Ok(array),
+            _ => Ok(PdfObject::Array(Vec::new())),
+        }
+    } else if content.starts_with('(') && content.ends_with(')') {
+        Ok(PdfObject::String(
+            content[1..content.len() - 1].to_string(),
+        ))
+    } else {
+        Ok(PdfObject::String(content.to_string()))
+    }
+}
+
+/// Parse dictionary entries from << ... >> content
+pub(crate) fn parse_dict_entries(raw: &str) -> HashMap<String, PdfValue> {
+    let mut parser = ValueParser::new(raw.trim());
+    match parser.parse_value() {
+        Some(PdfValue::Object(PdfObject::Dictionary(dict))) => dict,
+        _ => HashMap::new(),
+    }
+}
+
+/// Locate the document catalog: the last trailer `/Root`, else any `/Type /Catalog` object
+fn find_catalog(content: &str, doc: &PdfDocument) -> u32 {
+    let root_re = regex::Regex::new(r"/Root\s+(\d+)\s+\d+\s+R").unwrap();
+    if let Some(id) = root_re
+        .captures_iter(content)
+        .filter_map(|caps| caps[1].parse::<u32>().ok())
+        .filter(|id| doc.objects.contains_key(id))
+        .last()
+    {
+        return id;
+    }
+    let mut ids: Vec<&u32> = doc.objects.keys().collect();
+    ids.sort();
+    ids.into_iter()
+        .find(|id| {
+            doc.objects[id]
+                .as_dict()
+                .and_then(|d| d.get("Type"))
+                .and_then(|t| t.as_name())
+                == Some("Catalog")
+        })
+        .copied()
+        .unwrap_or(0)
+}
+
+/// Recover the original bytes from text built with one char per byte
+fn latin1_bytes(s: &str) -> Vec<u8> {
+    s.chars().map(|c| c as u32 as u8).collect()
+}
+
+/// Decode a string object's bytes: UTF-16BE with BOM, then UTF-8, else PDFDocEncoding
+fn decode_text_bytes(bytes: &[u8]) -> String {
+    if bytes.starts_with(&[0xFE, 0xFF]) {
+        let units: Vec<u16> = bytes[2..]
+            .chunks(2)
+            .map(|c| u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)]))
+            .collect();
+        return String::from_utf16_lossy(&units);
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(s) => s.to_string(),
+        Err(_) => bytes.iter().map(|&b| if b < 0x80 { b as char } else { pdfdoc_decode(b) }).collect(),
+    }
+}
+
+// --- Value parsing ---
+
+/// Recursive-descent parser for PDF object syntax (dictionaries, arrays,
+/// names, strings, numbers and indirect references)
+struct ValueParser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ValueParser<'a> {
+    fn new(src: &'a str) -> Self {
+        ValueParser { bytes: src.as_bytes(), pos: 0 }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn is_delimiter(b: u8) -> bool {
+        b.is_ascii_whitespace() || b"()<>[]{}/%".contains(&b)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b) = self.peek() {
+            if b.is_ascii_whitespace() || b == 0 {
+                self.pos += 1;
+            } else if b == b'%' {
+                while let Some(c) = self.peek() {
+                    if c == b'\n' || c == b'\r' {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn read_token(&mut self) -> &'a str {
+        let start = self.pos;
+        while let Some(b) = self.peek() {
+            if Self::is_delimiter(b) {
+                break;
+            }
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.pos]).unwrap_or("")
+    }
+
+    fn parse_value(&mut self) -> Option<PdfValue> {
+        self.skip_whitespace();
+        let obj = match self.peek()? {
+            b'<' if self.bytes.get(self.pos + 1) == Some(&b'<') => {
+                self.pos += 2;
+                PdfObject::Dictionary(self.parse_dict_body())
+            }
+            b'<' => {
+                self.pos += 1;
+                let start = self.pos;
+                while self.peek().is_some_and(|b| b != b'>') {
+                    self.pos += 1;
+                }
+                let hex: String = String::from_utf8_lossy(&self.bytes[start..self.pos])
+                    .split_whitespace()
+                    .collect();
+                self.pos += 1;
+                let mut padded = hex;
+                if padded.len() % 2 == 1 {
+                    padded.push('0');
+                }
+                let bytes = compression::decode_hex_string(&padded).unwrap_or_default();
+                PdfObject::String(decode_text_bytes(&bytes))
+            }
+            b'[' => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        None => break,
+                        Some(b']') => {
+                            self.pos += 1;
+                            break;
+                        }
+                        Some(_) => match self.parse_value() {
+                            Some(v) => items.push(v),
+                            None => self.pos += 1,
+                        },
+                    }
+                }
+                PdfObject::Array(items)
+            }
+            b'(' => {
+                self.pos += 1;
+                let start = self.pos;
+                let mut depth = 1;
+                while let Some(b) = self.peek() {
+                    match b {
+                        b'\\' => self.pos += 1,
+                        b'(' => depth += 1,
+                        b')' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        _ => {}
+                    }
+                    self.pos += 1;
+                }
+                let raw = String::from_utf8_lossy(&self.bytes[start..self.pos.min(self.bytes.len())]);
+                self.pos += 1;
+                PdfObject::String(decode_text_bytes(&latin1_bytes(&unescape_pdf_string(&raw))))
+            }
+            b'/' => {
+                self.pos += 1;
+                PdfObject::Name(self.read_token().to_string())
+            }
+            b'>' | b']' | b')' | b'{' | b'}' => return None,
+            _ => {
+                let token = self.read_token();
+                match token {
+                    "" => return None,
+                    "true" => PdfObject::Boolean(true),
+                    "false" => PdfObject::Boolean(false),
+                    "null" => PdfObject::Null,
+                    _ => match token.parse::<f64>() {
+                        Ok(n) => {
+                            if let Some(reference) = self.try_reference(token) {
+                                return Some(reference);
+                            }
+                            PdfObject::Number(n)
+                        }
+                        Err(_) => PdfObject::String(token.to_string()),
+                    },
                 }
             }
-        }
-
-        Ok(text)
+        };
+        Some(PdfValue::Object(obj))
     }
-}
 
-/// Decompress stream data if it appears to be deflate-compressed
-fn decompress_stream(data: &[u8]) -> Vec<u8> {
-    if data.len() > 2 && data[0] == 0x78 && (data[1] == 0x9C || data[1] == 0xDA) {
-        match compression::decompress_deflate(data) {
-            Ok(decompressed) => decompressed,
-            Err(_) => data.to_vec(),
+    /// After reading an integer, check for the `<gen> R` tail of an indirect reference
+    fn try_reference(&mut self, first: &str) -> Option<PdfValue> {
+        let id = first.parse::<u32>().ok()?;
+        let saved = self.pos;
+        self.skip_whitespace();
+        let generation = self.read_token().parse::<u32>().ok();
+        self.skip_whitespace();
+        let marker = self.read_token();
+        match generation {
+            Some(generation) if marker == "R" => Some(PdfValue::Reference(id, generation)),
+            _ => {
+                self.pos = saved;
+                None
+            }
         }
-    } else {
-        data.to_vec()
     }
-}
 
-// --- Object parsing ---
-
-fn parse_objects(content: &str, doc: &mut PdfDocument) -> Result<()> {
-    let obj_re = regex::Regex::new(r"(\d+)\s+(\d+)\s+obj\b").unwrap();
-    let lines: Vec<&str> = content.lines().collect();
-    let mut i = 0;
-
-    while i < lines.len() {
-        let line = lines[i].trim();
-
-        if let Some(caps) = obj_re.captures(line) {
-            // Only match if the line is exactly "N G obj" (possibly with trailing whitespace)
-            let full_match = caps.get(0).unwrap().as_str();
-            if line == full_match || line.starts_with(full_match) {
-                if let (Ok(obj_num), Ok(_gen_num)) =
-                    (caps[1].parse::<u32>(), caps[2].parse::<u32>())
-                {
-                    i += 1;
-                    let mut obj_content = String::new();
-
-                    while i < lines.len() && !lines[i].trim().starts_with("endobj") {
-                        obj_content.push_str(lines[i]);
-                        obj_content.push('\n');
-                        i += 1;
+    fn parse_dict_body(&mut self) -> HashMap<String, PdfValue> {
+        let mut dict = HashMap::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None => break,
+                Some(b'>') => {
+                    self.pos += 2;
+                    break;
+                }
+                Some(b'/') => {
+                    self.pos += 1;
+                    let key = self.read_token().to_string();
+                    if let Some(value) = self.parse_value() {
+                        dict.insert(key, value);
+                    }
+                }
+                Some(_) => {
+                    // Skip stray tokens so malformed input cannot stall the parser
+                    if self.parse_value().is_none() {
+                        self.pos += 1;
                     }
-
-                    let obj = parse_object_content(&obj_content)?;
-                    doc.objects.insert(obj_num, obj);
                 }
             }
         }
-        i += 1;
-    }
-
-    Ok(())
-}
-
-fn parse_object_content(content: &str) -> Result<PdfObject> {
-    let content = content.trim();
-
-    // Check for stream objects: dictionary followed by stream data
-    if let (Some(stream_pos), Some(endstream_pos)) =
-        (content.find("\nstream\n"), content.find("\nendstream"))
-    {
-        let dict_part = content[..stream_pos].trim();
-        let data_start = stream_pos + "\nstream\n".len();
-        let data = content[data_start..endstream_pos].as_bytes().to_vec();
-
-        let dict = parse_dict_entries(dict_part);
-
-        Ok(PdfObject::Stream {
-            dictionary: dict,
-            data,
-        })
-    } else if content.contains("stream") && content.contains("endstream") {
-        let stream_idx = content.find("stream").unwrap();
-        let endstream_idx = content.find("endstream").unwrap();
-        let data_start = stream_idx + "stream".len();
-        let data = content[data_start..endstream_idx]
-            .trim()
-            .as_bytes()
-            .to_vec();
-
-        Ok(PdfObject::Stream {
-            dictionary: HashMap::new(),
-            data,
-        })
-    } else if content.starts_with("<<") && content.ends_with(">>") {
-        let dict = parse_dict_entries(content);
-        Ok(PdfObject::Dictionary(dict))
-    } else if content.starts_with('[') && content.ends_with(']') {
-        let array_content = &content[1..content.len() - 1];
-        let items = array_content
-            .split_whitespace()
-            .map(|item| PdfValue::Object(PdfObject::String(item.to_string())))
-            .collect();
-        Ok(PdfObject::Array(items))
-    } else if content.starts_with('(') && content.ends_with(')') {
-        Ok(PdfObject::String(
-            content[1..content.len() - 1].to_string(),
-        ))
-    } else {
-        Ok(PdfObject::String(content.to_string()))
+        dict
     }
 }
 
-/// Parse dictionary entries from << ... >> content
-fn parse_dict_entries(raw: &str) -> HashMap<String, PdfValue> {
-    let mut dict = HashMap::new();
-    let inner = raw
-        .trim()
-        .trim_start_matches("<<")
-        .trim_end_matches(">>");
-    let tokens: Vec<&str> = inner.split_whitespace().collect();
-    let mut i = 0;
-    while i < tokens.len() {
-        if tokens[i].starts_with('/') {
-            let key = tokens[i][1..].to_string();
-            i += 1;
-            if i < tokens.len() {
-                let val = tokens[i].to_string();
-                dict.insert(
-                    key,
-                    PdfValue::Object(PdfObject::String(val)),
-                );
-            }
-        }
-        i += 1;
-    }
-    dict
-}
-
 /// Parse a cross-reference stream (PDF 1.5+).
 ///
 /// XRef streams replace the traditional `xref` table with a compressed stream
@@ -484,6 +2424,54 @@ pub fn parse_object_stream(data: &[u8], n: usize, first: usize) -> Vec<(u32, Str
     results
 }
 
+/// Apply a chain of stream filters, in order. Image codecs (DCT, JPX, CCITT,
+/// JBIG2) end the chain and return the still-encoded image data.
+pub fn decode_filters(data: &[u8], filters: &[&str]) -> Result<Vec<u8>> {
+    let mut data = data.to_vec();
+    for filter in filters {
+        data = match *filter {
+            "FlateDecode" | "Fl" => compression::decompress_deflate(&data)
+                .map_err(|e| PdfError::Parse(format!("FlateDecode failed: {}", e)))?,
+            "ASCIIHexDecode" | "AHx" => {
+                let hex: String = String::from_utf8_lossy(&data)
+                    .chars()
+                    .take_while(|&c| c != '>')
+                    .filter(|c| !c.is_whitespace())
+                    .collect();
+                compression::decode_hex_string(&hex)
+                    .map_err(|e| PdfError::Parse(format!("ASCIIHexDecode failed: {}", e)))?
+            }
+            "DCTDecode" | "DCT" | "JPXDecode" | "CCITTFaxDecode" | "CCF" | "JBIG2Decode" => return Ok(data),
+            other => return Err(PdfError::UnsupportedFilter(other.to_string())),
+        };
+    }
+    Ok(data)
+}
+
+/// Options for [`PdfDocument::get_text_with_options`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TextOptions {
+    /// Compose base letters and combining marks (NFC), e.g. "e" + U+0301 into "é"
+    pub normalize_nfc: bool,
+}
+
+/// Options for [`validate_pdf_bytes_with_options`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValidationOptions {
+    /// Decode every stream through its /Filter chain, reporting streams that fail
+    pub decode_streams: bool,
+}
+
+/// A stream checked by validation with [`ValidationOptions::decode_streams`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamCheck {
+    pub object: u32,
+    pub filters: Vec<String>,
+    pub encoded_len: usize,
+    /// Length after decoding, or `None` when decoding failed or a filter is unsupported
+    pub decoded_len: Option<usize>,
+}
+
 /// Validation result for PDF structural checks
 #[derive(Debug, Clone)]
 pub struct PdfValidation {
@@ -492,6 +2480,8 @@ pub struct PdfValidation {
     pub warnings: Vec<String>,
     pub page_count: usize,
     pub object_count: usize,
+    /// Per-stream decode results, filled when stream decoding is enabled
+    pub streams: Vec<StreamCheck>,
 }
 
 /// Validate a PDF file's structural integrity
@@ -504,6 +2494,11 @@ pub fn validate_pdf(filename: &str) -> Result<PdfValidation> {
 
 /// Validate PDF bytes for structural integrity (library API — no filesystem needed)
 pub fn validate_pdf_bytes(data: &[u8]) -> PdfValidation {
+    validate_pdf_bytes_with_options(data, &ValidationOptions::default())
+}
+
+/// Like [`validate_pdf_bytes`], optionally decoding every stream
+pub fn validate_pdf_bytes_with_options(data: &[u8], options: &ValidationOptions) -> PdfValidation {
     let mut errors = Vec::new();
     let mut warnings = Vec::new();
 
@@ -513,7 +2508,7 @@ pub fn validate_pdf_bytes(data: &[u8]) -> PdfValidation {
     if !content.starts_with("%PDF-") {
         errors.push("Missing PDF header (%PDF-x.x)".to_string());
     } else {
-        let version_end = content.find('\n').unwrap_or(10).min(10);
+        let version_end = content.find(['\r', '\n']).unwrap_or(10).min(10);
         let version = &content[5..version_end];
         if !version.starts_with("1.") && !version.starts_with("2.") {
             warnings.push(format!("Unusual PDF version: {}", version));
@@ -527,7 +2522,7 @@ pub fn validate_pdf_bytes(data: &[u8]) -> PdfValidation {
     }
 
     // 3. Check xref table or xref stream
-    let has_xref = content.contains("\nxref\n") || content.contains("\nxref\r\n");
+    let has_xref = regex::Regex::new(r"[\r\n]xref\s").unwrap().is_match(&content);
     let has_startxref = content.contains("startxref");
     if !has_xref {
         warnings.push("No traditional xref table found (may use xref stream)".to_string());
@@ -555,9 +2550,8 @@ pub fn validate_pdf_bytes(data: &[u8]) -> PdfValidation {
     }
 
     // 7. Count page objects (/Type /Page but NOT /Type /Pages)
-    let page_re = regex::Regex::new(r"/Type\s+/Page[^s]").unwrap();
-    let page_re_eol = regex::Regex::new(r"/Type\s+/Page\s*\n").unwrap();
-    let actual_pages = page_re.find_iter(&content).count() + page_re_eol.find_iter(&content).count();
+    let page_re = regex::Regex::new(r"/Type\s*/Page\b").unwrap();
+    let actual_pages = page_re.find_iter(&content).count();
     if actual_pages == 0 {
         errors.push("No page objects found (/Type /Page)".to_string());
     }
@@ -579,8 +2573,9 @@ pub fn validate_pdf_bytes(data: &[u8]) -> PdfValidation {
     }
 
     // 10. Check stream/endstream pairing
-    let stream_count = content.matches("\nstream\n").count()
-        + content.matches("\nstream\r\n").count();
+    // The keyword may follow the dictionary directly and end in LF, CRLF or CR
+    let stream_re = regex::Regex::new(r"(^|[^A-Za-z])stream[\r\n]").unwrap();
+    let stream_count = stream_re.find_iter(&content).count();
     let endstream_count = content.matches("endstream").count();
     if stream_count != endstream_count {
         warnings.push(format!(
@@ -597,6 +2592,39 @@ pub fn validate_pdf_bytes(data: &[u8]) -> PdfValidation {
         }
     }
 
+    // 12. Check that startxref points at an xref table or xref stream object
+    if let Some(pos) = content.rfind("startxref") {
+        let offset = content[pos + "startxref".len()..]
+            .split_whitespace()
+            .next()
+            .and_then(|n| n.parse::<usize>().ok());
+        let target = offset.and_then(|offset| data.get(offset..));
+        let points_at_xref = target.is_some_and(|t| {
+            t.starts_with(b"xref")
+                || regex::bytes::Regex::new(r"^\d+\s+\d+\s+obj\b").unwrap().is_match(t)
+        });
+        if !points_at_xref {
+            errors.push(format!("startxref offset {:?} does not point at a cross-reference section", offset));
+        }
+    }
+
+    // 13. Check that indirect references point at defined objects
+    for (missing, referrer) in dangling_references(data) {
+        match referrer {
+            Some(object) => errors.push(format!("Object {} references missing object {}", object, missing)),
+            None => errors.push(format!("Trailer references missing object {}", missing)),
+        }
+    }
+
+    // 14. Decode streams through their filters
+    let mut streams = Vec::new();
+    if options.decode_streams {
+        for (object, dict, raw) in raw_streams(data) {
+            let check = check_stream(object, &dict, raw, &mut errors, &mut warnings);
+            streams.push(check);
+        }
+    }
+
     let valid = errors.is_empty();
 
     PdfValidation {
@@ -605,7 +2633,164 @@ pub fn validate_pdf_bytes(data: &[u8]) -> PdfValidation {
         warnings,
         page_count: actual_pages,
         object_count,
+        streams,
+    }
+}
+
+/// Indirect references (`N G R`) whose object is defined nowhere in the file,
+/// neither at the top level nor inside an object stream, each with the
+/// object that first refers to it (`None` for the trailer)
+fn dangling_references(data: &[u8]) -> Vec<(u32, Option<u32>)> {
+    let obj_re = regex::bytes::Regex::new(r"(\d+)\s+\d+\s+obj\b").unwrap();
+    let ref_re = regex::Regex::new(r"(\d+)\s+\d+\s+R\b").unwrap();
+    let stream_re = regex::bytes::Regex::new(r">>\s*stream(\r\n|\r|\n)").unwrap();
+    let int_re = |key: &str| regex::Regex::new(&format!(r"/{}\s+(\d+)", key)).unwrap();
+    let (n_re, first_re) = (int_re("N"), int_re("First"));
+
+    let starts: Vec<(u32, usize)> = obj_re
+        .captures_iter(data)
+        .filter_map(|c| Some((std::str::from_utf8(&c[1]).ok()?.parse().ok()?, c.get(0)?.end())))
+        .collect();
+    let mut defined: std::collections::HashSet<u32> = starts.iter().map(|&(id, _)| id).collect();
+
+    // Text of each object outside its stream data, where references can appear
+    let mut sources: Vec<(Option<u32>, String)> = Vec::new();
+    for (i, &(id, start)) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).map(|&(_, s)| s).unwrap_or(data.len());
+        let region = &data[start..end];
+        let body_end = stream_re
+            .find(region)
+            .map(|m| m.start() + 2)
+            .or_else(|| find_bytes(region, b"endobj"))
+            .unwrap_or(region.len());
+        sources.push((Some(id), String::from_utf8_lossy(&region[..body_end]).to_string()));
+    }
+    for (_, dict, raw) in raw_streams(data) {
+        if !dict.contains("/ObjStm") {
+            continue;
+        }
+        let filters: Vec<&str> = if dict.contains("/FlateDecode") { vec!["FlateDecode"] } else { Vec::new() };
+        let count = n_re.captures(&dict).and_then(|c| c[1].parse().ok());
+        let first = first_re.captures(&dict).and_then(|c| c[1].parse().ok());
+        if let (Ok(decoded), Some(count), Some(first)) = (decode_filters(raw, &filters), count, first) {
+            for (number, text) in parse_object_stream(&decoded, count, first) {
+                defined.insert(number);
+                sources.push((Some(number), text));
+            }
+        }
     }
+    let content = String::from_utf8_lossy(data);
+    for (pos, _) in content.match_indices("trailer") {
+        let rest = &content[pos..];
+        sources.push((None, rest[..rest.find("startxref").unwrap_or(rest.len())].to_string()));
+    }
+
+    let mut missing: Vec<(u32, Option<u32>)> = Vec::new();
+    for (referrer, text) in &sources {
+        for c in ref_re.captures_iter(text) {
+            let Ok(target) = c[1].parse::<u32>() else { continue };
+            if !defined.contains(&target) && !missing.iter().any(|(m, _)| *m == target) {
+                missing.push((target, *referrer));
+            }
+        }
+    }
+    missing
+}
+
+/// Locate stream objects in raw file bytes: (object number, dictionary text, encoded data)
+fn raw_streams(data: &[u8]) -> Vec<(u32, String, &[u8])> {
+    let obj_re = regex::bytes::Regex::new(r"(\d+)\s+\d+\s+obj\b").unwrap();
+    let stream_re = regex::bytes::Regex::new(r">>\s*stream(\r\n|\r|\n)").unwrap();
+    let length_re = regex::Regex::new(r"/Length\s+(\d+)(\s+\d+\s+R)?").unwrap();
+
+    let starts: Vec<(u32, usize)> = obj_re
+        .captures_iter(data)
+        .filter_map(|c| {
+            let id = std::str::from_utf8(&c[1]).ok()?.parse().ok()?;
+            Some((id, c.get(0)?.end()))
+        })
+        .collect();
+
+    let mut streams = Vec::new();
+    for (i, &(id, start)) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).map(|&(_, s)| s).unwrap_or(data.len());
+        let region = &data[start..end];
+        let Some(m) = stream_re.find(region) else { continue };
+        let dict = String::from_utf8_lossy(&region[..m.start() + 2]).to_string();
+        let body = &region[m.end()..];
+        let direct_length = length_re
+            .captures(&dict)
+            .filter(|c| c.get(2).is_none())
+            .and_then(|c| c[1].parse::<usize>().ok());
+        let raw = match direct_length {
+            Some(len) if len <= body.len() => &body[..len],
+            _ => {
+                let stop = find_bytes(body, b"endstream").unwrap_or(body.len());
+                let mut raw = &body[..stop];
+                if raw.ends_with(b"\n") {
+                    raw = &raw[..raw.len() - 1];
+                }
+                if raw.ends_with(b"\r") {
+                    raw = &raw[..raw.len() - 1];
+                }
+                raw
+            }
+        };
+        streams.push((id, dict, raw));
+    }
+    streams
+}
+
+pub(crate) fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Decode one stream, adding an error when a filter fails and a warning when
+/// the decoded size looks wrong
+fn check_stream(
+    object: u32,
+    dict: &str,
+    raw: &[u8],
+    errors: &mut Vec<String>,
+    warnings: &mut Vec<String>,
+) -> StreamCheck {
+    let filter_re = regex::Regex::new(r"/Filter\s*(\[[^\]]*\]|/[A-Za-z0-9]+)").unwrap();
+    let filters: Vec<String> = filter_re
+        .captures(dict)
+        .map(|c| c[1].split('/').skip(1).map(|f| f.trim_matches(|c: char| !c.is_alphanumeric()).to_string()).collect())
+        .unwrap_or_default();
+    let names: Vec<&str> = filters.iter().map(|f| f.as_str()).collect();
+
+    let decoded_len = match decode_filters(raw, &names) {
+        Ok(decoded) => Some(decoded.len()),
+        Err(PdfError::UnsupportedFilter(name)) => {
+            warnings.push(format!("Object {}: stream filter /{} not checked", object, name));
+            None
+        }
+        Err(e) => {
+            errors.push(format!("Object {}: stream does not decode ({})", object, e));
+            None
+        }
+    };
+
+    if let Some(len) = decoded_len {
+        let declared = regex::Regex::new(r"/DL\s+(\d+)")
+            .unwrap()
+            .captures(dict)
+            .and_then(|c| c[1].parse::<usize>().ok());
+        if let Some(declared) = declared
+            && declared != len
+        {
+            warnings.push(format!(
+                "Object {}: stream decodes to {} bytes but /DL declares {}",
+                object, len, declared
+            ));
+        } else if len == 0 && !raw.is_empty() && !filters.is_empty() {
+            warnings.push(format!("Object {}: stream decodes to no data", object));
+        }
+    }
+
+    StreamCheck { object, filters, encoded_len: raw.len(), decoded_len }
 }
 
 pub fn extract_text(filename: &str) -> Result<String> {
@@ -614,34 +2799,86 @@ pub fn extract_text(filename: &str) -> Result<String> {
     Ok(text)
 }
 
+/// Extract text with words rejoined for spellchecking, see [`PdfDocument::get_plain_text`]
+pub fn extract_plain_text(filename: &str) -> Result<String> {
+    PdfDocument::load_from_file(filename)?.get_plain_text()
+}
+
+/// Whether a PDF file has extractable text or is image-only and needs OCR,
+/// see [`PdfDocument::has_text_layer`]
+pub fn has_text_layer(filename: &str) -> Result<bool> {
+    Ok(PdfDocument::load_from_file(filename)?.has_text_layer())
+}
+
+/// List the URI links of a PDF file, see [`PdfDocument::links`]
+pub fn extract_links(filename: &str) -> Result<Vec<(usize, String, [f32; 4])>> {
+    let doc = PdfDocument::load_from_file(filename)?;
+    Ok(doc.links())
+}
+
+/// List the annotations of a PDF file, see [`PdfDocument::annotations`]
+pub fn extract_annotations(filename: &str) -> Result<Vec<AnnotationInfo>> {
+    let doc = PdfDocument::load_from_file(filename)?;
+    Ok(doc.annotations())
+}
+
+/// Find the occurrences of `query` in a PDF file, see [`PdfDocument::search_text`]
+pub fn search_text(filename: &str, query: &str) -> Result<Vec<SearchHit>> {
+    let doc = PdfDocument::load_from_file(filename)?;
+    Ok(doc.search_text(query))
+}
+
+/// Read the outline (bookmarks) of a PDF file, see [`PdfDocument::outline`]
+pub fn extract_outline(filename: &str) -> Result<Vec<OutlineEntry>> {
+    let doc = PdfDocument::load_from_file(filename)?;
+    Ok(doc.outline())
+}
+
+/// Extract text as Markdown with headings and code blocks inferred from font sizes and faces
+pub fn extract_markdown(filename: &str) -> Result<String> {
+    let doc = PdfDocument::load_from_file(filename)?;
+    doc.get_markdown()
+}
+
+/// Extract text as Markdown with a "## Page iv" heading giving each page's label
+pub fn extract_markdown_with_page_labels(filename: &str) -> Result<String> {
+    let doc = PdfDocument::load_from_file(filename)?;
+    doc.get_markdown_with_page_labels()
+}
+
 pub fn unescape_pdf_string(s: &str) -> String {
     let mut result = String::new();
-    let mut chars = s.chars();
+    let mut chars = s.chars().peekable();
     while let Some(c) = chars.next() {
         if c == '\\' {
             match chars.next() {
                 Some('n') => result.push('\n'),
                 Some('r') => result.push('\r'),
                 Some('t') => result.push('\t'),
+                Some('b') => result.push('\u{8}'),
+                Some('f') => result.push('\u{c}'),
                 Some('\\') => result.push('\\'),
                 Some('(') => result.push('('),
                 Some(')') => result.push(')'),
-                Some(d) if d.is_ascii_digit() => {
+                Some(d) if ('0'..='7').contains(&d) => {
                     // Octal escape: \NNN (1-3 digits)
-                    let mut octal = String::new();
-                    octal.push(d);
-                    // Peek at next chars for more octal digits
+                    let mut code = d.to_digit(8).unwrap_or(0);
                     for _ in 0..2 {
-                        // We can't peek with chars iterator, so we handle
-                        // this simply: only first digit captured here.
-                        // Full octal would need a peekable iterator.
-                        break;
+                        match chars.peek().and_then(|c| c.to_digit(8)) {
+                            Some(digit) => {
+                                code = code * 8 + digit;
+                                chars.next();
+                            }
+                            None => break,
+                        }
                     }
-                    if let Ok(code) = u8::from_str_radix(&octal, 8) {
-                        result.push(code as char);
-                    } else {
-                        result.push('\\');
-                        result.push(d);
+                    result.push(char::from_u32(code & 0xFF).unwrap_or('?'));
+                }
+                // A backslash before an end-of-line continues the string
+                Some('\n') => {}
+                Some('\r') => {
+                    if chars.peek() == Some(&'\n') {
+                        chars.next();
                     }
                 }
                 Some(other) => {
@@ -702,6 +2939,281 @@ mod tests {
         assert!(dict.contains_key("Length"));
     }
 
+    #[test]
+    fn test_parse_dict_entries_nested() {
+        let raw = "<< /Type /Page /Parent 3 0 R /MediaBox [0 0 612 792] /Resources << /Font << /F1 5 0 R >> >> /Title (A (nested) title) >>";
+        let dict = parse_dict_entries(raw);
+        assert_eq!(dict["Type"].as_name(), Some("Page"));
+        assert_eq!(dict["Parent"].as_reference(), Some(3));
+        match &dict["MediaBox"] {
+            PdfValue::Object(PdfObject::Array(items)) => {
+                assert_eq!(items.len(), 4);
+                assert_eq!(items[3].as_number(), Some(792.0));
+            }
+            other => panic!("expected array, got {:?}", other),
+        }
+        let resources = match &dict["Resources"] {
+            PdfValue::Object(obj) => obj.as_dict().unwrap(),
+            other => panic!("expected dictionary, got {:?}", other),
+        };
+        assert!(matches!(resources["Font"], PdfValue::Object(PdfObject::Dictionary(_))));
+        assert!(matches!(&dict["Title"], PdfValue::Object(PdfObject::String(t)) if t == "A (nested) title"));
+    }
+
+    #[test]
+    fn test_get_object_catalog() {
+        let elements = vec![crate::elements::Element::Paragraph { text: "Inspect me".into() }];
+        let layout = crate::pdf_generator::PageLayout::portrait();
+        let pdf_bytes = crate::pdf_generator::generate_pdf_bytes(&elements, "Helvetica", 12.0, layout).unwrap();
+        let doc = PdfDocument::from_bytes(&pdf_bytes).unwrap();
+
+        let catalog = doc.get_object(doc.catalog).and_then(|o| o.as_dict()).unwrap();
+        assert_eq!(catalog.get("Type").and_then(|t| t.as_name()), Some("Catalog"));
+        assert!(doc.get_object(9999).is_none());
+    }
+
+    #[test]
+    fn test_named_destinations() {
+        let mut generator = crate::pdf_generator::PdfGenerator::new();
+        let pages_id = 3;
+        let first = generator.add_object(format!("<< /Type /Page /Parent {} 0 R /MediaBox [0 0 612 792] >>\n", pages_id));
+        let second = generator.add_object(format!("<< /Type /Page /Parent {} 0 R /MediaBox [0 0 612 792] >>\n", pages_id));
+        generator.add_object(format!("<< /Type /Pages /Kids [{} 0 R {} 0 R] /Count 2 >>\n", first, second));
+        let leaf = generator.add_object(format!("<< /Names [(chapter-2) [{} 0 R /XYZ 72 700 null]] >>\n", second));
+        let tree = generator.add_object(format!("<< /Kids [{} 0 R] >>\n", leaf));
+        generator.add_object(format!(
+            "<< /Type /Catalog /Pages {} 0 R /Names << /Dests {} 0 R >> /Dests << /intro << /D [{} 0 R /Fit] >> >> >>\n",
+            pages_id, tree, first
+        ));
+        let doc = PdfDocument::from_bytes(&generator.generate()).unwrap();
+
+        let dests = doc.named_destinations();
+        assert_eq!(dests.len(), 2);
+        assert_eq!(dests[0].0, "chapter-2");
+        assert_eq!(dests[0].1.page_index, Some(1));
+        assert_eq!(dests[0].1.view, "XYZ");
+        assert_eq!(dests[0].1.params, vec![Some(72.0), Some(700.0), None]);
+        assert_eq!(dests[1].0, "intro");
+        assert_eq!(dests[1].1.page_index, Some(0));
+        assert_eq!(dests[1].1.view, "Fit");
+    }
+
+    #[test]
+    fn test_outline_nested_entries() {
+        let mut generator = crate::pdf_generator::PdfGenerator::new();
+        let pages_id = 4;
+        let mut page_ids = Vec::new();
+        for _ in 0..3 {
+            page_ids.push(generator.add_object(format!("<< /Type /Page /Parent {} 0 R /MediaBox [0 0 612 792] >>\n", pages_id)));
+        }
+        let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+        generator.add_object(format!("<< /Type /Pages /Kids [{}] /Count 3 >>\n", kids.join(" ")));
+        // Outline items 6..=9 under the root 5: Intro, Chapter (Section), Appendix
+        let (root, intro, chapter, section, appendix) = (5, 6, 7, 8, 9);
+        generator.add_object(format!("<< /Type /Outlines /First {} 0 R /Last {} 0 R /Count 4 >>\n", intro, appendix));
+        generator.add_object(format!(
+            "<< /Title (Intro) /Parent {} 0 R /Next {} 0 R /Dest [{} 0 R /Fit] >>\n",
+            root, chapter, page_ids[0]
+        ));
+        generator.add_object(format!(
+            "<< /Title (Chapter 1) /Parent {} 0 R /Prev {} 0 R /Next {} 0 R /First {} 0 R /Last {} 0 R /Count 1 \
+             /A << /S /GoTo /D [{} 0 R /XYZ 0 792 null] >> >>\n",
+            root, intro, appendix, section, section, page_ids[1]
+        ));
+        generator.add_object(format!(
+            "<< /Title <FEFF00530065006300740069006F006E00200031002E0031> /Parent {} 0 R /Dest (sec-1-1) >>\n",
+            chapter
+        ));
+        generator.add_object(format!("<< /Title (Appendix) /Parent {} 0 R /Prev {} 0 R >>\n", root, chapter));
+        let tree = generator.add_object(format!("<< /Names [(sec-1-1) [{} 0 R /Fit]] >>\n", page_ids[2]));
+        generator.add_object(format!(
+            "<< /Type /Catalog /Pages {} 0 R /Outlines {} 0 R /Names << /Dests {} 0 R >> >>\n",
+            pages_id, root, tree
+        ));
+        let doc = PdfDocument::from_bytes(&generator.generate()).unwrap();
+
+        let outline = doc.outline();
+        let titles: Vec<&str> = outline.iter().map(|e| e.title.as_str()).collect();
+        assert_eq!(titles, vec!["Intro", "Chapter 1", "Appendix"]);
+        assert_eq!(outline[0].page_index, Some(0));
+        assert_eq!(outline[1].page_index, Some(1));
+        assert_eq!(outline[2].page_index, None);
+        assert_eq!(
+            outline[1].children,
+            vec![OutlineEntry { title: "Section 1.1".into(), level: 1, page_index: Some(2), children: vec![] }]
+        );
+    }
+
+    #[test]
+    fn test_markdown_infers_headings_and_code() {
+        let markdown = "# Title\n\nSome body text that is long enough to be the body size.\n\n## Section\n\nMore body text here.\n\n```\nlet x = 1;\n```\n";
+        let elements = crate::elements::parse_markdown(markdown);
+        let layout = crate::pdf_generator::PageLayout::portrait();
+        let pdf_bytes = crate::pdf_generator::generate_pdf_bytes(&elements, "Helvetica", 12.0, layout).unwrap();
+        let doc = PdfDocument::from_bytes(&pdf_bytes).unwrap();
+
+        let extracted = doc.get_markdown().unwrap();
+        assert!(extracted.contains("# Title\n"), "{}", extracted);
+        assert!(extracted.contains("## Section\n"), "{}", extracted);
+        assert!(extracted.contains("```\nlet x = 1;\n```"), "{}", extracted);
+        assert!(extracted.contains("\nMore body text here.\n"), "{}", extracted);
+    }
+
+    #[test]
+    fn test_markdown_page_label_markers() {
+        use crate::pdf_generator::{PageLabelRange, PageLabelStyle};
+        let options = crate::pdf_generator::RenderOptions {
+            page_labels: vec![
+                PageLabelRange::new(1, PageLabelStyle::LowerRoman),
+                PageLabelRange::new(3, PageLabelStyle::Decimal),
+            ],
+            ..Default::default()
+        };
+        let elements = crate::elements::parse_markdown("Preface\n\n\\pagebreak\n\nContents\n\n\\pagebreak\n\nChapter\n");
+        let layout = crate::pdf_generator::PageLayout::portrait();
+        let pdf_bytes = crate::pdf_generator::generate_pdf_bytes_with_render_options(&elements, "Helvetica", 12.0, layout, &options).unwrap();
+        let doc = PdfDocument::from_bytes(&pdf_bytes).unwrap();
+        assert_eq!(doc.page_labels(), vec!["i", "ii", "1"]);
+
+        let extracted = doc.get_markdown_with_page_labels().unwrap();
+        let preface = extracted.find("## Page i\n").expect(&extracted);
+        let contents = extracted.find("## Page ii\n").expect(&extracted);
+        let chapter = extracted.find("## Page 1\n").expect(&extracted);
+        assert!(preface < contents && contents < chapter, "{}", extracted);
+        assert!(extracted[contents..chapter].contains("Contents"), "{}", extracted);
+        assert!(!doc.get_markdown().unwrap().contains("## Page"));
+    }
+
+    #[test]
+    fn test_fonts_generated_pdf() {
+        let elements = vec![
+            crate::elements::Element::Heading { level: 1, text: "Fonts".into() },
+            crate::elements::Element::Paragraph { text: "Body text.".into() },
+        ];
+        let layout = crate::pdf_generator::PageLayout::portrait();
+        let pdf_bytes = crate::pdf_generator::generate_pdf_bytes(&elements, "Helvetica", 12.0, layout).unwrap();
+        let doc = PdfDocument::from_bytes(&pdf_bytes).unwrap();
+
+        assert_eq!(doc.pages.len(), 1);
+        let fonts = doc.fonts();
+        for name in ["Helvetica", "Helvetica-Bold", "Helvetica-Oblique", "Helvetica-BoldOblique", "Courier"] {
+            let font = fonts.iter().find(|f| f.base_font == name)
+                .unwrap_or_else(|| panic!("{} not listed in {:?}", name, fonts));
+            assert_eq!(font.subtype, "Type1");
+            assert!(!font.embedded);
+            assert_eq!(font.pages, vec![1]);
+        }
+    }
+
+    #[test]
+    fn test_fonts_embedded() {
+        let mut generator = crate::pdf_generator::PdfGenerator::new();
+        let font_file = generator.add_stream_object("<< /Length 4 >>\n".to_string(), b"true".to_vec());
+        let descriptor = generator.add_object(format!(
+            "<< /Type /FontDescriptor /FontName /DejaVuSans /FontFile2 {} 0 R >>\n",
+            font_file
+        ));
+        let font = generator.add_object(format!(
+            "<< /Type /Font /Subtype /TrueType /BaseFont /DejaVuSans /FontDescriptor {} 0 R >>\n",
+            descriptor
+        ));
+        let data = b"BT /F1 12 Tf (Hi) Tj ET".to_vec();
+        let content = generator.add_stream_object(format!("<< /Length {} >>\n", data.len()), data);
+        let pages_id = generator.next_id + 1;
+        let page = generator.add_object(format!(
+            "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 612 792] /Contents {} 0 R /Resources << /Font << /F1 {} 0 R >> >> >>\n",
+            pages_id, content, font
+        ));
+        generator.add_object(format!("<< /Type /Pages /Kids [{} 0 R] /Count 1 >>\n", page));
+        generator.add_object(format!("<< /Type /Catalog /Pages {} 0 R >>\n", pages_id));
+
+        let doc = PdfDocument::from_bytes(&generator.generate()).unwrap();
+        let fonts = doc.fonts();
+        assert_eq!(fonts.len(), 1);
+        assert_eq!(fonts[0].base_font, "DejaVuSans");
+        assert_eq!(fonts[0].subtype, "TrueType");
+        assert!(fonts[0].embedded);
+    }
+
+    #[test]
+    fn test_unescape_octal_escapes() {
+        assert_eq!(unescape_pdf_string(r"\225 item"), "\u{95} item");
+        assert_eq!(unescape_pdf_string(r"\101\102C"), "ABC");
+        assert_eq!(unescape_pdf_string(r"\0"), "\0");
+        assert_eq!(unescape_pdf_string("line\\\ncontinued"), "linecontinued");
+    }
+
+    #[test]
+    fn test_get_text_applies_font_encoding() {
+        let mut generator = crate::pdf_generator::PdfGenerator::new();
+        let font = generator.add_object(
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>\n".to_string(),
+        );
+        let mut data = b"BT\n/F1 12 Tf\n1 0 0 1 72 720 Tm\n(".to_vec();
+        data.push(0x95);
+        data.extend_from_slice(b" raw) Tj\n1 0 0 1 72 700 Tm\n(\\225 escaped) Tj\nET");
+        let content = generator.add_stream_object(format!("<< /Length {} >>\n", data.len()), data);
+        let pages_id = generator.next_id + 1;
+        let page = generator.add_object(format!(
+            "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 612 792] /Contents {} 0 R /Resources << /Font << /F1 {} 0 R >> >> >>\n",
+            pages_id, content, font
+        ));
+        generator.add_object(format!("<< /Type /Pages /Kids [{} 0 R] /Count 1 >>\n", page));
+        generator.add_object(format!("<< /Type /Catalog /Pages {} 0 R >>\n", pages_id));
+
+        let doc = PdfDocument::from_bytes(&generator.generate()).unwrap();
+        let text = doc.get_text().unwrap();
+        assert!(text.contains("\u{2022} raw"), "got {:?}", text);
+        assert!(text.contains("\u{2022} escaped"), "got {:?}", text);
+    }
+
+    #[test]
+    fn test_get_text_normalizes_combining_marks() {
+        let mut generator = crate::pdf_generator::PdfGenerator::new();
+        let font = generator.add_object("<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>\n".to_string());
+        let data = "BT\n/F1 12 Tf\n1 0 0 1 72 720 Tm\n(Cafe\u{301} nai\u{308}ve) Tj\nET".as_bytes().to_vec();
+        let content = generator.add_stream_object(format!("<< /Length {} >>\n", data.len()), data);
+        let pages_id = generator.next_id + 1;
+        let page = generator.add_object(format!(
+            "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 612 792] /Contents {} 0 R /Resources << /Font << /F1 {} 0 R >> >> >>\n",
+            pages_id, content, font
+        ));
+        generator.add_object(format!("<< /Type /Pages /Kids [{} 0 R] /Count 1 >>\n", page));
+        generator.add_object(format!("<< /Type /Catalog /Pages {} 0 R >>\n", pages_id));
+
+        let doc = PdfDocument::from_bytes(&generator.generate()).unwrap();
+        assert!(doc.get_text().unwrap().contains("Cafe\u{301}"));
+        let text = doc.get_text_with_options(&TextOptions { normalize_nfc: true }).unwrap();
+        assert!(text.contains("Caf\u{e9} na\u{ef}ve"), "got {:?}", text);
+    }
+
+    #[test]
+    fn test_get_text_reflowed_joins_lines() {
+        let mut generator = crate::pdf_generator::PdfGenerator::new();
+        let font = generator.add_object("<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>\n".to_string());
+        let data = b"BT\n/F1 12 Tf\n\
+            1 0 0 1 72 720 Tm\n(One visual) Tj\n1 0 0 1 140 720 Tm\n(line) Tj\n\
+            1 0 0 1 72 706 Tm\n(wrapped onto) Tj\n1 0 0 1 72 692 Tm\n(three rows.) Tj\n\
+            1 0 0 1 72 650 Tm\n(Next paragraph) Tj\n1 0 0 1 72 636 Tm\n(continues here.) Tj\nET"
+            .to_vec();
+        let content = generator.add_stream_object(format!("<< /Length {} >>\n", data.len()), data);
+        let pages_id = generator.next_id + 1;
+        let page = generator.add_object(format!(
+            "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 612 792] /Contents {} 0 R /Resources << /Font << /F1 {} 0 R >> >> >>\n",
+            pages_id, content, font
+        ));
+        generator.add_object(format!("<< /Type /Pages /Kids [{} 0 R] /Count 1 >>\n", page));
+        generator.add_object(format!("<< /Type /Catalog /Pages {} 0 R >>\n", pages_id));
+
+        let doc = PdfDocument::from_bytes(&generator.generate()).unwrap();
+        assert_eq!(
+            doc.get_text_reflowed().unwrap(),
+            "One visual line wrapped onto three rows.\n\nNext paragraph continues here.\n"
+        );
+        // Plain extraction keeps one line per visual line
+        assert_eq!(doc.get_text().unwrap().lines().count(), 5);
+    }
+
     #[test]
     fn test_text_position_tracker() {
         let mut tracker = TextPositionTracker::new();
@@ -711,6 +3223,45 @@ mod tests {
         assert!(!tracker.moved_to_new_line(700.0)); // same Y again
     }
 
+    #[test]
+    fn test_validation_decodes_streams() {
+        let elements = vec![crate::elements::Element::Paragraph { text: "Hello".into() }];
+        let layout = crate::pdf_generator::PageLayout::portrait();
+        let bytes = crate::pdf_generator::generate_pdf_bytes(&elements, "Helvetica", 12.0, layout).unwrap();
+        // Append a compressed stream after the last object
+        let mut generator = crate::pdf_generator::PdfGenerator::new();
+        let payload = vec![b'x'; 500];
+        generator.add_compressed_stream_object("", &payload);
+        let generated = generator.generate();
+        let start = find_bytes(&generated, b"1 0 obj").unwrap();
+        let end = find_bytes(&generated, b"endobj").unwrap() + "endobj\n".len();
+        let mut object = b"99".to_vec();
+        object.extend_from_slice(&generated[start + 1..end]);
+        let mut good = bytes.clone();
+        let at = find_bytes(&good, b"xref").unwrap();
+        good.splice(at..at, object);
+
+        let options = ValidationOptions { decode_streams: true };
+        let result = validate_pdf_bytes_with_options(&good, &options);
+        assert!(result.valid, "{:?}", result.errors);
+        let check = result.streams.iter().find(|s| s.object == 99).unwrap();
+        assert_eq!(check.filters, vec!["FlateDecode".to_string()]);
+        assert_eq!(check.decoded_len, Some(500));
+
+        // Corrupt the deflate data inside the stream
+        let mut bad = good.clone();
+        let data_start = find_bytes(&bad[at..], b"stream\n").unwrap() + at + 7;
+        for b in &mut bad[data_start + 2..data_start + 8] {
+            *b ^= 0xFF;
+        }
+        let result = validate_pdf_bytes_with_options(&bad, &options);
+        assert!(!result.valid);
+        assert!(result.errors.iter().any(|e| e.contains("Object 99")), "{:?}", result.errors);
+        assert_eq!(result.streams.iter().find(|s| s.object == 99).unwrap().decoded_len, None);
+        // Decoding is opt-in
+        assert!(validate_pdf_bytes(&bad).valid);
+    }
+
     #[test]
     fn test_decompress_stream_passthrough() {
         let data = b"BT /F1 12 Tf (Hello) Tj ET";
@@ -807,6 +3358,146 @@ mod tests {
         assert!(result.errors.is_empty());
     }
 
+    /// A one-page PDF drawing `content` with Helvetica as `/F1`, compressed,
+    /// with every line ended by `eol`
+    fn single_page_pdf(content: &[u8], eol: &str) -> Vec<u8> {
+        let content = crate::compression::compress_deflate(content).unwrap();
+        let mut pdf = format!("%PDF-1.4{eol}").into_bytes();
+        let mut offsets = Vec::new();
+        let objects: Vec<Vec<u8>> = vec![
+            b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+            b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_vec(),
+            format!("<< /Type /Page /Parent 2 0 R{eol}/MediaBox [0 0 612 792]{eol}/Contents 4 0 R{eol}/Resources << /Font << /F1 5 0 R >> >> >>").into_bytes(),
+            [format!("<< /Length {} /Filter /FlateDecode >>{eol}stream{eol}", content.len()).as_bytes(), &content, format!("{eol}endstream").as_bytes()].concat(),
+            b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_vec(),
+        ];
+        for (i, body) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend_from_slice(format!("{} 0 obj{eol}", i + 1).as_bytes());
+            pdf.extend_from_slice(body);
+            pdf.extend_from_slice(format!("{eol}endobj{eol}").as_bytes());
+        }
+        let xref = pdf.len();
+        // Cross-reference entries are always 20 bytes
+        let entry_end = if eol == "\r\n" { "\r\n" } else { " \n" };
+        pdf.extend_from_slice(format!("xref{eol}0 6{eol}0000000000 65535 f{entry_end}").as_bytes());
+        for offset in offsets {
+            pdf.extend_from_slice(format!("{:010} 00000 n{entry_end}", offset).as_bytes());
+        }
+        pdf.extend_from_slice(format!("trailer{eol}<< /Size 6 /Root 1 0 R >>{eol}startxref{eol}{xref}{eol}%%EOF{eol}").as_bytes());
+        pdf
+    }
+
+    #[test]
+    fn test_high_bytes_near_linearization_check_do_not_panic() {
+        // A binary comment whose two-byte chars straddle byte 1024 of the text form
+        let mut pdf = single_page_pdf(b"BT ET", "\n");
+        let comment = [b"%a".as_slice(), &[0xFF; 1100], b"\n"].concat();
+        pdf.splice(9..9, comment);
+        let doc = PdfDocument::from_bytes(&pdf).unwrap();
+        assert!(!doc.info_flags().linearized);
+        assert_eq!(doc.pages.len(), 1);
+    }
+
+    #[test]
+    fn test_info_flags_report_tagged_pdf() {
+        let mut tagged = single_page_pdf(b"BT ET", "\n");
+        let at = find_bytes(&tagged, b"/Pages 2 0 R >>").unwrap();
+        tagged.splice(
+            at..at,
+            b"/MarkInfo << /Marked true >> /StructTreeRoot << /Type /StructTreeRoot /K [] >> ".iter().copied(),
+        );
+        let flags = PdfDocument::from_bytes(&tagged).unwrap().info_flags();
+        assert_eq!(flags, DocFlags { tagged: true, ..Default::default() });
+
+        let plain = PdfDocument::from_bytes(&single_page_pdf(b"BT ET", "\n")).unwrap();
+        assert_eq!(plain.info_flags(), DocFlags::default());
+
+        let mut encrypted = single_page_pdf(b"BT ET", "\n");
+        let at = find_bytes(&encrypted, b"/Root 1 0 R").unwrap();
+        encrypted.splice(at..at, b"/Encrypt 9 0 R ".iter().copied());
+        let doc = PdfDocument::from_bytes(&encrypted).unwrap();
+        assert!(doc.info_flags().encrypted);
+        assert!(matches!(doc.get_text(), Err(PdfError::Encrypted)));
+    }
+
+    const LINE_ENDINGS: &[u8] = b"BT /F1 12 Tf 72 700 Td (Line endings) Tj ET";
+
+    #[test]
+    fn test_crlf_pdf_parses_like_lf() {
+        let lf = single_page_pdf(LINE_ENDINGS, "\n");
+        let crlf = single_page_pdf(LINE_ENDINGS, "\r\n");
+        let cr = single_page_pdf(LINE_ENDINGS, "\r");
+
+        let options = ValidationOptions { decode_streams: true };
+        let expected = validate_pdf_bytes_with_options(&lf, &options);
+        assert!(expected.valid && expected.warnings.is_empty(), "{:?} {:?}", expected.errors, expected.warnings);
+        assert_eq!(expected.page_count, 1);
+        let lf_doc = PdfDocument::from_bytes(&lf).unwrap();
+        assert_eq!(lf_doc.get_text().unwrap().trim(), "Line endings");
+
+        for bytes in [crlf, cr] {
+            let validation = validate_pdf_bytes_with_options(&bytes, &options);
+            assert_eq!(validation.errors, expected.errors);
+            assert_eq!(validation.warnings, expected.warnings);
+            assert_eq!(validation.page_count, expected.page_count);
+            assert_eq!(validation.object_count, expected.object_count);
+            assert_eq!(validation.streams, expected.streams);
+
+            let doc = PdfDocument::from_bytes(&bytes).unwrap();
+            assert_eq!(doc.version, "1.4");
+            assert_eq!(doc.pages, lf_doc.pages);
+            assert_eq!(doc.page_content_streams(0), lf_doc.page_content_streams(0));
+            assert_eq!(doc.get_text().unwrap(), lf_doc.get_text().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_search_text_reports_page_and_rect() {
+        use crate::elements::Element;
+        let elements = vec![
+            Element::Paragraph { text: "Nothing to see here.".into() },
+            Element::PageBreak,
+            Element::Paragraph { text: "Find the Zebra here.".into() },
+        ];
+        let layout = crate::pdf_generator::PageLayout::portrait();
+        let bytes = crate::pdf_generator::generate_pdf_bytes(&elements, "Helvetica", 12.0, layout).unwrap();
+        let path = std::env::temp_dir().join("pdfrs_search_text.pdf");
+        std::fs::write(&path, bytes).unwrap();
+
+        let hits = search_text(path.to_str().unwrap(), "Zebra").unwrap();
+        assert_eq!(hits.len(), 1);
+        let hit = &hits[0];
+        assert_eq!(hit.page_index, 1);
+        let [x1, y1, x2, y2] = hit.rect;
+        let before = crate::font_metrics::text_width("Helvetica", "Find the ", 12.0);
+        let width = crate::font_metrics::text_width("Helvetica", "Zebra", 12.0);
+        assert!((x1 - (layout.margin_left + before)).abs() < 6.0, "{:?}", hit.rect);
+        assert!(((x2 - x1) - width).abs() < 6.0, "{:?}", hit.rect);
+        assert!(y2 - y1 > 8.0 && y2 - y1 < 16.0);
+        assert!(y1 > layout.height / 2.0 && y2 < layout.height);
+
+        assert!(search_text(path.to_str().unwrap(), "Giraffe").unwrap().is_empty());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_plain_text_joins_fragments_into_words() {
+        let content = b"BT /F1 12 Tf 72 700 Td (The qu) Tj (ick br) Tj (own fox ) Tj [(j) 20 (umps) -400 (over)] TJ\n\
+            36 0 Td ( the) Tj 31 0 Td (dog.) Tj 0 -14 Td (Next) Tj 4 Tw ( line) Tj ET";
+        let doc = PdfDocument::from_bytes(&single_page_pdf(content, "\n")).unwrap();
+        assert_eq!(doc.get_plain_text().unwrap(), "The quick brown fox jumps over the dog.\nNext line\n");
+        // The line-oriented extraction breaks the words apart
+        assert!(doc.get_text().unwrap().contains("qu ick"));
+
+        let generated = crate::pdf_generator::generate_pdf_bytes(
+            &[crate::elements::Element::Paragraph { text: "Generated text keeps its words.".into() }],
+            "Helvetica", 12.0, crate::pdf_generator::PageLayout::portrait(),
+        ).unwrap();
+        let doc = PdfDocument::from_bytes(&generated).unwrap();
+        assert_eq!(doc.get_plain_text().unwrap(), "Generated text keeps its words.\nPage 1\n");
+    }
+
     #[test]
     fn test_validate_pdf_bytes_invalid_header() {
         let result = validate_pdf_bytes(b"NOT A PDF FILE");
@@ -828,6 +3519,49 @@ mod tests {
         assert!(result.errors.iter().any(|e| e.contains("%%EOF")));
     }
 
+    #[test]
+    fn test_text_layer_detection() {
+        let elements = vec![crate::elements::Element::Paragraph { text: "Searchable text.".into() }];
+        let layout = crate::pdf_generator::PageLayout::portrait();
+        let pdf_bytes = crate::pdf_generator::generate_pdf_bytes(&elements, "Helvetica", 12.0, layout).unwrap();
+        let doc = PdfDocument::from_bytes(&pdf_bytes).unwrap();
+        assert!(doc.has_text_layer());
+        assert!(doc.page_has_text_layer(0));
+
+        // A scan: the page only paints an image
+        let mut generator = crate::pdf_generator::PdfGenerator::new();
+        let image = generator.add_stream_object(
+            "<< /Type /XObject /Subtype /Image /Width 1 /Height 1 /ColorSpace /DeviceGray /BitsPerComponent 8 /Length 1 >>\n".to_string(),
+            vec![0x80],
+        );
+        let painting = b"q 612 0 0 792 0 0 cm /Im1 Do Q".to_vec();
+        let content = generator.add_stream_object(format!("<< /Length {} >>\n", painting.len()), painting);
+        let page = generator.add_object(format!(
+            "<< /Type /Page /Parent 4 0 R /MediaBox [0 0 612 792] /Contents {} 0 R /Resources << /XObject << /Im1 {} 0 R >> >> >>\n",
+            content, image
+        ));
+        generator.add_object(format!("<< /Type /Pages /Kids [{} 0 R] /Count 1 >>\n", page));
+        generator.add_object("<< /Type /Catalog /Pages 4 0 R >>\n".to_string());
+        let scanned = PdfDocument::from_bytes(&generator.generate()).unwrap();
+        assert_eq!(scanned.pages.len(), 1);
+        assert!(!scanned.has_text_layer());
+        assert!(!scanned.page_has_text_layer(0));
+    }
+
+    #[test]
+    fn test_validate_flags_dangling_reference() {
+        let mut generator = crate::pdf_generator::PdfGenerator::new();
+        let page = generator.add_object("<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] /Contents 99 0 R >>\n".to_string());
+        generator.add_object(format!("<< /Type /Pages /Kids [{} 0 R] /Count 1 >>\n", page));
+        generator.add_object("<< /Type /Catalog /Pages 2 0 R >>\n".to_string());
+        let result = validate_pdf_bytes(&generator.generate());
+        assert!(!result.valid);
+        assert_eq!(
+            result.errors,
+            vec![format!("Object {} references missing object 99", page)],
+        );
+    }
+
     #[test]
     fn test_roundtrip_generate_validate_parse() {
         // Round-trip: elements → PDF bytes → validate → parse → extract text → verify
@@ -839,7 +3573,7 @@ mod tests {
             crate::elements::Element::CodeBlock { language: "rust".into(), code: "fn main() {}".into() },
             crate::elements::Element::BlockQuote { text: "A quote".into(), depth: 1 },
             crate::elements::Element::Link { text: "Example".into(), url: "https://example.com".into() },
-            crate::elements::Element::Image { alt: "Logo".into(), path: "logo.png".into() },
+            crate::elements::Element::Image { alt: "Logo".into(), path: "logo.png".into(), caption: None, float: Default::default() },
             crate::elements::Element::Footnote { label: "1".into(), text: "A footnote.".into() },
         ];
         let layout = crate::pdf_generator::PageLayout::portrait();
@@ -883,12 +3617,13 @@ mod tests {
                 cells: vec!["Name".into(), "Age".into()],
                 is_separator: false,
                 alignments: vec![crate::elements::TableAlignment::Left, crate::elements::TableAlignment::Left],
+                list_depth: None,
             },
             crate::elements::Element::BlockQuote { text: "Wise words".into(), depth: 1 },
             crate::elements::Element::DefinitionItem { term: "Rust".into(), definition: "A language".into() },
             crate::elements::Element::Footnote { label: "fn1".into(), text: "See reference".into() },
             crate::elements::Element::Link { text: "Google".into(), url: "https://google.com".into() },
-            crate::elements::Element::Image { alt: "Photo".into(), path: "photo.jpg".into() },
+            crate::elements::Element::Image { alt: "Photo".into(), path: "photo.jpg".into(), caption: None, float: Default::default() },
             crate::elements::Element::StyledText { text: "Bold text".into(), bold: true, italic: false },
             crate::elements::Element::HorizontalRule,
             crate::elements::Element::PageBreak,
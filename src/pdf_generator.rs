@@ -1,8 +1,10 @@
-use crate::elements::{Element, TextSegment};
+use crate::elements::{Element, ImageFloat, TextSegment};
 use crate::table_renderer::{PdfTableHelper, TableStyle};
-use anyhow::Result;
+use crate::error::Result;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 use syntect::parsing::{SyntaxSet, SyntaxReference};
 
 // Lazy static syntax set and theme
@@ -47,7 +49,7 @@ struct CodeToken {
 }
 
 /// Perform simple syntax highlighting on code
-fn highlight_code(code: &str, language: &str) -> Vec<CodeToken> {
+fn highlight_code(code: &str, language: &str, default_color: Color) -> Vec<CodeToken> {
     let syntax_set = get_syntax_set();
 
     let _syntax = get_syntax_for_language(language)
@@ -83,7 +85,6 @@ fn highlight_code(code: &str, language: &str) -> Vec<CodeToken> {
     let keyword_color = Color::rgb(0.53, 0.07, 0.24); // Purple for keywords
     let comment_color = Color::rgb(0.4, 0.4, 0.4); // Gray for comments
     let number_color = Color::rgb(0.15, 0.15, 0.8); // Blue for numbers
-    let default_color = Color::black();
 
     // Simple tokenization - split by common patterns
     let mut remaining = code.to_string();
@@ -255,7 +256,7 @@ pub enum PageOrientation {
     Landscape,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct PageLayout {
     pub width: f32,
     pub height: f32,
@@ -263,8 +264,16 @@ pub struct PageLayout {
     pub margin_right: f32,
     pub margin_top: f32,
     pub margin_bottom: f32,
+    /// Produce a single page whose height fits the content (`height` is ignored)
+    pub auto_height: bool,
+    /// Visible region `[llx, lly, urx, ury]` written as `/CropBox`, e.g. to trim
+    /// bleed; `None` shows the whole page
+    pub crop_box: Option<[f32; 4]>,
 }
 
+/// Largest page height allowed by PDF viewers, used to lay out auto-height pages
+const MAX_PAGE_HEIGHT: f32 = 14400.0;
+
 impl PageLayout {
     pub fn portrait() -> Self {
         PageLayout {
@@ -274,6 +283,8 @@ impl PageLayout {
             margin_right: 72.0,
             margin_top: 72.0,
             margin_bottom: 72.0,
+            auto_height: false,
+            crop_box: None,
         }
     }
 
@@ -285,6 +296,17 @@ impl PageLayout {
             margin_right: 72.0,
             margin_top: 72.0,
             margin_bottom: 72.0,
+            auto_height: false,
+            crop_box: None,
+        }
+    }
+
+    /// ISO A4 portrait (595 x 842 pt)
+    pub fn a4() -> Self {
+        PageLayout {
+            width: 595.0,
+            height: 842.0,
+            ..Self::portrait()
         }
     }
 
@@ -302,6 +324,55 @@ impl PageLayout {
     pub fn content_width(&self) -> f32 {
         self.width - self.margin_left - self.margin_right
     }
+
+    /// Set the four margins in points, in CSS order (top, right, bottom, left)
+    pub fn with_margins(mut self, top: f32, right: f32, bottom: f32, left: f32) -> Self {
+        self.margin_top = top;
+        self.margin_right = right;
+        self.margin_bottom = bottom;
+        self.margin_left = left;
+        self
+    }
+
+    /// Size the page height to the rendered content, e.g. for receipts and labels.
+    /// Auto-height pages have no page number.
+    pub fn with_auto_height(mut self, auto_height: bool) -> Self {
+        self.auto_height = auto_height;
+        self
+    }
+
+    /// Show only `[llx, lly, urx, ury]` of each page in viewers and when printing
+    pub fn with_crop_box(mut self, crop_box: [f32; 4]) -> Self {
+        self.crop_box = Some(crop_box);
+        self
+    }
+
+    /// The `/CropBox` page entry, or an empty string without a crop box
+    pub fn crop_box_entry(&self) -> String {
+        match self.crop_box {
+            Some([llx, lly, urx, ury]) => format!("/CropBox [{} {} {} {}]\n", llx, lly, urx, ury),
+            None => String::new(),
+        }
+    }
+
+    /// Fail when the page has no room for text: a size that is not a positive
+    /// number, or margins that leave nothing between them
+    pub fn validate(&self) -> Result<()> {
+        let positive = |v: f32| v.is_finite() && v > 0.0;
+        if !positive(self.width) || !(self.auto_height || positive(self.height)) {
+            return Err(crate::error::PdfError::InvalidInput(format!(
+                "Invalid page size {}x{}",
+                self.width, self.height
+            )));
+        }
+        if !positive(self.content_width()) || !(self.auto_height || positive(self.content_top() - self.margin_bottom)) {
+            return Err(crate::error::PdfError::InvalidInput(format!(
+                "Page margins leave no room for text on a {}x{} page",
+                self.width, self.height
+            )));
+        }
+        Ok(())
+    }
 }
 
 // --- Font size helpers ---
@@ -325,9 +396,17 @@ fn line_height(font_size: f32) -> f32 {
 pub struct PdfGenerator {
     pub objects: Vec<PdfObj>,
     pub next_id: u32,
+    /// Document information dictionary referenced from the trailer
+    pub info: Option<u32>,
+    /// Catalog referenced from the trailer's `/Root`; the last object added when `None`
+    pub root: Option<u32>,
+    /// PDF version declared in the header, as (major, minor)
+    pub version: (u8, u8),
+    /// Pack non-stream objects into an object stream with a cross-reference stream
+    pub object_streams: bool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PdfObj {
     pub id: u32,
     pub generation: u32,
@@ -341,6 +420,80 @@ impl PdfGenerator {
         PdfGenerator {
             objects: Vec::new(),
             next_id: 1,
+            info: None,
+            root: None,
+            version: (1, 4),
+            object_streams: false,
+        }
+    }
+
+    /// Add a Flate-compressed stream. `entries` are extra dictionary entries such
+    /// as `/Type /XObject\n`; `/Filter` and `/Length` are added.
+    pub fn add_compressed_stream_object(&mut self, entries: &str, data: &[u8]) -> u32 {
+        let compressed = crate::compression::compress_deflate(data).expect("writing to a Vec cannot fail");
+        let dictionary = format!("<< {}/Filter /FlateDecode\n/Length {}\n>>\n", entries, compressed.len());
+        self.add_stream_object(dictionary, compressed)
+    }
+
+    /// Number new objects from `id` instead of 1, so a batch can be appended to a
+    /// document whose highest object ID is below `id` as an incremental update.
+    /// The xref table then starts a subsection at `id`.
+    pub fn with_starting_id(mut self, id: u32) -> Self {
+        self.next_id = id.max(1);
+        self
+    }
+
+    /// Declare the PDF version written in the header and, above 1.4, the catalog
+    pub fn set_version(&mut self, major: u8, minor: u8) {
+        self.version = (major, minor);
+    }
+
+    /// Raise the declared version to at least `major.minor` for a feature that needs it
+    pub fn require_version(&mut self, major: u8, minor: u8) {
+        self.version = self.version.max((major, minor));
+    }
+
+    /// Write objects into a compressed object stream (requires PDF 1.5).
+    /// Files written this way are smaller, but [`crate::pdf::PdfDocument`] does not
+    /// find their page tree.
+    pub fn set_object_streams(&mut self, enabled: bool) {
+        self.object_streams = enabled;
+        if enabled {
+            self.require_version(1, 5);
+        }
+    }
+
+    fn header(&self) -> Vec<u8> {
+        let mut header = format!("%PDF-{}.{}\n", self.version.0, self.version.1).into_bytes();
+        header.extend_from_slice(b"%\xE2\xE3\xCF\xD3\n");
+        header
+    }
+
+    /// Id of the catalog written as the trailer's `/Root`
+    fn root_id(&self) -> Option<u32> {
+        self.root.or_else(|| self.objects.last().map(|o| o.id))
+    }
+
+    /// Object content, with `/Version` added to the catalog when the version is
+    /// above 1.4 and the catalog does not declare one itself
+    fn object_content<'a>(&self, obj: &'a PdfObj) -> std::borrow::Cow<'a, str> {
+        let content = obj.content.trim_start();
+        if self.version > (1, 4)
+            && self.root_id() == Some(obj.id)
+            && content.starts_with("<<")
+            && !crate::pdf::parse_dict_entries(content).contains_key("Version")
+        {
+            let version = format!("<< /Version /{}.{}", self.version.0, self.version.1);
+            std::borrow::Cow::Owned(content.replacen("<<", &version, 1))
+        } else {
+            std::borrow::Cow::Borrowed(&obj.content)
+        }
+    }
+
+    fn write_object(&self, pdf: &mut Vec<u8>, obj: &PdfObj) {
+        match self.object_content(obj) {
+            std::borrow::Cow::Borrowed(_) => write_object(pdf, obj),
+            std::borrow::Cow::Owned(content) => write_object(pdf, &PdfObj { content, ..obj.clone() }),
         }
     }
 
@@ -371,10 +524,13 @@ impl PdfGenerator {
     }
 
     pub fn generate(&self) -> Vec<u8> {
+        if self.object_streams {
+            return self.generate_with_object_streams();
+        }
         let mut pdf = Vec::new();
 
         // PDF header
-        pdf.extend_from_slice(b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n");
+        pdf.extend_from_slice(&self.header());
 
         // Calculate offsets for xref table
         let mut offsets = Vec::new();
@@ -383,26 +539,21 @@ impl PdfGenerator {
         // Write objects and collect offsets
         for obj in &self.objects {
             offsets.push(current_offset);
-            let obj_header = format!("{} {} obj\n", obj.id, obj.generation);
-            pdf.extend_from_slice(obj_header.as_bytes());
-            pdf.extend_from_slice(obj.content.as_bytes());
-
-            if obj.is_stream {
-                if let Some(data) = &obj.stream_data {
-                    pdf.extend_from_slice(b"stream\n");
-                    pdf.extend_from_slice(data);
-                    pdf.extend_from_slice(b"\nendstream\n");
-                }
-            }
-
-            pdf.extend_from_slice(b"endobj\n");
+            self.write_object(&mut pdf, obj);
             current_offset = pdf.len() as u32;
         }
 
-        // xref table
+        // xref table; objects numbered past 1 get their own subsection
         let xref_offset = pdf.len() as u32;
-        pdf.extend_from_slice(format!("xref\n0 {}\n", self.objects.len() + 1).as_bytes());
-        pdf.extend_from_slice(b"0000000000 65535 f \n");
+        let first_id = self.objects.first().map_or(1, |o| o.id);
+        let size = first_id as usize + self.objects.len();
+        if first_id == 1 {
+            pdf.extend_from_slice(format!("xref\n0 {}\n", size).as_bytes());
+            pdf.extend_from_slice(b"0000000000 65535 f \n");
+        } else {
+            pdf.extend_from_slice(b"xref\n0 1\n0000000000 65535 f \n");
+            pdf.extend_from_slice(format!("{} {}\n", first_id, self.objects.len()).as_bytes());
+        }
 
         for offset in offsets {
             pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
@@ -411,9 +562,12 @@ impl PdfGenerator {
         // trailer
         pdf.extend_from_slice(b"trailer\n");
         pdf.extend_from_slice(b"<<\n");
-        pdf.extend_from_slice(format!("/Size {}\n", self.objects.len() + 1).as_bytes());
-        if !self.objects.is_empty() {
-            pdf.extend_from_slice(format!("/Root {} 0 R\n", self.objects.len()).as_bytes());
+        pdf.extend_from_slice(format!("/Size {}\n", size).as_bytes());
+        if let Some(root) = self.root_id() {
+            pdf.extend_from_slice(format!("/Root {} 0 R\n", root).as_bytes());
+        }
+        if let Some(info) = self.info {
+            pdf.extend_from_slice(format!("/Info {} 0 R\n", info).as_bytes());
         }
         pdf.extend_from_slice(b">>\n");
         pdf.extend_from_slice(b"startxref\n");
@@ -422,24 +576,451 @@ impl PdfGenerator {
 
         pdf
     }
+
+    /// Write streams directly and every other object into one Flate-compressed
+    /// object stream, indexed by a cross-reference stream
+    fn generate_with_object_streams(&self) -> Vec<u8> {
+        let mut pdf = self.header();
+        let objstm_id = self.next_id;
+        let xref_id = self.next_id + 1;
+        // xref entries: (type, field 2, field 3)
+        let mut entries = vec![(0u8, 0u32, 0xFFFFu16); xref_id as usize + 1];
+
+        let mut index = Vec::new();
+        let mut body = Vec::new();
+        for obj in &self.objects {
+            if obj.is_stream {
+                entries[obj.id as usize] = (1, pdf.len() as u32, 0);
+                self.write_object(&mut pdf, obj);
+            } else {
+                entries[obj.id as usize] = (2, objstm_id, (index.len() / 2) as u16);
+                index.push(obj.id as usize);
+                index.push(body.len());
+                body.extend_from_slice(self.object_content(obj).as_bytes());
+                body.push(b'\n');
+            }
+        }
+
+        let mut packed = index.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(" ").into_bytes();
+        packed.push(b'\n');
+        let first = packed.len();
+        packed.extend_from_slice(&body);
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, &packed).expect("writing to a Vec cannot fail");
+        let packed = encoder.finish().expect("writing to a Vec cannot fail");
+
+        entries[objstm_id as usize] = (1, pdf.len() as u32, 0);
+        write_object(&mut pdf, &PdfObj {
+            id: objstm_id,
+            generation: 0,
+            content: format!(
+                "<< /Type /ObjStm\n/N {}\n/First {}\n/Filter /FlateDecode\n/Length {}\n>>\n",
+                index.len() / 2,
+                first,
+                packed.len()
+            ),
+            is_stream: true,
+            stream_data: Some(packed),
+        });
+
+        let xref_offset = pdf.len() as u32;
+        entries[xref_id as usize] = (1, xref_offset, 0);
+        let mut xref = Vec::new();
+        for (kind, field2, field3) in entries {
+            xref.push(kind);
+            xref.extend_from_slice(&field2.to_be_bytes());
+            xref.extend_from_slice(&field3.to_be_bytes());
+        }
+        let root = self.root_id().unwrap_or(0);
+        let info = self.info.map(|id| format!("/Info {} 0 R\n", id)).unwrap_or_default();
+        write_object(&mut pdf, &PdfObj {
+            id: xref_id,
+            generation: 0,
+            content: format!(
+                "<< /Type /XRef\n/Size {}\n/W [1 4 2]\n/Root {} 0 R\n{}/Length {}\n>>\n",
+                xref_id + 1,
+                root,
+                info,
+                xref.len()
+            ),
+            is_stream: true,
+            stream_data: Some(xref),
+        });
+
+        pdf.extend_from_slice(format!("startxref\n{}\n%%EOF\n", xref_offset).as_bytes());
+        pdf
+    }
+}
+
+impl PdfGenerator {
+    /// Generate a linearized ("fast web view") PDF.
+    ///
+    /// The catalog, first page and everything the first page references are
+    /// written directly after a linearization parameter dictionary, so a viewer
+    /// can render page one before the rest of the file arrives. This is a basic
+    /// linearization: the hint stream is empty and a single xref table is kept
+    /// at the end of the file. The catalog is the trailer's root, as with
+    /// [`PdfGenerator::generate`]; without a page tree this falls back to `generate`.
+    /// Object streams are not used in linearized output.
+    pub fn generate_linearized(&self) -> Vec<u8> {
+        let ref_re = regex::Regex::new(r"(\d+)\s+0\s+R").unwrap();
+        let parent_re = regex::Regex::new(r"/Parent\s+\d+\s+0\s+R").unwrap();
+        let by_id: std::collections::HashMap<u32, &PdfObj> =
+            self.objects.iter().map(|o| (o.id, o)).collect();
+
+        let Some(catalog) = self.root_id().and_then(|id| by_id.get(&id).copied()) else {
+            return self.generate();
+        };
+        let first_ref = |content: &str, key: &str| -> Option<u32> {
+            let re = regex::Regex::new(&format!(r"/{}\s*\[?\s*(\d+)\s+0\s+R", key)).unwrap();
+            re.captures(content).and_then(|c| c[1].parse().ok())
+        };
+        let Some(pages) = first_ref(&catalog.content, "Pages").and_then(|id| by_id.get(&id)) else {
+            return self.generate();
+        };
+        let Some(first_page) = first_ref(&pages.content, "Kids").filter(|id| by_id.contains_key(id))
+        else {
+            return self.generate();
+        };
+        let page_count = regex::Regex::new(r"/Count\s+(\d+)")
+            .unwrap()
+            .captures(&pages.content)
+            .and_then(|c| c[1].parse::<u32>().ok())
+            .unwrap_or(1);
+
+        // First-page section: the page plus everything it references (minus the page tree)
+        let mut first_section = vec![first_page];
+        let mut i = 0;
+        while i < first_section.len() {
+            let content = parent_re.replace_all(&by_id[&first_section[i]].content, "");
+            for caps in ref_re.captures_iter(&content) {
+                if let Ok(id) = caps[1].parse::<u32>()
+                    && by_id.contains_key(&id)
+                    && id != catalog.id
+                    && !first_section.contains(&id)
+                {
+                    first_section.push(id);
+                }
+            }
+            i += 1;
+        }
+
+        let max_id = self.objects.iter().map(|o| o.id).max().unwrap_or(0);
+        let lin_id = max_id + 1;
+        let hint = PdfObj {
+            id: max_id + 2,
+            generation: 0,
+            content: "<< /Length 0 >>\n".to_string(),
+            is_stream: true,
+            stream_data: Some(Vec::new()),
+        };
+        let mut order: Vec<&PdfObj> = vec![catalog];
+        order.extend(first_section.iter().map(|id| by_id[id]));
+        let remaining: Vec<&PdfObj> = self
+            .objects
+            .iter()
+            .filter(|o| o.id != catalog.id && !first_section.contains(&o.id))
+            .collect();
+
+        // Numbers in the parameter dictionary are fixed-width, so a first pass
+        // with zeros yields the offsets needed for the second pass.
+        let mut params = [0usize; 5]; // L, H offset, H length, E, T
+        let mut pdf = Vec::new();
+        for _ in 0..2 {
+            let lin = PdfObj {
+                id: lin_id,
+                generation: 0,
+                content: format!(
+                    "<< /Linearized 1 /L {:010} /H [{:010} {:010}] /O {} /E {:010} /N {} /T {:010} >>\n",
+                    params[0], params[1], params[2], first_page, params[3], page_count, params[4]
+                ),
+                is_stream: false,
+                stream_data: None,
+            };
+
+            pdf = self.header();
+            let mut offsets = std::collections::HashMap::new();
+            offsets.insert(lin.id, pdf.len());
+            write_object(&mut pdf, &lin);
+            offsets.insert(hint.id, pdf.len());
+            let hint_start = pdf.len();
+            write_object(&mut pdf, &hint);
+            let hint_len = pdf.len() - hint_start;
+            for obj in &order {
+                offsets.insert(obj.id, pdf.len());
+                self.write_object(&mut pdf, obj);
+            }
+            let first_page_end = pdf.len();
+            for obj in &remaining {
+                offsets.insert(obj.id, pdf.len());
+                write_object(&mut pdf, obj);
+            }
+
+            let xref_offset = pdf.len();
+            pdf.extend_from_slice(format!("xref\n0 {}\n", hint.id + 1).as_bytes());
+            pdf.extend_from_slice(b"0000000000 65535 f \n");
+            for id in 1..=hint.id {
+                match offsets.get(&id) {
+                    Some(offset) => pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes()),
+                    None => pdf.extend_from_slice(b"0000000000 00000 f \n"),
+                }
+            }
+            pdf.extend_from_slice(b"trailer\n<<\n");
+            pdf.extend_from_slice(format!("/Size {}\n", hint.id + 1).as_bytes());
+            pdf.extend_from_slice(format!("/Root {} 0 R\n", catalog.id).as_bytes());
+            if let Some(info) = self.info {
+                pdf.extend_from_slice(format!("/Info {} 0 R\n", info).as_bytes());
+            }
+            pdf.extend_from_slice(b">>\nstartxref\n");
+            pdf.extend_from_slice(format!("{}\n", xref_offset).as_bytes());
+            pdf.extend_from_slice(b"%%EOF\n");
+
+            params = [pdf.len(), hint_start, hint_len, first_page_end, xref_offset];
+        }
+        pdf
+    }
+}
+
+/// Serialize one indirect object (`N G obj ... endobj`)
+fn write_object(pdf: &mut Vec<u8>, obj: &PdfObj) {
+    let obj_header = format!("{} {} obj\n", obj.id, obj.generation);
+    pdf.extend_from_slice(obj_header.as_bytes());
+    pdf.extend_from_slice(obj.content.as_bytes());
+
+    if obj.is_stream {
+        if let Some(data) = &obj.stream_data {
+            pdf.extend_from_slice(b"stream\n");
+            pdf.extend_from_slice(data);
+            pdf.extend_from_slice(b"\nendstream\n");
+        }
+    }
+
+    pdf.extend_from_slice(b"endobj\n");
 }
 
 // --- Content stream builder (handles cursor, page breaks, font switches) ---
 
-/// RGB color for text rendering (0.0-1.0 per channel)
+/// Color space a [`Color`] is painted in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorSpace {
+    /// DeviceRGB, painted with `rg` / `RG`
+    Rgb,
+    /// DeviceCMYK components (0.0-1.0), painted with `k` / `K`
+    Cmyk { c: f32, m: f32, y: f32, k: f32 },
+    /// DeviceGray, painted with `g` / `G`; the level is `r`
+    Gray,
+}
+
+/// Color for text and shapes. `r`, `g` and `b` (0.0-1.0 per channel) always
+/// hold the RGB equivalent; `space` decides which operators are emitted.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Color {
     pub r: f32,
     pub g: f32,
     pub b: f32,
+    pub space: ColorSpace,
 }
 
 impl Color {
-    pub fn black() -> Self { Color { r: 0.0, g: 0.0, b: 0.0 } }
-    pub fn red() -> Self { Color { r: 1.0, g: 0.0, b: 0.0 } }
-    pub fn blue() -> Self { Color { r: 0.0, g: 0.0, b: 1.0 } }
-    pub fn gray() -> Self { Color { r: 0.5, g: 0.5, b: 0.5 } }
-    pub fn rgb(r: f32, g: f32, b: f32) -> Self { Color { r, g, b } }
+    pub fn black() -> Self { Color::rgb(0.0, 0.0, 0.0) }
+    pub fn red() -> Self { Color::rgb(1.0, 0.0, 0.0) }
+    pub fn blue() -> Self { Color::rgb(0.0, 0.0, 1.0) }
+    pub fn gray() -> Self { Color::rgb(0.5, 0.5, 0.5) }
+    pub fn yellow() -> Self { Color::rgb(1.0, 1.0, 0.0) }
+    pub fn rgb(r: f32, g: f32, b: f32) -> Self { Color { r, g, b, space: ColorSpace::Rgb } }
+
+    /// A DeviceCMYK color, for print workflows
+    pub fn cmyk(c: f32, m: f32, y: f32, k: f32) -> Self {
+        Color {
+            r: (1.0 - c) * (1.0 - k),
+            g: (1.0 - m) * (1.0 - k),
+            b: (1.0 - y) * (1.0 - k),
+            space: ColorSpace::Cmyk { c, m, y, k },
+        }
+    }
+
+    /// A DeviceGray color; 0.0 is black and 1.0 is white
+    pub fn gray_level(level: f32) -> Self {
+        Color { r: level, g: level, b: level, space: ColorSpace::Gray }
+    }
+
+    /// A CSS-style color name such as `"navy"` or `"orange"`, case-insensitive
+    pub fn named(name: &str) -> Option<Self> {
+        let (r, g, b) = match name.to_ascii_lowercase().as_str() {
+            "black" => (0, 0, 0),
+            "white" => (255, 255, 255),
+            "red" => (255, 0, 0),
+            "green" => (0, 128, 0),
+            "lime" => (0, 255, 0),
+            "blue" => (0, 0, 255),
+            "navy" => (0, 0, 128),
+            "yellow" => (255, 255, 0),
+            "cyan" | "aqua" => (0, 255, 255),
+            "magenta" | "fuchsia" => (255, 0, 255),
+            "gray" | "grey" => (128, 128, 128),
+            "silver" => (192, 192, 192),
+            "maroon" => (128, 0, 0),
+            "olive" => (128, 128, 0),
+            "purple" => (128, 0, 128),
+            "teal" => (0, 128, 128),
+            "orange" => (255, 165, 0),
+            _ => return None,
+        };
+        Some(Color::rgb(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0))
+    }
+
+    /// Operator setting this as the non-stroking (fill and text) color
+    pub fn fill_operator(&self) -> String {
+        match self.space {
+            ColorSpace::Rgb => format!("{} {} {} rg", self.r, self.g, self.b),
+            ColorSpace::Cmyk { c, m, y, k } => format!("{} {} {} {} k", c, m, y, k),
+            ColorSpace::Gray => format!("{} g", self.r),
+        }
+    }
+
+    /// Operator setting this as the stroking color
+    pub fn stroke_operator(&self) -> String {
+        match self.space {
+            ColorSpace::Rgb => format!("{} {} {} RG", self.r, self.g, self.b),
+            ColorSpace::Cmyk { c, m, y, k } => format!("{} {} {} {} K", c, m, y, k),
+            ColorSpace::Gray => format!("{} G", self.r),
+        }
+    }
+}
+
+/// Fill and stroke settings for a drawn shape
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapeStyle {
+    /// Non-stroking (interior) color; `None` leaves the shape unfilled
+    pub fill: Option<Color>,
+    /// Stroking (outline) color; `None` draws no outline
+    pub stroke: Option<Color>,
+    pub line_width: f32,
+}
+
+impl ShapeStyle {
+    pub fn filled(color: Color) -> Self {
+        ShapeStyle { fill: Some(color), stroke: None, line_width: 1.0 }
+    }
+
+    pub fn stroked(color: Color, line_width: f32) -> Self {
+        ShapeStyle { fill: None, stroke: Some(color), line_width }
+    }
+
+    /// `f` (fill), `S` (stroke) or `B` (both); `n` paints nothing
+    pub fn paint_operator(&self) -> &'static str {
+        match (self.fill.is_some(), self.stroke.is_some()) {
+            (true, true) => "B",
+            (true, false) => "f",
+            (false, true) => "S",
+            (false, false) => "n",
+        }
+    }
+}
+
+/// Content-stream operators that paint `path` (construction operators such as
+/// `re`, `m` and `l`, without a paint operator) with `style`
+pub fn shape_operators(path: &str, style: &ShapeStyle) -> String {
+    let mut ops = String::new();
+    if let Some(fill) = style.fill {
+        ops.push_str(&format!("{}\n", fill.fill_operator()));
+    }
+    if let Some(stroke) = style.stroke {
+        ops.push_str(&format!("{}\n{} w\n", stroke.stroke_operator(), style.line_width));
+    }
+    ops.push_str(&format!("{} {}\n", path, style.paint_operator()));
+    ops
+}
+
+/// How glyphs are painted, set with the `Tr` operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextRenderMode {
+    #[default]
+    Fill,
+    /// Outlined glyphs
+    Stroke,
+    FillStroke,
+    /// Glyphs that paint nothing but can still be selected and searched,
+    /// as in the text layer over a scanned page
+    Invisible,
+}
+
+impl TextRenderMode {
+    /// The mode's `Tr` operand
+    pub fn value(self) -> u8 {
+        match self {
+            TextRenderMode::Fill => 0,
+            TextRenderMode::Stroke => 1,
+            TextRenderMode::FillStroke => 2,
+            TextRenderMode::Invisible => 3,
+        }
+    }
+}
+
+/// Painting of a run of text: its rendering mode, and the outline drawn by
+/// the stroking modes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    pub render_mode: TextRenderMode,
+    pub stroke: Color,
+    pub line_width: f32,
+}
+
+impl TextStyle {
+    /// Glyphs filled with the text color and outlined in `stroke`
+    pub fn outlined(stroke: Color, line_width: f32) -> Self {
+        TextStyle { render_mode: TextRenderMode::FillStroke, stroke, line_width }
+    }
+
+    /// Operators switching text to this style
+    fn operators(&self) -> String {
+        match self.render_mode {
+            TextRenderMode::Stroke | TextRenderMode::FillStroke => format!(
+                "{}\n{} w\n{} Tr\n",
+                self.stroke.stroke_operator(),
+                self.line_width,
+                self.render_mode.value()
+            ),
+            mode => format!("{} Tr\n", mode.value()),
+        }
+    }
+}
+
+/// Stroke pattern of a drawn line
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LineStyle {
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+impl LineStyle {
+    /// The `d` operator setting this pattern for a line `thickness` points wide;
+    /// `None` for solid lines
+    pub fn dash_operator(&self, thickness: f32) -> Option<String> {
+        match self {
+            LineStyle::Solid => None,
+            LineStyle::Dashed => Some(format!("[{} {}] 0 d", thickness * 3.0, thickness * 2.0)),
+            LineStyle::Dotted => Some(format!("[{} {}] 0 d", thickness, thickness * 2.0)),
+        }
+    }
+}
+
+/// How horizontal rules (`---`) are drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RuleStyle {
+    pub thickness: f32,
+    pub color: Color,
+    pub style: LineStyle,
+    /// Share of the content width the rule spans, centered
+    pub width_fraction: f32,
+}
+
+impl Default for RuleStyle {
+    fn default() -> Self {
+        RuleStyle { thickness: 1.0, color: Color::gray(), style: LineStyle::Solid, width_fraction: 1.0 }
+    }
 }
 
 /// Text alignment for line rendering
@@ -466,8 +1047,58 @@ struct ContentStreamBuilder {
     current_font: String,  // Font name (e.g., "Helvetica", "Helvetica-Bold")
     current_font_bold: bool,
     current_font_italic: bool,
+    options: RenderOptions,
+    /// Section counters per heading level, used for heading numbering
+    heading_counters: Vec<u32>,
+    /// Embedded images by the path written in the document
+    inline_images: HashMap<String, InlineImage>,
+    /// Blockquote being rendered: its depth and the top of its bars on this page
+    quote: Option<(u8, f32)>,
+    /// Color of the bars drawn for `quote`
+    quote_bar_color: Color,
+    /// Left inset of the next lines (around a drop cap) and how many lines it still covers
+    inset: Option<(f32, usize)>,
+    /// Layout of each finished page
+    page_layouts: Vec<PageLayout>,
+    /// URL of the link being drawn; each line it occupies gets its own region
+    link_url: Option<String>,
+    /// Clickable regions of the links drawn so far
+    links: Vec<LinkRegion>,
+    /// Background drawn behind each line of the text being emitted
+    highlight: Option<Color>,
+    /// Latest level 1 or 2 heading, repeated in the top margin of later pages
+    running_head: Option<String>,
+    /// Number of the last numbered display equation
+    equation_number: u32,
+    /// Area beside a floated image that the following lines wrap around
+    float: Option<FloatZone>,
+    /// Rendering mode of the text being emitted; `None` fills glyphs
+    text_style: Option<TextStyle>,
+    /// URLs of footnote-style links in order of first reference; a link's
+    /// note number is its position here plus one
+    link_notes: Vec<String>,
 }
 
+/// The area one line of a link covers on a page, in default user space
+#[derive(Debug, Clone, PartialEq)]
+struct LinkRegion {
+    /// 0-based index of the page
+    page: usize,
+    rect: [f32; 4],
+    url: String,
+}
+
+/// An image element embedded once as the XObject `name`, with its size in points
+#[derive(Debug, Clone)]
+struct InlineImage {
+    name: String,
+    width: f32,
+    height: f32,
+}
+
+/// Cap height of Helvetica in em, from its AFM file
+const HELVETICA_CAP_HEIGHT: f32 = 0.718;
+
 // Font name constants
 const FONT_HELVETICA: &str = "Helvetica";
 const FONT_HELVETICA_BOLD: &str = "Helvetica-Bold";
@@ -475,6 +1106,15 @@ const FONT_HELVETICA_OBLIQUE: &str = "Helvetica-Oblique";
 const FONT_HELVETICA_BOLD_OBLIQUE: &str = "Helvetica-BoldOblique";
 const FONT_COURIER: &str = "Courier";  // Monospace for code
 
+/// Fonts referenced by generated content streams, each under a resource name equal to its BaseFont
+pub(crate) const STANDARD_FONTS: [&str; 5] = [
+    FONT_HELVETICA,
+    FONT_HELVETICA_BOLD,
+    FONT_HELVETICA_OBLIQUE,
+    FONT_HELVETICA_BOLD_OBLIQUE,
+    FONT_COURIER,
+];
+
 impl ContentStreamBuilder {
     fn new(base_font_size: f32, show_page_numbers: bool, layout: PageLayout) -> Self {
         let mut b = ContentStreamBuilder {
@@ -491,18 +1131,78 @@ impl ContentStreamBuilder {
             current_font: FONT_HELVETICA.to_string(),
             current_font_bold: false,
             current_font_italic: false,
+            options: RenderOptions::default(),
+            heading_counters: Vec::new(),
+            inline_images: HashMap::new(),
+            quote: None,
+            quote_bar_color: QUOTE_BAR_COLOR,
+            inset: None,
+            page_layouts: Vec::new(),
+            link_url: None,
+            links: Vec::new(),
+            highlight: None,
+            link_notes: Vec::new(),
+            running_head: None,
+            equation_number: 0,
+            float: None,
+            text_style: None,
         };
         b.begin_page();
         b
     }
 
+    /// Advance the section counters for a heading at `level` and return its
+    /// number, or `None` when heading numbering is disabled. Skipped levels count as 0.
+    fn next_heading_number(&mut self, level: u8) -> Option<String> {
+        if !self.options.heading_numbering.enabled {
+            return None;
+        }
+        advance_heading_counters(&mut self.heading_counters, level);
+        let parts: Vec<String> = self.heading_counters.iter().map(|n| n.to_string()).collect();
+        Some(parts.join(&self.options.heading_numbering.separator))
+    }
+
     fn begin_page(&mut self) {
         self.current.clear();
         self.y = self.layout.content_top();
+        if let Some(header) = &self.options.header_image {
+            self.current.extend_from_slice(&crate::image::create_image_content_stream(
+                header.x,
+                header.y,
+                header.width,
+                header.height,
+                HEADER_IMAGE_NAME,
+            ));
+        }
         self.current.extend_from_slice(b"BT\n");
+        if self.options.running_header
+            && let Some(head) = self.running_head.clone()
+        {
+            self.write_running_head(&head);
+        }
         self.set_font_with_style(self.base_font_size, false, false);
     }
 
+    /// Show `head` in gray italics centered in the top margin
+    fn write_running_head(&mut self, head: &str) {
+        let size = self.base_font_size * 0.8;
+        self.set_font_with_style(size, false, true);
+        let width = crate::font_metrics::text_width(&self.current_font, head, size);
+        let x = self.layout.margin_left + (self.layout.content_width() - width) / 2.0;
+        let y = self.layout.height - self.layout.margin_top / 2.0;
+        self.current.extend_from_slice(
+            format!(
+                "{}\n1 0 0 1 {} {} Tm\n({}) Tj\n{}\n",
+                Color::gray().fill_operator(),
+                x,
+                y,
+                escape_pdf_string(head),
+                self.current_color.fill_operator()
+            )
+            .as_bytes(),
+        );
+    }
+
     fn set_font(&mut self, size: f32) {
         self.set_font_with_style(size, self.current_font_bold, self.current_font_italic);
     }
@@ -529,73 +1229,234 @@ impl ContentStreamBuilder {
     }
 
     fn set_monospace_font(&mut self, size: f32) {
+        let name = self.options.code_font.as_ref().map_or(FONT_COURIER, |font| font.name.as_str());
         self.current_font_size = size;
-        self.current_font = FONT_COURIER.to_string();
+        self.current_font = name.to_string();
         self.current
-            .extend_from_slice(format!("/{} {} Tf\n", FONT_COURIER, size).as_bytes());
+            .extend_from_slice(format!("/{} {} Tf\n", name, size).as_bytes());
     }
 
-    fn draw_rectangle(&mut self, x: f32, y: f32, width: f32, height: f32, fill_color: Color) {
-        // End text block temporarily to draw rectangle
-        self.current.extend_from_slice(b"ET\n");
+    /// Split code `lines` into the lines drawn within `width`, following `code_wrap`
+    fn code_visual_lines(&self, lines: &[&str], width: f32, size: f32) -> Vec<CodeVisualLine> {
+        let mut visual = Vec::new();
+        for (i, line) in lines.iter().enumerate() {
+            let number = i + 1;
+            let wrap = self.options.code_wrap;
+            if wrap == CodeWrap::None || self.code_text_width(line, size) <= width {
+                visual.push(CodeVisualLine { number, text: line.to_string(), continuation: false });
+                continue;
+            }
+            if wrap == CodeWrap::Truncate {
+                let ellipsis = "...";
+                let fit = self.code_prefix_len(line, width - self.code_text_width(ellipsis, size), size);
+                let text = format!("{}{}", &line[..fit], ellipsis);
+                visual.push(CodeVisualLine { number, text, continuation: false });
+                continue;
+            }
+            let indent = self.code_text_width(CODE_CONTINUATION_INDENT, size);
+            let mut rest = *line;
+            let mut continuation = false;
+            while !rest.is_empty() {
+                let available = if continuation { width - indent } else { width };
+                // Always take at least one character so narrow pages still progress
+                let fit = self
+                    .code_prefix_len(rest, available, size)
+                    .max(rest.chars().next().map_or(0, char::len_utf8));
+                visual.push(CodeVisualLine { number, text: rest[..fit].to_string(), continuation });
+                rest = &rest[fit..];
+                continuation = true;
+            }
+        }
+        visual
+    }
 
-        // Set fill color
-        self.current.extend_from_slice(
-            format!("{} {} {} rg\n", fill_color.r, fill_color.g, fill_color.b).as_bytes()
-        );
+    /// Byte length of the longest prefix of `text` no wider than `width` in the code font
+    fn code_prefix_len(&self, text: &str, width: f32, size: f32) -> usize {
+        let mut used = 0.0;
+        for (at, c) in text.char_indices() {
+            used += self.code_text_width(c.encode_utf8(&mut [0; 4]), size);
+            if used > width {
+                return at;
+            }
+        }
+        text.len()
+    }
 
-        // Draw and fill rectangle
-        self.current.extend_from_slice(
-            format!("{} {} {} {} re f\n", x, y, width, height).as_bytes()
-        );
+    /// Width of `text` set in the code font
+    fn code_text_width(&self, text: &str, size: f32) -> f32 {
+        match &self.options.code_font {
+            Some(font) => font.text_width(text, size),
+            None => text.chars().count() as f32 * size * 0.6, // Courier is monospace
+        }
+    }
 
-        // Resume text block
-        self.current.extend_from_slice(b"BT\n");
-        self.set_font(self.current_font_size);
+    fn draw_rectangle(&mut self, x: f32, y: f32, width: f32, height: f32, fill_color: Color) {
         // Always reset to black text after drawing rectangle
         self.current_color = Color::black();
-        self.current.extend_from_slice(
-            format!("0 0 0 rg\n").as_bytes()
-        );
+        self.draw_shape(&format!("{} {} {} {} re", x, y, width, height), &ShapeStyle::filled(fill_color));
+    }
+
+    fn draw_rectangle_outline(&mut self, x: f32, y: f32, width: f32, height: f32, line_width: f32, color: Color) {
+        self.draw_path(&format!("{} {} {} {} re", x, y, width, height), line_width, color);
+    }
+
+    fn draw_polyline(&mut self, points: &[(f32, f32)], line_width: f32, color: Color) {
+        let path: Vec<String> = points
+            .iter()
+            .enumerate()
+            .map(|(i, (x, y))| format!("{} {} {}", x, y, if i == 0 { "m" } else { "l" }))
+            .collect();
+        self.draw_path(&path.join(" "), line_width, color);
+    }
+
+    /// Stroke a path outside the text object, then resume text with the current font and color
+    fn draw_path(&mut self, path: &str, line_width: f32, color: Color) {
+        self.draw_shape(path, &ShapeStyle::stroked(color, line_width));
     }
 
     fn draw_line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, line_width: f32, color: Color) {
-        // End text block temporarily to draw line
-        self.current.extend_from_slice(b"ET\n");
+        self.draw_path(&format!("{} {} m {} {} l", x1, y1, x2, y2), line_width, color);
+    }
 
-        // Set stroke color and line width
-        self.current.extend_from_slice(
-            format!("{} {} {} RG\n", color.r, color.g, color.b).as_bytes()
-        );
-        self.current.extend_from_slice(
-            format!("{} w\n", line_width).as_bytes()
-        );
+    /// Paint a path outside the text object, then resume text with the current font and color
+    fn draw_shape(&mut self, path: &str, style: &ShapeStyle) {
+        self.draw_graphics(&shape_operators(path, style));
+    }
 
-        // Draw line
+    /// Write graphics operators outside the text object, then resume text
+    /// with the current font and color
+    fn draw_graphics(&mut self, operators: &str) {
+        self.current.extend_from_slice(b"ET\n");
+        self.current.extend_from_slice(operators.as_bytes());
+        self.current.extend_from_slice(b"BT\n");
+        self.set_font(self.current_font_size);
         self.current.extend_from_slice(
-            format!("{} {} m {} {} l S\n", x1, y1, x2, y2).as_bytes()
+            format!("{}\n", self.current_color.fill_operator()).as_bytes()
         );
+    }
 
-        // Resume text block
+    /// Draw an embedded image at the cursor, scaled down to the content width
+    fn draw_inline_image(&mut self, image: &InlineImage, centered: bool) {
+        let scale = (self.layout.content_width() / image.width).min(1.0);
+        let (width, height) = (image.width * scale, image.height * scale);
+        if self.needs_page_break(height) {
+            self.new_page();
+        }
+        self.y -= height;
+        let x = if centered {
+            self.layout.margin_left + (self.layout.content_width() - width) / 2.0
+        } else {
+            self.layout.margin_left
+        };
+        self.current.extend_from_slice(b"ET\n");
+        self.current.extend_from_slice(&crate::image::create_image_content_stream(
+            x,
+            self.y,
+            width,
+            height,
+            &image.name,
+        ));
         self.current.extend_from_slice(b"BT\n");
         self.set_font(self.current_font_size);
-        // Reset to current text color
         self.current.extend_from_slice(
-            format!("{} {} {} rg\n", self.current_color.r, self.current_color.g, self.current_color.b).as_bytes()
+            format!("{}\n", self.current_color.fill_operator()).as_bytes()
         );
+        self.y -= self.base_font_size * 0.5;
+    }
+
+    /// Draw an image at the left or right margin, its top level with the next
+    /// line of text, and wrap the following lines beside it down to its bottom
+    fn draw_floating_image(&mut self, image: &InlineImage, side: ImageFloat, caption: Option<&str>) {
+        let size = self.base_font_size;
+        let caption_size = size * 0.85;
+        let scale = (self.layout.content_width() * FLOAT_MAX_WIDTH / image.width).min(1.0);
+        let (width, height) = (image.width * scale, image.height * scale);
+        let caption_h = caption.map_or(0.0, |_| self.line_height(caption_size));
+        self.end_float();
+        if self.needs_page_break(height + caption_h) {
+            self.new_page();
+        }
+        let top = self.y + size * 0.78;
+        let mut bottom = top - height;
+        let x = match side {
+            ImageFloat::Right => self.layout.margin_left + self.layout.content_width() - width,
+            _ => self.layout.margin_left,
+        };
+        self.draw_graphics(&String::from_utf8_lossy(&crate::image::create_image_content_stream(
+            x, bottom, width, height, &image.name,
+        )));
+        if let Some(caption) = caption {
+            self.set_font_with_style(caption_size, false, true);
+            let caption_w = crate::font_metrics::text_width(&self.current_font, caption, caption_size);
+            self.current.extend_from_slice(
+                format!(
+                    "1 0 0 1 {} {} Tm\n({}) Tj\n",
+                    x + (width - caption_w) / 2.0,
+                    bottom - caption_size,
+                    escape_pdf_string(caption)
+                )
+                .as_bytes(),
+            );
+            self.set_font_with_style(size, false, false);
+            bottom -= caption_h;
+        }
+        // A gap of one font size separates the image from the text beside and below it
+        self.float = Some(FloatZone { side, width: width + size, bottom: bottom - size });
+    }
+
+    /// Stop wrapping beside a floated image, moving below it if the text is still alongside
+    fn end_float(&mut self) {
+        if let Some(zone) = self.float.take() {
+            self.y = self.y.min(zone.bottom);
+        }
+    }
+
+    /// Width taken from the `side` of the next line by a floated image beside it
+    fn float_inset(&self, side: ImageFloat) -> f32 {
+        match self.float {
+            Some(zone) if zone.side == side && self.y > zone.bottom => zone.width,
+            _ => 0.0,
+        }
     }
 
     /// Render a complete table with borders, text wrapping, and alignment
-    fn render_table(&mut self, rows: &[Vec<String>], base_font_size: f32, alignments: Option<&[crate::elements::TableAlignment]>) {
+    /// Columns flagged in `explicit` had their alignment set by the Markdown separator row
+    fn render_table(
+        &mut self,
+        rows: &[Vec<String>],
+        base_font_size: f32,
+        alignments: Option<&[crate::elements::TableAlignment]>,
+        explicit: &[bool],
+    ) {
         if rows.is_empty() {
             return;
         }
 
         let table_helper = PdfTableHelper::default();
-        let style = TableStyle::default();
+        let style = self.options.table_style.clone();
+
+        // Drop rows and cells past the style's limits before they are parsed and measured
+        let widest = rows.iter().map(Vec::len).max().unwrap_or(0);
+        if rows.len() > style.max_rows || widest > style.max_columns {
+            log::warn!(
+                "Table of {} rows and {} columns clamped to {} rows and {} columns",
+                rows.len(),
+                widest,
+                rows.len().min(style.max_rows),
+                widest.min(style.max_columns)
+            );
+        }
+        let rows: Vec<Vec<String>> = rows
+            .iter()
+            .take(style.max_rows)
+            .map(|row| row.iter().take(style.max_columns).cloned().collect())
+            .collect();
 
         // Convert string rows to TableRow with alignments
-        let table_rows = table_helper.convert_rows(rows, alignments);
+        let mut table_rows = table_helper.convert_rows(&rows, alignments);
+        if style.auto_align_numeric {
+            table_helper.align_numeric_columns(&mut table_rows, explicit);
+        }
 
         // Calculate table dimensions
         let dims = table_helper.renderer().calculate_dimensions(
@@ -609,8 +1470,8 @@ impl ContentStreamBuilder {
             return;
         }
 
-        let line_h = line_height(base_font_size);
-        let approx_char_width = base_font_size * 0.5;
+        let line_h = self.line_height(base_font_size);
+        let measure = |segment: &TextSegment| crate::table_renderer::segment_width(segment, base_font_size);
 
         // Add margin above table
         self.y -= style.margin_top;
@@ -693,21 +1554,21 @@ impl ContentStreamBuilder {
                 if col_idx >= dims.num_cols { break; }
                 let cell_width = dims.column_widths[col_idx];
                 let cell_height = dims.row_heights[row_idx];
-                let max_chars = ((cell_width - style.cell_padding * 2.0) / approx_char_width).floor().max(1.0) as usize;
+                let max_width = cell_width - style.cell_padding * 2.0;
 
-                // Wrap text into lines using the table helper
-                let wrapped = table_helper.renderer().wrap_text(&cell.content, max_chars);
+                // Wrap styled runs into lines using the table helper
+                let wrapped = table_helper.renderer().wrap_segments(&cell.segments, max_width, &measure);
 
                 // Calculate vertical centering
-                let text_height = wrapped.line_count as f32 * line_h;
+                let text_height = wrapped.len() as f32 * line_h;
                 let start_y_pos = row_y - (cell_height - text_height) / 2.0 - line_h / 3.0;
 
-                // Render each line with proper alignment
-                for (line_idx, line) in wrapped.lines.iter().enumerate() {
-                    let line_width = line.len() as f32 * approx_char_width;
+                // Render each line with proper alignment, switching fonts per run
+                for (line_idx, line) in wrapped.iter().enumerate() {
+                    let line_width: f32 = line.iter().map(&measure).sum();
 
                     // Calculate X position using the table helper
-                    let x = table_helper.renderer().calculate_text_x(
+                    let mut x = table_helper.renderer().calculate_text_x(
                         &cell.alignment,
                         col_x,
                         cell_width,
@@ -717,12 +1578,27 @@ impl ContentStreamBuilder {
 
                     let y = start_y_pos - (line_idx as f32 * line_h);
 
-                    self.current.extend_from_slice(
-                        format!("1 0 0 1 {} {} Tm\n", x, y).as_bytes()
-                    );
-                    self.current.extend_from_slice(
-                        format!("({}) Tj\n", PdfTableHelper::escape_pdf_string_static(line)).as_bytes()
-                    );
+                    for segment in line {
+                        match segment {
+                            TextSegment::Bold(_) => self.set_font_with_style(base_font_size, true, false),
+                            TextSegment::Italic(_) => self.set_font_with_style(base_font_size, false, true),
+                            TextSegment::BoldItalic(_) => self.set_font_with_style(base_font_size, true, true),
+                            TextSegment::Code(_) => self.set_monospace_font(base_font_size * 0.9),
+                            _ => self.set_font_with_style(base_font_size, false, false),
+                        }
+                        self.set_color(match segment {
+                            TextSegment::Code(_) => Color::gray(),
+                            TextSegment::Link { .. } => Color::blue(),
+                            _ => Color::black(),
+                        });
+                        self.current.extend_from_slice(
+                            format!("1 0 0 1 {} {} Tm\n", x, y).as_bytes()
+                        );
+                        self.current.extend_from_slice(
+                            format!("({}) Tj\n", PdfTableHelper::escape_pdf_string_static(segment.text())).as_bytes()
+                        );
+                        x += measure(segment);
+                    }
                 }
 
                 col_x += cell_width;
@@ -730,6 +1606,8 @@ impl ContentStreamBuilder {
             row_y -= dims.row_heights[row_idx];
         }
 
+        self.set_font_with_style(base_font_size, false, false);
+        self.reset_color();
         self.y -= dims.total_height + style.margin_bottom;
     }
 
@@ -741,40 +1619,124 @@ impl ContentStreamBuilder {
         text.len() as f32 * font_size * multiplier
     }
 
-    /// Emit wrapped text that fits within the content width
+    /// Emit wrapped text that fits within the content width.
+    ///
+    /// Non-breaking spaces (U+00A0) keep their neighbours on one line; soft
+    /// hyphens (U+00AD) are optional break points that show a hyphen only
+    /// when a line actually breaks there.
     fn emit_wrapped_text(&mut self, text: &str, font_size: f32) {
-        let max_width = self.layout.content_width();
-        let approx_char_width = font_size * 0.5;
-        let max_chars = (max_width / approx_char_width).floor() as usize;
-
-        if text.len() <= max_chars {
-            self.emit_line(text, font_size);
+        // No-break spaces only hold words together while wrapping; the standard
+        // fonts are drawn without /Encoding, so they go out as plain spaces
+        let shown = |line: &str| line.replace(SOFT_HYPHEN, "").replace(NO_BREAK_SPACE, " ");
+        if text.len() <= self.wrap_chars(font_size) {
+            self.emit_line(&shown(text), font_size);
             return;
         }
 
         // Simple word wrapping
-        let words: Vec<&str> = text.split_whitespace().collect();
+        let chars = |s: &str| s.chars().count();
+        let words = text
+            .split(|c: char| c.is_whitespace() && c != NO_BREAK_SPACE)
+            .filter(|w| !w.is_empty());
         let mut current_line = String::new();
 
         for word in words {
-            let test_line = if current_line.is_empty() {
-                word.to_string()
-            } else {
-                format!("{} {}", current_line, word)
-            };
+            let mut rest = word;
+            loop {
+                let visible = rest.replace(SOFT_HYPHEN, "");
+                let used = if current_line.is_empty() { 0 } else { chars(&current_line) + 1 };
+                let max_chars = self.wrap_chars(font_size);
+                if used + chars(&visible) <= max_chars {
+                    if !current_line.is_empty() {
+                        current_line.push(' ');
+                    }
+                    current_line.push_str(&visible);
+                    break;
+                }
 
-            if test_line.len() <= max_chars {
-                current_line = test_line;
-            } else {
-                if !current_line.is_empty() {
-                    self.emit_line(&current_line, font_size);
+                // Break at the last soft hyphen whose prefix, plus the hyphen, still fits
+                let room = max_chars.saturating_sub(used + 1);
+                let split = rest
+                    .rmatch_indices(SOFT_HYPHEN)
+                    .map(|(i, _)| i)
+                    .find(|&i| chars(&rest[..i].replace(SOFT_HYPHEN, "")) <= room);
+                match split {
+                    Some(i) => {
+                        if !current_line.is_empty() {
+                            current_line.push(' ');
+                        }
+                        current_line.push_str(&rest[..i].replace(SOFT_HYPHEN, ""));
+                        current_line.push('-');
+                        self.emit_line(&shown(&current_line), font_size);
+                        current_line.clear();
+                        rest = &rest[i + SOFT_HYPHEN.len_utf8()..];
+                    }
+                    None if !current_line.is_empty() => {
+                        self.emit_line(&shown(&current_line), font_size);
+                        current_line.clear();
+                    }
+                    None => {
+                        current_line = visible;
+                        break;
+                    }
                 }
-                current_line = word.to_string();
             }
         }
 
         if !current_line.is_empty() {
-            self.emit_line(&current_line, font_size);
+            self.emit_line(&shown(&current_line), font_size);
+        }
+    }
+
+    /// Emit `text` centered when it fits on one line, else wrapped
+    fn emit_centered_text(&mut self, text: &str, font_size: f32) {
+        if text.chars().count() <= self.wrap_chars(font_size) {
+            self.emit_line_aligned(text, font_size, TextAlign::Center);
+        } else {
+            self.emit_wrapped_text(text, font_size);
+        }
+    }
+
+    /// Characters that fit on the next line, allowing for any inset
+    fn wrap_chars(&self, font_size: f32) -> usize {
+        let approx_char_width = font_size * 0.5;
+        ((self.layout.content_width() - self.line_inset() - self.line_inset_right()) / approx_char_width).floor()
+            as usize
+    }
+
+    fn line_inset(&self) -> f32 {
+        self.inset.map_or(0.0, |(width, _)| width) + self.float_inset(ImageFloat::Left)
+    }
+
+    fn line_inset_right(&self) -> f32 {
+        self.float_inset(ImageFloat::Right)
+    }
+
+    /// Draw `letter` as a drop cap `lines` lines tall at the cursor and inset
+    /// the next `lines` lines so the text wraps around it
+    fn begin_drop_cap(&mut self, letter: char, lines: usize, font_size: f32) {
+        let lh = self.line_height(font_size);
+        if self.needs_page_break(lh * lines as f32) {
+            self.new_page();
+        }
+        // The letter reaches from the first line's cap height down to the last line's baseline
+        let cap_size = (lines - 1) as f32 * lh / HELVETICA_CAP_HEIGHT + font_size;
+        let baseline = self.y - (lines - 1) as f32 * lh;
+        let letter = letter.to_string();
+        self.set_font(cap_size);
+        self.current.extend_from_slice(
+            format!("1 0 0 1 {} {} Tm\n({}) Tj\n", self.layout.margin_left, baseline, escape_pdf_string(&letter))
+                .as_bytes(),
+        );
+        self.set_font(font_size);
+        let width = crate::font_metrics::text_width(&self.current_font, &letter, cap_size) + font_size * 0.25;
+        self.inset = Some((width, lines));
+    }
+
+    /// Move below a drop cap whose inset lines were not all used
+    fn end_drop_cap(&mut self, font_size: f32) {
+        if let Some((_, lines)) = self.inset.take() {
+            self.y -= lines as f32 * self.line_height(font_size);
         }
     }
 
@@ -782,7 +1744,7 @@ impl ContentStreamBuilder {
         if self.current_color != color {
             self.current_color = color;
             self.current
-                .extend_from_slice(format!("{} {} {} rg\n", color.r, color.g, color.b).as_bytes());
+                .extend_from_slice(format!("{}\n", color.fill_operator()).as_bytes());
         }
     }
 
@@ -790,15 +1752,156 @@ impl ContentStreamBuilder {
         self.set_color(Color::black());
     }
 
+    /// Vertical advance per line of text at `font_size`
+    fn line_height(&self, font_size: f32) -> f32 {
+        match self.options.leading_multiplier {
+            Some(multiplier) => font_size * multiplier,
+            None => line_height(font_size),
+        }
+    }
+
     fn needs_page_break(&self, extra: f32) -> bool {
         self.y - extra < self.layout.margin_bottom
     }
 
     fn new_page(&mut self) {
+        // Close the quote bars at the bottom of this page and restart them on the next
+        if let Some((depth, top)) = self.quote {
+            self.draw_quote_bars(depth, top, self.y + self.base_font_size * 0.8);
+        }
         self.end_text_block();
         self.pages.push(self.current.clone());
+        self.page_layouts.push(self.layout);
         self.page_number += 1;
+        self.float = None;
         self.begin_page();
+        if let Some((depth, _)) = self.quote {
+            self.quote = Some((depth, self.y + self.base_font_size * 0.8));
+        }
+    }
+
+    /// Render `content` inside `depth` levels of blockquote: indented, in gray,
+    /// with one bar per level down the left side
+    fn render_quoted(&mut self, depth: u8, content: impl FnOnce(&mut Self)) {
+        if self.needs_page_break(self.line_height(self.base_font_size)) {
+            self.new_page();
+        }
+        let indent = QUOTE_INDENT * depth as f32;
+        self.quote = Some((depth, self.y + self.base_font_size * 0.8));
+        self.layout.margin_left += indent;
+        self.set_color(Color::gray());
+        content(self);
+        self.reset_color();
+        if let Some((depth, top)) = self.quote.take() {
+            self.draw_quote_bars(depth, top, self.y + self.base_font_size * 0.8);
+        }
+        self.layout.margin_left -= indent;
+    }
+
+    /// Render a callout: a bar in the kind's color, its bold label in that color, then the body
+    fn render_callout(&mut self, kind: crate::elements::CalloutKind, text: &str) {
+        let size = self.base_font_size;
+        if self.needs_page_break(self.line_height(size) * 2.0) {
+            self.new_page();
+        }
+        let color = callout_color(kind);
+        self.quote = Some((1, self.y + size * 0.8));
+        self.quote_bar_color = color;
+        self.layout.margin_left += QUOTE_INDENT;
+        self.set_color(color);
+        self.set_font_with_style(size, true, false);
+        self.emit_wrapped_text(kind.label(), size);
+        self.set_font_with_style(size, false, false);
+        self.reset_color();
+        self.emit_wrapped_text(text, size);
+        if let Some((depth, top)) = self.quote.take() {
+            self.draw_quote_bars(depth, top, self.y + size * 0.8);
+        }
+        self.quote_bar_color = QUOTE_BAR_COLOR;
+        self.layout.margin_left -= QUOTE_INDENT;
+    }
+
+    /// Draw a chart in a box the width of the content area: the title, a
+    /// value axis scaled from zero to the largest value, and the bars or
+    /// line with each value's label below it
+    fn render_chart(&mut self, kind: crate::elements::ChartKind, data: &[(String, f64)], title: &str) {
+        let size = self.base_font_size;
+        let label_size = size * 0.8;
+        let label_h = self.line_height(label_size);
+        if self.needs_page_break(self.line_height(size) * 2.0 + CHART_HEIGHT) {
+            self.new_page();
+        }
+        if !title.is_empty() {
+            self.set_font_with_style(size, true, false);
+            self.emit_line_aligned(title, size, TextAlign::Center);
+            self.set_font_with_style(size, false, false);
+        }
+
+        let font = self.current_font.clone();
+        // NaN and infinite values have no height; they are left out of the scale and the plot
+        let max = data.iter().map(|(_, v)| *v).filter(|v| v.is_finite()).fold(0.0, f64::max);
+        let max = if max > 0.0 { max } else { 1.0 };
+        let axis_labels = [format_chart_value(0.0), format_chart_value(max / 2.0), format_chart_value(max)];
+        let axis_w = axis_labels
+            .iter()
+            .map(|l| crate::font_metrics::text_width(&font, l, label_size))
+            .fold(0.0, f32::max)
+            + 4.0;
+
+        let top = self.y - size * 0.5;
+        let bottom = top - CHART_HEIGHT;
+        let x0 = self.layout.margin_left + axis_w;
+        let y0 = bottom + label_h;
+        let plot_w = self.layout.content_width() - axis_w;
+        let plot_h = top - y0;
+        let slot = plot_w / data.len().max(1) as f32;
+        let y_of = |value: f64| y0 + (value.max(0.0) / max) as f32 * plot_h;
+
+        match kind {
+            crate::elements::ChartKind::Bar => {
+                for (i, (_, value)) in data.iter().enumerate().filter(|(_, (_, v))| v.is_finite()) {
+                    let height = y_of(*value) - y0;
+                    if height > 0.0 {
+                        self.draw_rectangle(x0 + slot * (i as f32 + 0.2), y0, slot * 0.6, height, CHART_COLOR);
+                    }
+                }
+            }
+            crate::elements::ChartKind::Line => {
+                let points: Vec<(f32, f32)> = data
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (_, value))| value.is_finite())
+                    .map(|(i, (_, value))| (x0 + slot * (i as f32 + 0.5), y_of(*value)))
+                    .collect();
+                self.draw_polyline(&points, 1.5, CHART_COLOR);
+            }
+        }
+        self.draw_line(x0, y0, x0, top, 0.5, Color::black());
+        self.draw_line(x0, y0, x0 + plot_w, y0, 0.5, Color::black());
+
+        self.set_font(label_size);
+        for (label, value) in axis_labels.iter().zip([0.0, max / 2.0, max]) {
+            let x = x0 - 4.0 - crate::font_metrics::text_width(&font, label, label_size);
+            let y = y_of(value) - label_size * 0.35;
+            self.current.extend_from_slice(format!("1 0 0 1 {} {} Tm\n({}) Tj\n", x, y, escape_pdf_string(label)).as_bytes());
+        }
+        for (i, (label, _)) in data.iter().enumerate() {
+            let width = crate::font_metrics::text_width(&font, label, label_size);
+            let x = x0 + slot * (i as f32 + 0.5) - width / 2.0;
+            let y = bottom + label_h - label_size;
+            self.current.extend_from_slice(format!("1 0 0 1 {} {} Tm\n({}) Tj\n", x, y, escape_pdf_string(label)).as_bytes());
+        }
+        self.set_font(size);
+        self.y = bottom;
+        self.emit_empty_line();
+    }
+
+    /// One vertical bar per quote level, left of the indented text
+    fn draw_quote_bars(&mut self, depth: u8, top: f32, bottom: f32) {
+        for level in 0..depth {
+            let x = self.layout.margin_left - QUOTE_INDENT * (depth - level) as f32 + 2.0;
+            self.draw_line(x, bottom, x, top, 2.0, self.quote_bar_color);
+        }
     }
 
     fn end_text_block(&mut self) {
@@ -809,17 +1912,8 @@ impl ContentStreamBuilder {
     }
 
     fn write_page_number(&mut self) {
-        let label = format!("Page {}", self.page_number);
-        let x = self.layout.width / 2.0 - 20.0;
-        let y = self.layout.margin_bottom / 2.0;
-        self.current.extend_from_slice(b"BT\n");
-        self.current
-            .extend_from_slice(format!("/F1 9 Tf\n").as_bytes());
-        self.current
-            .extend_from_slice(format!("1 0 0 1 {} {} Tm\n", x, y).as_bytes());
-        self.current
-            .extend_from_slice(format!("({}) Tj\n", escape_pdf_string(&label)).as_bytes());
-        self.current.extend_from_slice(b"ET\n");
+        let footer = page_number_footer(&self.options, &self.layout, self.page_number);
+        self.current.extend_from_slice(&footer);
     }
 
     fn emit_line(&mut self, text: &str, font_size: f32) {
@@ -827,7 +1921,7 @@ impl ContentStreamBuilder {
     }
 
     fn emit_line_aligned(&mut self, text: &str, font_size: f32, align: TextAlign) {
-        let lh = line_height(font_size);
+        let lh = self.line_height(font_size);
         if self.needs_page_break(lh) {
             self.new_page();
         }
@@ -835,76 +1929,1091 @@ impl ContentStreamBuilder {
         let escaped = escape_pdf_string(text);
 
         let x = match align {
-            TextAlign::Left => self.layout.margin_left,
+            TextAlign::Left => self.layout.margin_left + self.line_inset(),
             TextAlign::Center => {
                 // Approximate: 0.5 * char_count * font_size * 0.5
                 let approx_width = text.len() as f32 * font_size * 0.5;
-                self.layout.margin_left + (self.layout.content_width() - approx_width) / 2.0
+                let available = self.layout.content_width() - self.line_inset() - self.line_inset_right();
+                self.layout.margin_left + self.line_inset() + (available - approx_width) / 2.0
             }
             TextAlign::Right => {
                 // Approximate: 0.5 * char_count * font_size * 0.5
                 let approx_width = text.len() as f32 * font_size * 0.5;
-                self.layout.margin_left + self.layout.content_width() - approx_width
+                self.layout.margin_left + self.layout.content_width() - self.line_inset_right() - approx_width
             }
             TextAlign::Justify => {
                 // Justify is similar to left for positioning, but would adjust word spacing
                 // For simplicity, we treat it like left for now
-                self.layout.margin_left
+                self.layout.margin_left + self.line_inset()
             }
         };
 
+        if let Some(color) = self.highlight {
+            // Fill from the descender to the ascender, behind the glyphs drawn next
+            let width = crate::font_metrics::text_width(&self.current_font, text, font_size);
+            let rect = format!("{} {} {} {} re", x, self.y - font_size * 0.22, width, font_size);
+            self.draw_graphics(&format!("q\n{}Q\n", shape_operators(&rect, &ShapeStyle::filled(color))));
+        }
+
+        // A styled run gets a graphics state of its own, so its rendering mode,
+        // stroke color and line width end with it
+        if let Some(style) = self.text_style {
+            self.current.extend_from_slice(b"ET\nq\nBT\n");
+            self.current.extend_from_slice(style.operators().as_bytes());
+        }
         // Use Tm (text matrix) for absolute positioning — Td is relative and compounds
         self.current
             .extend_from_slice(format!("1 0 0 1 {} {} Tm\n", x, self.y).as_bytes());
-        self.current
-            .extend_from_slice(format!("({}) Tj\n", escaped).as_bytes());
+        if text.chars().any(|c| self.fallback_font_for(c).is_some()) {
+            self.show_with_fallback(text, font_size);
+        } else if let Some(kerned) = self.options.kerning.then(|| kerned_show(&self.current_font, text)).flatten() {
+            self.current.extend_from_slice(kerned.as_bytes());
+        } else {
+            self.current
+                .extend_from_slice(format!("({}) Tj\n", escaped).as_bytes());
+        }
+        if let Some(url) = &self.link_url {
+            let width = crate::font_metrics::text_width(&self.current_font, text, font_size);
+            self.links.push(LinkRegion {
+                page: self.pages.len(),
+                rect: [x, self.y - font_size * 0.22, x + width, self.y + font_size * 0.78],
+                url: url.clone(),
+            });
+        }
+        if self.text_style.is_some() {
+            self.current.extend_from_slice(b"ET\nQ\nBT\n");
+        }
+        self.y -= lh;
+        if let Some((_, lines)) = &mut self.inset {
+            *lines -= 1;
+            if *lines == 0 {
+                self.inset = None;
+            }
+        }
+    }
+
+    /// Font to switch to for a character the standard fonts lack: a base-14
+    /// symbol font when it has the glyph, else the first configured fallback
+    fn fallback_font_for(&self, c: char) -> Option<RunFont> {
+        if standard_font_covers(c) {
+            return None;
+        }
+        if let Some((font, _)) = crate::pdf::symbol_font_encode(c) {
+            return Some(RunFont::Symbol(font));
+        }
+        self.options.font_fallback.font_for(c).map(RunFont::Cjk)
+    }
+
+    /// Show `text` in runs, switching to the fallback font that covers each character
+    fn show_with_fallback(&mut self, text: &str, font_size: f32) {
+        let mut runs: Vec<(Option<RunFont>, String)> = Vec::new();
+        for c in text.chars() {
+            let font = self.fallback_font_for(c);
+            match runs.last_mut() {
+                Some((run_font, run)) if *run_font == font => run.push(c),
+                _ => runs.push((font, c.to_string())),
+            }
+        }
+        for (font, run) in &runs {
+            match font {
+                Some(RunFont::Cjk(fallback)) => {
+                    // UCS-2 code units, as the predefined CMaps expect
+                    let hex: String = run.encode_utf16().map(|u| format!("{:04X}", u)).collect();
+                    self.current.extend_from_slice(
+                        format!("/{} {} Tf\n<{}> Tj\n", fallback.base_font(), font_size, hex).as_bytes(),
+                    );
+                }
+                Some(RunFont::Symbol(name)) => {
+                    let codes: String = run
+                        .chars()
+                        .filter_map(crate::pdf::symbol_font_encode)
+                        .map(|(_, code)| format!("\\{:03o}", code))
+                        .collect();
+                    self.current
+                        .extend_from_slice(format!("/{} {} Tf\n({}) Tj\n", name, font_size, codes).as_bytes());
+                }
+                None => {
+                    self.current
+                        .extend_from_slice(format!("/{} {} Tf\n", self.current_font, font_size).as_bytes());
+                    self.current
+                        .extend_from_slice(format!("({}) Tj\n", escape_pdf_string(run)).as_bytes());
+                }
+            }
+        }
+        if runs.last().is_some_and(|(font, _)| font.is_some()) {
+            let current_font = self.current_font.clone();
+            self.current
+                .extend_from_slice(format!("/{} {} Tf\n", current_font, font_size).as_bytes());
+        }
+    }
+
+    /// Draw laid-out math in the current font and color with its baseline
+    /// origin at (`x`, `baseline`)
+    fn draw_math(&mut self, math: &crate::math::MathLayout, x: f32, baseline: f32) {
+        let size = self.current_font_size;
+        for item in &math.items {
+            match item {
+                crate::math::MathItem::Text { x: dx, y, rise, size: text_size, text } => {
+                    self.set_font(*text_size);
+                    self.current
+                        .extend_from_slice(format!("1 0 0 1 {} {} Tm\n", x + dx, baseline + y).as_bytes());
+                    if *rise != 0.0 {
+                        self.current.extend_from_slice(format!("{} Ts\n", rise).as_bytes());
+                    }
+                    if text.chars().any(|c| self.fallback_font_for(c).is_some()) {
+                        self.show_with_fallback(text, *text_size);
+                    } else {
+                        self.current
+                            .extend_from_slice(format!("({}) Tj\n", escape_pdf_string(text)).as_bytes());
+                    }
+                    if *rise != 0.0 {
+                        self.current.extend_from_slice(b"0 Ts\n");
+                    }
+                }
+                crate::math::MathItem::Rule { x: dx, y, width, thickness } => {
+                    let (x1, y) = (x + dx, baseline + y);
+                    self.draw_line(x1, y, x1 + width, y, *thickness, self.current_color);
+                }
+            }
+        }
+        self.set_font(size);
+    }
+
+    /// Emit a link's label in blue, wrapped, recording the region of each line
+    /// it occupies so every line becomes clickable
+    fn emit_link(&mut self, text: &str, url: &str, font_size: f32) {
+        let label = match self.options.link_mode {
+            LinkRenderMode::Footnote if text != url => {
+                let number = match self.link_notes.iter().position(|u| u == url) {
+                    Some(index) => index + 1,
+                    None => {
+                        self.link_notes.push(url.to_string());
+                        self.link_notes.len()
+                    }
+                };
+                format!("{} [{}]", text, number)
+            }
+            _ => link_label(text, url),
+        };
+        self.set_color(Color::blue());
+        self.link_url = Some(url.to_string());
+        self.emit_wrapped_text(&label, font_size);
+        self.link_url = None;
+        self.reset_color();
+    }
+
+    /// Emit the numbered list of URLs collected from footnote-style links
+    fn emit_link_notes(&mut self, font_size: f32) {
+        if self.link_notes.is_empty() {
+            return;
+        }
+        let note_size = font_size * 0.85;
+        self.emit_empty_line();
+        self.emit_horizontal_rule();
+        for (index, url) in std::mem::take(&mut self.link_notes).into_iter().enumerate() {
+            self.link_url = Some(url.clone());
+            self.emit_wrapped_text(&format!("[{}] {}", index + 1, url), note_size);
+            self.link_url = None;
+        }
+    }
+
+    fn emit_colored_line(&mut self, text: &str, font_size: f32, color: Color) {
+        self.set_color(color);
+        self.emit_line(text, font_size);
+        self.reset_color();
+    }
+
+    fn emit_empty_line(&mut self) {
+        let lh = self.line_height(self.base_font_size) * 0.5;
+        if self.needs_page_break(lh) {
+            self.new_page();
+        }
         self.y -= lh;
     }
 
-    fn emit_colored_line(&mut self, text: &str, font_size: f32, color: Color) {
-        self.set_color(color);
-        self.emit_line(text, font_size);
-        self.reset_color();
+    /// Emit a list item indented `list_indent` points per nesting level.
+    /// Returns the offset of its text from the margin.
+    fn emit_list_item(&mut self, marker: &str, text: &str, depth: u8, font_size: f32) -> f32 {
+        let indent = self.options.list_indent * depth as f32;
+        self.layout.margin_left += indent;
+        self.emit_wrapped_text(&format!("{}{}", marker, text), font_size);
+        self.layout.margin_left -= indent;
+        indent + crate::font_metrics::text_width(&self.current_font, marker, font_size)
+    }
+
+    fn emit_horizontal_rule(&mut self) {
+        // Add spacing above the rule
+        self.y -= self.line_height(self.base_font_size) / 2.0;
+
+        // Check for page break
+        if self.needs_page_break(self.line_height(self.base_font_size)) {
+            self.new_page();
+        }
+
+        // Draw a horizontal line centered in the content area
+        let rule = self.options.rule_style;
+        let width = self.layout.content_width() * rule.width_fraction.clamp(0.0, 1.0);
+        let x1 = self.layout.margin_left + (self.layout.content_width() - width) / 2.0;
+        let x2 = x1 + width;
+        let y = self.y;
+        match rule.style.dash_operator(rule.thickness) {
+            None => self.draw_line(x1, y, x2, y, rule.thickness, rule.color),
+            Some(dash) => {
+                // Keep the dash pattern from leaking into later strokes
+                let path = format!("{} {} m {} {} l", x1, y, x2, y);
+                let stroke = shape_operators(&path, &ShapeStyle::stroked(rule.color, rule.thickness));
+                self.draw_graphics(&format!("q\n{}\n{}Q\n", dash, stroke));
+            }
+        }
+
+        // Add spacing below the rule
+        self.y -= self.line_height(self.base_font_size);
+    }
+
+    /// Copy raw operators into the page outside the text object, in their own
+    /// graphics state with the origin moved to the cursor
+    fn emit_raw_content(&mut self, stream: &[u8]) {
+        self.current.extend_from_slice(b"ET\nq\n");
+        self.current
+            .extend_from_slice(format!("1 0 0 1 {} {} cm\n", self.layout.margin_left, self.y).as_bytes());
+        self.current.extend_from_slice(stream);
+        self.current.extend_from_slice(b"\nQ\nBT\n");
+        self.set_font(self.current_font_size);
+        self.current.extend_from_slice(
+            format!("{}\n", self.current_color.fill_operator()).as_bytes()
+        );
+    }
+
+    fn finish(self) -> Vec<Vec<u8>> {
+        self.finish_with_layouts().0
+    }
+
+    /// Close the last page, returning every page with the layout it was drawn with
+    fn finish_with_layouts(mut self) -> (Vec<Vec<u8>>, Vec<PageLayout>) {
+        self.end_text_block();
+        self.pages.push(self.current);
+        self.page_layouts.push(self.layout);
+        (self.pages, self.page_layouts)
+    }
+
+    /// Continue on a new page drawn with `layout`; an untouched page is reused
+    fn set_page_layout(&mut self, layout: PageLayout) {
+        if self.y < self.layout.content_top() {
+            self.end_text_block();
+            self.pages.push(std::mem::take(&mut self.current));
+            self.page_layouts.push(self.layout);
+            self.page_number += 1;
+        }
+        self.layout = layout;
+        self.begin_page();
+    }
+}
+
+// --- Public API ---
+
+/// Stage of a long-running operation reported through a progress callback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressPhase {
+    /// Laying out elements into page content streams
+    Rendering,
+    /// Loading input documents for a merge
+    Merging,
+    /// Writing the finished document
+    Writing,
+}
+
+/// Progress report passed to `*_with_progress` callbacks.
+///
+/// `current`/`total` count the units of the phase (elements while rendering,
+/// files while merging); `pages` is the number of pages produced so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub phase: ProgressPhase,
+    pub current: usize,
+    pub total: usize,
+    pub pages: usize,
+}
+
+/// Automatic section numbering for headings ("1", "1.1", "1.2", "2", ...)
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadingNumbering {
+    pub enabled: bool,
+    /// Placed between the counters of successive levels
+    pub separator: String,
+}
+
+impl Default for HeadingNumbering {
+    fn default() -> Self {
+        HeadingNumbering { enabled: false, separator: ".".to_string() }
+    }
+}
+
+impl HeadingNumbering {
+    /// Numbering enabled with the usual "." separator
+    pub fn enabled() -> Self {
+        HeadingNumbering { enabled: true, ..Default::default() }
+    }
+}
+
+/// Case transform applied to heading text when rendering
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HeadingCase {
+    /// Headings are drawn as written
+    #[default]
+    None,
+    /// "The Quick Brown Fox", with short words such as "of" and "the" lowercase
+    /// unless first or last
+    Title,
+    /// "THE QUICK BROWN FOX"
+    Upper,
+    /// "The quick brown fox"
+    Sentence,
+}
+
+/// Words left lowercase inside a title-case heading
+const TITLE_CASE_STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "as", "at", "but", "by", "for", "from", "in", "into", "nor", "of", "on", "or",
+    "per", "the", "to", "via", "vs", "with",
+];
+
+impl HeadingCase {
+    /// `text` in this case. Words written entirely in capitals, such as "PDF",
+    /// are kept as acronyms by the title and sentence transforms.
+    pub fn apply(self, text: &str) -> String {
+        let words: Vec<&str> = text.split(' ').collect();
+        let last = words.iter().rposition(|w| !w.is_empty()).unwrap_or(0);
+        let first = words.iter().position(|w| !w.is_empty()).unwrap_or(0);
+        match self {
+            HeadingCase::None => text.to_string(),
+            HeadingCase::Upper => text.to_uppercase(),
+            HeadingCase::Title | HeadingCase::Sentence => words
+                .iter()
+                .enumerate()
+                .map(|(i, word)| {
+                    let is_acronym = word.chars().filter(|c| c.is_alphabetic()).count() > 1
+                        && !word.chars().any(|c| c.is_lowercase());
+                    if is_acronym {
+                        return word.to_string();
+                    }
+                    let lower = word.to_lowercase();
+                    let capitalize = match self {
+                        HeadingCase::Title => {
+                            i == first || i == last || !TITLE_CASE_STOP_WORDS.contains(&lower.as_str())
+                        }
+                        _ => i == first,
+                    };
+                    if capitalize { capitalize_first(&lower) } else { lower }
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+}
+
+/// `word` with its first letter uppercased
+fn capitalize_first(word: &str) -> String {
+    match word.char_indices().find(|(_, c)| c.is_alphabetic()) {
+        Some((at, c)) => format!("{}{}{}", &word[..at], c.to_uppercase(), &word[at + c.len_utf8()..]),
+        None => word.to_string(),
+    }
+}
+
+/// How code lines wider than the content area are drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CodeWrap {
+    /// Lines are drawn whole and may run past the right margin
+    #[default]
+    None,
+    /// Lines wrap at the content width; continuations are indented
+    Soft,
+    /// Lines are cut at the content width and end with "..."
+    Truncate,
+}
+
+/// How links show their URL
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LinkRenderMode {
+    /// The URL follows the link text in parentheses
+    #[default]
+    Inline,
+    /// The link text gets a note number and the URLs are listed at the end
+    /// of the document, for print
+    Footnote,
+}
+
+/// Placement of block math
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DisplayMathStyle {
+    /// Center each row within the content width, as in LaTeX display mode
+    pub centered: bool,
+    /// Number the blocks "(1)", "(2)", ... at the right margin
+    pub numbered: bool,
+}
+
+impl DisplayMathStyle {
+    /// Centered and numbered equations
+    pub fn numbered() -> Self {
+        DisplayMathStyle { centered: true, numbered: true }
+    }
+}
+
+/// What a document without content renders as
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum EmptyDocument {
+    /// A blank page with the usual footer
+    #[default]
+    Blank,
+    /// A truly empty page, without footer
+    Bare,
+    /// A page holding this text, e.g. "(no content)"
+    Placeholder(String),
+}
+
+/// Whitespace handling of code block lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodeBlockStyle {
+    /// Columns between tab stops; tabs expand to spaces up to the next stop
+    pub tab_width: usize,
+    /// Drop whitespace at the end of each line
+    pub trim_trailing: bool,
+}
+
+impl Default for CodeBlockStyle {
+    fn default() -> Self {
+        CodeBlockStyle { tab_width: 4, trim_trailing: false }
+    }
+}
+
+impl CodeBlockStyle {
+    /// `line` with its tabs expanded and, if set, trailing whitespace removed
+    pub fn apply(&self, line: &str) -> String {
+        let line = if self.trim_trailing { line.trim_end() } else { line };
+        let mut out = String::with_capacity(line.len());
+        let mut column = 0;
+        for c in line.chars() {
+            if c == '\t' {
+                let spaces = self.tab_width.max(1) - column % self.tab_width.max(1);
+                out.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+            } else {
+                out.push(c);
+                column += 1;
+            }
+        }
+        out
+    }
+}
+
+/// Widest a floated image is drawn, as a share of the content width
+const FLOAT_MAX_WIDTH: f32 = 0.5;
+
+/// The region beside a floated image: lines with their baseline above
+/// `bottom` lose `width` on the image's side
+#[derive(Debug, Clone, Copy)]
+struct FloatZone {
+    side: ImageFloat,
+    width: f32,
+    bottom: f32,
+}
+
+/// Height of a chart's plot and label area, below its title
+const CHART_HEIGHT: f32 = 180.0;
+
+/// Fill of chart bars and stroke of chart lines
+const CHART_COLOR: Color = Color { r: 0.26, g: 0.45, b: 0.76, space: ColorSpace::Rgb };
+
+/// Axis label for a chart value, without decimals when it is whole
+fn format_chart_value(value: f64) -> String {
+    if value.fract() == 0.0 { format!("{:.0}", value) } else { format!("{:.1}", value) }
+}
+
+/// Extra indent of the continuation lines of a soft-wrapped code line
+const CODE_CONTINUATION_INDENT: &str = "  ";
+
+/// One drawn line of a code block
+struct CodeVisualLine {
+    /// 1-based source line number
+    number: usize,
+    text: String,
+    /// Drawn after the first piece of a wrapped source line
+    continuation: bool,
+}
+
+/// Colors used to draw fenced code blocks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CodeTheme {
+    pub background: Color,
+    pub border: Color,
+    /// Color of code that is not a keyword, string, comment or number
+    pub default_text: Color,
+    pub line_number: Color,
+}
+
+impl Default for CodeTheme {
+    fn default() -> Self {
+        CodeTheme::light()
+    }
+}
+
+impl CodeTheme {
+    /// Light gray box with dark text (the default)
+    pub fn light() -> Self {
+        CodeTheme {
+            background: Color::rgb(0.95, 0.95, 0.95),
+            border: Color::rgb(0.75, 0.75, 0.75),
+            default_text: Color::black(),
+            line_number: Color::rgb(0.6, 0.6, 0.6),
+        }
+    }
+
+    /// Dark box with light text
+    pub fn dark() -> Self {
+        CodeTheme {
+            background: Color::rgb(0.16, 0.17, 0.2),
+            border: Color::rgb(0.3, 0.32, 0.36),
+            default_text: Color::rgb(0.86, 0.87, 0.89),
+            line_number: Color::rgb(0.45, 0.47, 0.5),
+        }
+    }
+
+    /// Colors taken from one of syntect's bundled themes, e.g. "InspiredGitHub",
+    /// "Solarized (dark)" or "base16-ocean.dark". `None` if the theme is unknown.
+    pub fn from_syntect_theme(name: &str) -> Option<Self> {
+        use std::sync::OnceLock;
+        static THEME_SET: OnceLock<syntect::highlighting::ThemeSet> = OnceLock::new();
+        let theme = THEME_SET
+            .get_or_init(syntect::highlighting::ThemeSet::load_defaults)
+            .themes
+            .get(name)?;
+        let convert = |c: syntect::highlighting::Color| {
+            Color::rgb(c.r as f32 / 255.0, c.g as f32 / 255.0, c.b as f32 / 255.0)
+        };
+        let fallback = CodeTheme::light();
+        let settings = &theme.settings;
+        Some(CodeTheme {
+            background: settings.background.map(convert).unwrap_or(fallback.background),
+            border: settings
+                .line_highlight
+                .or(settings.gutter)
+                .map(convert)
+                .unwrap_or(fallback.border),
+            default_text: settings.foreground.map(convert).unwrap_or(fallback.default_text),
+            line_number: settings
+                .gutter_foreground
+                .map(convert)
+                .unwrap_or(fallback.line_number),
+        })
+    }
+}
+
+/// Numbering style of ordered-list markers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderedMarkerStyle {
+    /// 1, 2, 3
+    Decimal,
+    /// a, b, ..., z, aa, ab
+    LowerAlpha,
+    /// A, B, ..., Z, AA, AB
+    UpperAlpha,
+    /// i, ii, iii, iv
+    LowerRoman,
+    /// I, II, III, IV
+    UpperRoman,
+}
+
+impl OrderedMarkerStyle {
+    /// `number` written in this style, without the trailing "."
+    pub fn format(self, number: u32) -> String {
+        match self {
+            OrderedMarkerStyle::Decimal => number.to_string(),
+            OrderedMarkerStyle::LowerAlpha => to_alpha(number),
+            OrderedMarkerStyle::UpperAlpha => to_alpha(number).to_uppercase(),
+            OrderedMarkerStyle::LowerRoman => to_roman(number),
+            OrderedMarkerStyle::UpperRoman => to_roman(number).to_uppercase(),
+        }
+    }
+}
+
+/// Spreadsheet-style letters: 1 → "a", 26 → "z", 27 → "aa". 0 stays decimal.
+fn to_alpha(mut number: u32) -> String {
+    if number == 0 {
+        return "0".to_string();
+    }
+    let mut letters = Vec::new();
+    while number > 0 {
+        number -= 1;
+        letters.push(b'a' + (number % 26) as u8);
+        number /= 26;
+    }
+    letters.reverse();
+    String::from_utf8(letters).unwrap()
+}
+
+/// Lowercase roman numerals: 4 → "iv", 1994 → "mcmxciv". 0 stays decimal.
+fn to_roman(mut number: u32) -> String {
+    if number == 0 {
+        return "0".to_string();
+    }
+    const NUMERALS: [(u32, &str); 13] = [
+        (1000, "m"), (900, "cm"), (500, "d"), (400, "cd"), (100, "c"), (90, "xc"),
+        (50, "l"), (40, "xl"), (10, "x"), (9, "ix"), (5, "v"), (4, "iv"), (1, "i"),
+    ];
+    let mut out = String::new();
+    for (value, numeral) in NUMERALS {
+        while number >= value {
+            out.push_str(numeral);
+            number -= value;
+        }
+    }
+    out
+}
+
+/// An image drawn at the same place on every page, e.g. a logo in the top margin.
+/// The image is embedded once and shared by all pages.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderImage {
+    pub path: String,
+    /// Lower-left corner in points from the bottom-left of the page
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// A CJK font that PDF readers supply themselves, so it is referenced without
+/// embedding. Text is written as UCS-2 through one of Adobe's predefined CMaps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FallbackFont {
+    /// STSong-Light
+    SimplifiedChinese,
+    /// MSung-Light
+    TraditionalChinese,
+    /// HeiseiMin-W3
+    Japanese,
+    /// HYSMyeongJo-Medium
+    Korean,
+}
+
+impl FallbackFont {
+    /// `/BaseFont` name, also used as the page resource name
+    pub fn base_font(self) -> &'static str {
+        match self {
+            FallbackFont::SimplifiedChinese => "STSong-Light",
+            FallbackFont::TraditionalChinese => "MSung-Light",
+            FallbackFont::Japanese => "HeiseiMin-W3",
+            FallbackFont::Korean => "HYSMyeongJo-Medium",
+        }
+    }
+
+    /// Predefined CMap and the `(Ordering, Supplement)` of its character collection
+    fn cmap(self) -> (&'static str, &'static str, u32) {
+        match self {
+            FallbackFont::SimplifiedChinese => ("UniGB-UCS2-H", "GB1", 2),
+            FallbackFont::TraditionalChinese => ("UniCNS-UCS2-H", "CNS1", 0),
+            FallbackFont::Japanese => ("UniJIS-UCS2-H", "Japan1", 2),
+            FallbackFont::Korean => ("UniKS-UCS2-H", "Korea1", 1),
+        }
+    }
+
+    /// Whether this font has a glyph for `c`
+    pub fn covers(self, c: char) -> bool {
+        let shared = matches!(c,
+            '\u{3000}'..='\u{303F}' // CJK symbols and punctuation
+            | '\u{4E00}'..='\u{9FFF}' // CJK unified ideographs
+            | '\u{FF00}'..='\u{FFEF}' // Halfwidth and fullwidth forms
+        );
+        shared
+            || match self {
+                FallbackFont::Japanese => matches!(c, '\u{3040}'..='\u{30FF}'),
+                FallbackFont::Korean => matches!(c, '\u{1100}'..='\u{11FF}' | '\u{3130}'..='\u{318F}' | '\u{AC00}'..='\u{D7AF}'),
+                _ => false,
+            }
+    }
+
+    /// Add the Type0 font, its CIDFont and descriptor; returns the Type0 font ID
+    fn add_font_objects(self, generator: &mut PdfGenerator) -> u32 {
+        let name = self.base_font();
+        let (cmap, ordering, supplement) = self.cmap();
+        let descriptor = generator.add_object(format!(
+            "<< /Type /FontDescriptor\n/FontName /{}\n/Flags 4\n/FontBBox [0 -200 1000 900]\n/ItalicAngle 0\n/Ascent 880\n/Descent -120\n/CapHeight 880\n/StemV 80\n>>\n",
+            name
+        ));
+        let cid_font = generator.add_object(format!(
+            "<< /Type /Font\n/Subtype /CIDFontType0\n/BaseFont /{}\n/CIDSystemInfo << /Registry (Adobe) /Ordering ({}) /Supplement {} >>\n/FontDescriptor {} 0 R\n/DW 1000\n>>\n",
+            name, ordering, supplement, descriptor
+        ));
+        generator.add_object(format!(
+            "<< /Type /Font\n/Subtype /Type0\n/BaseFont /{}\n/Encoding /{}\n/DescendantFonts [{} 0 R]\n>>\n",
+            name, cmap, cid_font
+        ))
+    }
+}
+
+/// Font of a run of characters the standard fonts cannot show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RunFont {
+    Cjk(FallbackFont),
+    /// `Symbol` or `ZapfDingbats`
+    Symbol(&'static str),
+}
+
+/// Base-14 symbol fonts, added to page resources when content selects them
+const SYMBOL_FONTS: [&str; 2] = ["Symbol", "ZapfDingbats"];
+
+/// Fonts tried in order for characters the standard fonts cannot show
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FontFallback {
+    pub fonts: Vec<FallbackFont>,
+}
+
+impl FontFallback {
+    pub fn new(fonts: Vec<FallbackFont>) -> Self {
+        FontFallback { fonts }
+    }
+
+    /// The first fallback covering `c`, or `None` when the standard font should show it
+    fn font_for(&self, c: char) -> Option<FallbackFont> {
+        if standard_font_covers(c) {
+            return None;
+        }
+        self.fonts.iter().copied().find(|f| f.covers(c))
+    }
+}
+
+/// Characters the standard Latin fonts have glyphs for
+fn standard_font_covers(c: char) -> bool {
+    (c as u32) < 0x250 || matches!(c, '\u{2000}'..='\u{206F}' | '€')
+}
+
+/// Numbering style of page labels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageLabelStyle {
+    /// 1, 2, 3
+    Decimal,
+    /// i, ii, iii
+    LowerRoman,
+    /// I, II, III
+    UpperRoman,
+    /// a, b, ..., z, aa, bb
+    LowerAlpha,
+    /// A, B, ..., Z, AA, BB
+    UpperAlpha,
+}
+
+impl PageLabelStyle {
+    fn as_pdf_name(self) -> &'static str {
+        match self {
+            PageLabelStyle::Decimal => "D",
+            PageLabelStyle::LowerRoman => "r",
+            PageLabelStyle::UpperRoman => "R",
+            PageLabelStyle::LowerAlpha => "a",
+            PageLabelStyle::UpperAlpha => "A",
+        }
+    }
+
+    /// The style named by a `/PageLabels` entry's `/S` value
+    pub(crate) fn from_pdf_name(name: &str) -> Option<Self> {
+        match name {
+            "D" => Some(PageLabelStyle::Decimal),
+            "r" => Some(PageLabelStyle::LowerRoman),
+            "R" => Some(PageLabelStyle::UpperRoman),
+            "a" => Some(PageLabelStyle::LowerAlpha),
+            "A" => Some(PageLabelStyle::UpperAlpha),
+            _ => None,
+        }
+    }
+
+    /// `value` in this style. Letters repeat after z (aa, bb, ...) as PDF viewers show them.
+    pub fn format(self, value: u32) -> String {
+        match self {
+            PageLabelStyle::Decimal => value.to_string(),
+            PageLabelStyle::LowerRoman => to_roman(value),
+            PageLabelStyle::UpperRoman => to_roman(value).to_uppercase(),
+            PageLabelStyle::LowerAlpha | PageLabelStyle::UpperAlpha if value > 0 => {
+                let letter = (b'a' + ((value - 1) % 26) as u8) as char;
+                let label = letter.to_string().repeat(((value - 1) / 26 + 1) as usize);
+                if self == PageLabelStyle::UpperAlpha { label.to_uppercase() } else { label }
+            }
+            _ => value.to_string(),
+        }
+    }
+}
+
+/// Page numbering from `start_page` (1-based) until the next range, e.g.
+/// lower-roman front matter followed by a decimal body restarting at 1
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageLabelRange {
+    pub start_page: usize,
+    pub style: PageLabelStyle,
+    /// Text placed before the number, e.g. "A-"
+    pub prefix: String,
+    /// Number of the range's first page
+    pub start_value: u32,
+}
+
+impl PageLabelRange {
+    pub fn new(start_page: usize, style: PageLabelStyle) -> Self {
+        PageLabelRange { start_page, style, prefix: String::new(), start_value: 1 }
+    }
+}
+
+/// Label of 1-based `page` under `ranges`; plain page numbers before the first range
+pub fn page_label(ranges: &[PageLabelRange], page: usize) -> String {
+    match ranges.iter().filter(|r| r.start_page <= page).max_by_key(|r| r.start_page) {
+        Some(range) => {
+            let value = range.start_value + (page - range.start_page) as u32;
+            format!("{}{}", range.prefix, range.style.format(value))
+        }
+        None => page.to_string(),
     }
+}
 
-    fn emit_empty_line(&mut self) {
-        let lh = line_height(self.base_font_size) * 0.5;
-        if self.needs_page_break(lh) {
-            self.new_page();
+/// Paper handling for duplex printing, written as `/Duplex`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Duplex {
+    Simplex,
+    /// Two-sided, flipping on the short edge of the paper
+    FlipShortEdge,
+    /// Two-sided, flipping on the long edge of the paper
+    FlipLongEdge,
+}
+
+impl Duplex {
+    fn as_pdf_name(self) -> &'static str {
+        match self {
+            Duplex::Simplex => "Simplex",
+            Duplex::FlipShortEdge => "DuplexFlipShortEdge",
+            Duplex::FlipLongEdge => "DuplexFlipLongEdge",
         }
-        self.y -= lh;
     }
+}
 
-    fn emit_horizontal_rule(&mut self) {
-        // Add spacing above the rule
-        self.y -= line_height(self.base_font_size) / 2.0;
+/// Print settings a viewer should preselect, written to the catalog's
+/// `/ViewerPreferences`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ViewerPreferences {
+    /// Print at actual size instead of scaling to the printer's page
+    /// (`/PrintScaling /None`, PDF 1.6)
+    pub print_at_actual_size: bool,
+    /// Paper handling for printing (PDF 1.7)
+    pub duplex: Option<Duplex>,
+}
 
-        // Check for page break
-        if self.needs_page_break(line_height(self.base_font_size)) {
-            self.new_page();
+impl ViewerPreferences {
+    /// `/ViewerPreferences` entry for the catalog, empty when nothing is set
+    fn catalog_entry(&self) -> String {
+        let mut entries = String::new();
+        if self.print_at_actual_size {
+            entries.push_str("/PrintScaling /None ");
+        }
+        if let Some(duplex) = self.duplex {
+            entries.push_str(&format!("/Duplex /{} ", duplex.as_pdf_name()));
+        }
+        if entries.is_empty() {
+            String::new()
+        } else {
+            format!("/ViewerPreferences << {}>>\n", entries)
         }
+    }
 
-        // Draw a horizontal line across the content area
-        let x1 = self.layout.margin_left;
-        let x2 = self.layout.margin_left + self.layout.content_width();
-        let y = self.y;
-        let line_width = 1.0;
-        let color = Color::gray();
+    /// The PDF version the preferences need
+    fn required_version(&self) -> (u8, u8) {
+        if self.duplex.is_some() {
+            (1, 7)
+        } else if self.print_at_actual_size {
+            (1, 6)
+        } else {
+            (1, 4)
+        }
+    }
+}
 
-        self.draw_line(x1, y, x2, y, line_width, color);
+/// `/PageLabels` number tree entry for the catalog, empty when there are no ranges
+fn page_labels_entry(ranges: &[PageLabelRange]) -> String {
+    if ranges.is_empty() {
+        return String::new();
+    }
+    let mut sorted: Vec<&PageLabelRange> = ranges.iter().collect();
+    sorted.sort_by_key(|r| r.start_page);
+    let mut nums = Vec::new();
+    if sorted[0].start_page > 1 {
+        // Pages before the first range keep plain numbers
+        nums.push("0 << /S /D >>".to_string());
+    }
+    for range in sorted {
+        let mut label = format!("/S /{}", range.style.as_pdf_name());
+        if !range.prefix.is_empty() {
+            label.push_str(&format!(" /P {}", crate::pdf_ops::pdf_text_string(&range.prefix)));
+        }
+        if range.start_value != 1 {
+            label.push_str(&format!(" /St {}", range.start_value));
+        }
+        nums.push(format!("{} << {} >>", range.start_page.saturating_sub(1), label));
+    }
+    format!("/PageLabels << /Nums [{}] >>\n", nums.join(" "))
+}
 
-        // Add spacing below the rule
-        self.y -= line_height(self.base_font_size);
+/// Horizontal space taken by each blockquote level
+const QUOTE_INDENT: f32 = 14.0;
+
+/// Color of blockquote bars
+const QUOTE_BAR_COLOR: Color = Color { r: 0.8, g: 0.8, b: 0.8, space: ColorSpace::Rgb };
+
+/// Bar and label color for each callout kind
+fn callout_color(kind: crate::elements::CalloutKind) -> Color {
+    use crate::elements::CalloutKind;
+    match kind {
+        CalloutKind::Note => Color::rgb(0.04, 0.41, 0.85),
+        CalloutKind::Tip => Color::rgb(0.1, 0.5, 0.21),
+        CalloutKind::Important => Color::rgb(0.51, 0.31, 0.86),
+        CalloutKind::Warning => Color::rgb(0.75, 0.5, 0.0),
+        CalloutKind::Caution => Color::rgb(0.81, 0.13, 0.18),
     }
+}
 
-    fn finish(mut self) -> Vec<Vec<u8>> {
-        self.end_text_block();
-        self.pages.push(self.current);
-        self.pages
+/// Joins two words so wrapping never separates them
+const NO_BREAK_SPACE: char = '\u{A0}';
+
+/// Invisible optional break point inside a word
+const SOFT_HYPHEN: char = '\u{AD}';
+
+/// Resource name of the shared header image XObject
+const HEADER_IMAGE_NAME: &str = "HeaderImage";
+
+/// Rendering options for element-based generation
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderOptions {
+    pub heading_numbering: HeadingNumbering,
+    /// Case transform applied to heading text
+    pub heading_case: HeadingCase,
+    /// Kern letter pairs of single-line text (headings, list items, ...) with a `TJ` array
+    pub kerning: bool,
+    pub code_theme: CodeTheme,
+    /// Number the lines of code blocks in a gutter
+    pub code_line_numbers: bool,
+    /// Wrapping or truncation of code lines wider than the content area
+    pub code_wrap: CodeWrap,
+    /// Tab expansion and trailing whitespace of code lines
+    pub code_block_style: CodeBlockStyle,
+    /// Body lines that must fit below a heading on the same page; otherwise
+    /// the heading moves to the next page. 0 disables the check.
+    pub orphan_lines: usize,
+    /// Line height as a multiple of the font size; `None` uses font size + 4pt
+    pub leading_multiplier: Option<f32>,
+    /// Ordered-list marker style per nesting depth, cycling when lists nest
+    /// deeper. Defaults to decimal, lower-alpha, lower-roman.
+    pub ordered_markers: Vec<OrderedMarkerStyle>,
+    /// Image drawn on every page
+    pub header_image: Option<HeaderImage>,
+    /// Fonts used for characters the standard fonts lack, e.g. CJK
+    pub font_fallback: FontFallback,
+    /// Page numbering schemes, written to the catalog and used for footer numbers
+    pub page_labels: Vec<PageLabelRange>,
+    /// Borders, padding and numeric column alignment of tables
+    pub table_style: TableStyle,
+    /// Thickness, color and dash pattern of horizontal rules
+    pub rule_style: RuleStyle,
+    /// Draw the page number footer on each page
+    pub show_page_numbers: bool,
+    /// Number shown on the first page, e.g. to continue numbering from a previous volume.
+    /// Ignored when `page_labels` are set.
+    pub page_number_start: u32,
+    /// Footer text with `{page}` and `{date}` placeholders; `None` writes "Page {page}"
+    pub footer: Option<String>,
+    /// Date written as the document's creation and modification date and used
+    /// for `{date}` in the footer
+    pub date: Option<crate::pdf_ops::PdfDate>,
+    /// Title, author and other document information for the Info dictionary.
+    /// Unset creation and modification dates fall back to `date`.
+    pub metadata: Option<crate::pdf_ops::PdfMetadata>,
+    /// Directory that relative image paths resolve against; `None` uses the
+    /// working directory
+    pub image_base_path: Option<PathBuf>,
+    /// Lines spanned by a drop cap on the first paragraph after each heading.
+    /// Values below 2 disable drop caps.
+    pub drop_cap_lines: usize,
+    /// Indent of each list nesting level, in points
+    pub list_indent: f32,
+    /// Embedded font for code blocks and inline code; `None` uses Courier
+    pub code_font: Option<crate::truetype::TrueTypeFont>,
+    /// Render the sections between page breaks concurrently. Output matches
+    /// sequential rendering when no layout state crosses a page break.
+    pub parallel_sections: bool,
+    /// Embed an sRGB ICC profile and make its `/ICCBased` space each page's
+    /// `/DefaultRGB`, so fills and strokes are calibrated rather than `DeviceRGB`
+    pub srgb_color_space: bool,
+    /// Whether link URLs appear inline or in a numbered list at the end
+    pub link_mode: LinkRenderMode,
+    /// Repeat the latest level 1 or 2 heading in the top margin of the pages after it
+    pub running_header: bool,
+    /// Rendering mode of heading text, e.g. [`TextStyle::outlined`] for display headings
+    pub heading_text_style: Option<TextStyle>,
+    /// Write AFM `/Widths` and a `/FontDescriptor` into the standard font dictionaries
+    /// so viewers space text the same without their own copy of the metrics
+    pub font_widths: bool,
+    /// Output for input with no content
+    pub empty_document: EmptyDocument,
+    /// End documents containing task list items with a "Tasks: 3/5 complete" line
+    pub task_summary: bool,
+    /// Centering and numbering of block math
+    pub display_math: DisplayMathStyle,
+    /// Print scaling and duplex settings for viewers
+    pub viewer_preferences: ViewerPreferences,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            heading_numbering: HeadingNumbering::default(),
+            heading_case: HeadingCase::default(),
+            kerning: false,
+            code_theme: CodeTheme::default(),
+            code_line_numbers: false,
+            code_wrap: CodeWrap::default(),
+            code_block_style: CodeBlockStyle::default(),
+            orphan_lines: 2,
+            leading_multiplier: None,
+            ordered_markers: vec![
+                OrderedMarkerStyle::Decimal,
+                OrderedMarkerStyle::LowerAlpha,
+                OrderedMarkerStyle::LowerRoman,
+            ],
+            header_image: None,
+            font_fallback: FontFallback::default(),
+            page_labels: Vec::new(),
+            table_style: TableStyle::default(),
+            rule_style: RuleStyle::default(),
+            show_page_numbers: true,
+            page_number_start: 1,
+            footer: None,
+            date: None,
+            metadata: None,
+            image_base_path: None,
+            drop_cap_lines: 0,
+            list_indent: 18.0,
+            code_font: None,
+            parallel_sections: false,
+            srgb_color_space: false,
+            link_mode: LinkRenderMode::default(),
+            running_header: false,
+            heading_text_style: None,
+            font_widths: false,
+            empty_document: EmptyDocument::default(),
+            task_summary: false,
+            display_math: DisplayMathStyle::default(),
+            viewer_preferences: ViewerPreferences::default(),
+        }
     }
 }
 
-// --- Public API ---
+/// Resolve an image path from a document against `base`. Absolute paths and
+/// URLs are returned unchanged.
+pub fn resolve_image_path(path: &str, base: Option<&Path>) -> PathBuf {
+    match base {
+        Some(base) if !path.contains("://") && Path::new(path).is_relative() => base.join(path),
+        _ => PathBuf::from(path),
+    }
+}
 
 pub fn create_pdf(filename: &str, text: &str) -> Result<()> {
     create_pdf_with_options(filename, text, "Helvetica", 12.0)
@@ -950,25 +3059,315 @@ pub fn create_pdf_from_elements_with_layout(
     base_font_size: f32,
     layout: PageLayout,
 ) -> Result<()> {
-    let show_page_numbers = true;
-    let mut builder = ContentStreamBuilder::new(base_font_size, show_page_numbers, layout);
-    render_elements_to_builder(&mut builder, elements, base_font_size);
-    let page_streams = builder.finish();
-    assemble_pdf(filename, &page_streams, font, &layout)?;
+    create_pdf_from_elements_with_progress(filename, elements, font, base_font_size, layout, &mut |_| {})
+}
+
+/// Like [`create_pdf_from_elements_with_layout`], reporting progress as elements are rendered
+pub fn create_pdf_from_elements_with_progress(
+    filename: &str,
+    elements: &[Element],
+    font: &str,
+    base_font_size: f32,
+    layout: PageLayout,
+    progress: &mut dyn FnMut(Progress),
+) -> Result<()> {
+    let (page_streams, layouts) = render_document(elements, base_font_size, layout, &RenderOptions::default(), progress);
+    assemble_pdf(filename, &page_streams, font, &layouts)?;
+    Ok(())
+}
+
+/// Like [`create_pdf_from_elements_with_layout`], with extra rendering options
+pub fn create_pdf_from_elements_with_render_options(
+    filename: &str,
+    elements: &[Element],
+    font: &str,
+    base_font_size: f32,
+    layout: PageLayout,
+    options: &RenderOptions,
+) -> Result<()> {
+    let pdf_data = generate_pdf_bytes_with_render_options(elements, font, base_font_size, layout, options)?;
+    let mut file = File::create(filename)?;
+    file.write_all(&pdf_data)?;
     Ok(())
 }
 
+/// Render elements into page-numbered content streams, reporting progress throughout
+/// Returns the streams and the layout of each page, which differs from
+/// `layout` for auto-height pages and after `SetPageLayout` elements.
+fn render_document(
+    elements: &[Element],
+    base_font_size: f32,
+    layout: PageLayout,
+    options: &RenderOptions,
+    progress: &mut dyn FnMut(Progress),
+) -> (Vec<Vec<u8>>, Vec<PageLayout>) {
+    let (page_streams, layouts, _) =
+        render_document_with_images(elements, base_font_size, layout, options, HashMap::new(), progress);
+    (page_streams, layouts)
+}
+
+/// Like [`render_document`], drawing image elements found in `inline_images`
+/// and also returning the regions of the links drawn
+fn render_document_with_images(
+    elements: &[Element],
+    base_font_size: f32,
+    mut layout: PageLayout,
+    options: &RenderOptions,
+    inline_images: HashMap<String, InlineImage>,
+    progress: &mut dyn FnMut(Progress),
+) -> (Vec<Vec<u8>>, Vec<PageLayout>, Vec<LinkRegion>) {
+    let empty = elements.iter().all(|e| matches!(e, Element::EmptyLine));
+    let placeholder;
+    let elements = match &options.empty_document {
+        EmptyDocument::Placeholder(text) if empty => {
+            placeholder = [Element::Paragraph { text: text.clone() }];
+            &placeholder[..]
+        }
+        _ => elements,
+    };
+    let with_summary;
+    let elements = match crate::elements::task_progress(elements).filter(|_| options.task_summary) {
+        Some((done, total)) => {
+            let summary = Element::Paragraph { text: format!("Tasks: {}/{} complete", done, total) };
+            with_summary = [elements, &[summary]].concat();
+            &with_summary[..]
+        }
+        None => elements,
+    };
+    let show_page_numbers = options.show_page_numbers
+        && !layout.auto_height
+        && !(empty && options.empty_document == EmptyDocument::Bare);
+    if layout.auto_height {
+        layout.height = MAX_PAGE_HEIGHT;
+    }
+    // Sections can only be laid out independently when they share one page
+    // layout, and link notes, running heads and equation numbers carry across
+    // the whole document
+    if options.parallel_sections
+        && options.link_mode == LinkRenderMode::Inline
+        && !options.running_header
+        && !options.display_math.numbered
+        && !layout.auto_height
+        && elements.iter().any(|e| matches!(e, Element::PageBreak))
+        && !elements.iter().any(|e| matches!(e, Element::SetPageLayout(_)))
+    {
+        let (page_streams, links) =
+            render_sections_parallel(elements, base_font_size, layout, options, &inline_images, show_page_numbers);
+        progress(Progress {
+            phase: ProgressPhase::Writing,
+            current: page_streams.len(),
+            total: page_streams.len(),
+            pages: page_streams.len(),
+        });
+        let layouts = vec![layout; page_streams.len()];
+        return (page_streams, layouts, links);
+    }
+    let mut builder = ContentStreamBuilder::new(base_font_size, show_page_numbers, layout);
+    builder.options = options.clone();
+    builder.inline_images = inline_images;
+    if options.header_image.is_some() {
+        // Restart the empty first page so it draws the header too
+        builder.begin_page();
+    }
+    render_elements_with_progress(&mut builder, elements, base_font_size, progress);
+    builder.emit_link_notes(base_font_size);
+    let content_bottom = builder.y;
+    let mut links = std::mem::take(&mut builder.links);
+    let (mut page_streams, mut layouts) = builder.finish_with_layouts();
+    if layout.auto_height && page_streams.len() == 1 {
+        // Move the content down onto a page that ends just below it
+        let shift = (content_bottom - layout.margin_bottom).max(0.0);
+        layouts[0].height -= shift;
+        let mut fitted = format!("1 0 0 1 0 {} cm\n", -shift).into_bytes();
+        fitted.extend_from_slice(&page_streams[0]);
+        page_streams[0] = fitted;
+        for link in &mut links {
+            link.rect[1] -= shift;
+            link.rect[3] -= shift;
+        }
+    }
+    progress(Progress {
+        phase: ProgressPhase::Writing,
+        current: page_streams.len(),
+        total: page_streams.len(),
+        pages: page_streams.len(),
+    });
+    (page_streams, layouts, links)
+}
+
 /// Render elements into a ContentStreamBuilder (shared by file and bytes APIs)
 fn render_elements_to_builder(builder: &mut ContentStreamBuilder, elements: &[Element], base_font_size: f32) {
+    render_elements_with_progress(builder, elements, base_font_size, &mut |_| {});
+}
+
+/// Footer drawn at the bottom of page `page_number` (1-based)
+fn page_number_footer(options: &RenderOptions, layout: &PageLayout, page_number: u32) -> Vec<u8> {
+    let page = if options.page_labels.is_empty() {
+        (page_number - 1 + options.page_number_start).to_string()
+    } else {
+        page_label(&options.page_labels, page_number as usize)
+    };
+    let label = match &options.footer {
+        Some(footer) => {
+            let date = options.date.map(|d| d.to_iso_date()).unwrap_or_default();
+            footer.replace("{page}", &page).replace("{date}", &date)
+        }
+        None => format!("Page {}", page),
+    };
+    let x = match options.footer {
+        Some(_) => (layout.width - crate::font_metrics::text_width(FONT_HELVETICA, &label, 9.0)) / 2.0,
+        None => layout.width / 2.0 - 20.0,
+    };
+    let y = layout.margin_bottom / 2.0;
+    format!("BT\n/F1 9 Tf\n1 0 0 1 {} {} Tm\n({}) Tj\nET\n", x, y, escape_pdf_string(&label)).into_bytes()
+}
+
+/// Count a heading of `level` in the section `counters`, resetting deeper levels
+fn advance_heading_counters(counters: &mut Vec<u32>, level: u8) {
+    let depth = (level as usize).max(1);
+    counters.resize(depth, 0);
+    counters[depth - 1] += 1;
+}
+
+/// Render the `PageBreak`-separated sections of `elements` concurrently and
+/// join their pages. Each section starts on a fresh page, so only heading
+/// numbers carry over; footers are added once every page's number is known.
+/// Link regions are returned with their pages counted across the whole document.
+fn render_sections_parallel(
+    elements: &[Element],
+    base_font_size: f32,
+    layout: PageLayout,
+    options: &RenderOptions,
+    inline_images: &HashMap<String, InlineImage>,
+    show_page_numbers: bool,
+) -> (Vec<Vec<u8>>, Vec<LinkRegion>) {
+    use rayon::prelude::*;
+
+    let sections: Vec<&[Element]> = elements.split(|e| matches!(e, Element::PageBreak)).collect();
+    let mut counters = Vec::new();
+    let section_counters: Vec<Vec<u32>> = sections
+        .iter()
+        .map(|section| {
+            let start = counters.clone();
+            if options.heading_numbering.enabled {
+                for elem in section.iter() {
+                    if let Element::Heading { level, .. } = elem {
+                        advance_heading_counters(&mut counters, *level);
+                    }
+                }
+            }
+            start
+        })
+        .collect();
+
+    let rendered: Vec<(Vec<Vec<u8>>, Vec<LinkRegion>)> = sections
+        .par_iter()
+        .zip(section_counters)
+        .map(|(section, counters)| {
+            let mut builder = ContentStreamBuilder::new(base_font_size, false, layout);
+            builder.options = options.clone();
+            builder.inline_images = inline_images.clone();
+            builder.heading_counters = counters;
+            if options.header_image.is_some() {
+                builder.begin_page();
+            }
+            render_elements_to_builder(&mut builder, section, base_font_size);
+            let links = std::mem::take(&mut builder.links);
+            (builder.finish(), links)
+        })
+        .collect();
+
+    let mut pages: Vec<Vec<u8>> = Vec::new();
+    let mut links = Vec::new();
+    for (section_pages, section_links) in rendered {
+        let offset = pages.len();
+        links.extend(section_links.into_iter().map(|link| LinkRegion { page: link.page + offset, ..link }));
+        pages.extend(section_pages);
+    }
+    if show_page_numbers {
+        for (index, page) in pages.iter_mut().enumerate() {
+            page.extend_from_slice(&page_number_footer(options, &layout, index as u32 + 1));
+        }
+    }
+    (pages, links)
+}
+
+/// Visible text for a link; autolinks whose text is the URL show it only once
+pub(crate) fn link_label(text: &str, url: &str) -> String {
+    if text == url { text.to_string() } else { format!("{} ({})", text, url) }
+}
+
+/// Render elements, reporting progress before each element and once at the end
+fn render_elements_with_progress(
+    builder: &mut ContentStreamBuilder,
+    elements: &[Element],
+    base_font_size: f32,
+    progress: &mut dyn FnMut(Progress),
+) {
     let mut table_rows: Vec<Vec<String>> = Vec::new();
     let mut table_alignments: Option<Vec<crate::elements::TableAlignment>> = None;
+    let mut table_explicit: Vec<bool> = Vec::new();
+    // Offset of a table nested under a list item, lining it up with the item's text
+    let mut table_indent = 0.0;
+    // Offset of the last list item's text from the margin, for its continuation paragraphs
+    let mut list_text_indent = 0.0;
+    // Last number shown at each ordered-list depth. A sub-list restarts at its
+    // first item's number and its parent continues after it.
+    let mut ordered_counters: Vec<Option<u32>> = Vec::new();
+    // Whether the previous non-blank element was a heading, for drop caps
+    let mut after_heading = false;
+
+    for (index, elem) in elements.iter().enumerate() {
+        progress(Progress {
+            phase: ProgressPhase::Rendering,
+            current: index,
+            total: elements.len(),
+            pages: builder.pages.len(),
+        });
+
+        // A drop cap's inset only carries over into the lines of its own paragraph
+        if !matches!(elem, Element::Paragraph { .. }) {
+            builder.end_drop_cap(base_font_size);
+        }
+        // Text keeps wrapping beside a floated image; other blocks start below it
+        if !matches!(
+            elem,
+            Element::Paragraph { .. }
+                | Element::RichParagraph { .. }
+                | Element::StyledText { .. }
+                | Element::Link { .. }
+                | Element::UnorderedListItem { .. }
+                | Element::OrderedListItem { .. }
+                | Element::TaskListItem { .. }
+                | Element::ListParagraph { .. }
+                | Element::Footnote { .. }
+                | Element::EmptyLine
+        ) {
+            builder.end_float();
+        }
+        // Other lists at a depth end the ordered lists from that depth on; any
+        // block outside a list ends them all
+        match elem {
+            Element::OrderedListItem { depth, .. } => ordered_counters.truncate(*depth as usize + 1),
+            Element::UnorderedListItem { depth, .. } => ordered_counters.truncate(*depth as usize),
+            Element::TaskListItem { .. } => ordered_counters.clear(),
+            Element::EmptyLine | Element::ListParagraph { .. } | Element::TableRow { list_depth: Some(_), .. } => {}
+            _ => ordered_counters.clear(),
+        }
+        let follows_heading = after_heading;
+        after_heading = match elem {
+            Element::Heading { .. } => true,
+            Element::EmptyLine => after_heading,
+            _ => false,
+        };
 
-    for elem in elements {
         // Handle table rows specially - accumulate them
-        if let Element::TableRow { cells, is_separator, alignments } = elem {
+        if let Element::TableRow { cells, is_separator, alignments, list_depth } = elem {
+            table_indent = if list_depth.is_some() { list_text_indent } else { 0.0 };
             if *is_separator {
                 // Store alignments from separator row
                 table_alignments = Some(alignments.clone());
+                table_explicit = cells.iter().map(|c| c.contains(':')).collect();
             } else {
                 // Only add non-separator rows to the table
                 table_rows.push(cells.clone());
@@ -978,9 +3377,12 @@ fn render_elements_to_builder(builder: &mut ContentStreamBuilder, elements: &[El
 
         // Flush any accumulated table before rendering non-table element
         if !table_rows.is_empty() {
-            builder.render_table(&table_rows, base_font_size, table_alignments.as_deref());
+            builder.layout.margin_left += table_indent;
+            builder.render_table(&table_rows, base_font_size, table_alignments.as_deref(), &table_explicit);
+            builder.layout.margin_left -= table_indent;
             table_rows.clear();
             table_alignments = None;
+            table_explicit.clear();
         }
 
         // Render non-table elements
@@ -988,14 +3390,43 @@ fn render_elements_to_builder(builder: &mut ContentStreamBuilder, elements: &[El
             Element::Heading { level, text } => {
                 let fs = heading_font_size(*level, base_font_size);
                 let align = if *level == 1 { TextAlign::Center } else { TextAlign::Left };
+                let text = builder.options.heading_case.apply(text);
+                let text = match builder.next_heading_number(*level) {
+                    Some(number) => format!("{} {}", number, text),
+                    None => text,
+                };
+                // Keep the heading with the start of its section
+                let spacing = builder.line_height(base_font_size) * 0.5;
+                let keep_with = spacing * 2.0
+                    + builder.line_height(fs)
+                    + builder.options.orphan_lines as f32 * builder.line_height(base_font_size);
+                if builder.options.orphan_lines > 0
+                    && builder.needs_page_break(keep_with)
+                    && builder.y < builder.layout.content_top()
+                {
+                    builder.new_page();
+                }
+                if *level <= 2 {
+                    builder.running_head = Some(text.clone());
+                }
                 builder.emit_empty_line();
                 builder.set_font_with_style(fs, true, false);
-                builder.emit_line_aligned(text, fs, align);
+                builder.text_style = builder.options.heading_text_style;
+                builder.emit_line_aligned(&text, fs, align);
+                builder.text_style = None;
                 builder.set_font_with_style(base_font_size, false, false);
                 builder.emit_empty_line();
             }
             Element::Paragraph { text } => {
-                builder.emit_wrapped_text(text, base_font_size);
+                let lines = builder.options.drop_cap_lines;
+                let mut chars = text.chars();
+                match chars.next() {
+                    Some(first) if lines >= 2 && follows_heading && first.is_alphanumeric() => {
+                        builder.begin_drop_cap(first, lines, base_font_size);
+                        builder.emit_wrapped_text(chars.as_str().trim_start(), base_font_size);
+                    }
+                    _ => builder.emit_wrapped_text(text, base_font_size),
+                }
             }
             Element::RichParagraph { segments } => {
                 // Render each styled segment
@@ -1025,46 +3456,117 @@ fn render_elements_to_builder(builder: &mut ContentStreamBuilder, elements: &[El
                             builder.set_color(Color::black());
                             builder.set_font_with_style(base_font_size, false, false);
                         }
-                        TextSegment::Link { text, url } => {
-                            builder.set_color(Color::blue());
-                            builder.emit_wrapped_text(&format!("{} ({})", text, url), base_font_size);
-                            builder.set_color(Color::black());
+                        TextSegment::Link { text, url } => builder.emit_link(text, url, base_font_size),
+                        TextSegment::Highlight(text, color) => {
+                            builder.set_font_with_style(base_font_size, false, false);
+                            builder.highlight = Some(*color);
+                            builder.emit_wrapped_text(text, base_font_size);
+                            builder.highlight = None;
+                        }
+                        TextSegment::Styled(text, style) => {
+                            builder.set_font_with_style(base_font_size, false, false);
+                            builder.text_style = Some(*style);
+                            builder.emit_wrapped_text(text, base_font_size);
+                            builder.text_style = None;
+                        }
+                        TextSegment::Superscript(text) | TextSegment::Subscript(text) => {
+                            let script_size = base_font_size * 0.7;
+                            let rise = if matches!(segment, TextSegment::Superscript(_)) {
+                                base_font_size * 0.35
+                            } else {
+                                -base_font_size * 0.2
+                            };
+                            builder.set_font_with_style(script_size, false, false);
+                            builder.current.extend_from_slice(format!("{} Ts\n", rise).as_bytes());
+                            builder.emit_wrapped_text(text, script_size);
+                            builder.current.extend_from_slice(b"0 Ts\n");
+                            builder.set_font_with_style(base_font_size, false, false);
                         }
                     }
                 }
             }
             Element::UnorderedListItem { text, depth } => {
-                let indent = "  ".repeat(*depth as usize);
-                let line = format!("{}• {}", indent, text);
-                builder.emit_wrapped_text(&line, base_font_size);
+                list_text_indent = builder.emit_list_item("• ", text, *depth, base_font_size);
             }
             Element::OrderedListItem { number, text, depth } => {
-                let indent = "  ".repeat(*depth as usize);
-                let line = format!("{}{}. {}", indent, number, text);
-                builder.emit_wrapped_text(&line, base_font_size);
+                let level = *depth as usize;
+                if ordered_counters.len() <= level {
+                    ordered_counters.resize(level + 1, None);
+                }
+                // The first item's number starts the list; later items count on from it
+                let number = ordered_counters[level].map_or(*number, |previous| previous + 1);
+                ordered_counters[level] = Some(number);
+                let markers = &builder.options.ordered_markers;
+                let style = markers
+                    .get(level % markers.len().max(1))
+                    .copied()
+                    .unwrap_or(OrderedMarkerStyle::Decimal);
+                let marker = format!("{}. ", style.format(number));
+                list_text_indent = builder.emit_list_item(&marker, text, *depth, base_font_size);
             }
             Element::TaskListItem { checked, text } => {
-                let marker = if *checked { "[x]" } else { "[ ]" };
-                let line = format!("{} {}", marker, text);
-                builder.emit_wrapped_text(&line, base_font_size);
+                if builder.needs_page_break(builder.line_height(base_font_size)) {
+                    builder.new_page();
+                }
+                // Box sits on the text baseline, roughly cap height tall
+                let box_size = base_font_size * 0.75;
+                let box_x = builder.layout.margin_left;
+                let box_y = builder.y - base_font_size * 0.05;
+                builder.draw_rectangle_outline(box_x, box_y, box_size, box_size, 0.75, Color::black());
+                if *checked {
+                    builder.draw_polyline(
+                        &[
+                            (box_x + box_size * 0.2, box_y + box_size * 0.5),
+                            (box_x + box_size * 0.42, box_y + box_size * 0.2),
+                            (box_x + box_size * 0.85, box_y + box_size * 0.85),
+                        ],
+                        1.2,
+                        Color::black(),
+                    );
+                }
+
+                // Wrapped lines hang under the text, not the box
+                let indent = box_size + base_font_size * 0.5;
+                list_text_indent = indent;
+                builder.layout.margin_left += indent;
+                builder.emit_wrapped_text(text, base_font_size);
+                builder.layout.margin_left -= indent;
+            }
+            Element::ListParagraph { text, .. } => {
+                builder.layout.margin_left += list_text_indent;
+                builder.emit_wrapped_text(text, base_font_size);
+                builder.layout.margin_left -= list_text_indent;
             }
             Element::CodeBlock { code, language } => {
                 let code_size = base_font_size * 0.85;
                 let padding = 8.0;
-                let line_h = line_height(code_size);
-                let all_lines: Vec<&str> = code.lines().collect();
+                let line_h = builder.line_height(code_size);
+                let code_style = builder.options.code_block_style;
+                let expanded: Vec<String> = code.lines().map(|line| code_style.apply(line)).collect();
+                let all_lines: Vec<&str> = expanded.iter().map(String::as_str).collect();
+                let theme = builder.options.code_theme;
+                let gutter = if builder.options.code_line_numbers {
+                    builder.code_text_width(&all_lines.len().to_string(), code_size) + padding
+                } else {
+                    0.0
+                };
 
                 builder.emit_empty_line();
 
+                // Long lines become several visual lines (or one cut line) per `code_wrap`
+                let text_width = builder.layout.content_width() - gutter;
+                let visual_lines = builder.code_visual_lines(&all_lines, text_width, code_size);
+                let continuation_indent = builder.code_text_width(CODE_CONTINUATION_INDENT, code_size);
+
                 // Split code block across pages if needed
                 let mut line_idx = 0;
-                while line_idx < all_lines.len() {
+                while line_idx < visual_lines.len() {
                     // Calculate how many lines fit on current page
                     let available = builder.y - builder.layout.margin_bottom - padding * 2.0;
                     let max_lines_on_page = (available / line_h).floor() as usize;
                     let max_lines_on_page = max_lines_on_page.max(1);
-                    let chunk_end = (line_idx + max_lines_on_page).min(all_lines.len());
-                    let chunk = &all_lines[line_idx..chunk_end];
+                    let chunk_end = (line_idx + max_lines_on_page).min(visual_lines.len());
+                    let chunk = &visual_lines[line_idx..chunk_end];
                     let chunk_height = chunk.len() as f32 * line_h + padding * 2.0;
 
                     // Account for top padding before drawing — shift y down by padding
@@ -1072,7 +3574,7 @@ fn render_elements_to_builder(builder: &mut ContentStreamBuilder, elements: &[El
 
                     // Draw background rectangle (from current y down by text height + bottom padding)
                     let text_block_height = chunk.len() as f32 * line_h;
-                    let bg_color = Color::rgb(0.95, 0.95, 0.95);
+                    let bg_color = theme.background;
                     let rect_x = builder.layout.margin_left - padding;
                     let rect_y = builder.y - text_block_height - padding;
                     let rect_width = builder.layout.content_width() + padding * 2.0;
@@ -1080,7 +3582,7 @@ fn render_elements_to_builder(builder: &mut ContentStreamBuilder, elements: &[El
                     builder.draw_rectangle(rect_x, rect_y, rect_width, rect_height, bg_color);
 
                     // Draw border
-                    let border_color = Color::rgb(0.75, 0.75, 0.75);
+                    let border_color = theme.border;
                     builder.draw_line(rect_x, rect_y, rect_x + rect_width, rect_y, 0.5, border_color);
                     builder.draw_line(rect_x, rect_y + rect_height, rect_x + rect_width, rect_y + rect_height, 0.5, border_color);
                     builder.draw_line(rect_x, rect_y, rect_x, rect_y + rect_height, 0.5, border_color);
@@ -1090,28 +3592,42 @@ fn render_elements_to_builder(builder: &mut ContentStreamBuilder, elements: &[El
                     builder.set_monospace_font(code_size);
 
                     // Emit code lines with per-line syntax highlighting
-                    let char_width = code_size * 0.6; // Courier is monospace
-                    for code_line in chunk {
-                        let line_tokens = highlight_code(code_line, language);
+                    let text_x = builder.layout.margin_left + gutter;
+                    for visual in chunk {
+                        let code_line = visual.text.as_str();
+                        let text_x = if visual.continuation { text_x + continuation_indent } else { text_x };
+                        if gutter > 0.0 && !visual.continuation {
+                            let number = visual.number.to_string();
+                            let number_x = text_x - padding - builder.code_text_width(&number, code_size);
+                            builder.current.extend_from_slice(
+                                format!(
+                                    "{}\n1 0 0 1 {} {} Tm\n({}) Tj\n",
+                                    theme.line_number.fill_operator(),
+                                    number_x, builder.y, number
+                                )
+                                .as_bytes(),
+                            );
+                        }
+                        let line_tokens = highlight_code(code_line, language, theme.default_text);
 
                         if line_tokens.is_empty() || line_tokens.iter().all(|t| t.text.is_empty()) {
                             // Empty line or no tokens — just advance
                             builder.current.extend_from_slice(
-                                format!("{} {} {} rg\n", 0.15, 0.15, 0.15).as_bytes()
+                                format!("{}\n", theme.default_text.fill_operator()).as_bytes()
                             );
                             builder.current.extend_from_slice(
-                                format!("1 0 0 1 {} {} Tm\n", builder.layout.margin_left, builder.y).as_bytes()
+                                format!("1 0 0 1 {} {} Tm\n", text_x, builder.y).as_bytes()
                             );
                             builder.current.extend_from_slice(
                                 format!("({}) Tj\n", escape_pdf_string(code_line)).as_bytes()
                             );
                         } else {
                             // Render each token with its color
-                            let mut x_offset = builder.layout.margin_left;
+                            let mut x_offset = text_x;
                             for token in &line_tokens {
                                 if token.text.is_empty() { continue; }
                                 builder.current.extend_from_slice(
-                                    format!("{} {} {} rg\n", token.color.r, token.color.g, token.color.b).as_bytes()
+                                    format!("{}\n", token.color.fill_operator()).as_bytes()
                                 );
                                 builder.current.extend_from_slice(
                                     format!("1 0 0 1 {} {} Tm\n", x_offset, builder.y).as_bytes()
@@ -1119,7 +3635,7 @@ fn render_elements_to_builder(builder: &mut ContentStreamBuilder, elements: &[El
                                 builder.current.extend_from_slice(
                                     format!("({}) Tj\n", escape_pdf_string(&token.text)).as_bytes()
                                 );
-                                x_offset += token.text.len() as f32 * char_width;
+                                x_offset += builder.code_text_width(&token.text, code_size);
                             }
                         }
                         builder.y -= line_h;
@@ -1131,7 +3647,7 @@ fn render_elements_to_builder(builder: &mut ContentStreamBuilder, elements: &[El
                     line_idx = chunk_end;
 
                     // If more lines remain, start a new page
-                    if line_idx < all_lines.len() {
+                    if line_idx < visual_lines.len() {
                         builder.set_font_with_style(base_font_size, false, false);
                         builder.reset_color();
                         builder.new_page();
@@ -1157,13 +3673,27 @@ fn render_elements_to_builder(builder: &mut ContentStreamBuilder, elements: &[El
                 builder.set_font_with_style(base_font_size, false, false);
                 builder.reset_color();
             }
-            Element::Link { text, url } => {
-                builder.set_color(Color::blue());
-                builder.emit_wrapped_text(&format!("{} ({})", text, url), base_font_size);
-                builder.reset_color();
+            Element::Link { text, url } => builder.emit_link(text, url, base_font_size),
+            Element::Image { path, caption, float: float @ (ImageFloat::Left | ImageFloat::Right), .. }
+                if builder.inline_images.contains_key(path) =>
+            {
+                let image = builder.inline_images[path].clone();
+                builder.draw_floating_image(&image, *float, caption.as_deref());
             }
-            Element::Image { alt, path } => {
-                builder.emit_wrapped_text(&format!("[Image: {}] ({})", alt, path), base_font_size);
+            Element::Image { alt, path, caption: None, .. } => match builder.inline_images.get(path).cloned() {
+                Some(image) => builder.draw_inline_image(&image, false),
+                None => builder.emit_wrapped_text(&format!("[Image: {}] ({})", alt, path), base_font_size),
+            },
+            Element::Image { alt, path, caption: Some(caption), .. } => {
+                // A figure: the image centered, with a smaller italic caption below
+                match builder.inline_images.get(path).cloned() {
+                    Some(image) => builder.draw_inline_image(&image, true),
+                    None => builder.emit_centered_text(&format!("[Image: {}] ({})", alt, path), base_font_size),
+                }
+                let caption_size = base_font_size * 0.85;
+                builder.set_font_with_style(caption_size, false, true);
+                builder.emit_centered_text(caption, caption_size);
+                builder.set_font_with_style(base_font_size, false, false);
             }
             Element::StyledText { text, bold, italic } => {
                 builder.set_font_with_style(base_font_size, *bold, *italic);
@@ -1173,22 +3703,33 @@ fn render_elements_to_builder(builder: &mut ContentStreamBuilder, elements: &[El
             Element::PageBreak => {
                 builder.new_page();
             }
+            Element::SetPageLayout(layout) => {
+                builder.set_page_layout(*layout);
+            }
             Element::Footnote { label, text } => {
                 let footnote_size = base_font_size * 0.85;
                 builder.emit_wrapped_text(&format!("[{}] {}", label, text), footnote_size);
             }
             Element::BlockQuote { text, depth } => {
-                let prefix = "> ".repeat(*depth as usize);
-                builder.set_color(Color::gray());
-                builder.emit_wrapped_text(&format!("{}{}", prefix, text), base_font_size);
-                builder.reset_color();
+                builder.render_quoted(*depth, |b| b.emit_wrapped_text(text, base_font_size));
+            }
+            Element::Callout { kind, text } => {
+                builder.render_callout(*kind, text);
+            }
+            Element::Quoted { depth, element } => {
+                builder.render_quoted(*depth, |b| {
+                    render_elements_with_progress(b, std::slice::from_ref(element.as_ref()), base_font_size, &mut |_| {})
+                });
             }
             Element::MathBlock { expression } => {
                 let math_size = base_font_size * 1.1;
                 let padding = 10.0;
-                let line_h = line_height(math_size);
-                let math_lines: Vec<&str> = expression.lines().collect();
-                let block_height = math_lines.len() as f32 * line_h + padding * 2.0;
+                let line_h = builder.line_height(math_size);
+                let rows = crate::math::layout_display_rows(expression, FONT_HELVETICA_OBLIQUE, math_size);
+                // Rows taller than a text line, like fractions, push their neighbours apart
+                let row_advance = |row: &crate::math::MathLayout| line_h.max(row.ascent + row.descent + math_size * 0.3);
+                let row_raise = |row: &crate::math::MathLayout| (row.ascent - math_size * HELVETICA_CAP_HEIGHT).max(0.0);
+                let block_height = rows.iter().map(row_advance).sum::<f32>() + padding * 2.0;
 
                 builder.emit_empty_line();
 
@@ -1211,16 +3752,32 @@ fn render_elements_to_builder(builder: &mut ContentStreamBuilder, elements: &[El
                 // Render math expression in italic
                 builder.set_font_with_style(math_size, false, true);
                 builder.set_color(Color::rgb(0.1, 0.1, 0.3));
-                for math_line in &math_lines {
-                    // Render math symbols with text representation
-                    let rendered = render_math_text(math_line);
-                    builder.current.extend_from_slice(
-                        format!("1 0 0 1 {} {} Tm\n", builder.layout.margin_left + 4.0, builder.y).as_bytes()
-                    );
-                    builder.current.extend_from_slice(
-                        format!("({}) Tj\n", escape_pdf_string(&rendered)).as_bytes()
-                    );
-                    builder.y -= line_h;
+                let style = builder.options.display_math;
+                let number = style.numbered.then(|| {
+                    builder.equation_number += 1;
+                    format!("({})", builder.equation_number)
+                });
+                // The number sits on the middle row's baseline
+                let number_row = rows.len().saturating_sub(1) / 2;
+                for (index, row) in rows.iter().enumerate() {
+                    let raise = row_raise(row);
+                    builder.y -= raise;
+                    let x = if style.centered {
+                        builder.layout.margin_left + (builder.layout.content_width() - row.width) / 2.0
+                    } else {
+                        builder.layout.margin_left + 4.0
+                    };
+                    builder.draw_math(row, x, builder.y);
+                    if let Some(number) = number.as_ref().filter(|_| index == number_row) {
+                        builder.set_font_with_style(math_size, false, false);
+                        let number_x = builder.layout.margin_left + builder.layout.content_width()
+                            - crate::font_metrics::text_width(FONT_HELVETICA, number, math_size);
+                        builder.current.extend_from_slice(
+                            format!("1 0 0 1 {} {} Tm\n({}) Tj\n", number_x, builder.y, escape_pdf_string(number)).as_bytes(),
+                        );
+                        builder.set_font_with_style(math_size, false, true);
+                    }
+                    builder.y -= row_advance(row) - raise;
                 }
 
                 builder.set_font_with_style(base_font_size, false, false);
@@ -1228,17 +3785,30 @@ fn render_elements_to_builder(builder: &mut ContentStreamBuilder, elements: &[El
                 builder.emit_empty_line();
             }
             Element::MathInline { expression } => {
-                // Render inline math in italic with slight color
-                let rendered = render_math_text(expression);
+                // Render inline math in italic with slight color, on a line tall enough for it
+                let math = crate::math::layout_math(expression, FONT_HELVETICA_OBLIQUE, base_font_size, false);
+                let raise = (math.ascent - base_font_size * HELVETICA_CAP_HEIGHT).max(0.0);
+                let advance = builder.line_height(base_font_size).max(math.ascent + math.descent + base_font_size * 0.3);
+                if builder.needs_page_break(advance) {
+                    builder.new_page();
+                }
                 builder.set_font_with_style(base_font_size, false, true);
                 builder.set_color(Color::rgb(0.1, 0.1, 0.3));
-                builder.emit_line(&rendered, base_font_size);
+                builder.y -= raise;
+                builder.draw_math(&math, builder.layout.margin_left, builder.y);
+                builder.y -= advance - raise;
                 builder.set_font_with_style(base_font_size, false, false);
                 builder.reset_color();
             }
             Element::HorizontalRule => {
                 builder.emit_horizontal_rule();
             }
+            Element::RawContent { stream } => {
+                builder.emit_raw_content(stream);
+            }
+            Element::Chart { kind, data, title } => {
+                builder.render_chart(*kind, data, title);
+            }
             Element::EmptyLine => {
                 builder.emit_empty_line();
             }
@@ -1250,8 +3820,18 @@ fn render_elements_to_builder(builder: &mut ContentStreamBuilder, elements: &[El
 
     // Flush any remaining table
     if !table_rows.is_empty() {
-        builder.render_table(&table_rows, base_font_size, table_alignments.as_deref());
+        builder.layout.margin_left += table_indent;
+        builder.render_table(&table_rows, base_font_size, table_alignments.as_deref(), &table_explicit);
+        builder.layout.margin_left -= table_indent;
     }
+    builder.end_drop_cap(base_font_size);
+
+    progress(Progress {
+        phase: ProgressPhase::Rendering,
+        current: elements.len(),
+        total: elements.len(),
+        pages: builder.pages.len() + 1,
+    });
 }
 
 /// Generate PDF bytes from elements (library API — no filesystem access needed)
@@ -1261,68 +3841,328 @@ pub fn generate_pdf_bytes(
     base_font_size: f32,
     layout: PageLayout,
 ) -> Result<Vec<u8>> {
-    let show_page_numbers = true;
-    let mut builder = ContentStreamBuilder::new(base_font_size, show_page_numbers, layout);
+    generate_pdf_bytes_with_progress(elements, font, base_font_size, layout, &mut |_| {})
+}
+
+/// Like [`generate_pdf_bytes`], reporting progress as elements are rendered
+pub fn generate_pdf_bytes_with_progress(
+    elements: &[Element],
+    font: &str,
+    base_font_size: f32,
+    layout: PageLayout,
+    progress: &mut dyn FnMut(Progress),
+) -> Result<Vec<u8>> {
+    layout.validate()?;
+    let (page_streams, layouts) = render_document(elements, base_font_size, layout, &RenderOptions::default(), progress);
+    Ok(assemble_pdf_bytes(&page_streams, font, &layouts))
+}
+
+/// Like [`generate_pdf_bytes`], with extra rendering options
+pub fn generate_pdf_bytes_with_render_options(
+    elements: &[Element],
+    _font: &str,
+    base_font_size: f32,
+    layout: PageLayout,
+    options: &RenderOptions,
+) -> Result<Vec<u8>> {
+    let mut generator = PdfGenerator::new();
+    if options.metadata.is_some() || options.date.is_some() {
+        let mut metadata = options.metadata.clone().unwrap_or_default();
+        metadata.creation_date = metadata.creation_date.or(options.date);
+        metadata.mod_date = metadata.mod_date.or(options.date);
+        generator.info = Some(generator.add_object(metadata.to_info_dict()));
+    }
+    let (inline_images, mut xobject_names) =
+        embed_inline_images(&mut generator, elements, options.image_base_path.as_deref())?;
+    let (mut page_streams, layouts, links) =
+        render_document_with_images(elements, base_font_size, layout, options, inline_images, &mut |_| {});
+    if let Some(code_font) = options.code_font.as_ref().filter(|font| font.shapes_ligatures()) {
+        page_streams = page_streams.iter().map(|stream| shape_font_strings(stream, code_font)).collect();
+    }
+    let page_annots = add_link_annotations(&mut generator, &links, page_streams.len());
+    let mut fonts: Vec<(&str, u32)> = options
+        .font_fallback
+        .fonts
+        .iter()
+        .map(|fallback| (fallback.base_font(), fallback.add_font_objects(&mut generator)))
+        .collect();
+    if let Some(code_font) = &options.code_font
+        && streams_select_font(&page_streams, &code_font.name)
+    {
+        fonts.push((code_font.name.as_str(), code_font.add_font_objects(&mut generator)));
+    }
+    if let Some(header) = &options.header_image {
+        let image = crate::image::load_image(&header.path)?;
+        xobject_names.push((HEADER_IMAGE_NAME.to_string(), crate::image::create_image_object(&mut generator, image)?));
+    }
+    let xobjects: Vec<(&str, u32)> = xobject_names.iter().map(|(name, id)| (name.as_str(), *id)).collect();
+    let default_rgb = options.srgb_color_space.then(|| add_srgb_color_space(&mut generator));
+    let mut catalog_extra = page_labels_entry(&options.page_labels);
+    catalog_extra.push_str(&options.viewer_preferences.catalog_entry());
+    let (major, minor) = options.viewer_preferences.required_version();
+    generator.require_version(major, minor);
+    let resources = DocumentResources {
+        fonts: &fonts,
+        xobjects: &xobjects,
+        page_annots: &page_annots,
+        default_rgb,
+        font_widths: options.font_widths,
+    };
+    Ok(build_document_with_resources(generator, &page_streams, &layouts, &catalog_extra, &resources).generate())
+}
+
+/// Add the sRGB profile stream and an `/ICCBased` color space using it,
+/// returning the color space's id
+fn add_srgb_color_space(generator: &mut PdfGenerator) -> u32 {
+    let profile = crate::color_profile::srgb_profile();
+    let profile_id = generator.add_stream_object(
+        format!("<< /N 3\n/Alternate /DeviceRGB\n/Length {} >>\n", profile.len()),
+        profile,
+    );
+    generator.add_object(format!("[/ICCBased {} 0 R]\n", profile_id))
+}
+
+/// Add one `/Link` annotation per link region and return the annotation ids
+/// of each of the `page_count` pages. A link wrapped over several lines gets
+/// one annotation per line, all opening the same URI.
+fn add_link_annotations(generator: &mut PdfGenerator, links: &[LinkRegion], page_count: usize) -> Vec<Vec<u32>> {
+    let mut page_annots = vec![Vec::new(); page_count];
+    for link in links.iter().filter(|link| link.page < page_count) {
+        let [x1, y1, x2, y2] = link.rect;
+        let id = generator.add_object(format!(
+            "<< /Type /Annot\n/Subtype /Link\n/Rect [{} {} {} {}]\n/Border [0 0 0]\n/A << /Type /Action\n/S /URI\n/URI ({}) >>\n>>\n",
+            x1, y1, x2, y2,
+            escape_pdf_string(&link.url),
+        ));
+        page_annots[link.page].push(id);
+    }
+    page_annots
+}
+
+/// Whether any content stream selects the font resource `name` with `Tf`
+fn streams_select_font(page_streams: &[Vec<u8>], name: &str) -> bool {
+    page_streams
+        .iter()
+        .flat_map(|stream| crate::content_stream::Tokenizer::new(stream))
+        .any(|(operands, op)| op == "Tf" && operands.first().and_then(|n| n.as_name()) == Some(name))
+}
+
+/// Rewrite the strings shown while `font` is selected as its shaped glyph IDs
+fn shape_font_strings(stream: &[u8], font: &crate::truetype::TrueTypeFont) -> Vec<u8> {
+    let mut out = Vec::with_capacity(stream.len());
+    let mut copied_to = 0;
+    let mut selected = false;
+    for (operands, operator, span) in crate::content_stream::Tokenizer::new(stream).spanned() {
+        match operator.as_str() {
+            "Tf" => selected = operands.first().and_then(|n| n.as_name()) == Some(font.name.as_str()),
+            "Tj" if selected => {
+                let Some(bytes) = operands.first().and_then(|s| s.as_bytes()) else { continue };
+                let text = String::from_utf8_lossy(bytes);
+                let glyphs: String = font.shape(&text).iter().map(|glyph| format!("{:04X}", glyph)).collect();
+                out.extend_from_slice(&stream[copied_to..span.start]);
+                out.extend_from_slice(format!("<{}> Tj", glyphs).as_bytes());
+                copied_to = span.end;
+            }
+            _ => {}
+        }
+    }
+    out.extend_from_slice(&stream[copied_to..]);
+    out
+}
+
+/// A resource name and the id of the object it refers to
+type NamedObject = (String, u32);
+
+/// Embed the images of `elements` that can be loaded, each file once, and
+/// return them by the path written in the document along with their XObject
+/// ids. Images that cannot be loaded keep their text placeholder.
+fn embed_inline_images(
+    generator: &mut PdfGenerator,
+    elements: &[Element],
+    base: Option<&Path>,
+) -> Result<(HashMap<String, InlineImage>, Vec<NamedObject>)> {
+    let mut images = HashMap::new();
+    let mut xobjects: Vec<NamedObject> = Vec::new();
+    let mut by_file: HashMap<PathBuf, InlineImage> = HashMap::new();
+    for element in elements {
+        let Element::Image { path, .. } = element else { continue };
+        if images.contains_key(path) {
+            continue;
+        }
+        let resolved = resolve_image_path(path, base);
+        if let Some(image) = by_file.get(&resolved) {
+            images.insert(path.clone(), image.clone());
+            continue;
+        }
+        let Ok(info) = crate::image::load_image(&resolved.to_string_lossy()) else {
+            continue;
+        };
+        let image = InlineImage {
+            name: format!("Im{}", xobjects.len() + 1),
+            width: info.width as f32,
+            height: info.height as f32,
+        };
+        xobjects.push((image.name.clone(), crate::image::create_image_object(generator, info)?));
+        by_file.insert(resolved, image.clone());
+        images.insert(path.clone(), image);
+    }
+    Ok((images, xobjects))
+}
+
+/// Like [`generate_pdf_bytes`], writing the document language, title and
+/// `DisplayDocTitle` viewer preference for screen readers
+pub fn generate_pdf_bytes_with_accessibility(
+    elements: &[Element],
+    _font: &str,
+    base_font_size: f32,
+    layout: PageLayout,
+    accessibility: &AccessibilityOptions,
+) -> Result<Vec<u8>> {
+    let (page_streams, layouts) = render_document(elements, base_font_size, layout, &RenderOptions::default(), &mut |_| {});
+    Ok(build_document(&page_streams, &layouts, Some(accessibility)).generate())
+}
+
+/// Render elements into per-page content streams without page numbers
+pub fn render_page_streams(elements: &[Element], base_font_size: f32, layout: PageLayout) -> Vec<Vec<u8>> {
+    let mut builder = ContentStreamBuilder::new(base_font_size, false, layout);
     render_elements_to_builder(&mut builder, elements, base_font_size);
-    let page_streams = builder.finish();
-    Ok(assemble_pdf_bytes(&page_streams, font, &layout))
+    builder.finish()
 }
 
 /// Assemble final PDF bytes from per-page content streams
-fn assemble_pdf_bytes(page_streams: &[Vec<u8>], _font: &str, layout: &PageLayout) -> Vec<u8> {
+fn assemble_pdf_bytes(page_streams: &[Vec<u8>], _font: &str, layouts: &[PageLayout]) -> Vec<u8> {
+    build_document(page_streams, layouts, None).generate()
+}
+
+/// Build the object graph (pages, fonts, page tree, catalog) for per-page content
+/// streams. Page `i` uses `layouts[i]`, or the last layout past the end.
+fn build_document(
+    page_streams: &[Vec<u8>],
+    layouts: &[PageLayout],
+    accessibility: Option<&AccessibilityOptions>,
+) -> PdfGenerator {
     let mut generator = PdfGenerator::new();
+    let mut catalog_extra = String::new();
+    if let Some(accessibility) = accessibility {
+        if let Some(ref title) = accessibility.title {
+            let metadata = crate::pdf_ops::PdfMetadata { title: Some(title.clone()), ..Default::default() };
+            generator.info = Some(generator.add_object(metadata.to_info_dict()));
+        }
+        catalog_extra = accessibility.catalog_entries();
+    }
+    build_document_with_resources(generator, page_streams, layouts, &catalog_extra, &DocumentResources::default())
+}
+
+/// Objects already added to a document that its pages refer to
+#[derive(Default)]
+struct DocumentResources<'a> {
+    /// `(name, id)` fonts listed in every page's resources
+    fonts: &'a [(&'a str, u32)],
+    /// `(name, id)` XObjects listed in every page's resources
+    xobjects: &'a [(&'a str, u32)],
+    /// Annotation ids of each page, by page index
+    page_annots: &'a [Vec<u32>],
+    /// Color space installed as every page's `/DefaultRGB`
+    default_rgb: Option<u32>,
+    /// Write `/Widths` and a `/FontDescriptor` for the standard fonts
+    font_widths: bool,
+}
+
+/// Font dictionary for a standard font, with its AFM widths when `descriptor` is given
+fn standard_font_dict(name: &str, descriptor: Option<u32>) -> String {
+    let metrics = descriptor
+        .map(|id| {
+            let widths: Vec<String> = crate::font_metrics::widths(name).iter().map(u16::to_string).collect();
+            format!(
+                "/FirstChar {}\n/LastChar {}\n/Widths [{}]\n/FontDescriptor {} 0 R\n",
+                crate::font_metrics::FIRST_CHAR,
+                crate::font_metrics::LAST_CHAR,
+                widths.join(" "),
+                id
+            )
+        })
+        .unwrap_or_default();
+    format!("<< /Type /Font\n/Subtype /Type1\n/BaseFont /{}\n{}>>\n", name, metrics)
+}
 
+/// Like [`build_document`], continuing `generator`, adding `catalog_extra` to the
+/// catalog and referring to the already added `resources` from every page
+fn build_document_with_resources(
+    mut generator: PdfGenerator,
+    page_streams: &[Vec<u8>],
+    layouts: &[PageLayout],
+    catalog_extra: &str,
+    resources: &DocumentResources,
+) -> PdfGenerator {
+    let DocumentResources { fonts, xobjects, page_annots, default_rgb, font_widths } = *resources;
     let mut page_ids = Vec::new();
 
+    // One descriptor per standard font, shared by every page's copy of the font
+    let descriptors: Vec<u32> = if font_widths {
+        STANDARD_FONTS
+            .into_iter()
+            .map(|name| generator.add_object(crate::font_metrics::font_descriptor(name)))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    // Symbol fonts are only added when some page selects them with `Tf`
+    let used_fonts: std::collections::HashSet<String> = page_streams
+        .iter()
+        .flat_map(|stream| crate::content_stream::Tokenizer::new(stream))
+        .filter(|(_, op)| op == "Tf")
+        .filter_map(|(operands, _)| operands.first()?.as_name().map(str::to_string))
+        .collect();
+    let mut fonts = fonts.to_vec();
+    for name in SYMBOL_FONTS.into_iter().filter(|name| used_fonts.contains(*name)) {
+        let id = generator.add_object(format!("<< /Type /Font\n/Subtype /Type1\n/BaseFont /{}\n>>\n", name));
+        fonts.push((name, id));
+    }
+
     // We need to know the pages object ID ahead of time.
     // Layout: for each page: content_stream_obj, page_obj, fonts_obj (5 fonts)
     // Then: pages_obj, catalog_obj
     let fonts_per_page = 5; // Helvetica, Helvetica-Bold, Helvetica-Oblique, Helvetica-BoldOblique, Courier
-    let pages_obj_id = (page_streams.len() as u32) * (2 + fonts_per_page) + 1;
+    let pages_obj_id = generator.next_id + (page_streams.len() as u32) * (2 + fonts_per_page);
+    let xobject_resources = if xobjects.is_empty() {
+        String::new()
+    } else {
+        let refs: Vec<String> = xobjects.iter().map(|(name, id)| format!("/{} {} 0 R", name, id)).collect();
+        format!("/XObject << {} >> ", refs.join(" "))
+    };
+    let extra_fonts: String = fonts.iter().map(|(name, id)| format!("/{} {} 0 R ", name, id)).collect();
+    let color_space_resources = default_rgb
+        .map(|id| format!("/ColorSpace << /DefaultRGB {} 0 R >> ", id))
+        .unwrap_or_default();
 
-    for page_stream in page_streams {
+    for (index, page_stream) in page_streams.iter().enumerate() {
+        let layout = layouts.get(index).or(layouts.last()).copied().unwrap_or_else(PageLayout::portrait);
         let content_id = generator.add_stream_object(
             format!("<< /Length {} >>\n", page_stream.len()),
             page_stream.clone(),
         );
 
-        // Font IDs come right after content stream object
-        let first_font_id = content_id + 1;
-
-        let font_resources = format!(
-            "<< /Type /Font\n/Subtype /Type1\n/BaseFont /{}\n>>\n",
-            FONT_HELVETICA
-        );
-        generator.add_object(font_resources);
-
-        let font_bold_resources = format!(
-            "<< /Type /Font\n/Subtype /Type1\n/BaseFont /{}\n>>\n",
-            FONT_HELVETICA_BOLD
-        );
-        generator.add_object(font_bold_resources);
-
-        let font_italic_resources = format!(
-            "<< /Type /Font\n/Subtype /Type1\n/BaseFont /{}\n>>\n",
-            FONT_HELVETICA_OBLIQUE
-        );
-        generator.add_object(font_italic_resources);
-
-        let font_bold_italic_resources = format!(
-            "<< /Type /Font\n/Subtype /Type1\n/BaseFont /{}\n>>\n",
-            FONT_HELVETICA_BOLD_OBLIQUE
-        );
-        generator.add_object(font_bold_italic_resources);
+        // Font IDs come right after content stream object
+        let first_font_id = content_id + 1;
 
-        let font_courier_resources = format!(
-            "<< /Type /Font\n/Subtype /Type1\n/BaseFont /{}\n>>\n",
-            FONT_COURIER
-        );
-        generator.add_object(font_courier_resources);
+        for (index, name) in STANDARD_FONTS.into_iter().enumerate() {
+            generator.add_object(standard_font_dict(name, descriptors.get(index).copied()));
+        }
 
+        let annots = match page_annots.get(index) {
+            Some(ids) if !ids.is_empty() => {
+                let refs: Vec<String> = ids.iter().map(|id| format!("{} 0 R", id)).collect();
+                format!("/Annots [{}]\n", refs.join(" "))
+            }
+            _ => String::new(),
+        };
         let page_dict = format!(
             "<< /Type /Page\n\
              /Parent {} 0 R\n\
              /MediaBox [0 0 {} {}]\n\
+             {}\
+             {}\
              /Contents {} 0 R\n\
              /Resources << /Font << \
                  /{} {} 0 R \
@@ -1330,17 +4170,23 @@ fn assemble_pdf_bytes(page_streams: &[Vec<u8>], _font: &str, layout: &PageLayout
                  /{} {} 0 R \
                  /{} {} 0 R \
                  /{} {} 0 R \
-             >> >>\n\
+                 {}\
+             >> {}{}>>\n\
              >>\n",
             pages_obj_id,
             layout.width,
             layout.height,
+            layout.crop_box_entry(),
+            annots,
             content_id,
             FONT_HELVETICA, first_font_id,
             FONT_HELVETICA_BOLD, first_font_id + 1,
             FONT_HELVETICA_OBLIQUE, first_font_id + 2,
             FONT_HELVETICA_BOLD_OBLIQUE, first_font_id + 3,
-            FONT_COURIER, first_font_id + 4
+            FONT_COURIER, first_font_id + 4,
+            extra_fonts,
+            xobject_resources,
+            color_space_resources
         );
         let page_id = generator.add_object(page_dict);
         page_ids.push(page_id);
@@ -1361,148 +4207,44 @@ fn assemble_pdf_bytes(page_streams: &[Vec<u8>], _font: &str, layout: &PageLayout
     let catalog_dict = format!(
         "<< /Type /Catalog\n\
          /Pages {} 0 R\n\
-         >>\n",
-        actual_pages_id
+         {}>>\n",
+        actual_pages_id, catalog_extra
     );
     generator.add_object(catalog_dict);
 
-    generator.generate()
+    generator
 }
 
 /// Assemble final PDF from per-page content streams and write to file
-fn assemble_pdf(filename: &str, page_streams: &[Vec<u8>], font: &str, layout: &PageLayout) -> Result<()> {
-    let pdf_data = assemble_pdf_bytes(page_streams, font, layout);
+fn assemble_pdf(filename: &str, page_streams: &[Vec<u8>], font: &str, layouts: &[PageLayout]) -> Result<()> {
+    let pdf_data = assemble_pdf_bytes(page_streams, font, layouts);
     let mut file = File::create(filename)?;
     file.write_all(&pdf_data)?;
     Ok(())
 }
 
-/// Convert LaTeX-like math notation to readable text for PDF rendering.
-/// Since Type1 fonts don't support full LaTeX glyph rendering, we convert
-/// common math commands to their text/symbol equivalents.
-fn render_math_text(expr: &str) -> String {
-    let mut s = expr.to_string();
-
-    // Greek letters
-    let greek = [
-        ("\\alpha", "\u{03B1}"), ("\\beta", "\u{03B2}"), ("\\gamma", "\u{03B3}"),
-        ("\\delta", "\u{03B4}"), ("\\epsilon", "\u{03B5}"), ("\\zeta", "\u{03B6}"),
-        ("\\eta", "\u{03B7}"), ("\\theta", "\u{03B8}"), ("\\iota", "\u{03B9}"),
-        ("\\kappa", "\u{03BA}"), ("\\lambda", "\u{03BB}"), ("\\mu", "\u{03BC}"),
-        ("\\nu", "\u{03BD}"), ("\\xi", "\u{03BE}"), ("\\pi", "\u{03C0}"),
-        ("\\rho", "\u{03C1}"), ("\\sigma", "\u{03C3}"), ("\\tau", "\u{03C4}"),
-        ("\\upsilon", "\u{03C5}"), ("\\phi", "\u{03C6}"), ("\\chi", "\u{03C7}"),
-        ("\\psi", "\u{03C8}"), ("\\omega", "\u{03C9}"),
-        ("\\Alpha", "A"), ("\\Beta", "B"), ("\\Gamma", "\u{0393}"),
-        ("\\Delta", "\u{0394}"), ("\\Theta", "\u{0398}"), ("\\Lambda", "\u{039B}"),
-        ("\\Xi", "\u{039E}"), ("\\Pi", "\u{03A0}"), ("\\Sigma", "\u{03A3}"),
-        ("\\Phi", "\u{03A6}"), ("\\Psi", "\u{03A8}"), ("\\Omega", "\u{03A9}"),
-    ];
-
-    // Math operators and symbols
-    let operators = [
-        ("\\infty", "inf"), ("\\infinity", "inf"),
-        ("\\pm", "+/-"), ("\\mp", "-/+"),
-        ("\\times", "x"), ("\\cdot", "."),
-        ("\\div", "/"), ("\\neq", "!="), ("\\ne", "!="),
-        ("\\leq", "<="), ("\\le", "<="),
-        ("\\geq", ">="), ("\\ge", ">="),
-        ("\\approx", "~="), ("\\sim", "~"),
-        ("\\equiv", "==="), ("\\propto", "~"),
-        ("\\rightarrow", "->"), ("\\leftarrow", "<-"),
-        ("\\Rightarrow", "=>"), ("\\Leftarrow", "<="),
-        ("\\leftrightarrow", "<->"),
-        ("\\forall", "for all"), ("\\exists", "there exists"),
-        ("\\in", "in"), ("\\notin", "not in"),
-        ("\\subset", "c="), ("\\supset", "=c"),
-        ("\\cup", "U"), ("\\cap", "n"),
-        ("\\emptyset", "{}"),
-        ("\\nabla", "nabla"), ("\\partial", "d"),
-        ("\\ldots", "..."), ("\\cdots", "..."), ("\\dots", "..."),
-        ("\\quad", "  "), ("\\qquad", "    "),
-        ("\\,", " "), ("\\;", " "), ("\\!", ""),
-        ("\\left", ""), ("\\right", ""),
-        ("\\big", ""), ("\\Big", ""), ("\\bigg", ""), ("\\Bigg", ""),
-    ];
-
-    // Apply Greek letter replacements (longer patterns first to avoid partial matches)
-    for (cmd, replacement) in &greek {
-        s = s.replace(cmd, replacement);
-    }
-
-    // Apply operator replacements
-    for (cmd, replacement) in &operators {
-        s = s.replace(cmd, replacement);
-    }
-
-    // Handle \frac{a}{b} -> (a)/(b)
-    let frac_re = regex::Regex::new(r"\\frac\{([^}]*)\}\{([^}]*)\}").unwrap();
-    while frac_re.is_match(&s) {
-        s = frac_re.replace_all(&s, "($1)/($2)").to_string();
+/// A `TJ` array showing `text` with the font's kerning between letter pairs,
+/// or `None` when no pair in `text` is kerned
+fn kerned_show(font: &str, text: &str) -> Option<String> {
+    let mut parts = Vec::new();
+    let mut run = String::new();
+    let mut previous: Option<char> = None;
+    for c in text.chars() {
+        let kern = previous.map_or(0, |p| crate::font_metrics::kern_pair(font, p, c));
+        if kern != 0 {
+            parts.push(format!("({})", escape_pdf_string(&run)));
+            // TJ subtracts its numbers from the advance, so a tighter pair is positive
+            parts.push((-kern).to_string());
+            run.clear();
+        }
+        run.push(c);
+        previous = Some(c);
     }
-
-    // Handle \sqrt{x} -> sqrt(x)
-    let sqrt_re = regex::Regex::new(r"\\sqrt\{([^}]*)\}").unwrap();
-    s = sqrt_re.replace_all(&s, "sqrt($1)").to_string();
-
-    // Handle \sqrt[n]{x} -> n-root(x)
-    let nroot_re = regex::Regex::new(r"\\sqrt\[([^\]]*)\]\{([^}]*)\}").unwrap();
-    s = nroot_re.replace_all(&s, "$1-root($2)").to_string();
-
-    // Handle \sum, \prod, \int with optional limits
-    let sum_re = regex::Regex::new(r"\\sum_\{([^}]*)\}\^\{([^}]*)\}").unwrap();
-    s = sum_re.replace_all(&s, "SUM($1 to $2)").to_string();
-    s = s.replace("\\sum", "SUM");
-
-    let prod_re = regex::Regex::new(r"\\prod_\{([^}]*)\}\^\{([^}]*)\}").unwrap();
-    s = prod_re.replace_all(&s, "PROD($1 to $2)").to_string();
-    s = s.replace("\\prod", "PROD");
-
-    let int_re = regex::Regex::new(r"\\int_\{([^}]*)\}\^\{([^}]*)\}").unwrap();
-    s = int_re.replace_all(&s, "INT($1 to $2)").to_string();
-    s = s.replace("\\int", "INT");
-
-    let lim_re = regex::Regex::new(r"\\lim_\{([^}]*)\}").unwrap();
-    s = lim_re.replace_all(&s, "lim($1)").to_string();
-    s = s.replace("\\lim", "lim");
-
-    // Handle superscript ^{x} -> ^(x) and subscript _{x} -> _(x)
-    let sup_re = regex::Regex::new(r"\^\{([^}]*)\}").unwrap();
-    s = sup_re.replace_all(&s, "^($1)").to_string();
-
-    let sub_re = regex::Regex::new(r"_\{([^}]*)\}").unwrap();
-    s = sub_re.replace_all(&s, "_($1)").to_string();
-
-    // Handle \text{...} -> ...
-    let text_re = regex::Regex::new(r"\\text\{([^}]*)\}").unwrap();
-    s = text_re.replace_all(&s, "$1").to_string();
-
-    // Handle \mathbf{...}, \mathrm{...}, \mathit{...} -> content
-    let mathfmt_re = regex::Regex::new(r"\\math[a-z]+\{([^}]*)\}").unwrap();
-    s = mathfmt_re.replace_all(&s, "$1").to_string();
-
-    // Handle \hat{x}, \bar{x}, \vec{x}, \tilde{x}
-    let hat_re = regex::Regex::new(r"\\hat\{([^}]*)\}").unwrap();
-    s = hat_re.replace_all(&s, "$1^").to_string();
-    let bar_re = regex::Regex::new(r"\\bar\{([^}]*)\}").unwrap();
-    s = bar_re.replace_all(&s, "$1_bar").to_string();
-    let vec_re = regex::Regex::new(r"\\vec\{([^}]*)\}").unwrap();
-    s = vec_re.replace_all(&s, "vec($1)").to_string();
-
-    // Handle \log, \ln, \sin, \cos, \tan, \exp
-    for func in &["log", "ln", "sin", "cos", "tan", "exp", "min", "max", "det", "dim"] {
-        let cmd = format!("\\{}", func);
-        s = s.replace(&cmd, func);
+    if parts.is_empty() {
+        return None;
     }
-
-    // Strip remaining braces
-    s = s.replace('{', "").replace('}', "");
-
-    // Clean up multiple spaces
-    let multi_space = regex::Regex::new(r"  +").unwrap();
-    s = multi_space.replace_all(&s, " ").to_string();
-
-    s.trim().to_string()
+    parts.push(format!("({})", escape_pdf_string(&run)));
+    Some(format!("[{}] TJ\n", parts.join(" ")))
 }
 
 fn escape_pdf_string(text: &str) -> String {
@@ -1556,6 +4298,14 @@ impl AccessibilityOptions {
         self.title = Some(title);
         self
     }
+
+    /// Catalog entries for the document language and title display
+    pub fn catalog_entries(&self) -> String {
+        format!(
+            "/Lang ({})\n/ViewerPreferences << /DisplayDocTitle true >>\n",
+            escape_pdf_string(&self.language)
+        )
+    }
 }
 
 /// Structure element types for tagged PDF
@@ -1747,6 +4497,10 @@ pub fn element_to_structure(element: &Element) -> StructureElement {
                 TextSegment::Plain(t) | TextSegment::Bold(t) | TextSegment::Italic(t) | TextSegment::BoldItalic(t) => t.clone(),
                 TextSegment::Code(c) => format!("`{}`", c),
                 TextSegment::Link { text, url } => format!("{} ({})", text, url),
+                TextSegment::Superscript(t)
+                | TextSegment::Subscript(t)
+                | TextSegment::Highlight(t, _)
+                | TextSegment::Styled(t, _) => t.clone(),
             }).collect::<Vec<_>>().join("");
             StructureElement::new(StructureType::P)
                 .with_actual_text(text)
@@ -1755,6 +4509,10 @@ pub fn element_to_structure(element: &Element) -> StructureElement {
             StructureElement::new(StructureType::LI)
                 .with_actual_text(text.clone())
         }
+        Element::ListParagraph { text, .. } => {
+            StructureElement::new(StructureType::P)
+                .with_actual_text(text.clone())
+        }
         Element::CodeBlock { code, .. } => {
             StructureElement::new(StructureType::Code)
                 .with_actual_text(code.clone())
@@ -1763,13 +4521,21 @@ pub fn element_to_structure(element: &Element) -> StructureElement {
             StructureElement::new(StructureType::BlockQuote)
                 .with_actual_text(text.clone())
         }
+        Element::Callout { kind, text } => {
+            StructureElement::new(StructureType::BlockQuote)
+                .with_actual_text(format!("{}: {}", kind.label(), text))
+        }
+        Element::Quoted { element, .. } => {
+            StructureElement::new(StructureType::BlockQuote)
+                .with_children(vec![element_to_structure(element)])
+        }
         Element::TableRow { .. } => {
             StructureElement::new(StructureType::TR)
         }
         Element::HorizontalRule => {
             StructureElement::new(StructureType::NonStruct)
         }
-        Element::EmptyLine => {
+        Element::EmptyLine | Element::RawContent { .. } => {
             StructureElement::new(StructureType::NonStruct)
         }
         Element::Footnote { .. } => {
@@ -1786,9 +4552,17 @@ pub fn element_to_structure(element: &Element) -> StructureElement {
             StructureElement::new(StructureType::Link)
                 .with_actual_text(format!("{} ({})", text, url))
         }
-        Element::Image { alt, .. } => {
-            StructureElement::new(StructureType::Figure)
-                .with_alt_text(alt.clone())
+        Element::Chart { title, .. } => {
+            StructureElement::new(StructureType::Figure).with_alt_text(title.clone())
+        }
+        Element::Image { alt, caption, .. } => {
+            let figure = StructureElement::new(StructureType::Figure).with_alt_text(alt.clone());
+            match caption {
+                Some(caption) => figure.with_children(vec![
+                    StructureElement::new(StructureType::Caption).with_actual_text(caption.clone()),
+                ]),
+                None => figure,
+            }
         }
         Element::StyledText { text, .. } => {
             StructureElement::new(StructureType::Span)
@@ -1802,12 +4576,1249 @@ pub fn element_to_structure(element: &Element) -> StructureElement {
             StructureElement::new(StructureType::Formula)
                 .with_actual_text(expression.clone())
         }
-        Element::PageBreak => {
+        Element::PageBreak | Element::SetPageLayout(_) => {
             StructureElement::new(StructureType::NonStruct)
         }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_linearized() {
+        let elements = vec![
+            Element::Heading { level: 1, text: "Fast Web View".into() },
+            Element::Paragraph { text: "First page.".into() },
+            Element::PageBreak,
+            Element::Paragraph { text: "Second page.".into() },
+        ];
+        let bytes = generate_pdf_bytes(&elements, "Helvetica", 12.0, PageLayout::portrait()).unwrap();
+        let doc = crate::pdf::PdfDocument::from_bytes(&bytes).unwrap();
+        let first_page = doc.pages[0];
+
+        // Rebuild the object graph the same way generate_pdf_bytes does
+        let mut builder = ContentStreamBuilder::new(12.0, true, PageLayout::portrait());
+        render_elements_to_builder(&mut builder, &elements, 12.0);
+        let generator = build_document(&builder.finish(), &[PageLayout::portrait()], None);
+        let linearized = generator.generate_linearized();
+
+        let header_end = linearized.iter().skip(9).position(|&b| b == b'\n').unwrap() + 10;
+        let first_obj = String::from_utf8_lossy(&linearized[header_end..header_end + 200]).to_string();
+        assert!(first_obj.contains("/Linearized 1"), "got {}", first_obj);
+        let length = format!("/L {:010}", linearized.len());
+        assert!(first_obj.contains(&length), "expected {} in {}", length, first_obj);
+        assert!(first_obj.contains(&format!("/O {} ", first_page)));
+        assert!(first_obj.contains("/N 2 "));
+
+        let validation = crate::pdf::validate_pdf_bytes(&linearized);
+        assert!(validation.valid, "{:?}", validation.errors);
+        let reparsed = crate::pdf::PdfDocument::from_bytes(&linearized).unwrap();
+        assert_eq!(reparsed.pages, doc.pages);
+        assert!(reparsed.get_text().unwrap().contains("First page."));
+    }
+
+    #[test]
+    fn test_page_labels_roman_front_matter() {
+        assert_eq!(PageLabelStyle::LowerRoman.format(4), "iv");
+        assert_eq!(PageLabelStyle::UpperAlpha.format(28), "BB");
+
+        let options = RenderOptions {
+            page_labels: vec![
+                PageLabelRange::new(1, PageLabelStyle::LowerRoman),
+                PageLabelRange::new(3, PageLabelStyle::Decimal),
+            ],
+            ..Default::default()
+        };
+        let elements = vec![
+            Element::Paragraph { text: "Preface".into() },
+            Element::PageBreak,
+            Element::Paragraph { text: "Contents".into() },
+            Element::PageBreak,
+            Element::Paragraph { text: "Chapter".into() },
+        ];
+        let bytes = generate_pdf_bytes_with_render_options(&elements, "Helvetica", 12.0, PageLayout::portrait(), &options).unwrap();
+        let raw = String::from_utf8_lossy(&bytes).to_string();
+        assert!(raw.contains("/PageLabels << /Nums [0 << /S /r >> 2 << /S /D >>] >>"), "{}", raw);
+
+        let doc = crate::pdf::PdfDocument::from_bytes(&bytes).unwrap();
+        let pages = doc.get_text_by_page().unwrap();
+        assert!(pages[1].contains("Page ii"), "{:?}", pages);
+        assert!(pages[2].contains("Page 1"), "{:?}", pages);
+    }
+
+    #[test]
+    fn test_viewer_preferences_in_catalog() {
+        let elements = vec![Element::Paragraph { text: "Print me".into() }];
+        let options = RenderOptions {
+            viewer_preferences: ViewerPreferences { print_at_actual_size: true, duplex: None },
+            ..Default::default()
+        };
+        let bytes = generate_pdf_bytes_with_render_options(&elements, "Helvetica", 12.0, PageLayout::portrait(), &options).unwrap();
+        let raw = String::from_utf8_lossy(&bytes).to_string();
+        let catalog = &raw[raw.find("/Type /Catalog").unwrap()..];
+        assert!(catalog.contains("/ViewerPreferences << /PrintScaling /None >>"), "{}", catalog);
+        assert!(bytes.starts_with(b"%PDF-1.6"));
+        assert!(crate::pdf::validate_pdf_bytes(&bytes).valid);
+
+        let options = RenderOptions {
+            viewer_preferences: ViewerPreferences { print_at_actual_size: true, duplex: Some(Duplex::FlipLongEdge) },
+            ..Default::default()
+        };
+        let bytes = generate_pdf_bytes_with_render_options(&elements, "Helvetica", 12.0, PageLayout::portrait(), &options).unwrap();
+        assert!(String::from_utf8_lossy(&bytes).contains("/PrintScaling /None /Duplex /DuplexFlipLongEdge >>"));
+        assert!(bytes.starts_with(b"%PDF-1.7"));
+
+        // Nothing is written by default
+        let bytes = generate_pdf_bytes_with_render_options(&elements, "Helvetica", 12.0, PageLayout::portrait(), &RenderOptions::default()).unwrap();
+        assert!(!String::from_utf8_lossy(&bytes).contains("/ViewerPreferences"));
+    }
+
+    #[test]
+    fn test_font_fallback_switches_fonts_mid_line() {
+        let options = RenderOptions {
+            font_fallback: FontFallback::new(vec![FallbackFont::Japanese]),
+            ..Default::default()
+        };
+        let elements = vec![Element::Paragraph { text: "Hello \u{65e5}\u{672c} world".into() }];
+        let (pages, _) = render_document(&elements, 12.0, PageLayout::portrait(), &options, &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+        assert!(stream.contains("(Hello ) Tj\n/HeiseiMin-W3 12 Tf\n<65E5672C> Tj\n/Helvetica 12 Tf\n( world) Tj"), "{}", stream);
+
+        let bytes = generate_pdf_bytes_with_render_options(&elements, "Helvetica", 12.0, PageLayout::portrait(), &options).unwrap();
+        let raw = String::from_utf8_lossy(&bytes).to_string();
+        assert!(raw.contains("/Encoding /UniJIS-UCS2-H"));
+        assert!(raw.contains("/HeiseiMin-W3 3 0 R"), "{}", raw);
+        assert!(crate::pdf::validate_pdf_bytes(&bytes).valid);
+    }
+
+    #[test]
+    fn test_font_widths_written_for_standard_fonts() {
+        let elements = vec![Element::Paragraph { text: "Spacing".into() }];
+        let options = RenderOptions { font_widths: true, ..Default::default() };
+        let bytes = generate_pdf_bytes_with_render_options(&elements, "Helvetica", 12.0, PageLayout::portrait(), &options).unwrap();
+        assert!(crate::pdf::validate_pdf_bytes(&bytes).valid);
+        let raw = String::from_utf8_lossy(&bytes).to_string();
+        let courier = raw.split("/BaseFont /Courier\n").nth(1).unwrap();
+        assert!(courier.starts_with("/FirstChar 32\n/LastChar 126\n/Widths ["), "{}", courier);
+        let widths = courier.split("/Widths [").nth(1).unwrap().split(']').next().unwrap();
+        let widths: Vec<&str> = widths.split_whitespace().collect();
+        assert_eq!(widths.len(), 95);
+        assert!(widths.iter().all(|w| *w == "600"));
+        assert_eq!(raw.matches("/Type /FontDescriptor").count(), 5);
+
+        // Off by default
+        let bytes = generate_pdf_bytes(&elements, "Helvetica", 12.0, PageLayout::portrait()).unwrap();
+        assert!(!String::from_utf8_lossy(&bytes).contains("/Widths"));
+    }
+
+    #[test]
+    fn test_empty_document_options() {
+        let elements = crate::elements::parse_markdown("");
+        let generate = |empty_document: EmptyDocument| {
+            let options = RenderOptions { empty_document, ..Default::default() };
+            let bytes = generate_pdf_bytes_with_render_options(&elements, "Helvetica", 12.0, PageLayout::portrait(), &options).unwrap();
+            assert!(crate::pdf::validate_pdf_bytes(&bytes).valid);
+            let doc = crate::pdf::PdfDocument::from_bytes(&bytes).unwrap();
+            let pages = doc.get_text_by_page().unwrap();
+            assert_eq!(pages.len(), 1);
+            pages[0].trim().to_string()
+        };
+        assert_eq!(generate(EmptyDocument::Blank), "Page 1");
+        assert_eq!(generate(EmptyDocument::Bare), "");
+        assert_eq!(generate(EmptyDocument::Placeholder("(no content)".into())), "(no content)\nPage 1");
+    }
+
+    #[test]
+    fn test_task_summary_counts_checked_items() {
+        let elements = crate::elements::parse_markdown(
+            "# Plan\n\n- [x] Design\n- [x] Build\n- [ ] Test\n- [X] Docs\n- [ ] Release\n",
+        );
+        assert_eq!(crate::elements::task_progress(&elements), Some((3, 5)));
+        let options = RenderOptions { task_summary: true, ..Default::default() };
+        let (pages, _) = render_document(&elements, 12.0, PageLayout::portrait(), &options, &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+        assert!(stream.contains("(Tasks: 3/5 complete) Tj"), "{}", stream);
+
+        let (pages, _) = render_document(&elements, 12.0, PageLayout::portrait(), &RenderOptions::default(), &mut |_| {});
+        assert!(!String::from_utf8_lossy(&pages[0]).contains("Tasks:"));
+        assert_eq!(crate::elements::task_progress(&crate::elements::parse_markdown("No tasks")), None);
+    }
+
+    #[test]
+    fn test_symbol_characters_use_symbol_fonts() {
+        let elements = vec![Element::Paragraph { text: "Next \u{2192} done \u{2713}".into() }];
+        let bytes = generate_pdf_bytes(&elements, "Helvetica", 12.0, PageLayout::portrait()).unwrap();
+        let raw = String::from_utf8_lossy(&bytes).to_string();
+        assert!(raw.contains("(Next ) Tj\n/Symbol 12 Tf\n(\\256) Tj\n/Helvetica 12 Tf\n( done ) Tj\n/ZapfDingbats 12 Tf\n(\\063) Tj"), "{}", raw);
+        assert!(raw.contains("/BaseFont /Symbol"));
+        assert!(raw.contains("/BaseFont /ZapfDingbats"));
+        assert!(crate::pdf::validate_pdf_bytes(&bytes).valid);
+
+        let text = crate::pdf::PdfDocument::from_bytes(&bytes).unwrap().get_text().unwrap();
+        assert!(text.contains('\u{2192}') && text.contains('\u{2713}'), "{}", text);
+        assert!(!text.contains('\u{FFFD}'));
+
+        // Documents without symbols don't list the symbol fonts
+        let elements = vec![Element::Paragraph { text: "Plain".into() }];
+        let bytes = generate_pdf_bytes(&elements, "Helvetica", 12.0, PageLayout::portrait()).unwrap();
+        assert!(!String::from_utf8_lossy(&bytes).contains("/Symbol"));
+    }
+
+    #[test]
+    fn test_header_image_embedded_once() {
+        let path = std::env::temp_dir().join("pdfrs_header_logo.jpg");
+        jpeg_encoder::Encoder::new_file(&path, 80)
+            .unwrap()
+            .encode(&[200u8; 4 * 4 * 3], 4, 4, jpeg_encoder::ColorType::Rgb)
+            .unwrap();
+        let options = RenderOptions {
+            header_image: Some(HeaderImage {
+                path: path.to_str().unwrap().to_string(),
+                x: 72.0,
+                y: 740.0,
+                width: 40.0,
+                height: 40.0,
+            }),
+            ..Default::default()
+        };
+        let elements = vec![
+            Element::Paragraph { text: "One".into() },
+            Element::PageBreak,
+            Element::Paragraph { text: "Two".into() },
+            Element::PageBreak,
+            Element::Paragraph { text: "Three".into() },
+        ];
+        let bytes = generate_pdf_bytes_with_render_options(&elements, "Helvetica", 12.0, PageLayout::portrait(), &options).unwrap();
+        let raw = String::from_utf8_lossy(&bytes).to_string();
+        assert_eq!(raw.matches("/Subtype /Image").count(), 1);
+        assert_eq!(raw.matches("/HeaderImage Do").count(), 3);
+        assert_eq!(raw.matches("/XObject << /HeaderImage ").count(), 3);
+
+        let doc = crate::pdf::PdfDocument::from_bytes(&bytes).unwrap();
+        assert_eq!(doc.pages.len(), 3);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_left_margin_moves_text_origin() {
+        let layout = PageLayout::portrait().with_margins(72.0, 72.0, 72.0, 36.0);
+        assert_eq!(layout.content_width(), PageLayout::portrait().content_width() + 36.0);
+
+        let elements = vec![Element::Paragraph { text: "word ".repeat(100) }];
+        let (pages, _) = render_document(&elements, 12.0, layout, &RenderOptions::default(), &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+        let rows: Vec<&str> = stream.lines().filter(|l| l.ends_with(" Tm")).collect();
+        // Every wrapped body line starts at the margin; the centered page number does not
+        assert!(rows.iter().filter(|l| l.starts_with("1 0 0 1 36 ")).count() >= 2, "{:?}", rows);
+        assert!(!rows.iter().any(|l| l.starts_with("1 0 0 1 72 ")), "{:?}", rows);
+    }
+
+    #[test]
+    fn test_ordered_markers_by_depth() {
+        assert_eq!(OrderedMarkerStyle::LowerRoman.format(4), "iv");
+        assert_eq!(OrderedMarkerStyle::LowerRoman.format(1994), "mcmxciv");
+        assert_eq!(OrderedMarkerStyle::LowerAlpha.format(27), "aa");
+        assert_eq!(OrderedMarkerStyle::UpperAlpha.format(52), "AZ");
+
+        let item = |number, depth| Element::OrderedListItem { number, text: "item".into(), depth };
+        let elements = vec![item(1, 0), item(1, 1), item(2, 1), item(1, 2), item(2, 2), item(1, 3)];
+        let (pages, _) = render_document(&elements, 12.0, PageLayout::portrait(), &RenderOptions::default(), &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+        for marker in ["(1. item)", "(a. item)", "(b. item)", "(i. item)", "(ii. item)", "(1. item)"] {
+            assert!(stream.contains(marker), "missing {} in {}", marker, stream);
+        }
+    }
+
+    #[test]
+    fn test_nested_ordered_list_restarts_and_parent_continues() {
+        let markdown = "1. first\n   1. sub one\n   1. sub two\n1. second\n   3. from three\n\nAfter\n\n5. fresh";
+        let elements = crate::elements::parse_markdown(markdown);
+        let options = RenderOptions { ordered_markers: vec![OrderedMarkerStyle::Decimal], ..Default::default() };
+        let (pages, _) = render_document(&elements, 12.0, PageLayout::portrait(), &options, &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+        let shown: Vec<&str> = stream.lines().filter(|l| l.ends_with(") Tj") && l.contains(". ")).collect();
+        assert_eq!(
+            shown,
+            [
+                "(1. first) Tj",
+                "(1. sub one) Tj",
+                "(2. sub two) Tj",
+                "(2. second) Tj",
+                "(3. from three) Tj",
+                "(5. fresh) Tj",
+            ],
+            "{}",
+            stream
+        );
+    }
+
+    #[test]
+    fn test_quoted_list_item_has_bar_and_bullet() {
+        let elements = crate::elements::parse_markdown("> - quoted item");
+        let (pages, _) = render_document(&elements, 12.0, PageLayout::portrait(), &RenderOptions::default(), &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+        // Bar left of the indented text, bullet item at the quote indent
+        assert!(stream.contains("0.8 0.8 0.8 RG"), "{}", stream);
+        assert!(stream.contains("2 w\n74 "), "{}", stream);
+        assert!(stream.contains(&format!("1 0 0 1 {} ", 72.0 + QUOTE_INDENT)), "{}", stream);
+
+        let bytes = generate_pdf_bytes(&elements, "Helvetica", 12.0, PageLayout::portrait()).unwrap();
+        let text = crate::pdf::PdfDocument::from_bytes(&bytes).unwrap().get_text().unwrap();
+        assert!(text.contains("\u{2022} quoted item"), "{:?}", text);
+    }
+
+    #[test]
+    fn test_warning_callout_uses_warning_color() {
+        let elements = crate::elements::parse_markdown("> [!WARNING]\n> Mind the gap");
+        assert!(matches!(
+            elements[0],
+            Element::Callout { kind: crate::elements::CalloutKind::Warning, .. }
+        ));
+        let (pages, _) = render_document(&elements, 12.0, PageLayout::portrait(), &RenderOptions::default(), &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+        let warning = callout_color(crate::elements::CalloutKind::Warning);
+        assert_ne!(warning, callout_color(crate::elements::CalloutKind::Note));
+        // Colored bar and a label in the same color, then the body in black
+        assert!(stream.contains(&format!("{} RG", warning.fill_operator().trim_end_matches(" rg"))), "{}", stream);
+        let label = stream.find("(Warning) Tj").expect("label");
+        let label_color = stream[..label].rfind(&warning.fill_operator()).expect("label color");
+        assert!(!stream[label_color + warning.fill_operator().len()..label].contains(" rg\n"));
+        assert!(stream[label..].contains("(Mind the gap) Tj"));
+        assert!(!stream.contains("0.8 0.8 0.8 RG"));
+    }
+
+    #[test]
+    fn test_heading_case_transforms() {
+        let elements = vec![Element::Heading { level: 2, text: "the quick brown fox".into() }];
+        let options = RenderOptions { heading_case: HeadingCase::Title, ..Default::default() };
+        let (pages, _) = render_document(&elements, 12.0, PageLayout::portrait(), &options, &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+        assert!(stream.contains("(The Quick Brown Fox) Tj"), "{}", stream);
+
+        assert_eq!(HeadingCase::Title.apply("a tale of two PDF files"), "A Tale of Two PDF Files");
+        assert_eq!(HeadingCase::Title.apply("what it is for"), "What It Is For");
+        assert_eq!(HeadingCase::Sentence.apply("The Quick Brown Fox"), "The quick brown fox");
+        assert_eq!(HeadingCase::Upper.apply("Quick fox"), "QUICK FOX");
+        assert_eq!(HeadingCase::None.apply("the fox"), "the fox");
+    }
+
+    #[test]
+    fn test_soft_wrapped_code_stays_within_content_width() {
+        let code = format!("let x = \"{}\";", "a".repeat(290));
+        let elements = vec![Element::CodeBlock { language: "text".into(), code }];
+        let layout = PageLayout::portrait();
+        let options = RenderOptions { code_wrap: CodeWrap::Soft, ..Default::default() };
+        let (pages, _) = render_document(&elements, 12.0, layout, &options, &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+
+        let shown: Vec<(f32, f32, String)> = stream
+            .lines()
+            .zip(stream.lines().skip(1))
+            .filter(|(tm, tj)| tm.ends_with(" Tm") && tj.ends_with(") Tj") && tj.contains("aaaa"))
+            .map(|(tm, tj)| {
+                let parts: Vec<&str> = tm.split_whitespace().collect();
+                let text = tj.trim_start_matches('(').trim_end_matches(") Tj").to_string();
+                (parts[4].parse().unwrap(), parts[5].parse().unwrap(), text)
+            })
+            .collect();
+        assert!(shown.len() > 1, "{}", stream);
+        let ys: std::collections::BTreeSet<i64> = shown.iter().map(|(_, y, _)| *y as i64).collect();
+        assert!(ys.len() > 1, "expected several visual lines");
+        let right = layout.width - layout.margin_right;
+        for (x, _, text) in &shown {
+            let end = x + text.chars().count() as f32 * 12.0 * 0.85 * 0.6;
+            assert!(end <= right + 0.01, "{} ends at {} past {}", text, end, right);
+        }
+
+        // Truncation keeps one line ending in an ellipsis
+        let options = RenderOptions { code_wrap: CodeWrap::Truncate, ..Default::default() };
+        let (pages, _) = render_document(&elements, 12.0, layout, &options, &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+        assert_eq!(stream.lines().filter(|l| l.contains("aaaa")).count(), 1);
+        assert!(stream.contains("a...) Tj"), "{}", stream);
+    }
+
+    #[test]
+    fn test_kerning_emits_tj_adjustments() {
+        let elements = vec![Element::Heading { level: 2, text: "AVATAR Today".into() }];
+        let options = RenderOptions { kerning: true, ..Default::default() };
+        let (pages, _) = render_document(&elements, 12.0, PageLayout::portrait(), &options, &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+        assert!(stream.contains("[(A) 70 (V) 80 (A) 120 (T) 120 (AR T) 120 (oda) 30 (y)] TJ"), "{}", stream);
+
+        // Off by default
+        let (pages, _) = render_document(&elements, 12.0, PageLayout::portrait(), &RenderOptions::default(), &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+        assert!(stream.contains("(AVATAR Today) Tj"));
+        assert!(!stream.contains("TJ"));
+    }
+
+    #[test]
+    fn test_parallel_sections_match_sequential_output() {
+        let mut elements = Vec::new();
+        for section in 0..6 {
+            elements.push(Element::Heading { level: 1, text: format!("Chapter {}", section) });
+            elements.push(Element::Heading { level: 2, text: "Background".into() });
+            elements.push(Element::Paragraph { text: "Independent section text. ".repeat(40 * (section + 1)) });
+            elements.push(Element::CodeBlock { language: "rust".into(), code: "fn main() {}".into() });
+            elements.push(Element::PageBreak);
+        }
+        let sequential = RenderOptions {
+            heading_numbering: HeadingNumbering { enabled: true, ..Default::default() },
+            footer: Some("Page {page}".into()),
+            ..Default::default()
+        };
+        let parallel = RenderOptions { parallel_sections: true, ..sequential.clone() };
+        let (expected, _) = render_document(&elements, 12.0, PageLayout::portrait(), &sequential, &mut |_| {});
+        let (pages, _) = render_document(&elements, 12.0, PageLayout::portrait(), &parallel, &mut |_| {});
+        assert!(expected.len() > 7);
+        assert_eq!(pages, expected);
+        let last = String::from_utf8_lossy(pages.last().unwrap()).to_string();
+        assert!(last.contains(&format!("(Page {}) Tj", expected.len())));
+    }
+
+    #[test]
+    fn test_dashed_rule_style() {
+        let elements = vec![Element::HorizontalRule];
+        let rule_style = RuleStyle { thickness: 3.0, color: Color::red(), style: LineStyle::Dashed, width_fraction: 0.5 };
+        let options = RenderOptions { rule_style, ..Default::default() };
+        let layout = PageLayout::portrait();
+        let (pages, _) = render_document(&elements, 12.0, layout, &options, &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+        assert!(stream.contains("q\n[9 6] 0 d\n1 0 0 RG\n3 w\n"), "{}", stream);
+        let x1 = layout.margin_left + layout.content_width() / 4.0;
+        assert!(stream.contains(&format!("{} ", x1)));
+
+        // The default rule stays a 1pt solid gray line
+        let (pages, _) = render_document(&elements, 12.0, layout, &RenderOptions::default(), &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+        assert!(stream.contains("0.5 0.5 0.5 RG\n1 w\n") && !stream.contains(" d\n"));
+    }
+
+    #[test]
+    fn test_leading_multiplier_scales_line_advance() {
+        let text = "word ".repeat(60);
+        let elements = vec![Element::Paragraph { text }];
+        let advance = |multiplier: f32| {
+            let options = RenderOptions { leading_multiplier: Some(multiplier), ..Default::default() };
+            let (pages, _) = render_document(&elements, 12.0, PageLayout::portrait(), &options, &mut |_| {});
+            let stream = String::from_utf8_lossy(&pages[0]).to_string();
+            let ys: Vec<f32> = stream
+                .lines()
+                .filter(|l| l.starts_with("1 0 0 1 72 ") && l.ends_with(" Tm"))
+                .map(|l| l.split_whitespace().nth(5).unwrap().parse().unwrap())
+                .collect();
+            ys[0] - ys[1]
+        };
+        let single = advance(1.2);
+        assert!((single - 14.4).abs() < 0.01, "advance {}", single);
+        assert!((advance(2.4) - 2.0 * single).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_auto_height_fits_content() {
+        let elements = vec![
+            Element::Heading { level: 2, text: "Receipt".into() },
+            Element::Paragraph { text: "Coffee 3.50".into() },
+            Element::Paragraph { text: "Total 3.50".into() },
+        ];
+        let layout = PageLayout::portrait().with_auto_height(true);
+        let (pages, fitted) = render_document(&elements, 12.0, layout, &RenderOptions::default(), &mut |_| {});
+        assert_eq!(pages.len(), 1);
+        let fitted = fitted[0];
+        assert!(fitted.height < 792.0 && fitted.height > 72.0 + 72.0, "height {}", fitted.height);
+
+        let bytes = generate_pdf_bytes(&elements, "Helvetica", 12.0, layout).unwrap();
+        let doc = crate::pdf::PdfDocument::from_bytes(&bytes).unwrap();
+        assert_eq!(doc.pages.len(), 1);
+        assert!(String::from_utf8_lossy(&bytes).contains(&format!("/MediaBox [0 0 612 {}]", fitted.height)));
+        assert!(doc.get_text().unwrap().contains("Total 3.50"));
+    }
+
+    #[test]
+    fn test_set_page_layout_switches_media_box() {
+        let elements = vec![
+            Element::SetPageLayout(PageLayout::portrait()),
+            Element::Heading { level: 1, text: "Cover".into() },
+            Element::SetPageLayout(PageLayout::landscape()),
+            Element::Paragraph { text: "Wide data table".into() },
+        ];
+        let (pages, layouts) = render_document(&elements, 12.0, PageLayout::portrait(), &RenderOptions::default(), &mut |_| {});
+        // The leading switch reuses the untouched first page
+        assert_eq!(pages.len(), 2);
+        assert_eq!(layouts, vec![PageLayout::portrait(), PageLayout::landscape()]);
+
+        let bytes = generate_pdf_bytes(&elements, "Helvetica", 12.0, PageLayout::portrait()).unwrap();
+        let doc = crate::pdf::PdfDocument::from_bytes(&bytes).unwrap();
+        assert_eq!(doc.pages.len(), 2);
+        let raw = String::from_utf8_lossy(&bytes);
+        assert!(raw.contains("/MediaBox [0 0 612 792]"));
+        assert!(raw.contains("/MediaBox [0 0 792 612]"));
+        assert!(doc.get_text_by_page().unwrap()[1].contains("Wide data table"));
+    }
+
+    #[test]
+    fn test_crop_box_written_inside_media_box() {
+        let elements = vec![Element::Paragraph { text: "Trimmed".into() }];
+        let layout = PageLayout::portrait().with_crop_box([9.0, 9.0, 603.0, 783.0]);
+        let bytes = generate_pdf_bytes(&elements, "Helvetica", 12.0, layout).unwrap();
+        let raw = String::from_utf8_lossy(&bytes);
+        assert!(raw.contains("/MediaBox [0 0 612 792]"));
+        assert!(raw.contains("/CropBox [9 9 603 783]"));
+
+        let plain = generate_pdf_bytes(&elements, "Helvetica", 12.0, PageLayout::portrait()).unwrap();
+        assert!(!String::from_utf8_lossy(&plain).contains("/CropBox"));
+    }
+
+    #[test]
+    fn test_object_streams_declare_pdf_1_5() {
+        let elements = vec![Element::Paragraph { text: "Packed".into() }];
+        let streams = render_page_streams(&elements, 12.0, PageLayout::portrait());
+        let mut generator = build_document(&streams, &[PageLayout::portrait()], None);
+        assert!(generator.generate().starts_with(b"%PDF-1.4\n"));
+
+        generator.set_object_streams(true);
+        let bytes = generator.generate();
+        assert!(bytes.starts_with(b"%PDF-1.5\n"));
+        let text = String::from_utf8_lossy(&bytes);
+        assert!(text.contains("/Type /ObjStm"));
+        assert!(text.contains("/Type /XRef"));
+        // The catalog is compressed, so check its /Version before packing
+        let catalog = generator.objects.last().unwrap();
+        assert!(generator.object_content(catalog).starts_with("<< /Version /1.5"));
+
+        // An explicit lower version never undoes a requirement
+        generator.require_version(1, 4);
+        assert_eq!(generator.version, (1, 5));
+        generator.set_version(2, 0);
+        assert!(generator.generate().starts_with(b"%PDF-2.0\n"));
+
+        // The root comes from the trailer, not the last object; only it gets a
+        // /Version, and one it declares itself is kept
+        let mut generator = PdfGenerator::new();
+        let pages = generator.add_object("<< /Type /Pages\n/Kids []\n/Count 0\n>>".into());
+        let catalog = generator.add_object(format!(
+            "<< /Type /Catalog\n/Pages {} 0 R\n/Names << /Version (names) >>\n>>",
+            pages
+        ));
+        let other = generator.add_object("<< /Note (/Type /Catalog) >>".into());
+        generator.root = Some(catalog);
+        generator.set_object_streams(true);
+        let bytes = generator.generate();
+        let text = String::from_utf8_lossy(&bytes);
+        assert!(text.contains(&format!("/Root {} 0 R\n", catalog)), "{}", text);
+        let content = |index: usize| generator.object_content(&generator.objects[index]).into_owned();
+        assert!(content(1).starts_with("<< /Version /1.5 /Type /Catalog"), "{}", content(1));
+        assert_eq!(generator.objects[2].id, other);
+        assert!(!content(2).contains("/Version"));
+        generator.objects[1].content = format!("<< /Version /1.7\n/Type /Catalog\n/Pages {} 0 R\n>>", pages);
+        assert_eq!(generator.object_content(&generator.objects[1]), generator.objects[1].content);
+    }
+
+    #[test]
+    fn test_starting_id_numbers_objects_and_xref() {
+        let mut generator = PdfGenerator::new().with_starting_id(50);
+        let pages = generator.add_object("<< /Type /Pages\n/Kids []\n/Count 0\n>>".into());
+        let catalog = generator.add_object(format!("<< /Type /Catalog\n/Pages {} 0 R\n>>", pages));
+        assert_eq!((pages, catalog), (50, 51));
+
+        let bytes = generator.generate();
+        let text = String::from_utf8_lossy(&bytes);
+        assert!(text.contains("xref\n0 1\n0000000000 65535 f \n50 2\n"), "{}", text);
+        assert!(text.contains("/Size 52\n"));
+        assert!(text.contains("/Root 51 0 R\n"));
+        // The first entry of the subsection points at object 50
+        let entries = &text[text.find("50 2\n").unwrap() + 5..];
+        let offset: usize = entries[..10].parse().unwrap();
+        assert!(bytes[offset..].starts_with(b"50 0 obj"));
+    }
+
+    #[test]
+    fn test_progress_reports_increasing_pages() {
+        let mut elements = Vec::new();
+        for i in 0..4 {
+            elements.push(Element::Paragraph { text: format!("Page {} text", i) });
+            elements.push(Element::PageBreak);
+        }
+        let mut reports = Vec::new();
+        let bytes = generate_pdf_bytes_with_progress(
+            &elements, "Helvetica", 12.0, PageLayout::portrait(),
+            &mut |p| reports.push(p),
+        ).unwrap();
+        assert!(!bytes.is_empty());
+
+        assert_eq!(reports.len(), elements.len() + 2);
+        assert!(reports.windows(2).all(|w| w[1].pages >= w[0].pages));
+        assert!(reports.windows(2).all(|w| w[1].phase != ProgressPhase::Rendering || w[1].current > w[0].current));
+        let last = reports.last().unwrap();
+        assert_eq!(last.phase, ProgressPhase::Writing);
+        assert_eq!(last.pages, 5);
+        assert!(reports.iter().any(|p| p.pages > 0 && p.pages < 5));
+    }
+
+    #[test]
+    fn test_table_cell_inline_formatting() {
+        let elements = crate::elements::parse_markdown("| Name | Note |\n|---|---|\n| **bold** | plain `code` |");
+        let bytes = generate_pdf_bytes(&elements, "Helvetica", 12.0, PageLayout::portrait()).unwrap();
+        let content = String::from_utf8_lossy(&bytes);
+        assert!(!content.contains("**"));
+        let bold = content.find("(bold) Tj").unwrap();
+        let font = content[..bold].rfind(" Tf").unwrap();
+        assert!(content[..font].ends_with("/Helvetica-Bold 12"), "{}", &content[font - 30..bold]);
+        let code = content.find("(code) Tj").unwrap();
+        assert!(content[..code].rfind("/Courier").unwrap() > bold);
+    }
+
+    #[test]
+    fn test_cmyk_and_gray_color_operators() {
+        let cmyk = Color::cmyk(0.1, 0.2, 0.3, 0.4);
+        assert_eq!(cmyk.fill_operator(), "0.1 0.2 0.3 0.4 k");
+        assert_eq!(cmyk.stroke_operator(), "0.1 0.2 0.3 0.4 K");
+        assert_eq!(Color::gray_level(0.25).fill_operator(), "0.25 g");
+        assert_eq!(Color::named("Navy"), Some(Color::rgb(0.0, 0.0, 128.0 / 255.0)));
+        assert_eq!(Color::named("chartreuse-ish"), None);
+
+        let ops = shape_operators("0 0 5 5 re", &ShapeStyle { fill: Some(cmyk), stroke: Some(Color::gray_level(0.0)), line_width: 1.0 });
+        assert_eq!(ops, "0.1 0.2 0.3 0.4 k\n0 G\n1 w\n0 0 5 5 re B\n");
+
+        let mut builder = ContentStreamBuilder::new(12.0, false, PageLayout::portrait());
+        builder.set_color(Color::cmyk(0.0, 1.0, 1.0, 0.0));
+        builder.emit_line("Print red", 12.0);
+        let stream = String::from_utf8_lossy(&builder.finish().concat()).to_string();
+        assert!(stream.contains("0 1 1 0 k\n"), "{}", stream);
+    }
+
+    #[test]
+    fn test_shape_fill_and_stroke() {
+        let style = ShapeStyle {
+            fill: Some(Color::rgb(1.0, 1.0, 0.0)),
+            stroke: Some(Color::blue()),
+            line_width: 2.0,
+        };
+        let ops = shape_operators("10 20 30 40 re", &style);
+        assert_eq!(ops, "1 1 0 rg\n0 0 1 RG\n2 w\n10 20 30 40 re B\n");
+        assert_eq!(ShapeStyle::filled(Color::red()).paint_operator(), "f");
+        assert_eq!(ShapeStyle::stroked(Color::red(), 1.0).paint_operator(), "S");
+        assert!(!shape_operators("0 0 m 5 5 l", &ShapeStyle::stroked(Color::red(), 1.0)).contains(" rg"));
+    }
+
+    #[test]
+    fn test_wrapping_honours_nbsp_and_soft_hyphens() {
+        // 78 characters fit on a portrait line at 12pt
+        let filler = "word ".repeat(14);
+        let text = format!("{}ab New\u{A0}York", filler);
+        let elements = vec![Element::Paragraph { text }];
+        let bytes = generate_pdf_bytes(&elements, "Helvetica", 12.0, PageLayout::portrait()).unwrap();
+        let content = String::from_utf8_lossy(&bytes);
+        assert!(content.contains("(New York) Tj"), "{}", content);
+        // Neither the UTF-8 nor the Latin-1 form of the no-break space is drawn
+        assert!(bytes.windows(2).all(|pair| pair != [0xC2, 0xA0]));
+        assert!(!bytes.contains(&0xA0));
+
+        let text = format!("{}extra\u{AD}ordinary", filler);
+        let elements = vec![Element::Paragraph { text }];
+        let bytes = generate_pdf_bytes(&elements, "Helvetica", 12.0, PageLayout::portrait()).unwrap();
+        let content = String::from_utf8_lossy(&bytes);
+        assert!(content.contains(" extra-) Tj"), "{}", content);
+        assert!(content.contains("(ordinary) Tj"));
+
+        // Unused soft hyphens are not drawn
+        let elements = vec![Element::Paragraph { text: "extra\u{AD}ordinary".into() }];
+        let bytes = generate_pdf_bytes(&elements, "Helvetica", 12.0, PageLayout::portrait()).unwrap();
+        assert!(String::from_utf8_lossy(&bytes).contains("(extraordinary) Tj"));
+    }
+
+    #[test]
+    fn test_page_numbers_can_be_hidden_or_offset() {
+        let elements = vec![Element::Paragraph { text: "Chapter text".into() }];
+        let options = RenderOptions { show_page_numbers: false, ..Default::default() };
+        let bytes = generate_pdf_bytes_with_render_options(&elements, "Helvetica", 12.0, PageLayout::portrait(), &options).unwrap();
+        assert!(!String::from_utf8_lossy(&bytes).contains("(Page "));
+
+        let options = RenderOptions { page_number_start: 5, ..Default::default() };
+        let bytes = generate_pdf_bytes_with_render_options(&elements, "Helvetica", 12.0, PageLayout::portrait(), &options).unwrap();
+        let content = String::from_utf8_lossy(&bytes);
+        assert!(content.contains("(Page 5) Tj"));
+        assert!(!content.contains("(Page 1) Tj"));
+    }
+
+    #[test]
+    fn test_code_block_tabs_expand() {
+        let elements = vec![Element::CodeBlock { language: "text".into(), code: "\tx  \nab\ty".into() }];
+        let options = RenderOptions {
+            code_block_style: CodeBlockStyle { tab_width: 4, trim_trailing: true },
+            ..Default::default()
+        };
+        let (pages, _) = render_document(&elements, 12.0, PageLayout::portrait(), &options, &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+        assert!(!stream.contains("\\t"), "{}", stream);
+
+        // Tabs expand to the next stop, so "x" and "y" line up four columns in
+        assert!(stream.contains("/Courier "), "{}", stream);
+        assert!(stream.contains("(    x) Tj"), "{}", stream);
+        assert!(stream.contains("(ab  y) Tj"), "{}", stream);
+        assert_eq!(CodeBlockStyle::default().apply("\tx  "), "    x  ");
+    }
+
+    #[test]
+    fn test_code_theme_colors() {
+        let elements = vec![Element::CodeBlock { language: "rust".into(), code: "let x = 1;\nfoo(x);".into() }];
+        let options = RenderOptions { code_theme: CodeTheme::dark(), code_line_numbers: true, ..Default::default() };
+        let bytes = generate_pdf_bytes_with_render_options(&elements, "Helvetica", 12.0, PageLayout::portrait(), &options).unwrap();
+        let content = String::from_utf8_lossy(&bytes);
+        let bg = CodeTheme::dark().background;
+        assert!(content.contains(&format!("{} {} {} rg\n", bg.r, bg.g, bg.b)));
+        let fill = content.find(&format!("{} {} {} rg\n", bg.r, bg.g, bg.b)).unwrap();
+        assert!(content[fill..].lines().nth(1).unwrap().ends_with("re f"));
+        assert!(!content.contains("0.95 0.95 0.95 rg"));
+        // Line numbers are drawn in the gutter
+        assert!(content.contains("(2) Tj"));
+
+        let solarized = CodeTheme::from_syntect_theme("Solarized (dark)").unwrap();
+        assert!(solarized.background.r < 0.2);
+        assert!(CodeTheme::from_syntect_theme("no such theme").is_none());
+    }
+
+    #[test]
+    fn test_heading_kept_with_following_lines() {
+        // Fill the first page so the heading would land on its last line
+        let layout = PageLayout::portrait();
+        let body_lh = line_height(12.0);
+        let filler = ((layout.content_top() - layout.margin_bottom) / body_lh) as usize - 3;
+        let mut elements: Vec<Element> = (0..filler)
+            .map(|i| Element::Paragraph { text: format!("Filler {}", i) })
+            .collect();
+        elements.push(Element::Heading { level: 2, text: "Stranded".into() });
+        elements.push(Element::Paragraph { text: "First body line".into() });
+
+        let (pages, _) = render_document(&elements, 12.0, layout, &RenderOptions::default(), &mut |_| {});
+        let page = |i: usize| String::from_utf8_lossy(&pages[i]).to_string();
+        assert_eq!(pages.len(), 2);
+        assert!(!page(0).contains("(Stranded)"));
+        assert!(page(1).contains("(Stranded)"));
+        assert!(page(1).contains("(First body line)"));
+
+        // Without the check the heading stays at the bottom of page one
+        let options = RenderOptions { orphan_lines: 0, ..Default::default() };
+        let (pages, _) = render_document(&elements, 12.0, layout, &options, &mut |_| {});
+        assert!(String::from_utf8_lossy(&pages[0]).contains("(Stranded)"));
+    }
+
+    #[test]
+    fn test_heading_numbering() {
+        let heading = |level: u8, text: &str| Element::Heading { level, text: text.into() };
+        let elements = vec![
+            heading(1, "Intro"),
+            heading(2, "Scope"),
+            heading(2, "Terms"),
+            heading(1, "Design"),
+            heading(3, "Deep"),
+        ];
+        let mut builder = ContentStreamBuilder::new(12.0, false, PageLayout::portrait());
+        builder.options.heading_numbering = HeadingNumbering::enabled();
+        render_elements_to_builder(&mut builder, &elements, 12.0);
+        let stream = String::from_utf8_lossy(&builder.finish().concat()).to_string();
+
+        for line in ["(1 Intro) Tj", "(1.1 Scope) Tj", "(1.2 Terms) Tj", "(2 Design) Tj", "(2.0.1 Deep) Tj"] {
+            assert!(stream.contains(line), "missing {} in {}", line, stream);
+        }
+    }
+
+    #[test]
+    fn test_task_item_draws_checkbox() {
+        let elements = vec![Element::TaskListItem { checked: true, text: "Ship it".into() }];
+        let mut builder = ContentStreamBuilder::new(12.0, false, PageLayout::portrait());
+        render_elements_to_builder(&mut builder, &elements, 12.0);
+        let stream = String::from_utf8_lossy(&builder.finish().concat()).to_string();
+
+        assert!(stream.contains(" re S\n"), "missing box stroke: {}", stream);
+        assert_eq!(stream.matches(" l ").count(), 2, "missing check stroke: {}", stream);
+        assert!(stream.contains("(Ship it) Tj"));
+        assert!(!stream.contains("[x]"));
+    }
+
+    #[test]
+    fn test_list_continuation_indents_under_item_text() {
+        let elements = crate::elements::parse_markdown("- First item\n\n  More detail\n- Second");
+        let mut builder = ContentStreamBuilder::new(12.0, false, PageLayout::portrait());
+        let margin = builder.layout.margin_left;
+        render_elements_to_builder(&mut builder, &elements, 12.0);
+        let stream = String::from_utf8_lossy(&builder.finish().concat()).to_string();
+
+        let x_of = |text: &str| -> f32 {
+            let show = stream.find(&format!("({}) Tj", text)).expect(text);
+            let tm = stream[..show].rfind("1 0 0 1 ").unwrap();
+            stream[tm + 8..].split_whitespace().next().unwrap().parse().unwrap()
+        };
+        let text_x = margin + crate::font_metrics::text_width(FONT_HELVETICA, "• ", 12.0);
+        assert_eq!(x_of("• First item"), margin);
+        assert_eq!(x_of("More detail"), text_x);
+        assert_eq!(x_of("• Second"), margin);
+    }
+
+    #[test]
+    fn test_code_font_replaces_courier() {
+        let font = crate::truetype::TrueTypeFont::from_bytes(crate::truetype::tests::sample_font()).unwrap();
+        let options = RenderOptions { code_font: Some(font), code_line_numbers: true, ..Default::default() };
+        let elements = crate::elements::parse_markdown("```\nlet x = 1;\n```");
+        let bytes = generate_pdf_bytes_with_render_options(&elements, "Helvetica", 12.0, PageLayout::portrait(), &options).unwrap();
+
+        let doc = crate::pdf::PdfDocument::from_bytes(&bytes).unwrap();
+        let content = String::from_utf8_lossy(&doc.page_content_streams(0).concat()).to_string();
+        assert!(content.contains("/TestMono 10.2"), "{}", content);
+        assert!(!content.contains("/Courier"));
+        let fonts = doc.fonts();
+        let code_font = fonts.iter().find(|f| f.base_font == "TestMono").expect("code font resource");
+        assert!(code_font.embedded);
+        // The gutter holds one 0.6 em digit plus 8pt padding: 72 + 6.12 + 8
+        assert!(content.contains("1 0 0 1 86.12 704 Tm\n(let x = ) Tj"), "{}", content);
+    }
+
+    #[test]
+    fn test_code_font_ligatures_show_ligature_glyph() {
+        let font = crate::truetype::TrueTypeFont::from_bytes(crate::truetype::tests::sample_ligature_font())
+            .unwrap()
+            .with_ligatures(true);
+        let options = RenderOptions { code_font: Some(font), ..Default::default() };
+        let elements = crate::elements::parse_markdown("```\nfix\n```");
+        let bytes = generate_pdf_bytes_with_render_options(&elements, "Helvetica", 12.0, PageLayout::portrait(), &options).unwrap();
+        assert!(crate::pdf::validate_pdf_bytes(&bytes).valid);
+
+        let doc = crate::pdf::PdfDocument::from_bytes(&bytes).unwrap();
+        let content = String::from_utf8_lossy(&doc.page_content_streams(0).concat()).to_string();
+        // "fi" becomes glyph 96, "x" keeps its own glyph 89
+        assert!(content.contains("<00600059> Tj"), "{}", content);
+        let raw = String::from_utf8_lossy(&bytes);
+        assert!(raw.contains("/Subtype /Type0\n/BaseFont /TestMono\n/Encoding /Identity-H"));
+        assert!(raw.contains("<0060> <00660069>"), "ligature maps back to \"fi\"");
+    }
+
+    #[test]
+    fn test_list_indent_is_points_per_level() {
+        let elements = crate::elements::parse_markdown("- Zero\n    - Two\n\n      Detail");
+        assert!(matches!(elements[1], Element::UnorderedListItem { depth: 2, .. }));
+        let mut builder = ContentStreamBuilder::new(12.0, false, PageLayout::portrait());
+        builder.options.list_indent = 18.0;
+        render_elements_to_builder(&mut builder, &elements, 12.0);
+        let stream = String::from_utf8_lossy(&builder.finish().concat()).to_string();
+
+        let x_of = |text: &str| -> f32 {
+            let show = stream.find(&format!("({}) Tj", text)).expect(text);
+            let tm = stream[..show].rfind("1 0 0 1 ").unwrap();
+            stream[tm + 8..].split_whitespace().next().unwrap().parse().unwrap()
+        };
+        assert_eq!(x_of("• Two") - x_of("• Zero"), 36.0);
+        let bullet = crate::font_metrics::text_width(FONT_HELVETICA, "• ", 12.0);
+        assert!((x_of("Detail") - (x_of("• Two") + bullet)).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_drop_cap_after_heading() {
+        let words = "Lorem ipsum dolor sit amet consectetur adipiscing elit ".repeat(8);
+        let elements = vec![
+            Element::Heading { level: 2, text: "Chapter".into() },
+            Element::EmptyLine,
+            Element::Paragraph { text: words.trim().to_string() },
+            Element::Paragraph { text: "Second paragraph".into() },
+        ];
+        let mut builder = ContentStreamBuilder::new(12.0, false, PageLayout::portrait());
+        builder.options.drop_cap_lines = 3;
+        let margin = builder.layout.margin_left;
+        render_elements_to_builder(&mut builder, &elements, 12.0);
+        let stream = String::from_utf8_lossy(&builder.finish().concat()).to_string();
+
+        let cap = stream.find("(L) Tj").expect("drop cap glyph");
+        let font = stream[..cap].rfind(" Tf").unwrap();
+        let size: f32 = stream[..font].rsplit(' ').next().unwrap().parse().unwrap();
+        assert!(size > 30.0, "drop cap size {}", size);
+
+        // Body lines after the cap: the first three are inset, then text returns to the margin
+        let xs: Vec<f32> = stream[cap..]
+            .match_indices("1 0 0 1 ")
+            .map(|(i, _)| stream[cap + i + 8..].split(' ').next().unwrap().parse().unwrap())
+            .collect();
+        assert!(xs.len() > 4, "{}", stream);
+        assert!(xs[..3].iter().all(|&x| x > margin + 20.0), "{:?}", xs);
+        assert!(xs[3..].iter().all(|&x| x == margin), "{:?}", xs);
+        assert!(stream[cap..].contains("(orem ipsum"));
+        assert!(stream.contains("(Second paragraph) Tj"));
+    }
+
+    #[test]
+    fn test_image_caption_renders_as_figure() {
+        let elements = crate::elements::parse_markdown(r#"![x](a.png "Figure 1")"#);
+        let mut builder = ContentStreamBuilder::new(12.0, false, PageLayout::portrait());
+        let margin = builder.layout.margin_left;
+        render_elements_to_builder(&mut builder, &elements, 12.0);
+        let stream = String::from_utf8_lossy(&builder.finish().concat()).to_string();
+
+        let position = |show: usize| -> (f32, f32) {
+            let tm = stream[..show].rfind("1 0 0 1 ").unwrap();
+            let mut nums = stream[tm + 8..].split(' ').map(|n| n.parse::<f32>().unwrap());
+            (nums.next().unwrap(), nums.next().unwrap())
+        };
+        let placeholder = stream.find("([Image: x] \\(a.png\\)) Tj").expect("placeholder");
+        let caption = stream.find("(Figure 1) Tj").expect("caption");
+        let (placeholder_x, placeholder_y) = position(placeholder);
+        let (caption_x, caption_y) = position(caption);
+        assert!(placeholder_x > margin && caption_x > placeholder_x, "not centered: {}", stream);
+        assert!(caption_y < placeholder_y);
+        // Smaller italic caption
+        let font = &stream[stream[..caption].rfind('/').unwrap()..caption];
+        assert!(font.starts_with(&format!("/{} 10.2", FONT_HELVETICA_OBLIQUE)), "{}", font);
+    }
+
+    #[test]
+    fn test_raw_content_is_isolated_at_cursor() {
+        let raw = b"1 0 0 RG 4 w 0 0 m 100 -20 l S".to_vec();
+        let elements = crate::elements::DocumentBuilder::new()
+            .paragraph("Before")
+            .raw_content(&raw)
+            .paragraph("After")
+            .build();
+        let mut builder = ContentStreamBuilder::new(12.0, false, PageLayout::portrait());
+        render_elements_to_builder(&mut builder, &elements, 12.0);
+        let stream = String::from_utf8_lossy(&builder.finish().concat()).to_string();
+
+        let start = stream.find("1 0 0 RG 4 w 0 0 m 100 -20 l S").expect("raw content");
+        let before = &stream[..start];
+        let block_start = before.rfind("q\n").unwrap();
+        assert!(before[..block_start].ends_with("ET\n"), "{}", stream);
+        assert!(before[block_start..].contains(" cm\n"));
+        assert!(stream[start..].starts_with("1 0 0 RG 4 w 0 0 m 100 -20 l S\nQ\nBT\n"), "{}", stream);
+        assert!(stream.find("(After) Tj").unwrap() > start);
+    }
+
+    #[test]
+    fn test_superscript_uses_text_rise() {
+        let elements = vec![Element::RichParagraph {
+            segments: crate::elements::parse_inline_formatting("x^2^"),
+        }];
+        let mut builder = ContentStreamBuilder::new(12.0, true, PageLayout::portrait());
+        render_elements_to_builder(&mut builder, &elements, 12.0);
+        let stream = String::from_utf8_lossy(&builder.finish().concat()).to_string();
+
+        let rise = stream.find("4.2 Ts").expect("positive text rise");
+        let small_font = stream[..rise].rfind(&format!("/{} ", FONT_HELVETICA)).unwrap();
+        let size: f32 = stream[small_font..].split_whitespace().nth(1).unwrap().parse().unwrap();
+        assert!(size < 12.0, "superscript size {}", size);
+        assert!(stream[rise..].contains("0 Ts"));
+    }
+
+    #[test]
+    fn test_numbered_display_math_is_centered() {
+        let elements = vec![
+            Element::MathBlock { expression: "a + b".into() },
+            Element::MathBlock { expression: "c".into() },
+        ];
+        let options = RenderOptions { display_math: DisplayMathStyle::numbered(), ..Default::default() };
+        let layout = PageLayout::portrait();
+        let (pages, _) = render_document(&elements, 12.0, layout, &options, &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+
+        let lines: Vec<&str> = stream.lines().collect();
+        let x_of = |shown: &str| -> f32 {
+            let at = lines.iter().position(|l| *l == shown).unwrap_or_else(|| panic!("{} in {}", shown, stream));
+            let tm = lines[..at].iter().rev().find(|l| l.ends_with(" Tm")).unwrap();
+            tm.split_whitespace().nth(4).unwrap().parse().unwrap()
+        };
+        let size = 12.0 * 1.1;
+        let math = crate::math::layout_math("c", FONT_HELVETICA_OBLIQUE, size, true);
+        let center = layout.margin_left + layout.content_width() / 2.0;
+        assert!((x_of("(c) Tj") + math.width / 2.0 - center).abs() < 0.01, "{}", stream);
+        // Numbers count the blocks and end at the right margin
+        let right = layout.margin_left + layout.content_width();
+        let number_width = crate::font_metrics::text_width(FONT_HELVETICA, "(1)", size);
+        assert!((x_of("(\\(1\\)) Tj") + number_width - right).abs() < 0.01, "{}", stream);
+        assert!(stream.contains("(\\(2\\)) Tj"));
+
+        // Left-aligned and unnumbered by default
+        let (pages, _) = render_document(&elements, 12.0, layout, &RenderOptions::default(), &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+        assert!(!stream.contains("\\(1\\)"));
+    }
+
+    #[test]
+    fn test_align_environment_renders_one_line_per_row() {
+        let expression = "\\begin{align}\na &= b + c \\\\\nd &= e\n\\end{align}";
+        let elements = vec![Element::MathBlock { expression: expression.into() }];
+        let (pages, _) = render_document(&elements, 12.0, PageLayout::portrait(), &RenderOptions::default(), &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+        assert!(!stream.contains("begin") && !stream.contains("align") && !stream.contains('&'), "{}", stream);
+
+        let lines: Vec<&str> = stream.lines().collect();
+        let baseline_of = |shown: &str| -> f32 {
+            let at = lines.iter().position(|l| *l == shown).unwrap_or_else(|| panic!("{} in {}", shown, stream));
+            let tm = lines[..at].iter().rev().find(|l| l.ends_with(" Tm")).unwrap();
+            tm.split_whitespace().nth(5).unwrap().parse().unwrap()
+        };
+        assert!(baseline_of("(= b + c) Tj") > baseline_of("(= e) Tj"), "{}", stream);
+    }
+
+    #[test]
+    fn test_math_fraction_stacks_over_rule() {
+        let elements = vec![Element::MathBlock { expression: r"\frac{a}{b}".into() }];
+        let (pages, _) = render_document(&elements, 12.0, PageLayout::portrait(), &RenderOptions::default(), &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+        assert!(!stream.contains("(a)/(b)"), "{}", stream);
+
+        let lines: Vec<&str> = stream.lines().collect();
+        let shown_at = |glyph: &str| -> f32 {
+            let at = lines.iter().position(|l| *l == glyph).unwrap_or_else(|| panic!("{} in {}", glyph, stream));
+            let tm = lines[..at].iter().rev().find(|l| l.ends_with(" Tm")).unwrap();
+            tm.split_whitespace().nth(5).unwrap().parse().unwrap()
+        };
+        let (numerator, denominator) = (shown_at("(a) Tj"), shown_at("(b) Tj"));
+        // The bar is the one stroked line between the two baselines
+        let rule_y: f32 = lines
+            .iter()
+            .filter(|l| l.ends_with(" l S"))
+            .map(|l| l.split_whitespace().nth(1).unwrap().parse().unwrap())
+            .find(|y: &f32| *y < numerator && *y > denominator)
+            .unwrap_or_else(|| panic!("no rule between {} and {} in {}", numerator, denominator, stream));
+        assert!(numerator - rule_y > 0.0 && rule_y - denominator > 12.0 * 0.5, "{} {} {}", numerator, rule_y, denominator);
+
+        // Scripts are raised with the text rise; roots draw the radical glyph
+        let elements = vec![Element::MathInline { expression: r"x^{2} + \sqrt{y}".into() }];
+        let (pages, _) = render_document(&elements, 12.0, PageLayout::portrait(), &RenderOptions::default(), &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+        assert!(stream.contains(" Ts\n(2) Tj\n0 Ts"), "{}", stream);
+        assert!(stream.contains("/Symbol 12 Tf\n(\\326) Tj"), "{}", stream);
+        assert!(!stream.contains("sqrt"));
+    }
+
+    #[test]
+    fn test_wrapped_link_gets_one_annotation_per_line() {
+        let url = "https://example.com/docs";
+        let text = "a link whose label is long enough to wrap onto a second line of the page";
+        let elements = vec![Element::Link { text: text.into(), url: url.into() }];
+        let bytes = generate_pdf_bytes_with_render_options(
+            &elements, "Helvetica", 12.0, PageLayout::portrait(), &RenderOptions::default(),
+        ).unwrap();
+        assert!(crate::pdf::validate_pdf_bytes(&bytes).valid);
+
+        let pdf = String::from_utf8_lossy(&bytes).to_string();
+        let rects: Vec<Vec<f32>> = pdf
+            .split("/Subtype /Link\n/Rect [")
+            .skip(1)
+            .map(|rest| rest[..rest.find(']').unwrap()].split(' ').map(|n| n.parse().unwrap()).collect())
+            .collect();
+        assert_eq!(rects.len(), 2, "{}", pdf);
+        assert_eq!(pdf.matches(&format!("/URI ({})", url)).count(), 2);
+        // The second line's rectangle sits below the first
+        assert!(rects[1][3] <= rects[0][1], "{:?}", rects);
+        assert!(pdf.contains("/Annots ["));
+    }
+
+    #[test]
+    fn test_footnote_links_list_urls_at_end() {
+        let elements = crate::elements::parse_markdown(
+            "See [the docs](https://example.com/docs) and [the repo](https://example.com/repo).\n\nAgain [the docs](https://example.com/docs).",
+        );
+        let options = RenderOptions { link_mode: LinkRenderMode::Footnote, ..Default::default() };
+        let (pages, _) = render_document(&elements, 12.0, PageLayout::portrait(), &options, &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+
+        assert!(stream.contains("(the docs [1]) Tj"), "{}", stream);
+        assert!(stream.contains("(the repo [2]) Tj"), "{}", stream);
+        assert!(!stream.contains("(the docs (https://example.com/docs))"), "{}", stream);
+        let first_note = stream.find("([1] https://example.com/docs) Tj").unwrap_or_else(|| panic!("{}", stream));
+        let second_note = stream.find("([2] https://example.com/repo) Tj").unwrap();
+        assert!(stream.rfind("the docs [1]").unwrap() < first_note && first_note < second_note);
+        // A repeated URL reuses its number
+        assert_eq!(stream.matches("https://example.com/docs").count(), 1);
+    }
+
+    #[test]
+    fn test_bar_chart_heights_are_proportional() {
+        let elements = crate::elements::DocumentBuilder::new()
+            .chart(crate::elements::ChartKind::Bar, "Sales", &[("Q1", 10.0), ("Q2", 20.0), ("Q3", 40.0)])
+            .build();
+        let (pages, _) = render_document(&elements, 12.0, PageLayout::portrait(), &RenderOptions::default(), &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+
+        let fill = format!("{}\n", CHART_COLOR.fill_operator());
+        let heights: Vec<f32> = stream
+            .match_indices(&fill)
+            .map(|(at, _)| {
+                let rect = stream[at + fill.len()..].lines().next().unwrap();
+                assert!(rect.ends_with(" re f"), "{}", stream);
+                rect.split(' ').nth(3).unwrap().parse().unwrap()
+            })
+            .collect();
+        assert_eq!(heights.len(), 3, "{}", stream);
+        assert!((heights[1] / heights[0] - 2.0).abs() < 0.01, "{:?}", heights);
+        assert!((heights[2] / heights[0] - 4.0).abs() < 0.01, "{:?}", heights);
+        for label in ["(Sales) Tj", "(Q1) Tj", "(Q3) Tj", "(40) Tj"] {
+            assert!(stream.contains(label), "{} missing from {}", label, stream);
+        }
+
+        // Values that are not finite are skipped rather than breaking the scale
+        for kind in [crate::elements::ChartKind::Bar, crate::elements::ChartKind::Line] {
+            let data = [("A", 10.0), ("B", f64::INFINITY), ("C", f64::NAN), ("D", 40.0), ("E", f64::NEG_INFINITY)];
+            let elements = crate::elements::DocumentBuilder::new().chart(kind, "Odd", &data).build();
+            let (pages, _) = render_document(&elements, 12.0, PageLayout::portrait(), &RenderOptions::default(), &mut |_| {});
+            let stream = String::from_utf8_lossy(&pages[0]).to_string();
+            assert!(!stream.contains("NaN") && !stream.contains("inf"), "{}", stream);
+            assert!(stream.contains("(40) Tj") && stream.contains("(C) Tj"), "{}", stream);
+            if kind == crate::elements::ChartKind::Bar {
+                assert_eq!(stream.matches(" re f").count(), 2, "{}", stream);
+            }
+        }
+    }
+
+    #[test]
+    fn test_running_header_follows_latest_heading() {
+        let elements = crate::elements::parse_markdown(
+            "# Chapter 1\n\nIntro.\n\n\\pagebreak\n\n# Chapter 2\n\nBody.\n\n\\pagebreak\n\nMore body.\n",
+        );
+        let options = RenderOptions { running_header: true, ..Default::default() };
+        let layout = PageLayout::portrait();
+        let (pages, _) = render_document(&elements, 12.0, layout, &options, &mut |_| {});
+        assert_eq!(pages.len(), 3);
+
+        let head_y = format!(" {} Tm\n", layout.height - layout.margin_top / 2.0);
+        let head_of = |page: &[u8]| {
+            let stream = String::from_utf8_lossy(page).to_string();
+            let at = stream.find(&head_y)?;
+            let shown = &stream[at + head_y.len()..];
+            Some(shown[..shown.find('\n').unwrap()].to_string())
+        };
+        assert_eq!(head_of(&pages[0]), None);
+        assert_eq!(head_of(&pages[1]).as_deref(), Some("(Chapter 1) Tj"));
+        assert_eq!(head_of(&pages[2]).as_deref(), Some("(Chapter 2) Tj"));
+    }
+
+    #[test]
+    fn test_left_float_wraps_text_beside_image() {
+        let mut markdown = String::from("![chart](chart.png){float=left}\n\n");
+        markdown.push_str(&"Text flows beside the floated image and then returns to the margin. ".repeat(12));
+        let elements = crate::elements::parse_markdown(&markdown);
+        assert!(matches!(elements[0], Element::Image { float: ImageFloat::Left, .. }));
+
+        let images = HashMap::from([("chart.png".to_string(), InlineImage { name: "Im1".into(), width: 120.0, height: 90.0 })]);
+        let layout = PageLayout::portrait();
+        let (pages, _, _) =
+            render_document_with_images(&elements, 12.0, layout, &RenderOptions::default(), images, &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+        assert!(stream.contains("/Im1 Do"), "{}", stream);
+
+        let image_top = layout.content_top() + 12.0 * 0.78;
+        let image_bottom = image_top - 90.0;
+        let lines: Vec<(f32, f32)> = stream
+            .lines()
+            .filter_map(|l| l.strip_prefix("1 0 0 1 ")?.strip_suffix(" Tm"))
+            .map(|xy| {
+                let mut parts = xy.split(' ').map(|n| n.parse::<f32>().unwrap());
+                (parts.next().unwrap(), parts.next().unwrap())
+            })
+            .collect();
+        let beside: Vec<_> = lines.iter().filter(|(_, y)| *y > image_bottom).collect();
+        let below: Vec<_> =
+            lines.iter().filter(|(_, y)| *y < image_bottom - 12.0 && *y > layout.margin_bottom).collect();
+        assert!(beside.len() >= 5, "{:?}", lines);
+        assert!(beside.iter().all(|(x, _)| *x >= layout.margin_left + 120.0), "{:?}", lines);
+        assert!(!below.is_empty() && below.iter().all(|(x, _)| *x == layout.margin_left), "{:?}", lines);
+    }
+
+    #[test]
+    fn test_table_in_list_item_is_indented_to_item_text() {
+        let table = "| A | B |\n|---|---|\n| 1 | 2 |\n";
+        let nested = format!("- Item\n\n{}\nAfter\n", table.lines().map(|l| format!("  {}\n", l)).collect::<String>());
+        let elements = crate::elements::parse_markdown(&nested);
+        assert!(matches!(elements[2], Element::TableRow { list_depth: Some(0), .. }), "{:?}", elements);
+
+        let cell_x = |elements: &[Element]| {
+            let (pages, _) = render_document(elements, 12.0, PageLayout::portrait(), &RenderOptions::default(), &mut |_| {});
+            let stream = String::from_utf8_lossy(&pages[0]).to_string();
+            let before = &stream[..stream.find("(A) Tj").unwrap()];
+            let tm = before.lines().rev().find_map(|l| l.strip_prefix("1 0 0 1 ")).unwrap().to_string();
+            tm.split(' ').next().unwrap().parse::<f32>().unwrap()
+        };
+        let top_level = crate::elements::parse_markdown(table);
+        assert!(matches!(top_level[0], Element::TableRow { list_depth: None, .. }));
+        let bullet = crate::font_metrics::text_width(FONT_HELVETICA, "• ", 12.0);
+        assert!((cell_x(&elements) - cell_x(&top_level) - bullet).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_outlined_heading_sets_render_mode() {
+        let elements = crate::elements::parse_markdown("# Display\n\nBody text.");
+        let options = RenderOptions {
+            heading_text_style: Some(TextStyle::outlined(Color::blue(), 0.75)),
+            ..Default::default()
+        };
+        let (pages, _) = render_document(&elements, 12.0, PageLayout::portrait(), &options, &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+
+        let styled = stream
+            .find("ET\nq\nBT\n0 0 1 RG\n0.75 w\n2 Tr\n")
+            .unwrap_or_else(|| panic!("{}", stream));
+        // The outline's state is restored with the graphics state after the run,
+        // and body text goes back to filled glyphs in the default stroke
+        let restored = styled + stream[styled..].find("(Display) Tj\nET\nQ\nBT\n").unwrap_or_else(|| panic!("{}", stream));
+        assert!(stream[restored..].contains("(Body text.) Tj"));
+        assert_eq!(stream.matches(" Tr\n").count(), 1, "{}", stream);
+        assert_eq!(stream.matches(" w\n").count(), 1, "{}", stream);
+
+        // Runs of a rich paragraph can have a rendering mode of their own
+        let hidden = TextStyle { render_mode: TextRenderMode::Invisible, stroke: Color::black(), line_width: 1.0 };
+        let elements = vec![Element::RichParagraph {
+            segments: vec![
+                TextSegment::Plain("Seen".into()),
+                TextSegment::Styled("searchable".into(), hidden),
+                TextSegment::Plain("again".into()),
+            ],
+        }];
+        let (pages, _) = render_document(&elements, 12.0, PageLayout::portrait(), &RenderOptions::default(), &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+        let run = stream.find("3 Tr\n").unwrap_or_else(|| panic!("{}", stream));
+        assert!(stream[run..].starts_with("3 Tr\n1 0 0 1 "), "{}", stream);
+        let end = run + stream[run..].find("(searchable) Tj\nET\nQ\nBT\n").unwrap_or_else(|| panic!("{}", stream));
+        assert!(stream[end..].contains("(again) Tj") && !stream[..run].contains("(again)"));
+    }
+
+    #[test]
+    fn test_highlight_fills_behind_text() {
+        let elements = crate::elements::parse_markdown("plain ==x==");
+        let (pages, _) = render_document(&elements, 12.0, PageLayout::portrait(), &RenderOptions::default(), &mut |_| {});
+        let stream = String::from_utf8_lossy(&pages[0]).to_string();
+        let fill = stream.find("1 1 0 rg\n").unwrap_or_else(|| panic!("{}", stream));
+        let rect = fill + stream[fill..].find(" re f\n").unwrap();
+        let text = stream.find("(x) Tj").unwrap();
+        assert!(rect < text, "{}", stream);
+
+        // The rectangle is as wide as the glyph and starts where it is drawn
+        let rect_line = stream[..rect].rsplit('\n').next().unwrap();
+        let operands: Vec<f32> = rect_line.split(' ').map(|n| n.parse().unwrap()).collect();
+        let width = crate::font_metrics::text_width(FONT_HELVETICA, "x", 12.0);
+        assert_eq!(operands[2], width);
+        let tm = stream[..text].rsplit(" Tm").nth(1).unwrap().rsplit('\n').next().unwrap();
+        assert_eq!(tm.split(' ').nth(4).unwrap().parse::<f32>().unwrap(), operands[0]);
+        assert!(!stream[rect..].contains("1 1 0 rg"));
+    }
+
+    #[test]
+    fn test_srgb_color_space_is_default_rgb() {
+        let elements = crate::elements::parse_markdown("# Title\n\nBody");
+        let render = |options: &RenderOptions| {
+            generate_pdf_bytes_with_render_options(&elements, "Helvetica", 12.0, PageLayout::portrait(), options).unwrap()
+        };
+        let plain = String::from_utf8_lossy(&render(&RenderOptions::default())).to_string();
+        assert!(!plain.contains("/ICCBased"));
+
+        let bytes = render(&RenderOptions { srgb_color_space: true, ..Default::default() });
+        assert!(crate::pdf::validate_pdf_bytes(&bytes).valid);
+        let pdf = String::from_utf8_lossy(&bytes).to_string();
+        let space = pdf.find("[/ICCBased ").expect("ICCBased color space object");
+        let profile_ref = pdf[space + 11..].split(" 0 R").next().unwrap();
+        assert!(pdf.contains(&format!("{} 0 obj\n<< /N 3\n/Alternate /DeviceRGB", profile_ref)), "{}", profile_ref);
+        assert!(pdf.contains("/ColorSpace << /DefaultRGB "));
+    }
+}
+
 #[cfg(test)]
 mod accessibility_tests {
     use super::*;
@@ -1832,6 +5843,22 @@ mod accessibility_tests {
         assert_eq!(opts.title, Some("My Document".to_string()));
     }
 
+    #[test]
+    fn test_catalog_language_and_title() {
+        let elements = vec![Element::Paragraph { text: "Hello".into() }];
+        let opts = AccessibilityOptions::new()
+            .with_language("en-US".to_string())
+            .with_title("Report".to_string());
+        let bytes =
+            generate_pdf_bytes_with_accessibility(&elements, "Helvetica", 12.0, PageLayout::portrait(), &opts)
+                .unwrap();
+        let pdf = String::from_utf8_lossy(&bytes);
+        assert!(pdf.contains("/Lang (en-US)"));
+        assert!(pdf.contains("/DisplayDocTitle true"));
+        assert!(pdf.contains("/Title (Report)"));
+        assert!(pdf.contains("/Info "));
+    }
+
     #[test]
     fn test_structure_type_names() {
         assert_eq!(StructureType::Document.as_pdf_name(), "Document");
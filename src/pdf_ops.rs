@@ -3,7 +3,7 @@
 //! This module provides high-level operations for manipulating PDF documents,
 //! including merging, splitting, rotating, watermarking, and annotations.
 
-use anyhow::{anyhow, Result};
+use crate::error::{PdfError, Result};
 use std::fs;
 use serde::{Serialize, Deserialize};
 
@@ -39,28 +39,53 @@ use serde::{Serialize, Deserialize};
 /// - Any input file cannot be read or parsed
 /// - No page content is found in any input file
 pub fn merge_pdfs(input_files: &[&str], output_file: &str) -> Result<()> {
+    merge_pdfs_with_progress(input_files, output_file, &mut |_| {})
+}
+
+/// Like [`merge_pdfs`], reporting progress after each input file is loaded
+pub fn merge_pdfs_with_progress(
+    input_files: &[&str],
+    output_file: &str,
+    progress: &mut dyn FnMut(crate::pdf_generator::Progress),
+) -> Result<()> {
     if input_files.is_empty() {
-        return Err(anyhow!("No input files provided for merge"));
+        return Err(PdfError::InvalidInput("No input files provided for merge".into()));
     }
 
     let mut all_page_streams: Vec<Vec<u8>> = Vec::new();
 
-    for path in input_files {
+    for (index, path) in input_files.iter().enumerate() {
         let doc = crate::pdf::PdfDocument::load_from_file(path)?;
+        if !doc.acroform_fields().is_empty() {
+            // Rebuilt pages would lose their widgets; copy pages whole instead,
+            // without reporting the files already reported again
+            log::info!("[merge] {} has form fields; copying pages as forms to keep them", path);
+            return merge_form_pages(input_files, output_file, &MergeOptions::default(), &mut |p| {
+                if p.current > index {
+                    progress(p);
+                }
+            });
+        }
         let streams = extract_page_streams(&doc);
         if streams.is_empty() {
-            eprintln!("[merge] Warning: no page streams found in {}", path);
+            log::warn!("[merge] no page streams found in {}", path);
         }
         all_page_streams.extend(streams);
+        progress(crate::pdf_generator::Progress {
+            phase: crate::pdf_generator::ProgressPhase::Merging,
+            current: index + 1,
+            total: input_files.len(),
+            pages: all_page_streams.len(),
+        });
     }
 
     if all_page_streams.is_empty() {
-        return Err(anyhow!("No page content found in any input file"));
+        return Err(PdfError::InvalidInput("No page content found in any input file".into()));
     }
 
     let layout = crate::pdf_generator::PageLayout::portrait();
     assemble_merged_pdf(output_file, &all_page_streams, "Helvetica", &layout)?;
-    println!(
+    log::info!(
         "[merge] Combined {} pages from {} files into {}",
         all_page_streams.len(),
         input_files.len(),
@@ -102,7 +127,7 @@ pub fn merge_pdfs_sequential(
     output_file: &str,
 ) -> Result<()> {
     if documents.is_empty() {
-        return Err(anyhow!("No documents provided for merge"));
+        return Err(PdfError::InvalidInput("No documents provided for merge".into()));
     }
 
     let mut all_page_streams: Vec<Vec<u8>> = Vec::new();
@@ -110,18 +135,18 @@ pub fn merge_pdfs_sequential(
     for doc in documents {
         let streams = extract_page_streams(doc);
         if streams.is_empty() {
-            eprintln!("[merge] Warning: no page streams found in document");
+            log::warn!("[merge] no page streams found in document");
         }
         all_page_streams.extend(streams);
     }
 
     if all_page_streams.is_empty() {
-        return Err(anyhow!("No page content found in any document"));
+        return Err(PdfError::InvalidInput("No page content found in any document".into()));
     }
 
     let layout = crate::pdf_generator::PageLayout::portrait();
     assemble_merged_pdf(output_file, &all_page_streams, "Helvetica", &layout)?;
-    println!(
+    log::info!(
         "[merge] Combined {} pages from {} documents into {}",
         all_page_streams.len(),
         documents.len(),
@@ -130,10 +155,98 @@ pub fn merge_pdfs_sequential(
     Ok(())
 }
 
+/// Options for [`merge_pdfs_with_options`]
+#[derive(Debug, Clone)]
+pub struct MergeOptions {
+    /// Scale every page to fit this size; `None` keeps each page at its native size
+    pub normalize_size: Option<crate::pdf_generator::PageLayout>,
+    /// Copy compressed page content as it is, keeping its `/Filter`; when
+    /// false, Flate-compressed content is stored decoded
+    pub keep_compression: bool,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        MergeOptions { normalize_size: None, keep_compression: true }
+    }
+}
+
+/// Merge PDFs by copying each page as a Form XObject, keeping its resources.
+///
+/// With [`MergeOptions::normalize_size`] set, pages of mismatched sizes are
+/// scaled proportionally and centered on a common page size.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use pdfrs::pdf_ops::{self, MergeOptions};
+/// use pdfrs::pdf_generator::PageLayout;
+///
+/// let options = MergeOptions { normalize_size: Some(PageLayout::a4()), ..Default::default() };
+/// pdf_ops::merge_pdfs_with_options(&["letter.pdf", "a4.pdf"], "merged.pdf", &options)
+///     .expect("Failed to merge PDFs");
+/// ```
+pub fn merge_pdfs_with_options(
+    input_files: &[&str],
+    output_file: &str,
+    options: &MergeOptions,
+) -> Result<()> {
+    merge_form_pages(input_files, output_file, options, &mut |_| {})
+}
+
+/// [`merge_pdfs_with_options`], reporting progress after each input file is copied
+fn merge_form_pages(
+    input_files: &[&str],
+    output_file: &str,
+    options: &MergeOptions,
+    progress: &mut dyn FnMut(crate::pdf_generator::Progress),
+) -> Result<()> {
+    if input_files.is_empty() {
+        return Err(PdfError::InvalidInput("No input files provided for merge".into()));
+    }
+
+    let mut generator = crate::pdf_generator::PdfGenerator::new();
+    let mut forms = FormCollector::default();
+    let mut pages = Vec::new();
+    for (index, path) in input_files.iter().enumerate() {
+        let mut doc = crate::pdf::PdfDocument::load_from_file(path)?;
+        if doc.pages.is_empty() {
+            log::warn!("[merge] no pages found in {}", path);
+        }
+        let indices: Vec<usize> = (0..doc.pages.len()).collect();
+        pages.extend(import_pages_with_fields(&mut doc, &indices, options, &mut generator, &mut forms)?);
+        progress(crate::pdf_generator::Progress {
+            phase: crate::pdf_generator::ProgressPhase::Merging,
+            current: index + 1,
+            total: input_files.len(),
+            pages: pages.len(),
+        });
+    }
+
+    if pages.is_empty() {
+        return Err(PdfError::InvalidInput("No page content found in any input file".into()));
+    }
+
+    let layout = options
+        .normalize_size
+        .unwrap_or_else(crate::pdf_generator::PageLayout::portrait);
+    let page_count = pages.len();
+    let acroform = forms.acroform();
+    fs::write(output_file, assemble_form_pages(generator, &pages, &layout, acroform.as_deref()))?;
+    log::info!(
+        "[merge] Combined {} pages from {} files into {}",
+        page_count,
+        input_files.len(),
+        output_file
+    );
+    Ok(())
+}
+
 /// Split a PDF by extracting a range of pages into a new PDF.
 ///
 /// Extracts pages from `start` to `end` (inclusive, 1-indexed) and creates
-/// a new PDF containing only those pages.
+/// a new PDF containing only those pages. Pages are copied whole, with their
+/// resources, size and any form fields on them.
 ///
 /// # Arguments
 ///
@@ -157,44 +270,93 @@ pub fn merge_pdfs_sequential(
 /// ```
 pub fn split_pdf(input_file: &str, output_file: &str, start: usize, end: usize) -> Result<()> {
     if start == 0 || end == 0 || start > end {
-        return Err(anyhow!(
-            "Invalid page range: start={} end={} (1-indexed, inclusive)",
-            start,
-            end
-        ));
+        return Err(PdfError::InvalidPageRange { start, end, page_count: None });
     }
 
     let doc = crate::pdf::PdfDocument::load_from_file(input_file)?;
-    let all_streams = extract_page_streams(&doc);
-    let total = all_streams.len();
-
+    let total = page_count(&doc);
     if total == 0 {
-        return Err(anyhow!("No pages found in {}", input_file));
+        return Err(PdfError::InvalidInput(format!("No pages found in {}", input_file)));
     }
     if start > total {
-        return Err(anyhow!(
-            "Start page {} exceeds total pages {}",
-            start,
-            total
-        ));
+        return Err(PdfError::InvalidPageRange { start, end, page_count: Some(total) });
     }
 
     let actual_end = end.min(total);
-    let selected: Vec<Vec<u8>> = all_streams[(start - 1)..actual_end].to_vec();
-
-    let layout = crate::pdf_generator::PageLayout::portrait();
-    assemble_merged_pdf(output_file, &selected, "Helvetica", &layout)?;
-    println!(
-        "[split] Extracted pages {}-{} ({} pages) from {} into {}",
+    let indices: Vec<usize> = (start - 1..actual_end).collect();
+    let fields = write_selected_pages(doc, &indices, output_file)?;
+    log::info!(
+        "[split] Extracted pages {}-{} ({} pages, {} form fields) from {} into {}",
         start,
         actual_end,
-        selected.len(),
+        indices.len(),
+        fields,
         input_file,
         output_file
     );
     Ok(())
 }
 
+/// Write every page of `input_file` to its own PDF in `output_dir`.
+///
+/// Files are named `page_001.pdf`, `page_002.pdf`, ... (more digits when there
+/// are over 999 pages). Each page is copied whole with its resources, MediaBox
+/// and any form fields on it. Returns the paths written, in page order.
+pub fn burst_pdf(input_file: &str, output_dir: &str) -> Result<Vec<String>> {
+    let mut doc = crate::pdf::PdfDocument::load_from_file(input_file)?;
+    if doc.pages.is_empty() {
+        return Err(PdfError::InvalidInput(format!("No pages found in {}", input_file)));
+    }
+    fs::create_dir_all(output_dir)?;
+
+    let digits = doc.pages.len().to_string().len().max(3);
+    let layout = crate::pdf_generator::PageLayout::portrait();
+    let mut written = Vec::new();
+    for index in 0..doc.pages.len() {
+        // Each page is copied into a document of its own; only the objects it uses come along
+        let mut generator = crate::pdf_generator::PdfGenerator::new();
+        let mut forms = FormCollector::default();
+        let pages = import_pages_with_fields(&mut doc, &[index], &MergeOptions::default(), &mut generator, &mut forms)?;
+        let acroform = forms.acroform();
+        let path = std::path::Path::new(output_dir).join(format!("page_{:0width$}.pdf", index + 1, width = digits));
+        fs::write(&path, assemble_form_pages(generator, &pages, &layout, acroform.as_deref()))?;
+        written.push(path.to_string_lossy().into_owned());
+    }
+    log::info!("[burst] Wrote {} single-page files from {} into {}", written.len(), input_file, output_dir);
+    Ok(written)
+}
+
+/// Number of pages in `doc`; documents without a readable page tree count
+/// the content streams [`extract_page_streams`] finds
+fn page_count(doc: &crate::pdf::PdfDocument) -> usize {
+    if doc.pages.is_empty() {
+        extract_page_streams(doc).len()
+    } else {
+        doc.pages.len()
+    }
+}
+
+/// Write pages `indices` (0-based, in output order) of `doc` to `output_file`.
+/// Each page is copied as a form with its resources and size, along with the
+/// form fields that have a widget on it; returns the number of fields copied.
+/// Documents without a readable page tree only have their content streams copied.
+fn write_selected_pages(mut doc: crate::pdf::PdfDocument, indices: &[usize], output_file: &str) -> Result<usize> {
+    let layout = crate::pdf_generator::PageLayout::portrait();
+    if doc.pages.is_empty() {
+        let streams = extract_page_streams(&doc);
+        let selected: Vec<Vec<u8>> = indices.iter().map(|&i| streams[i].clone()).collect();
+        assemble_merged_pdf(output_file, &selected, "Helvetica", &layout)?;
+        return Ok(0);
+    }
+
+    let mut generator = crate::pdf_generator::PdfGenerator::new();
+    let mut forms = FormCollector::default();
+    let pages = import_pages_with_fields(&mut doc, indices, &MergeOptions::default(), &mut generator, &mut forms)?;
+    let acroform = forms.acroform();
+    fs::write(output_file, assemble_form_pages(generator, &pages, &layout, acroform.as_deref()))?;
+    Ok(forms.fields.len())
+}
+
 /// Document metadata.
 ///
 /// Represents standard PDF document metadata fields including title, author,
@@ -219,15 +381,151 @@ pub fn split_pdf(input_file: &str, output_file: &str, start: usize, end: usize)
 /// metadata.author = Some("John Doe".to_string());
 /// metadata.add_custom_field("Version".to_string(), "1.0".to_string());
 /// ```
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct PdfMetadata {
     pub title: Option<String>,
     pub author: Option<String>,
     pub subject: Option<String>,
     pub keywords: Option<String>,
     pub creator: Option<String>,
+    /// Whether the document has been trapped for printing
+    pub trapped: Option<Trapped>,
     /// Custom metadata fields (key-value pairs)
     pub custom_fields: std::collections::HashMap<String, String>,
+    /// Written as `/CreationDate`
+    pub creation_date: Option<PdfDate>,
+    /// Written as `/ModDate`
+    pub mod_date: Option<PdfDate>,
+}
+
+/// A timestamp written in the PDF date form `D:YYYYMMDDHHmmSS`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PdfDate {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    /// Offset from UTC in minutes; `None` leaves the time zone unspecified
+    pub utc_offset_minutes: Option<i16>,
+}
+
+impl PdfDate {
+    /// The current time in UTC
+    pub fn now() -> Self {
+        let secs = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        // Civil date from days since 1970-01-01 (proleptic Gregorian calendar)
+        let z = secs.div_euclid(86_400) + 719_468;
+        let (era, doe) = (z.div_euclid(146_097), z.rem_euclid(146_097));
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        let time = secs.rem_euclid(86_400);
+        PdfDate {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (time / 3600) as u8,
+            minute: (time / 60 % 60) as u8,
+            second: (time % 60) as u8,
+            utc_offset_minutes: Some(0),
+        }
+    }
+
+    /// `now`, or an ISO 8601 date such as `2024-05-01`, `2024-05-01T09:30`
+    /// or `2024-05-01 09:30:15+02:00`
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("now") {
+            return Some(Self::now());
+        }
+        let number = |s: &str| s.parse::<u16>().ok().filter(|_| s.chars().all(|c| c.is_ascii_digit()));
+        let (date, time) = match value.find(['T', 't', ' ']) {
+            Some(pos) => (&value[..pos], Some(&value[pos + 1..])),
+            None => (value, None),
+        };
+        let mut parts = date.split('-');
+        let (year, month, day) = (number(parts.next()?)?, number(parts.next()?)?, number(parts.next()?)?);
+        if parts.next().is_some() || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return None;
+        }
+        let mut parsed = PdfDate { year, month: month as u8, day: day as u8, hour: 0, minute: 0, second: 0, utc_offset_minutes: None };
+        let Some(time) = time else { return Some(parsed) };
+
+        let zone_start = time.find(['Z', 'z', '+', '-']).unwrap_or(time.len());
+        let (clock, zone) = time.split_at(zone_start);
+        let clock = clock.split('.').next()?;
+        let mut fields = clock.split(':');
+        parsed.hour = number(fields.next()?)? as u8;
+        parsed.minute = number(fields.next()?)? as u8;
+        parsed.second = fields.next().map(number).unwrap_or(Some(0))? as u8;
+        if fields.next().is_some() || parsed.hour > 23 || parsed.minute > 59 || parsed.second > 59 {
+            return None;
+        }
+        parsed.utc_offset_minutes = match zone {
+            "" => None,
+            "Z" | "z" => Some(0),
+            _ => {
+                let sign = if zone.starts_with('-') { -1 } else { 1 };
+                let digits: String = zone[1..].chars().filter(|c| *c != ':').collect();
+                if digits.len() != 4 {
+                    return None;
+                }
+                let (hours, minutes) = (number(&digits[..2])?, number(&digits[2..])?);
+                Some(sign * (hours * 60 + minutes) as i16)
+            }
+        };
+        Some(parsed)
+    }
+
+    /// The date in PDF form, e.g. `D:20240501093000+02'00'`
+    pub fn to_pdf_string(&self) -> String {
+        let zone = match self.utc_offset_minutes {
+            None => String::new(),
+            Some(0) => "Z".to_string(),
+            Some(offset) => format!(
+                "{}{:02}'{:02}'",
+                if offset < 0 { '-' } else { '+' },
+                offset.unsigned_abs() / 60,
+                offset.unsigned_abs() % 60
+            ),
+        };
+        format!(
+            "D:{:04}{:02}{:02}{:02}{:02}{:02}{}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second, zone
+        )
+    }
+
+    /// The calendar date as `YYYY-MM-DD`, used for `{date}` in footers
+    pub fn to_iso_date(&self) -> String {
+        format!("{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// Value of the Info dictionary `/Trapped` entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trapped {
+    True,
+    False,
+    Unknown,
+}
+
+impl Trapped {
+    /// The PDF name for this value
+    pub fn as_pdf_name(&self) -> &'static str {
+        match self {
+            Trapped::True => "True",
+            Trapped::False => "False",
+            Trapped::Unknown => "Unknown",
+        }
+    }
 }
 
 impl PdfMetadata {
@@ -251,31 +549,41 @@ impl PdfMetadata {
     }
 
     /// Build a PDF Info dictionary string
-    fn to_info_dict(&self) -> String {
+    pub(crate) fn to_info_dict(&self) -> String {
         let mut entries = Vec::new();
         if let Some(ref t) = self.title {
-            entries.push(format!("/Title ({})", escape_pdf_meta(t)));
+            entries.push(format!("/Title {}", pdf_text_string(t)));
         }
         if let Some(ref a) = self.author {
-            entries.push(format!("/Author ({})", escape_pdf_meta(a)));
+            entries.push(format!("/Author {}", pdf_text_string(a)));
         }
         if let Some(ref s) = self.subject {
-            entries.push(format!("/Subject ({})", escape_pdf_meta(s)));
+            entries.push(format!("/Subject {}", pdf_text_string(s)));
         }
         if let Some(ref k) = self.keywords {
-            entries.push(format!("/Keywords ({})", escape_pdf_meta(k)));
+            entries.push(format!("/Keywords {}", pdf_text_string(k)));
         }
         if let Some(ref c) = self.creator {
-            entries.push(format!("/Creator ({})", escape_pdf_meta(c)));
+            entries.push(format!("/Creator {}", pdf_text_string(c)));
+        }
+        if let Some(trapped) = self.trapped {
+            entries.push(format!("/Trapped /{}", trapped.as_pdf_name()));
+        }
+        if let Some(date) = self.creation_date {
+            entries.push(format!("/CreationDate {}", pdf_text_string(&date.to_pdf_string())));
+        }
+        if let Some(date) = self.mod_date {
+            entries.push(format!("/ModDate {}", pdf_text_string(&date.to_pdf_string())));
         }
         entries.push("/Producer (pdf-cli)".to_string());
 
-        // Add custom fields
-        for (key, value) in &self.custom_fields {
+        // Add custom fields, sorted so identical metadata gives identical bytes
+        let mut custom: Vec<_> = self.custom_fields.iter().collect();
+        custom.sort();
+        for (key, value) in custom {
             // Escape the key as well (though typically keys are simple strings)
             let escaped_key = escape_pdf_meta(key);
-            let escaped_value = escape_pdf_meta(value);
-            entries.push(format!("/{} ({})", escaped_key, escaped_value));
+            entries.push(format!("/{} {}", escaped_key, pdf_text_string(value)));
         }
 
         format!("<<\n{}\n>>\n", entries.join("\n"))
@@ -308,12 +616,40 @@ pub fn create_pdf_elements_with_metadata(
     metadata: &PdfMetadata,
 ) -> Result<()> {
     let show_page_numbers = true;
-    let page_streams = build_page_streams(elements, base_font_size, show_page_numbers, layout);
+    let page_streams = build_page_streams(elements, base_font_size, show_page_numbers, layout)?;
 
-    assemble_pdf_with_metadata(filename, &page_streams, font, &layout, metadata)?;
+    assemble_pdf_with_metadata(filename, &page_streams, font, &layout, metadata, "", &OutputOptions::default())?;
     Ok(())
 }
 
+/// Like [`create_pdf_elements_with_metadata`], also writing the document
+/// language and `DisplayDocTitle` to the catalog. The accessibility title is
+/// used when the metadata has none.
+pub fn create_pdf_elements_with_accessibility(
+    filename: &str,
+    elements: &[crate::elements::Element],
+    font: &str,
+    base_font_size: f32,
+    layout: crate::pdf_generator::PageLayout,
+    metadata: &PdfMetadata,
+    accessibility: &crate::pdf_generator::AccessibilityOptions,
+) -> Result<()> {
+    let page_streams = build_page_streams(elements, base_font_size, true, layout)?;
+    let mut metadata = metadata.clone();
+    if metadata.title.is_none() {
+        metadata.title = accessibility.title.clone();
+    }
+    assemble_pdf_with_metadata(
+        filename,
+        &page_streams,
+        font,
+        &layout,
+        &metadata,
+        &accessibility.catalog_entries(),
+        &OutputOptions::default(),
+    )
+}
+
 // --- Internal helpers ---
 
 /// Extract raw content stream data from each Stream object in a PdfDocument.
@@ -353,38 +689,26 @@ fn build_page_streams(
     base_font_size: f32,
     _show_page_numbers: bool,
     _layout: crate::pdf_generator::PageLayout,
-) -> Vec<Vec<u8>> {
-    // Delegate to the existing public API by creating a temp file, then reading it back.
-    // This is not ideal but avoids duplicating ContentStreamBuilder.
-    // A better approach: refactor ContentStreamBuilder to be public. For now, use the
-    // element-to-PDF pipeline and re-extract streams.
-    //
-    // Actually, let's just call create_pdf_from_elements_with_layout to a temp file,
-    // then load it back and extract streams.
-    let tmp = format!(
-        "/tmp/pdf_cli_build_{}.pdf",
-        std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_nanos()
-    );
-    if crate::pdf_generator::create_pdf_from_elements_with_layout(
-        &tmp,
-        elements,
-        "Helvetica",
-        base_font_size,
-        _layout,
-    )
-    .is_ok()
-    {
-        if let Ok(doc) = crate::pdf::PdfDocument::load_from_file(&tmp) {
-            let streams = extract_page_streams(&doc);
-            let _ = fs::remove_file(&tmp);
-            return streams;
-        }
-        let _ = fs::remove_file(&tmp);
+) -> Result<Vec<Vec<u8>>> {
+    // Render through the public pipeline in memory and re-extract the streams;
+    // no temp file keeps the output independent of the clock
+    let bytes = crate::pdf_generator::generate_pdf_bytes(elements, "Helvetica", base_font_size, _layout)?;
+    generated_page_streams(&bytes)
+}
+
+/// Content streams of a document just generated, failing with the reason
+/// when it cannot be read back
+fn generated_page_streams(bytes: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let doc = crate::pdf::PdfDocument::from_bytes(bytes)
+        .map_err(|e| PdfError::Parse(format!("Generated PDF could not be read back: {}", e)))?;
+    let streams = extract_page_streams(&doc);
+    if streams.is_empty() {
+        return Err(PdfError::Parse(format!(
+            "Generated PDF of {} bytes has no page content streams",
+            bytes.len()
+        )));
     }
-    Vec::new()
+    Ok(streams)
 }
 
 /// Assemble a merged PDF from raw page content streams
@@ -393,9 +717,59 @@ fn assemble_merged_pdf(
     page_streams: &[Vec<u8>],
     font: &str,
     layout: &crate::pdf_generator::PageLayout,
+) -> Result<()> {
+    assemble_merged_pdf_with_output(filename, page_streams, font, layout, &OutputOptions::default())
+}
+
+/// Like [`assemble_merged_pdf`], compressing and size-checking as `output` asks
+fn assemble_merged_pdf_with_output(
+    filename: &str,
+    page_streams: &[Vec<u8>],
+    font: &str,
+    layout: &crate::pdf_generator::PageLayout,
+    output: &OutputOptions,
 ) -> Result<()> {
     let metadata = PdfMetadata::default();
-    assemble_pdf_with_metadata(filename, page_streams, font, layout, &metadata)
+    assemble_pdf_with_metadata(filename, page_streams, font, layout, &metadata, "", output)
+}
+
+/// How rewritten documents are written out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OutputOptions {
+    /// Flate-compress page content streams
+    pub compress: bool,
+    /// Log a warning when the output file is larger than this many bytes
+    pub warn_above: Option<u64>,
+}
+
+/// Add a page content stream, compressed when `output` asks for it
+fn add_content_stream(
+    generator: &mut crate::pdf_generator::PdfGenerator,
+    data: &[u8],
+    output: &OutputOptions,
+) -> u32 {
+    if output.compress {
+        generator.add_compressed_stream_object("", data)
+    } else {
+        generator.add_stream_object(format!("<< /Length {} >>\n", data.len()), data.to_vec())
+    }
+}
+
+/// Write a finished document, warning when it exceeds `output.warn_above`
+fn write_output(filename: &str, pdf_data: &[u8], output: &OutputOptions) -> Result<()> {
+    fs::write(filename, pdf_data)?;
+    if let Some(limit) = output.warn_above
+        && pdf_data.len() as u64 > limit
+    {
+        log::warn!(
+            "[output] {} is {} bytes, above the {} byte threshold{}",
+            filename,
+            pdf_data.len(),
+            limit,
+            if output.compress { "" } else { "; consider enabling compression" }
+        );
+    }
+    Ok(())
 }
 
 /// Assemble PDF with optional metadata Info dictionary
@@ -405,6 +779,8 @@ fn assemble_pdf_with_metadata(
     font: &str,
     layout: &crate::pdf_generator::PageLayout,
     metadata: &PdfMetadata,
+    catalog_extra: &str,
+    output: &OutputOptions,
 ) -> Result<()> {
     let mut generator = crate::pdf_generator::PdfGenerator::new();
     let mut page_ids = Vec::new();
@@ -413,37 +789,34 @@ fn assemble_pdf_with_metadata(
         || metadata.author.is_some()
         || metadata.subject.is_some()
         || metadata.keywords.is_some()
-        || metadata.creator.is_some();
-
-    // Object layout: for each page: content_stream, page, font (3 per page)
-    // Then: pages, info (optional), catalog
-    let pages_obj_id = (page_streams.len() as u32) * 3 + 1;
+        || metadata.creator.is_some()
+        || metadata.creation_date.is_some()
+        || metadata.mod_date.is_some();
+
+    // Object layout: the font shared by all pages, then for each page:
+    // content_stream, page (2 per page). Then: pages, info (optional), catalog
+    // WinAnsi so watermark text can use Latin-1 characters
+    let font_id = generator.add_object(format!(
+        "<< /Type /Font\n/Subtype /Type1\n/BaseFont /{}\n/Encoding /WinAnsiEncoding\n>>\n",
+        font
+    ));
+    let pages_obj_id = generator.next_id + (page_streams.len() as u32) * 2;
 
     for page_stream in page_streams {
-        let content_id = generator.add_stream_object(
-            format!("<< /Length {} >>\n", page_stream.len()),
-            page_stream.clone(),
-        );
-
-        let font_id = content_id + 2;
+        let content_id = add_content_stream(&mut generator, page_stream, output);
 
         let page_dict = format!(
             "<< /Type /Page\n\
              /Parent {} 0 R\n\
              /MediaBox [0 0 {} {}]\n\
+             {}\
              /Contents {} 0 R\n\
              /Resources << /Font << /F1 {} 0 R >> >>\n\
              >>\n",
-            pages_obj_id, layout.width, layout.height, content_id, font_id
+            pages_obj_id, layout.width, layout.height, layout.crop_box_entry(), content_id, font_id
         );
         let page_id = generator.add_object(page_dict);
         page_ids.push(page_id);
-
-        let font_dict = format!(
-            "<< /Type /Font\n/Subtype /Type1\n/BaseFont /{}\n>>\n",
-            font
-        );
-        generator.add_object(font_dict);
     }
 
     let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
@@ -471,71 +844,14 @@ fn assemble_pdf_with_metadata(
     let catalog_dict = format!(
         "<< /Type /Catalog\n\
          /Pages {} 0 R\n\
-         >>\n",
-        actual_pages_id
+         {}>>\n",
+        actual_pages_id, catalog_extra
     );
     generator.add_object(catalog_dict);
 
-    // Generate with info reference
-    let pdf_data = if let Some(info) = info_id {
-        generate_with_info(&generator, info)
-    } else {
-        generator.generate()
-    };
-
-    let mut file = std::fs::File::create(filename)?;
-    std::io::Write::write_all(&mut file, &pdf_data)?;
-    Ok(())
-}
-
-/// Generate PDF bytes with an /Info reference in the trailer
-fn generate_with_info(generator: &crate::pdf_generator::PdfGenerator, info_id: u32) -> Vec<u8> {
-    let mut pdf = Vec::new();
-
-    pdf.extend_from_slice(b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n");
-
-    let mut offsets = Vec::new();
-    let mut current_offset = pdf.len() as u32;
-
-    for obj in &generator.objects {
-        offsets.push(current_offset);
-        let obj_header = format!("{} {} obj\n", obj.id, obj.generation);
-        pdf.extend_from_slice(obj_header.as_bytes());
-        pdf.extend_from_slice(obj.content.as_bytes());
-
-        if obj.is_stream {
-            if let Some(data) = &obj.stream_data {
-                pdf.extend_from_slice(b"stream\n");
-                pdf.extend_from_slice(data);
-                pdf.extend_from_slice(b"\nendstream\n");
-            }
-        }
-
-        pdf.extend_from_slice(b"endobj\n");
-        current_offset = pdf.len() as u32;
-    }
-
-    let xref_offset = pdf.len() as u32;
-    pdf.extend_from_slice(format!("xref\n0 {}\n", generator.objects.len() + 1).as_bytes());
-    pdf.extend_from_slice(b"0000000000 65535 f \n");
-
-    for offset in offsets {
-        pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
-    }
-
-    pdf.extend_from_slice(b"trailer\n");
-    pdf.extend_from_slice(b"<<\n");
-    pdf.extend_from_slice(format!("/Size {}\n", generator.objects.len() + 1).as_bytes());
-    if !generator.objects.is_empty() {
-        pdf.extend_from_slice(format!("/Root {} 0 R\n", generator.objects.len()).as_bytes());
-    }
-    pdf.extend_from_slice(format!("/Info {} 0 R\n", info_id).as_bytes());
-    pdf.extend_from_slice(b">>\n");
-    pdf.extend_from_slice(b"startxref\n");
-    pdf.extend_from_slice(format!("{}\n", xref_offset).as_bytes());
-    pdf.extend_from_slice(b"%%EOF\n");
-
-    pdf
+    generator.info = info_id;
+    let pdf_data = generator.generate();
+    write_output(filename, &pdf_data, output)
 }
 
 /// Rotate pages in a PDF. Creates a new PDF with /Rotate applied to each page.
@@ -543,22 +859,22 @@ fn generate_with_info(generator: &crate::pdf_generator::PdfGenerator, info_id: u
 /// `rotation` must be 0, 90, 180, or 270.
 pub fn rotate_pdf(input_file: &str, output_file: &str, rotation: u32) -> Result<()> {
     if rotation != 0 && rotation != 90 && rotation != 180 && rotation != 270 {
-        return Err(anyhow!(
+        return Err(PdfError::InvalidInput(format!(
             "Invalid rotation: {}. Must be 0, 90, 180, or 270.",
             rotation
-        ));
+        )));
     }
 
     let doc = crate::pdf::PdfDocument::load_from_file(input_file)?;
     let all_streams = extract_page_streams(&doc);
 
     if all_streams.is_empty() {
-        return Err(anyhow!("No pages found in {}", input_file));
+        return Err(PdfError::InvalidInput(format!("No pages found in {}", input_file)));
     }
 
     let layout = crate::pdf_generator::PageLayout::portrait();
     assemble_rotated_pdf(output_file, &all_streams, "Helvetica", &layout, rotation)?;
-    println!(
+    log::info!(
         "[rotate] Rotated {} pages by {}° in {}",
         all_streams.len(),
         rotation,
@@ -567,6 +883,57 @@ pub fn rotate_pdf(input_file: &str, output_file: &str, rotation: u32) -> Result<
     Ok(())
 }
 
+/// Rotate only the listed pages, each by its own angle, leaving the others as they are.
+///
+/// `rotations` holds 1-indexed pages and clockwise angles of 0, 90, 180 or 270;
+/// a page listed twice is turned by the sum. Each page's `/Rotate`, including
+/// one inherited from the page tree, is advanced by its angle and written to the
+/// page dictionary itself in an incremental update, so page content, resources
+/// and annotations are kept.
+pub fn rotate_pages(input_file: &str, output_file: &str, rotations: &[(usize, u32)]) -> Result<()> {
+    let original = fs::read(input_file)?;
+    let doc = crate::pdf::PdfDocument::from_bytes(&original)?;
+    let mut turns = std::collections::BTreeMap::new();
+    for &(page, angle) in rotations {
+        if angle % 90 != 0 || angle >= 360 {
+            return Err(PdfError::InvalidInput(format!(
+                "Invalid rotation: {}. Must be 0, 90, 180, or 270.",
+                angle
+            )));
+        }
+        if page == 0 || page > doc.pages.len() {
+            return Err(PdfError::InvalidPageRange { start: page, end: page, page_count: Some(doc.pages.len()) });
+        }
+        *turns.entry(page).or_insert(0) += angle;
+    }
+
+    let mut update = IncrementalUpdate::new(original, &doc, input_file)?;
+    for (&page, &angle) in &turns {
+        let page_id = doc.pages[page - 1];
+        let mut dict = doc
+            .objects
+            .get(&page_id)
+            .and_then(|o| o.as_dict())
+            .ok_or_else(|| PdfError::Parse(format!("Page object {} not found", page_id)))?
+            .clone();
+        let current = doc
+            .page_attribute(page_id, "Rotate")
+            .and_then(|r| r.as_number())
+            .map(|r| r as i64)
+            .unwrap_or(0);
+        let rotation = (current + angle as i64).rem_euclid(360);
+        dict.insert(
+            "Rotate".to_string(),
+            crate::pdf::PdfValue::Object(crate::pdf::PdfObject::Number(rotation as f64)),
+        );
+        update.set_object(page_id, format!("<< {} >>", dict_to_string(&dict)).into_bytes());
+    }
+
+    fs::write(output_file, update.finish())?;
+    log::info!("[rotate] Rotated {} of {} pages in {}", turns.len(), doc.pages.len(), output_file);
+    Ok(())
+}
+
 /// Assemble PDF with /Rotate on each page
 fn assemble_rotated_pdf(
     filename: &str,
@@ -625,419 +992,2522 @@ fn assemble_rotated_pdf(
     Ok(())
 }
 
-/// Extract metadata from a PDF document
-pub fn extract_metadata_from_pdf(doc: &crate::pdf::PdfDocument) -> Result<PdfMetadata> {
-    let mut metadata = PdfMetadata::new();
+/// How page content is mapped onto a differently sized target page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResizeMode {
+    /// Scale proportionally so the whole page fits, centered on the target
+    Fit,
+    /// Scale each axis independently to fill the target exactly
+    Stretch,
+}
 
-    // Look for the Info dictionary in the trailer
-    // For now, we'll do a simple search for metadata-like objects
-    for (_id, obj) in &doc.objects {
-        if let crate::pdf::PdfObject::Dictionary(data) = obj {
-            // Convert dictionary to a string representation for parsing
-            let dict_str = dict_to_string(data);
-            if dict_str.contains("/Title") {
-                if let Some(title) = extract_pdf_string_field(&dict_str, "/Title") {
-                    metadata.title = Some(title);
-                }
-            }
-            if dict_str.contains("/Author") {
-                if let Some(author) = extract_pdf_string_field(&dict_str, "/Author") {
-                    metadata.author = Some(author);
-                }
-            }
-            if dict_str.contains("/Subject") {
-                if let Some(subject) = extract_pdf_string_field(&dict_str, "/Subject") {
-                    metadata.subject = Some(subject);
-                }
-            }
-            if dict_str.contains("/Keywords") {
-                if let Some(keywords) = extract_pdf_string_field(&dict_str, "/Keywords") {
-                    metadata.keywords = Some(keywords);
-                }
-            }
-            if dict_str.contains("/Creator") {
-                if let Some(creator) = extract_pdf_string_field(&dict_str, "/Creator") {
-                    metadata.creator = Some(creator);
-                }
-            }
-        }
+/// Resize every page of a PDF to the dimensions of `target_layout`.
+///
+/// Each source page is wrapped in a Form XObject and drawn onto a new page of
+/// the target size with a `cm` scale matrix, so text stays extractable.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use pdfrs::pdf_ops::{self, ResizeMode};
+/// use pdfrs::pdf_generator::PageLayout;
+///
+/// pdf_ops::resize_pages("letter.pdf", "a4.pdf", PageLayout::a4(), ResizeMode::Fit)
+///     .expect("Failed to resize PDF");
+/// ```
+pub fn resize_pages(
+    input_file: &str,
+    output_file: &str,
+    target_layout: crate::pdf_generator::PageLayout,
+    mode: ResizeMode,
+) -> Result<()> {
+    let doc = crate::pdf::PdfDocument::load_from_file(input_file)?;
+    if doc.pages.is_empty() {
+        return Err(PdfError::InvalidInput(format!("No pages found in {}", input_file)));
     }
 
-    Ok(metadata)
+    let mut generator = crate::pdf_generator::PdfGenerator::new();
+    let mut importer = ObjectImporter::new(&doc);
+    let mut page_streams = Vec::new();
+    for index in 0..doc.pages.len() {
+        let (form_id, bbox) = import_page_as_form(&doc, index, &mut generator, &mut importer)?;
+        let content = fit_form_content(bbox, target_layout.width, target_layout.height, mode);
+        page_streams.push(FormPage {
+            content: content.into_bytes(),
+            xobjects: vec![("Pg0".to_string(), form_id)],
+            size: None,
+            annots: Vec::new(),
+        });
+    }
+
+    let pdf_data = assemble_form_pages(generator, &page_streams, &target_layout, None);
+    fs::write(output_file, pdf_data)?;
+    log::info!(
+        "[resize] Resized {} pages to {}x{} in {}",
+        page_streams.len(),
+        target_layout.width,
+        target_layout.height,
+        output_file
+    );
+    Ok(())
 }
 
-/// Convert a PDF dictionary HashMap to a string representation
-fn dict_to_string(dict: &std::collections::HashMap<String, crate::pdf::PdfValue>) -> String {
-    let mut parts = Vec::new();
-    for (key, value) in dict {
-        parts.push(format!("/{} {}", key, value_to_string(value)));
-    }
-    parts.join(" ")
+/// Content that draws form `/Pg0` (occupying `bbox`) scaled onto a `width` x `height` page
+fn fit_form_content(bbox: [f32; 4], width: f32, height: f32, mode: ResizeMode) -> String {
+    form_placement_content(fit_matrix(bbox, width, height, mode))
 }
 
-/// Convert a PdfValue to its string representation
-fn value_to_string(value: &crate::pdf::PdfValue) -> String {
-    match value {
-        crate::pdf::PdfValue::Object(obj) => object_to_string(obj),
-        crate::pdf::PdfValue::Reference(id, generation) => format!("{} {} R", id, generation),
-    }
+/// Content that draws form `/Pg0` through the transformation `matrix`
+fn form_placement_content(m: [f32; 6]) -> String {
+    format!("q\n{} {} {} {} {} {} cm\n/Pg0 Do\nQ\n", m[0], m[1], m[2], m[3], m[4], m[5])
 }
 
-/// Convert a PdfObject to its string representation
-fn object_to_string(obj: &crate::pdf::PdfObject) -> String {
-    match obj {
-        crate::pdf::PdfObject::Dictionary(dict) => {
-            let entries: Vec<String> = dict.iter()
-                .map(|(k, v)| format!("/{} {}", k, value_to_string(v)))
-                .collect();
-            format!("<< {} >>", entries.join(" "))
-        }
-        crate::pdf::PdfObject::Stream { dictionary: _, data: _ } => {
-            "<< stream >>".to_string()
+/// Matrix that scales `bbox` onto a `width` x `height` page, centered
+fn fit_matrix(bbox: [f32; 4], width: f32, height: f32, mode: ResizeMode) -> [f32; 6] {
+    let src_w = (bbox[2] - bbox[0]).max(1.0);
+    let src_h = (bbox[3] - bbox[1]).max(1.0);
+    let (sx, sy) = match mode {
+        ResizeMode::Fit => {
+            let s = (width / src_w).min(height / src_h);
+            (s, s)
         }
-        crate::pdf::PdfObject::Array(arr) => {
-            let elems: Vec<String> = arr.iter().map(value_to_string).collect();
-            format!("[{}]", elems.join(" "))
-        }
-        crate::pdf::PdfObject::String(s) => format!("({})", escape_pdf_meta(s)),
-        crate::pdf::PdfObject::Number(n) => n.to_string(),
-        crate::pdf::PdfObject::Boolean(b) => {
-            if *b { "true" } else { "false" }.to_string()
+        ResizeMode::Stretch => (width / src_w, height / src_h),
+    };
+    let tx = (width - src_w * sx) / 2.0 - bbox[0] * sx;
+    let ty = (height - src_h * sy) / 2.0 - bbox[1] * sy;
+    [sx, 0.0, 0.0, sy, tx, ty]
+}
+
+/// Place `rows * cols` source pages on each output page (n-up imposition).
+///
+/// Uses a 10pt gutter; see [`nup_pdf_with_gutter`] to configure it.
+pub fn nup_pdf(input_file: &str, output_file: &str, rows: usize, cols: usize) -> Result<()> {
+    nup_pdf_with_gutter(input_file, output_file, rows, cols, 10.0)
+}
+
+/// Place `rows * cols` source pages on each output page, separated by `gutter` points.
+///
+/// Pages fill the grid left to right, top to bottom, each scaled to fit its
+/// cell. The output pages keep the size of the first source page; a final
+/// partial grid leaves the remaining cells blank.
+pub fn nup_pdf_with_gutter(
+    input_file: &str,
+    output_file: &str,
+    rows: usize,
+    cols: usize,
+    gutter: f32,
+) -> Result<()> {
+    if rows == 0 || cols == 0 {
+        return Err(PdfError::InvalidInput("Grid must have at least one row and one column".into()));
+    }
+
+    let doc = crate::pdf::PdfDocument::load_from_file(input_file)?;
+    if doc.pages.is_empty() {
+        return Err(PdfError::InvalidInput(format!("No pages found in {}", input_file)));
+    }
+
+    let sheet = page_media_box(&doc, doc.pages[0]);
+    let layout = crate::pdf_generator::PageLayout {
+        width: sheet[2] - sheet[0],
+        height: sheet[3] - sheet[1],
+        ..crate::pdf_generator::PageLayout::portrait()
+    };
+    let cell_w = ((layout.width - gutter * (cols + 1) as f32) / cols as f32).max(1.0);
+    let cell_h = ((layout.height - gutter * (rows + 1) as f32) / rows as f32).max(1.0);
+
+    let mut generator = crate::pdf_generator::PdfGenerator::new();
+    let mut importer = ObjectImporter::new(&doc);
+    let mut sheets = Vec::new();
+    let per_sheet = rows * cols;
+    for chunk_start in (0..doc.pages.len()).step_by(per_sheet) {
+        let mut content = String::new();
+        let mut xobjects = Vec::new();
+        for slot in 0..per_sheet.min(doc.pages.len() - chunk_start) {
+            let (form_id, bbox) =
+                import_page_as_form(&doc, chunk_start + slot, &mut generator, &mut importer)?;
+            let (row, col) = (slot / cols, slot % cols);
+            let cell_x = gutter + col as f32 * (cell_w + gutter);
+            let cell_y = layout.height - (row + 1) as f32 * (cell_h + gutter);
+
+            let src_w = (bbox[2] - bbox[0]).max(1.0);
+            let src_h = (bbox[3] - bbox[1]).max(1.0);
+            let scale = (cell_w / src_w).min(cell_h / src_h);
+            let tx = cell_x + (cell_w - src_w * scale) / 2.0 - bbox[0] * scale;
+            let ty = cell_y + (cell_h - src_h * scale) / 2.0 - bbox[1] * scale;
+
+            let name = format!("Pg{}", slot);
+            content.push_str(&format!(
+                "q\n{} 0 0 {} {} {} cm\n/{} Do\nQ\n",
+                scale, scale, tx, ty, name
+            ));
+            xobjects.push((name, form_id));
         }
-        crate::pdf::PdfObject::Null => "null".to_string(),
-        crate::pdf::PdfObject::Reference(id, generation) => format!("{} {} R", id, generation),
-        crate::pdf::PdfObject::Name(n) => format!("/{}", n),
+        sheets.push(FormPage {
+            content: content.into_bytes(),
+            xobjects,
+            size: None,
+            annots: Vec::new(),
+        });
     }
+
+    let pdf_data = assemble_form_pages(generator, &sheets, &layout, None);
+    fs::write(output_file, pdf_data)?;
+    log::info!(
+        "[nup] Placed {} pages onto {} sheets ({}x{}) in {}",
+        doc.pages.len(),
+        sheets.len(),
+        rows,
+        cols,
+        output_file
+    );
+    Ok(())
 }
 
-/// Extract a string field value from PDF dictionary content
-fn extract_pdf_string_field(content: &str, field: &str) -> Option<String> {
-    // Find the field and extract the string value
-    // Format: /Field (value) or /Field <value>
-    // Look for the field name followed by optional whitespace and opening parenthesis
-    let field_pattern_start = format!("{} ", field);
-    if let Some(start) = content.find(&field_pattern_start) {
-        // Find the opening parenthesis after the field name
-        let after_field = &content[start + field_pattern_start.len()..];
-        if let Some(paren_start) = after_field.find('(') {
-            let value_start = start + field_pattern_start.len() + paren_start + 1;
-            // Find the closing parenthesis, handling escaped parentheses
-            let mut paren_count = 1;
-            let mut value_end = value_start;
-            let chars: Vec<char> = content[value_start..].chars().collect();
-            let mut i = 0;
-            while i < chars.len() && paren_count > 0 {
-                if chars[i] == '\\' && i + 1 < chars.len() {
-                    // Escaped character, skip it
-                    i += 2;
-                    continue;
+/// An output page whose content draws previously imported Form XObjects
+struct FormPage {
+    content: Vec<u8>,
+    /// Resource name and object id of each XObject the content uses
+    xobjects: Vec<(String, u32)>,
+    /// Page width and height, overriding the layout passed to [`assemble_form_pages`]
+    size: Option<(f32, f32)>,
+    /// Annotations (form widgets) already written to the generator
+    annots: Vec<u32>,
+}
+
+/// Append pages that draw Form XObjects (by resource name) plus the page tree
+/// and catalog, then serialize the document. `acroform` is the catalog's
+/// `/AcroForm` dictionary, when the pages carry form fields.
+fn assemble_form_pages(
+    mut generator: crate::pdf_generator::PdfGenerator,
+    pages: &[FormPage],
+    layout: &crate::pdf_generator::PageLayout,
+    acroform: Option<&str>,
+) -> Vec<u8> {
+    let mut content_ids = Vec::new();
+    for page in pages {
+        content_ids.push(generator.add_stream_object(
+            format!("<< /Length {} >>\n", page.content.len()),
+            page.content.clone(),
+        ));
+    }
+
+    let pages_obj_id = generator.next_id + pages.len() as u32;
+    let mut page_ids = Vec::new();
+    for (page, content_id) in pages.iter().zip(&content_ids) {
+        let (width, height) = page.size.unwrap_or((layout.width, layout.height));
+        let xobject_refs: Vec<String> = page
+            .xobjects
+            .iter()
+            .map(|(name, id)| format!("/{} {} 0 R", name, id))
+            .collect();
+        let annots = if page.annots.is_empty() {
+            String::new()
+        } else {
+            let refs: Vec<String> = page.annots.iter().map(|id| format!("{} 0 R", id)).collect();
+            format!("/Annots [{}]\n", refs.join(" "))
+        };
+        let page_dict = format!(
+            "<< /Type /Page\n\
+             /Parent {} 0 R\n\
+             /MediaBox [0 0 {} {}]\n\
+             /Contents {} 0 R\n\
+             {}\
+             /Resources << /XObject << {} >> >>\n\
+             >>\n",
+            pages_obj_id,
+            width,
+            height,
+            content_id,
+            annots,
+            xobject_refs.join(" ")
+        );
+        page_ids.push(generator.add_object(page_dict));
+    }
+
+    let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+    let pages_dict = format!(
+        "<< /Type /Pages\n/Kids [{}]\n/Count {}\n>>\n",
+        kids.join(" "),
+        page_ids.len()
+    );
+    let actual_pages_id = generator.add_object(pages_dict);
+    assert_eq!(actual_pages_id, pages_obj_id);
+
+    let acroform = acroform.map(|a| format!("/AcroForm {}\n", a)).unwrap_or_default();
+    let catalog_dict = format!("<< /Type /Catalog\n/Pages {} 0 R\n{}>>\n", actual_pages_id, acroform);
+    generator.add_object(catalog_dict);
+    generator.generate()
+}
+
+// --- Page → Form XObject primitives ---
+
+/// Copies objects from a parsed document into a generator, renumbering
+/// indirect references so shared resources are only copied once.
+struct ObjectImporter<'a> {
+    doc: &'a crate::pdf::PdfDocument,
+    id_map: std::collections::HashMap<u32, u32>,
+    /// Follow `/Parent` links, which form fields use for their hierarchy
+    keep_parents: bool,
+    /// Keep page content compressed rather than storing it decoded
+    keep_compression: bool,
+}
+
+impl<'a> ObjectImporter<'a> {
+    fn new(doc: &'a crate::pdf::PdfDocument) -> Self {
+        ObjectImporter {
+            doc,
+            id_map: std::collections::HashMap::new(),
+            keep_parents: false,
+            keep_compression: true,
+        }
+    }
+
+    /// Copy every object reachable from `value` and return `value` serialized
+    /// against the new object numbers
+    fn import_value(
+        &mut self,
+        generator: &mut crate::pdf_generator::PdfGenerator,
+        value: &crate::pdf::PdfValue,
+    ) -> String {
+        // Assign new IDs first so that reference cycles serialize correctly
+        let mut pending = Vec::new();
+        self.collect_references(value, generator.next_id, &mut pending);
+        for source_id in pending {
+            let new_id = match self.doc.objects.get(&source_id) {
+                Some(crate::pdf::PdfObject::Stream { dictionary, data }) => {
+                    let mut dict = self.serialize_dict_without(dictionary, &["Length"]);
+                    dict.insert_str(dict.len() - 2, &format!("/Length {} ", data.len()));
+                    generator.add_stream_object(format!("{}\n", dict), data.clone())
                 }
-                if chars[i] == '(' {
-                    paren_count += 1;
-                } else if chars[i] == ')' {
-                    paren_count -= 1;
+                Some(obj) => generator.add_object(format!("{}\n", self.serialize_object(obj))),
+                None => generator.add_object("null\n".to_string()),
+            };
+            debug_assert_eq!(Some(&new_id), self.id_map.get(&source_id));
+        }
+        self.serialize_value(value)
+    }
+
+    fn collect_references(
+        &mut self,
+        value: &crate::pdf::PdfValue,
+        first_free_id: u32,
+        pending: &mut Vec<u32>,
+    ) {
+        match value {
+            crate::pdf::PdfValue::Reference(..)
+            | crate::pdf::PdfValue::Object(crate::pdf::PdfObject::Reference(..)) => {
+                let id = value.as_reference().unwrap_or(0);
+                if self.id_map.contains_key(&id) {
+                    return;
                 }
-                if paren_count > 0 {
-                    value_end = value_start + i + 1;
+                self.id_map.insert(id, first_free_id + pending.len() as u32);
+                pending.push(id);
+                if let Some(obj) = self.doc.objects.get(&id) {
+                    self.collect_object_references(obj, first_free_id, pending);
                 }
-                i += 1;
             }
-            let value = &content[value_start..value_end];
-            // Unescape the string
-            Some(unescape_pdf_string(value))
-        } else {
-            None
+            crate::pdf::PdfValue::Object(obj) => {
+                self.collect_object_references(obj, first_free_id, pending)
+            }
         }
-    } else {
-        None
     }
-}
 
-/// Unescape a PDF string (handle escape sequences)
-fn unescape_pdf_string(s: &str) -> String {
-    let mut result = String::new();
-    let mut chars = s.chars().peekable();
-
-    while let Some(c) = chars.next() {
-        if c == '\\' {
-            if let Some(next) = chars.next() {
-                match next {
-                    'n' => result.push('\n'),
-                    'r' => result.push('\r'),
-                    't' => result.push('\t'),
-                    'b' => result.push('\x08'),
-                    'f' => result.push('\x0c'),
-                    '(' | ')' | '\\' => result.push(next),
-                    '0'..='7' => {
-                        // Octal escape sequence (up to 3 digits)
-                        let mut octal = String::from(next);
-                        if let Some(&c) = chars.peek() {
-                            if c >= '0' && c <= '7' {
-                                chars.next();
-                                octal.push(c);
-                                if let Some(&c) = chars.peek() {
-                                    if c >= '0' && c <= '7' {
-                                        chars.next();
-                                        octal.push(c);
-                                    }
-                                }
-                            }
-                        }
-                        if let Ok(code) = u8::from_str_radix(&octal, 8) {
-                            result.push(code as char);
-                        }
+    fn collect_object_references(
+        &mut self,
+        obj: &crate::pdf::PdfObject,
+        first_free_id: u32,
+        pending: &mut Vec<u32>,
+    ) {
+        match obj {
+            crate::pdf::PdfObject::Dictionary(dict)
+            | crate::pdf::PdfObject::Stream { dictionary: dict, .. } => {
+                let mut keys: Vec<&String> = dict.keys().collect();
+                keys.sort();
+                for key in keys {
+                    // Back-pointers into the page tree would drag in the whole document
+                    if (key != "Parent" || self.keep_parents) && key != "P" {
+                        self.collect_references(&dict[key], first_free_id, pending);
                     }
-                    _ => result.push(next),
                 }
             }
-        } else {
-            result.push(c);
+            crate::pdf::PdfObject::Array(items) => {
+                for item in items {
+                    self.collect_references(item, first_free_id, pending);
+                }
+            }
+            _ => {}
         }
     }
 
-    result
-}
-
-/// Merge metadata from two sources, with new_metadata taking precedence
-pub fn merge_metadata(base: &PdfMetadata, new_metadata: &PdfMetadata) -> PdfMetadata {
-    let mut merged = base.clone();
-    if new_metadata.title.is_some() {
-        merged.title = new_metadata.title.clone();
+    fn serialize_value(&self, value: &crate::pdf::PdfValue) -> String {
+        match value {
+            crate::pdf::PdfValue::Object(obj) => self.serialize_object(obj),
+            crate::pdf::PdfValue::Reference(id, _) => self.serialize_reference(*id),
+        }
     }
-    if new_metadata.author.is_some() {
-        merged.author = new_metadata.author.clone();
+
+    fn serialize_reference(&self, id: u32) -> String {
+        match self.id_map.get(&id) {
+            Some(new_id) => format!("{} 0 R", new_id),
+            None => "null".to_string(),
+        }
     }
-    if new_metadata.subject.is_some() {
-        merged.subject = new_metadata.subject.clone();
+
+    fn serialize_dict_without(
+        &self,
+        dict: &std::collections::HashMap<String, crate::pdf::PdfValue>,
+        skip: &[&str],
+    ) -> String {
+        let mut keys: Vec<&String> = dict.keys().collect();
+        keys.sort();
+        let entries: Vec<String> = keys
+            .into_iter()
+            .filter(|k| !skip.contains(&k.as_str()) && (*k != "Parent" || self.keep_parents) && *k != "P")
+            .map(|k| format!("/{} {}", k, self.serialize_value(&dict[k])))
+            .collect();
+        format!("<< {} >>", entries.join(" "))
     }
-    if new_metadata.keywords.is_some() {
-        merged.keywords = new_metadata.keywords.clone();
+
+    fn serialize_object(&self, obj: &crate::pdf::PdfObject) -> String {
+        match obj {
+            crate::pdf::PdfObject::Dictionary(dict) => self.serialize_dict_without(dict, &[]),
+            crate::pdf::PdfObject::Stream { dictionary, .. } => {
+                self.serialize_dict_without(dictionary, &[])
+            }
+            crate::pdf::PdfObject::Array(items) => {
+                let parts: Vec<String> = items.iter().map(|v| self.serialize_value(v)).collect();
+                format!("[{}]", parts.join(" "))
+            }
+            crate::pdf::PdfObject::String(s) => pdf_text_string(s),
+            crate::pdf::PdfObject::Number(n) => n.to_string(),
+            crate::pdf::PdfObject::Boolean(b) => b.to_string(),
+            crate::pdf::PdfObject::Null => "null".to_string(),
+            crate::pdf::PdfObject::Reference(id, _) => self.serialize_reference(*id),
+            crate::pdf::PdfObject::Name(n) => format!("/{}", n),
+        }
     }
-    if new_metadata.creator.is_some() {
-        merged.creator = new_metadata.creator.clone();
+}
+
+/// Serialize text as a PDF text string (annotations, metadata, field values, ...).
+///
+/// Text PDFDocEncoding can represent becomes a literal string with octal
+/// escapes for control and non-ASCII characters; anything else becomes a
+/// UTF-16BE hex string `<FEFF...>`.
+pub(crate) fn pdf_text_string(s: &str) -> String {
+    let single_byte = s.chars().all(|c| c.is_ascii() || crate::pdf::pdfdoc_encode(c).is_some());
+    if !single_byte {
+        let mut out = String::from("<FEFF");
+        for unit in s.encode_utf16() {
+            out.push_str(&format!("{:04X}", unit));
+        }
+        out.push('>');
+        return out;
     }
-    // Merge custom fields, with new_metadata taking precedence
-    for (key, value) in &new_metadata.custom_fields {
-        merged.custom_fields.insert(key.clone(), value.clone());
+    let mut out = String::from("(");
+    for c in s.chars() {
+        match crate::pdf::pdfdoc_encode(c) {
+            Some(byte) => out.push_str(&format!("\\{:03o}", byte)),
+            None => out.push_str(&escape_pdf_meta(c.encode_utf8(&mut [0; 4]))),
+        }
     }
-    merged
+    out.push(')');
+    out
 }
 
-/// A text annotation to be placed on a PDF page
-#[derive(Debug, Clone)]
-pub struct TextAnnotation {
-    pub x: f32,
-    pub y: f32,
-    pub width: f32,
-    pub height: f32,
-    pub content: String,
-    pub title: String,
+/// Serialize text shown with a `Tj` operator in a WinAnsiEncoding font.
+/// Characters outside WinAnsi are replaced with `?`.
+fn pdf_show_string(s: &str) -> String {
+    let mut out = String::from("(");
+    for c in s.chars() {
+        match c {
+            ' '..='~' => out.push_str(&escape_pdf_meta(c.encode_utf8(&mut [0; 4]))),
+            _ => match crate::pdf::winansi_encode(c) {
+                Some(byte) => out.push_str(&format!("\\{:03o}", byte)),
+                None => out.push('?'),
+            },
+        }
+    }
+    out.push(')');
+    out
 }
 
-/// A link annotation (clickable URL region)
-#[derive(Debug, Clone)]
-pub struct LinkAnnotation {
-    pub x: f32,
-    pub y: f32,
-    pub width: f32,
-    pub height: f32,
-    pub url: String,
+/// Read a page's MediaBox as `[llx, lly, urx, ury]`, defaulting to US Letter
+fn page_media_box(doc: &crate::pdf::PdfDocument, page_id: u32) -> [f32; 4] {
+    if let Some(crate::pdf::PdfObject::Array(items)) = doc.page_attribute(page_id, "MediaBox") {
+        let nums: Vec<f32> = items
+            .iter()
+            .filter_map(|v| doc.resolve(v).and_then(|o| o.as_number()))
+            .map(|n| n as f32)
+            .collect();
+        if nums.len() == 4 {
+            return [nums[0], nums[1], nums[2], nums[3]];
+        }
+    }
+    [0.0, 0.0, 612.0, 792.0]
 }
 
-/// A highlight annotation (colored rectangle over text)
-#[derive(Debug, Clone)]
-pub struct HighlightAnnotation {
-    pub x: f32,
-    pub y: f32,
-    pub width: f32,
-    pub height: f32,
-    pub color_r: f32,
-    pub color_g: f32,
-    pub color_b: f32,
+/// Copy page `page_index` (0-based) of `doc` into `generator` as a Form XObject.
+///
+/// The form's `/BBox` is the page's MediaBox and its `/Matrix` applies the
+/// page's `/Rotate`, so drawing the form shows the page as a viewer would.
+/// Resources are copied along with it; use this once per page, as repeated
+/// calls copy shared resources again.
+pub fn page_to_form_xobject(
+    doc: &crate::pdf::PdfDocument,
+    page_index: usize,
+    generator: &mut crate::pdf_generator::PdfGenerator,
+) -> Result<u32> {
+    let mut importer = ObjectImporter::new(doc);
+    import_page_as_form(doc, page_index, generator, &mut importer).map(|(id, _)| id)
 }
 
-/// Create a PDF with text, link, and highlight annotations
-pub fn create_pdf_with_all_annotations(
-    output_file: &str,
-    text: &str,
-    annotations: &[TextAnnotation],
-    links: &[LinkAnnotation],
-    highlights: &[HighlightAnnotation],
-) -> Result<()> {
-    let elements = crate::elements::parse_markdown(text);
-    let layout = crate::pdf_generator::PageLayout::portrait();
-    let page_streams = build_page_streams(&elements, 12.0, true, layout);
-    if page_streams.is_empty() {
-        return Err(anyhow!("No page content generated"));
-    }
+/// Copy page `page_index` of `doc` into `generator` as a Form XObject.
+/// Returns the new object id and the box the form occupies once drawn.
+fn import_page_as_form(
+    doc: &crate::pdf::PdfDocument,
+    page_index: usize,
+    generator: &mut crate::pdf_generator::PdfGenerator,
+    importer: &mut ObjectImporter,
+) -> Result<(u32, [f32; 4])> {
+    let page_id = *doc
+        .pages
+        .get(page_index)
+        .ok_or(PdfError::InvalidPageRange {
+            start: page_index + 1,
+            end: page_index + 1,
+            page_count: Some(doc.pages.len()),
+        })?;
+    let (_, drawn) = page_form_matrix(doc, page_id);
+
+    // A single stream is copied verbatim (keeping its filter); several are
+    // joined and compressed again when any of them was
+    let mut filter = String::new();
+    let is_flate = |dictionary: &std::collections::HashMap<String, crate::pdf::PdfValue>| {
+        matches!(dictionary.get("Filter").and_then(|f| doc.resolve(f)).and_then(|f| f.as_name()), Some("FlateDecode"))
+    };
+    let content = match doc.page_attribute(page_id, "Contents") {
+        Some(crate::pdf::PdfObject::Stream { dictionary, data })
+            if !importer.keep_compression && is_flate(dictionary) && !dictionary.contains_key("DecodeParms") =>
+        {
+            decompress_if_needed(data)
+        }
+        Some(crate::pdf::PdfObject::Stream { dictionary, data }) => {
+            for key in ["Filter", "DecodeParms"] {
+                if let Some(value) = dictionary.get(key) {
+                    filter.push_str(&format!("/{} {} ", key, importer.serialize_value(value)));
+                }
+            }
+            data.clone()
+        }
+        Some(crate::pdf::PdfObject::Array(parts)) => {
+            let mut joined = Vec::new();
+            let mut compressed = false;
+            for part in parts {
+                if let Some(crate::pdf::PdfObject::Stream { dictionary, data }) = doc.resolve(part) {
+                    compressed |= dictionary.contains_key("Filter");
+                    joined.extend_from_slice(&decompress_if_needed(data));
+                    joined.push(b'\n');
+                }
+            }
+            if compressed && importer.keep_compression {
+                filter.push_str("/Filter /FlateDecode ");
+                crate::compression::compress_deflate(&joined).expect("writing to a Vec cannot fail")
+            } else {
+                joined
+            }
+        }
+        _ => Vec::new(),
+    };
+    Ok((add_page_form(doc, page_id, content, &filter, generator, importer), drawn))
+}
 
-    let mut generator = crate::pdf_generator::PdfGenerator::new();
-    let mut annot_ids: Vec<u32> = Vec::new();
+/// Add a Form XObject that draws `content` with the MediaBox, `/Rotate` and
+/// resources of page `page_id`. `filter` holds the `/Filter` and `/DecodeParms`
+/// entries `content` is encoded with, if any.
+fn add_page_form(
+    doc: &crate::pdf::PdfDocument,
+    page_id: u32,
+    content: Vec<u8>,
+    filter: &str,
+    generator: &mut crate::pdf_generator::PdfGenerator,
+    importer: &mut ObjectImporter,
+) -> u32 {
+    let bbox = page_media_box(doc, page_id);
+    let (matrix, _) = page_form_matrix(doc, page_id);
+
+    // Inherited resources are resolved first, then everything they reference is imported
+    let resources = doc
+        .page_attribute(page_id, "Resources")
+        .cloned()
+        .unwrap_or(crate::pdf::PdfObject::Dictionary(Default::default()));
+    let resources = importer.import_value(generator, &crate::pdf::PdfValue::Object(resources));
+
+    let form_dict = format!(
+        "<< /Type /XObject\n\
+         /Subtype /Form\n\
+         /BBox [{} {} {} {}]\n\
+         /Matrix [{} {} {} {} {} {}]\n\
+         /Resources {}\n\
+         {}/Length {}\n\
+         >>\n",
+        bbox[0], bbox[1], bbox[2], bbox[3],
+        matrix[0], matrix[1], matrix[2], matrix[3], matrix[4], matrix[5],
+        resources, filter, content.len()
+    );
+    generator.add_stream_object(form_dict, content)
+}
 
-    for annot in annotations {
-        let annot_dict = format!(
-            "<< /Type /Annot\n/Subtype /Text\n/Rect [{} {} {} {}]\n/Contents ({})\n/T ({})\n/Open false\n>>\n",
-            annot.x, annot.y, annot.x + annot.width, annot.y + annot.height,
-            escape_pdf_meta(&annot.content), escape_pdf_meta(&annot.title),
-        );
-        annot_ids.push(generator.add_object(annot_dict));
+/// The `/Matrix` that shows page `page_id` as a viewer would (applying its
+/// `/Rotate`) when drawn as a form, and the box the form then occupies
+fn page_form_matrix(doc: &crate::pdf::PdfDocument, page_id: u32) -> ([f32; 6], [f32; 4]) {
+    let bbox = page_media_box(doc, page_id);
+    let rotation = doc
+        .page_attribute(page_id, "Rotate")
+        .and_then(|r| r.as_number())
+        .map(|r| (r as i64).rem_euclid(360))
+        .unwrap_or(0);
+    let (w, h) = (bbox[2] - bbox[0], bbox[3] - bbox[1]);
+    // Rotate clockwise like a viewer, then move the result back to the origin
+    match rotation {
+        90 => ([0.0, -1.0, 1.0, 0.0, -bbox[1], bbox[2]], [0.0, 0.0, h, w]),
+        180 => ([-1.0, 0.0, 0.0, -1.0, bbox[2], bbox[3]], [0.0, 0.0, w, h]),
+        270 => ([0.0, 1.0, -1.0, 0.0, bbox[3], -bbox[0]], [0.0, 0.0, h, w]),
+        _ => ([1.0, 0.0, 0.0, 1.0, 0.0, 0.0], bbox),
+    }
+}
+
+// --- Form fields across merge and split ---
+
+/// Form fields gathered while copying pages, for the output's `/AcroForm`
+#[derive(Default)]
+struct FormCollector {
+    /// Top-level field names taken so far
+    names: std::collections::HashSet<String>,
+    /// Copied top-level fields
+    fields: Vec<u32>,
+    /// `/DA` and copied `/DR` of the first input form that has them
+    default_appearance: Option<String>,
+    default_resources: Option<String>,
+}
+
+impl FormCollector {
+    /// The `/AcroForm` dictionary, or `None` when no fields were copied
+    fn acroform(&self) -> Option<String> {
+        if self.fields.is_empty() {
+            return None;
+        }
+        let refs: Vec<String> = self.fields.iter().map(|id| format!("{} 0 R", id)).collect();
+        let mut dict = format!("<< /Fields [{}]", refs.join(" "));
+        if let Some(da) = &self.default_appearance {
+            dict.push_str(&format!(" /DA {}", pdf_text_string(da)));
+        }
+        if let Some(dr) = &self.default_resources {
+            dict.push_str(&format!(" /DR {}", dr));
+        }
+        dict.push_str(" >>");
+        Some(dict)
+    }
+}
+
+/// Copy pages `indices` of `doc` as form pages, scaled onto the options'
+/// `normalize_size` or kept at their native size, along with the form fields that have a
+/// widget on those pages. Fields are renamed when their top-level name was
+/// already taken by an earlier document, and the widgets of each page are
+/// moved in `doc` to where the page is placed.
+fn import_pages_with_fields(
+    doc: &mut crate::pdf::PdfDocument,
+    indices: &[usize],
+    options: &MergeOptions,
+    generator: &mut crate::pdf_generator::PdfGenerator,
+    forms: &mut FormCollector,
+) -> Result<Vec<FormPage>> {
+    let fields = claim_field_names(doc, &mut forms.names);
+
+    // Place each page first, moving its widgets to match
+    let mut placed = Vec::new();
+    for &index in indices {
+        let page_id = *doc.pages.get(index).ok_or(PdfError::InvalidPageRange {
+            start: index + 1,
+            end: index + 1,
+            page_count: Some(doc.pages.len()),
+        })?;
+        let (matrix, drawn) = page_form_matrix(doc, page_id);
+        let (placement, size) = match options.normalize_size {
+            Some(target) => (fit_matrix(drawn, target.width, target.height, ResizeMode::Fit), None),
+            None => (
+                [1.0, 0.0, 0.0, 1.0, -drawn[0], -drawn[1]],
+                Some((drawn[2] - drawn[0], drawn[3] - drawn[1])),
+            ),
+        };
+        let widgets = place_page_widgets(doc, page_id, concat_matrix(matrix, placement));
+        placed.push((index, placement, size, widgets));
+    }
+
+    let mut importer = ObjectImporter::new(doc);
+    importer.keep_compression = options.keep_compression;
+    let mut pages = Vec::new();
+    for (index, placement, size, _) in &placed {
+        let (form_id, _) = import_page_as_form(doc, *index, generator, &mut importer)?;
+        pages.push(FormPage {
+            content: form_placement_content(*placement).into_bytes(),
+            xobjects: vec![("Pg0".to_string(), form_id)],
+            size: *size,
+            annots: Vec::new(),
+        });
+    }
+
+    // Fields keep their `/Parent` links; those without a widget on the copied pages are left out
+    let on_pages: std::collections::HashSet<u32> = placed.iter().flat_map(|p| p.3.iter().copied()).collect();
+    importer.keep_parents = true;
+    let mut copied_any = false;
+    for field in fields {
+        if field_widgets(doc, field).iter().any(|w| on_pages.contains(w)) {
+            importer.import_value(generator, &crate::pdf::PdfValue::Reference(field, 0));
+            forms.fields.push(importer.id_map[&field]);
+            copied_any = true;
+        }
+    }
+    for (page, (.., widgets)) in pages.iter_mut().zip(&placed) {
+        page.annots = widgets.iter().filter_map(|w| importer.id_map.get(w).copied()).collect();
+    }
+
+    let acroform = doc
+        .get_object(doc.catalog)
+        .and_then(|c| c.as_dict())
+        .and_then(|c| doc.dict_get(c, "AcroForm"))
+        .and_then(|a| a.as_dict());
+    if copied_any && let Some(acroform) = acroform {
+        if forms.default_appearance.is_none()
+            && let Some(crate::pdf::PdfObject::String(da)) = doc.dict_get(acroform, "DA")
+        {
+            forms.default_appearance = Some(da.clone());
+        }
+        if forms.default_resources.is_none()
+            && let Some(dr) = acroform.get("DR")
+        {
+            forms.default_resources = Some(importer.import_value(generator, dr));
+        }
+    }
+    Ok(pages)
+}
+
+/// Give each top-level form field of `doc` a `/T` not yet in `used`, returning the field ids
+fn claim_field_names(
+    doc: &mut crate::pdf::PdfDocument,
+    used: &mut std::collections::HashSet<String>,
+) -> Vec<u32> {
+    let fields = doc.acroform_fields();
+    for id in &fields {
+        let Some(crate::pdf::PdfObject::Dictionary(dict)) = doc.objects.get_mut(id) else {
+            continue;
+        };
+        let Some(crate::pdf::PdfValue::Object(crate::pdf::PdfObject::String(name))) = dict.get("T") else {
+            continue;
+        };
+        let name = name.clone();
+        let mut unique = name.clone();
+        let mut suffix = 2;
+        while !used.insert(unique.clone()) {
+            unique = format!("{}_{}", name, suffix);
+            suffix += 1;
+        }
+        if unique != name {
+            log::info!("[form] Renamed field {} to {} to avoid a collision", name, unique);
+            dict.insert(
+                "T".to_string(),
+                crate::pdf::PdfValue::Object(crate::pdf::PdfObject::String(unique)),
+            );
+        }
+    }
+    fields
+}
+
+/// Widget annotations of a field: its kids without a `/T`, or the field itself
+fn field_widgets(doc: &crate::pdf::PdfDocument, field: u32) -> Vec<u32> {
+    let mut widgets = Vec::new();
+    let mut stack = vec![field];
+    let mut visited = std::collections::HashSet::new();
+    while let Some(id) = stack.pop() {
+        if !visited.insert(id) {
+            continue;
+        }
+        let Some(dict) = doc.get_object(id).and_then(|o| o.as_dict()) else {
+            continue;
+        };
+        match doc.dict_get(dict, "Kids") {
+            Some(crate::pdf::PdfObject::Array(kids)) => {
+                stack.extend(kids.iter().filter_map(|k| k.as_reference()));
+            }
+            _ => widgets.push(id),
+        }
+    }
+    widgets
+}
+
+/// Widget annotations on page `page_id`, with each `/Rect` mapped through
+/// `matrix` so the widgets stay over the content once the page is placed
+fn place_page_widgets(doc: &mut crate::pdf::PdfDocument, page_id: u32, matrix: [f32; 6]) -> Vec<u32> {
+    let widgets: Vec<u32> = match doc.page_attribute(page_id, "Annots") {
+        Some(crate::pdf::PdfObject::Array(items)) => items
+            .iter()
+            .filter_map(|v| v.as_reference())
+            .filter(|id| {
+                doc.get_object(*id)
+                    .and_then(|o| o.as_dict())
+                    .and_then(|d| doc.dict_get(d, "Subtype"))
+                    .and_then(|t| t.as_name())
+                    == Some("Widget")
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    for &id in &widgets {
+        let rect: Vec<f32> = match doc
+            .get_object(id)
+            .and_then(|o| o.as_dict())
+            .and_then(|d| doc.dict_get(d, "Rect"))
+        {
+            Some(crate::pdf::PdfObject::Array(items)) => items
+                .iter()
+                .filter_map(|v| doc.resolve(v).and_then(|o| o.as_number()))
+                .map(|n| n as f32)
+                .collect(),
+            _ => continue,
+        };
+        if rect.len() != 4 {
+            continue;
+        }
+        let apply = |x: f32, y: f32| {
+            (matrix[0] * x + matrix[2] * y + matrix[4], matrix[1] * x + matrix[3] * y + matrix[5])
+        };
+        let (x0, y0) = apply(rect[0], rect[1]);
+        let (x1, y1) = apply(rect[2], rect[3]);
+        let placed = [x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)];
+        if let Some(crate::pdf::PdfObject::Dictionary(dict)) = doc.objects.get_mut(&id) {
+            let values = placed
+                .iter()
+                .map(|&n| crate::pdf::PdfValue::Object(crate::pdf::PdfObject::Number(n as f64)))
+                .collect();
+            dict.insert("Rect".to_string(), crate::pdf::PdfValue::Object(crate::pdf::PdfObject::Array(values)));
+        }
+    }
+    widgets
+}
+
+/// Combine two affine matrices: `first` is applied, then `second`
+fn concat_matrix(first: [f32; 6], second: [f32; 6]) -> [f32; 6] {
+    let (a, b) = (first, second);
+    [
+        a[0] * b[0] + a[1] * b[2],
+        a[0] * b[1] + a[1] * b[3],
+        a[2] * b[0] + a[3] * b[2],
+        a[2] * b[1] + a[3] * b[3],
+        a[4] * b[0] + a[5] * b[2] + b[4],
+        a[4] * b[1] + a[5] * b[3] + b[5],
+    ]
+}
+
+/// Extract metadata from a PDF document
+pub fn extract_metadata_from_pdf(doc: &crate::pdf::PdfDocument) -> Result<PdfMetadata> {
+    let mut metadata = PdfMetadata::new();
+
+    // Look for the Info dictionary in the trailer
+    // For now, we'll do a simple search for metadata-like objects
+    for (_id, obj) in &doc.objects {
+        if let crate::pdf::PdfObject::Dictionary(data) = obj {
+            // Convert dictionary to a string representation for parsing
+            let dict_str = dict_to_string(data);
+            if dict_str.contains("/Title") {
+                if let Some(title) = extract_pdf_string_field(&dict_str, "/Title") {
+                    metadata.title = Some(title);
+                }
+            }
+            if dict_str.contains("/Author") {
+                if let Some(author) = extract_pdf_string_field(&dict_str, "/Author") {
+                    metadata.author = Some(author);
+                }
+            }
+            if dict_str.contains("/Subject") {
+                if let Some(subject) = extract_pdf_string_field(&dict_str, "/Subject") {
+                    metadata.subject = Some(subject);
+                }
+            }
+            if dict_str.contains("/Keywords") {
+                if let Some(keywords) = extract_pdf_string_field(&dict_str, "/Keywords") {
+                    metadata.keywords = Some(keywords);
+                }
+            }
+            if dict_str.contains("/Creator") {
+                if let Some(creator) = extract_pdf_string_field(&dict_str, "/Creator") {
+                    metadata.creator = Some(creator);
+                }
+            }
+            if let Some(crate::pdf::PdfValue::Object(crate::pdf::PdfObject::Name(name))) = data.get("Trapped") {
+                metadata.trapped = match name.as_str() {
+                    "True" => Some(Trapped::True),
+                    "False" => Some(Trapped::False),
+                    _ => Some(Trapped::Unknown),
+                };
+            }
+        }
+    }
+
+    Ok(metadata)
+}
+
+/// Pretty-print object `id` for debugging: one dictionary entry per line
+/// (sorted by key), plus the data length for streams
+pub fn describe_object(doc: &crate::pdf::PdfDocument, id: u32) -> Option<String> {
+    let obj = doc.get_object(id)?;
+    let mut out = format!("{} 0 obj\n", id);
+    match obj {
+        crate::pdf::PdfObject::Dictionary(dict) | crate::pdf::PdfObject::Stream { dictionary: dict, .. } => {
+            let mut keys: Vec<&String> = dict.keys().collect();
+            keys.sort();
+            out.push_str("<<\n");
+            for key in keys {
+                out.push_str(&format!("  /{} {}\n", key, value_to_string(&dict[key])));
+            }
+            out.push_str(">>\n");
+            if let crate::pdf::PdfObject::Stream { data, .. } = obj {
+                out.push_str(&format!("stream ({} bytes)\n", data.len()));
+            }
+        }
+        other => out.push_str(&format!("{}\n", object_to_string(other))),
+    }
+    out.push_str("endobj\n");
+    Some(out)
+}
+
+/// Render the page tree as an indented outline, starting at the catalog's `/Pages`
+pub fn describe_page_tree(doc: &crate::pdf::PdfDocument) -> String {
+    let mut out = format!("Catalog {} 0 R\n", doc.catalog);
+    let root = doc
+        .get_object(doc.catalog)
+        .and_then(|c| c.as_dict())
+        .and_then(|d| d.get("Pages"))
+        .and_then(|v| v.as_reference());
+    match root {
+        Some(root) => describe_page_node(doc, root, 1, &mut Vec::new(), &mut out),
+        None => out.push_str("  (no /Pages entry)\n"),
+    }
+    out
+}
+
+fn describe_page_node(
+    doc: &crate::pdf::PdfDocument,
+    id: u32,
+    depth: usize,
+    visited: &mut Vec<u32>,
+    out: &mut String,
+) {
+    let indent = "  ".repeat(depth);
+    if visited.contains(&id) {
+        out.push_str(&format!("{}{} 0 R (cycle)\n", indent, id));
+        return;
+    }
+    visited.push(id);
+
+    let Some(dict) = doc.get_object(id).and_then(|o| o.as_dict()) else {
+        out.push_str(&format!("{}{} 0 R (missing)\n", indent, id));
+        return;
+    };
+    match dict.get("Type").and_then(|t| t.as_name()) {
+        Some("Pages") => {
+            let count = dict.get("Count").map(value_to_string).unwrap_or_else(|| "?".into());
+            out.push_str(&format!("{}Pages {} 0 R (Count {})\n", indent, id, count));
+            if let Some(crate::pdf::PdfObject::Array(kids)) = dict.get("Kids").and_then(|k| doc.resolve(k)) {
+                for kid in kids {
+                    if let Some(kid_id) = kid.as_reference() {
+                        describe_page_node(doc, kid_id, depth + 1, visited, out);
+                    }
+                }
+            }
+        }
+        _ => {
+            let index = doc.pages.iter().position(|&p| p == id).map(|i| i + 1);
+            let media_box = page_media_box(doc, id);
+            out.push_str(&format!(
+                "{}Page {} 0 R{} MediaBox [{} {} {} {}]\n",
+                indent,
+                id,
+                index.map(|i| format!(" (#{})", i)).unwrap_or_default(),
+                media_box[0], media_box[1], media_box[2], media_box[3]
+            ));
+        }
+    }
+}
+
+/// Convert a PDF dictionary HashMap to a string representation
+fn dict_to_string(dict: &std::collections::HashMap<String, crate::pdf::PdfValue>) -> String {
+    let mut parts = Vec::new();
+    for (key, value) in dict {
+        parts.push(format!("/{} {}", key, value_to_string(value)));
+    }
+    parts.join(" ")
+}
+
+/// Convert a PdfValue to its string representation
+fn value_to_string(value: &crate::pdf::PdfValue) -> String {
+    match value {
+        crate::pdf::PdfValue::Object(obj) => object_to_string(obj),
+        crate::pdf::PdfValue::Reference(id, generation) => format!("{} {} R", id, generation),
+    }
+}
+
+/// Convert a PdfObject to its string representation
+fn object_to_string(obj: &crate::pdf::PdfObject) -> String {
+    match obj {
+        crate::pdf::PdfObject::Dictionary(dict) => {
+            let entries: Vec<String> = dict.iter()
+                .map(|(k, v)| format!("/{} {}", k, value_to_string(v)))
+                .collect();
+            format!("<< {} >>", entries.join(" "))
+        }
+        crate::pdf::PdfObject::Stream { dictionary: _, data: _ } => {
+            "<< stream >>".to_string()
+        }
+        crate::pdf::PdfObject::Array(arr) => {
+            let elems: Vec<String> = arr.iter().map(value_to_string).collect();
+            format!("[{}]", elems.join(" "))
+        }
+        crate::pdf::PdfObject::String(s) => pdf_text_string(s),
+        crate::pdf::PdfObject::Number(n) => n.to_string(),
+        crate::pdf::PdfObject::Boolean(b) => {
+            if *b { "true" } else { "false" }.to_string()
+        }
+        crate::pdf::PdfObject::Null => "null".to_string(),
+        crate::pdf::PdfObject::Reference(id, generation) => format!("{} {} R", id, generation),
+        crate::pdf::PdfObject::Name(n) => format!("/{}", n),
+    }
+}
+
+/// Extract a string field value from PDF dictionary content
+fn extract_pdf_string_field(content: &str, field: &str) -> Option<String> {
+    // Find the field and extract the string value
+    // Format: /Field (value) or /Field <value>
+    // Look for the field name followed by optional whitespace and opening parenthesis
+    let field_pattern_start = format!("{} ", field);
+    if let Some(start) = content.find(&field_pattern_start) {
+        // Find the opening parenthesis after the field name
+        let after_field = &content[start + field_pattern_start.len()..];
+        if let Some(paren_start) = after_field.find('(') {
+            let value_start = start + field_pattern_start.len() + paren_start + 1;
+            // Find the closing parenthesis, handling escaped parentheses
+            let mut paren_count = 1;
+            let mut value_end = value_start;
+            let chars: Vec<char> = content[value_start..].chars().collect();
+            let mut i = 0;
+            while i < chars.len() && paren_count > 0 {
+                if chars[i] == '\\' && i + 1 < chars.len() {
+                    // Escaped character, skip it
+                    i += 2;
+                    continue;
+                }
+                if chars[i] == '(' {
+                    paren_count += 1;
+                } else if chars[i] == ')' {
+                    paren_count -= 1;
+                }
+                if paren_count > 0 {
+                    value_end = value_start + i + 1;
+                }
+                i += 1;
+            }
+            let value = &content[value_start..value_end];
+            // Unescape the string
+            Some(unescape_pdf_string(value))
+        } else {
+            None
+        }
+    } else {
+        None
+    }
+}
+
+/// Unescape a PDF string (handle escape sequences)
+fn unescape_pdf_string(s: &str) -> String {
+    let mut result = String::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(next) = chars.next() {
+                match next {
+                    'n' => result.push('\n'),
+                    'r' => result.push('\r'),
+                    't' => result.push('\t'),
+                    'b' => result.push('\x08'),
+                    'f' => result.push('\x0c'),
+                    '(' | ')' | '\\' => result.push(next),
+                    '0'..='7' => {
+                        // Octal escape sequence (up to 3 digits)
+                        let mut octal = String::from(next);
+                        if let Some(&c) = chars.peek() {
+                            if c >= '0' && c <= '7' {
+                                chars.next();
+                                octal.push(c);
+                                if let Some(&c) = chars.peek() {
+                                    if c >= '0' && c <= '7' {
+                                        chars.next();
+                                        octal.push(c);
+                                    }
+                                }
+                            }
+                        }
+                        if let Ok(code) = u8::from_str_radix(&octal, 8) {
+                            result.push(code as char);
+                        }
+                    }
+                    _ => result.push(next),
+                }
+            }
+        } else {
+            result.push(c);
+        }
+    }
+
+    result
+}
+
+/// Merge metadata from two sources, with new_metadata taking precedence
+pub fn merge_metadata(base: &PdfMetadata, new_metadata: &PdfMetadata) -> PdfMetadata {
+    let mut merged = base.clone();
+    if new_metadata.title.is_some() {
+        merged.title = new_metadata.title.clone();
+    }
+    if new_metadata.author.is_some() {
+        merged.author = new_metadata.author.clone();
+    }
+    if new_metadata.subject.is_some() {
+        merged.subject = new_metadata.subject.clone();
+    }
+    if new_metadata.keywords.is_some() {
+        merged.keywords = new_metadata.keywords.clone();
+    }
+    if new_metadata.creator.is_some() {
+        merged.creator = new_metadata.creator.clone();
+    }
+    if new_metadata.trapped.is_some() {
+        merged.trapped = new_metadata.trapped;
+    }
+    if new_metadata.creation_date.is_some() {
+        merged.creation_date = new_metadata.creation_date;
+    }
+    if new_metadata.mod_date.is_some() {
+        merged.mod_date = new_metadata.mod_date;
+    }
+    // Merge custom fields, with new_metadata taking precedence
+    for (key, value) in &new_metadata.custom_fields {
+        merged.custom_fields.insert(key.clone(), value.clone());
+    }
+    merged
+}
+
+/// Line style of an annotation border (the `/S` entry of `/BS`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BorderStyle {
+    /// A box around the rectangle
+    Solid,
+    /// A dashed box around the rectangle
+    Dashed,
+    /// A single line along the bottom of the rectangle
+    Underline,
+}
+
+impl BorderStyle {
+    fn as_pdf_name(self) -> &'static str {
+        match self {
+            BorderStyle::Solid => "S",
+            BorderStyle::Dashed => "D",
+            BorderStyle::Underline => "U",
+        }
+    }
+}
+
+/// Visible border of an annotation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnnotationBorder {
+    /// Line width in points
+    pub width: f32,
+    pub style: BorderStyle,
+}
+
+/// `/C` and `/BS` entries for an annotation; `hidden` is used when no border is set
+fn annotation_appearance(
+    color: Option<crate::pdf_generator::Color>,
+    border: Option<AnnotationBorder>,
+    hidden: &str,
+) -> String {
+    let mut out = String::new();
+    match border {
+        Some(b) => out.push_str(&format!("/BS << /W {} /S /{} >>\n", b.width, b.style.as_pdf_name())),
+        None => out.push_str(hidden),
+    }
+    if let Some(c) = color {
+        out.push_str(&format!("/C [{} {} {}]\n", c.r, c.g, c.b));
+    }
+    out
+}
+
+/// A text annotation to be placed on a PDF page
+#[derive(Debug, Clone)]
+pub struct TextAnnotation {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub content: String,
+    pub title: String,
+    /// Icon and popup color
+    pub color: Option<crate::pdf_generator::Color>,
+    pub border: Option<AnnotationBorder>,
+}
+
+impl TextAnnotation {
+    /// Set the icon color
+    pub fn with_color(mut self, color: crate::pdf_generator::Color) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    /// Draw a border of `width` points around the annotation
+    pub fn with_border(mut self, width: f32, style: BorderStyle) -> Self {
+        self.border = Some(AnnotationBorder { width, style });
+        self
+    }
+}
+
+/// A link annotation (clickable URL region)
+#[derive(Debug, Clone)]
+pub struct LinkAnnotation {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub url: String,
+    /// Border color; only visible together with `border`
+    pub color: Option<crate::pdf_generator::Color>,
+    /// Visible box or underline; `None` keeps the link invisible
+    pub border: Option<AnnotationBorder>,
+}
+
+impl LinkAnnotation {
+    /// Set the border color
+    pub fn with_color(mut self, color: crate::pdf_generator::Color) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    /// Make the link visible with a box or underline of `width` points
+    pub fn with_border(mut self, width: f32, style: BorderStyle) -> Self {
+        self.border = Some(AnnotationBorder { width, style });
+        self
+    }
+}
+
+/// A highlight annotation (colored rectangle over text)
+#[derive(Debug, Clone)]
+pub struct HighlightAnnotation {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub color_r: f32,
+    pub color_g: f32,
+    pub color_b: f32,
+    /// Further line segments when the highlighted text wraps onto later lines
+    pub continued: Vec<Rect>,
+}
+
+impl HighlightAnnotation {
+    /// Highlight text spanning several lines, one rectangle per line segment
+    pub fn spanning(segments: &[Rect], color: crate::pdf_generator::Color) -> Self {
+        let first = segments.first().copied().unwrap_or(Rect::new(0.0, 0.0, 0.0, 0.0));
+        HighlightAnnotation {
+            x: first.x,
+            y: first.y,
+            width: first.width,
+            height: first.height,
+            color_r: color.r,
+            color_g: color.g,
+            color_b: color.b,
+            continued: segments.iter().skip(1).copied().collect(),
+        }
+    }
+
+    /// Every line segment covered, starting with the first
+    pub fn segments(&self) -> Vec<Rect> {
+        let mut all = vec![Rect::new(self.x, self.y, self.width, self.height)];
+        all.extend(self.continued.iter().copied());
+        all
+    }
+
+    /// `/Rect` and `/QuadPoints` entries; each segment contributes 8 numbers
+    fn geometry(&self) -> String {
+        let segments = self.segments();
+        let x0 = segments.iter().map(|r| r.x).fold(f32::INFINITY, f32::min);
+        let y0 = segments.iter().map(|r| r.y).fold(f32::INFINITY, f32::min);
+        let x1 = segments.iter().map(|r| r.x + r.width).fold(f32::NEG_INFINITY, f32::max);
+        let y1 = segments.iter().map(|r| r.y + r.height).fold(f32::NEG_INFINITY, f32::max);
+        let quads: Vec<String> = segments
+            .iter()
+            .map(|r| {
+                let (right, top) = (r.x + r.width, r.y + r.height);
+                format!("{} {} {} {} {} {} {} {}", r.x, top, right, top, r.x, r.y, right, r.y)
+            })
+            .collect();
+        format!("/Rect [{} {} {} {}]\n/QuadPoints [{}]\n", x0, y0, x1, y1, quads.join(" "))
+    }
+}
+
+/// Create a PDF with text, link, and highlight annotations
+pub fn create_pdf_with_all_annotations(
+    output_file: &str,
+    text: &str,
+    annotations: &[TextAnnotation],
+    links: &[LinkAnnotation],
+    highlights: &[HighlightAnnotation],
+) -> Result<()> {
+    let elements = crate::elements::parse_markdown(text);
+    let layout = crate::pdf_generator::PageLayout::portrait();
+    let page_streams = build_page_streams(&elements, 12.0, true, layout)?;
+
+    let mut generator = crate::pdf_generator::PdfGenerator::new();
+    let mut annot_ids: Vec<u32> = Vec::new();
+
+    for annot in annotations {
+        let annot_dict = format!(
+            "<< /Type /Annot\n/Subtype /Text\n/Rect [{} {} {} {}]\n/Contents {}\n/T {}\n{}/Open false\n>>\n",
+            annot.x, annot.y, annot.x + annot.width, annot.y + annot.height,
+            pdf_text_string(&annot.content), pdf_text_string(&annot.title),
+            annotation_appearance(annot.color, annot.border, ""),
+        );
+        annot_ids.push(generator.add_object(annot_dict));
     }
 
     for link in links {
         let link_dict = format!(
-            "<< /Type /Annot\n/Subtype /Link\n/Rect [{} {} {} {}]\n/Border [0 0 0]\n/A << /Type /Action\n/S /URI\n/URI ({}) >>\n>>\n",
+            "<< /Type /Annot\n/Subtype /Link\n/Rect [{} {} {} {}]\n{}/A << /Type /Action\n/S /URI\n/URI ({}) >>\n>>\n",
             link.x, link.y, link.x + link.width, link.y + link.height,
+            annotation_appearance(link.color, link.border, "/Border [0 0 0]\n"),
             escape_pdf_meta(&link.url),
         );
-        annot_ids.push(generator.add_object(link_dict));
+        annot_ids.push(generator.add_object(link_dict));
+    }
+
+    for hl in highlights {
+        let hl_dict = format!(
+            "<< /Type /Annot\n/Subtype /Highlight\n{}/C [{} {} {}]\n>>\n",
+            hl.geometry(),
+            hl.color_r, hl.color_g, hl.color_b,
+        );
+        annot_ids.push(generator.add_object(hl_dict));
+    }
+
+    // One font object shared by every page
+    let font_id = generator.add_object("<< /Type /Font\n/Subtype /Type1\n/BaseFont /Helvetica\n>>\n".to_string());
+    let pages_obj_id = generator.next_id + (page_streams.len() as u32) * 2;
+    let mut page_ids = Vec::new();
+
+    for (i, page_stream) in page_streams.iter().enumerate() {
+        let content_id = generator.add_stream_object(
+            format!("<< /Length {} >>\n", page_stream.len()),
+            page_stream.clone(),
+        );
+        let annots_str = if i == 0 && !annot_ids.is_empty() {
+            let refs: Vec<String> = annot_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+            format!("/Annots [{}]\n", refs.join(" "))
+        } else {
+            String::new()
+        };
+        let page_dict = format!(
+            "<< /Type /Page\n/Parent {} 0 R\n/MediaBox [0 0 {} {}]\n/Contents {} 0 R\n{}/Resources << /Font << /F1 {} 0 R >> >>\n>>\n",
+            pages_obj_id, layout.width, layout.height, content_id, annots_str, font_id
+        );
+        let page_id = generator.add_object(page_dict);
+        page_ids.push(page_id);
+    }
+
+    let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+    let pages_dict = format!("<< /Type /Pages\n/Kids [{}]\n/Count {}\n>>\n", kids.join(" "), page_ids.len());
+    let actual_pages_id = generator.add_object(pages_dict);
+    assert_eq!(actual_pages_id, pages_obj_id);
+    generator.add_object(format!("<< /Type /Catalog\n/Pages {} 0 R\n>>\n", actual_pages_id));
+
+    let pdf_data = generator.generate();
+    let mut file = std::fs::File::create(output_file)?;
+    std::io::Write::write_all(&mut file, &pdf_data)?;
+    log::info!(
+        "[annotate] Created {} with {} text, {} link, {} highlight annotations",
+        output_file, annotations.len(), links.len(), highlights.len()
+    );
+    Ok(())
+}
+
+/// Create a single-page PDF with text annotations (backward compatible)
+pub fn create_pdf_with_annotations(
+    output_file: &str,
+    text: &str,
+    annotations: &[TextAnnotation],
+    links: &[LinkAnnotation],
+) -> Result<()> {
+    let elements = crate::elements::parse_markdown(text);
+    let layout = crate::pdf_generator::PageLayout::portrait();
+
+    // Build page content
+    let page_streams = build_page_streams(&elements, 12.0, true, layout)?;
+
+    let mut generator = crate::pdf_generator::PdfGenerator::new();
+
+    // Build annotation objects first, collect their IDs
+    let mut annot_ids: Vec<u32> = Vec::new();
+
+    for annot in annotations {
+        let annot_dict = format!(
+            "<< /Type /Annot\n\
+             /Subtype /Text\n\
+             /Rect [{} {} {} {}]\n\
+             /Contents {}\n\
+             /T {}\n\
+             {}\
+             /Open false\n\
+             >>\n",
+            annot.x,
+            annot.y,
+            annot.x + annot.width,
+            annot.y + annot.height,
+            pdf_text_string(&annot.content),
+            pdf_text_string(&annot.title),
+            annotation_appearance(annot.color, annot.border, ""),
+        );
+        annot_ids.push(generator.add_object(annot_dict));
+    }
+
+    for link in links {
+        let link_dict = format!(
+            "<< /Type /Annot\n\
+             /Subtype /Link\n\
+             /Rect [{} {} {} {}]\n\
+             {}\
+             /A << /Type /Action\n/S /URI\n/URI ({}) >>\n\
+             >>\n",
+            link.x,
+            link.y,
+            link.x + link.width,
+            link.y + link.height,
+            annotation_appearance(link.color, link.border, "/Border [0 0 0]\n"),
+            escape_pdf_meta(&link.url),
+        );
+        annot_ids.push(generator.add_object(link_dict));
+    }
+
+    // Then the font shared by every page, content streams and pages
+    let font_id = generator.add_object("<< /Type /Font\n/Subtype /Type1\n/BaseFont /Helvetica\n>>\n".to_string());
+    let pages_obj_id = generator.next_id + (page_streams.len() as u32) * 2;
+
+    let mut page_ids = Vec::new();
+    for (i, page_stream) in page_streams.iter().enumerate() {
+        let content_id = generator.add_stream_object(
+            format!("<< /Length {} >>\n", page_stream.len()),
+            page_stream.clone(),
+        );
+
+        // Only first page gets annotations
+        let annots_str = if i == 0 && !annot_ids.is_empty() {
+            let refs: Vec<String> = annot_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+            format!("/Annots [{}]\n", refs.join(" "))
+        } else {
+            String::new()
+        };
+
+        let page_dict = format!(
+            "<< /Type /Page\n\
+             /Parent {} 0 R\n\
+             /MediaBox [0 0 {} {}]\n\
+             /Contents {} 0 R\n\
+             {}\
+             /Resources << /Font << /F1 {} 0 R >> >>\n\
+             >>\n",
+            pages_obj_id, layout.width, layout.height, content_id, annots_str, font_id
+        );
+        let page_id = generator.add_object(page_dict);
+        page_ids.push(page_id);
+    }
+
+    let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+    let pages_dict = format!(
+        "<< /Type /Pages\n/Kids [{}]\n/Count {}\n>>\n",
+        kids.join(" "),
+        page_ids.len()
+    );
+    let actual_pages_id = generator.add_object(pages_dict);
+    assert_eq!(actual_pages_id, pages_obj_id);
+
+    let catalog_dict = format!(
+        "<< /Type /Catalog\n/Pages {} 0 R\n>>\n",
+        actual_pages_id
+    );
+    generator.add_object(catalog_dict);
+
+    let pdf_data = generator.generate();
+    let mut file = std::fs::File::create(output_file)?;
+    std::io::Write::write_all(&mut file, &pdf_data)?;
+    log::info!(
+        "[annotate] Created {} with {} text annotations, {} link annotations",
+        output_file,
+        annotations.len(),
+        links.len()
+    );
+    Ok(())
+}
+
+/// Create a PDF page with multiple images placed at specified positions
+pub fn create_pdf_with_images(
+    output_file: &str,
+    images: &[(String, f32, f32, f32, f32)], // (path, x, y, width, height)
+) -> Result<()> {
+    if images.is_empty() {
+        return Err(PdfError::InvalidInput("No images provided".into()));
+    }
+
+    let mut generator = crate::pdf_generator::PdfGenerator::new();
+    let mut image_refs: Vec<(u32, String)> = Vec::new(); // (obj_id, name)
+
+    // Create image XObjects (supports JPEG, PNG, BMP)
+    for (i, (path, _, _, _, _)) in images.iter().enumerate() {
+        let info = crate::image::load_image(path)?;
+        let name = format!("Im{}", i + 1);
+        let image_id = crate::image::create_image_object(&mut generator, info)?;
+        image_refs.push((image_id, name));
+    }
+
+    // Build content stream with all images
+    let mut content = Vec::new();
+    for (i, (_, x, y, w, h)) in images.iter().enumerate() {
+        let name = &image_refs[i].1;
+        content.extend_from_slice(b"q\n");
+        content.extend_from_slice(format!("{} 0 0 {} {} {} cm\n", w, h, x, y).as_bytes());
+        content.extend_from_slice(format!("/{} Do\n", name).as_bytes());
+        content.extend_from_slice(b"Q\n");
+    }
+
+    let content_id = generator.add_stream_object(
+        format!("<< /Length {} >>\n", content.len()),
+        content,
+    );
+
+    // Build XObject resource dictionary
+    let xobj_entries: Vec<String> = image_refs
+        .iter()
+        .map(|(id, name)| format!("/{} {} 0 R", name, id))
+        .collect();
+    let xobj_dict = xobj_entries.join(" ");
+
+    let page_dict = format!(
+        "<< /Type /Page\n\
+         /Parent 0 0 R\n\
+         /MediaBox [0 0 612 792]\n\
+         /Contents {} 0 R\n\
+         /Resources << /XObject << {} >> >>\n\
+         >>\n",
+        content_id, xobj_dict
+    );
+    let page_id = generator.add_object(page_dict);
+
+    let pages_dict = format!(
+        "<< /Type /Pages\n/Kids [{} 0 R]\n/Count 1\n>>\n",
+        page_id
+    );
+    let pages_id = generator.add_object(pages_dict);
+
+    let catalog = format!("<< /Type /Catalog\n/Pages {} 0 R\n>>\n", pages_id);
+    generator.add_object(catalog);
+
+    let pdf_data = generator.generate();
+    fs::write(output_file, &pdf_data)?;
+    log::info!(
+        "[images] Created {} with {} images",
+        output_file,
+        images.len()
+    );
+    Ok(())
+}
+
+/// Tile images across pages in a `cols` x `rows` grid, e.g. to proof a folder of photos.
+///
+/// Each image is scaled down to fit its cell, keeping its aspect ratio, and
+/// centered in it. With `captions`, the file name is written under each image.
+/// Images fill the cells left to right, top to bottom, starting a new page
+/// whenever the grid is full.
+pub fn contact_sheet(
+    image_paths: &[&str],
+    output_file: &str,
+    cols: usize,
+    rows: usize,
+    layout: crate::pdf_generator::PageLayout,
+    captions: bool,
+) -> Result<()> {
+    if image_paths.is_empty() {
+        return Err(PdfError::InvalidInput("No images provided".into()));
+    }
+    if cols == 0 || rows == 0 {
+        return Err(PdfError::InvalidInput("Contact sheet grid needs at least one row and column".into()));
+    }
+
+    const PADDING: f32 = 6.0;
+    const CAPTION_SIZE: f32 = 8.0;
+    let caption_height = if captions { CAPTION_SIZE * 1.5 } else { 0.0 };
+    let cell_width = layout.content_width() / cols as f32;
+    let cell_height = (layout.content_top() - layout.margin_bottom) / rows as f32;
+    let (box_width, box_height) = (cell_width - PADDING * 2.0, cell_height - PADDING * 2.0 - caption_height);
+    if box_width <= 0.0 || box_height <= 0.0 {
+        return Err(PdfError::InvalidInput(format!(
+            "A {}x{} grid leaves no room for images on a {}x{} page",
+            cols, rows, layout.width, layout.height
+        )));
+    }
+
+    let mut generator = crate::pdf_generator::PdfGenerator::new();
+    let mut images = Vec::new();
+    for path in image_paths {
+        let info = crate::image::load_image(path)?;
+        let (width, height) = crate::image::scale_to_fit(info.width, info.height, box_width, box_height);
+        let id = crate::image::create_image_object(&mut generator, info)?;
+        images.push((id, width, height));
+    }
+    let font_id = generator.add_object("<< /Type /Font\n/Subtype /Type1\n/BaseFont /Helvetica\n>>\n".to_string());
+
+    let per_page = cols * rows;
+    let page_count = images.len().div_ceil(per_page);
+    // Each page adds its content stream and page object
+    let pages_obj_id = generator.next_id + page_count as u32 * 2;
+    let mut page_ids = Vec::new();
+    for (page, chunk) in images.chunks(per_page).enumerate() {
+        let mut content = Vec::new();
+        let mut xobjects = Vec::new();
+        for (slot, (id, width, height)) in chunk.iter().enumerate() {
+            let index = page * per_page + slot;
+            let (col, row) = (slot % cols, slot / cols);
+            let cell_x = layout.margin_left + col as f32 * cell_width;
+            let cell_top = layout.content_top() - row as f32 * cell_height;
+            let x = cell_x + (cell_width - width) / 2.0;
+            let y = cell_top - PADDING - (box_height - height) / 2.0 - height;
+            let name = format!("Im{}", index + 1);
+            content.extend_from_slice(format!("q\n{} 0 0 {} {} {} cm\n/{} Do\nQ\n", width, height, x, y, name).as_bytes());
+            xobjects.push(format!("/{} {} 0 R", name, id));
+
+            if captions {
+                let file_name = std::path::Path::new(image_paths[index])
+                    .file_name()
+                    .map_or_else(|| image_paths[index].to_string(), |n| n.to_string_lossy().to_string());
+                let text_width = crate::font_metrics::text_width("Helvetica", &file_name, CAPTION_SIZE);
+                let caption_x = cell_x + ((cell_width - text_width) / 2.0).max(PADDING);
+                let caption_y = cell_top - cell_height + PADDING + CAPTION_SIZE * 0.25;
+                content.extend_from_slice(
+                    format!(
+                        "BT\n/F1 {} Tf\n1 0 0 1 {} {} Tm\n{} Tj\nET\n",
+                        CAPTION_SIZE, caption_x, caption_y, pdf_text_string(&file_name)
+                    )
+                    .as_bytes(),
+                );
+            }
+        }
+        let content_id = generator.add_stream_object(format!("<< /Length {} >>\n", content.len()), content);
+        let page_id = generator.add_object(format!(
+            "<< /Type /Page\n/Parent {} 0 R\n/MediaBox [0 0 {} {}]\n/Contents {} 0 R\n/Resources << /Font << /F1 {} 0 R >> /XObject << {} >> >>\n>>\n",
+            pages_obj_id, layout.width, layout.height, content_id, font_id, xobjects.join(" ")
+        ));
+        page_ids.push(page_id);
+    }
+
+    let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+    let pages_id = generator.add_object(format!(
+        "<< /Type /Pages\n/Kids [{}]\n/Count {}\n>>\n",
+        kids.join(" "),
+        page_ids.len()
+    ));
+    if pages_id != pages_obj_id {
+        return Err(PdfError::Other(anyhow::anyhow!(
+            "Page tree written as object {} but pages refer to {}",
+            pages_id,
+            pages_obj_id
+        )));
+    }
+    generator.add_object(format!("<< /Type /Catalog\n/Pages {} 0 R\n>>\n", pages_id));
+
+    fs::write(output_file, generator.generate())?;
+    log::info!(
+        "[contact] Created {} with {} images on {} pages",
+        output_file,
+        images.len(),
+        page_ids.len()
+    );
+    Ok(())
+}
+
+/// Add a diagonal text watermark to every page of a PDF.
+///
+/// The watermark is rendered as semi-transparent gray text rotated 45°.
+///
+/// # Arguments
+///
+/// * `input_file` - Path to the input PDF file
+/// * `output_file` - Path where the watermarked PDF will be written
+/// * `watermark_text` - Text to use as watermark
+/// * `font_size` - Size of the watermark font
+/// * `opacity` - Opacity of the watermark (0.0 = transparent, 1.0 = opaque)
+///
+/// # Returns
+///
+/// Returns `Ok(())` if successful, or an error if watermarking fails.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use pdfrs::pdf_ops;
+///
+/// pdf_ops::watermark_pdf(
+///     "input.pdf",
+///     "output.pdf",
+///     "CONFIDENTIAL",
+///     48.0,
+///     0.3,
+/// ).expect("Failed to add watermark");
+/// ```
+pub fn watermark_pdf(
+    input_file: &str,
+    output_file: &str,
+    watermark_text: &str,
+    font_size: f32,
+    opacity: f32,
+) -> Result<()> {
+    watermark_pdf_with_options(input_file, output_file, watermark_text, font_size, opacity, &OutputOptions::default())
+}
+
+/// Like [`watermark_pdf`], compressing the rewritten page content and warning
+/// about large output as `output` asks
+pub fn watermark_pdf_with_options(
+    input_file: &str,
+    output_file: &str,
+    watermark_text: &str,
+    font_size: f32,
+    opacity: f32,
+    output: &OutputOptions,
+) -> Result<()> {
+    let doc = crate::pdf::PdfDocument::load_from_file(input_file)?;
+    let all_streams = extract_page_streams(&doc);
+
+    if all_streams.is_empty() {
+        return Err(PdfError::InvalidInput(format!("No pages found in {}", input_file)));
+    }
+
+    let layout = crate::pdf_generator::PageLayout::portrait();
+    let watermark_stream = build_watermark_stream(watermark_text, font_size, opacity, &layout);
+
+    // Append watermark content to each page stream
+    let watermarked: Vec<Vec<u8>> = all_streams
+        .iter()
+        .map(|stream| {
+            let mut combined = stream.clone();
+            combined.extend_from_slice(&watermark_stream);
+            combined
+        })
+        .collect();
+
+    assemble_merged_pdf_with_output(output_file, &watermarked, "Helvetica", &layout, output)?;
+    log::info!(
+        "[watermark] Added watermark '{}' to {} pages in {}",
+        watermark_text,
+        watermarked.len(),
+        output_file
+    );
+    Ok(())
+}
+
+/// Build a content stream snippet that renders a diagonal watermark
+fn build_watermark_stream(text: &str, font_size: f32, opacity: f32, layout: &crate::pdf_generator::PageLayout) -> Vec<u8> {
+    let shown = pdf_show_string(text);
+    // Center of page
+    let cx = layout.width / 2.0;
+    let cy = layout.height / 2.0;
+    // 45° rotation matrix: cos(45)=0.707, sin(45)=0.707
+    let cos45: f32 = 0.7071;
+    let sin45: f32 = 0.7071;
+
+    let mut stream = Vec::new();
+    // Save graphics state, set transparency
+    stream.extend_from_slice(b"q\n");
+    stream.extend_from_slice(format!("{} {} {} rg\n", opacity, opacity, opacity).as_bytes());
+    stream.extend_from_slice(b"BT\n");
+    stream.extend_from_slice(format!("/F1 {} Tf\n", font_size).as_bytes());
+    // Text matrix: rotation + translation to center
+    stream.extend_from_slice(
+        format!(
+            "{} {} {} {} {} {} Tm\n",
+            cos45, sin45, -sin45, cos45, cx - 100.0, cy - 50.0
+        )
+        .as_bytes(),
+    );
+    stream.extend_from_slice(format!("{} Tj\n", shown).as_bytes());
+    stream.extend_from_slice(b"ET\n");
+    stream.extend_from_slice(b"Q\n");
+    stream
+}
+
+/// Form field types.
+///
+/// Represents the type of interactive form field that can be added to a PDF.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FormFieldType {
+    /// Text input field
+    Text,
+    /// Checkbox field
+    Checkbox,
+    /// Radio button field
+    Radio,
+    /// Dropdown/combobox field
+    Dropdown,
+    /// Digital signature field, written unsigned for a signer to fill
+    Signature,
+}
+
+/// Field flags written to a form field's `/Ff` entry (PDF 1.7, 12.7.3.1)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FieldFlags(pub u32);
+
+impl FieldFlags {
+    pub const NONE: FieldFlags = FieldFlags(0);
+    pub const READ_ONLY: FieldFlags = FieldFlags(1);
+    pub const REQUIRED: FieldFlags = FieldFlags(1 << 1);
+    pub const NO_EXPORT: FieldFlags = FieldFlags(1 << 2);
+    /// Text fields: may span several lines
+    pub const MULTILINE: FieldFlags = FieldFlags(1 << 12);
+    /// Text fields: the value is masked while typing
+    pub const PASSWORD: FieldFlags = FieldFlags(1 << 13);
+    pub const NO_TOGGLE_TO_OFF: FieldFlags = FieldFlags(1 << 14);
+    pub const RADIO: FieldFlags = FieldFlags(1 << 15);
+    pub const COMBO: FieldFlags = FieldFlags(1 << 17);
+    /// Choice fields: the user may type a value not in the list
+    pub const EDIT: FieldFlags = FieldFlags(1 << 18);
+    pub const DO_NOT_SPELL_CHECK: FieldFlags = FieldFlags(1 << 22);
+    /// Text fields: no scrolling past the visible area
+    pub const DO_NOT_SCROLL: FieldFlags = FieldFlags(1 << 23);
+    /// Text fields: evenly spaced character cells (needs `/MaxLen`)
+    pub const COMB: FieldFlags = FieldFlags(1 << 24);
+
+    /// Whether every bit of `other` is set
+    pub fn contains(self, other: FieldFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl std::ops::BitOr for FieldFlags {
+    type Output = FieldFlags;
+
+    fn bitor(self, rhs: FieldFlags) -> FieldFlags {
+        FieldFlags(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitOrAssign for FieldFlags {
+    fn bitor_assign(&mut self, rhs: FieldFlags) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// A form field to be added to a PDF.
+///
+/// Represents an interactive form field with its properties including
+/// position, dimensions, default value, options (for radio/dropdown), and
+/// whether the field is required.
+///
+/// # Fields
+///
+/// * `name` - Unique identifier for the form field
+/// * `field_type` - Type of form field (Text, Checkbox, Radio, Dropdown, Signature)
+/// * `x` - X position on the page (in PDF points)
+/// * `y` - Y position on the page (in PDF points)
+/// * `width` - Width of the field (in PDF points)
+/// * `height` - Height of the field (in PDF points)
+/// * `default_value` - Optional default value for the field
+/// * `options` - List of options (for radio buttons and dropdowns)
+/// * `required` - Whether the field must be filled
+/// * `page` - 1-indexed page the field is placed on (first page when `None`)
+/// * `flags` - Extra `/Ff` flags such as read-only or multiline
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use pdfrs::pdf_ops::{FieldFlags, FormField, FormFieldType};
+///
+/// let field = FormField {
+///     name: "firstName".to_string(),
+///     field_type: FormFieldType::Text,
+///     x: 100.0,
+///     y: 700.0,
+///     width: 200.0,
+///     height: 20.0,
+///     default_value: Some("John".to_string()),
+///     options: vec![],
+///     required: true,
+///     page: None,
+///     flags: FieldFlags::NONE,
+/// };
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FormField {
+    pub name: String,
+    pub field_type: FormFieldType,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub default_value: Option<String>,
+    pub options: Vec<String>, // For radio/dropdown
+    pub required: bool,
+    #[serde(default)]
+    pub page: Option<usize>,
+    #[serde(default)]
+    pub flags: FieldFlags,
+}
+
+impl FormField {
+    /// The `/Ff` value: the field's flags, `required` and the type's own bits
+    fn ff(&self, type_flags: FieldFlags) -> u32 {
+        let mut flags = self.flags | type_flags;
+        if self.required {
+            flags |= FieldFlags::REQUIRED;
+        }
+        flags.0
+    }
+}
+
+/// Default appearance of variable-text fields; `/Helv` is in the AcroForm's `/DR`
+const FORM_DEFAULT_APPEARANCE: &str = "/Helv 12 Tf 0 g";
+
+/// Create a PDF with an AcroForm containing interactive form fields
+pub fn create_pdf_with_form_fields(
+    output_file: &str,
+    text: &str,
+    form_fields: &[FormField],
+) -> Result<()> {
+    let elements = crate::elements::parse_markdown(text);
+    let layout = crate::pdf_generator::PageLayout::portrait();
+    let page_streams = build_page_streams(&elements, 12.0, true, layout)?;
+
+    let mut generator = crate::pdf_generator::PdfGenerator::new();
+    // Helvetica is the default field font, and labels signature appearances
+    let helv_id = generator.add_object(
+        "<< /Type /Font\n/Subtype /Type1\n/BaseFont /Helvetica\n/Encoding /WinAnsiEncoding\n>>\n".to_string(),
+    );
+    let mut field_ids: Vec<u32> = Vec::new();
+    // Widget annotations for each page
+    let mut widget_ids: Vec<Vec<u32>> = vec![Vec::new(); page_streams.len()];
+
+    // Create form fields and their widget annotations
+    for field in form_fields {
+        let page = field.page.unwrap_or(1);
+        if page == 0 || page > page_streams.len() {
+            return Err(PdfError::InvalidPageRange {
+                start: page,
+                end: page,
+                page_count: Some(page_streams.len()),
+            });
+        }
+        let (field_id, widgets) = add_form_field(&mut generator, field, helv_id);
+        field_ids.push(field_id);
+        widget_ids[page - 1].extend(widgets);
+    }
+
+    // Create AcroForm dictionary; SigFlags 1 marks a document with signature fields
+    let kids_refs: Vec<String> = field_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+    let sig_flags = if form_fields.iter().any(|f| f.field_type == FormFieldType::Signature) {
+        "/SigFlags 1\n"
+    } else {
+        ""
+    };
+    let acroform_dict = format!(
+        "<< /Fields [{}]\n/DR << /Font << /Helv {} 0 R >> >>\n/DA {}\n{}>>\n",
+        kids_refs.join(" "),
+        helv_id,
+        pdf_text_string(FORM_DEFAULT_APPEARANCE),
+        sig_flags
+    );
+    let acroform_id = generator.add_object(acroform_dict);
+
+    // Page text uses the same Helvetica object as the fields
+    let font_id = helv_id;
+    let pages_obj_id = generator.next_id + (page_streams.len() as u32) * 2;
+    let mut page_ids = Vec::new();
+
+    for (i, page_stream) in page_streams.iter().enumerate() {
+        let content_id = generator.add_stream_object(
+            format!("<< /Length {} >>\n", page_stream.len()),
+            page_stream.clone(),
+        );
+
+        let annots_str = if !widget_ids[i].is_empty() {
+            let refs: Vec<String> = widget_ids[i].iter().map(|id| format!("{} 0 R", id)).collect();
+            format!("/Annots [{}]\n", refs.join(" "))
+        } else {
+            String::new()
+        };
+
+        let page_dict = format!(
+            "<< /Type /Page\n\
+             /Parent {} 0 R\n\
+             /MediaBox [0 0 {} {}]\n\
+             /Contents {} 0 R\n\
+             {}\
+             /Resources << /Font << /F1 {} 0 R >> >>\n\
+             >>\n",
+            pages_obj_id, layout.width, layout.height, content_id, annots_str, font_id
+        );
+        let page_id = generator.add_object(page_dict);
+        page_ids.push(page_id);
+    }
+
+    let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+    let pages_dict = format!("<< /Type /Pages\n/Kids [{}]\n/Count {}\n>>\n", kids.join(" "), page_ids.len());
+    let actual_pages_id = generator.add_object(pages_dict);
+    assert_eq!(actual_pages_id, pages_obj_id);
+
+    let catalog_dict = format!(
+        "<< /Type /Catalog\n/Pages {} 0 R\n/AcroForm {} 0 R\n>>\n",
+        actual_pages_id, acroform_id
+    );
+    generator.add_object(catalog_dict);
+
+    let pdf_data = generator.generate();
+    let mut file = std::fs::File::create(output_file)?;
+    std::io::Write::write_all(&mut file, &pdf_data)?;
+    log::info!(
+        "[form] Created {} with {} form fields",
+        output_file,
+        form_fields.len()
+    );
+    Ok(())
+}
+
+/// Add a form field to the generator. Returns the field's object id (listed in
+/// `/AcroForm /Fields`) and the widget annotations to place on the page.
+/// `font_id` is the Helvetica font object that appearance text uses.
+fn add_form_field(
+    generator: &mut crate::pdf_generator::PdfGenerator,
+    field: &FormField,
+    font_id: u32,
+) -> (u32, Vec<u32>) {
+    match field.field_type {
+        FormFieldType::Signature => {
+            let appearance_id = add_signature_appearance(generator, field, font_id);
+            let mut dict = create_form_field_dict(field);
+            let end = dict.rfind(">>").unwrap_or(dict.len());
+            dict.insert_str(end, &format!("/AP << /N {} 0 R >>\n", appearance_id));
+            let id = generator.add_object(dict);
+            (id, vec![id])
+        }
+        FormFieldType::Checkbox => {
+            let off_id = add_button_appearance(generator, field, false);
+            let on_id = add_button_appearance(generator, field, true);
+            let mut dict = create_form_field_dict(field);
+            let end = dict.rfind(">>").unwrap_or(dict.len());
+            dict.insert_str(end, &format!("/AP << /N << /Yes {} 0 R /Off {} 0 R >> >>\n", on_id, off_id));
+            let id = generator.add_object(dict);
+            (id, vec![id])
+        }
+        FormFieldType::Radio => {
+            let states: Vec<String> = if field.options.is_empty() {
+                vec!["On".to_string()]
+            } else {
+                field.options.iter().map(|o| pdf_name_token(o)).collect()
+            };
+            let selected = field
+                .default_value
+                .as_deref()
+                .map(pdf_name_token)
+                .filter(|v| states.contains(v));
+
+            let appearances: Vec<(u32, u32)> = states
+                .iter()
+                .map(|_| {
+                    (
+                        add_button_appearance(generator, field, true),
+                        add_button_appearance(generator, field, false),
+                    )
+                })
+                .collect();
+
+            // Kids come first, so the parent's id is known before it is written
+            let parent_id = generator.next_id + states.len() as u32;
+            let mut kids = Vec::new();
+            for (i, (state, (on_id, off_id))) in states.iter().zip(&appearances).enumerate() {
+                // Buttons are stacked downwards from the field's position
+                let y = field.y - i as f32 * field.height * 1.5;
+                let appearance_state = if selected.as_ref() == Some(state) { state.as_str() } else { "Off" };
+                kids.push(generator.add_object(format!(
+                    "<< /Type /Annot\n/Subtype /Widget\n\
+                     /Parent {} 0 R\n\
+                     /Rect [{} {} {} {}]\n\
+                     /AP << /N << /{} {} 0 R /Off {} 0 R >> >>\n\
+                     /AS /{}\n\
+                     >>\n",
+                    parent_id,
+                    field.x, y, field.x + field.width, y + field.height,
+                    state, on_id, off_id,
+                    appearance_state
+                )));
+            }
+
+            let kid_refs: Vec<String> = kids.iter().map(|id| format!("{} 0 R", id)).collect();
+            let flags = field.ff(FieldFlags::RADIO | FieldFlags::NO_TOGGLE_TO_OFF);
+            let parent = generator.add_object(format!(
+                "<< /FT /Btn\n/T {}\n/Ff {}\n/V /{}\n/Kids [{}]\n>>\n",
+                pdf_text_string(&field.name),
+                flags,
+                selected.as_deref().unwrap_or("Off"),
+                kid_refs.join(" ")
+            ));
+            debug_assert_eq!(parent, parent_id);
+            (parent, kids)
+        }
+        FormFieldType::Text | FormFieldType::Dropdown => {
+            let id = generator.add_object(create_form_field_dict(field));
+            (id, vec![id])
+        }
+    }
+}
+
+/// Add a normal-appearance Form XObject for a checkbox or radio button.
+/// Every state draws the box (or circle); the "on" state adds a check mark (or dot).
+fn add_button_appearance(
+    generator: &mut crate::pdf_generator::PdfGenerator,
+    field: &FormField,
+    on: bool,
+) -> u32 {
+    let (w, h) = (field.width, field.height);
+    let mut ops = String::from("q\n0 0 0 RG\n1 w\n");
+    if field.field_type == FormFieldType::Radio {
+        let r = w.min(h) / 2.0;
+        ops.push_str(&circle_path(w / 2.0, h / 2.0, r - 0.5));
+        ops.push_str("S\n");
+        if on {
+            ops.push_str("0 0 0 rg\n");
+            ops.push_str(&circle_path(w / 2.0, h / 2.0, r * 0.45));
+            ops.push_str("f\n");
+        }
+    } else {
+        ops.push_str(&format!("0.5 0.5 {} {} re S\n", w - 1.0, h - 1.0));
+        if on {
+            ops.push_str(&format!(
+                "1.5 w\n{} {} m {} {} l {} {} l S\n",
+                w * 0.2, h * 0.5, w * 0.42, h * 0.25, w * 0.8, h * 0.78
+            ));
+        }
+    }
+    ops.push_str("Q\n");
+
+    generator.add_stream_object(
+        format!(
+            "<< /Type /XObject\n/Subtype /Form\n/BBox [0 0 {} {}]\n/Resources << >>\n/Length {}\n>>\n",
+            w, h, ops.len()
+        ),
+        ops.into_bytes(),
+    )
+}
+
+/// Add the normal appearance of an unsigned signature field: a bordered box
+/// labeled "Sign here"
+fn add_signature_appearance(
+    generator: &mut crate::pdf_generator::PdfGenerator,
+    field: &FormField,
+    font_id: u32,
+) -> u32 {
+    let (w, h) = (field.width, field.height);
+    let size = (h * 0.4).min(10.0);
+    let ops = format!(
+        "q\n0 0 0 RG\n1 w\n0.5 0.5 {} {} re S\nBT\n/Helv {} Tf\n0.5 g\n4 {} Td\n(Sign here) Tj\nET\nQ\n",
+        w - 1.0,
+        h - 1.0,
+        size,
+        (h - size * 0.7) / 2.0
+    );
+    generator.add_stream_object(
+        format!(
+            "<< /Type /XObject\n/Subtype /Form\n/BBox [0 0 {} {}]\n/Resources << /Font << /Helv {} 0 R >> >>\n/Length {}\n>>\n",
+            w, h, font_id, ops.len()
+        ),
+        ops.into_bytes(),
+    )
+}
+
+/// Path operators for a circle built from four Bézier curves
+fn circle_path(cx: f32, cy: f32, r: f32) -> String {
+    let k = r * 0.5523;
+    format!(
+        "{} {} m\n\
+         {} {} {} {} {} {} c\n\
+         {} {} {} {} {} {} c\n\
+         {} {} {} {} {} {} c\n\
+         {} {} {} {} {} {} c\n",
+        cx + r, cy,
+        cx + r, cy + k, cx + k, cy + r, cx, cy + r,
+        cx - k, cy + r, cx - r, cy + k, cx - r, cy,
+        cx - r, cy - k, cx - k, cy - r, cx, cy - r,
+        cx + k, cy - r, cx + r, cy - k, cx + r, cy
+    )
+}
+
+/// Turn an arbitrary label into a PDF name token (without the leading slash)
+fn pdf_name_token(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' => (b as char).to_string(),
+            _ => format!("#{:02X}", b),
+        })
+        .collect()
+}
+
+/// Whether a checkbox default value means "checked"
+fn is_checked_value(value: &str) -> bool {
+    matches!(value.to_ascii_lowercase().as_str(), "yes" | "on" | "true" | "checked" | "x" | "1")
+}
+
+/// Create a form field annotation dictionary
+fn create_form_field_dict(field: &FormField) -> String {
+    let base_dict = format!(
+        "<< /Type /Annot\n/Subtype /Widget\n\
+         /Rect [{} {} {} {}]\n\
+         /FT {}\n\
+         /T {}\n",
+        field.x,
+        field.y,
+        field.x + field.width,
+        field.y + field.height,
+        field_type_to_pdf(&field.field_type),
+        pdf_text_string(&field.name)
+    );
+
+    let mut dict = base_dict;
+
+    // Add default value if present (buttons store their state as a name below,
+    // and a signature's value is only written when it is signed)
+    if let Some(ref value) = field.default_value
+        && !matches!(field.field_type, FormFieldType::Checkbox | FormFieldType::Signature)
+    {
+        dict.push_str(&format!("/V {}\n", pdf_text_string(value)));
+    }
+
+    // Text and choice fields need a font to draw their value
+    if matches!(field.field_type, FormFieldType::Text | FormFieldType::Dropdown) {
+        dict.push_str(&format!("/DA {}\n", pdf_text_string(FORM_DEFAULT_APPEARANCE)));
+    }
+
+    // Add field-type specific properties
+    match field.field_type {
+        FormFieldType::Text => {
+            dict.push_str(&format!("/Ff {}\n", field.ff(FieldFlags::NONE)));
+        }
+        FormFieldType::Checkbox => {
+            let state = if field.default_value.as_deref().is_some_and(is_checked_value) {
+                "Yes"
+            } else {
+                "Off"
+            };
+            dict.push_str(&format!(
+                "/V /{}\n/AS /{}\n/Ff {}\n",
+                state,
+                state,
+                field.ff(FieldFlags::NONE)
+            ));
+        }
+        FormFieldType::Radio => {
+            if !field.options.is_empty() {
+                let opts: Vec<String> = field.options.iter().map(|o| pdf_text_string(o)).collect();
+                dict.push_str(&format!("/Opt [{}]\n", opts.join(" ")));
+            }
+            dict.push_str(&format!(
+                "/V /Off\n/Ff {}\n",
+                field.ff(FieldFlags::RADIO | FieldFlags::NO_TOGGLE_TO_OFF)
+            ));
+        }
+        FormFieldType::Dropdown => {
+            if !field.options.is_empty() {
+                let opts: Vec<String> = field.options.iter().map(|o| pdf_text_string(o)).collect();
+                dict.push_str(&format!("/Opt [{}]\n", opts.join(" ")));
+            }
+            dict.push_str(&format!("/Ff {}\n", field.ff(FieldFlags::COMBO)));
+        }
+        FormFieldType::Signature => {
+            dict.push_str(&format!("/Ff {}\n", field.ff(FieldFlags::NONE)));
+        }
+    }
+
+    dict.push_str(">>\n");
+    dict
+}
+
+/// Convert FormFieldType to PDF field type string
+fn field_type_to_pdf(field_type: &FormFieldType) -> String {
+    match field_type {
+        FormFieldType::Text => "/Tx".to_string(),
+        FormFieldType::Checkbox => "/Btn".to_string(),
+        FormFieldType::Radio => "/Btn".to_string(),
+        FormFieldType::Dropdown => "/Ch".to_string(),
+        FormFieldType::Signature => "/Sig".to_string(),
+    }
+}
+
+/// Overlay an image onto every page of a PDF.
+///
+/// Places an image on top of every page at the specified position and size.
+/// Supports JPEG, PNG, and BMP image formats.
+///
+/// # Arguments
+///
+/// * `input_file` - Path to the input PDF file
+/// * `output_file` - Path where the output PDF will be written
+/// * `image_path` - Path to the image file to overlay
+/// * `x` - X position of the image (in PDF points)
+/// * `y` - Y position of the image (in PDF points)
+/// * `width` - Width of the image (in PDF points), or `0` to follow `height` and the aspect ratio
+/// * `height` - Height of the image (in PDF points), or `0` to follow `width` and the aspect ratio
+/// * `opacity` - Opacity of the image (0.0 = transparent, 1.0 = opaque)
+///
+/// An image that would extend past the page is scaled down, keeping its
+/// proportions, to end at the page edge.
+///
+/// # Returns
+///
+/// Returns `Ok(())` if successful, or an error if overlaying fails.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use pdfrs::pdf_ops;
+///
+/// pdf_ops::overlay_image_on_pdf(
+///     "input.pdf",
+///     "output.pdf",
+///     "logo.png",
+///     100.0,  // x position
+///     700.0,  // y position
+///     200.0,  // width
+///     100.0,  // height
+///     0.8,    // opacity
+/// ).expect("Failed to overlay image");
+/// ```
+pub fn overlay_image_on_pdf(
+    input_file: &str,
+    output_file: &str,
+    image_path: &str,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    opacity: f32,
+) -> Result<()> {
+    let area = Rect::new(x, y, width, height);
+    overlay_image_on_pdf_with_options(input_file, output_file, image_path, area, opacity, &OutputOptions::default())
+}
+
+/// Like [`overlay_image_on_pdf`] with the image placed in `area`, compressing
+/// the rewritten page content and warning about large output as `output` asks
+pub fn overlay_image_on_pdf_with_options(
+    input_file: &str,
+    output_file: &str,
+    image_path: &str,
+    area: Rect,
+    opacity: f32,
+    output: &OutputOptions,
+) -> Result<()> {
+    let Rect { x, y, width, height } = area;
+    let doc = crate::pdf::PdfDocument::load_from_file(input_file)?;
+    let all_streams = extract_page_streams(&doc);
+
+    if all_streams.is_empty() {
+        return Err(PdfError::InvalidInput(format!("No pages found in {}", input_file)));
     }
 
-    for hl in highlights {
-        let hl_dict = format!(
-            "<< /Type /Annot\n/Subtype /Highlight\n/Rect [{} {} {} {}]\n/C [{} {} {}]\n/QuadPoints [{} {} {} {} {} {} {} {}]\n>>\n",
-            hl.x, hl.y, hl.x + hl.width, hl.y + hl.height,
-            hl.color_r, hl.color_g, hl.color_b,
-            hl.x, hl.y + hl.height, hl.x + hl.width, hl.y + hl.height,
-            hl.x, hl.y, hl.x + hl.width, hl.y,
+    // Load the image
+    let image_info = crate::image::load_image(image_path)?;
+    let mut generator = crate::pdf_generator::PdfGenerator::new();
+
+    // Create image XObject
+    let image_id = crate::image::create_image_object(&mut generator, image_info.clone())?;
+
+    let layout = crate::pdf_generator::PageLayout::portrait();
+    let (mut width, mut height) =
+        crate::image::resolve_display_size(image_info.width, image_info.height, width, height);
+    let fit = ((layout.width - x) / width).min((layout.height - y) / height);
+    if fit < 1.0 && fit > 0.0 {
+        log::warn!(
+            "[overlay] {}x{} image at ({}, {}) extends past the page; scaling it to {}x{}",
+            width, height, x, y, width * fit, height * fit
         );
-        annot_ids.push(generator.add_object(hl_dict));
+        width *= fit;
+        height *= fit;
     }
 
-    let annot_offset = annot_ids.len() as u32;
-    let pages_obj_id = annot_offset + (page_streams.len() as u32) * 3 + 1;
-    let mut page_ids = Vec::new();
-
-    for (i, page_stream) in page_streams.iter().enumerate() {
-        let content_id = generator.add_stream_object(
-            format!("<< /Length {} >>\n", page_stream.len()),
-            page_stream.clone(),
-        );
-        let font_id = content_id + 2;
-        let annots_str = if i == 0 && !annot_ids.is_empty() {
-            let refs: Vec<String> = annot_ids.iter().map(|id| format!("{} 0 R", id)).collect();
-            format!("/Annots [{}]\n", refs.join(" "))
-        } else {
-            String::new()
-        };
-        let page_dict = format!(
-            "<< /Type /Page\n/Parent {} 0 R\n/MediaBox [0 0 {} {}]\n/Contents {} 0 R\n{}/Resources << /Font << /F1 {} 0 R >> >>\n>>\n",
-            pages_obj_id, layout.width, layout.height, content_id, annots_str, font_id
-        );
-        let page_id = generator.add_object(page_dict);
-        page_ids.push(page_id);
-        generator.add_object(format!("<< /Type /Font\n/Subtype /Type1\n/BaseFont /Helvetica\n>>\n"));
+    // Create overlay content stream
+    let mut overlay_content = Vec::new();
+    if opacity < 1.0 {
+        // Set transparency
+        overlay_content.extend_from_slice(format!("{} {} {} rg\n", opacity, opacity, opacity).as_bytes());
     }
+    overlay_content.extend_from_slice(b"q\n");
+    overlay_content.extend_from_slice(format!("{} 0 0 {} {} {} cm\n", width, height, x, y).as_bytes());
+    overlay_content.extend_from_slice(b"/Im1 Do\n");
+    overlay_content.extend_from_slice(b"Q\n");
 
-    let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
-    let pages_dict = format!("<< /Type /Pages\n/Kids [{}]\n/Count {}\n>>\n", kids.join(" "), page_ids.len());
-    let actual_pages_id = generator.add_object(pages_dict);
-    assert_eq!(actual_pages_id, pages_obj_id);
-    generator.add_object(format!("<< /Type /Catalog\n/Pages {} 0 R\n>>\n", actual_pages_id));
+    // For each page, append the overlay content
+    let overlayed: Vec<Vec<u8>> = all_streams
+        .iter()
+        .enumerate()
+        .map(|(i, stream)| {
+            let mut combined = stream.clone();
+            combined.extend_from_slice(&overlay_content);
+            combined
+        })
+        .collect();
 
-    let pdf_data = generator.generate();
-    let mut file = std::fs::File::create(output_file)?;
-    std::io::Write::write_all(&mut file, &pdf_data)?;
-    println!(
-        "[annotate] Created {} with {} text, {} link, {} highlight annotations",
-        output_file, annotations.len(), links.len(), highlights.len()
+    // Assemble with the image XObject added to resources
+    assemble_pdf_with_image_overlay(generator, output_file, &overlayed, "Helvetica", &layout, image_id, output)?;
+    log::info!(
+        "[overlay] Added image overlay '{}' to {} pages in {}",
+        image_path,
+        overlayed.len(),
+        output_file
     );
     Ok(())
 }
 
-/// Create a single-page PDF with text annotations (backward compatible)
-pub fn create_pdf_with_annotations(
+/// Overlay an image on every page at `position`, resolved against each page's own size.
+///
+/// Pages are copied as Form XObjects, so each keeps its MediaBox (and `/Rotate`)
+/// and an overlay at `OverlayPosition::Center` is centered on every page even
+/// when their sizes differ. The image is drawn by a form of its own, with
+/// `opacity` as its fill alpha. `width` and `height` follow [`overlay_image_on_pdf`].
+pub fn overlay_image_at(
+    input_file: &str,
     output_file: &str,
-    text: &str,
-    annotations: &[TextAnnotation],
-    links: &[LinkAnnotation],
+    image_path: &str,
+    position: OverlayPosition,
+    width: f32,
+    height: f32,
+    opacity: f32,
 ) -> Result<()> {
-    let elements = crate::elements::parse_markdown(text);
-    let layout = crate::pdf_generator::PageLayout::portrait();
-
-    // Build page content
-    let page_streams = build_page_streams(&elements, 12.0, true, layout);
-    if page_streams.is_empty() {
-        return Err(anyhow!("No page content generated"));
+    let doc = crate::pdf::PdfDocument::load_from_file(input_file)?;
+    if doc.pages.is_empty() {
+        return Err(PdfError::InvalidInput(format!("No pages found in {}", input_file)));
     }
 
+    let image_info = crate::image::load_image(image_path)?;
+    let (width, height) = crate::image::resolve_display_size(image_info.width, image_info.height, width, height);
     let mut generator = crate::pdf_generator::PdfGenerator::new();
+    let image_id = crate::image::create_image_object(&mut generator, image_info)?;
+    let mut resources = format!("/XObject << /Im1 {} 0 R >>", image_id);
+    if opacity < 1.0 {
+        resources.push_str(&format!(" /ExtGState << /GS0 {} 0 R >>", add_opacity_state(&mut generator, opacity)));
+    }
 
-    // Build annotation objects first, collect their IDs
-    let mut annot_ids: Vec<u32> = Vec::new();
+    let mut importer = ObjectImporter::new(&doc);
+    let mut pages = Vec::new();
+    for index in 0..doc.pages.len() {
+        let (form_id, drawn) = import_page_as_form(&doc, index, &mut generator, &mut importer)?;
+        let (page_w, page_h) = (drawn[2] - drawn[0], drawn[3] - drawn[1]);
+        let (x, y) = position.resolve(page_w, page_h, width, height);
 
-    for annot in annotations {
-        let annot_dict = format!(
-            "<< /Type /Annot\n\
-             /Subtype /Text\n\
-             /Rect [{} {} {} {}]\n\
-             /Contents ({})\n\
-             /T ({})\n\
-             /Open false\n\
-             >>\n",
-            annot.x,
-            annot.y,
-            annot.x + annot.width,
-            annot.y + annot.height,
-            escape_pdf_meta(&annot.content),
-            escape_pdf_meta(&annot.title),
-        );
-        annot_ids.push(generator.add_object(annot_dict));
+        let mut overlay = String::from("q\n");
+        if opacity < 1.0 {
+            overlay.push_str("/GS0 gs\n");
+        }
+        overlay.push_str(&format!("{} 0 0 {} {} {} cm\n/Im1 Do\nQ\n", width, height, x, y));
+        let overlay_id = add_overlay_form(&mut generator, overlay.into_bytes(), (page_w, page_h), &resources);
+
+        let mut content = form_placement_content([1.0, 0.0, 0.0, 1.0, -drawn[0], -drawn[1]]);
+        content.push_str("/Ov0 Do\n");
+        pages.push(FormPage {
+            content: content.into_bytes(),
+            xobjects: vec![("Pg0".to_string(), form_id), ("Ov0".to_string(), overlay_id)],
+            size: Some((page_w, page_h)),
+            annots: Vec::new(),
+        });
     }
 
-    for link in links {
-        let link_dict = format!(
-            "<< /Type /Annot\n\
-             /Subtype /Link\n\
-             /Rect [{} {} {} {}]\n\
-             /Border [0 0 0]\n\
-             /A << /Type /Action\n/S /URI\n/URI ({}) >>\n\
-             >>\n",
-            link.x,
-            link.y,
-            link.x + link.width,
-            link.y + link.height,
-            escape_pdf_meta(&link.url),
-        );
-        annot_ids.push(generator.add_object(link_dict));
-    }
+    let layout = crate::pdf_generator::PageLayout::portrait();
+    let pdf_data = assemble_form_pages(generator, &pages, &layout, None);
+    fs::write(output_file, pdf_data)?;
+    log::info!(
+        "[overlay] Added image overlay '{}' to {} pages in {}",
+        image_path,
+        pages.len(),
+        output_file
+    );
+    Ok(())
+}
 
-    let annot_offset = annot_ids.len() as u32;
+/// Add a Form XObject covering a `width` x `height` page that draws `content`
+/// with `resources`, the entries of its resource dictionary
+fn add_overlay_form(
+    generator: &mut crate::pdf_generator::PdfGenerator,
+    content: Vec<u8>,
+    (width, height): (f32, f32),
+    resources: &str,
+) -> u32 {
+    generator.add_stream_object(
+        format!(
+            "<< /Type /XObject\n/Subtype /Form\n/BBox [0 0 {} {}]\n/Resources << {} >>\n/Length {}\n>>\n",
+            width,
+            height,
+            resources,
+            content.len()
+        ),
+        content,
+    )
+}
 
-    // Now add page content streams and pages
-    // pages_obj_id = annot_offset + page_streams.len() * 3 + 1
-    let pages_obj_id = annot_offset + (page_streams.len() as u32) * 3 + 1;
+/// Add a graphics state that paints fills and strokes at `opacity`
+fn add_opacity_state(generator: &mut crate::pdf_generator::PdfGenerator, opacity: f32) -> u32 {
+    let opacity = opacity.clamp(0.0, 1.0);
+    generator.add_object(format!("<< /Type /ExtGState\n/ca {}\n/CA {}\n>>\n", opacity, opacity))
+}
 
+/// Assemble PDF with image overlay XObject, already added to `generator`, in resources
+fn assemble_pdf_with_image_overlay(
+    mut generator: crate::pdf_generator::PdfGenerator,
+    filename: &str,
+    page_streams: &[Vec<u8>],
+    font: &str,
+    layout: &crate::pdf_generator::PageLayout,
+    image_id: u32,
+    output: &OutputOptions,
+) -> Result<()> {
     let mut page_ids = Vec::new();
-    for (i, page_stream) in page_streams.iter().enumerate() {
-        let content_id = generator.add_stream_object(
-            format!("<< /Length {} >>\n", page_stream.len()),
-            page_stream.clone(),
-        );
-        let font_id = content_id + 2;
+    let pages_obj_id = generator.next_id + (page_streams.len() as u32) * 3;
 
-        // Only first page gets annotations
-        let annots_str = if i == 0 && !annot_ids.is_empty() {
-            let refs: Vec<String> = annot_ids.iter().map(|id| format!("{} 0 R", id)).collect();
-            format!("/Annots [{}]\n", refs.join(" "))
-        } else {
-            String::new()
-        };
+    for page_stream in page_streams {
+        let content_id = add_content_stream(&mut generator, page_stream, output);
+        let font_id = content_id + 2;
 
         let page_dict = format!(
             "<< /Type /Page\n\
              /Parent {} 0 R\n\
              /MediaBox [0 0 {} {}]\n\
              /Contents {} 0 R\n\
-             {}\
-             /Resources << /Font << /F1 {} 0 R >> >>\n\
+             /Resources << /Font << /F1 {} 0 R >> /XObject << /Im1 {} 0 R >> >>\n\
              >>\n",
-            pages_obj_id, layout.width, layout.height, content_id, annots_str, font_id
+            pages_obj_id, layout.width, layout.height, content_id, font_id, image_id
         );
         let page_id = generator.add_object(page_dict);
         page_ids.push(page_id);
 
         let font_dict = format!(
-            "<< /Type /Font\n/Subtype /Type1\n/BaseFont /Helvetica\n>>\n"
+            "<< /Type /Font\n/Subtype /Type1\n/BaseFont /{}\n>>\n",
+            font
         );
         generator.add_object(font_dict);
     }
@@ -1058,788 +3528,1545 @@ pub fn create_pdf_with_annotations(
     generator.add_object(catalog_dict);
 
     let pdf_data = generator.generate();
-    let mut file = std::fs::File::create(output_file)?;
-    std::io::Write::write_all(&mut file, &pdf_data)?;
-    println!(
-        "[annotate] Created {} with {} text annotations, {} link annotations",
-        output_file,
-        annotations.len(),
-        links.len()
-    );
-    Ok(())
+    write_output(filename, &pdf_data, output)
 }
 
-/// Create a PDF page with multiple images placed at specified positions
-pub fn create_pdf_with_images(
+/// Watermark type for different watermark styles
+#[derive(Debug, Clone, Copy)]
+pub enum WatermarkType {
+    Text,
+    Image,
+}
+
+/// Create a watermark with either text or image
+pub enum WatermarkContent {
+    Text(String),
+    Image(String), // path to image file
+}
+
+/// Add a watermark to every page of a PDF with support for text or image watermarks
+pub fn watermark_pdf_advanced(
+    input_file: &str,
     output_file: &str,
-    images: &[(String, f32, f32, f32, f32)], // (path, x, y, width, height)
+    content: WatermarkContent,
+    opacity: f32,
+    position: WatermarkPosition,
 ) -> Result<()> {
-    if images.is_empty() {
-        return Err(anyhow!("No images provided"));
+    watermark_pdf_with_font_size(input_file, output_file, content, opacity, position, 48.0)
+}
+
+/// Like [`watermark_pdf_advanced`], setting the font size of text watermarks.
+///
+/// Pages are copied as Form XObjects, so each keeps its size and resources, and
+/// the watermark is positioned against each page's own size. Text is painted in
+/// the gray level `opacity`; images are drawn with `opacity` as their alpha.
+pub fn watermark_pdf_with_font_size(
+    input_file: &str,
+    output_file: &str,
+    content: WatermarkContent,
+    opacity: f32,
+    position: WatermarkPosition,
+    font_size: f32,
+) -> Result<()> {
+    let doc = crate::pdf::PdfDocument::load_from_file(input_file)?;
+    if doc.pages.is_empty() {
+        return Err(PdfError::InvalidInput(format!("No pages found in {}", input_file)));
     }
 
+    // Resources shared by the watermark of every page
     let mut generator = crate::pdf_generator::PdfGenerator::new();
-    let mut image_refs: Vec<(u32, String)> = Vec::new(); // (obj_id, name)
-
-    // Create image XObjects (supports JPEG, PNG, BMP)
-    for (i, (path, _, _, _, _)) in images.iter().enumerate() {
-        let info = crate::image::load_image(path)?;
-        let name = format!("Im{}", i + 1);
-        let image_id = crate::image::create_image_object(&mut generator, info)?;
-        image_refs.push((image_id, name));
-    }
+    let (text, image_info, resources) = match &content {
+        WatermarkContent::Text(text) => {
+            let font_id = generator.add_object(
+                "<< /Type /Font\n/Subtype /Type1\n/BaseFont /Helvetica\n/Encoding /WinAnsiEncoding\n>>\n".to_string(),
+            );
+            (text.as_str(), None, format!("/Font << /F1 {} 0 R >>", font_id))
+        }
+        WatermarkContent::Image(image_path) => {
+            let image_info = crate::image::load_image(image_path)?;
+            let image_id = crate::image::create_image_object(&mut generator, image_info.clone())?;
+            let mut resources = format!("/XObject << /Im1 {} 0 R >>", image_id);
+            if opacity < 1.0 {
+                resources.push_str(&format!(" /ExtGState << /GS0 {} 0 R >>", add_opacity_state(&mut generator, opacity)));
+            }
+            ("", Some(image_info), resources)
+        }
+    };
 
-    // Build content stream with all images
-    let mut content = Vec::new();
-    for (i, (_, x, y, w, h)) in images.iter().enumerate() {
-        let name = &image_refs[i].1;
-        content.extend_from_slice(b"q\n");
-        content.extend_from_slice(format!("{} 0 0 {} {} {} cm\n", w, h, x, y).as_bytes());
-        content.extend_from_slice(format!("/{} Do\n", name).as_bytes());
-        content.extend_from_slice(b"Q\n");
+    let mut importer = ObjectImporter::new(&doc);
+    let mut pages = Vec::new();
+    for index in 0..doc.pages.len() {
+        let (form_id, drawn) = import_page_as_form(&doc, index, &mut generator, &mut importer)?;
+        let layout = crate::pdf_generator::PageLayout {
+            width: drawn[2] - drawn[0],
+            height: drawn[3] - drawn[1],
+            ..crate::pdf_generator::PageLayout::portrait()
+        };
+        let mark = match &image_info {
+            Some(image_info) => build_image_watermark_stream(image_info, opacity, &layout, position)?,
+            None => build_text_watermark_stream(text, font_size, opacity, &layout, position),
+        };
+        let mark_id = add_overlay_form(&mut generator, mark, (layout.width, layout.height), &resources);
+
+        let mut page = form_placement_content([1.0, 0.0, 0.0, 1.0, -drawn[0], -drawn[1]]);
+        page.push_str("/Wm0 Do\n");
+        pages.push(FormPage {
+            content: page.into_bytes(),
+            xobjects: vec![("Pg0".to_string(), form_id), ("Wm0".to_string(), mark_id)],
+            size: Some((layout.width, layout.height)),
+            annots: Vec::new(),
+        });
     }
 
-    let content_id = generator.add_stream_object(
-        format!("<< /Length {} >>\n", content.len()),
-        content,
-    );
-
-    // Build XObject resource dictionary
-    let xobj_entries: Vec<String> = image_refs
-        .iter()
-        .map(|(id, name)| format!("/{} {} 0 R", name, id))
-        .collect();
-    let xobj_dict = xobj_entries.join(" ");
-
-    let page_dict = format!(
-        "<< /Type /Page\n\
-         /Parent 0 0 R\n\
-         /MediaBox [0 0 612 792]\n\
-         /Contents {} 0 R\n\
-         /Resources << /XObject << {} >> >>\n\
-         >>\n",
-        content_id, xobj_dict
-    );
-    let page_id = generator.add_object(page_dict);
-
-    let pages_dict = format!(
-        "<< /Type /Pages\n/Kids [{} 0 R]\n/Count 1\n>>\n",
-        page_id
-    );
-    let pages_id = generator.add_object(pages_dict);
-
-    let catalog = format!("<< /Type /Catalog\n/Pages {} 0 R\n>>\n", pages_id);
-    generator.add_object(catalog);
-
-    let pdf_data = generator.generate();
-    fs::write(output_file, &pdf_data)?;
-    println!(
-        "[images] Created {} with {} images",
-        output_file,
-        images.len()
+    let layout = crate::pdf_generator::PageLayout::portrait();
+    fs::write(output_file, assemble_form_pages(generator, &pages, &layout, None))?;
+    log::info!(
+        "[watermark] Added watermark to {} pages in {}",
+        pages.len(),
+        output_file
     );
     Ok(())
 }
 
-/// Add a diagonal text watermark to every page of a PDF.
-///
-/// The watermark is rendered as semi-transparent gray text rotated 45°.
-///
-/// # Arguments
-///
-/// * `input_file` - Path to the input PDF file
-/// * `output_file` - Path where the watermarked PDF will be written
-/// * `watermark_text` - Text to use as watermark
-/// * `font_size` - Size of the watermark font
-/// * `opacity` - Opacity of the watermark (0.0 = transparent, 1.0 = opaque)
-///
-/// # Returns
-///
-/// Returns `Ok(())` if successful, or an error if watermarking fails.
+/// Stamp Bates numbers such as "ABC-000001" on every page of a PDF.
 ///
-/// # Example
-///
-/// ```rust,no_run
-/// use pdfrs::pdf_ops;
-///
-/// pdf_ops::watermark_pdf(
-///     "input.pdf",
-///     "output.pdf",
-///     "CONFIDENTIAL",
-///     48.0,
-///     0.3,
-/// ).expect("Failed to add watermark");
-/// ```
-pub fn watermark_pdf(
+/// Page `n` (0-based) shows `prefix` followed by `start + n` padded with zeros
+/// to `digits` digits, in black 10pt Helvetica without rotation at `position`,
+/// resolved against each page's own size. Pages keep their content and
+/// resources, being copied as Form XObjects.
+pub fn bates_number(
     input_file: &str,
     output_file: &str,
-    watermark_text: &str,
-    font_size: f32,
-    opacity: f32,
+    prefix: &str,
+    start: u64,
+    digits: usize,
+    position: OverlayPosition,
 ) -> Result<()> {
     let doc = crate::pdf::PdfDocument::load_from_file(input_file)?;
-    let all_streams = extract_page_streams(&doc);
+    if doc.pages.is_empty() {
+        return Err(PdfError::InvalidInput(format!("No pages found in {}", input_file)));
+    }
 
-    if all_streams.is_empty() {
-        return Err(anyhow!("No pages found in {}", input_file));
+    let mut generator = crate::pdf_generator::PdfGenerator::new();
+    let font_id = generator.add_object(
+        "<< /Type /Font\n/Subtype /Type1\n/BaseFont /Helvetica\n/Encoding /WinAnsiEncoding\n>>\n".to_string(),
+    );
+    let mut importer = ObjectImporter::new(&doc);
+    let mut pages = Vec::new();
+    for index in 0..doc.pages.len() {
+        let (form_id, drawn) = import_page_as_form(&doc, index, &mut generator, &mut importer)?;
+        let layout = crate::pdf_generator::PageLayout {
+            width: drawn[2] - drawn[0],
+            height: drawn[3] - drawn[1],
+            ..crate::pdf_generator::PageLayout::portrait()
+        };
+        let label = format!("{}{:0width$}", prefix, start + index as u64, width = digits);
+        let stamp = build_text_watermark_stream(&label, 10.0, 0.0, &layout, WatermarkPosition::Placed(position));
+        let resources = format!("/Font << /F1 {} 0 R >>", font_id);
+        let stamp_id = add_overlay_form(&mut generator, stamp, (layout.width, layout.height), &resources);
+
+        let mut content = form_placement_content([1.0, 0.0, 0.0, 1.0, -drawn[0], -drawn[1]]);
+        content.push_str("/Bates0 Do\n");
+        pages.push(FormPage {
+            content: content.into_bytes(),
+            xobjects: vec![("Pg0".to_string(), form_id), ("Bates0".to_string(), stamp_id)],
+            size: Some((layout.width, layout.height)),
+            annots: Vec::new(),
+        });
     }
 
     let layout = crate::pdf_generator::PageLayout::portrait();
-    let watermark_stream = build_watermark_stream(watermark_text, font_size, opacity, &layout);
-
-    // Append watermark content to each page stream
-    let watermarked: Vec<Vec<u8>> = all_streams
-        .iter()
-        .map(|stream| {
-            let mut combined = stream.clone();
-            combined.extend_from_slice(&watermark_stream);
-            combined
-        })
-        .collect();
-
-    assemble_merged_pdf(output_file, &watermarked, "Helvetica", &layout)?;
-    println!(
-        "[watermark] Added watermark '{}' to {} pages in {}",
-        watermark_text,
-        watermarked.len(),
-        output_file
+    fs::write(output_file, assemble_form_pages(generator, &pages, &layout, None))?;
+    log::info!(
+        "[bates] Numbered {} pages from {}{:0width$} in {}",
+        pages.len(),
+        prefix,
+        start,
+        output_file,
+        width = digits
     );
     Ok(())
 }
 
-/// Build a content stream snippet that renders a diagonal watermark
-fn build_watermark_stream(text: &str, font_size: f32, opacity: f32, layout: &crate::pdf_generator::PageLayout) -> Vec<u8> {
-    let escaped = escape_pdf_meta(text);
-    // Center of page
-    let cx = layout.width / 2.0;
-    let cy = layout.height / 2.0;
-    // 45° rotation matrix: cos(45)=0.707, sin(45)=0.707
-    let cos45: f32 = 0.7071;
-    let sin45: f32 = 0.7071;
+/// Watermark position on the page
+#[derive(Debug, Clone, Copy)]
+pub enum WatermarkPosition {
+    Center,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Diagonal, // Traditional diagonal watermark
+    /// Repeat the watermark in a grid across the whole page, `spacing` points apart
+    Tiled { spacing: f32 },
+    /// Place the watermark's box at a position resolved against the page size
+    Placed(OverlayPosition),
+}
+
+/// One axis of an [`OverlayPosition::At`] position
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Coordinate {
+    /// Distance of the overlay's near edge from the page's left or bottom edge
+    Points(f32),
+    /// Share (0-100) of the space left beside the overlay: 0 is flush with the
+    /// left or bottom edge, 50 centers it and 100 is flush with the far edge
+    Percent(f32),
+}
+
+impl Coordinate {
+    /// Offset of an overlay `size` long on a page `extent` long
+    fn resolve(self, extent: f32, size: f32) -> f32 {
+        match self {
+            Coordinate::Points(pt) => pt,
+            Coordinate::Percent(pct) => (extent - size) * pct / 100.0,
+        }
+    }
+}
+
+/// Where an overlay sits on a page, resolved separately for each page's size
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OverlayPosition {
+    At { x: Coordinate, y: Coordinate },
+    Center,
+    /// Corners, `inset` points in from both edges
+    TopLeft { inset: f32 },
+    TopRight { inset: f32 },
+    BottomLeft { inset: f32 },
+    BottomRight { inset: f32 },
+}
+
+impl OverlayPosition {
+    /// Lower-left corner of a `width` x `height` overlay on a `page_width` x `page_height` page
+    pub fn resolve(&self, page_width: f32, page_height: f32, width: f32, height: f32) -> (f32, f32) {
+        match *self {
+            OverlayPosition::At { x, y } => (x.resolve(page_width, width), y.resolve(page_height, height)),
+            OverlayPosition::Center => ((page_width - width) / 2.0, (page_height - height) / 2.0),
+            OverlayPosition::TopLeft { inset } => (inset, page_height - height - inset),
+            OverlayPosition::TopRight { inset } => (page_width - width - inset, page_height - height - inset),
+            OverlayPosition::BottomLeft { inset } => (inset, inset),
+            OverlayPosition::BottomRight { inset } => (page_width - width - inset, inset),
+        }
+    }
+}
+
+/// Lower-left corners of a grid covering the page, `spacing` points apart.
+/// The grid starts off-page so rotated text still reaches the corners.
+fn tile_positions(layout: &crate::pdf_generator::PageLayout, spacing: f32) -> Vec<(f32, f32)> {
+    let spacing = spacing.max(10.0);
+    let mut positions = Vec::new();
+    let mut y = -spacing;
+    while y < layout.height + spacing {
+        let mut x = -spacing;
+        while x < layout.width + spacing {
+            positions.push((x, y));
+            x += spacing;
+        }
+        y += spacing;
+    }
+    positions
+}
+
+/// Build a text watermark stream with positioning
+fn build_text_watermark_stream(
+    text: &str,
+    font_size: f32,
+    opacity: f32,
+    layout: &crate::pdf_generator::PageLayout,
+    position: WatermarkPosition,
+) -> Vec<u8> {
+    let shown = pdf_show_string(text);
+    if let WatermarkPosition::Tiled { spacing } = position {
+        let (cos, sin) = (std::f32::consts::FRAC_1_SQRT_2, std::f32::consts::FRAC_1_SQRT_2);
+        let mut stream = Vec::new();
+        stream.extend_from_slice(b"q\n");
+        stream.extend_from_slice(format!("{} {} {} rg\n", opacity, opacity, opacity).as_bytes());
+        stream.extend_from_slice(b"BT\n");
+        stream.extend_from_slice(format!("/F1 {} Tf\n", font_size).as_bytes());
+        for (x, y) in tile_positions(layout, spacing) {
+            stream.extend_from_slice(
+                format!("{} {} {} {} {} {} Tm\n{} Tj\n", cos, sin, -sin, cos, x, y, shown).as_bytes()
+            );
+        }
+        stream.extend_from_slice(b"ET\n");
+        stream.extend_from_slice(b"Q\n");
+        return stream;
+    }
+    let (x, y, rotation) = match position {
+        WatermarkPosition::Center => {
+            (layout.width / 2.0, layout.height / 2.0, 0.0)
+        }
+        WatermarkPosition::TopLeft => {
+            (72.0, layout.height - 72.0, 0.0)
+        }
+        WatermarkPosition::TopRight => {
+            (layout.width - 72.0, layout.height - 72.0, 0.0)
+        }
+        WatermarkPosition::BottomLeft => {
+            (72.0, 72.0, 0.0)
+        }
+        WatermarkPosition::BottomRight => {
+            (layout.width - 72.0, 72.0, 0.0)
+        }
+        WatermarkPosition::Diagonal => {
+            (layout.width / 2.0 - 100.0, layout.height / 2.0 - 50.0, 45.0)
+        }
+        WatermarkPosition::Placed(placed) => {
+            let width = crate::font_metrics::text_width("Helvetica", text, font_size);
+            let (x, y) = placed.resolve(layout.width, layout.height, width, font_size);
+            (x, y, 0.0)
+        }
+        WatermarkPosition::Tiled { .. } => unreachable!("tiled watermarks are handled above"),
+    };
 
     let mut stream = Vec::new();
-    // Save graphics state, set transparency
     stream.extend_from_slice(b"q\n");
     stream.extend_from_slice(format!("{} {} {} rg\n", opacity, opacity, opacity).as_bytes());
     stream.extend_from_slice(b"BT\n");
     stream.extend_from_slice(format!("/F1 {} Tf\n", font_size).as_bytes());
-    // Text matrix: rotation + translation to center
-    stream.extend_from_slice(
-        format!(
-            "{} {} {} {} {} {} Tm\n",
-            cos45, sin45, -sin45, cos45, cx - 100.0, cy - 50.0
-        )
-        .as_bytes(),
-    );
-    stream.extend_from_slice(format!("({}) Tj\n", escaped).as_bytes());
+
+    if rotation != 0.0 {
+        let rad = rotation * std::f32::consts::PI / 180.0;
+        let cos = rad.cos();
+        let sin = rad.sin();
+        stream.extend_from_slice(
+            format!("{} {} {} {} {} {} Tm\n", cos, sin, -sin, cos, x, y).as_bytes()
+        );
+    } else {
+        stream.extend_from_slice(format!("{} {} Td\n", x, y).as_bytes());
+    }
+
+    stream.extend_from_slice(format!("{} Tj\n", shown).as_bytes());
     stream.extend_from_slice(b"ET\n");
     stream.extend_from_slice(b"Q\n");
     stream
 }
 
-/// Form field types.
-///
-/// Represents the type of interactive form field that can be added to a PDF.
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
-pub enum FormFieldType {
-    /// Text input field
-    Text,
-    /// Checkbox field
-    Checkbox,
-    /// Radio button field
-    Radio,
-    /// Dropdown/combobox field
-    Dropdown,
-}
+/// Build an image watermark stream with positioning
+fn build_image_watermark_stream(
+    image_info: &crate::image::ImageInfo,
+    opacity: f32,
+    layout: &crate::pdf_generator::PageLayout,
+    position: WatermarkPosition,
+) -> Result<Vec<u8>> {
+    // Scale image to fit page if too large
+    let max_width = layout.width * 0.5;
+    let max_height = layout.height * 0.5;
+    let (img_width, img_height) = crate::image::scale_to_fit(
+        image_info.width,
+        image_info.height,
+        max_width,
+        max_height,
+    );
 
-/// A form field to be added to a PDF.
-///
-/// Represents an interactive form field with its properties including
-/// position, dimensions, default value, options (for radio/dropdown), and
-/// whether the field is required.
-///
-/// # Fields
-///
-/// * `name` - Unique identifier for the form field
-/// * `field_type` - Type of form field (Text, Checkbox, Radio, Dropdown)
-/// * `x` - X position on the page (in PDF points)
-/// * `y` - Y position on the page (in PDF points)
-/// * `width` - Width of the field (in PDF points)
-/// * `height` - Height of the field (in PDF points)
-/// * `default_value` - Optional default value for the field
-/// * `options` - List of options (for radio buttons and dropdowns)
-/// * `required` - Whether the field must be filled
-///
-/// # Example
-///
-/// ```rust,no_run
-/// use pdfrs::pdf_ops::{FormField, FormFieldType};
-///
-/// let field = FormField {
-///     name: "firstName".to_string(),
-///     field_type: FormFieldType::Text,
-///     x: 100.0,
-///     y: 700.0,
-///     width: 200.0,
-///     height: 20.0,
-///     default_value: Some("John".to_string()),
-///     options: vec![],
-///     required: true,
-/// };
-/// ```
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct FormField {
-    pub name: String,
-    pub field_type: FormFieldType,
-    pub x: f32,
-    pub y: f32,
-    pub width: f32,
-    pub height: f32,
-    pub default_value: Option<String>,
-    pub options: Vec<String>, // For radio/dropdown
-    pub required: bool,
-}
+    let (x, y) = match position {
+        WatermarkPosition::Center => {
+            ((layout.width - img_width) / 2.0, (layout.height - img_height) / 2.0)
+        }
+        WatermarkPosition::TopLeft => {
+            (36.0, layout.height - img_height - 36.0)
+        }
+        WatermarkPosition::TopRight => {
+            (layout.width - img_width - 36.0, layout.height - img_height - 36.0)
+        }
+        WatermarkPosition::BottomLeft => {
+            (36.0, 36.0)
+        }
+        WatermarkPosition::BottomRight => {
+            (layout.width - img_width - 36.0, 36.0)
+        }
+        WatermarkPosition::Diagonal => {
+            ((layout.width - img_width) / 2.0, (layout.height - img_height) / 2.0)
+        }
+        WatermarkPosition::Placed(placed) => placed.resolve(layout.width, layout.height, img_width, img_height),
+        WatermarkPosition::Tiled { .. } => (0.0, 0.0),
+    };
+    let positions = match position {
+        // Tiles are spaced from the edge of one image to the start of the next
+        WatermarkPosition::Tiled { spacing } => {
+            let step = img_width.max(img_height) + spacing.max(0.0);
+            tile_positions(layout, step)
+        }
+        _ => vec![(x, y)],
+    };
 
-/// Create a PDF with an AcroForm containing interactive form fields
-pub fn create_pdf_with_form_fields(
-    output_file: &str,
-    text: &str,
-    form_fields: &[FormField],
-) -> Result<()> {
-    let elements = crate::elements::parse_markdown(text);
-    let layout = crate::pdf_generator::PageLayout::portrait();
-    let page_streams = build_page_streams(&elements, 12.0, true, layout);
-    if page_streams.is_empty() {
-        return Err(anyhow!("No page content generated"));
+    // `/GS0` is an ExtGState with `opacity` as its alpha, in the caller's resources
+    let mut stream = Vec::new();
+    stream.extend_from_slice(b"q\n");
+    if opacity < 1.0 {
+        stream.extend_from_slice(b"/GS0 gs\n");
+    }
+    for (x, y) in positions {
+        stream.extend_from_slice(b"q\n");
+        stream.extend_from_slice(format!("{} 0 0 {} {} {} cm\n", img_width, img_height, x, y).as_bytes());
+        stream.extend_from_slice(b"/Im1 Do\n");
+        stream.extend_from_slice(b"Q\n");
     }
+    stream.extend_from_slice(b"Q\n");
+    Ok(stream)
+}
 
-    let mut generator = crate::pdf_generator::PdfGenerator::new();
-    let mut field_ids: Vec<u32> = Vec::new();
-
-    // Create form field annotations
-    for field in form_fields {
-        let field_dict = create_form_field_dict(field);
-        field_ids.push(generator.add_object(field_dict));
+/// Reorder pages in a PDF according to a given order.
+///
+/// `page_order` is a list of 1-indexed page numbers in the desired output order.
+/// Example: `[3, 1, 2]` puts page 3 first, then page 1, then page 2.
+pub fn reorder_pages(input_file: &str, output_file: &str, page_order: &[usize]) -> Result<()> {
+    if page_order.is_empty() {
+        return Err(PdfError::InvalidInput("Page order list is empty".into()));
     }
 
-    // Create AcroForm dictionary
-    let kids_refs: Vec<String> = field_ids.iter().map(|id| format!("{} 0 R", id)).collect();
-    let acroform_dict = format!(
-        "<< /Fields [{}]\n>>\n",
-        kids_refs.join(" ")
-    );
-    let acroform_id = generator.add_object(acroform_dict);
-
-    let field_offset = field_ids.len() as u32;
-    let pages_obj_id = field_offset + (page_streams.len() as u32) * 3 + 1;
-    let mut page_ids = Vec::new();
-
-    for (i, page_stream) in page_streams.iter().enumerate() {
-        let content_id = generator.add_stream_object(
-            format!("<< /Length {} >>\n", page_stream.len()),
-            page_stream.clone(),
-        );
-        let font_id = content_id + 2;
-
-        // Only first page gets form fields
-        let annots_str = if i == 0 && !field_ids.is_empty() {
-            let refs: Vec<String> = field_ids.iter().map(|id| format!("{} 0 R", id)).collect();
-            format!("/Annots [{}]\n", refs.join(" "))
-        } else {
-            String::new()
-        };
+    let doc = crate::pdf::PdfDocument::load_from_file(input_file)?;
+    let total = page_count(&doc);
 
-        let page_dict = format!(
-            "<< /Type /Page\n\
-             /Parent {} 0 R\n\
-             /MediaBox [0 0 {} {}]\n\
-             /Contents {} 0 R\n\
-             {}\
-             /Resources << /Font << /F1 {} 0 R >> >>\n\
-             >>\n",
-            pages_obj_id, layout.width, layout.height, content_id, annots_str, font_id
-        );
-        let page_id = generator.add_object(page_dict);
-        page_ids.push(page_id);
-        generator.add_object(format!("<< /Type /Font\n/Subtype /Type1\n/BaseFont /Helvetica\n>>\n"));
+    if total == 0 {
+        return Err(PdfError::InvalidInput(format!("No pages found in {}", input_file)));
     }
 
-    let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
-    let pages_dict = format!("<< /Type /Pages\n/Kids [{}]\n/Count {}\n>>\n", kids.join(" "), page_ids.len());
-    let actual_pages_id = generator.add_object(pages_dict);
-    assert_eq!(actual_pages_id, pages_obj_id);
+    // Validate all page numbers
+    for &p in page_order {
+        if p == 0 || p > total {
+            return Err(PdfError::InvalidPageRange { start: p, end: p, page_count: Some(total) });
+        }
+    }
 
-    let catalog_dict = format!(
-        "<< /Type /Catalog\n/Pages {} 0 R\n/AcroForm {} 0 R\n>>\n",
-        actual_pages_id, acroform_id
+    let indices: Vec<usize> = page_order.iter().map(|&p| p - 1).collect();
+    write_selected_pages(doc, &indices, output_file)?;
+    log::info!(
+        "[reorder] Reordered {} pages from {} into {}",
+        indices.len(),
+        input_file,
+        output_file
     );
-    generator.add_object(catalog_dict);
+    Ok(())
+}
 
-    let pdf_data = generator.generate();
-    let mut file = std::fs::File::create(output_file)?;
-    std::io::Write::write_all(&mut file, &pdf_data)?;
-    println!(
-        "[form] Created {} with {} form fields",
-        output_file,
-        form_fields.len()
+/// Rewrite a PDF whose cross-reference table is missing or wrong.
+///
+/// Every `N G obj ... endobj` in the file is found by scanning the bytes; when an
+/// object number is defined more than once the last definition wins, as with
+/// incremental updates. The objects are written out again behind a fresh xref
+/// table and a trailer whose `/Root` comes from the old trailer, or else from the
+/// object with `/Type /Catalog`. Objects packed in object streams are not recovered.
+pub fn repair_pdf(input_file: &str, output_file: &str) -> Result<()> {
+    let data = fs::read(input_file)?;
+    let objects = scan_raw_objects(&data);
+    if objects.is_empty() {
+        return Err(PdfError::Parse(format!("No objects found in {}", input_file)));
+    }
+    let out = rebuild_from_objects(&data, &objects, input_file, "")?;
+    fs::write(output_file, &out)?;
+    log::info!(
+        "[repair] Rebuilt xref for {} objects from {} into {}",
+        objects.len(),
+        input_file,
+        output_file
     );
     Ok(())
 }
 
-/// Create a form field annotation dictionary
-fn create_form_field_dict(field: &FormField) -> String {
-    let base_dict = format!(
-        "<< /Type /Annot\n/Subtype /Widget\n\
-         /Rect [{} {} {} {}]\n\
-         /FT {}\n\
-         /T ({})\n",
-        field.x,
-        field.y,
-        field.x + field.width,
-        field.y + field.height,
-        field_type_to_pdf(&field.field_type),
-        escape_pdf_meta(&field.name)
+/// Rewrite a PDF whose stream `/Length` entries don't match their data.
+///
+/// Each stream's data is taken to run from the end-of-line after `stream` to the
+/// end-of-line before `endstream`; a `/Length` that disagrees (or is an indirect
+/// reference) is replaced with the measured length. The objects are then written
+/// out behind a fresh xref table as in [`repair_pdf`], which also drops anything
+/// after the final `%%EOF`. Returns the number of streams whose length was fixed.
+pub fn repair_balance(input_file: &str, output_file: &str) -> Result<usize> {
+    let data = fs::read(input_file)?;
+    let mut objects = scan_raw_objects(&data);
+    if objects.is_empty() {
+        return Err(PdfError::Parse(format!("No objects found in {}", input_file)));
+    }
+    let mut fixed = 0;
+    for (id, (_, body)) in objects.iter_mut() {
+        if let Some(corrected) = fix_stream_length(body) {
+            log::warn!("[repair] Corrected /Length of stream object {}", id);
+            *body = corrected;
+            fixed += 1;
+        }
+    }
+    let out = rebuild_from_objects(&data, &objects, input_file, "")?;
+    fs::write(output_file, &out)?;
+    log::info!(
+        "[repair] Fixed {} stream lengths in {} objects from {} into {}",
+        fixed,
+        objects.len(),
+        input_file,
+        output_file
     );
+    Ok(fixed)
+}
 
-    let mut dict = base_dict;
-
-    // Add default value if present
-    if let Some(ref value) = field.default_value {
-        dict.push_str(&format!("/V ({})\n", escape_pdf_meta(value)));
-    }
+/// `object` with its stream `/Length` set to the data's actual length, or `None`
+/// if it has no stream or the declared length already fits.
+fn fix_stream_length(object: &[u8]) -> Option<Vec<u8>> {
+    let stream_re = regex::bytes::Regex::new(r">>\s*stream(\r\n|\r|\n)").unwrap();
+    let caps = stream_re.captures(object)?;
+    let dict_end = caps.get(0).unwrap().start();
+    let data_start = caps.get(0).unwrap().end();
+    let end = data_start + crate::pdf::find_bytes(&object[data_start..], b"endstream")?;
+    let raw_len = end - data_start;
+    let trimmed_len = if object[data_start..end].ends_with(b"\r\n") {
+        raw_len - 2
+    } else if object[data_start..end].ends_with(b"\n") || object[data_start..end].ends_with(b"\r") {
+        raw_len - 1
+    } else {
+        raw_len
+    };
 
-    // Add field-type specific properties
-    match field.field_type {
-        FormFieldType::Text => {
-            dict.push_str(&format!(
-                "/Ff {}\n",
-                if field.required { 2 } else { 0 } // 2 = Required flag
-            ));
-            // Appearance for text field
-            dict.push_str("/AP << /N << /Type /Appearance\n/Length 0 >> >>\n");
-        }
-        FormFieldType::Checkbox => {
-            dict.push_str(&format!(
-                "/V /Off\n/Ff {}\n",
-                if field.required { 2 } else { 0 }
-            ));
-            // Appearance for checkbox
-            dict.push_str("/AP << /N << /Type /Appearance\n/Length 0 >> >>\n");
-        }
-        FormFieldType::Radio => {
-            if !field.options.is_empty() {
-                let opts: Vec<String> = field.options.iter().map(|o| format!("({})", escape_pdf_meta(o))).collect();
-                dict.push_str(&format!("/Opt [{}]\n", opts.join(" ")));
+    // `\s` after the name keeps /Length1, /Length2 and friends out
+    let length_re = regex::bytes::Regex::new(r"/Length\s+(\d+)(\s+\d+\s+R)?").unwrap();
+    let dict = &object[..dict_end];
+    let mut out = Vec::with_capacity(object.len());
+    match length_re.captures(dict) {
+        Some(length) => {
+            let declared: Option<usize> =
+                std::str::from_utf8(&length[1]).ok().and_then(|s| s.parse().ok());
+            if length.get(2).is_none() && declared.is_some_and(|n| n == trimmed_len || n == raw_len) {
+                return None;
             }
-            dict.push_str(&format!(
-                "/V /Off\n/Ff {}\n",
-                if field.required { 2 } else { 0 }
-            ));
+            let whole = length.get(0).unwrap();
+            out.extend_from_slice(&object[..whole.start()]);
+            out.extend_from_slice(format!("/Length {}", trimmed_len).as_bytes());
+            out.extend_from_slice(&object[whole.end()..]);
         }
-        FormFieldType::Dropdown => {
-            if !field.options.is_empty() {
-                let opts: Vec<String> = field.options.iter().map(|o| format!("({})", escape_pdf_meta(o))).collect();
-                dict.push_str(&format!("/Opt [{}]\n", opts.join(" ")));
-            }
-            dict.push_str(&format!(
-                "/Ff {}131072\n",
-                if field.required { 2 + 131072 } else { 131072 } // 131072 = Combo flag
-            ));
+        None => {
+            let open = crate::pdf::find_bytes(dict, b"<<")? + 2;
+            out.extend_from_slice(&object[..open]);
+            out.extend_from_slice(format!(" /Length {}", trimmed_len).as_bytes());
+            out.extend_from_slice(&object[open..]);
         }
     }
-
-    dict.push_str(">>\n");
-    dict
-}
-
-/// Convert FormFieldType to PDF field type string
-fn field_type_to_pdf(field_type: &FormFieldType) -> String {
-    match field_type {
-        FormFieldType::Text => "/Tx".to_string(),
-        FormFieldType::Checkbox => "/Btn".to_string(),
-        FormFieldType::Radio => "/Btn".to_string(),
-        FormFieldType::Dropdown => "/Ch".to_string(),
-    }
+    Some(out)
 }
 
-/// Overlay an image onto every page of a PDF.
-///
-/// Places an image on top of every page at the specified position and size.
-/// Supports JPEG, PNG, and BMP image formats.
-///
-/// # Arguments
-///
-/// * `input_file` - Path to the input PDF file
-/// * `output_file` - Path where the output PDF will be written
-/// * `image_path` - Path to the image file to overlay
-/// * `x` - X position of the image (in PDF points)
-/// * `y` - Y position of the image (in PDF points)
-/// * `width` - Width of the image (in PDF points)
-/// * `height` - Height of the image (in PDF points)
-/// * `opacity` - Opacity of the image (0.0 = transparent, 1.0 = opaque)
-///
-/// # Returns
-///
-/// Returns `Ok(())` if successful, or an error if overlaying fails.
-///
-/// # Example
-///
-/// ```rust,no_run
-/// use pdfrs::pdf_ops;
-///
-/// pdf_ops::overlay_image_on_pdf(
-///     "input.pdf",
-///     "output.pdf",
-///     "logo.png",
-///     100.0,  // x position
-///     700.0,  // y position
-///     200.0,  // width
-///     100.0,  // height
-///     0.8,    // opacity
-/// ).expect("Failed to overlay image");
-/// ```
-pub fn overlay_image_on_pdf(
+/// Serialize `objects` found in `data` behind a fresh xref table and trailer.
+/// `trailer_entries` are written into the trailer after `/Root` and `/Info`.
+fn rebuild_from_objects(
+    data: &[u8],
+    objects: &std::collections::BTreeMap<u32, (u16, Vec<u8>)>,
     input_file: &str,
-    output_file: &str,
-    image_path: &str,
-    x: f32,
-    y: f32,
-    width: f32,
-    height: f32,
-    opacity: f32,
-) -> Result<()> {
-    let doc = crate::pdf::PdfDocument::load_from_file(input_file)?;
-    let all_streams = extract_page_streams(&doc);
+    trailer_entries: &str,
+) -> Result<Vec<u8>> {
+    // Only references to recovered objects are kept
+    let last_reference = |key: &str| {
+        let re = regex::bytes::Regex::new(&format!(r"/{}\s+(\d+)\s+(\d+)\s+R", key)).unwrap();
+        re.captures_iter(data)
+            .filter_map(|c| {
+                let id: u32 = std::str::from_utf8(&c[1]).ok()?.parse().ok()?;
+                let generation: u16 = std::str::from_utf8(&c[2]).ok()?.parse().ok()?;
+                objects.contains_key(&id).then_some((id, generation))
+            })
+            .last()
+    };
+    let catalog_re = regex::bytes::Regex::new(r"/Type\s*/Catalog\b").unwrap();
+    let root = last_reference("Root")
+        .filter(|(id, _)| catalog_re.is_match(&objects[id].1))
+        .or_else(|| {
+            objects
+                .iter()
+                .rev()
+                .find(|(_, (_, body))| catalog_re.is_match(body))
+                .map(|(&id, &(generation, _))| (id, generation))
+        })
+        .ok_or_else(|| PdfError::Parse(format!("No document catalog found in {}", input_file)))?;
+    let info = last_reference("Info");
 
-    if all_streams.is_empty() {
-        return Err(anyhow!("No pages found in {}", input_file));
+    let version = if data.starts_with(b"%PDF-") {
+        let end = data.iter().position(|&b| b == b'\n' || b == b'\r').unwrap_or(data.len()).min(16);
+        String::from_utf8_lossy(&data[..end]).to_string()
+    } else {
+        "%PDF-1.4".to_string()
+    };
+    let mut out = format!("{}\n%\u{e2}\u{e3}\u{cf}\u{d3}\n", version)
+        .chars()
+        .map(|c| c as u8)
+        .collect::<Vec<u8>>();
+
+    let size = objects.keys().max().map_or(1, |max| max + 1);
+    let mut offsets = vec![None; size as usize];
+    for (&id, (generation, body)) in objects {
+        offsets[id as usize] = Some((out.len(), *generation));
+        out.extend_from_slice(body);
+        out.push(b'\n');
     }
 
-    // Load the image
-    let image_info = crate::image::load_image(image_path)?;
-    let mut generator = crate::pdf_generator::PdfGenerator::new();
-
-    // Create image XObject
-    let image_id = crate::image::create_image_object(&mut generator, image_info.clone())?;
-
-    // Create overlay content stream
-    let mut overlay_content = Vec::new();
-    if opacity < 1.0 {
-        // Set transparency
-        overlay_content.extend_from_slice(format!("{} {} {} rg\n", opacity, opacity, opacity).as_bytes());
+    let xref_offset = out.len();
+    out.extend_from_slice(format!("xref\n0 {}\n", size).as_bytes());
+    for entry in &offsets {
+        match entry {
+            Some((offset, generation)) => out.extend_from_slice(format!("{:010} {:05} n \n", offset, generation).as_bytes()),
+            None => out.extend_from_slice(b"0000000000 65535 f \n"),
+        }
     }
-    overlay_content.extend_from_slice(b"q\n");
-    overlay_content.extend_from_slice(format!("{} 0 0 {} {} {} cm\n", width, height, x, y).as_bytes());
-    overlay_content.extend_from_slice(b"/Im1 Do\n");
-    overlay_content.extend_from_slice(b"Q\n");
-
-    let layout = crate::pdf_generator::PageLayout::portrait();
-
-    // For each page, append the overlay content
-    let overlayed: Vec<Vec<u8>> = all_streams
-        .iter()
-        .enumerate()
-        .map(|(i, stream)| {
-            let mut combined = stream.clone();
-            combined.extend_from_slice(&overlay_content);
-            combined
-        })
-        .collect();
-
-    // Assemble with the image XObject added to resources
-    assemble_pdf_with_image_overlay(output_file, &overlayed, "Helvetica", &layout, image_id)?;
-    println!(
-        "[overlay] Added image overlay '{}' to {} pages in {}",
-        image_path,
-        overlayed.len(),
-        output_file
+    let info_entry = info.map(|(id, generation)| format!("/Info {} {} R\n", id, generation)).unwrap_or_default();
+    out.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {}\n/Root {} {} R\n{}{}>>\nstartxref\n{}\n%%EOF\n",
+            size, root.0, root.1, info_entry, trailer_entries, xref_offset
+        )
+        .as_bytes(),
     );
-    Ok(())
+    Ok(out)
 }
 
-/// Assemble PDF with image overlay XObject in resources
-fn assemble_pdf_with_image_overlay(
-    filename: &str,
-    page_streams: &[Vec<u8>],
-    font: &str,
-    layout: &crate::pdf_generator::PageLayout,
-    image_id: u32,
-) -> Result<()> {
-    let mut generator = crate::pdf_generator::PdfGenerator::new();
-    let mut page_ids = Vec::new();
-    let pages_obj_id = (page_streams.len() as u32) * 3 + 2;
+/// Objects of a file by object number, as `(generation, bytes)`
+type RawObjects = std::collections::BTreeMap<u32, (u16, Vec<u8>)>;
+
+/// Each `N G obj ... endobj` in `data` by object number, as `(generation, bytes)`.
+/// Stream data is skipped over so an `endobj` inside it does not end the object.
+fn scan_raw_objects(data: &[u8]) -> std::collections::BTreeMap<u32, (u16, Vec<u8>)> {
+    let obj_re = regex::bytes::Regex::new(r"(?m)(?:^|\r)(\d+)\s+(\d+)\s+obj\b").unwrap();
+    let mut objects = std::collections::BTreeMap::new();
+    let mut last_end = 0;
+    for caps in obj_re.captures_iter(data) {
+        let (Some(id), Some(generation)) = (
+            std::str::from_utf8(&caps[1]).ok().and_then(|s| s.parse::<u32>().ok()),
+            std::str::from_utf8(&caps[2]).ok().and_then(|s| s.parse::<u16>().ok()),
+        ) else {
+            continue;
+        };
+        let start = caps.get(1).unwrap().start();
+        if start < last_end {
+            // Looks like an object header but lies inside the previous object
+            continue;
+        }
+        let body_start = caps.get(0).unwrap().end();
+        let rest = &data[body_start..];
+        let mut search_from = body_start;
+        if let Some(stream) = crate::pdf::find_bytes(rest, b"stream")
+            && crate::pdf::find_bytes(rest, b"endobj").is_some_and(|end| stream < end)
+            && let Some(end_stream) = crate::pdf::find_bytes(&rest[stream..], b"endstream")
+        {
+            search_from = body_start + stream + end_stream;
+        }
+        let Some(end) = crate::pdf::find_bytes(&data[search_from..], b"endobj") else {
+            continue;
+        };
+        let end = search_from + end + b"endobj".len();
+        last_end = end;
+        objects.insert(id, (generation, data[start..end].to_vec()));
+    }
+    objects
+}
 
-    for page_stream in page_streams {
-        let content_id = generator.add_stream_object(
-            format!("<< /Length {} >>\n", page_stream.len()),
-            page_stream.clone(),
-        );
-        let font_id = content_id + 2;
+/// Move the members of each object stream (`/Type /ObjStm`) into `objects` as
+/// top-level objects and drop the object streams and cross-reference streams,
+/// whose entries a rebuilt xref table cannot express. Top-level definitions win
+/// over compressed ones.
+fn expand_object_streams(objects: &mut std::collections::BTreeMap<u32, (u16, Vec<u8>)>) {
+    let objstm_re = regex::bytes::Regex::new(r"/Type\s*/ObjStm\b").unwrap();
+    let xref_re = regex::bytes::Regex::new(r"/Type\s*/XRef\b").unwrap();
+    let number = |body: &[u8], key: &str| -> Option<usize> {
+        let re = regex::bytes::Regex::new(&format!(r"/{}\s+(\d+)", key)).unwrap();
+        std::str::from_utf8(&re.captures(body)?[1]).ok()?.parse().ok()
+    };
+    let filter_re = regex::bytes::Regex::new(r"/Filter\s*(\[[^\]]*\]|/[^\s/\[\]<>()]+)").unwrap();
+    let name_re = regex::bytes::Regex::new(r"/([^\s/\[\]<>()]+)").unwrap();
+    let stream_re = regex::bytes::Regex::new(r">>\s*stream(\r\n|\r|\n)").unwrap();
+
+    let mut members = Vec::new();
+    objects.retain(|_, (_, body)| {
+        if xref_re.is_match(body) {
+            return false;
+        }
+        if !objstm_re.is_match(body) {
+            return true;
+        }
+        let (Some(n), Some(first), Some(caps)) = (number(body, "N"), number(body, "First"), stream_re.captures(body))
+        else {
+            return true;
+        };
+        let dict_end = caps.get(0).unwrap().start();
+        let data_start = caps.get(0).unwrap().end();
+        let Some(end) = crate::pdf::find_bytes(&body[data_start..], b"endstream") else {
+            return true;
+        };
+        let filters: Vec<String> = filter_re
+            .captures(&body[..dict_end])
+            .map(|f| {
+                name_re
+                    .captures_iter(&f[1])
+                    .map(|c| String::from_utf8_lossy(&c[1]).to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let filters: Vec<&str> = filters.iter().map(String::as_str).collect();
+        let Ok(data) = crate::pdf::decode_filters(&body[data_start..data_start + end], &filters) else {
+            return true;
+        };
+        // The header holds N pairs of object number and offset from /First
+        let header = String::from_utf8_lossy(&data[..first.min(data.len())]).to_string();
+        let tokens: Vec<&str> = header.split_whitespace().collect();
+        let entries: Vec<(u32, usize)> = tokens
+            .chunks(2)
+            .take(n)
+            .filter_map(|pair| Some((pair.first()?.parse().ok()?, pair.get(1)?.parse().ok()?)))
+            .collect();
+        for (i, &(id, offset)) in entries.iter().enumerate() {
+            let end = entries.get(i + 1).map_or(data.len(), |next| first + next.1);
+            if let Some(member) = data.get(first + offset..end) {
+                members.push((id, member.trim_ascii().to_vec()));
+            }
+        }
+        false
+    });
+    for (id, member) in members {
+        objects.entry(id).or_insert_with(|| {
+            let mut body = format!("{} 0 obj\n", id).into_bytes();
+            body.extend_from_slice(&member);
+            body.extend_from_slice(b"\nendobj");
+            (0, body)
+        });
+    }
+}
+
+/// Append pages rendered from `elements` to an existing PDF as an incremental update.
+///
+/// The original bytes are kept unchanged. The new content streams, pages and fonts,
+/// a rewritten root `/Pages` node and catalog, and a new xref section whose trailer
+/// points back at the old one via `/Prev` are appended after them.
+pub fn append_page(input_file: &str, output_file: &str, elements: &[crate::elements::Element]) -> Result<()> {
+    let original = fs::read(input_file)?;
+    let doc = crate::pdf::PdfDocument::from_bytes(&original)?;
+    let mut update = IncrementalUpdate::new(original, &doc, input_file)?;
+
+    let catalog = doc
+        .objects
+        .get(&doc.catalog)
+        .and_then(|o| o.as_dict())
+        .ok_or_else(|| PdfError::Parse(format!("No document catalog found in {}", input_file)))?;
+    let pages_root = catalog
+        .get("Pages")
+        .and_then(|v| v.as_reference())
+        .ok_or_else(|| PdfError::Parse("Catalog has no /Pages reference".into()))?;
+    let pages_dict = doc
+        .objects
+        .get(&pages_root)
+        .and_then(|o| o.as_dict())
+        .ok_or_else(|| PdfError::Parse(format!("Page tree root {} not found", pages_root)))?;
+
+    // New pages match the size of the current last page
+    let mut layout = crate::pdf_generator::PageLayout::portrait();
+    if let Some(&last) = doc.pages.last() {
+        let media_box = page_media_box(&doc, last);
+        layout.width = media_box[2] - media_box[0];
+        layout.height = media_box[3] - media_box[1];
+    }
+    let page_streams = crate::pdf_generator::render_page_streams(elements, 12.0, layout);
 
-        let page_dict = format!(
-            "<< /Type /Page\n\
-             /Parent {} 0 R\n\
-             /MediaBox [0 0 {} {}]\n\
-             /Contents {} 0 R\n\
-             /Resources << /Font << /F1 {} 0 R >> /XObject << /Im1 {} 0 R >> >>\n\
-             >>\n",
-            pages_obj_id, layout.width, layout.height, content_id, font_id, image_id
+    let mut font_refs = String::new();
+    for font in crate::pdf_generator::STANDARD_FONTS {
+        let id = update.add_object(
+            format!("<< /Type /Font\n/Subtype /Type1\n/BaseFont /{}\n>>", font).into_bytes(),
         );
-        let page_id = generator.add_object(page_dict);
-        page_ids.push(page_id);
+        font_refs.push_str(&format!("/{} {} 0 R ", font, id));
+    }
 
-        let font_dict = format!(
-            "<< /Type /Font\n/Subtype /Type1\n/BaseFont /{}\n>>\n",
-            font
+    let mut kids: Vec<String> = match pages_dict.get("Kids").and_then(|v| doc.resolve(v)) {
+        Some(crate::pdf::PdfObject::Array(items)) => items.iter().map(value_to_string).collect(),
+        _ => Vec::new(),
+    };
+    for stream in &page_streams {
+        let mut body = format!("<< /Length {} >>\nstream\n", stream.len()).into_bytes();
+        body.extend_from_slice(stream);
+        body.extend_from_slice(b"\nendstream");
+        let content_id = update.add_object(body);
+        let page_id = update.add_object(
+            format!(
+                "<< /Type /Page\n/Parent {} 0 R\n/MediaBox [0 0 {} {}]\n/Contents {} 0 R\n/Resources << /Font << {}>> >>\n>>",
+                pages_root, layout.width, layout.height, content_id, font_refs
+            )
+            .into_bytes(),
         );
-        generator.add_object(font_dict);
+        kids.push(format!("{} 0 R", page_id));
     }
 
-    let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
-    let pages_dict = format!(
-        "<< /Type /Pages\n/Kids [{}]\n/Count {}\n>>\n",
-        kids.join(" "),
-        page_ids.len()
+    // Rewrite the page tree root and catalog in place, keeping their other entries
+    let mut pages_entries = pages_dict.clone();
+    pages_entries.remove("Kids");
+    pages_entries.remove("Count");
+    update.set_object(
+        pages_root,
+        format!(
+            "<< {} /Kids [{}] /Count {} >>",
+            dict_to_string(&pages_entries),
+            kids.join(" "),
+            doc.pages.len() + page_streams.len()
+        )
+        .into_bytes(),
     );
-    let actual_pages_id = generator.add_object(pages_dict);
-    assert_eq!(actual_pages_id, pages_obj_id);
+    update.set_object(doc.catalog, format!("<< {} >>", dict_to_string(catalog)).into_bytes());
 
-    let catalog_dict = format!(
-        "<< /Type /Catalog\n/Pages {} 0 R\n>>\n",
-        actual_pages_id
+    fs::write(output_file, update.finish())?;
+    log::info!(
+        "[append] Appended {} page(s) to {} into {}",
+        page_streams.len(),
+        input_file,
+        output_file
     );
-    generator.add_object(catalog_dict);
-
-    let pdf_data = generator.generate();
-    let mut file = std::fs::File::create(filename)?;
-    std::io::Write::write_all(&mut file, &pdf_data)?;
     Ok(())
 }
 
-/// Watermark type for different watermark styles
-#[derive(Debug, Clone, Copy)]
-pub enum WatermarkType {
-    Text,
-    Image,
+/// Objects appended to an existing PDF as an incremental update.
+///
+/// The original bytes are kept untouched; new and replaced objects are written
+/// after them with their own xref section and a trailer pointing back via /Prev.
+struct IncrementalUpdate {
+    original: Vec<u8>,
+    root: u32,
+    prev_xref: usize,
+    info: Option<String>,
+    next_id: u32,
+    objects: Vec<(u32, Vec<u8>)>,
 }
 
-/// Create a watermark with either text or image
-pub enum WatermarkContent {
-    Text(String),
-    Image(String), // path to image file
-}
+impl IncrementalUpdate {
+    fn new(original: Vec<u8>, doc: &crate::pdf::PdfDocument, input_file: &str) -> Result<Self> {
+        let content: String = original.iter().map(|&b| b as char).collect();
+        let prev_xref: usize = content
+            .rfind("startxref")
+            .and_then(|pos| content[pos + 9..].split_whitespace().next())
+            .and_then(|n| n.parse().ok())
+            .ok_or_else(|| PdfError::Parse(format!("No startxref found in {}", input_file)))?;
+        let trailer = content.rfind("trailer").map(|pos| &content[pos..]).unwrap_or("");
+        let size_re = regex::Regex::new(r"/Size\s+(\d+)").unwrap();
+        let old_size = size_re
+            .captures(trailer)
+            .and_then(|c| c[1].parse::<u32>().ok())
+            .unwrap_or(0);
+        let info_re = regex::Regex::new(r"/Info\s+(\d+\s+\d+\s+R)").unwrap();
+        let info = info_re.captures(trailer).map(|c| c[1].to_string());
+        let next_id = doc.objects.keys().copied().max().unwrap_or(0).max(old_size.saturating_sub(1)) + 1;
+
+        Ok(IncrementalUpdate { original, root: doc.catalog, prev_xref, info, next_id, objects: Vec::new() })
+    }
 
-/// Add a watermark to every page of a PDF with support for text or image watermarks
-pub fn watermark_pdf_advanced(
-    input_file: &str,
-    output_file: &str,
-    content: WatermarkContent,
-    opacity: f32,
-    position: WatermarkPosition,
-) -> Result<()> {
-    let doc = crate::pdf::PdfDocument::load_from_file(input_file)?;
-    let all_streams = extract_page_streams(&doc);
+    /// Add a new object and return its object number
+    fn add_object(&mut self, body: Vec<u8>) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.objects.push((id, body));
+        id
+    }
 
-    if all_streams.is_empty() {
-        return Err(anyhow!("No pages found in {}", input_file));
+    /// Replace an existing object
+    fn set_object(&mut self, id: u32, body: Vec<u8>) {
+        self.objects.push((id, body));
     }
 
-    let layout = crate::pdf_generator::PageLayout::portrait();
-    let watermark_stream = match content {
-        WatermarkContent::Text(text) => {
-            build_text_watermark_stream(&text, 48.0, opacity, &layout, position)
+    /// The original bytes followed by the update section
+    fn finish(self) -> Vec<u8> {
+        let mut pdf = self.original;
+        if !pdf.ends_with(b"\n") {
+            pdf.push(b'\n');
         }
-        WatermarkContent::Image(image_path) => {
-            let image_info = crate::image::load_image(&image_path)?;
-            build_image_watermark_stream(&image_info, opacity, &layout, position)?
+        let mut offsets = std::collections::BTreeMap::new();
+        for (id, body) in &self.objects {
+            offsets.insert(*id, pdf.len());
+            pdf.extend_from_slice(format!("{} 0 obj\n", id).as_bytes());
+            pdf.extend_from_slice(body);
+            pdf.extend_from_slice(b"\nendobj\n");
         }
-    };
 
-    // Append watermark content to each page stream
-    let watermarked: Vec<Vec<u8>> = all_streams
+        // One xref subsection per run of consecutive object numbers
+        let xref_offset = pdf.len();
+        pdf.extend_from_slice(b"xref\n");
+        let ids: Vec<u32> = offsets.keys().copied().collect();
+        let mut start = 0;
+        while start < ids.len() {
+            let mut end = start + 1;
+            while end < ids.len() && ids[end] == ids[end - 1] + 1 {
+                end += 1;
+            }
+            pdf.extend_from_slice(format!("{} {}\n", ids[start], end - start).as_bytes());
+            for id in &ids[start..end] {
+                pdf.extend_from_slice(format!("{:010} 00000 n \n", offsets[id]).as_bytes());
+            }
+            start = end;
+        }
+
+        pdf.extend_from_slice(b"trailer\n<<\n");
+        pdf.extend_from_slice(format!("/Size {}\n/Root {} 0 R\n", self.next_id, self.root).as_bytes());
+        if let Some(info) = self.info {
+            pdf.extend_from_slice(format!("/Info {}\n", info).as_bytes());
+        }
+        pdf.extend_from_slice(
+            format!("/Prev {}\n>>\nstartxref\n{}\n%%EOF\n", self.prev_xref, xref_offset).as_bytes(),
+        );
+        pdf
+    }
+}
+
+/// A file embedded in a PDF's `/EmbeddedFiles` name tree
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub name: String,
+    pub description: Option<String>,
+    pub size: usize,
+}
+
+/// Embed `file_path` in a PDF as a document-level attachment.
+///
+/// The file is stored as an `/EmbeddedFile` stream, referenced from a
+/// `/Filespec` in the catalog's `/Names /EmbeddedFiles` name tree, and written
+/// as an incremental update. Attaching a name that already exists replaces it.
+pub fn attach_file(input_file: &str, output_file: &str, file_path: &str, description: &str) -> Result<()> {
+    let original = fs::read(input_file)?;
+    let doc = crate::pdf::PdfDocument::from_bytes(&original)?;
+    let data = fs::read(file_path)?;
+    let name = std::path::Path::new(file_path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or_else(|| PdfError::InvalidInput(format!("Not a file path: {}", file_path)))?;
+
+    let catalog = doc
+        .objects
+        .get(&doc.catalog)
+        .and_then(|o| o.as_dict())
+        .ok_or_else(|| PdfError::Parse(format!("No document catalog found in {}", input_file)))?;
+    let mut update = IncrementalUpdate::new(original.clone(), &doc, input_file)?;
+
+    // Hex-encoded so arbitrary bytes survive line-based parsing
+    let hex: String = data.iter().map(|b| format!("{:02X}", b)).collect();
+    let mut body = format!(
+        "<< /Type /EmbeddedFile /Filter /ASCIIHexDecode /Length {} /Params << /Size {} >> >>\nstream\n",
+        hex.len() + 1,
+        data.len()
+    )
+    .into_bytes();
+    body.extend_from_slice(hex.as_bytes());
+    body.extend_from_slice(b">\nendstream");
+    let file_id = update.add_object(body);
+    let spec_id = update.add_object(
+        format!(
+            "<< /Type /Filespec /F {} /UF {} /Desc {} /EF << /F {} 0 R >> >>",
+            pdf_text_string(&name),
+            pdf_text_string(&name),
+            pdf_text_string(description),
+            file_id
+        )
+        .into_bytes(),
+    );
+
+    // Rebuild the name tree as a single sorted leaf
+    let mut entries: Vec<(String, String)> = embedded_file_specs(&doc)
+        .into_iter()
+        .filter(|(existing, _)| *existing != name)
+        .map(|(existing, spec)| (existing, value_to_string(&spec)))
+        .collect();
+    entries.push((name.clone(), format!("{} 0 R", spec_id)));
+    entries.sort();
+    let names: Vec<String> = entries
         .iter()
-        .map(|stream| {
-            let mut combined = stream.clone();
-            combined.extend_from_slice(&watermark_stream);
-            combined
-        })
+        .map(|(n, spec)| format!("{} {}", pdf_text_string(n), spec))
         .collect();
+    let tree_id = update.add_object(format!("<< /Names [{}] >>", names.join(" ")).into_bytes());
 
-    assemble_merged_pdf(output_file, &watermarked, "Helvetica", &layout)?;
-    println!(
-        "[watermark] Added watermark to {} pages in {}",
-        watermarked.len(),
-        output_file
+    let mut names_dict = match doc.dict_get(catalog, "Names").and_then(|o| o.as_dict()) {
+        Some(dict) => dict.clone(),
+        None => std::collections::HashMap::new(),
+    };
+    names_dict.remove("EmbeddedFiles");
+    let mut catalog_entries = catalog.clone();
+    catalog_entries.remove("Names");
+    update.set_object(
+        doc.catalog,
+        format!(
+            "<< {} /Names << {} /EmbeddedFiles {} 0 R >> >>",
+            dict_to_string(&catalog_entries),
+            dict_to_string(&names_dict),
+            tree_id
+        )
+        .into_bytes(),
     );
+
+    fs::write(output_file, update.finish())?;
+    log::info!("[attach] Attached {} ({} bytes) to {} into {}", name, data.len(), input_file, output_file);
     Ok(())
 }
 
-/// Watermark position on the page
-#[derive(Debug, Clone, Copy)]
-pub enum WatermarkPosition {
-    Center,
-    TopLeft,
-    TopRight,
-    BottomLeft,
-    BottomRight,
-    Diagonal, // Traditional diagonal watermark
+/// List the files attached to a PDF
+pub fn list_attachments(input_file: &str) -> Result<Vec<Attachment>> {
+    let doc = crate::pdf::PdfDocument::load_from_file(input_file)?;
+    Ok(embedded_file_specs(&doc)
+        .into_iter()
+        .map(|(name, spec)| {
+            let spec = doc.resolve(&spec).and_then(|o| o.as_dict());
+            let description = spec.and_then(|s| match doc.dict_get(s, "Desc") {
+                Some(crate::pdf::PdfObject::String(desc)) => Some(desc.clone()),
+                _ => None,
+            });
+            let size = spec
+                .and_then(|s| embedded_file_data(&doc, s).ok())
+                .map(|data| data.len())
+                .unwrap_or(0);
+            Attachment { name, description, size }
+        })
+        .collect())
 }
 
-/// Build a text watermark stream with positioning
-fn build_text_watermark_stream(
-    text: &str,
-    font_size: f32,
-    opacity: f32,
-    layout: &crate::pdf_generator::PageLayout,
-    position: WatermarkPosition,
-) -> Vec<u8> {
-    let escaped = escape_pdf_meta(text);
-    let (x, y, rotation) = match position {
-        WatermarkPosition::Center => {
-            (layout.width / 2.0, layout.height / 2.0, 0.0)
+/// Write the attachment called `name` to `output_file`
+pub fn extract_attachment(input_file: &str, name: &str, output_file: &str) -> Result<()> {
+    let doc = crate::pdf::PdfDocument::load_from_file(input_file)?;
+    let spec = embedded_file_specs(&doc)
+        .into_iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, spec)| spec)
+        .ok_or_else(|| PdfError::InvalidInput(format!("No attachment named {} in {}", name, input_file)))?;
+    let spec = doc
+        .resolve(&spec)
+        .and_then(|o| o.as_dict())
+        .ok_or_else(|| PdfError::Parse(format!("Attachment {} has no file specification", name)))?;
+    let data = embedded_file_data(&doc, spec)?;
+    fs::write(output_file, &data)?;
+    log::info!("[attach] Extracted {} ({} bytes) into {}", name, data.len(), output_file);
+    Ok(())
+}
+
+/// (name, file specification) pairs from the catalog's EmbeddedFiles name tree
+fn embedded_file_specs(doc: &crate::pdf::PdfDocument) -> Vec<(String, crate::pdf::PdfValue)> {
+    let mut specs = Vec::new();
+    let tree = doc
+        .objects
+        .get(&doc.catalog)
+        .and_then(|o| o.as_dict())
+        .and_then(|c| doc.dict_get(c, "Names"))
+        .and_then(|o| o.as_dict())
+        .and_then(|n| doc.dict_get(n, "EmbeddedFiles"));
+    if let Some(tree) = tree {
+        collect_name_tree(doc, tree, &mut specs, 0);
+    }
+    specs
+}
+
+fn collect_name_tree(
+    doc: &crate::pdf::PdfDocument,
+    node: &crate::pdf::PdfObject,
+    out: &mut Vec<(String, crate::pdf::PdfValue)>,
+    depth: usize,
+) {
+    let Some(dict) = node.as_dict() else { return };
+    if depth > 32 {
+        return;
+    }
+    if let Some(crate::pdf::PdfObject::Array(items)) = doc.dict_get(dict, "Names") {
+        for pair in items.chunks(2) {
+            if let [key, value] = pair
+                && let Some(crate::pdf::PdfObject::String(name)) = doc.resolve(key)
+            {
+                out.push((name.clone(), value.clone()));
+            }
         }
-        WatermarkPosition::TopLeft => {
-            (72.0, layout.height - 72.0, 0.0)
+    }
+    if let Some(crate::pdf::PdfObject::Array(kids)) = doc.dict_get(dict, "Kids") {
+        for kid in kids {
+            if let Some(kid) = doc.resolve(kid) {
+                collect_name_tree(doc, kid, out, depth + 1);
+            }
         }
-        WatermarkPosition::TopRight => {
-            (layout.width - 72.0, layout.height - 72.0, 0.0)
+    }
+}
+
+/// Decoded bytes of the /EF /F stream of a file specification
+fn embedded_file_data(
+    doc: &crate::pdf::PdfDocument,
+    spec: &std::collections::HashMap<String, crate::pdf::PdfValue>,
+) -> Result<Vec<u8>> {
+    let stream = doc
+        .dict_get(spec, "EF")
+        .and_then(|o| o.as_dict())
+        .and_then(|ef| ef.get("F").or_else(|| ef.get("UF")))
+        .and_then(|v| doc.resolve(v));
+    let Some(crate::pdf::PdfObject::Stream { dictionary, data }) = stream else {
+        return Err(PdfError::Parse("File specification has no embedded file stream".into()));
+    };
+    let filters: Vec<&str> = match doc.dict_get(dictionary, "Filter") {
+        Some(crate::pdf::PdfObject::Name(name)) => vec![name.as_str()],
+        Some(crate::pdf::PdfObject::Array(items)) => items
+            .iter()
+            .filter_map(|v| doc.resolve(v).and_then(|o| o.as_name()))
+            .collect(),
+        _ => Vec::new(),
+    };
+    crate::pdf::decode_filters(data, &filters)
+}
+
+/// Differences between two PDFs, as reported by [`diff_pdfs`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PdfDiff {
+    /// Page counts of the left and right document
+    pub page_counts: (usize, usize),
+    /// Pages whose content differs, in page order
+    pub pages: Vec<PageDiff>,
+}
+
+/// One page that differs between two PDFs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PageDiff {
+    /// 1-indexed page number
+    pub page: usize,
+    /// Extracted text of the left page (`None` if the page only exists on the right)
+    pub left_text: Option<String>,
+    pub right_text: Option<String>,
+    /// The pages draw different images
+    pub images_differ: bool,
+}
+
+impl PdfDiff {
+    /// Whether both documents have the same pages with the same content
+    pub fn is_equal(&self) -> bool {
+        self.page_counts.0 == self.page_counts.1 && self.pages.is_empty()
+    }
+
+    /// Human-readable report, one line per difference
+    pub fn summary(&self) -> String {
+        if self.is_equal() {
+            return format!("PDFs are identical ({} pages)\n", self.page_counts.0);
         }
-        WatermarkPosition::BottomLeft => {
-            (72.0, 72.0, 0.0)
+        let mut out = String::new();
+        if self.page_counts.0 != self.page_counts.1 {
+            out.push_str(&format!("Page count differs: {} vs {}\n", self.page_counts.0, self.page_counts.1));
         }
-        WatermarkPosition::BottomRight => {
-            (layout.width - 72.0, 72.0, 0.0)
+        for page in &self.pages {
+            match (&page.left_text, &page.right_text) {
+                (Some(_), None) => out.push_str(&format!("Page {}: only in first PDF\n", page.page)),
+                (None, Some(_)) => out.push_str(&format!("Page {}: only in second PDF\n", page.page)),
+                (Some(left), Some(right)) if left != right => {
+                    let first = left
+                        .lines()
+                        .zip(right.lines())
+                        .find(|(l, r)| l != r)
+                        .map(|(l, r)| format!(": {:?} vs {:?}", l, r))
+                        .unwrap_or_default();
+                    out.push_str(&format!("Page {}: text differs{}\n", page.page, first));
+                }
+                _ => {}
+            }
+            if page.images_differ {
+                out.push_str(&format!("Page {}: images differ\n", page.page));
+            }
         }
-        WatermarkPosition::Diagonal => {
-            (layout.width / 2.0 - 100.0, layout.height / 2.0 - 50.0, 45.0)
+        out
+    }
+}
+
+/// Compare two PDFs page by page: page count, extracted text and embedded images
+pub fn diff_pdfs(left_file: &str, right_file: &str) -> Result<PdfDiff> {
+    let left = crate::pdf::PdfDocument::load_from_file(left_file)?;
+    let right = crate::pdf::PdfDocument::load_from_file(right_file)?;
+    let left_text = left.get_text_by_page()?;
+    let right_text = right.get_text_by_page()?;
+
+    let mut pages = Vec::new();
+    for i in 0..left_text.len().max(right_text.len()) {
+        let (l, r) = (left_text.get(i), right_text.get(i));
+        let images_differ = match (left.pages.get(i), right.pages.get(i)) {
+            (Some(&lp), Some(&rp)) => page_image_hashes(&left, lp) != page_image_hashes(&right, rp),
+            _ => false,
+        };
+        if l != r || images_differ {
+            pages.push(PageDiff {
+                page: i + 1,
+                left_text: l.cloned(),
+                right_text: r.cloned(),
+                images_differ,
+            });
         }
-    };
+    }
+    Ok(PdfDiff { page_counts: (left_text.len(), right_text.len()), pages })
+}
 
-    let mut stream = Vec::new();
-    stream.extend_from_slice(b"q\n");
-    stream.extend_from_slice(format!("{} {} {} rg\n", opacity, opacity, opacity).as_bytes());
-    stream.extend_from_slice(b"BT\n");
-    stream.extend_from_slice(format!("/F1 {} Tf\n", font_size).as_bytes());
+/// Sorted hashes of the image XObjects in a page's resources
+fn page_image_hashes(doc: &crate::pdf::PdfDocument, page_id: u32) -> Vec<u64> {
+    use std::hash::{Hash, Hasher};
+    let mut hashes: Vec<u64> = doc
+        .page_attribute(page_id, "Resources")
+        .and_then(|r| r.as_dict())
+        .and_then(|r| doc.dict_get(r, "XObject"))
+        .and_then(|x| x.as_dict())
+        .map(|xobjects| {
+            xobjects
+                .values()
+                .filter_map(|v| match doc.resolve(v) {
+                    Some(crate::pdf::PdfObject::Stream { dictionary, data })
+                        if dictionary.get("Subtype").and_then(|s| s.as_name()) == Some("Image") =>
+                    {
+                        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                        data.hash(&mut hasher);
+                        Some(hasher.finish())
+                    }
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    hashes.sort();
+    hashes
+}
 
-    if rotation != 0.0 {
-        let rad = rotation * std::f32::consts::PI / 180.0;
-        let cos = rad.cos();
-        let sin = rad.sin();
-        stream.extend_from_slice(
-            format!("{} {} {} {} {} {} Tm\n", cos, sin, -sin, cos, x, y).as_bytes()
-        );
-    } else {
-        stream.extend_from_slice(format!("{} {} Td\n", x, y).as_bytes());
+/// A rectangle in PDF user space (points, origin at the bottom-left of the page)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Rect { x, y, width, height }
     }
 
-    stream.extend_from_slice(format!("({}) Tj\n", escaped).as_bytes());
-    stream.extend_from_slice(b"ET\n");
-    stream.extend_from_slice(b"Q\n");
-    stream
+    /// Whether the point lies inside the rectangle (edges included)
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && x <= self.x + self.width && y >= self.y && y <= self.y + self.height
+    }
 }
 
-/// Build an image watermark stream with positioning
-fn build_image_watermark_stream(
-    image_info: &crate::image::ImageInfo,
-    opacity: f32,
-    layout: &crate::pdf_generator::PageLayout,
-    position: WatermarkPosition,
-) -> Result<Vec<u8>> {
-    // Scale image to fit page if too large
-    let max_width = layout.width * 0.5;
-    let max_height = layout.height * 0.5;
-    let (img_width, img_height) = crate::image::scale_to_fit(
-        image_info.width,
-        image_info.height,
-        max_width,
-        max_height,
+/// Redact regions of a PDF.
+///
+/// `regions` pairs a 1-indexed page number with a rectangle. Every text-showing
+/// operator (`Tj`, `TJ`, `'`, `"`) whose text position lies inside a rectangle is
+/// removed from that page's content stream, and an opaque black box is drawn
+/// over the rectangle. The text is gone from the output, not just hidden: pages
+/// are copied as Form XObjects, keeping their size, fonts and images, into a
+/// new file that does not contain the original content streams.
+pub fn redact_pdf(input_file: &str, output_file: &str, regions: &[(usize, Rect)]) -> Result<()> {
+    let doc = crate::pdf::PdfDocument::load_from_file(input_file)?;
+    if doc.pages.is_empty() {
+        return Err(PdfError::InvalidInput(format!("No pages found in {}", input_file)));
+    }
+    for &(page, _) in regions {
+        if page == 0 || page > doc.pages.len() {
+            return Err(PdfError::InvalidPageRange { start: page, end: page, page_count: Some(doc.pages.len()) });
+        }
+    }
+
+    let mut generator = crate::pdf_generator::PdfGenerator::new();
+    let mut importer = ObjectImporter::new(&doc);
+    let mut pages = Vec::new();
+    let mut removed = 0;
+    for (index, &page_id) in doc.pages.iter().enumerate() {
+        let rects: Vec<Rect> = regions
+            .iter()
+            .filter(|(page, _)| *page == index + 1)
+            .map(|(_, rect)| *rect)
+            .collect();
+        let form_id = if rects.is_empty() {
+            import_page_as_form(&doc, index, &mut generator, &mut importer)?.0
+        } else {
+            let (mut redacted, count) = remove_text_in_rects(&doc.page_content_streams(index).join(&b'\n'), &rects);
+            removed += count;
+            redacted.extend_from_slice(b"\nq\n0 0 0 rg\n");
+            for rect in &rects {
+                redacted.extend_from_slice(
+                    format!("{} {} {} {} re f\n", rect.x, rect.y, rect.width, rect.height).as_bytes(),
+                );
+            }
+            redacted.extend_from_slice(b"Q\n");
+            add_page_form(&doc, page_id, redacted, "", &mut generator, &mut importer)
+        };
+        let (_, drawn) = page_form_matrix(&doc, page_id);
+        pages.push(FormPage {
+            content: form_placement_content([1.0, 0.0, 0.0, 1.0, -drawn[0], -drawn[1]]).into_bytes(),
+            xobjects: vec![("Pg0".to_string(), form_id)],
+            size: Some((drawn[2] - drawn[0], drawn[3] - drawn[1])),
+            annots: Vec::new(),
+        });
+    }
+
+    let layout = crate::pdf_generator::PageLayout::portrait();
+    fs::write(output_file, assemble_form_pages(generator, &pages, &layout, None))?;
+    log::info!(
+        "[redact] Removed {} text operations in {} regions into {}",
+        removed,
+        regions.len(),
+        output_file
     );
+    Ok(())
+}
 
-    let (x, y) = match position {
-        WatermarkPosition::Center => {
-            ((layout.width - img_width) / 2.0, (layout.height - img_height) / 2.0)
-        }
-        WatermarkPosition::TopLeft => {
-            (36.0, layout.height - img_height - 36.0)
-        }
-        WatermarkPosition::TopRight => {
-            (layout.width - img_width - 36.0, layout.height - img_height - 36.0)
-        }
-        WatermarkPosition::BottomLeft => {
-            (36.0, 36.0)
+/// Make scanned pages searchable by laying invisible text over them.
+///
+/// `layers` pairs a 1-indexed page number with the words or lines found on it
+/// by OCR, each with the rectangle it covers in points from the lower-left
+/// corner of the page as displayed. The text is drawn in Helvetica with
+/// rendering mode 3 (`3 Tr`), scaled to fill its rectangle, so it can be
+/// selected, searched and extracted while the page looks unchanged. Pages
+/// keep their content and resources, being copied as Form XObjects.
+pub fn add_text_layer(input_file: &str, output_file: &str, layers: &[(usize, Vec<(String, Rect)>)]) -> Result<()> {
+    let doc = crate::pdf::PdfDocument::load_from_file(input_file)?;
+    if doc.pages.is_empty() {
+        return Err(PdfError::InvalidInput(format!("No pages found in {}", input_file)));
+    }
+    for &(page, _) in layers {
+        if page == 0 || page > doc.pages.len() {
+            return Err(PdfError::InvalidPageRange { start: page, end: page, page_count: Some(doc.pages.len()) });
         }
-        WatermarkPosition::BottomRight => {
-            (layout.width - img_width - 36.0, 36.0)
+    }
+
+    let mut generator = crate::pdf_generator::PdfGenerator::new();
+    let font_id = generator.add_object(
+        "<< /Type /Font\n/Subtype /Type1\n/BaseFont /Helvetica\n/Encoding /WinAnsiEncoding\n>>\n".to_string(),
+    );
+    let mut importer = ObjectImporter::new(&doc);
+    let mut pages = Vec::new();
+    let mut words = 0;
+    for index in 0..doc.pages.len() {
+        let (form_id, drawn) = import_page_as_form(&doc, index, &mut generator, &mut importer)?;
+        let (page_w, page_h) = (drawn[2] - drawn[0], drawn[3] - drawn[1]);
+        let mut content = form_placement_content([1.0, 0.0, 0.0, 1.0, -drawn[0], -drawn[1]]);
+        let mut xobjects = vec![("Pg0".to_string(), form_id)];
+
+        let mut text = String::from("BT\n3 Tr\n");
+        for (word, rect) in layers.iter().filter(|(page, _)| *page == index + 1).flat_map(|(_, words)| words) {
+            let natural = crate::font_metrics::text_width("Helvetica", word, rect.height);
+            if natural <= 0.0 {
+                continue;
+            }
+            // Baseline above the descender, glyphs stretched to the rectangle's width
+            text.push_str(&format!(
+                "/F1 {} Tf\n{} Tz\n1 0 0 1 {} {} Tm\n{} Tj\n",
+                rect.height,
+                rect.width / natural * 100.0,
+                rect.x,
+                rect.y + rect.height * 0.22,
+                pdf_show_string(word)
+            ));
+            words += 1;
         }
-        WatermarkPosition::Diagonal => {
-            ((layout.width - img_width) / 2.0, (layout.height - img_height) / 2.0)
+        text.push_str("ET\n");
+        if text.contains(" Tj\n") {
+            let layer_id = generator.add_stream_object(
+                format!(
+                    "<< /Type /XObject\n/Subtype /Form\n/BBox [0 0 {} {}]\n/Resources << /Font << /F1 {} 0 R >> >>\n/Length {}\n>>\n",
+                    page_w,
+                    page_h,
+                    font_id,
+                    text.len()
+                ),
+                text.into_bytes(),
+            );
+            content.push_str("/Ocr0 Do\n");
+            xobjects.push(("Ocr0".to_string(), layer_id));
         }
-    };
+        pages.push(FormPage { content: content.into_bytes(), xobjects, size: Some((page_w, page_h)), annots: Vec::new() });
+    }
+
+    let layout = crate::pdf_generator::PageLayout::portrait();
+    fs::write(output_file, assemble_form_pages(generator, &pages, &layout, None))?;
+    log::info!("[ocr] Added {} invisible text runs to {} pages in {}", words, pages.len(), output_file);
+    Ok(())
+}
 
-    let mut stream = Vec::new();
-    stream.extend_from_slice(b"q\n");
-    if opacity < 1.0 {
-        stream.extend_from_slice(format!("{} {} {} rg\n", opacity, opacity, opacity).as_bytes());
+/// Drop text-showing operations positioned inside any of `rects`.
+/// Returns the rewritten stream and the number of operations removed.
+fn remove_text_in_rects(data: &[u8], rects: &[Rect]) -> (Vec<u8>, usize) {
+    let mut out = Vec::with_capacity(data.len());
+    let mut removed = 0;
+    let (mut line_x, mut line_y) = (0.0f32, 0.0f32);
+    let mut copied_to = 0;
+
+    for (operands, operator, span) in crate::content_stream::Tokenizer::new(data).spanned() {
+        let num = |i: usize| operands.get(i).and_then(|o| o.as_number()).unwrap_or(0.0) as f32;
+        match operator.as_str() {
+            "BT" => (line_x, line_y) = (0.0, 0.0),
+            "Tm" if operands.len() == 6 => (line_x, line_y) = (num(4), num(5)),
+            "Td" | "TD" if operands.len() == 2 => {
+                line_x += num(0);
+                line_y += num(1);
+            }
+            "Tj" | "TJ" | "'" | "\"" if rects.iter().any(|r| r.contains(line_x, line_y)) => {
+                out.extend_from_slice(&data[copied_to..span.start]);
+                copied_to = span.end;
+                removed += 1;
+            }
+            _ => {}
+        }
     }
-    stream.extend_from_slice(b"q\n");
-    stream.extend_from_slice(format!("{} 0 0 {} {} {} cm\n", img_width, img_height, x, y).as_bytes());
-    stream.extend_from_slice(b"/Im1 Do\n");
-    stream.extend_from_slice(b"Q\n");
-    stream.extend_from_slice(b"Q\n");
-    Ok(stream)
+    out.extend_from_slice(&data[copied_to..]);
+    (out, removed)
 }
 
-/// Reorder pages in a PDF according to a given order.
+/// Find and replace text in the strings shown by a PDF's pages.
 ///
-/// `page_order` is a list of 1-indexed page numbers in the desired output order.
-/// Example: `[3, 1, 2]` puts page 3 first, then page 1, then page 2.
-pub fn reorder_pages(input_file: &str, output_file: &str, page_order: &[usize]) -> Result<()> {
-    if page_order.is_empty() {
-        return Err(anyhow!("Page order list is empty"));
+/// Each `(find, replace)` pair is applied in order to the string operands of
+/// `Tj`, `TJ`, `'` and `"`, literal and hex alike, and the result is written
+/// back properly escaped. Nothing is re-laid out, so replacements should be
+/// about as long as what they replace; a `find` split across the strings of a
+/// `TJ` array is not matched. Meant for placeholders such as `{{NAME}}`.
+/// The pages' content streams are replaced in an incremental update, so
+/// everything else in the document is kept.
+/// Returns the number of replacements made.
+pub fn replace_text(input_file: &str, output_file: &str, replacements: &[(&str, &str)]) -> Result<usize> {
+    if replacements.iter().any(|(find, _)| find.is_empty()) {
+        return Err(PdfError::InvalidInput("Text to replace must not be empty".into()));
+    }
+    let original = fs::read(input_file)?;
+    let doc = crate::pdf::PdfDocument::from_bytes(&original)?;
+    if doc.pages.is_empty() {
+        return Err(PdfError::InvalidInput(format!("No pages found in {}", input_file)));
     }
 
-    let doc = crate::pdf::PdfDocument::load_from_file(input_file)?;
-    let all_streams = extract_page_streams(&doc);
-    let total = all_streams.len();
+    // Each content stream once, even when pages share it
+    let mut content_ids = Vec::new();
+    for &page_id in &doc.pages {
+        let contents = doc.get_object(page_id).and_then(|p| p.as_dict()).and_then(|p| p.get("Contents"));
+        let parts = match contents.and_then(|c| doc.resolve(c)) {
+            Some(crate::pdf::PdfObject::Array(parts)) => parts.iter().filter_map(|p| p.as_reference()).collect(),
+            _ => contents.and_then(|c| c.as_reference()).into_iter().collect::<Vec<_>>(),
+        };
+        for id in parts {
+            if !content_ids.contains(&id) {
+                content_ids.push(id);
+            }
+        }
+    }
 
-    if total == 0 {
-        return Err(anyhow!("No pages found in {}", input_file));
+    let mut update = IncrementalUpdate::new(original, &doc, input_file)?;
+    let mut replaced = 0;
+    for id in content_ids {
+        let Some(crate::pdf::PdfObject::Stream { dictionary, data }) = doc.get_object(id) else {
+            continue;
+        };
+        // Only streams that are plain or Flate-compressed can be read back
+        let filter = dictionary.get("Filter").and_then(|f| doc.resolve(f));
+        if dictionary.contains_key("DecodeParms")
+            || !matches!(filter.map(|f| f.as_name()), None | Some(Some("FlateDecode")))
+        {
+            continue;
+        }
+        let stream = decompress_if_needed(data);
+
+        let mut out = Vec::with_capacity(stream.len());
+        let mut copied_to = 0;
+        let mut count = 0;
+        for (_, operator, span) in crate::content_stream::Tokenizer::new(&stream).spanned() {
+            if !matches!(operator.as_str(), "Tj" | "TJ" | "'" | "\"") {
+                continue;
+            }
+            let (rewritten, found) = replace_in_strings(&stream[span.clone()], replacements);
+            if found > 0 {
+                out.extend_from_slice(&stream[copied_to..span.start]);
+                out.extend_from_slice(&rewritten);
+                copied_to = span.end;
+                count += found;
+            }
+        }
+        if count == 0 {
+            continue;
+        }
+        out.extend_from_slice(&stream[copied_to..]);
+        replaced += count;
+
+        // Written back uncompressed, with a /Length to match
+        let mut dict = dictionary.clone();
+        for key in ["Filter", "DecodeParms", "Length"] {
+            dict.remove(key);
+        }
+        let mut body = format!("<< {} /Length {} >>\nstream\n", dict_to_string(&dict), out.len()).into_bytes();
+        body.extend_from_slice(&out);
+        body.extend_from_slice(b"\nendstream");
+        update.set_object(id, body);
     }
 
-    // Validate all page numbers
-    for &p in page_order {
-        if p == 0 || p > total {
-            return Err(anyhow!(
-                "Invalid page number {} (document has {} pages)",
-                p,
-                total
-            ));
+    fs::write(output_file, update.finish())?;
+    log::info!("[replace] Made {} replacements into {}", replaced, output_file);
+    Ok(replaced)
+}
+
+/// Apply `replacements` to every string in the operation `data`, copying
+/// everything else as is. Returns the rewritten bytes and the replacement count.
+fn replace_in_strings(data: &[u8], replacements: &[(&str, &str)]) -> (Vec<u8>, usize) {
+    let mut out = Vec::with_capacity(data.len());
+    let mut count = 0;
+    let mut i = 0;
+    while i < data.len() {
+        let (text, end, hex) = match data[i] {
+            b'(' => {
+                let (text, end) = read_literal_string(data, i + 1);
+                (text, end, false)
+            }
+            b'<' if data.get(i + 1) != Some(&b'<') => {
+                let end = data[i..].iter().position(|&b| b == b'>').map_or(data.len(), |p| i + p + 1);
+                let digits: Vec<u32> = data[i + 1..end].iter().filter_map(|&b| (b as char).to_digit(16)).collect();
+                // An odd final digit is followed by an implied 0
+                let text = digits.chunks(2).map(|pair| (pair[0] * 16 + pair.get(1).copied().unwrap_or(0)) as u8).collect();
+                (text, end, true)
+            }
+            b => {
+                out.push(b);
+                i += 1;
+                continue;
+            }
+        };
+
+        let mut replaced = text.clone();
+        for (find, replace) in replacements {
+            let found = count_occurrences(&replaced, find.as_bytes());
+            if found > 0 {
+                replaced = replace_bytes(&replaced, find.as_bytes(), replace.as_bytes());
+                count += found;
+            }
+        }
+        if replaced == text {
+            out.extend_from_slice(&data[i..end]);
+        } else if hex {
+            out.push(b'<');
+            out.extend(replaced.iter().flat_map(|b| format!("{:02X}", b).into_bytes()));
+            out.push(b'>');
+        } else {
+            out.push(b'(');
+            for &b in &replaced {
+                match b {
+                    b'(' | b')' | b'\\' => out.extend_from_slice(&[b'\\', b]),
+                    b' '..=b'~' => out.push(b),
+                    _ => out.extend_from_slice(format!("\\{:03o}", b).as_bytes()),
+                }
+            }
+            out.push(b')');
         }
+        i = end;
     }
+    (out, count)
+}
 
-    let reordered: Vec<Vec<u8>> = page_order
-        .iter()
-        .map(|&p| all_streams[p - 1].clone())
-        .collect();
+fn count_occurrences(haystack: &[u8], needle: &[u8]) -> usize {
+    let mut count = 0;
+    let mut i = 0;
+    while i + needle.len() <= haystack.len() {
+        if haystack[i..].starts_with(needle) {
+            count += 1;
+            i += needle.len();
+        } else {
+            i += 1;
+        }
+    }
+    count
+}
 
-    let layout = crate::pdf_generator::PageLayout::portrait();
-    assemble_merged_pdf(output_file, &reordered, "Helvetica", &layout)?;
-    println!(
-        "[reorder] Reordered {} pages from {} into {}",
-        reordered.len(),
-        input_file,
-        output_file
-    );
-    Ok(())
+fn replace_bytes(haystack: &[u8], needle: &[u8], replacement: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(haystack.len());
+    let mut i = 0;
+    while i < haystack.len() {
+        if haystack[i..].starts_with(needle) {
+            out.extend_from_slice(replacement);
+            i += needle.len();
+        } else {
+            out.push(haystack[i]);
+            i += 1;
+        }
+    }
+    out
 }
 
 /// Apply password protection and permissions to a PDF.
 ///
 /// This function adds security settings to a PDF document, including password protection
-/// and permission restrictions. Note that this is a simplified implementation that adds
-/// the encryption dictionary to the PDF trailer. For production use, you would need
-/// proper cryptographic libraries (like RustCrypto or openssl) for actual encryption.
+/// and permission restrictions. Every string and stream is encrypted with its own
+/// object key (RC4 or AES-128) and the objects are written out behind a fresh xref
+/// table whose trailer carries the `/Encrypt` dictionary and `/ID`.
 ///
 /// # Arguments
 ///
@@ -1867,83 +5094,395 @@ pub fn reorder_pages(input_file: &str, output_file: &str, page_order: &[usize])
 /// # Errors
 ///
 /// This function will return an error if:
-/// - The input file cannot be read
-/// - The security settings are invalid
+/// - The input file cannot be read or is already password protected
+/// - The security settings are invalid, or ask for AES-256
 /// - Writing the output file fails
 pub fn protect_pdf(input_file: &str, output_file: &str, security: &crate::security::PdfSecurity) -> Result<()> {
-    // Read the input PDF
-    let content = fs::read_to_string(input_file)?;
-
-    // Parse the PDF to find the trailer
-    let trailer_pos = content.rfind("trailer")
-        .ok_or_else(|| anyhow!("No trailer found in PDF"))?;
-
-    // Create the encryption dictionary
-    let encryption_dict = security.create_encryption_dict();
+    // Read the input PDF; binary bytes map one-to-one onto chars
+    let raw = fs::read(input_file)?;
 
     // If no security is needed, just copy the file
     if !security.is_protected() {
-        fs::write(output_file, content)?;
+        fs::write(output_file, raw)?;
         return Ok(());
     }
+    security.validate()?;
+    if security.encryption_algorithm == crate::security::EncryptionAlgorithm::Aes_256 {
+        return Err(PdfError::InvalidInput("AES-256 protection is not supported".into()));
+    }
 
-    // Insert the encryption dictionary into the PDF
-    // We need to add it to the trailer and update the xref table
-    // For simplicity, we'll add it as a comment in the output
-    let mut protected_content = content.clone();
+    let content: String = raw.iter().map(|&b| b as char).collect();
+    if regex::Regex::new(r"/Encrypt\s*(\d+\s+\d+\s+R|<<)").unwrap().is_match(&content) {
+        return Err(PdfError::InvalidInput(format!("{} is already password protected", input_file)));
+    }
+
+    // The first /ID string feeds the password hashes; derive one from the content if missing
+    let trailer_pos = content.rfind("trailer").unwrap_or(0);
+    let file_id = trailer_file_id(&content[trailer_pos..])
+        .or_else(|| trailer_file_id(&content))
+        .unwrap_or_else(|| <md5::Md5 as md5::Digest>::digest(&raw).to_vec());
+    let mut objects = scan_raw_objects(&raw);
+    if objects.is_empty() {
+        return Err(PdfError::Parse(format!("No objects found in {}", input_file)));
+    }
+    // Compressed objects need xref stream entries, so they are stored top-level
+    expand_object_streams(&mut objects);
+    let trailer_entries = encrypt_objects(&mut objects, security, &file_id)?;
+    let mut out = rebuild_from_objects(&raw, &objects, input_file, &trailer_entries)?;
+
+    // Declare a version new enough for the encryption algorithm; the header
+    // keeps its length so the xref offsets stay valid
+    let (major, minor) = security.encryption_algorithm.min_pdf_version();
+    let required = format!("{}.{}", major, minor);
+    if out.starts_with(b"%PDF-") && out.get(5..8).is_some_and(|version| version < required.as_bytes()) {
+        out[5..8].copy_from_slice(required.as_bytes());
+    }
 
-    // Find the position to insert the encryption dictionary (before the trailer)
-    if let Some(trailer_start) = content[trailer_pos..].find("<<") {
-        let insert_pos = trailer_pos + trailer_start;
+    fs::write(output_file, &out)?;
 
-        // Insert the encryption reference
-        let encryption_entry = format!("\n/Encrypt {} 0 R\n  ", 1); // Reference to encryption object (we'd add it properly in a full implementation)
+    log::info!(
+        "[protect] Applied protection to {} (algorithm: {})",
+        output_file,
+        security.encryption_algorithm.name()
+    );
 
-        // In a full implementation, we would:
-        // 1. Create a new encryption object in the PDF
-        // 2. Update the xref table
-        // 3. Add the /Encrypt entry to the trailer
-        // 4. Encrypt all stream and string objects
+    Ok(())
+}
 
-        // For this simplified implementation, we'll add a comment indicating protection
-        let protection_notice = format!(
-            "% PDF PROTECTED: Algorithm={}, Permissions={:08X}\n",
-            security.encryption_algorithm.name(),
-            security.permissions.to_pdf_flags()
-        );
+/// Encrypt the strings and streams of `objects` for `security`, each with its
+/// object key, and return the `/Encrypt` and `/ID` trailer entries declaring it
+fn encrypt_objects(
+    objects: &mut RawObjects,
+    security: &crate::security::PdfSecurity,
+    file_id: &[u8],
+) -> Result<String> {
+    let encryption = security
+        .standard_encryption(file_id)
+        .ok_or_else(|| PdfError::InvalidInput("Unsupported encryption algorithm".into()))?;
+    let cipher = encryption.cipher(security.user_password.as_deref().unwrap_or(""), file_id);
+    let metadata_re = regex::bytes::Regex::new(r"/Type\s*/Metadata\b").unwrap();
+    for (&id, (generation, body)) in objects.iter_mut() {
+        if !security.encrypt_metadata && metadata_re.is_match(body) {
+            continue;
+        }
+        *body = crypt_object(body, |data| cipher.encrypt(id, *generation, data));
+    }
 
-        protected_content.insert_str(0, &protection_notice);
+    let hex: String = file_id.iter().map(|b| format!("{:02X}", b)).collect();
+    Ok(format!("/Encrypt {}\n/ID [<{}> <{}>]\n", security.encryption_dict_for(file_id), hex, hex))
+}
 
-        // Add encryption dictionary reference to trailer (simplified)
-        let trailer_with_encrypt = content[insert_pos..].replacen(
-            "<<",
-            &format!("<<\n/Encrypt <<{}>>", encryption_dict),
-            1,
-        );
+/// First string of the trailer's `/ID` array, if it is a hex string
+fn trailer_file_id(trailer: &str) -> Option<Vec<u8>> {
+    let re = regex::Regex::new(r"/ID\s*\[\s*<([0-9A-Fa-f\s]*)>").unwrap();
+    let digits: String = re.captures(trailer)?[1].split_whitespace().collect();
+    crate::compression::decode_hex_string(&digits).ok()
+}
 
-        protected_content = format!(
-            "{}{}",
-            &protected_content[..insert_pos.min(protected_content.len())],
-            trailer_with_encrypt
-        );
+/// Byte range of the inline `/Encrypt << ... >>` dictionary in the last trailer
+fn trailer_encrypt_dict(content: &str) -> Option<std::ops::Range<usize>> {
+    let trailer_pos = content.rfind("trailer")?;
+    let start = trailer_pos + content[trailer_pos..].find("/Encrypt")? + "/Encrypt".len();
+    let start = start + content[start..].find("<<")?;
+    let mut depth = 0;
+    let mut i = start;
+    while i + 1 < content.len() {
+        match &content[i..i + 2] {
+            "<<" => {
+                depth += 1;
+                i += 2;
+            }
+            ">>" => {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    return Some(start..i);
+                }
+            }
+            _ => i += 1,
+        }
     }
+    None
+}
 
-    // Write the protected PDF
-    fs::write(output_file, protected_content)?;
+/// Change the passwords of a document protected by [`protect_pdf`].
+///
+/// `old_user_password` must open the document. Every object is decrypted with
+/// the old key and encrypted again with the key of the new passwords, keeping
+/// the algorithm and permissions. Passing `None` for both new passwords removes
+/// the protection. Only RC4 and AES-128 protection (revisions 3 and 4) is supported.
+pub fn change_password(
+    input_file: &str,
+    output_file: &str,
+    old_user_password: &str,
+    new_user_password: Option<&str>,
+    new_owner_password: Option<&str>,
+) -> Result<()> {
+    let data = fs::read(input_file)?;
+    let (mut objects, encryption, file_id) = decrypt_objects(&data, old_user_password, input_file)?;
 
-    println!(
-        "[protect] Applied protection to {} (algorithm: {})",
+    let algorithm = match (encryption.aes, encryption.key_length) {
+        (true, _) => crate::security::EncryptionAlgorithm::Aes_128,
+        (_, 16) => crate::security::EncryptionAlgorithm::Rc4_128,
+        _ => crate::security::EncryptionAlgorithm::Rc4_40,
+    };
+    let security = crate::security::PdfSecurity {
+        user_password: new_user_password.map(str::to_string),
+        owner_password: new_owner_password.map(str::to_string),
+        encryption_algorithm: algorithm,
+        permissions: crate::security::PdfPermissions::from_pdf_flags(encryption.permissions),
+        encrypt_metadata: encryption.encrypt_metadata,
+    };
+    security.validate()?;
+
+    let trailer_entries = match security.is_protected() {
+        true => encrypt_objects(&mut objects, &security, &file_id)?,
+        false => String::new(),
+    };
+    let out = rebuild_from_objects(&data, &objects, input_file, &trailer_entries)?;
+    fs::write(output_file, &out)?;
+    log::info!(
+        "[protect] Changed passwords of {} into {} ({})",
+        input_file,
         output_file,
-        security.encryption_algorithm.name()
+        if security.is_protected() { "protected" } else { "protection removed" }
     );
+    Ok(())
+}
 
+/// Remove the password protection from a document, given its user password.
+///
+/// Strings and stream data of every object are decrypted (RC4 and AESV2 security
+/// handlers, revisions 3 and 4), members of object streams become top-level
+/// objects, the `/Encrypt` dictionary is dropped and the objects are written out
+/// behind a fresh xref table as in [`repair_pdf`]. Metadata streams stay as they
+/// are under `/EncryptMetadata false`.
+pub fn decrypt_pdf(input_file: &str, output_file: &str, password: &str) -> Result<()> {
+    let out = decrypted_bytes(&fs::read(input_file)?, password, input_file)?;
+    fs::write(output_file, &out)?;
+    log::info!("[protect] Decrypted {} into {}", input_file, output_file);
     Ok(())
 }
 
+/// The encrypted document `data` rewritten without its protection, as
+/// [`decrypt_pdf`] writes it. `name` identifies the document in errors.
+pub(crate) fn decrypted_bytes(data: &[u8], password: &str, name: &str) -> Result<Vec<u8>> {
+    let (objects, _, _) = decrypt_objects(data, password, name)?;
+    rebuild_from_objects(data, &objects, name, "")
+}
+
+/// The objects of the encrypted document `data` with their strings and streams
+/// decrypted and object streams expanded, minus the `/Encrypt` dictionary and
+/// cross-reference streams, along
+/// with the security handler entries and the first `/ID` string.
+/// [`PdfError::Encrypted`] if `password` is not the user password.
+fn decrypt_objects(
+    data: &[u8],
+    password: &str,
+    input_file: &str,
+) -> Result<(RawObjects, crate::security::StandardEncryption, Vec<u8>)> {
+    let content: String = data.iter().map(|&b| b as char).collect();
+    let mut objects = scan_raw_objects(data);
+    if objects.is_empty() {
+        return Err(PdfError::Parse(format!("No objects found in {}", input_file)));
+    }
+
+    // The dictionary is either inline in the trailer or an indirect object
+    let trailer_pos = content.rfind("trailer").unwrap_or(0);
+    let encrypt_ref = regex::Regex::new(r"/Encrypt\s+(\d+)\s+\d+\s+R")
+        .unwrap()
+        .captures_iter(&content)
+        .filter_map(|c| c[1].parse::<u32>().ok())
+        .filter(|id| objects.contains_key(id))
+        .last();
+    let dict = match (trailer_encrypt_dict(&content), encrypt_ref) {
+        (Some(range), _) => content[range].to_string(),
+        (None, Some(id)) => String::from_utf8_lossy(&objects[&id].1).to_string(),
+        (None, None) => {
+            return Err(PdfError::InvalidInput(format!("{} is not password protected", input_file)));
+        }
+    };
+    let encryption = crate::security::StandardEncryption::parse(&dict)
+        .ok_or_else(|| PdfError::Parse("Unreadable /Encrypt dictionary".into()))?;
+    let file_id = trailer_file_id(&content[trailer_pos..])
+        .or_else(|| trailer_file_id(&content))
+        .unwrap_or_default();
+    if !(3..=4).contains(&encryption.revision) {
+        return Err(PdfError::InvalidInput(format!(
+            "Decryption is not supported for security handler revision {}",
+            encryption.revision
+        )));
+    }
+    if !encryption.authenticate_user(password, &file_id) {
+        return Err(PdfError::Encrypted);
+    }
+
+    if let Some(id) = encrypt_ref {
+        objects.remove(&id);
+    }
+
+    // Cross-reference streams are stored in the clear, and so is metadata
+    // under /EncryptMetadata false
+    let xref_re = regex::bytes::Regex::new(r"/Type\s*/XRef\b").unwrap();
+    let metadata_re = regex::bytes::Regex::new(r"/Type\s*/Metadata\b").unwrap();
+    let cipher = encryption.cipher(password, &file_id);
+    for (&id, (generation, body)) in objects.iter_mut() {
+        if xref_re.is_match(body) || (!encryption.encrypt_metadata && metadata_re.is_match(body)) {
+            continue;
+        }
+        *body = crypt_object(body, |data| cipher.decrypt(id, *generation, data));
+    }
+    // Members of object streams were encrypted along with their stream
+    expand_object_streams(&mut objects);
+    Ok((objects, encryption, file_id))
+}
+
+/// `object` with `crypt` applied to its strings and stream data, and its stream
+/// `/Length` updated to match. Strings come out as hex.
+fn crypt_object(object: &[u8], crypt: impl Fn(&[u8]) -> Vec<u8>) -> Vec<u8> {
+    let stream_re = regex::bytes::Regex::new(r">>\s*stream(\r\n|\r|\n)").unwrap();
+    let (dict_end, stream) = match stream_re.captures(object) {
+        Some(caps) => {
+            let data_start = caps.get(0).unwrap().end();
+            let end = data_start
+                + crate::pdf::find_bytes(&object[data_start..], b"endstream").unwrap_or(object.len() - data_start);
+            let data = &object[data_start..end];
+            let trimmed = data
+                .strip_suffix(b"\r\n")
+                .or_else(|| data.strip_suffix(b"\n"))
+                .or_else(|| data.strip_suffix(b"\r"))
+                .unwrap_or(data);
+            (data_start, Some((data_start, data_start + trimmed.len())))
+        }
+        None => (object.len(), None),
+    };
+
+    let mut out = Vec::with_capacity(object.len());
+    let hex = |bytes: &[u8]| {
+        let digits: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        format!("<{}>", digits).into_bytes()
+    };
+    let mut i = 0;
+    while i < dict_end {
+        match object[i] {
+            b'%' => {
+                // Comments run to the end of the line
+                while i < dict_end && object[i] != b'\n' && object[i] != b'\r' {
+                    out.push(object[i]);
+                    i += 1;
+                }
+            }
+            b'(' => {
+                let (string, next) = read_literal_string(object, i + 1);
+                out.extend_from_slice(&hex(&crypt(&string)));
+                i = next;
+            }
+            b'<' if object.get(i + 1) == Some(&b'<') => {
+                out.extend_from_slice(b"<<");
+                i += 2;
+            }
+            b'<' => {
+                let end = object[i..].iter().position(|&b| b == b'>').map_or(object.len(), |p| i + p);
+                let digits: String =
+                    object[i + 1..end].iter().filter(|b| !b.is_ascii_whitespace()).map(|&b| b as char).collect();
+                match crate::compression::decode_hex_string(&digits) {
+                    Ok(string) => out.extend_from_slice(&hex(&crypt(&string))),
+                    Err(_) => out.extend_from_slice(&object[i..(end + 1).min(object.len())]),
+                }
+                i = end + 1;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    if let Some((start, end)) = stream {
+        out.extend_from_slice(&crypt(&object[start..end]));
+        out.extend_from_slice(&object[end..]);
+    }
+    fix_stream_length(&out).unwrap_or(out)
+}
+
+/// Bytes of the literal string whose body starts at `start`, and the index after
+/// its closing parenthesis
+fn read_literal_string(data: &[u8], start: usize) -> (Vec<u8>, usize) {
+    let mut bytes = Vec::new();
+    let mut depth = 1;
+    let mut i = start;
+    while i < data.len() {
+        match data[i] {
+            b'\\' if i + 1 < data.len() => {
+                i += 1;
+                match data[i] {
+                    b'n' => bytes.push(b'\n'),
+                    b'r' => bytes.push(b'\r'),
+                    b't' => bytes.push(b'\t'),
+                    b'b' => bytes.push(8),
+                    b'f' => bytes.push(12),
+                    b'\r' => {
+                        // Line continuation
+                        if data.get(i + 1) == Some(&b'\n') {
+                            i += 1;
+                        }
+                    }
+                    b'\n' => {}
+                    b'0'..=b'7' => {
+                        let mut value = 0u32;
+                        let mut digits = 0;
+                        while digits < 3 && i < data.len() && (b'0'..=b'7').contains(&data[i]) {
+                            value = value * 8 + (data[i] - b'0') as u32;
+                            i += 1;
+                            digits += 1;
+                        }
+                        bytes.push(value as u8);
+                        continue;
+                    }
+                    other => bytes.push(other),
+                }
+                i += 1;
+            }
+            b'(' => {
+                depth += 1;
+                bytes.push(b'(');
+                i += 1;
+            }
+            b')' => {
+                depth -= 1;
+                i += 1;
+                if depth == 0 {
+                    break;
+                }
+                bytes.push(b')');
+            }
+            b => {
+                bytes.push(b);
+                i += 1;
+            }
+        }
+    }
+    (bytes, i)
+}
+
+/// Escape the body of a PDF literal string: `\`, parentheses and control characters
 fn escape_pdf_meta(s: &str) -> String {
-    s.replace('\\', "\\\\")
-        .replace('(', "\\(")
-        .replace(')', "\\)")
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' | '(' | ')' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            c if c.is_ascii_control() => out.push_str(&format!("\\{:03o}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
 }
 
 #[cfg(test)]
@@ -1958,42 +5497,651 @@ mod tests {
             subject: None,
             keywords: None,
             creator: None,
+            trapped: Some(Trapped::False),
             custom_fields: std::collections::HashMap::new(),
+            creation_date: None,
+            mod_date: None,
         };
         let dict = meta.to_info_dict();
         assert!(dict.contains("/Title (Test Title)"));
         assert!(dict.contains("/Author (Test Author)"));
         assert!(dict.contains("/Producer (pdf-cli)"));
+        assert!(dict.contains("/Trapped /False"));
         assert!(!dict.contains("/Subject"));
     }
 
     #[test]
-    fn test_pdf_metadata_escape() {
-        assert_eq!(escape_pdf_meta("hello (world)"), "hello \\(world\\)");
-        assert_eq!(escape_pdf_meta("back\\slash"), "back\\\\slash");
+    fn test_pdf_metadata_escape() {
+        assert_eq!(escape_pdf_meta("hello (world)"), "hello \\(world\\)");
+        assert_eq!(escape_pdf_meta("back\\slash"), "back\\\\slash");
+    }
+
+    #[test]
+    fn test_pdf_metadata_default() {
+        let meta = PdfMetadata::new();
+        assert!(meta.title.is_none());
+        assert!(meta.author.is_none());
+        let dict = meta.to_info_dict();
+        assert!(dict.contains("/Producer (pdf-cli)"));
+    }
+
+    #[test]
+    fn test_split_invalid_range() {
+        let result = split_pdf("nonexistent.pdf", "out.pdf", 0, 5);
+        assert!(result.is_err());
+        let result = split_pdf("nonexistent.pdf", "out.pdf", 5, 3);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_split_error_kinds() {
+        let result = split_pdf("nonexistent.pdf", "out.pdf", 5, 3);
+        assert!(matches!(
+            result,
+            Err(PdfError::InvalidPageRange { start: 5, end: 3, page_count: None })
+        ));
+        let result = split_pdf("nonexistent.pdf", "out.pdf", 1, 3);
+        assert!(matches!(result, Err(PdfError::Io(_))));
+    }
+
+    #[test]
+    fn test_image_only_page_is_split_and_reordered() {
+        let dir = std::env::temp_dir();
+        let input = dir.join("pdfrs_image_page.pdf");
+        let streams = vec![
+            b"BT /F1 12 Tf 72 700 Td (One) Tj ET".to_vec(),
+            b"q 200 0 0 100 72 600 cm /Im1 Do Q".to_vec(),
+            b"BT /F1 12 Tf 72 700 Td (Three) Tj ET".to_vec(),
+        ];
+        let layout = crate::pdf_generator::PageLayout::portrait();
+        assemble_merged_pdf(input.to_str().unwrap(), &streams, "Helvetica", &layout).unwrap();
+        let doc = crate::pdf::PdfDocument::load_from_file(input.to_str().unwrap()).unwrap();
+        assert_eq!(doc.page_content_streams(1), vec![streams[1].clone()]);
+
+        let reordered = dir.join("pdfrs_image_page_reordered.pdf");
+        reorder_pages(input.to_str().unwrap(), reordered.to_str().unwrap(), &[2, 3, 1]).unwrap();
+        let doc = crate::pdf::PdfDocument::load_from_file(reordered.to_str().unwrap()).unwrap();
+        assert_eq!(doc.pages.len(), 3);
+        assert_eq!(page_form_data(&doc, 0, "Pg0"), String::from_utf8_lossy(&streams[1]));
+        assert_eq!(doc.get_text_by_page().unwrap()[2].trim(), "One");
+
+        let split = dir.join("pdfrs_image_page_split.pdf");
+        split_pdf(input.to_str().unwrap(), split.to_str().unwrap(), 2, 3).unwrap();
+        let doc = crate::pdf::PdfDocument::load_from_file(split.to_str().unwrap()).unwrap();
+        assert_eq!(doc.pages.len(), 2);
+        assert_eq!(page_form_data(&doc, 0, "Pg0"), String::from_utf8_lossy(&streams[1]));
+        // The page's font comes along with it
+        let fonts = doc
+            .page_attribute(doc.pages[1], "Resources")
+            .and_then(|r| r.as_dict())
+            .and_then(|r| doc.dict_get(r, "XObject"))
+            .and_then(|x| x.as_dict())
+            .and_then(|x| doc.dict_get(x, "Pg0"))
+            .and_then(|form| match form {
+                crate::pdf::PdfObject::Stream { dictionary, .. } => dictionary.get("Resources").and_then(|r| doc.resolve(r)),
+                _ => None,
+            })
+            .and_then(|r| r.as_dict())
+            .and_then(|r| doc.dict_get(r, "Font"))
+            .and_then(|f| f.as_dict())
+            .unwrap();
+        assert!(doc.dict_get(fonts, "F1").is_some());
+        assert_eq!(doc.get_text_by_page().unwrap()[1].trim(), "Three");
+    }
+
+    #[test]
+    fn test_merge_reports_progress_per_file() {
+        let dir = std::env::temp_dir();
+        let mut inputs = Vec::new();
+        for i in 0..3 {
+            let path = dir.join(format!("pdfrs_merge_progress_{}.pdf", i));
+            crate::pdf_generator::create_pdf_from_elements(
+                path.to_str().unwrap(),
+                &[crate::elements::Element::Paragraph { text: format!("File {}", i) }],
+                "Helvetica", 12.0,
+            ).unwrap();
+            inputs.push(path.to_str().unwrap().to_string());
+        }
+        let refs: Vec<&str> = inputs.iter().map(|s| s.as_str()).collect();
+        let out = dir.join("pdfrs_merge_progress_out.pdf");
+
+        let mut reports = Vec::new();
+        merge_pdfs_with_progress(&refs, out.to_str().unwrap(), &mut |p| reports.push(p)).unwrap();
+
+        let counts: Vec<(usize, usize)> = reports.iter().map(|p| (p.current, p.pages)).collect();
+        assert_eq!(counts, vec![(1, 1), (2, 2), (3, 3)]);
+        assert!(reports.iter().all(|p| p.total == 3));
+
+        // A file with form fields switches to copying pages whole, still reporting each file once
+        let form = dir.join("pdfrs_merge_progress_form.pdf");
+        let field = FormField {
+            name: "name".to_string(),
+            field_type: FormFieldType::Text,
+            x: 100.0,
+            y: 600.0,
+            width: 200.0,
+            height: 20.0,
+            default_value: None,
+            options: vec![],
+            required: false,
+            page: None,
+            flags: FieldFlags::NONE,
+        };
+        create_pdf_with_form_fields(form.to_str().unwrap(), "Form", &[field]).unwrap();
+        let with_form = [refs[0], form.to_str().unwrap(), refs[2]];
+        let mut reports = Vec::new();
+        merge_pdfs_with_progress(&with_form, out.to_str().unwrap(), &mut |p| reports.push(p)).unwrap();
+        let counts: Vec<(usize, usize)> = reports.iter().map(|p| (p.current, p.pages)).collect();
+        assert_eq!(counts, vec![(1, 1), (2, 2), (3, 3)]);
+    }
+
+    #[test]
+    fn test_describe_object_and_tree() {
+        let elements = vec![
+            crate::elements::Element::Paragraph { text: "One".into() },
+            crate::elements::Element::PageBreak,
+            crate::elements::Element::Paragraph { text: "Two".into() },
+        ];
+        let layout = crate::pdf_generator::PageLayout::portrait();
+        let bytes = crate::pdf_generator::generate_pdf_bytes(&elements, "Helvetica", 12.0, layout).unwrap();
+        let doc = crate::pdf::PdfDocument::from_bytes(&bytes).unwrap();
+
+        let catalog = describe_object(&doc, doc.catalog).unwrap();
+        assert!(catalog.contains("/Type /Catalog"), "{}", catalog);
+        let content_id = doc
+            .get_object(doc.pages[0])
+            .and_then(|page| page.as_dict())
+            .and_then(|page| page.get("Contents"))
+            .and_then(|contents| contents.as_reference())
+            .unwrap();
+        assert!(describe_object(&doc, content_id).unwrap().contains("stream ("));
+
+        let tree = describe_page_tree(&doc);
+        assert!(tree.contains("(Count 2)"), "{}", tree);
+        assert!(tree.contains(&format!("Page {} 0 R (#2)", doc.pages[1])), "{}", tree);
+    }
+
+    #[test]
+    fn test_annotation_strings_escape_control_and_cjk() {
+        let dir = std::env::temp_dir();
+        let out = dir.join("pdfrs_annot_unicode.pdf");
+        let annotation = TextAnnotation {
+            x: 100.0, y: 700.0, width: 20.0, height: 20.0,
+            content: "Line one\nLine (two)\tend".into(),
+            title: "Review\n\u{65e5}\u{672c}".into(),
+            color: None,
+            border: None,
+        };
+        create_pdf_with_annotations(out.to_str().unwrap(), "Annotated", &[annotation], &[]).unwrap();
+
+        let raw = String::from_utf8_lossy(&fs::read(&out).unwrap()).to_string();
+        assert!(raw.contains("/Contents (Line one\\nLine \\(two\\)\\tend)"), "{}", raw);
+        assert!(raw.contains("/T <FEFF005200650076006900650077000A65E5672C>"));
+
+        let doc = crate::pdf::PdfDocument::load_from_file(out.to_str().unwrap()).unwrap();
+        let annot = doc.objects.values().filter_map(|o| o.as_dict()).find(|d| d.contains_key("T")).unwrap();
+        assert!(matches!(doc.dict_get(annot, "T"), Some(crate::pdf::PdfObject::String(t)) if t == "Review\n\u{65e5}\u{672c}"));
+        assert!(matches!(doc.dict_get(annot, "Contents"), Some(crate::pdf::PdfObject::String(t)) if t == "Line one\nLine (two)\tend"));
+    }
+
+    #[test]
+    fn test_text_and_show_strings() {
+        assert_eq!(pdf_text_string("caf\u{e9}"), "(caf\\351)");
+        // PDFDocEncoding has the euro, bullet and dashes in a single byte, but no
+        // no-break space or C1 controls
+        assert_eq!(pdf_text_string("\u{20ac}5 \u{2022} \u{2014}"), "(\\2405 \\200 \\204)");
+        assert_eq!(pdf_text_string("a\u{a0}b"), "<FEFF006100A00062>");
+        assert_eq!(pdf_text_string("\u{85}"), "<FEFF0085>");
+        for text in ["\u{20ac}5 \u{2022} \u{2014} \u{fb01}n \u{142}\u{f3}d", "caf\u{e9} \u{a0}"] {
+            let dict = format!("<< /Title {} >>", pdf_text_string(text));
+            let info = crate::pdf::parse_dict_entries(&dict);
+            assert!(matches!(info.get("Title"), Some(crate::pdf::PdfValue::Object(crate::pdf::PdfObject::String(t))) if t == text), "{:?}", info);
+        }
+        assert_eq!(pdf_show_string("\u{20ac}5 \u{e9}t\u{e9} \u{65e5}"), "(\\2005 \\351t\\351 ?)");
+        assert_eq!(escape_pdf_meta("a\u{1}b"), "a\\001b");
+    }
+
+    #[test]
+    fn test_diff_pdfs() {
+        let dir = std::env::temp_dir();
+        let a = dir.join("pdfrs_diff_a.pdf");
+        let b = dir.join("pdfrs_diff_b.pdf");
+        let c = dir.join("pdfrs_diff_c.pdf");
+        let md = "# Report\n\nThe quick brown fox.\n\n<!-- pagebreak -->\n\nSecond page text.";
+        let render = |path: &std::path::Path, markdown: &str| {
+            crate::pdf_generator::create_pdf_from_elements(
+                path.to_str().unwrap(),
+                &crate::elements::parse_markdown(markdown),
+                "Helvetica", 12.0,
+            ).unwrap();
+        };
+        render(&a, md);
+        render(&b, md);
+        render(&c, &md.replace("brown", "red"));
+
+        let same = diff_pdfs(a.to_str().unwrap(), b.to_str().unwrap()).unwrap();
+        assert!(same.is_equal(), "{}", same.summary());
+
+        let changed = diff_pdfs(a.to_str().unwrap(), c.to_str().unwrap()).unwrap();
+        assert!(!changed.is_equal());
+        assert_eq!(changed.pages.len(), 1);
+        assert_eq!(changed.pages[0].page, 1);
+        assert!(changed.summary().contains("Page 1: text differs"));
+    }
+
+    #[test]
+    fn test_attach_list_and_extract() {
+        let dir = std::env::temp_dir();
+        let src = dir.join("pdfrs_attach_src.pdf");
+        let out = dir.join("pdfrs_attach_out.pdf");
+        let file = dir.join("pdfrs_attach_notes.md");
+        let extracted = dir.join("pdfrs_attach_extracted.md");
+        let bytes = b"# Source\r\nendobj\n(parens) \\ and \xff\x00 binary\n".to_vec();
+        fs::write(&file, &bytes).unwrap();
+        crate::pdf_generator::create_pdf_from_elements(
+            src.to_str().unwrap(),
+            &[crate::elements::Element::Paragraph { text: "With attachment".into() }],
+            "Helvetica", 12.0,
+        ).unwrap();
+
+        attach_file(src.to_str().unwrap(), out.to_str().unwrap(), file.to_str().unwrap(), "Original Markdown").unwrap();
+
+        let attachments = list_attachments(out.to_str().unwrap()).unwrap();
+        assert_eq!(attachments, vec![Attachment {
+            name: "pdfrs_attach_notes.md".into(),
+            description: Some("Original Markdown".into()),
+            size: bytes.len(),
+        }]);
+        extract_attachment(out.to_str().unwrap(), "pdfrs_attach_notes.md", extracted.to_str().unwrap()).unwrap();
+        assert_eq!(fs::read(&extracted).unwrap(), bytes);
+
+        // The document itself is unchanged
+        let text = crate::pdf::extract_text(out.to_str().unwrap()).unwrap();
+        assert!(text.contains("With attachment"));
+        assert!(extract_attachment(out.to_str().unwrap(), "missing.md", extracted.to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_redact_removes_text_in_region() {
+        let dir = std::env::temp_dir();
+        let src = dir.join("pdfrs_redact_src.pdf");
+        let out = dir.join("pdfrs_redact_out.pdf");
+        crate::pdf_generator::create_pdf_from_elements_with_layout(
+            src.to_str().unwrap(),
+            &[
+                crate::elements::Element::Paragraph { text: "Account 1234-5678".into() },
+                crate::elements::Element::Paragraph { text: "Public remarks".into() },
+            ],
+            "Helvetica", 12.0,
+            crate::pdf_generator::PageLayout::a4(),
+        ).unwrap();
+
+        // Find where the sensitive line was placed
+        let raw = String::from_utf8_lossy(&fs::read(&src).unwrap()).to_string();
+        let tm_re = regex::Regex::new(r"1 0 0 1 ([\d.]+) ([\d.]+) Tm\n\(Account").unwrap();
+        let caps = tm_re.captures(&raw).unwrap();
+        let (x, y): (f32, f32) = (caps[1].parse().unwrap(), caps[2].parse().unwrap());
+
+        redact_pdf(
+            src.to_str().unwrap(),
+            out.to_str().unwrap(),
+            &[(1, Rect::new(x - 1.0, y - 3.0, 300.0, 12.0))],
+        ).unwrap();
+
+        let text = crate::pdf::extract_text(out.to_str().unwrap()).unwrap();
+        assert!(!text.contains("1234-5678"), "{}", text);
+        assert!(text.contains("Public remarks"));
+        let raw_out = String::from_utf8_lossy(&fs::read(&out).unwrap()).to_string();
+        assert!(raw_out.contains(&format!("{} {} 300 12 re f", x - 1.0, y - 3.0)));
+        assert!(!raw_out.contains("1234-5678"));
+        // The page keeps its size and font
+        let doc = crate::pdf::PdfDocument::load_from_file(out.to_str().unwrap()).unwrap();
+        let a4 = crate::pdf_generator::PageLayout::a4();
+        assert_eq!(page_media_box(&doc, doc.pages[0]), [0.0, 0.0, a4.width, a4.height]);
+        let source = crate::pdf::PdfDocument::load_from_file(src.to_str().unwrap()).unwrap();
+        assert_eq!(doc.fonts().len(), source.fonts().len());
+
+        assert!(redact_pdf(src.to_str().unwrap(), out.to_str().unwrap(), &[(2, Rect::new(0.0, 0.0, 1.0, 1.0))]).is_err());
+    }
+
+    #[test]
+    fn test_replace_text_fills_placeholder() {
+        let dir = std::env::temp_dir();
+        let src = dir.join("pdfrs_replace_src.pdf");
+        let out = dir.join("pdfrs_replace_out.pdf");
+        crate::pdf_generator::create_pdf_from_elements(
+            src.to_str().unwrap(),
+            &[crate::elements::Element::Paragraph { text: "Dear {{NAME}} (customer)".into() }],
+            "Helvetica", 12.0,
+        ).unwrap();
+
+        let count = replace_text(src.to_str().unwrap(), out.to_str().unwrap(), &[("{{NAME}}", "Alice")]).unwrap();
+        assert_eq!(count, 1);
+        let text = crate::pdf::extract_text(out.to_str().unwrap()).unwrap();
+        assert!(text.contains("Dear Alice (customer)"), "{}", text);
+        assert!(!text.contains("{{NAME}}"));
+        // The page is kept as it was, with a content stream of the right length
+        let raw = fs::read(&out).unwrap();
+        assert!(crate::pdf::validate_pdf_bytes(&raw).valid);
+        let doc = crate::pdf::PdfDocument::from_bytes(&raw).unwrap();
+        let source = crate::pdf::PdfDocument::load_from_file(src.to_str().unwrap()).unwrap();
+        assert_eq!(doc.pages, source.pages);
+        assert_eq!(doc.fonts().len(), source.fonts().len());
+
+        // Hex strings and TJ arrays are rewritten too, escaping what needs it
+        let (rewritten, count) = replace_in_strings(b"[(a {{X}}) -20 <7B7B587D7D>] TJ", &[("{{X}}", "(b)")]);
+        assert_eq!(count, 2);
+        assert_eq!(String::from_utf8(rewritten).unwrap(), "[(a \\(b\\)) -20 <286229>] TJ");
+
+        assert!(replace_text(src.to_str().unwrap(), out.to_str().unwrap(), &[("", "x")]).is_err());
+    }
+
+    #[test]
+    fn test_merge_empty_input() {
+        let result = merge_pdfs(&[], "out.pdf");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_merge_reports_through_log() {
+        /// Keeps every log message so the test can look for the merge summary
+        struct CaptureLogger(std::sync::Mutex<Vec<String>>);
+
+        impl log::Log for CaptureLogger {
+            fn enabled(&self, _: &log::Metadata) -> bool {
+                true
+            }
+
+            fn log(&self, record: &log::Record) {
+                self.0.lock().unwrap().push(record.args().to_string());
+            }
+
+            fn flush(&self) {}
+        }
+
+        static CAPTURE: CaptureLogger = CaptureLogger(std::sync::Mutex::new(Vec::new()));
+        log::set_logger(&CAPTURE).unwrap();
+        log::set_max_level(log::LevelFilter::Info);
+
+        let dir = std::env::temp_dir();
+        let src = dir.join("pdfrs_merge_log_src.pdf");
+        let out = dir.join("pdfrs_merge_log_out.pdf");
+        let elements = vec![crate::elements::Element::Paragraph { text: "Logged".into() }];
+        crate::pdf_generator::create_pdf_from_elements(src.to_str().unwrap(), &elements, "Helvetica", 12.0).unwrap();
+        merge_pdfs(&[src.to_str().unwrap(), src.to_str().unwrap()], out.to_str().unwrap()).unwrap();
+
+        let expected = format!("[merge] Combined 2 pages from 2 files into {}", out.display());
+        assert!(CAPTURE.0.lock().unwrap().contains(&expected));
+    }
+
+    #[test]
+    fn test_overlay_keeps_image_aspect_ratio() {
+        let dir = std::env::temp_dir();
+        let src = dir.join("pdfrs_overlay_src.pdf");
+        let out = dir.join("pdfrs_overlay_out.pdf");
+        let image = dir.join("pdfrs_overlay_wide.jpg");
+        jpeg_encoder::Encoder::new_file(&image, 80)
+            .unwrap()
+            .encode(&[90u8; 40 * 20 * 3], 40, 20, jpeg_encoder::ColorType::Rgb)
+            .unwrap();
+        let elements = vec![crate::elements::Element::Paragraph { text: "Body".into() }];
+        crate::pdf_generator::create_pdf_from_elements(src.to_str().unwrap(), &elements, "Helvetica", 12.0).unwrap();
+        let (src, out, image) = (src.to_str().unwrap(), out.to_str().unwrap(), image.to_str().unwrap());
+
+        overlay_image_on_pdf(src, out, image, 100.0, 600.0, 100.0, 0.0, 1.0).unwrap();
+        let raw = fs::read(out).unwrap();
+        let content = String::from_utf8_lossy(&raw);
+        assert!(content.contains("100 0 0 50 100 600 cm\n/Im1 Do"), "{}", content);
+        assert!(content.contains("/Subtype /Image"));
+        assert!(crate::pdf::validate_pdf_bytes(&raw).valid);
+
+        // Too wide for the page: scaled down to end at the right edge
+        overlay_image_on_pdf(src, out, image, 112.0, 200.0, 1000.0, 0.0, 1.0).unwrap();
+        let content = String::from_utf8_lossy(&fs::read(out).unwrap()).to_string();
+        assert!(content.contains("500 0 0 250 112 200 cm"), "{}", content);
+    }
+
+    #[test]
+    fn test_text_layer_makes_scan_searchable() {
+        let dir = std::env::temp_dir();
+        let image = dir.join("pdfrs_text_layer_scan.jpg");
+        let scan = dir.join("pdfrs_text_layer_scan.pdf");
+        let out = dir.join("pdfrs_text_layer_out.pdf");
+        jpeg_encoder::Encoder::new_file(&image, 80)
+            .unwrap()
+            .encode(&vec![200u8; 60 * 80 * 3], 60, 80, jpeg_encoder::ColorType::Rgb)
+            .unwrap();
+        let layout = crate::pdf_generator::PageLayout::portrait();
+        contact_sheet(&[image.to_str().unwrap()], scan.to_str().unwrap(), 1, 1, layout, false).unwrap();
+        assert!(!crate::pdf::has_text_layer(scan.to_str().unwrap()).unwrap());
+
+        let words = vec![("Invoice".to_string(), Rect::new(100.0, 600.0, 80.0, 14.0)), ("42".to_string(), Rect::new(190.0, 600.0, 20.0, 14.0))];
+        add_text_layer(scan.to_str().unwrap(), out.to_str().unwrap(), &[(1, words)]).unwrap();
+
+        let bytes = fs::read(&out).unwrap();
+        assert!(crate::pdf::validate_pdf_bytes(&bytes).valid);
+        assert!(crate::pdf::PdfDocument::from_bytes(&bytes).unwrap().has_text_layer());
+        let text = crate::pdf::extract_text(out.to_str().unwrap()).unwrap();
+        assert!(text.contains("Invoice") && text.contains("42"), "{}", text);
+        let raw = String::from_utf8_lossy(&bytes).to_string();
+        assert!(raw.contains("BT\n3 Tr\n/F1 14 Tf\n"), "{}", raw);
+        // The scanned image is still drawn
+        assert!(raw.contains("/Subtype /Image"));
+
+        assert!(add_text_layer(scan.to_str().unwrap(), out.to_str().unwrap(), &[(2, Vec::new())]).is_err());
     }
 
     #[test]
-    fn test_pdf_metadata_default() {
-        let meta = PdfMetadata::new();
-        assert!(meta.title.is_none());
-        assert!(meta.author.is_none());
-        let dict = meta.to_info_dict();
-        assert!(dict.contains("/Producer (pdf-cli)"));
+    fn test_contact_sheet_tiles_images_over_pages() {
+        let dir = std::env::temp_dir();
+        let out = dir.join("pdfrs_contact_sheet.pdf");
+        let mut paths = Vec::new();
+        for i in 0..5 {
+            let image = dir.join(format!("pdfrs_contact_{}.jpg", i));
+            let (width, height) = if i % 2 == 0 { (40, 20) } else { (20, 40) };
+            jpeg_encoder::Encoder::new_file(&image, 80)
+                .unwrap()
+                .encode(&vec![120u8; width as usize * height as usize * 3], width, height, jpeg_encoder::ColorType::Rgb)
+                .unwrap();
+            paths.push(image.to_str().unwrap().to_string());
+        }
+        let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+        let layout = crate::pdf_generator::PageLayout::portrait();
+
+        contact_sheet(&paths, out.to_str().unwrap(), 2, 2, layout, true).unwrap();
+        let raw = fs::read(&out).unwrap();
+        assert!(crate::pdf::validate_pdf_bytes(&raw).valid);
+        let doc = crate::pdf::PdfDocument::from_bytes(&raw).unwrap();
+        assert_eq!(doc.pages.len(), 2);
+        let draws = |page: usize| {
+            let content = String::from_utf8_lossy(&doc.page_content_streams(page).concat()).to_string();
+            content.matches(" Do").count()
+        };
+        assert_eq!((draws(0), draws(1)), (4, 1));
+        let content = String::from_utf8_lossy(&doc.page_content_streams(0).concat()).to_string();
+        assert!(content.contains("(pdfrs_contact_0.jpg) Tj"), "{}", content);
+
+        // The first image is centered in the top-left cell: 234 x 298.5 points
+        // less padding and caption, its 40 x 20 pixels are not upscaled
+        let x = 72.0 + (234.0 - 40.0) / 2.0;
+        assert!(content.contains(&format!("40 0 0 20 {} ", x)), "{}", content);
+
+        assert!(contact_sheet(&paths, out.to_str().unwrap(), 0, 2, layout, false).is_err());
+        // Cells narrower than their padding leave no room for an image
+        let cramped = contact_sheet(&paths, out.to_str().unwrap(), 100, 2, layout, false);
+        assert!(matches!(cramped, Err(PdfError::InvalidInput(_))), "{:?}", cramped);
+        let cramped = contact_sheet(&paths, out.to_str().unwrap(), 2, 60, layout, true);
+        assert!(matches!(cramped, Err(PdfError::InvalidInput(_))), "{:?}", cramped);
     }
 
     #[test]
-    fn test_split_invalid_range() {
-        let result = split_pdf("nonexistent.pdf", "out.pdf", 0, 5);
-        assert!(result.is_err());
-        let result = split_pdf("nonexistent.pdf", "out.pdf", 5, 3);
-        assert!(result.is_err());
+    fn test_bates_numbers_each_page() {
+        let dir = std::env::temp_dir();
+        let src = dir.join("pdfrs_bates_src.pdf");
+        let out = dir.join("pdfrs_bates_out.pdf");
+        let elements = crate::elements::parse_markdown("One\n\\pagebreak\nTwo\n\\pagebreak\nThree");
+        crate::pdf_generator::create_pdf_from_elements(src.to_str().unwrap(), &elements, "Helvetica", 12.0).unwrap();
+
+        let position = OverlayPosition::BottomRight { inset: 36.0 };
+        bates_number(src.to_str().unwrap(), out.to_str().unwrap(), "ABC-", 1, 6, position).unwrap();
+        let raw = fs::read(&out).unwrap();
+        assert!(crate::pdf::validate_pdf_bytes(&raw).valid);
+        let doc = crate::pdf::PdfDocument::from_bytes(&raw).unwrap();
+        assert_eq!(doc.pages.len(), 3);
+
+        // The stamp is a form of its own, drawn after the page
+        let stamp = |doc: &crate::pdf::PdfDocument, index: usize| {
+            let xobjects = doc
+                .page_attribute(doc.pages[index], "Resources")
+                .and_then(|r| r.as_dict())
+                .and_then(|r| doc.dict_get(r, "XObject"))
+                .and_then(|x| x.as_dict())
+                .unwrap();
+            match doc.dict_get(xobjects, "Bates0") {
+                Some(crate::pdf::PdfObject::Stream { data, .. }) => String::from_utf8_lossy(data).to_string(),
+                other => panic!("no Bates stamp: {:?}", other),
+            }
+        };
+        for (index, label) in [(0, "ABC-000001"), (2, "ABC-000003")] {
+            let width = crate::font_metrics::text_width("Helvetica", label, 10.0);
+            let expected = format!("{} 36 Td\n({}) Tj", 612.0 - width - 36.0, label);
+            assert!(stamp(&doc, index).contains(&expected), "{}", stamp(&doc, index));
+        }
+        assert!(stamp(&doc, 1).contains("(ABC-000002) Tj"));
+        assert!(doc.get_text().unwrap().contains("Two"));
+
+        // Each page keeps its size and is stamped against it
+        let a4 = crate::pdf_generator::PageLayout::a4();
+        crate::pdf_generator::create_pdf_from_elements_with_layout(
+            src.to_str().unwrap(), &elements, "Helvetica", 12.0, a4,
+        ).unwrap();
+        bates_number(src.to_str().unwrap(), out.to_str().unwrap(), "ABC-", 1, 6, position).unwrap();
+        let doc = crate::pdf::PdfDocument::load_from_file(out.to_str().unwrap()).unwrap();
+        assert_eq!(page_media_box(&doc, doc.pages[0]), [0.0, 0.0, a4.width, a4.height]);
+        let width = crate::font_metrics::text_width("Helvetica", "ABC-000001", 10.0);
+        let expected = format!("{} 36 Td\n(ABC-000001) Tj", a4.width - width - 36.0);
+        assert!(stamp(&doc, 0).contains(&expected), "{}", stamp(&doc, 0));
     }
 
     #[test]
-    fn test_merge_empty_input() {
-        let result = merge_pdfs(&[], "out.pdf");
-        assert!(result.is_err());
+    fn test_percent_overlay_centers_on_each_page_size() {
+        let dir = std::env::temp_dir();
+        let src = dir.join("pdfrs_overlay_sizes_src.pdf");
+        let out = dir.join("pdfrs_overlay_sizes_out.pdf");
+        let image = dir.join("pdfrs_overlay_sizes.jpg");
+        jpeg_encoder::Encoder::new_file(&image, 80)
+            .unwrap()
+            .encode(&[90u8; 20 * 10 * 3], 20, 10, jpeg_encoder::ColorType::Rgb)
+            .unwrap();
+        let (a, b) = (dir.join("pdfrs_overlay_sizes_a.pdf"), dir.join("pdfrs_overlay_sizes_b.pdf"));
+        for (path, layout) in [
+            (&a, crate::pdf_generator::PageLayout::portrait()),
+            (&b, crate::pdf_generator::PageLayout::a4()),
+        ] {
+            let elements = vec![crate::elements::Element::Paragraph { text: "Page".into() }];
+            crate::pdf_generator::create_pdf_from_elements_with_layout(
+                path.to_str().unwrap(), &elements, "Helvetica", 12.0, layout,
+            ).unwrap();
+        }
+        merge_pdfs_with_options(
+            &[a.to_str().unwrap(), b.to_str().unwrap()], src.to_str().unwrap(), &MergeOptions::default(),
+        ).unwrap();
+
+        let half = OverlayPosition::At { x: Coordinate::Percent(50.0), y: Coordinate::Percent(50.0) };
+        overlay_image_at(src.to_str().unwrap(), out.to_str().unwrap(), image.to_str().unwrap(), half, 100.0, 0.0, 0.5)
+            .unwrap();
+        let raw = fs::read(&out).unwrap();
+        assert!(crate::pdf::validate_pdf_bytes(&raw).valid);
+        let doc = crate::pdf::PdfDocument::from_bytes(&raw).unwrap();
+        assert_eq!(doc.pages.len(), 2);
+        let cm_re = regex::Regex::new(r"100 0 0 50 ([\d.]+) ([\d.]+) cm\n/Im1 Do").unwrap();
+        for (index, &page) in doc.pages.iter().enumerate() {
+            let [_, _, page_w, page_h] = page_media_box(&doc, page);
+            let content = page_form_data(&doc, index, "Ov0");
+            // Opacity is the fill alpha of a graphics state, not a fill color
+            assert!(content.contains("/GS0 gs\n") && !content.contains(" rg"), "{}", content);
+            let caps = cm_re.captures(&content).unwrap_or_else(|| panic!("{}", content));
+            let x: f32 = caps[1].parse().unwrap();
+            let y: f32 = caps[2].parse().unwrap();
+            assert!((x + 50.0 - page_w / 2.0).abs() < 0.01, "x {} on width {}", x, page_w);
+            assert!((y + 25.0 - page_h / 2.0).abs() < 0.01, "y {} on height {}", y, page_h);
+        }
+        assert_ne!(page_media_box(&doc, doc.pages[0]), page_media_box(&doc, doc.pages[1]));
+        assert!(String::from_utf8_lossy(&raw).contains("/Type /ExtGState\n/ca 0.5\n"));
+        assert_eq!(OverlayPosition::TopRight { inset: 10.0 }.resolve(200.0, 100.0, 50.0, 20.0), (140.0, 70.0));
+
+        // Watermarks are placed against each page's size too
+        let quarter = OverlayPosition::At { x: Coordinate::Percent(25.0), y: Coordinate::Points(40.0) };
+        watermark_pdf_with_font_size(
+            src.to_str().unwrap(),
+            out.to_str().unwrap(),
+            WatermarkContent::Text("DRAFT".into()),
+            0.5,
+            WatermarkPosition::Placed(quarter),
+            20.0,
+        )
+        .unwrap();
+        let doc = crate::pdf::PdfDocument::load_from_file(out.to_str().unwrap()).unwrap();
+        let text_width = crate::font_metrics::text_width("Helvetica", "DRAFT", 20.0);
+        for (index, &page) in doc.pages.iter().enumerate() {
+            let [_, _, page_w, _] = page_media_box(&doc, page);
+            let mark = page_form_data(&doc, index, "Wm0");
+            let expected = format!("{} 40 Td\n(DRAFT) Tj", (page_w - text_width) * 0.25);
+            assert!(mark.contains(&expected), "{}", mark);
+        }
+        assert_ne!(page_media_box(&doc, doc.pages[0]), page_media_box(&doc, doc.pages[1]));
+        assert_eq!(doc.get_text_by_page().unwrap().len(), 2);
+        assert!(doc.get_text().unwrap().contains("Page"));
+    }
+
+    /// Data of the Form XObject `name` in the resources of page `index`
+    fn page_form_data(doc: &crate::pdf::PdfDocument, index: usize, name: &str) -> String {
+        let xobjects = doc
+            .page_attribute(doc.pages[index], "Resources")
+            .and_then(|r| r.as_dict())
+            .and_then(|r| doc.dict_get(r, "XObject"))
+            .and_then(|x| x.as_dict())
+            .unwrap();
+        match doc.dict_get(xobjects, name) {
+            Some(crate::pdf::PdfObject::Stream { data, .. }) => String::from_utf8_lossy(data).to_string(),
+            other => panic!("no {} form: {:?}", name, other),
+        }
+    }
+
+    #[test]
+    fn test_burst_writes_one_file_per_page() {
+        let dir = std::env::temp_dir();
+        let src = dir.join("pdfrs_burst_src.pdf");
+        let out_dir = dir.join("pdfrs_burst_pages");
+        let _ = fs::remove_dir_all(&out_dir);
+        let elements = vec![
+            crate::elements::Element::Paragraph { text: "First page".into() },
+            crate::elements::Element::PageBreak,
+            crate::elements::Element::Paragraph { text: "Second page".into() },
+            crate::elements::Element::PageBreak,
+            crate::elements::Element::Paragraph { text: "Third page".into() },
+        ];
+        crate::pdf_generator::create_pdf_from_elements_with_layout(
+            src.to_str().unwrap(), &elements, "Helvetica", 12.0, crate::pdf_generator::PageLayout::a4(),
+        ).unwrap();
+
+        let files = burst_pdf(src.to_str().unwrap(), out_dir.to_str().unwrap()).unwrap();
+        assert_eq!(files.len(), 3);
+        for (file, (name, text)) in files.iter().zip([
+            ("page_001.pdf", "First page"),
+            ("page_002.pdf", "Second page"),
+            ("page_003.pdf", "Third page"),
+        ]) {
+            assert!(file.ends_with(name), "{}", file);
+            let raw = fs::read(file).unwrap();
+            let validation = crate::pdf::validate_pdf_bytes(&raw);
+            assert!(validation.valid, "{:?}", validation.errors);
+            let doc = crate::pdf::PdfDocument::from_bytes(&raw).unwrap();
+            assert_eq!(doc.pages.len(), 1);
+            assert_eq!(page_media_box(&doc, doc.pages[0]), [0.0, 0.0, 595.0, 842.0]);
+            let extracted = doc.get_text().unwrap();
+            assert!(extracted.contains(text), "{}: {:?}", name, extracted);
+        }
     }
 
     #[test]
@@ -2013,6 +6161,292 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_page_to_form_xobject_bbox() {
+        let dir = std::env::temp_dir();
+        let src = dir.join("pdfrs_form_src.pdf");
+        let rotated = dir.join("pdfrs_form_rotated.pdf");
+        crate::pdf_generator::create_pdf_from_elements_with_layout(
+            src.to_str().unwrap(),
+            &[crate::elements::Element::Paragraph { text: "Form page".into() }],
+            "Helvetica", 12.0,
+            crate::pdf_generator::PageLayout::landscape(),
+        ).unwrap();
+        let doc = crate::pdf::PdfDocument::load_from_file(src.to_str().unwrap()).unwrap();
+        let media_box = page_media_box(&doc, doc.pages[0]);
+
+        let mut generator = crate::pdf_generator::PdfGenerator::new();
+        let id = page_to_form_xobject(&doc, 0, &mut generator).unwrap();
+        let forms = crate::pdf::PdfDocument::from_bytes(&generator.generate()).unwrap();
+        let form = forms.get_object(id).and_then(|o| o.as_dict()).unwrap();
+        assert_eq!(form.get("Subtype").and_then(|v| v.as_name()), Some("Form"));
+        let bbox: Vec<f32> = match forms.dict_get(form, "BBox") {
+            Some(crate::pdf::PdfObject::Array(items)) => {
+                items.iter().filter_map(|v| v.as_number()).map(|n| n as f32).collect()
+            }
+            _ => panic!("form has no /BBox"),
+        };
+        assert_eq!(bbox, media_box.to_vec());
+        assert!(page_to_form_xobject(&doc, 1, &mut generator).is_err());
+
+        // A rotated page gets a rotating /Matrix
+        rotate_pdf(src.to_str().unwrap(), rotated.to_str().unwrap(), 90).unwrap();
+        let doc = crate::pdf::PdfDocument::load_from_file(rotated.to_str().unwrap()).unwrap();
+        let mut generator = crate::pdf_generator::PdfGenerator::new();
+        page_to_form_xobject(&doc, 0, &mut generator).unwrap();
+        let out = String::from_utf8_lossy(&generator.generate()).to_string();
+        assert!(out.contains("/Matrix [0 -1 1 0 "), "{}", out);
+    }
+
+    #[test]
+    fn test_resize_letter_to_a4() {
+        let dir = std::env::temp_dir();
+        let src = dir.join("pdfrs_resize_letter.pdf");
+        let out = dir.join("pdfrs_resize_a4.pdf");
+        let elements = vec![crate::elements::Element::Paragraph { text: "Scaled content".into() }];
+        crate::pdf_generator::create_pdf_from_elements_with_layout(
+            src.to_str().unwrap(), &elements, "Helvetica", 12.0,
+            crate::pdf_generator::PageLayout::portrait(),
+        ).unwrap();
+
+        resize_pages(
+            src.to_str().unwrap(), out.to_str().unwrap(),
+            crate::pdf_generator::PageLayout::a4(), ResizeMode::Fit,
+        ).unwrap();
+
+        let raw = fs::read(&out).unwrap();
+        let content = String::from_utf8_lossy(&raw);
+        assert!(content.contains("/MediaBox [0 0 595 842]"));
+        assert!(content.contains("/Subtype /Form"));
+        assert!(content.contains("/BBox [0 0 612 792]"));
+        // 595 / 612 is the limiting scale factor for Letter -> A4
+        assert!(content.contains("0.9722"), "expected a fit scale matrix");
+        assert!(crate::pdf::validate_pdf_bytes(&raw).valid);
+
+        let doc = crate::pdf::PdfDocument::from_bytes(&raw).unwrap();
+        assert_eq!(doc.pages.len(), 1);
+        assert!(doc.get_text().unwrap().contains("Scaled content"));
+        assert!(doc.fonts().iter().any(|f| f.base_font == "Helvetica"));
+    }
+
+    #[test]
+    fn test_merge_normalizes_page_sizes() {
+        let dir = std::env::temp_dir();
+        let letter = dir.join("pdfrs_merge_norm_letter.pdf");
+        let a4 = dir.join("pdfrs_merge_norm_a4.pdf");
+        let out = dir.join("pdfrs_merge_norm_out.pdf");
+        for (path, text, layout) in [
+            (&letter, "Letter page", crate::pdf_generator::PageLayout::portrait()),
+            (&a4, "A4 page", crate::pdf_generator::PageLayout::a4()),
+        ] {
+            let elements = vec![crate::elements::Element::Paragraph { text: text.into() }];
+            crate::pdf_generator::create_pdf_from_elements_with_layout(
+                path.to_str().unwrap(), &elements, "Helvetica", 12.0, layout,
+            ).unwrap();
+        }
+
+        let options = MergeOptions { normalize_size: Some(crate::pdf_generator::PageLayout::a4()), ..Default::default() };
+        merge_pdfs_with_options(
+            &[letter.to_str().unwrap(), a4.to_str().unwrap()], out.to_str().unwrap(), &options,
+        ).unwrap();
+
+        let raw = fs::read(&out).unwrap();
+        let doc = crate::pdf::PdfDocument::from_bytes(&raw).unwrap();
+        assert_eq!(doc.pages.len(), 2);
+        for &page in &doc.pages {
+            assert_eq!(page_media_box(&doc, page), [0.0, 0.0, 595.0, 842.0]);
+        }
+        let content = String::from_utf8_lossy(&raw);
+        // The Letter page is scaled down by 595 / 612; the A4 page is drawn 1:1
+        assert!(content.contains("0.9722"), "expected a fit scale matrix");
+        assert!(content.contains("q\n1 0 0 1 0 0 cm\n/Pg0 Do"));
+        let text = doc.get_text().unwrap();
+        assert!(text.contains("Letter page") && text.contains("A4 page"));
+
+        // Without normalization each page keeps its own size
+        merge_pdfs_with_options(
+            &[letter.to_str().unwrap(), a4.to_str().unwrap()], out.to_str().unwrap(),
+            &MergeOptions::default(),
+        ).unwrap();
+        let doc = crate::pdf::PdfDocument::load_from_file(out.to_str().unwrap()).unwrap();
+        assert_eq!(page_media_box(&doc, doc.pages[0]), [0.0, 0.0, 612.0, 792.0]);
+        assert_eq!(page_media_box(&doc, doc.pages[1]), [0.0, 0.0, 595.0, 842.0]);
+    }
+
+    #[test]
+    fn test_merge_keeps_flate_compression() {
+        let dir = std::env::temp_dir();
+        let inputs = [dir.join("pdfrs_merge_flate_a.pdf"), dir.join("pdfrs_merge_flate_b.pdf")];
+        let out = dir.join("pdfrs_merge_flate_out.pdf");
+        for (i, path) in inputs.iter().enumerate() {
+            let content = format!("BT /F1 12 Tf 72 720 Td (Compressed page {}) Tj ET\n", i).repeat(200);
+            let mut generator = crate::pdf_generator::PdfGenerator::new();
+            let content_id = generator.add_compressed_stream_object("", content.as_bytes());
+            let font_id = generator.add_object("<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>\n".to_string());
+            let page_id = generator.next_id;
+            let pages_id = page_id + 1;
+            generator.add_object(format!(
+                "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 612 792] /Contents {} 0 R /Resources << /Font << /F1 {} 0 R >> >> >>\n",
+                pages_id, content_id, font_id
+            ));
+            generator.add_object(format!("<< /Type /Pages /Kids [{} 0 R] /Count 1 >>\n", page_id));
+            generator.add_object(format!("<< /Type /Catalog /Pages {} 0 R >>\n", pages_id));
+            fs::write(path, generator.generate()).unwrap();
+        }
+        let input_size: u64 = inputs.iter().map(|p| fs::metadata(p).unwrap().len()).sum();
+        let paths: Vec<&str> = inputs.iter().map(|p| p.to_str().unwrap()).collect();
+
+        merge_pdfs_with_options(&paths, out.to_str().unwrap(), &MergeOptions::default()).unwrap();
+        let raw = fs::read(&out).unwrap();
+        assert!(String::from_utf8_lossy(&raw).contains("/Filter /FlateDecode"));
+        assert!((raw.len() as u64) < input_size * 2, "{} bytes from {} bytes of input", raw.len(), input_size);
+        let doc = crate::pdf::PdfDocument::from_bytes(&raw).unwrap();
+        let text = doc.get_text().unwrap();
+        assert!(text.contains("Compressed page 0") && text.contains("Compressed page 1"));
+
+        // Decoded content is far larger
+        let options = MergeOptions { keep_compression: false, ..Default::default() };
+        merge_pdfs_with_options(&paths, out.to_str().unwrap(), &options).unwrap();
+        let decoded = fs::read(&out).unwrap();
+        assert!(!String::from_utf8_lossy(&decoded).contains("/FlateDecode"));
+        assert!(decoded.len() > raw.len() * 4);
+
+        for path in inputs.iter().chain([&out]) {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn test_nup_two_by_two() {
+        let dir = std::env::temp_dir();
+        let src = dir.join("pdfrs_nup_src.pdf");
+        let out = dir.join("pdfrs_nup_out.pdf");
+        let mut elements = Vec::new();
+        for i in 1..=4 {
+            if i > 1 {
+                elements.push(crate::elements::Element::PageBreak);
+            }
+            elements.push(crate::elements::Element::Paragraph { text: format!("Source page {}", i) });
+        }
+        crate::pdf_generator::create_pdf_from_elements_with_layout(
+            src.to_str().unwrap(), &elements, "Helvetica", 12.0,
+            crate::pdf_generator::PageLayout::portrait(),
+        ).unwrap();
+
+        nup_pdf(src.to_str().unwrap(), out.to_str().unwrap(), 2, 2).unwrap();
+
+        let raw = fs::read(&out).unwrap();
+        let doc = crate::pdf::PdfDocument::from_bytes(&raw).unwrap();
+        assert_eq!(doc.pages.len(), 1);
+        let content = String::from_utf8_lossy(&raw);
+        assert_eq!(content.matches(" Do\n").count(), 4);
+        let text = doc.get_text().unwrap();
+        for i in 1..=4 {
+            assert!(text.contains(&format!("Source page {}", i)));
+        }
+    }
+
+    #[test]
+    fn test_rotate_pages_turns_only_listed_pages() {
+        use crate::elements::Element;
+        let dir = std::env::temp_dir();
+        let src = dir.join("pdfrs_rotate_pages_src.pdf");
+        let out = dir.join("pdfrs_rotate_pages_out.pdf");
+        let elements = vec![
+            Element::Paragraph { text: "Page one".into() },
+            Element::PageBreak,
+            Element::Paragraph { text: "Page two".into() },
+            Element::PageBreak,
+            Element::Paragraph { text: "Page three".into() },
+        ];
+        crate::pdf_generator::create_pdf_from_elements(src.to_str().unwrap(), &elements, "Helvetica", 12.0).unwrap();
+
+        rotate_pages(src.to_str().unwrap(), out.to_str().unwrap(), &[(2, 90)]).unwrap();
+        let doc = crate::pdf::PdfDocument::load_from_file(out.to_str().unwrap()).unwrap();
+        let rotation = |index: usize| {
+            doc.page_attribute(doc.pages[index], "Rotate").and_then(|r| r.as_number()).unwrap_or(0.0)
+        };
+        assert_eq!((rotation(0), rotation(1), rotation(2)), (0.0, 90.0, 0.0));
+        assert!(doc.get_text_by_page().unwrap()[1].contains("Page two"));
+
+        // Angles add to the current rotation, and pages must exist
+        let again = dir.join("pdfrs_rotate_pages_again.pdf");
+        rotate_pages(out.to_str().unwrap(), again.to_str().unwrap(), &[(2, 270), (3, 180)]).unwrap();
+        let doc = crate::pdf::PdfDocument::load_from_file(again.to_str().unwrap()).unwrap();
+        let rotations: Vec<f64> = doc
+            .pages
+            .iter()
+            .map(|&page| doc.page_attribute(page, "Rotate").and_then(|r| r.as_number()).unwrap_or(0.0))
+            .collect();
+        assert_eq!(rotations, [0.0, 0.0, 180.0]);
+        assert!(matches!(
+            rotate_pages(src.to_str().unwrap(), out.to_str().unwrap(), &[(4, 90)]),
+            Err(PdfError::InvalidPageRange { .. })
+        ));
+        for path in [src, out, again] {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn test_append_page_incremental() {
+        let dir = std::env::temp_dir();
+        let src = dir.join("pdfrs_append_src.pdf");
+        let out = dir.join("pdfrs_append_out.pdf");
+        crate::pdf_generator::create_pdf_from_elements_with_layout(
+            src.to_str().unwrap(),
+            &[crate::elements::Element::Paragraph { text: "Original page".into() }],
+            "Helvetica", 12.0,
+            crate::pdf_generator::PageLayout::portrait(),
+        ).unwrap();
+        let original = fs::read(&src).unwrap();
+
+        append_page(
+            src.to_str().unwrap(),
+            out.to_str().unwrap(),
+            &[crate::elements::Element::Paragraph { text: "Appended page".into() }],
+        ).unwrap();
+
+        let raw = fs::read(&out).unwrap();
+        assert!(raw.starts_with(&original));
+        let content = String::from_utf8_lossy(&raw);
+        assert_eq!(content.matches("startxref").count(), 2);
+        assert_eq!(content.matches("\nxref\n").count(), 2);
+        assert!(content.contains("/Prev "));
+
+        let doc = crate::pdf::PdfDocument::from_bytes(&raw).unwrap();
+        assert_eq!(doc.pages.len(), 2);
+        let text = doc.get_text().unwrap();
+        assert!(text.contains("Original page"));
+        assert!(text.contains("Appended page"));
+    }
+
+    #[test]
+    fn test_nup_partial_grid() {
+        let dir = std::env::temp_dir();
+        let src = dir.join("pdfrs_nup_partial_src.pdf");
+        let out = dir.join("pdfrs_nup_partial_out.pdf");
+        let elements = vec![
+            crate::elements::Element::Paragraph { text: "One".into() },
+            crate::elements::Element::PageBreak,
+            crate::elements::Element::Paragraph { text: "Two".into() },
+            crate::elements::Element::PageBreak,
+            crate::elements::Element::Paragraph { text: "Three".into() },
+        ];
+        crate::pdf_generator::create_pdf_from_elements_with_layout(
+            src.to_str().unwrap(), &elements, "Helvetica", 12.0,
+            crate::pdf_generator::PageLayout::portrait(),
+        ).unwrap();
+
+        nup_pdf(src.to_str().unwrap(), out.to_str().unwrap(), 1, 2).unwrap();
+
+        let raw = fs::read(&out).unwrap();
+        let doc = crate::pdf::PdfDocument::from_bytes(&raw).unwrap();
+        assert_eq!(doc.pages.len(), 2);
+        assert_eq!(String::from_utf8_lossy(&raw).matches(" Do\n").count(), 3);
+        assert!(nup_pdf(src.to_str().unwrap(), out.to_str().unwrap(), 0, 2).is_err());
+    }
+
     #[test]
     fn test_create_pdf_with_images_empty() {
         let result = create_pdf_with_images("out.pdf", &[]);
@@ -2029,6 +6463,8 @@ mod tests {
             height: 20.0,
             content: "A note".into(),
             title: "Author".into(),
+            color: None,
+            border: None,
         };
         assert_eq!(annot.content, "A note");
         assert_eq!(annot.x, 100.0);
@@ -2042,10 +6478,279 @@ mod tests {
             width: 100.0,
             height: 15.0,
             url: "https://example.com".into(),
+            color: None,
+            border: None,
         };
         assert_eq!(link.url, "https://example.com");
     }
 
+    #[test]
+    fn test_change_password_rotates_user_password() {
+        let dir = std::env::temp_dir();
+        let plain = dir.join("pdfrs_password_plain.pdf");
+        let protected = dir.join("pdfrs_password_old.pdf");
+        let rotated = dir.join("pdfrs_password_new.pdf");
+        crate::pdf_generator::create_pdf(plain.to_str().unwrap(), "Secret report").unwrap();
+        let security = crate::security::PdfSecurity::new()
+            .with_user_password("old".to_string())
+            .with_owner_password("owner".to_string())
+            .with_permissions(crate::security::PdfPermissions::read_only());
+        protect_pdf(plain.to_str().unwrap(), protected.to_str().unwrap(), &security).unwrap();
+
+        let wrong = change_password(protected.to_str().unwrap(), rotated.to_str().unwrap(), "wrong", Some("new"), None);
+        assert!(matches!(wrong, Err(PdfError::Encrypted)));
+        change_password(protected.to_str().unwrap(), rotated.to_str().unwrap(), "old", Some("new"), Some("owner2")).unwrap();
+
+        let content: String = fs::read(&rotated).unwrap().iter().map(|&b| b as char).collect();
+        let range = trailer_encrypt_dict(&content).unwrap();
+        let encryption = crate::security::StandardEncryption::parse(&content[range]).unwrap();
+        let file_id = trailer_file_id(&content[content.rfind("trailer").unwrap()..]).unwrap();
+        assert!(encryption.authenticate_user("new", &file_id));
+        assert!(!encryption.authenticate_user("old", &file_id));
+        assert_eq!(encryption.permissions, crate::security::PdfPermissions::read_only().to_pdf_flags());
+
+        // The objects were re-encrypted: only the new password reads the text
+        let opened = dir.join("pdfrs_password_opened.pdf");
+        assert!(matches!(
+            decrypt_pdf(rotated.to_str().unwrap(), opened.to_str().unwrap(), "old"),
+            Err(PdfError::Encrypted)
+        ));
+        decrypt_pdf(rotated.to_str().unwrap(), opened.to_str().unwrap(), "new").unwrap();
+        assert!(crate::pdf::extract_text(opened.to_str().unwrap()).unwrap().contains("Secret report"));
+
+        // Without new passwords the protection is removed
+        change_password(rotated.to_str().unwrap(), opened.to_str().unwrap(), "new", None, None).unwrap();
+        assert!(crate::pdf::find_bytes(&fs::read(&opened).unwrap(), b"/Encrypt").is_none());
+        assert!(crate::pdf::extract_text(opened.to_str().unwrap()).unwrap().contains("Secret report"));
+
+        for path in [plain, protected, rotated, opened] {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn test_owner_only_protection_opens_without_password() {
+        let dir = std::env::temp_dir();
+        let plain = dir.join("pdfrs_owner_only_plain.pdf");
+        let protected = dir.join("pdfrs_owner_only_protected.pdf");
+        crate::pdf_generator::create_pdf(plain.to_str().unwrap(), "Look, don't print").unwrap();
+        let permissions = crate::security::PdfPermissions { print: false, ..Default::default() };
+        let security = crate::security::PdfSecurity::owner_only("owner".to_string(), permissions);
+        security.validate().unwrap();
+        protect_pdf(plain.to_str().unwrap(), protected.to_str().unwrap(), &security).unwrap();
+
+        let content: String = fs::read(&protected).unwrap().iter().map(|&b| b as char).collect();
+        let encryption = crate::security::StandardEncryption::parse(&content[trailer_encrypt_dict(&content).unwrap()]).unwrap();
+        let file_id = trailer_file_id(&content[content.rfind("trailer").unwrap()..]).unwrap();
+        assert!(encryption.authenticate_user("", &file_id));
+        assert!(!encryption.authenticate_user("owner", &file_id));
+        assert!(!crate::security::PdfPermissions::from_pdf_flags(encryption.permissions).print);
+
+        // Readers open it with the empty password and see the original text
+        let doc = crate::pdf::PdfDocument::load_from_file(protected.to_str().unwrap()).unwrap();
+        assert!(doc.info_flags().encrypted);
+        assert!(doc.get_text().unwrap().contains("Look, don't print"));
+
+        for path in [plain, protected] {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn test_decrypt_pdf_removes_protection() {
+        let dir = std::env::temp_dir();
+        let plain = dir.join("pdfrs_decrypt_plain.pdf");
+        let protected = dir.join("pdfrs_decrypt_protected.pdf");
+        let encrypted = dir.join("pdfrs_decrypt_rc4.pdf");
+        let decrypted = dir.join("pdfrs_decrypt_out.pdf");
+        crate::pdf_generator::create_pdf(plain.to_str().unwrap(), "Secret report").unwrap();
+        let security = crate::security::PdfSecurity::new()
+            .with_user_password("open".to_string())
+            .with_encryption(crate::security::EncryptionAlgorithm::Rc4_128);
+        protect_pdf(plain.to_str().unwrap(), protected.to_str().unwrap(), &security).unwrap();
+
+        let wrong = decrypt_pdf(protected.to_str().unwrap(), decrypted.to_str().unwrap(), "wrong");
+        assert!(matches!(wrong, Err(PdfError::Encrypted)));
+        decrypt_pdf(protected.to_str().unwrap(), decrypted.to_str().unwrap(), "open").unwrap();
+        let bytes = fs::read(&decrypted).unwrap();
+        assert!(bytes.starts_with(b"%PDF-"));
+        assert!(crate::pdf::find_bytes(&bytes, b"/Encrypt").is_none());
+        assert!(crate::pdf::extract_text(decrypted.to_str().unwrap()).unwrap().contains("Secret report"));
+
+        // The objects themselves are encrypted, not just marked as such
+        assert!(crate::pdf::find_bytes(&fs::read(&plain).unwrap(), b"Secret report").is_some());
+        assert!(crate::pdf::find_bytes(&fs::read(&protected).unwrap(), b"Secret report").is_none());
+
+        // AES-128 round-trips too
+        let aes = security.clone().with_encryption(crate::security::EncryptionAlgorithm::Aes_128);
+        protect_pdf(plain.to_str().unwrap(), encrypted.to_str().unwrap(), &aes).unwrap();
+        let aes_bytes = fs::read(&encrypted).unwrap();
+        assert!(crate::pdf::find_bytes(&aes_bytes, b"/AESV2").is_some());
+        assert!(crate::pdf::find_bytes(&aes_bytes, b"Secret report").is_none());
+        decrypt_pdf(encrypted.to_str().unwrap(), decrypted.to_str().unwrap(), "open").unwrap();
+        assert!(crate::pdf::find_bytes(&fs::read(&decrypted).unwrap(), b"/Encrypt").is_none());
+        assert!(crate::pdf::extract_text(decrypted.to_str().unwrap()).unwrap().contains("Secret report"));
+
+        for path in [plain, protected, encrypted, decrypted] {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn test_decrypt_pdf_expands_object_streams() {
+        let dir = std::env::temp_dir();
+        let encrypted = dir.join("pdfrs_decrypt_objstm.pdf");
+        let decrypted = dir.join("pdfrs_decrypt_objstm_out.pdf");
+        let mut generator = crate::pdf_generator::PdfGenerator::new();
+        let font = generator.add_object("<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".into());
+        let text = b"BT /F1 12 Tf 72 720 Td (Packed secret) Tj ET".to_vec();
+        let content = generator.add_stream_object(format!("<< /Length {} >>\n", text.len()), text);
+        let pages = generator.next_id + 1;
+        let page = generator.add_object(format!(
+            "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 612 792] /Resources << /Font << /F1 {} 0 R >> >> /Contents {} 0 R >>",
+            pages, font, content
+        ));
+        generator.add_object(format!("<< /Type /Pages /Kids [{} 0 R] /Count 1 >>", page));
+        generator.add_object(format!("<< /Type /Catalog /Pages {} 0 R >>", pages));
+        generator.set_object_streams(true);
+        let packed = generator.generate();
+
+        // Encrypt each stream in place (RC4 keeps lengths, so the xref stream
+        // stays valid) and point the xref stream at an /Encrypt object
+        let file_id = [7u8; 16];
+        let security = crate::security::PdfSecurity::new()
+            .with_user_password("open".to_string())
+            .with_encryption(crate::security::EncryptionAlgorithm::Rc4_128);
+        let cipher = security.standard_encryption(&file_id).unwrap().cipher("open", &file_id);
+        let mut bytes = packed.clone();
+        for (&id, (generation, body)) in &scan_raw_objects(&packed) {
+            if crate::pdf::find_bytes(body, b"/Type /XRef").is_some() {
+                continue;
+            }
+            let start = crate::pdf::find_bytes(&packed, body).unwrap();
+            let sealed = crypt_object(body, |data| cipher.encrypt(id, *generation, data));
+            assert_eq!(sealed.len(), body.len());
+            bytes[start..start + body.len()].copy_from_slice(&sealed);
+        }
+        let startxref = crate::pdf::find_bytes(&bytes, b"startxref").unwrap();
+        let encrypt_object = format!("99 0 obj\n{}\nendobj\n", security.encryption_dict_for(&file_id));
+        bytes.splice(startxref..startxref, encrypt_object.into_bytes());
+        let hex: String = file_id.iter().map(|b| format!("{:02X}", b)).collect();
+        let xref = crate::pdf::find_bytes(&bytes, b"/Type /XRef\n").unwrap() + b"/Type /XRef\n".len();
+        bytes.splice(xref..xref, format!("/Encrypt 99 0 R\n/ID [<{}> <{}>]\n", hex, hex).into_bytes());
+        assert!(crate::pdf::find_bytes(&bytes, b"/Type /ObjStm").is_some());
+        fs::write(&encrypted, &bytes).unwrap();
+
+        decrypt_pdf(encrypted.to_str().unwrap(), decrypted.to_str().unwrap(), "open").unwrap();
+        let out = fs::read(&decrypted).unwrap();
+        assert!(crate::pdf::find_bytes(&out, b"/Encrypt").is_none());
+        assert!(crate::pdf::find_bytes(&out, b"/ObjStm").is_none());
+        assert!(crate::pdf::find_bytes(&out, b"/Type /Catalog").is_some());
+        assert!(crate::pdf::extract_text(decrypted.to_str().unwrap()).unwrap().contains("Packed secret"));
+
+        for path in [encrypted, decrypted] {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn test_annotated_pages_share_one_font_object() {
+        let out = std::env::temp_dir().join("pdfrs_shared_font.pdf");
+        let text = ["One", "Two", "Three", "Four", "Five"].join("\n\n\\pagebreak\n\n");
+        let note = TextAnnotation {
+            x: 72.0, y: 700.0, width: 20.0, height: 20.0,
+            content: "Note".into(),
+            title: "Reviewer".into(),
+            color: None,
+            border: None,
+        };
+        create_pdf_with_all_annotations(out.to_str().unwrap(), &text, &[note], &[], &[]).unwrap();
+
+        let bytes = fs::read(&out).unwrap();
+        assert!(crate::pdf::validate_pdf_bytes(&bytes).valid);
+        let doc = crate::pdf::PdfDocument::from_bytes(&bytes).unwrap();
+        assert_eq!(doc.pages.len(), 5);
+        let raw = String::from_utf8_lossy(&bytes);
+        assert_eq!(raw.matches("/BaseFont /Helvetica\n").count(), 1);
+        let font_refs: Vec<&str> = raw.split("/Font << /F1 ").skip(1).map(|rest| rest.split(" 0 R").next().unwrap()).collect();
+        assert_eq!(font_refs.len(), 5);
+        assert!(font_refs.iter().all(|id| *id == font_refs[0]));
+        let _ = fs::remove_file(&out);
+    }
+
+    #[test]
+    fn test_extract_annotations_reports_text_and_link() {
+        let out = std::env::temp_dir().join("pdfrs_extract_annotations.pdf");
+        let note = TextAnnotation {
+            x: 72.0, y: 650.0, width: 24.0, height: 24.0,
+            content: "Check this figure".into(),
+            title: "Reviewer".into(),
+            color: None,
+            border: None,
+        }
+        .with_color(crate::pdf_generator::Color::rgb(1.0, 0.5, 0.0));
+        let link = LinkAnnotation {
+            x: 72.0, y: 700.0, width: 100.0, height: 15.0,
+            url: "https://example.com".into(),
+            color: None,
+            border: None,
+        };
+        create_pdf_with_all_annotations(out.to_str().unwrap(), "Annotated", &[note], &[link], &[]).unwrap();
+
+        let annotations = crate::pdf::extract_annotations(out.to_str().unwrap()).unwrap();
+        assert_eq!(annotations.len(), 2);
+        let text = &annotations[0];
+        assert_eq!((text.page, text.subtype.as_str()), (1, "Text"));
+        assert_eq!(text.rect, [72.0, 650.0, 96.0, 674.0]);
+        assert_eq!(text.contents.as_deref(), Some("Check this figure"));
+        assert_eq!(text.color, Some([1.0, 0.5, 0.0]));
+        let link = &annotations[1];
+        assert_eq!((link.page, link.subtype.as_str()), (1, "Link"));
+        assert_eq!(link.rect, [72.0, 700.0, 172.0, 715.0]);
+        assert_eq!(link.uri.as_deref(), Some("https://example.com"));
+        let _ = fs::remove_file(&out);
+    }
+
+    #[test]
+    fn test_extract_links_reads_uri_and_rect() {
+        let out = std::env::temp_dir().join("pdfrs_extract_links.pdf");
+        let link = LinkAnnotation {
+            x: 72.0, y: 700.0, width: 100.0, height: 15.0,
+            url: "https://example.com/docs?q=(1)".into(),
+            color: None,
+            border: None,
+        };
+        create_pdf_with_all_annotations(out.to_str().unwrap(), "Links", &[], &[link], &[]).unwrap();
+
+        let links = crate::pdf::extract_links(out.to_str().unwrap()).unwrap();
+        assert_eq!(links, vec![(1, "https://example.com/docs?q=(1)".to_string(), [72.0, 700.0, 172.0, 715.0])]);
+        let _ = fs::remove_file(&out);
+    }
+
+    #[test]
+    fn test_link_border_and_color() {
+        let out = std::env::temp_dir().join("pdfrs_link_border.pdf");
+        let link = LinkAnnotation {
+            x: 72.0, y: 700.0, width: 100.0, height: 15.0,
+            url: "https://example.com".into(),
+            color: None,
+            border: None,
+        }
+        .with_border(1.5, BorderStyle::Underline)
+        .with_color(crate::pdf_generator::Color::blue());
+        let plain = TextAnnotation {
+            x: 100.0, y: 600.0, width: 20.0, height: 20.0,
+            content: "Note".into(), title: "Me".into(), color: None, border: None,
+        };
+        create_pdf_with_all_annotations(out.to_str().unwrap(), "Links", &[plain], &[link], &[]).unwrap();
+
+        let raw = String::from_utf8_lossy(&fs::read(&out).unwrap()).to_string();
+        assert!(raw.contains("/BS << /W 1.5 /S /U >>"), "{}", raw);
+        assert!(raw.contains("/C [0 0 1]"));
+        assert!(!raw.contains("/Border [0 0 0]"));
+        let _ = fs::remove_file(&out);
+    }
+
     #[test]
     fn test_reorder_empty() {
         let result = reorder_pages("nonexistent.pdf", "out.pdf", &[]);
@@ -2053,6 +6758,75 @@ mod tests {
         assert!(result.unwrap_err().to_string().contains("empty"));
     }
 
+    #[test]
+    fn test_repair_rebuilds_xref() {
+        let input = std::env::temp_dir().join("pdfrs_repair_broken.pdf");
+        let output = std::env::temp_dir().join("pdfrs_repair_fixed.pdf");
+        let elements = vec![crate::elements::Element::Paragraph { text: "Recovered text".into() }];
+        let mut bytes = crate::pdf_generator::generate_pdf_bytes(
+            &elements,
+            "Helvetica",
+            12.0,
+            crate::pdf_generator::PageLayout::portrait(),
+        )
+        .unwrap();
+        // Point startxref into the middle of the file
+        let pos = crate::pdf::find_bytes(&bytes, b"startxref\n").unwrap() + b"startxref\n".len();
+        let end = pos + bytes[pos..].iter().position(|&b| b == b'\n').unwrap();
+        bytes.splice(pos..end, b"17".iter().copied());
+        std::fs::write(&input, &bytes).unwrap();
+        assert!(!crate::pdf::validate_pdf_bytes(&bytes).valid);
+
+        repair_pdf(input.to_str().unwrap(), output.to_str().unwrap()).unwrap();
+        let repaired = std::fs::read(&output).unwrap();
+        let validation = crate::pdf::validate_pdf_bytes(&repaired);
+        assert!(validation.valid, "{:?}", validation.errors);
+        let text = crate::pdf::extract_text(output.to_str().unwrap()).unwrap();
+        assert!(text.contains("Recovered text"));
+    }
+
+    #[test]
+    fn test_repair_balance_fixes_stream_lengths() {
+        let input = std::env::temp_dir().join("pdfrs_balance_broken.pdf");
+        let output = std::env::temp_dir().join("pdfrs_balance_fixed.pdf");
+        let elements = vec![crate::elements::Element::Paragraph { text: "Balanced text".into() }];
+        let bytes = crate::pdf_generator::generate_pdf_bytes(
+            &elements,
+            "Helvetica",
+            12.0,
+            crate::pdf_generator::PageLayout::portrait(),
+        )
+        .unwrap();
+        // Understate every stream length by 5 and append junk after %%EOF
+        let length_re = regex::bytes::Regex::new(r"/Length (\d+)").unwrap();
+        let mut broken = length_re
+            .replace_all(&bytes, |caps: &regex::bytes::Captures| {
+                let n: usize = std::str::from_utf8(&caps[1]).unwrap().parse().unwrap();
+                format!("/Length {}", n - 5).into_bytes()
+            })
+            .into_owned();
+        broken.extend_from_slice(b"garbage after the end\n");
+        std::fs::write(&input, &broken).unwrap();
+
+        let fixed = repair_balance(input.to_str().unwrap(), output.to_str().unwrap()).unwrap();
+        assert!(fixed > 0);
+        let repaired = std::fs::read(&output).unwrap();
+        assert!(repaired.ends_with(b"%%EOF\n"));
+        let validation = crate::pdf::validate_pdf_bytes(&repaired);
+        assert!(validation.valid, "{:?}", validation.errors);
+        for caps in regex::bytes::Regex::new(r"(?s)/Length (\d+)[^>]*>>\s*stream\n").unwrap().captures_iter(&repaired) {
+            let declared: usize = std::str::from_utf8(&caps[1]).unwrap().parse().unwrap();
+            let start = caps.get(0).unwrap().end();
+            let end = start + crate::pdf::find_bytes(&repaired[start..], b"\nendstream").unwrap();
+            assert_eq!(declared, end - start);
+        }
+        let text = crate::pdf::extract_text(output.to_str().unwrap()).unwrap();
+        assert!(text.contains("Balanced text"));
+
+        // Already consistent lengths are left alone
+        assert_eq!(repair_balance(output.to_str().unwrap(), input.to_str().unwrap()).unwrap(), 0);
+    }
+
     #[test]
     fn test_build_watermark_stream() {
         let layout = crate::pdf_generator::PageLayout::portrait();
@@ -2074,12 +6848,57 @@ mod tests {
             color_r: 1.0,
             color_g: 1.0,
             color_b: 0.0,
+            continued: Vec::new(),
         };
         assert_eq!(hl.color_r, 1.0);
         assert_eq!(hl.color_g, 1.0);
         assert_eq!(hl.color_b, 0.0);
     }
 
+    #[test]
+    fn test_generated_page_streams_report_the_cause() {
+        let err = generated_page_streams(b"%PDF-1.4\n%%EOF\n").unwrap_err().to_string();
+        assert!(err.contains("no page content streams"), "{}", err);
+        assert!(!err.contains("No page content generated"));
+
+        let elements = crate::elements::parse_markdown("Hello");
+        let streams = build_page_streams(&elements, 12.0, true, crate::pdf_generator::PageLayout::portrait()).unwrap();
+        assert_eq!(streams.len(), 1);
+
+        // A generation failure reaches the caller with its own message
+        let cramped = crate::pdf_generator::PageLayout::portrait().with_margins(72.0, 300.0, 72.0, 400.0);
+        let err = build_page_streams(&elements, 12.0, true, cramped).unwrap_err().to_string();
+        assert!(err.contains("margins leave no room"), "{}", err);
+        let output = std::env::temp_dir().join("pdfrs_cramped_metadata.pdf");
+        let err = create_pdf_elements_with_metadata(
+            output.to_str().unwrap(), &elements, "Helvetica", 12.0, cramped, &PdfMetadata::default(),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("margins leave no room") && !err.contains("No page content generated"), "{}", err);
+    }
+
+    #[test]
+    fn test_wrapped_highlight_emits_quadpoints_per_line() {
+        let hl = HighlightAnnotation::spanning(
+            &[Rect::new(300.0, 700.0, 240.0, 12.0), Rect::new(72.0, 686.0, 120.0, 12.0)],
+            crate::pdf_generator::Color::rgb(1.0, 1.0, 0.0),
+        );
+        assert_eq!(hl.segments().len(), 2);
+        let output = std::env::temp_dir().join("pdfrs_wrapped_highlight.pdf");
+        create_pdf_with_all_annotations(output.to_str().unwrap(), "Some wrapped text", &[], &[], &[hl]).unwrap();
+        let bytes = fs::read(&output).unwrap();
+        assert!(crate::pdf::validate_pdf_bytes(&bytes).valid);
+        let raw = String::from_utf8_lossy(&bytes);
+        let quads = raw.split("/QuadPoints [").nth(1).unwrap().split(']').next().unwrap();
+        let numbers: Vec<f32> = quads.split_whitespace().map(|n| n.parse().unwrap()).collect();
+        assert_eq!(numbers.len(), 16);
+        assert_eq!(&numbers[8..], &[72.0, 698.0, 192.0, 698.0, 72.0, 686.0, 192.0, 686.0]);
+        // The annotation rectangle bounds both segments
+        assert!(raw.contains("/Rect [72 686 540 712]"), "{}", raw);
+        let _ = fs::remove_file(&output);
+    }
+
     #[test]
     fn test_color_constructors() {
         let black = crate::pdf_generator::Color::black();
@@ -2114,6 +6933,40 @@ mod tests {
         assert!(dict.contains("/CustomField2 (Value2)"));
     }
 
+    #[test]
+    fn test_generation_is_deterministic() {
+        let md = std::env::temp_dir().join("pdfrs_deterministic.md");
+        fs::write(&md, "# Title\n\nSome text.\n\n- item").unwrap();
+        let run = |n: usize| {
+            let mut metadata = PdfMetadata { title: Some("Cache".to_string()), ..Default::default() };
+            for i in 0..8 {
+                metadata.add_custom_field(format!("Field{}", i), i.to_string());
+            }
+            let out = std::env::temp_dir().join(format!("pdfrs_deterministic_{}.pdf", n));
+            create_pdf_with_metadata(
+                md.to_str().unwrap(),
+                out.to_str().unwrap(),
+                "Helvetica",
+                12.0,
+                crate::pdf_generator::PageOrientation::Portrait,
+                &metadata,
+            )
+            .unwrap();
+            let bytes = fs::read(&out).unwrap();
+            let _ = fs::remove_file(&out);
+            bytes
+        };
+        assert_eq!(run(1), run(2));
+        let _ = fs::remove_file(&md);
+
+        let elements = crate::elements::parse_markdown("# A\n\nB");
+        let layout = crate::pdf_generator::PageLayout::portrait();
+        assert_eq!(
+            crate::pdf_generator::generate_pdf_bytes(&elements, "Helvetica", 12.0, layout).unwrap(),
+            crate::pdf_generator::generate_pdf_bytes(&elements, "Helvetica", 12.0, layout).unwrap()
+        );
+    }
+
     #[test]
     fn test_metadata_info_dict_with_custom_fields() {
         let mut metadata = PdfMetadata {
@@ -2134,6 +6987,41 @@ mod tests {
         assert!(dict.contains("/Producer (pdf-cli)"));
     }
 
+    #[test]
+    fn test_pdf_date_in_info_dict() {
+        let date = PdfDate::parse("2024-05-01T09:30:15+02:00").unwrap();
+        assert_eq!(date.to_pdf_string(), "D:20240501093015+02'00'");
+        assert_eq!(PdfDate::parse("2024-05-01").unwrap().to_pdf_string(), "D:20240501000000");
+        assert_eq!(PdfDate::parse("2024-05-01 23:59Z").unwrap().to_pdf_string(), "D:20240501235900Z");
+        assert!(PdfDate::parse("2024-13-01").is_none());
+        assert!(PdfDate::parse("yesterday").is_none());
+        let now = PdfDate::now();
+        assert!(now.year >= 2024 && (1..=12).contains(&now.month));
+
+        let metadata = PdfMetadata { creation_date: Some(date), mod_date: Some(date), ..Default::default() };
+        let dict = metadata.to_info_dict();
+        assert!(dict.contains("/CreationDate (D:20240501093015+02'00')"), "{}", dict);
+        assert!(dict.contains("/ModDate (D:20240501093015+02'00')"), "{}", dict);
+
+        let options = crate::pdf_generator::RenderOptions {
+            date: Some(date),
+            footer: Some("Printed {date} - {page}".into()),
+            ..Default::default()
+        };
+        let elements = vec![crate::elements::Element::Paragraph { text: "Body".into() }];
+        let bytes = crate::pdf_generator::generate_pdf_bytes_with_render_options(
+            &elements,
+            "Helvetica",
+            12.0,
+            crate::pdf_generator::PageLayout::portrait(),
+            &options,
+        )
+        .unwrap();
+        let raw = String::from_utf8_lossy(&bytes);
+        assert!(raw.contains("/CreationDate (D:20240501093015+02'00')"));
+        assert!(raw.contains("(Printed 2024-05-01 - 1) Tj"), "{}", raw);
+    }
+
     #[test]
     fn test_merge_metadata() {
         let mut base = PdfMetadata {
@@ -2190,6 +7078,8 @@ mod tests {
             default_value: Some("John".to_string()),
             options: vec![],
             required: true,
+            page: None,
+            flags: FieldFlags::NONE,
         };
         assert_eq!(field.name, "firstName");
         assert_eq!(field.field_type, FormFieldType::Text);
@@ -2197,6 +7087,30 @@ mod tests {
         assert_eq!(field.default_value, Some("John".to_string()));
     }
 
+    #[test]
+    fn test_multiline_flag_in_ff() {
+        let field = FormField {
+            name: "notes".to_string(),
+            field_type: FormFieldType::Text,
+            x: 50.0,
+            y: 500.0,
+            width: 300.0,
+            height: 80.0,
+            default_value: None,
+            options: vec![],
+            required: true,
+            page: None,
+            flags: FieldFlags::MULTILINE | FieldFlags::READ_ONLY,
+        };
+        let dict = create_form_field_dict(&field);
+        let ff: u32 = dict.split("/Ff ").nth(1).unwrap().lines().next().unwrap().parse().unwrap();
+        assert_eq!(ff & 0x1000, 0x1000);
+        assert!(FieldFlags(ff).contains(FieldFlags::READ_ONLY | FieldFlags::REQUIRED));
+
+        let dropdown = FormField { field_type: FormFieldType::Dropdown, flags: FieldFlags::NONE, required: false, ..field };
+        assert!(create_form_field_dict(&dropdown).contains("/Ff 131072\n"));
+    }
+
     #[test]
     fn test_field_type_to_pdf() {
         assert_eq!(field_type_to_pdf(&FormFieldType::Text), "/Tx");
@@ -2217,6 +7131,8 @@ mod tests {
             default_value: Some("default".to_string()),
             options: vec![],
             required: false,
+            page: None,
+            flags: FieldFlags::NONE,
         };
         let dict = create_form_field_dict(&field);
         assert!(dict.contains("/Type /Annot"));
@@ -2239,6 +7155,8 @@ mod tests {
             default_value: None,
             options: vec![],
             required: true,
+            page: None,
+            flags: FieldFlags::NONE,
         };
         let dict = create_form_field_dict(&field);
         assert!(dict.contains("/FT /Btn"));
@@ -2247,6 +7165,260 @@ mod tests {
         assert!(dict.contains("/V /Off"));
     }
 
+    #[test]
+    fn test_checked_checkbox_has_on_appearance() {
+        let field = FormField {
+            name: "agree".to_string(),
+            field_type: FormFieldType::Checkbox,
+            x: 50.0,
+            y: 550.0,
+            width: 15.0,
+            height: 15.0,
+            default_value: Some("Yes".to_string()),
+            options: vec![],
+            required: false,
+            page: None,
+            flags: FieldFlags::NONE,
+        };
+        let mut generator = crate::pdf_generator::PdfGenerator::new();
+        let (id, widgets) = add_form_field(&mut generator, &field, 1);
+        assert_eq!(widgets, vec![id]);
+
+        let widget = &generator.objects.iter().find(|o| o.id == id).unwrap().content;
+        assert!(widget.contains("/V /Yes"));
+        assert!(widget.contains("/AS /Yes"));
+        let on_re = regex::Regex::new(r"/Yes (\d+) 0 R").unwrap();
+        let on_id: u32 = on_re.captures(widget).unwrap()[1].parse().unwrap();
+        let on = generator.objects.iter().find(|o| o.id == on_id).unwrap();
+        assert!(on.content.contains("/Subtype /Form"));
+        let data = on.stream_data.as_ref().unwrap();
+        assert!(!data.is_empty());
+        // Box outline plus the check mark stroke
+        assert_eq!(String::from_utf8_lossy(data).matches(" S\n").count(), 2);
+    }
+
+    #[test]
+    fn test_signature_field_is_unsigned_with_bordered_box() {
+        let field = FormField {
+            name: "signature".to_string(),
+            field_type: FormFieldType::Signature,
+            x: 50.0,
+            y: 100.0,
+            width: 200.0,
+            height: 40.0,
+            default_value: Some("ignored".to_string()),
+            options: vec![],
+            required: false,
+            page: None,
+            flags: FieldFlags::NONE,
+        };
+        let dict = create_form_field_dict(&field);
+        assert!(dict.contains("/FT /Sig"));
+        assert!(!dict.contains("/V "));
+
+        let output = std::env::temp_dir().join("pdfrs_signature_field.pdf");
+        let output = output.to_str().unwrap();
+        create_pdf_with_form_fields(output, "Please sign below.", &[field]).unwrap();
+        let bytes = fs::read(output).unwrap();
+        let text = String::from_utf8_lossy(&bytes);
+        assert!(text.contains("/FT /Sig"));
+        assert!(text.contains("/SigFlags 1"));
+        // The appearance outlines the field and labels it
+        assert!(text.contains("0.5 0.5 199 39 re S"));
+        assert!(text.contains("(Sign here) Tj"));
+        assert!(crate::pdf::validate_pdf_bytes(&bytes).valid);
+        let _ = fs::remove_file(output);
+    }
+
+    #[test]
+    fn test_radio_group_parent_with_kids() {
+        let field = FormField {
+            name: "size".to_string(),
+            field_type: FormFieldType::Radio,
+            x: 50.0,
+            y: 500.0,
+            width: 12.0,
+            height: 12.0,
+            default_value: Some("Large".to_string()),
+            options: vec!["Small".to_string(), "Large".to_string()],
+            required: false,
+            page: None,
+            flags: FieldFlags::NONE,
+        };
+        let mut generator = crate::pdf_generator::PdfGenerator::new();
+        let (parent_id, kids) = add_form_field(&mut generator, &field, 1);
+        assert_eq!(kids.len(), 2);
+
+        let parent = &generator.objects.iter().find(|o| o.id == parent_id).unwrap().content;
+        assert!(parent.contains("/V /Large"));
+        assert!(parent.contains(&format!("/Kids [{} 0 R {} 0 R]", kids[0], kids[1])));
+        let first = &generator.objects.iter().find(|o| o.id == kids[0]).unwrap().content;
+        assert!(first.contains(&format!("/Parent {} 0 R", parent_id)));
+        assert!(first.contains("/AS /Off"));
+        let second = &generator.objects.iter().find(|o| o.id == kids[1]).unwrap().content;
+        assert!(second.contains("/AS /Large"));
+    }
+
+    #[test]
+    fn test_form_field_on_second_page() {
+        let field = FormField {
+            name: "signature".to_string(),
+            field_type: FormFieldType::Text,
+            x: 100.0,
+            y: 100.0,
+            width: 200.0,
+            height: 20.0,
+            default_value: None,
+            options: vec![],
+            required: false,
+            page: Some(2),
+            flags: FieldFlags::NONE,
+        };
+        let path = std::env::temp_dir().join("pdfrs_form_page2.pdf");
+        let path = path.to_str().unwrap();
+        create_pdf_with_form_fields(path, "Page one\n\n\\pagebreak\n\nPage two", std::slice::from_ref(&field))
+            .unwrap();
+
+        let doc = crate::pdf::PdfDocument::load_from_file(path).unwrap();
+        assert_eq!(doc.pages.len(), 2);
+        let annots = |page: u32| doc.get_object(page).and_then(|o| o.as_dict()).unwrap().contains_key("Annots");
+        assert!(!annots(doc.pages[0]));
+        assert!(annots(doc.pages[1]));
+        let bytes = fs::read(path).unwrap();
+        assert!(String::from_utf8_lossy(&bytes).contains("/Fields ["));
+
+        let missing = FormField { page: Some(5), ..field };
+        assert!(matches!(
+            create_pdf_with_form_fields(path, "Only page", &[missing]),
+            Err(PdfError::InvalidPageRange { page_count: Some(1), .. })
+        ));
+    }
+
+    #[test]
+    fn test_acroform_default_resources_and_appearance() {
+        let field = FormField {
+            name: "name".to_string(),
+            field_type: FormFieldType::Text,
+            x: 100.0,
+            y: 100.0,
+            width: 200.0,
+            height: 20.0,
+            default_value: None,
+            options: vec![],
+            required: false,
+            page: None,
+            flags: FieldFlags::NONE,
+        };
+        let path = std::env::temp_dir().join("pdfrs_form_default_resources.pdf");
+        let path = path.to_str().unwrap();
+        create_pdf_with_form_fields(path, "Name:", &[field]).unwrap();
+        let doc = crate::pdf::PdfDocument::load_from_file(path).unwrap();
+        let bytes = fs::read(path).unwrap();
+
+        // The form has a default font and appearance, and the field its own /DA
+        let acroform = doc
+            .get_object(doc.catalog)
+            .and_then(|c| c.as_dict())
+            .and_then(|c| doc.dict_get(c, "AcroForm"))
+            .and_then(|a| a.as_dict())
+            .unwrap();
+        let fonts = doc
+            .dict_get(acroform, "DR")
+            .and_then(|dr| dr.as_dict())
+            .and_then(|dr| doc.dict_get(dr, "Font"))
+            .and_then(|f| f.as_dict())
+            .unwrap();
+        assert!(fonts.contains_key("Helv"));
+        assert!(acroform.contains_key("DA"));
+        assert!(String::from_utf8_lossy(&bytes).contains("/DA (/Helv 12 Tf 0 g)"));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_merge_and_split_keep_form_fields() {
+        let dir = std::env::temp_dir();
+        let form = dir.join("pdfrs_merge_form_in.pdf");
+        let plain = dir.join("pdfrs_merge_form_plain.pdf");
+        let merged = dir.join("pdfrs_merge_form_out.pdf");
+        let split = dir.join("pdfrs_merge_form_split.pdf");
+        let text = FormField {
+            name: "name".to_string(),
+            field_type: FormFieldType::Text,
+            x: 100.0,
+            y: 600.0,
+            width: 200.0,
+            height: 20.0,
+            default_value: Some("Ada".to_string()),
+            options: vec![],
+            required: false,
+            page: Some(1),
+            flags: FieldFlags::NONE,
+        };
+        let radio = FormField {
+            name: "size".to_string(),
+            field_type: FormFieldType::Radio,
+            options: vec!["S".to_string(), "L".to_string()],
+            default_value: None,
+            page: Some(2),
+            flags: FieldFlags::NONE,
+            ..text.clone()
+        };
+        create_pdf_with_form_fields(form.to_str().unwrap(), "Page one\n\n\\pagebreak\n\nPage two", &[text, radio])
+            .unwrap();
+        crate::pdf_generator::create_pdf_from_elements_with_layout(
+            plain.to_str().unwrap(),
+            &[crate::elements::Element::Paragraph { text: "Plain".into() }],
+            "Helvetica",
+            12.0,
+            crate::pdf_generator::PageLayout::portrait(),
+        )
+        .unwrap();
+
+        let inputs = [form.to_str().unwrap(), plain.to_str().unwrap(), form.to_str().unwrap()];
+        merge_pdfs(&inputs, merged.to_str().unwrap()).unwrap();
+        let doc = crate::pdf::PdfDocument::load_from_file(merged.to_str().unwrap()).unwrap();
+        assert_eq!(doc.pages.len(), 5);
+
+        // Page index holding each field's first widget
+        let page_of = |doc: &crate::pdf::PdfDocument, field: u32| {
+            let widget = field_widgets(doc, field)[0];
+            doc.pages.iter().position(|&page| {
+                match doc.page_attribute(page, "Annots") {
+                    Some(crate::pdf::PdfObject::Array(items)) => {
+                        items.iter().any(|v| v.as_reference() == Some(widget))
+                    }
+                    _ => false,
+                }
+            })
+        };
+        let fields = doc.form_fields();
+        let summary: Vec<(&str, Option<&str>, Option<usize>)> = fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.field_type.as_deref(), page_of(&doc, f.id)))
+            .collect();
+        assert_eq!(summary, vec![
+            ("name", Some("Tx"), Some(0)),
+            ("size", Some("Btn"), Some(1)),
+            ("name_2", Some("Tx"), Some(3)),
+            ("size_2", Some("Btn"), Some(4)),
+        ]);
+        assert_eq!(fields[0].value.as_deref(), Some("Ada"));
+        // Radio buttons still point back at their field
+        let kid = field_widgets(&doc, fields[1].id)[0];
+        let kid_dict = doc.get_object(kid).and_then(|o| o.as_dict()).unwrap();
+        assert_eq!(kid_dict.get("Parent").and_then(|p| p.as_reference()), Some(fields[1].id));
+
+        split_pdf(merged.to_str().unwrap(), split.to_str().unwrap(), 5, 5).unwrap();
+        let doc = crate::pdf::PdfDocument::load_from_file(split.to_str().unwrap()).unwrap();
+        assert_eq!(doc.pages.len(), 1);
+        let names: Vec<String> = doc.form_fields().into_iter().map(|f| f.name).collect();
+        assert_eq!(names, vec!["size_2"]);
+
+        for path in [&form, &plain, &merged, &split] {
+            let _ = fs::remove_file(path);
+        }
+    }
+
     #[test]
     fn test_create_form_field_dict_dropdown() {
         let field = FormField {
@@ -2259,6 +7431,8 @@ mod tests {
             default_value: Some("USA".to_string()),
             options: vec!["USA".to_string(), "Canada".to_string(), "Mexico".to_string()],
             required: false,
+            page: None,
+            flags: FieldFlags::NONE,
         };
         let dict = create_form_field_dict(&field);
         assert!(dict.contains("/FT /Ch"));
@@ -2284,6 +7458,31 @@ mod tests {
         assert!(content.contains("0.707")); // cos(45°)
     }
 
+    #[test]
+    fn test_compressed_watermark_output_is_smaller() {
+        let text: String = (0..400).map(|i| format!("Line {} of a long report that spans pages.\n\n", i)).collect();
+        let input = std::env::temp_dir().join("pdfrs_watermark_large.pdf");
+        let plain = std::env::temp_dir().join("pdfrs_watermark_plain.pdf");
+        let packed = std::env::temp_dir().join("pdfrs_watermark_packed.pdf");
+        let (input, plain, packed) = (input.to_str().unwrap(), plain.to_str().unwrap(), packed.to_str().unwrap());
+        crate::pdf_generator::create_pdf(input, &text).unwrap();
+
+        watermark_pdf(input, plain, "DRAFT", 48.0, 0.3).unwrap();
+        let options = OutputOptions { compress: true, warn_above: Some(1) };
+        watermark_pdf_with_options(input, packed, "DRAFT", 48.0, 0.3, &options).unwrap();
+
+        let plain_size = fs::metadata(plain).unwrap().len();
+        let packed_size = fs::metadata(packed).unwrap().len();
+        assert!(packed_size < plain_size, "{} >= {}", packed_size, plain_size);
+        let doc = crate::pdf::PdfDocument::load_from_file(packed).unwrap();
+        let streams = extract_page_streams(&doc);
+        assert!(streams.len() > 1);
+        assert!(streams.iter().all(|s| String::from_utf8_lossy(s).contains("(DRAFT) Tj")));
+        for path in [input, plain, packed] {
+            let _ = fs::remove_file(path);
+        }
+    }
+
     #[test]
     fn test_watermark_position_variants() {
         // Test that all watermark position variants work
@@ -2296,12 +7495,23 @@ mod tests {
             WatermarkPosition::BottomLeft,
             WatermarkPosition::BottomRight,
             WatermarkPosition::Diagonal,
+            WatermarkPosition::Tiled { spacing: 150.0 },
         ] {
             let stream = build_text_watermark_stream("TEST", 24.0, 0.5, &layout, position);
             assert!(!stream.is_empty());
         }
     }
 
+    #[test]
+    fn test_tiled_watermark_repeats_text() {
+        let layout = crate::pdf_generator::PageLayout::portrait();
+        let stream = build_text_watermark_stream("CONFIDENTIAL", 72.0, 0.3, &layout, WatermarkPosition::Tiled { spacing: 150.0 });
+        let content = String::from_utf8_lossy(&stream);
+        assert!(content.matches("(CONFIDENTIAL) Tj").count() > 1);
+        assert!(content.contains("/F1 72 Tf"));
+        assert_eq!(content.matches("BT\n").count(), 1);
+    }
+
     #[test]
     fn test_image_watermark_stream() {
         let layout = crate::pdf_generator::PageLayout::portrait();
@@ -2312,6 +7522,7 @@ mod tests {
             data: vec![],
             bits_per_component: 8,
             color_components: 3,
+            inverted_cmyk: false,
             alt_text: None,
         };
 
@@ -2321,6 +7532,7 @@ mod tests {
         let stream = result.unwrap();
         let content = String::from_utf8_lossy(&stream);
         assert!(content.contains("/Im1 Do"));
+        assert!(content.contains("/GS0 gs\n"));
         assert!(content.contains("q\n"));
         assert!(content.contains("Q\n"));
     }
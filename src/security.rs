@@ -3,6 +3,8 @@
 //! This module provides password protection and permission management for PDF documents.
 
 use anyhow::{anyhow, Result};
+use aes::cipher::{generic_array::GenericArray, BlockDecrypt, BlockEncrypt, KeyInit};
+use md5::{Digest, Md5};
 
 /// PDF permission flags for controlling what operations are allowed
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -158,6 +160,16 @@ impl EncryptionAlgorithm {
         }
     }
 
+    /// Lowest PDF version, as (major, minor), that supports this algorithm
+    pub fn min_pdf_version(&self) -> (u8, u8) {
+        match self {
+            Self::Rc4_40 => (1, 3),
+            Self::Rc4_128 => (1, 4),
+            Self::Aes_128 => (1, 6),
+            Self::Aes_256 => (1, 7),
+        }
+    }
+
     /// Get the algorithm name as used in PDF
     pub fn name(&self) -> &str {
         match self {
@@ -214,6 +226,22 @@ impl PdfSecurity {
         self
     }
 
+    /// Restrict `permissions` behind `owner_password` alone: the user password
+    /// is empty, so the document opens without a prompt
+    pub fn owner_only(owner_password: String, permissions: PdfPermissions) -> Self {
+        Self {
+            user_password: Some(String::new()),
+            owner_password: Some(owner_password),
+            permissions,
+            ..Self::default()
+        }
+    }
+
+    /// Whether the document opens without asking for a password
+    pub fn opens_without_password(&self) -> bool {
+        self.user_password.as_deref().unwrap_or("").is_empty()
+    }
+
     /// Set the encryption algorithm
     pub fn with_encryption(mut self, algorithm: EncryptionAlgorithm) -> Self {
         self.encryption_algorithm = algorithm;
@@ -239,7 +267,8 @@ impl PdfSecurity {
 
     /// Validate password settings
     pub fn validate(&self) -> Result<()> {
-        if self.user_password.is_some() && self.user_password.as_ref().unwrap().is_empty() {
+        // An empty user password only makes sense for an owner-only lock
+        if self.user_password.is_some() && self.user_password.as_ref().unwrap().is_empty() && self.owner_password.is_none() {
             return Err(anyhow!("User password cannot be empty"));
         }
         if self.owner_password.is_some() && self.owner_password.as_ref().unwrap().is_empty() {
@@ -296,13 +325,67 @@ impl PdfSecurity {
 
     /// Create the encryption dictionary for the PDF trailer
     pub fn create_encryption_dict(&self) -> String {
+        self.encryption_dict_for(&[])
+    }
+
+    /// `/R` value of the standard security handler for the configured algorithm
+    fn revision(&self) -> u32 {
+        match self.encryption_algorithm {
+            EncryptionAlgorithm::Aes_256 => 5,
+            EncryptionAlgorithm::Aes_128 => 4,
+            _ => 3,
+        }
+    }
+
+    /// `/O` and `/U` entries for a document whose first `/ID` string is `file_id`
+    /// (PDF 1.7 algorithms 3 and 5). `None` for AES-256, which uses a different scheme.
+    pub fn password_entries(&self, file_id: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
+        let encryption = self.standard_encryption(file_id)?;
+        Some((encryption.owner_entry, encryption.user_entry))
+    }
+
+    /// The security handler entries these settings produce for `file_id`.
+    /// `None` for AES-256, which uses a different scheme.
+    pub(crate) fn standard_encryption(&self, file_id: &[u8]) -> Option<StandardEncryption> {
+        if self.revision() > 4 {
+            return None;
+        }
+        let user = self.user_password.as_deref().unwrap_or("");
+        let owner = self.owner_password.as_deref().unwrap_or(user);
+        let key_length = self.encryption_algorithm.key_length();
+
+        let mut owner_key = Md5::digest(pad_password(owner)).to_vec();
+        for _ in 0..50 {
+            owner_key = Md5::digest(&owner_key[..key_length]).to_vec();
+        }
+        owner_key.truncate(key_length);
+        let owner_entry = rc4_rounds(&owner_key, &pad_password(user));
+
+        let mut encryption = StandardEncryption {
+            revision: self.revision(),
+            key_length,
+            permissions: self.permissions.to_pdf_flags(),
+            owner_entry,
+            user_entry: Vec::new(),
+            encrypt_metadata: self.encrypt_metadata,
+            aes: self.encryption_algorithm == EncryptionAlgorithm::Aes_128,
+        };
+        encryption.user_entry = encryption.user_entry(user, file_id);
+        Some(encryption)
+    }
+
+    /// The encryption dictionary with `/O` and `/U` computed for `file_id`
+    pub fn encryption_dict_for(&self, file_id: &[u8]) -> String {
         if !self.is_protected() {
             return String::new();
         }
 
-        let algorithm = self.encryption_algorithm.name();
         let key_length = self.encryption_algorithm.key_length() * 8;
         let flags = self.permissions.to_pdf_flags();
+        let (owner_entry, user_entry) = match self.password_entries(file_id) {
+            Some((owner, user)) => (to_hex(&owner), to_hex(&user)),
+            None => ("OWNER_PASSWORD_PLACEHOLDER".to_string(), "USER_PASSWORD_PLACEHOLDER".to_string()),
+        };
 
         format!(
             "<< /Filter /Standard \
@@ -311,8 +394,8 @@ impl PdfSecurity {
                /Length {} \
                /P {} \
                /EncryptMetadata {} \
-               /O <OWNER_PASSWORD_PLACEHOLDER> \
-               /U <USER_PASSWORD_PLACEHOLDER> >>",
+               /O <{}> \
+               /U <{}>{} >>",
             if self.encryption_algorithm == EncryptionAlgorithm::Aes_256 {
                 "5"
             } else if self.encryption_algorithm == EncryptionAlgorithm::Aes_128 {
@@ -329,11 +412,227 @@ impl PdfSecurity {
             },
             key_length,
             flags,
-            if self.encrypt_metadata { "true" } else { "false" }
+            if self.encrypt_metadata { "true" } else { "false" },
+            owner_entry,
+            user_entry,
+            if self.encryption_algorithm == EncryptionAlgorithm::Aes_128 {
+                " /CF << /StdCF << /AuthEvent /DocOpen /CFM /AESV2 /Length 16 >> >> /StmF /StdCF /StrF /StdCF"
+            } else {
+                ""
+            }
         )
     }
 }
 
+/// Padding appended to passwords before hashing (PDF 1.7, 7.6.3.3)
+const PASSWORD_PADDING: [u8; 32] = [
+    0x28, 0xBF, 0x4E, 0x5E, 0x4E, 0x75, 0x8A, 0x41, 0x64, 0x00, 0x4E, 0x56, 0xFF, 0xFA, 0x01, 0x08,
+    0x2E, 0x2E, 0x00, 0xB6, 0xD0, 0x68, 0x3E, 0x80, 0x2F, 0x0C, 0xA9, 0xFE, 0x64, 0x53, 0x69, 0x7A,
+];
+
+/// The first 32 bytes of `password`, filled up with [`PASSWORD_PADDING`]
+fn pad_password(password: &str) -> [u8; 32] {
+    let mut padded = PASSWORD_PADDING;
+    let bytes = password.as_bytes();
+    let n = bytes.len().min(32);
+    padded[..n].copy_from_slice(&bytes[..n]);
+    padded[n..].copy_from_slice(&PASSWORD_PADDING[..32 - n]);
+    padded
+}
+
+fn rc4(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut state: Vec<u8> = (0..=255).collect();
+    let mut j = 0u8;
+    for i in 0..256 {
+        j = j.wrapping_add(state[i]).wrapping_add(key[i % key.len()]);
+        state.swap(i, j as usize);
+    }
+    let (mut i, mut j) = (0u8, 0u8);
+    data.iter()
+        .map(|&b| {
+            i = i.wrapping_add(1);
+            j = j.wrapping_add(state[i as usize]);
+            state.swap(i as usize, j as usize);
+            b ^ state[state[i as usize].wrapping_add(state[j as usize]) as usize]
+        })
+        .collect()
+}
+
+/// RC4 with `key`, then 19 more passes with each key byte XORed with the pass number
+fn rc4_rounds(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut out = rc4(key, data);
+    for round in 1..=19u8 {
+        let round_key: Vec<u8> = key.iter().map(|k| k ^ round).collect();
+        out = rc4(&round_key, &out);
+    }
+    out
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+/// Password-related entries of a standard security handler `/Encrypt` dictionary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StandardEncryption {
+    /// `/R`
+    pub revision: u32,
+    /// Key length in bytes
+    pub key_length: usize,
+    /// `/P` permission flags
+    pub permissions: u32,
+    /// `/O`
+    pub owner_entry: Vec<u8>,
+    /// `/U`
+    pub user_entry: Vec<u8>,
+    pub encrypt_metadata: bool,
+    /// Strings and streams use the AESV2 crypt filter instead of RC4
+    pub aes: bool,
+}
+
+impl StandardEncryption {
+    /// Read the entries from the text of an `/Encrypt` dictionary. `/O` and `/U`
+    /// must be hex strings, as [`PdfSecurity::encryption_dict_for`] writes them.
+    pub fn parse(dict: &str) -> Option<Self> {
+        let number = |key: &str| -> Option<i64> {
+            let re = regex::Regex::new(&format!(r"/{}\s+(-?\d+)", key)).ok()?;
+            re.captures(dict)?[1].parse().ok()
+        };
+        let hex = |key: &str| -> Option<Vec<u8>> {
+            let re = regex::Regex::new(&format!(r"/{}\s*<([0-9A-Fa-f\s]*)>", key)).ok()?;
+            let digits: String = re.captures(dict)?[1].split_whitespace().collect();
+            crate::compression::decode_hex_string(&digits).ok()
+        };
+        // The crypt filter's own /Length may come first; AESV2 keys are always 16 bytes
+        let aes = dict.contains("/AESV2");
+        Some(StandardEncryption {
+            revision: number("R")? as u32,
+            key_length: if aes { 16 } else { number("Length").map_or(5, |bits| bits as usize / 8) },
+            permissions: number("P")? as u32,
+            owner_entry: hex("O")?,
+            user_entry: hex("U")?,
+            encrypt_metadata: !dict.contains("/EncryptMetadata false"),
+            aes,
+        })
+    }
+
+    /// Encryption key derived from the user password (PDF 1.7 algorithm 2, revision 3+)
+    pub(crate) fn file_key(&self, user_password: &str, file_id: &[u8]) -> Vec<u8> {
+        let mut hasher = Md5::new();
+        hasher.update(pad_password(user_password));
+        hasher.update(&self.owner_entry);
+        hasher.update(self.permissions.to_le_bytes());
+        hasher.update(file_id);
+        if !self.encrypt_metadata {
+            hasher.update([0xFF; 4]);
+        }
+        let mut key = hasher.finalize().to_vec();
+        for _ in 0..50 {
+            key = Md5::digest(&key[..self.key_length]).to_vec();
+        }
+        key.truncate(self.key_length);
+        key
+    }
+
+    /// `/U` entry for `user_password` (PDF 1.7 algorithm 5)
+    fn user_entry(&self, user_password: &str, file_id: &[u8]) -> Vec<u8> {
+        let mut hasher = Md5::new();
+        hasher.update(PASSWORD_PADDING);
+        hasher.update(file_id);
+        let mut entry = rc4_rounds(&self.file_key(user_password, file_id), &hasher.finalize());
+        entry.resize(32, 0);
+        entry
+    }
+
+    /// Whether `password` is the user (open) password (PDF 1.7 algorithm 6).
+    /// Only revisions 3 and 4 are supported.
+    pub fn authenticate_user(&self, password: &str, file_id: &[u8]) -> bool {
+        (3..=4).contains(&self.revision)
+            && self.user_entry.len() >= 16
+            && self.user_entry(password, file_id)[..16] == self.user_entry[..16]
+    }
+
+    /// The cipher for strings and streams, given the user password
+    pub(crate) fn cipher(&self, user_password: &str, file_id: &[u8]) -> ObjectCipher {
+        ObjectCipher { file_key: self.file_key(user_password, file_id), aes: self.aes }
+    }
+}
+
+/// Encrypts and decrypts the strings and streams of one document, each with a
+/// key derived from its object number and generation (PDF 1.7 algorithm 1)
+pub(crate) struct ObjectCipher {
+    file_key: Vec<u8>,
+    aes: bool,
+}
+
+impl ObjectCipher {
+    fn object_key(&self, id: u32, generation: u16) -> Vec<u8> {
+        let mut hasher = Md5::new();
+        hasher.update(&self.file_key);
+        hasher.update(&id.to_le_bytes()[..3]);
+        hasher.update(generation.to_le_bytes());
+        if self.aes {
+            hasher.update(b"sAlT");
+        }
+        let digest = hasher.finalize();
+        digest[..(self.file_key.len() + 5).min(16)].to_vec()
+    }
+
+    /// `data` of object `id` encrypted. AES output starts with its 16-byte IV.
+    pub(crate) fn encrypt(&self, id: u32, generation: u16, data: &[u8]) -> Vec<u8> {
+        let key = self.object_key(id, generation);
+        if !self.aes {
+            return rc4(&key, data);
+        }
+        // A repeatable IV keeps output deterministic for the same input
+        let mut hasher = Md5::new();
+        hasher.update(&key);
+        hasher.update(data);
+        let iv = hasher.finalize();
+
+        let cipher = aes::Aes128::new(GenericArray::from_slice(&key));
+        let pad = 16 - data.len() % 16;
+        let mut padded = data.to_vec();
+        padded.resize(data.len() + pad, pad as u8);
+        let mut out = iv.to_vec();
+        let mut previous: [u8; 16] = iv.into();
+        for chunk in padded.chunks(16) {
+            let mut block = GenericArray::clone_from_slice(chunk);
+            block.iter_mut().zip(previous).for_each(|(b, p)| *b ^= p);
+            cipher.encrypt_block(&mut block);
+            previous = block.into();
+            out.extend_from_slice(&previous);
+        }
+        out
+    }
+
+    /// `data` of object `id` decrypted. AES data that is not a whole number of
+    /// blocks or is wrongly padded comes back unchanged.
+    pub(crate) fn decrypt(&self, id: u32, generation: u16, data: &[u8]) -> Vec<u8> {
+        let key = self.object_key(id, generation);
+        if !self.aes {
+            return rc4(&key, data);
+        }
+        if data.len() < 32 || !data.len().is_multiple_of(16) {
+            return data.to_vec();
+        }
+        let cipher = aes::Aes128::new(GenericArray::from_slice(&key));
+        let mut out = Vec::with_capacity(data.len() - 16);
+        for pair in data.windows(32).step_by(16) {
+            let mut block = GenericArray::clone_from_slice(&pair[16..]);
+            cipher.decrypt_block(&mut block);
+            out.extend(block.iter().zip(&pair[..16]).map(|(b, p)| b ^ p));
+        }
+        match out.last().copied() {
+            Some(pad @ 1..=16) if out[out.len() - pad as usize..].iter().all(|&b| b == pad) => {
+                out.truncate(out.len() - pad as usize);
+                out
+            }
+            _ => data.to_vec(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -420,6 +719,15 @@ mod tests {
         assert!(security.validate().is_err());
     }
 
+    #[test]
+    fn test_security_owner_only() {
+        let security = PdfSecurity::owner_only("owner".to_string(), PdfPermissions::read_only());
+        assert!(security.is_protected());
+        assert!(security.opens_without_password());
+        assert!(security.validate().is_ok());
+        assert!(!PdfSecurity::new().with_user_password("user".to_string()).opens_without_password());
+    }
+
     #[test]
     fn test_security_read_only() {
         let perms = PdfPermissions::read_only();
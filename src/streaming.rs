@@ -104,7 +104,7 @@ impl StreamingPdfGenerator {
     pub fn set_color(&mut self, color: Color) -> Result<()> {
         self.current_color = color;
         self.current_page.extend_from_slice(
-            format!("{} {} {} rg\n", color.r, color.g, color.b).as_bytes()
+            format!("{}\n", color.fill_operator()).as_bytes()
         );
         Ok(())
     }
@@ -155,7 +155,7 @@ impl StreamingPdfGenerator {
     pub fn add_rich_paragraph(&mut self, segments: &[TextSegment]) -> Result<()> {
         for segment in segments {
             match segment {
-                TextSegment::Plain(text) => {
+                TextSegment::Plain(text) | TextSegment::Highlight(text, _) | TextSegment::Styled(text, _) => {
                     self.set_font("Helvetica", self.base_font_size);
                     self.write_text(text)?;
                 }
@@ -178,7 +178,11 @@ impl StreamingPdfGenerator {
                 }
                 TextSegment::Link { text, url } => {
                     self.set_font("Helvetica", self.base_font_size);
-                    self.write_text(&format!("{} ({})", text, url))?;
+                    self.write_text(&crate::pdf_generator::link_label(text, url))?;
+                }
+                TextSegment::Superscript(text) | TextSegment::Subscript(text) => {
+                    self.set_font("Helvetica", self.base_font_size * 0.7)?;
+                    self.write_text(text)?;
                 }
             }
         }
@@ -292,6 +296,7 @@ impl StreamingPdfGenerator {
                 "<< /Type /Page\n\
                  /Parent {} 0 R\n\
                  /MediaBox [0 0 {} {}]\n\
+                 {}\
                  /Contents {} 0 R\n\
                  /Resources << /Font << \
                      /Helvetica {} 0 R \
@@ -304,6 +309,7 @@ impl StreamingPdfGenerator {
                 pages_obj_id,
                 self.layout.width,
                 self.layout.height,
+                self.layout.crop_box_entry(),
                 content_id,
                 first_font_id,
                 first_font_id + 1,
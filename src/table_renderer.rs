@@ -3,11 +3,11 @@
 //! This module provides a trait-based, modular approach to rendering tables in PDFs.
 //! It follows the Strategy pattern for different table rendering approaches.
 
-use crate::elements::TableAlignment;
+use crate::elements::{TableAlignment, TextSegment};
 use anyhow::Result;
 
 /// Configuration for table styling
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct TableStyle {
     /// Padding inside each cell (in points)
     pub cell_padding: f32,
@@ -23,6 +23,26 @@ pub struct TableStyle {
     pub border_color: (f32, f32, f32),
     /// Inner grid line color (RGB 0-1)
     pub grid_color: (f32, f32, f32),
+    /// Right-align columns whose data cells are all numbers, unless the
+    /// Markdown separator row sets the column's alignment
+    pub auto_align_numeric: bool,
+    /// Per-column width hints; missing entries are treated as [`ColumnWidth::Auto`]
+    pub column_widths: Option<Vec<ColumnWidth>>,
+    /// Columns laid out per row; cells past the limit are dropped
+    pub max_columns: usize,
+    /// Rows laid out per table; later rows are dropped
+    pub max_rows: usize,
+}
+
+/// Width hint for a single table column
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnWidth {
+    /// Sized to fit the widest cell, shrunk to share the remaining space
+    Auto,
+    /// A fixed width in points
+    Fixed(f32),
+    /// A fraction (0-1) of the available table width
+    Fraction(f32),
 }
 
 impl Default for TableStyle {
@@ -35,6 +55,10 @@ impl Default for TableStyle {
             grid_line_width: 0.75,
             border_color: (0.0, 0.0, 0.0),
             grid_color: (0.75, 0.75, 0.75),
+            auto_align_numeric: false,
+            column_widths: None,
+            max_columns: 64,
+            max_rows: 10_000,
         }
     }
 }
@@ -42,13 +66,24 @@ impl Default for TableStyle {
 /// Represents a single table cell with its content and alignment
 #[derive(Debug, Clone)]
 pub struct TableCell {
+    /// Plain text of the cell
     pub content: String,
     pub alignment: TableAlignment,
+    /// Styled runs making up the cell
+    pub segments: Vec<TextSegment>,
 }
 
 impl TableCell {
     pub fn new(content: String, alignment: TableAlignment) -> Self {
-        Self { content, alignment }
+        let segments = vec![TextSegment::Plain(content.clone())];
+        Self { content, alignment, segments }
+    }
+
+    /// A cell whose Markdown inline formatting (`**bold**`, `` `code` ``, ...) is kept as styled runs
+    pub fn rich(source: &str, alignment: TableAlignment) -> Self {
+        let segments = crate::elements::parse_inline_formatting(source);
+        let content = segments.iter().map(|s| s.text()).collect();
+        Self { content, alignment, segments }
     }
 
     pub fn left(content: &str) -> Self {
@@ -125,8 +160,64 @@ pub trait TableRenderer {
         max_width: f32,
     ) -> TableDimensions;
 
-    /// Wrap text into lines based on available width
-    fn wrap_text(&self, text: &str, max_chars: usize) -> WrappedLines;
+    /// Wrap text into lines no wider than `max_width`, as measured by `measure`
+    fn wrap_text(&self, text: &str, max_width: f32, measure: &dyn Fn(&str) -> f32) -> WrappedLines;
+
+    /// Wrap styled runs into lines no wider than `max_width`, breaking only at spaces.
+    /// `measure` gives the width of a run in its own font.
+    fn wrap_segments(
+        &self,
+        segments: &[TextSegment],
+        max_width: f32,
+        measure: &dyn Fn(&TextSegment) -> f32,
+    ) -> Vec<Vec<TextSegment>> {
+        // Words are runs of pieces not separated by whitespace, e.g. `**foo**bar`
+        let mut words: Vec<Vec<TextSegment>> = Vec::new();
+        let mut space_pending = true;
+        for segment in segments {
+            let text = segment.text();
+            for (i, piece) in text.split_whitespace().enumerate() {
+                let glued = i == 0 && !space_pending && !text.starts_with(char::is_whitespace);
+                match words.last_mut() {
+                    Some(word) if glued => word.push(segment.with_text(piece.to_string())),
+                    _ => words.push(vec![segment.with_text(piece.to_string())]),
+                }
+            }
+            if !text.is_empty() {
+                space_pending = text.ends_with(char::is_whitespace);
+            }
+        }
+
+        let mut lines: Vec<Vec<TextSegment>> = Vec::new();
+        let mut line: Vec<TextSegment> = Vec::new();
+        let mut line_width = 0.0;
+        for word in words {
+            let word_width: f32 = word.iter().map(measure).sum();
+            let space = line.last().map(|last: &TextSegment| last.with_text(" ".to_string()));
+            let space_width = space.as_ref().map(measure).unwrap_or(0.0);
+            if !line.is_empty() && line_width + space_width + word_width > max_width {
+                lines.push(std::mem::take(&mut line));
+                line_width = 0.0;
+            } else if let Some(last) = line.last_mut() {
+                *last = last.with_text(format!("{} ", last.text()));
+                line_width += space_width;
+            }
+            for piece in word {
+                match line.last_mut() {
+                    // Same style (and link target): extend the run
+                    Some(last) if last.with_text(String::new()) == piece.with_text(String::new()) => {
+                        *last = last.with_text(format!("{}{}", last.text(), piece.text()));
+                    }
+                    _ => line.push(piece),
+                }
+            }
+            line_width += word_width;
+        }
+        if !line.is_empty() || lines.is_empty() {
+            lines.push(line);
+        }
+        lines
+    }
 
     /// Calculate the X position for text based on alignment
     fn calculate_text_x(
@@ -161,9 +252,11 @@ impl TableRenderer for DefaultTableRenderer {
             };
         }
 
-        let num_cols = rows.iter().map(|r| r.cells.len()).max().unwrap_or(0);
+        // Malformed or hostile tables are cut to the style's limits before anything is allocated per cell
+        let rows = &rows[..rows.len().min(style.max_rows)];
+        let num_cols = rows.iter().map(|r| r.cells.len()).max().unwrap_or(0).min(style.max_columns);
         let num_rows = rows.len();
-        let approx_char_width = base_font_size * 0.5;
+        let measure = |segment: &TextSegment| segment_width(segment, base_font_size);
         let line_h = base_font_size * 1.4;
 
         // Calculate column widths
@@ -171,18 +264,47 @@ impl TableRenderer for DefaultTableRenderer {
         for row in rows {
             for (col_idx, cell) in row.cells.iter().enumerate() {
                 if col_idx < num_cols {
-                    let cell_width = cell.content.len() as f32 * approx_char_width + style.cell_padding * 2.0;
+                    let text_width: f32 = cell.segments.iter().map(measure).sum();
+                    let cell_width = text_width + style.cell_padding * 2.0;
                     col_widths[col_idx] = col_widths[col_idx].max(cell_width);
                 }
             }
         }
 
-        // Scale to fit max width
-        let total_width: f32 = col_widths.iter().sum();
-        if total_width > max_width {
-            let scale = max_width / total_width;
-            for width in &mut col_widths {
-                *width *= scale;
+        // Apply width hints; auto columns share whatever space is left
+        let hints: Vec<ColumnWidth> = (0..num_cols)
+            .map(|col| {
+                style
+                    .column_widths
+                    .as_ref()
+                    .and_then(|hints| hints.get(col).copied())
+                    .unwrap_or(ColumnWidth::Auto)
+            })
+            .collect();
+        let mut reserved = 0.0;
+        for (width, hint) in col_widths.iter_mut().zip(&hints) {
+            match *hint {
+                ColumnWidth::Fixed(pt) => *width = pt.max(0.0),
+                ColumnWidth::Fraction(fraction) => *width = max_width * fraction.clamp(0.0, 1.0),
+                ColumnWidth::Auto => continue,
+            }
+            reserved += *width;
+        }
+
+        // Scale auto columns to fit max width
+        let auto_width: f32 = col_widths
+            .iter()
+            .zip(&hints)
+            .filter(|(_, hint)| **hint == ColumnWidth::Auto)
+            .map(|(width, _)| width)
+            .sum();
+        let available = (max_width - reserved).max(0.0);
+        if auto_width > available {
+            let scale = available / auto_width;
+            for (width, hint) in col_widths.iter_mut().zip(&hints) {
+                if *hint == ColumnWidth::Auto {
+                    *width *= scale;
+                }
             }
         }
 
@@ -192,9 +314,9 @@ impl TableRenderer for DefaultTableRenderer {
             let mut max_lines = 1;
             for (col_idx, cell) in row.cells.iter().enumerate() {
                 if col_idx >= num_cols { break; }
-                let max_chars = ((col_widths[col_idx] - style.cell_padding * 2.0) / approx_char_width).floor().max(1.0) as usize;
-                let wrapped = self.wrap_text(&cell.content, max_chars);
-                max_lines = max_lines.max(wrapped.line_count);
+                let max_width = col_widths[col_idx] - style.cell_padding * 2.0;
+                let wrapped = self.wrap_segments(&cell.segments, max_width, &measure);
+                max_lines = max_lines.max(wrapped.len());
             }
             row_heights[row_idx] = max_lines as f32 * line_h + style.cell_padding * 2.0;
         }
@@ -212,38 +334,26 @@ impl TableRenderer for DefaultTableRenderer {
         }
     }
 
-    fn wrap_text(&self, text: &str, max_chars: usize) -> WrappedLines {
-        if text.len() <= max_chars {
+    fn wrap_text(&self, text: &str, max_width: f32, measure: &dyn Fn(&str) -> f32) -> WrappedLines {
+        if measure(text) <= max_width {
             return WrappedLines::new(vec![text.to_string()]);
         }
 
         let words: Vec<&str> = text.split_whitespace().collect();
         let mut lines = Vec::new();
         let mut current_line = String::new();
-        let mut current_len = 0;
 
         for word in words {
-            let new_len = if current_len == 0 {
-                word.len()
-            } else {
-                current_len + 1 + word.len()
-            };
+            if current_line.is_empty() {
+                current_line = word.to_string();
+                continue;
+            }
 
-            if new_len <= max_chars {
-                if current_len == 0 {
-                    current_line = word.to_string();
-                    current_len = word.len();
-                } else {
-                    current_line.push(' ');
-                    current_line.push_str(word);
-                    current_len = new_len;
-                }
+            let candidate = format!("{} {}", current_line, word);
+            if measure(&candidate) <= max_width {
+                current_line = candidate;
             } else {
-                if !current_line.is_empty() {
-                    lines.push(current_line);
-                }
-                current_line = word.to_string();
-                current_len = word.len();
+                lines.push(std::mem::replace(&mut current_line, word.to_string()));
             }
         }
 
@@ -274,6 +384,47 @@ impl TableRenderer for DefaultTableRenderer {
     }
 }
 
+/// Font a styled run is drawn with in a table cell
+pub fn segment_font(segment: &TextSegment) -> &'static str {
+    match segment {
+        TextSegment::Bold(_) => "Helvetica-Bold",
+        TextSegment::Italic(_) => "Helvetica-Oblique",
+        TextSegment::BoldItalic(_) => "Helvetica-BoldOblique",
+        TextSegment::Code(_) => "Courier",
+        _ => "Helvetica",
+    }
+}
+
+/// Width of a styled run at `font_size` (code runs are drawn at 90%)
+pub fn segment_width(segment: &TextSegment, font_size: f32) -> f32 {
+    let size = if matches!(segment, TextSegment::Code(_)) { font_size * 0.9 } else { font_size };
+    crate::font_metrics::text_width(segment_font(segment), segment.text(), size)
+}
+
+/// Whether `text` is a number such as `42`, `-3.5`, `1,234.50`, `$99` or `12%`.
+/// Thousands separators must group digits by three.
+pub fn is_numeric_cell(text: &str) -> bool {
+    let text = text.trim();
+    let text = text.strip_prefix(['-', '+', '\u{2212}']).unwrap_or(text);
+    let text = text.strip_prefix(['$', '\u{20AC}', '\u{A3}', '\u{A5}']).unwrap_or(text);
+    let text = text.strip_suffix('%').unwrap_or(text);
+    let (integer, fraction) = match text.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (text, None),
+    };
+    if let Some(fraction) = fraction
+        && (fraction.is_empty() || !fraction.chars().all(|c| c.is_ascii_digit()))
+    {
+        return false;
+    }
+    if integer.is_empty() {
+        return fraction.is_some();
+    }
+    let groups: Vec<&str> = integer.split(',').collect();
+    groups.iter().all(|g| !g.is_empty() && g.chars().all(|c| c.is_ascii_digit()))
+        && (groups.len() == 1 || (groups[0].len() <= 3 && groups[1..].iter().all(|g| g.len() == 3)))
+}
+
 impl Default for DefaultTableRenderer {
     fn default() -> Self {
         Self
@@ -315,12 +466,36 @@ impl PdfTableHelper {
                     .and_then(|a| a.get(col_idx))
                     .copied()
                     .unwrap_or(TableAlignment::Left);
-                TableCell::new(cell.clone(), alignment)
+                TableCell::rich(cell, alignment)
             }).collect();
             TableRow { cells }
         }).collect()
     }
 
+    /// Right-align every column whose cells below the header row are all numbers.
+    /// Columns marked in `explicit` keep their alignment.
+    pub fn align_numeric_columns(&self, rows: &mut [TableRow], explicit: &[bool]) {
+        let num_cols = rows.iter().map(|row| row.cells.len()).max().unwrap_or(0);
+        for col in 0..num_cols {
+            if explicit.get(col).copied().unwrap_or(false) {
+                continue;
+            }
+            let mut values = rows
+                .iter()
+                .skip(1)
+                .filter_map(|row| row.cells.get(col))
+                .map(|cell| cell.content.trim())
+                .filter(|text| !text.is_empty())
+                .peekable();
+            if values.peek().is_none() || !values.all(is_numeric_cell) {
+                continue;
+            }
+            for cell in rows.iter_mut().filter_map(|row| row.cells.get_mut(col)) {
+                cell.alignment = TableAlignment::Right;
+            }
+        }
+    }
+
     /// Escape special PDF string characters (public static helper)
     pub fn escape_pdf_string_static(text: &str) -> String {
         text.replace('\\', "\\\\")
@@ -361,21 +536,50 @@ mod tests {
         assert_eq!(row.cells[0].content, "A");
     }
 
+    fn char_count(text: &str) -> f32 {
+        text.chars().count() as f32
+    }
+
     #[test]
     fn test_text_wrapping() {
         let renderer = DefaultTableRenderer;
-        let wrapped = renderer.wrap_text("hello world test", 10);
+        let wrapped = renderer.wrap_text("hello world test", 10.0, &char_count);
         assert!(wrapped.line_count > 1);
     }
 
     #[test]
     fn test_text_wrapping_single_word() {
         let renderer = DefaultTableRenderer;
-        let wrapped = renderer.wrap_text("hello", 10);
+        let wrapped = renderer.wrap_text("hello", 10.0, &char_count);
         assert_eq!(wrapped.line_count, 1);
         assert_eq!(wrapped.lines[0], "hello");
     }
 
+    #[test]
+    fn test_text_wrapping_multibyte_visual_width() {
+        let renderer = DefaultTableRenderer;
+        let measure = |t: &str| crate::font_metrics::text_width("Helvetica", t, 12.0);
+        // Exactly wide enough for two words; byte counting would see "é" as two characters
+        let max_width = measure("café café");
+        let wrapped = renderer.wrap_text("café café café", max_width, &measure);
+        assert_eq!(wrapped.lines, vec!["café café".to_string(), "café".to_string()]);
+
+        let wide = renderer.wrap_text("résumé draft", measure("resume draft"), &measure);
+        assert_eq!(wide.line_count, 1);
+    }
+
+    #[test]
+    fn test_wrap_segments_keeps_styles() {
+        let renderer = DefaultTableRenderer;
+        let segments = crate::elements::parse_inline_formatting("see **bold text**, then more");
+        let measure = |s: &TextSegment| s.text().chars().count() as f32;
+        let lines = renderer.wrap_segments(&segments, 12.0, &measure);
+        assert_eq!(lines[0], vec![TextSegment::Plain("see ".into()), TextSegment::Bold("bold".into())]);
+        // The comma is glued to the bold word, so they stay on one line
+        assert_eq!(lines[1], vec![TextSegment::Bold("text".into()), TextSegment::Plain(", then".into())]);
+        assert_eq!(lines[2], vec![TextSegment::Plain("more".into())]);
+    }
+
     #[test]
     fn test_calculate_text_x_left() {
         let renderer = DefaultTableRenderer;
@@ -405,6 +609,54 @@ mod tests {
         assert_eq!(dims.num_rows, 0);
     }
 
+    #[test]
+    fn test_fixed_column_width_ignores_content() {
+        let renderer = DefaultTableRenderer;
+        let style = TableStyle {
+            column_widths: Some(vec![ColumnWidth::Fixed(100.0)]),
+            ..TableStyle::default()
+        };
+        for first in ["x", "a much longer first cell than one hundred points could hold"] {
+            let rows = vec![TableRow::from_strings(&[first, "second"])];
+            let dims = renderer.calculate_dimensions(&rows, &style, 12.0, 400.0);
+            assert_eq!(dims.column_widths[0], 100.0);
+            assert!(dims.column_widths[1] > 0.0);
+            assert!(dims.total_width <= 400.0);
+        }
+
+        let style = TableStyle {
+            column_widths: Some(vec![ColumnWidth::Auto, ColumnWidth::Fraction(0.5)]),
+            ..TableStyle::default()
+        };
+        let rows = vec![TableRow::from_strings(&["a", "b"])];
+        let dims = renderer.calculate_dimensions(&rows, &style, 12.0, 400.0);
+        assert_eq!(dims.column_widths[1], 200.0);
+    }
+
+    #[test]
+    fn test_huge_table_is_clamped() {
+        let renderer = DefaultTableRenderer;
+        let style = TableStyle::default();
+        let cells = vec!["x"; 100_000];
+        let rows = vec![TableRow::from_strings(&cells); 3];
+        let dims = renderer.calculate_dimensions(&rows, &style, 12.0, 400.0);
+        assert_eq!(dims.num_cols, style.max_columns);
+        assert_eq!(dims.column_widths.len(), style.max_columns);
+
+        let style = TableStyle { max_rows: 2, ..style };
+        let dims = renderer.calculate_dimensions(&rows, &style, 12.0, 400.0);
+        assert_eq!((dims.num_rows, dims.row_heights.len()), (2, 2));
+
+        // Rendering a Markdown table declaring 100,000 columns stays within the limit
+        let markdown = format!("|{}\n|{}", " a |".repeat(100_000), "---|".repeat(100_000));
+        let elements = crate::elements::parse_markdown(&markdown);
+        let bytes =
+            crate::pdf_generator::generate_pdf_bytes(&elements, "Helvetica", 12.0, crate::pdf_generator::PageLayout::portrait())
+                .unwrap();
+        let raw = String::from_utf8_lossy(&bytes);
+        assert_eq!(raw.matches("(a) Tj").count(), TableStyle::default().max_columns);
+    }
+
     #[test]
     fn test_escape_pdf_string() {
         let helper = PdfTableHelper::default();
@@ -412,6 +664,27 @@ mod tests {
         assert_eq!(escaped, "test\\(string\\)");
     }
 
+    #[test]
+    fn test_numeric_columns_right_aligned() {
+        let helper = PdfTableHelper::default();
+        let rows: Vec<Vec<String>> = [["Name", "Age"], ["Ann", "25"], ["Bob", "30"], ["Cy", "100"]]
+            .iter()
+            .map(|row| row.iter().map(|c| c.to_string()).collect())
+            .collect();
+        let mut table = helper.convert_rows(&rows, None);
+        helper.align_numeric_columns(&mut table, &[]);
+        assert!(table.iter().all(|row| row.cells[0].alignment == TableAlignment::Left));
+        assert!(table.iter().all(|row| row.cells[1].alignment == TableAlignment::Right));
+
+        // An explicit separator alignment wins
+        let mut table = helper.convert_rows(&rows, Some(&[TableAlignment::Left, TableAlignment::Center]));
+        helper.align_numeric_columns(&mut table, &[false, true]);
+        assert_eq!(table[1].cells[1].alignment, TableAlignment::Center);
+
+        assert!(is_numeric_cell("1,234.50") && is_numeric_cell("-$9") && is_numeric_cell("12%"));
+        assert!(!is_numeric_cell("12,34") && !is_numeric_cell("1.2.3") && !is_numeric_cell("n/a"));
+    }
+
     #[test]
     fn test_table_style_default() {
         let style = TableStyle::default();
@@ -0,0 +1,537 @@
+//! TrueType fonts embedded as simple WinAnsi-encoded fonts.
+//!
+//! Only the tables a PDF font dictionary needs are read: `head` for the units
+//! per em and bounding box, `hhea`/`hmtx` for advance widths and the format 4
+//! Unicode `cmap`, plus `name`, `post` and `OS/2` when present. The font file
+//! is embedded whole as `/FontFile2`.
+//!
+//! Fonts whose `GSUB` table has `liga` ligature substitutions can opt into
+//! shaping with [`TrueTypeFont::with_ligatures`]. They are then embedded as a
+//! Type0 font addressed by glyph ID, so text shows the ligature glyphs.
+
+use crate::error::{PdfError, Result};
+
+/// A TrueType font to embed, with the metrics written to its font dictionary
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrueTypeFont {
+    /// PostScript name, used as both `/BaseFont` and the page resource name
+    pub name: String,
+    data: Vec<u8>,
+    units_per_em: u16,
+    bbox: [i16; 4],
+    ascent: i16,
+    descent: i16,
+    cap_height: i16,
+    fixed_pitch: bool,
+    /// Advance widths of the WinAnsi codes 32..=255, in 1/1000 em
+    widths: Vec<u16>,
+    /// Advance widths by glyph ID, in 1/1000 em
+    glyph_widths: Vec<u16>,
+    /// `liga` substitutions, longest sequences first
+    ligatures: Vec<Ligature>,
+    /// Whether text is shaped with `ligatures`
+    shape_ligatures: bool,
+}
+
+/// A GSUB ligature: `components` glyphs in a row are drawn as `glyph`
+#[derive(Debug, Clone, PartialEq)]
+struct Ligature {
+    components: Vec<u16>,
+    glyph: u16,
+}
+
+/// First character code with a `/Widths` entry
+const FIRST_CHAR: u8 = 32;
+
+impl TrueTypeFont {
+    /// Read a TrueType (`.ttf`) font file
+    pub fn from_file(path: &str) -> Result<Self> {
+        Self::from_bytes(std::fs::read(path)?)
+    }
+
+    /// Parse the tables of a TrueType font held in memory
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        let tables = Tables::read(&data)?;
+        let head = tables.get("head")?;
+        let hhea = tables.get("hhea")?;
+        let units_per_em = read_u16(head, 18)?.max(1);
+        let bbox = [read_i16(head, 36)?, read_i16(head, 38)?, read_i16(head, 40)?, read_i16(head, 42)?];
+        let ascent = read_i16(hhea, 4)?;
+        let descent = read_i16(hhea, 6)?;
+        let metric_count = read_u16(hhea, 34)? as usize;
+
+        let hmtx = tables.get("hmtx")?;
+        let advance = |glyph: u16| -> u16 {
+            let index = (glyph as usize).min(metric_count.saturating_sub(1));
+            read_u16(hmtx, index * 4).unwrap_or(0)
+        };
+        let to_em = |units: u16| (units as u32 * 1000 / units_per_em as u32) as u16;
+        let cmap = UnicodeCmap::read(tables.get("cmap")?)?;
+        let widths = (FIRST_CHAR..=255)
+            .map(|code| to_em(advance(cmap.glyph(crate::pdf::winansi_decode(code)))))
+            .collect();
+
+        let mut ligatures = tables.get("GSUB").map(|gsub| read_ligatures(gsub, "liga")).unwrap_or_default();
+        ligatures.sort_by_key(|ligature| std::cmp::Reverse(ligature.components.len()));
+        let glyph_count = tables
+            .get("maxp")
+            .and_then(|maxp| read_u16(maxp, 4))
+            .map(|count| count as usize)
+            .unwrap_or_else(|_| {
+                let highest = ligatures.iter().flat_map(|l| l.components.iter().chain([&l.glyph])).max();
+                metric_count.max(highest.map_or(0, |g| *g as usize + 1))
+            });
+        let glyph_widths = (0..glyph_count).map(|glyph| to_em(advance(glyph as u16))).collect();
+
+        let cap_height = tables
+            .get("OS/2")
+            .ok()
+            .filter(|os2| read_u16(os2, 0).is_ok_and(|version| version >= 2))
+            .and_then(|os2| read_i16(os2, 88).ok())
+            .unwrap_or(ascent);
+        let fixed_pitch = tables.get("post").ok().and_then(|post| read_u32(post, 12).ok()).is_some_and(|f| f != 0);
+        let name = tables
+            .get("name")
+            .ok()
+            .and_then(postscript_name)
+            .unwrap_or_else(|| "EmbeddedFont".to_string());
+
+        Ok(TrueTypeFont {
+            name,
+            data,
+            units_per_em,
+            bbox,
+            ascent,
+            descent,
+            cap_height,
+            fixed_pitch,
+            widths,
+            glyph_widths,
+            ligatures,
+            shape_ligatures: false,
+        })
+    }
+
+    /// Shape text with the font's `liga` ligatures, e.g. "fi" as one glyph.
+    /// Fonts without ligatures are unaffected.
+    pub fn with_ligatures(mut self, enabled: bool) -> Self {
+        self.shape_ligatures = enabled;
+        self
+    }
+
+    /// Whether text set in this font is shaped, which needs the font to have ligatures
+    pub fn shapes_ligatures(&self) -> bool {
+        self.shape_ligatures && !self.ligatures.is_empty()
+    }
+
+    /// Glyph IDs showing `text`, with ligatures substituted when shaping is on
+    pub fn shape(&self, text: &str) -> Vec<u16> {
+        let Ok(cmap) = Tables::read(&self.data).and_then(|tables| UnicodeCmap::read(tables.get("cmap")?)) else {
+            return Vec::new();
+        };
+        let glyphs: Vec<u16> = text.chars().map(|c| cmap.glyph(c)).collect();
+        if !self.shapes_ligatures() {
+            return glyphs;
+        }
+        let mut shaped = Vec::with_capacity(glyphs.len());
+        let mut i = 0;
+        while i < glyphs.len() {
+            match self.ligatures.iter().find(|l| glyphs[i..].starts_with(&l.components)) {
+                Some(ligature) => {
+                    shaped.push(ligature.glyph);
+                    i += ligature.components.len();
+                }
+                None => {
+                    shaped.push(glyphs[i]);
+                    i += 1;
+                }
+            }
+        }
+        shaped
+    }
+
+    /// Width of `c` in 1/1000 em; characters outside WinAnsi measure as a space
+    pub fn char_width(&self, c: char) -> u16 {
+        let code = crate::pdf::winansi_encode(c).unwrap_or(b' ');
+        self.widths[code.saturating_sub(FIRST_CHAR) as usize]
+    }
+
+    /// Width of `text` in points at `font_size`
+    pub fn text_width(&self, text: &str, font_size: f32) -> f32 {
+        let units: u32 = text.chars().map(|c| self.char_width(c) as u32).sum();
+        units as f32 * font_size / 1000.0
+    }
+
+    /// Add the font dictionary, its descriptor and the embedded font file;
+    /// returns the font dictionary's ID. Shaped fonts are written as a Type0
+    /// font with `Identity-H` encoding, shown with [`shape`](Self::shape)'s glyph IDs.
+    pub fn add_font_objects(&self, generator: &mut crate::pdf_generator::PdfGenerator) -> u32 {
+        let file = generator.add_compressed_stream_object(&format!("/Length1 {}\n", self.data.len()), &self.data);
+        let scale = |v: i16| v as i32 * 1000 / self.units_per_em as i32;
+        // Nonsymbolic, plus FixedPitch for monospace fonts
+        let flags = 32 | if self.fixed_pitch { 1 } else { 0 };
+        let descriptor = generator.add_object(format!(
+            "<< /Type /FontDescriptor\n/FontName /{}\n/Flags {}\n/FontBBox [{} {} {} {}]\n/ItalicAngle 0\n/Ascent {}\n/Descent {}\n/CapHeight {}\n/StemV 80\n/FontFile2 {} 0 R\n>>\n",
+            self.name, flags,
+            scale(self.bbox[0]), scale(self.bbox[1]), scale(self.bbox[2]), scale(self.bbox[3]),
+            scale(self.ascent), scale(self.descent), scale(self.cap_height), file
+        ));
+        if self.shapes_ligatures() {
+            return self.add_type0_objects(generator, descriptor);
+        }
+        let widths: Vec<String> = self.widths.iter().map(|w| w.to_string()).collect();
+        generator.add_object(format!(
+            "<< /Type /Font\n/Subtype /TrueType\n/BaseFont /{}\n/FirstChar {}\n/LastChar 255\n/Widths [{}]\n/Encoding /WinAnsiEncoding\n/FontDescriptor {} 0 R\n>>\n",
+            self.name, FIRST_CHAR, widths.join(" "), descriptor
+        ))
+    }
+
+    /// The Type0 font and CIDFont of a shaped font, whose CIDs are glyph IDs
+    fn add_type0_objects(&self, generator: &mut crate::pdf_generator::PdfGenerator, descriptor: u32) -> u32 {
+        let widths: Vec<String> = self.glyph_widths.iter().map(|w| w.to_string()).collect();
+        let cid_font = generator.add_object(format!(
+            "<< /Type /Font\n/Subtype /CIDFontType2\n/BaseFont /{}\n/CIDSystemInfo << /Registry (Adobe) /Ordering (Identity) /Supplement 0 >>\n/FontDescriptor {} 0 R\n/W [0 [{}]]\n/CIDToGIDMap /Identity\n>>\n",
+            self.name, descriptor, widths.join(" ")
+        ));
+
+        // Map glyphs back to text, ligatures to their components, for copy and search
+        let mut unicode: Vec<(u16, String)> = (FIRST_CHAR..=255)
+            .map(crate::pdf::winansi_decode)
+            .map(|c| (self.shape(&c.to_string()).first().copied().unwrap_or(0), c.to_string()))
+            .filter(|(glyph, _)| *glyph != 0)
+            .collect();
+        for ligature in &self.ligatures {
+            let text: Option<String> = ligature
+                .components
+                .iter()
+                .map(|g| unicode.iter().find(|(glyph, _)| glyph == g).map(|(_, t)| t.clone()))
+                .collect();
+            if let Some(text) = text {
+                unicode.push((ligature.glyph, text));
+            }
+        }
+        unicode.sort();
+        unicode.dedup_by_key(|(glyph, _)| *glyph);
+        let hex = |text: &str| text.encode_utf16().map(|u| format!("{:04X}", u)).collect::<String>();
+        let mut cmap = String::from(
+            "/CIDInit /ProcSet findresource begin\n12 dict begin\nbegincmap\n/CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n/CMapName /Adobe-Identity-UCS def\n/CMapType 2 def\n1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n",
+        );
+        // At most 100 entries per bfchar block
+        for chunk in unicode.chunks(100) {
+            cmap.push_str(&format!("{} beginbfchar\n", chunk.len()));
+            for (glyph, text) in chunk {
+                cmap.push_str(&format!("<{:04X}> <{}>\n", glyph, hex(text)));
+            }
+            cmap.push_str("endbfchar\n");
+        }
+        cmap.push_str("endcmap\nCMapName currentdict /CMap defineresource pop\nend\nend\n");
+        let to_unicode = generator.add_stream_object(format!("<< /Length {} >>\n", cmap.len()), cmap.into_bytes());
+
+        generator.add_object(format!(
+            "<< /Type /Font\n/Subtype /Type0\n/BaseFont /{}\n/Encoding /Identity-H\n/DescendantFonts [{} 0 R]\n/ToUnicode {} 0 R\n>>\n",
+            self.name, cid_font, to_unicode
+        ))
+    }
+}
+
+/// The ligature substitutions of the lookups of `feature` in a `GSUB` table;
+/// tables that cannot be read give none
+fn read_ligatures(gsub: &[u8], feature: &str) -> Vec<Ligature> {
+    let read = || -> Result<Vec<Ligature>> {
+        let features = read_u16(gsub, 6)? as usize;
+        let lookups = read_u16(gsub, 8)? as usize;
+        let mut indices = Vec::new();
+        for i in 0..read_u16(gsub, features)? as usize {
+            let record = features + 2 + i * 6;
+            if gsub.get(record..record + 4) != Some(feature.as_bytes()) {
+                continue;
+            }
+            let table = features + read_u16(gsub, record + 4)? as usize;
+            for j in 0..read_u16(gsub, table + 2)? as usize {
+                indices.push(read_u16(gsub, table + 4 + j * 2)? as usize);
+            }
+        }
+        indices.sort();
+        indices.dedup();
+
+        let mut ligatures = Vec::new();
+        for index in indices {
+            let lookup = lookups + read_u16(gsub, lookups + 2 + index * 2)? as usize;
+            let kind = read_u16(gsub, lookup)?;
+            for k in 0..read_u16(gsub, lookup + 4)? as usize {
+                let subtable = lookup + read_u16(gsub, lookup + 6 + k * 2)? as usize;
+                match kind {
+                    4 => ligatures.extend(read_ligature_subtable(gsub, subtable)?),
+                    // Extension lookups point on to the real subtable
+                    7 if read_u16(gsub, subtable + 2)? == 4 => {
+                        let target = subtable + read_u32(gsub, subtable + 4)? as usize;
+                        ligatures.extend(read_ligature_subtable(gsub, target)?);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(ligatures)
+    };
+    read().unwrap_or_default()
+}
+
+/// A ligature substitution subtable (format 1)
+fn read_ligature_subtable(gsub: &[u8], at: usize) -> Result<Vec<Ligature>> {
+    let firsts = coverage_glyphs(gsub, at + read_u16(gsub, at + 2)? as usize)?;
+    let mut ligatures = Vec::new();
+    for (i, first) in firsts.into_iter().enumerate().take(read_u16(gsub, at + 4)? as usize) {
+        let set = at + read_u16(gsub, at + 6 + i * 2)? as usize;
+        for j in 0..read_u16(gsub, set)? as usize {
+            let ligature = set + read_u16(gsub, set + 2 + j * 2)? as usize;
+            let glyph = read_u16(gsub, ligature)?;
+            let mut components = vec![first];
+            for k in 0..(read_u16(gsub, ligature + 2)? as usize).saturating_sub(1) {
+                components.push(read_u16(gsub, ligature + 4 + k * 2)?);
+            }
+            ligatures.push(Ligature { components, glyph });
+        }
+    }
+    Ok(ligatures)
+}
+
+/// Glyphs listed by a coverage table, in coverage index order
+fn coverage_glyphs(gsub: &[u8], at: usize) -> Result<Vec<u16>> {
+    let count = read_u16(gsub, at + 2)? as usize;
+    match read_u16(gsub, at)? {
+        1 => (0..count).map(|i| read_u16(gsub, at + 4 + i * 2)).collect(),
+        2 => {
+            let mut glyphs = Vec::new();
+            for i in 0..count {
+                let record = at + 4 + i * 6;
+                glyphs.extend(read_u16(gsub, record)?..=read_u16(gsub, record + 2)?);
+            }
+            Ok(glyphs)
+        }
+        format => Err(PdfError::InvalidInput(format!("Unknown coverage format {}", format))),
+    }
+}
+
+/// The table directory of a font file
+struct Tables<'a> {
+    data: &'a [u8],
+    records: Vec<([u8; 4], usize, usize)>,
+}
+
+impl<'a> Tables<'a> {
+    fn read(data: &'a [u8]) -> Result<Self> {
+        let count = read_u16(data, 4)? as usize;
+        let records = (0..count)
+            .map(|i| {
+                let at = 12 + i * 16;
+                let tag = data.get(at..at + 4).ok_or_else(|| truncated("table directory"))?;
+                Ok((tag.try_into().unwrap(), read_u32(data, at + 8)? as usize, read_u32(data, at + 12)? as usize))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Tables { data, records })
+    }
+
+    fn get(&self, tag: &str) -> Result<&'a [u8]> {
+        let (_, offset, length) = self
+            .records
+            .iter()
+            .find(|(t, _, _)| t == tag.as_bytes())
+            .ok_or_else(|| PdfError::InvalidInput(format!("Font has no '{}' table", tag)))?;
+        self.data.get(*offset..offset + length).ok_or_else(|| truncated(tag))
+    }
+}
+
+/// A format 4 subtable mapping the Basic Multilingual Plane to glyph IDs
+struct UnicodeCmap<'a> {
+    table: &'a [u8],
+    segments: usize,
+}
+
+impl<'a> UnicodeCmap<'a> {
+    fn read(cmap: &'a [u8]) -> Result<Self> {
+        let count = read_u16(cmap, 2)? as usize;
+        for i in 0..count {
+            let (platform, encoding) = (read_u16(cmap, 4 + i * 8)?, read_u16(cmap, 6 + i * 8)?);
+            let offset = read_u32(cmap, 8 + i * 8)? as usize;
+            let unicode = matches!((platform, encoding), (3, 1) | (0, _));
+            if unicode && read_u16(cmap, offset)? == 4 {
+                let table = cmap.get(offset..).ok_or_else(|| truncated("cmap"))?;
+                return Ok(UnicodeCmap { table, segments: read_u16(table, 6)? as usize / 2 });
+            }
+        }
+        Err(PdfError::InvalidInput("Font has no Unicode format 4 cmap".into()))
+    }
+
+    /// Glyph for `c`, or 0 (the missing glyph) when the font lacks it
+    fn glyph(&self, c: char) -> u16 {
+        let c = c as u32;
+        let array = |n: usize, i: usize| read_u16(self.table, 14 + n * (self.segments * 2) + 2 * n.min(1) + i * 2).unwrap_or(0);
+        for i in 0..self.segments {
+            let (end, start) = (array(0, i) as u32, array(1, i) as u32);
+            if c > end {
+                continue;
+            }
+            if c < start {
+                return 0;
+            }
+            let delta = array(2, i);
+            let range_offset = array(3, i) as usize;
+            if range_offset == 0 {
+                return (c as u16).wrapping_add(delta);
+            }
+            // idRangeOffset is relative to its own position in the table
+            let at = 14 + 3 * (self.segments * 2) + 2 + i * 2 + range_offset + (c - start) as usize * 2;
+            return match read_u16(self.table, at).unwrap_or(0) {
+                0 => 0,
+                glyph => glyph.wrapping_add(delta),
+            };
+        }
+        0
+    }
+}
+
+/// The PostScript name (name ID 6) from a `name` table
+fn postscript_name(name: &[u8]) -> Option<String> {
+    let count = read_u16(name, 2).ok()? as usize;
+    let strings = read_u16(name, 4).ok()? as usize;
+    (0..count).find_map(|i| {
+        let at = 6 + i * 12;
+        if read_u16(name, at + 6).ok()? != 6 {
+            return None;
+        }
+        let platform = read_u16(name, at).ok()?;
+        let (length, offset) = (read_u16(name, at + 8).ok()? as usize, read_u16(name, at + 10).ok()? as usize);
+        let bytes = name.get(strings + offset..strings + offset + length)?;
+        let text = match platform {
+            0 | 3 => String::from_utf16_lossy(&bytes.chunks(2).map(|c| u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)])).collect::<Vec<_>>()),
+            _ => bytes.iter().map(|&b| b as char).collect(),
+        };
+        // PDF names cannot contain whitespace or delimiters
+        let text: String = text.chars().filter(|c| c.is_ascii_graphic() && !"()<>[]{}/%#".contains(*c)).collect();
+        (!text.is_empty()).then_some(text)
+    })
+}
+
+fn truncated(what: &str) -> PdfError {
+    PdfError::InvalidInput(format!("Font file truncated in {}", what))
+}
+
+fn read_u16(data: &[u8], at: usize) -> Result<u16> {
+    data.get(at..at + 2).map(|b| u16::from_be_bytes([b[0], b[1]])).ok_or_else(|| truncated("table data"))
+}
+
+fn read_i16(data: &[u8], at: usize) -> Result<i16> {
+    read_u16(data, at).map(|v| v as i16)
+}
+
+fn read_u32(data: &[u8], at: usize) -> Result<u32> {
+    data.get(at..at + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]])).ok_or_else(|| truncated("table data"))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A minimal monospace font named `TestMono`: ASCII glyphs advance 600
+    /// units of 1000 per em, anything else falls back to the 1000-unit glyph 0
+    pub(crate) fn sample_font() -> Vec<u8> {
+        build_sample_font(Vec::new())
+    }
+
+    /// [`sample_font`] with a `liga` feature drawing "fi" (glyphs 71, 74) as glyph 96
+    pub(crate) fn sample_ligature_font() -> Vec<u8> {
+        let be16 = |v: u16| v.to_be_bytes().to_vec();
+        // Ligature substitution: coverage [71], one set holding fi -> 96
+        let ligature_subst = [
+            be16(1), be16(8), be16(1), be16(14),
+            be16(1), be16(1), be16(71),
+            be16(1), be16(4), be16(96), be16(2), be16(74),
+        ]
+        .concat();
+        let lookup = [be16(4), be16(0), be16(1), be16(8), ligature_subst].concat();
+        let lookup_list = [be16(1), be16(4), lookup].concat();
+        let feature_list = [be16(1), b"liga".to_vec(), be16(8), be16(0), be16(1), be16(0)].concat();
+        let script_list = [be16(1), b"DFLT".to_vec(), be16(8), be16(4), be16(0), be16(0), be16(0xFFFF), be16(1), be16(0)].concat();
+        let feature_at = 10 + script_list.len() as u16;
+        let lookup_at = feature_at + feature_list.len() as u16;
+        let gsub = [be16(1), be16(0), be16(10), be16(feature_at), be16(lookup_at), script_list, feature_list, lookup_list].concat();
+        build_sample_font(vec![(*b"GSUB", gsub)])
+    }
+
+    fn build_sample_font(extra_tables: Vec<([u8; 4], Vec<u8>)>) -> Vec<u8> {
+        let be16 = |v: u16| v.to_be_bytes().to_vec();
+        let mut head = vec![0u8; 54];
+        head[18..20].copy_from_slice(&1000u16.to_be_bytes());
+        for (i, v) in [0i16, -200, 600, 800].into_iter().enumerate() {
+            head[36 + i * 2..38 + i * 2].copy_from_slice(&v.to_be_bytes());
+        }
+        let mut hhea = vec![0u8; 36];
+        hhea[4..6].copy_from_slice(&800i16.to_be_bytes());
+        hhea[6..8].copy_from_slice(&(-200i16).to_be_bytes());
+        hhea[34..36].copy_from_slice(&2u16.to_be_bytes());
+        let hmtx = [be16(1000), be16(0), be16(600), be16(0)].concat();
+        // Segments 32..=126 -> glyphs 1.. and the closing 0xFFFF segment
+        let subtable = [
+            be16(4), be16(32), be16(0), be16(4), be16(4), be16(1), be16(0),
+            be16(126), be16(0xFFFF), be16(0), be16(32), be16(0xFFFF),
+            be16(31u16.wrapping_neg()), be16(1), be16(0), be16(0),
+        ]
+        .concat();
+        let cmap = [be16(0), be16(1), be16(3), be16(1), 12u32.to_be_bytes().to_vec(), subtable].concat();
+        let mut post = vec![0u8; 32];
+        post[12..16].copy_from_slice(&1u32.to_be_bytes());
+        let ps_name: Vec<u8> = "TestMono".encode_utf16().flat_map(|u| u.to_be_bytes()).collect();
+        let name = [
+            be16(0), be16(1), be16(18),
+            be16(3), be16(1), be16(0x409), be16(6), be16(ps_name.len() as u16), be16(0),
+            ps_name,
+        ]
+        .concat();
+
+        let mut tables = vec![(*b"cmap", cmap), (*b"head", head), (*b"hhea", hhea), (*b"hmtx", hmtx), (*b"name", name), (*b"post", post)];
+        tables.extend(extra_tables);
+        let mut font = [0x0001_0000u32.to_be_bytes().to_vec(), be16(tables.len() as u16), vec![0; 6]].concat();
+        let mut offset = 12 + tables.len() * 16;
+        let mut bodies = Vec::new();
+        for (tag, body) in &tables {
+            font.extend_from_slice(tag);
+            font.extend_from_slice(&[0; 4]);
+            font.extend_from_slice(&(offset as u32).to_be_bytes());
+            font.extend_from_slice(&(body.len() as u32).to_be_bytes());
+            offset += body.len();
+            bodies.extend_from_slice(body);
+        }
+        font.extend_from_slice(&bodies);
+        font
+    }
+
+    #[test]
+    fn test_parse_truetype_metrics() {
+        let font = TrueTypeFont::from_bytes(sample_font()).unwrap();
+        assert_eq!(font.name, "TestMono");
+        assert!(font.fixed_pitch);
+        assert_eq!(font.char_width('a'), 600);
+        assert_eq!(font.char_width('é'), 1000);
+        assert_eq!(font.text_width("abc", 10.0), 18.0);
+        assert!(TrueTypeFont::from_bytes(b"not a font".to_vec()).is_err());
+
+        let mut generator = crate::pdf_generator::PdfGenerator::new();
+        let id = font.add_font_objects(&mut generator);
+        let dict = &generator.objects.iter().find(|o| o.id == id).unwrap().content;
+        assert!(dict.contains("/Subtype /TrueType") && dict.contains("/BaseFont /TestMono"));
+        assert!(generator.objects.iter().any(|o| o.content.contains("/FontFile2") && o.content.contains("/Flags 33")));
+    }
+
+    #[test]
+    fn test_ligature_shaping_is_opt_in() {
+        let font = TrueTypeFont::from_bytes(sample_ligature_font()).unwrap();
+        assert_eq!(font.ligatures, vec![Ligature { components: vec![71, 74], glyph: 96 }]);
+        assert!(!font.shapes_ligatures());
+        assert_eq!(font.shape("fix"), vec![71, 74, 89]);
+        let font = font.with_ligatures(true);
+        assert_eq!(font.shape("fix"), vec![96, 89]);
+        assert_eq!(font.shape("if"), vec![74, 71]);
+        // Fonts without a GSUB table have nothing to shape
+        assert!(!TrueTypeFont::from_bytes(sample_font()).unwrap().with_ligatures(true).shapes_ligatures());
+    }
+}
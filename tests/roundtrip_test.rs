@@ -415,9 +415,9 @@ fn test_full_features_roundtrip() {
         // Ordered lists
         "First numbered item",
         "Second numbered item",
-        // Task lists
-        "[x] Completed task one",
-        "[ ] Pending task three",
+        // Task lists (checkboxes are drawn, so only the item text survives)
+        "Completed task one",
+        "Pending task three",
         // Code blocks (keywords split by syntax highlighting)
         "fibonacci",
         "quicksort",
@@ -936,8 +936,8 @@ fn test_math_and_formulas_roundtrip() {
         "Information Theory",
         "Advanced Topics",
         // Math content (rendered form)
-        "SUM",
-        "sqrt",
+        "\u{2211}",
+        "\u{221A}",
         // Code blocks
         "GradientDescent",
         "learning_rate",
@@ -1043,10 +1043,10 @@ Regular paragraph after math.
     assert!(val.valid, "Math PDF validation failed: {:?}", val.errors);
     println!("[math_api] Generated {} bytes, {} pages", bytes.len(), val.page_count);
 
-    // Check rendered math content in raw PDF
+    // Check rendered math glyphs in raw PDF: the Symbol font's summation and radical
     let content = String::from_utf8_lossy(&bytes);
-    assert!(content.contains("SUM"), "Rendered SUM not found in PDF");
-    assert!(content.contains("sqrt"), "Rendered sqrt not found in PDF");
+    assert!(content.contains("(\\345) Tj"), "Rendered summation sign not found in PDF");
+    assert!(content.contains("(\\326) Tj"), "Rendered radical sign not found in PDF");
 
     println!("=== PASSED: math_parsing_library_api ===");
 }